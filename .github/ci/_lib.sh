@@ -44,6 +44,8 @@ RMK_FEATURESETS=(
     "split,vial,storage"
     "passkey_entry"
     "split,vial,storage,passkey_entry"
+    # The default features without `combo` and `macros`, whose code must be gated with them
+    "vial,vial_lock,storage,watchdog,pointing"
 )
 
 # Examples auto-discovery skiplist. Reasons:
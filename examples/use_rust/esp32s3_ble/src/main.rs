@@ -51,9 +51,9 @@ async fn main(_s: Spawner) {
 
     let connector = BleConnector::new(peripherals.BT, Default::default()).unwrap();
     let controller: ExternalController<_, 20> = ExternalController::new(connector);
-    let central_addr = [0x18, 0xe2, 0x21, 0x80, 0xc0, 0xc7];
+    let ble_addr = rmk::ble::esp::ble_address();
     let mut host_resources = HostResources::new();
-    let stack = build_ble_stack(controller, central_addr, &mut rng, &mut host_resources).await;
+    let stack = build_ble_stack(controller, ble_addr, &mut rng, &mut host_resources).await;
 
     // Initialize USB
     static mut EP_MEMORY: [u8; 1024] = [0; 1024];
@@ -165,6 +165,7 @@ async fn main(spawner: Spawner) {
         manufacturer: "Haobo",
         product_name: "RMK Keyboard",
         serial_number: "vial:f64c2b3c:000001",
+        serial_from_chip_id: true,
     };
     let vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, UNLOCK_KEYS);
     let ble_battery_config = BleBatteryConfig::new(Some(is_charging_pin), true, None, false);
@@ -178,6 +179,7 @@ async fn main(spawner: Spawner) {
         vial_config,
         ble_battery_config,
         storage_config,
+        ..Default::default()
     };
 
     // Initialze keyboard stuffs
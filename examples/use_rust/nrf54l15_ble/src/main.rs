@@ -172,6 +172,7 @@ async fn main(spawner: Spawner) {
         manufacturer: "Haobo",
         product_name: "RMK nRF54L15",
         serial_number: "vial:f64c2b3c:000055",
+        serial_from_chip_id: true,
     };
     let vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, UNLOCK_KEYS);
     let storage_config = StorageConfig {
@@ -115,6 +115,7 @@ async fn main(spawner: Spawner) {
         manufacturer: "Haobo",
         product_name: "RMK PicoW",
         serial_number: "vial:f64c2b3c:000001",
+        serial_from_chip_id: false,
     };
 
     let vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, &[(0, 0), (1, 1)]);
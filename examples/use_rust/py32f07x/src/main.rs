@@ -58,6 +58,7 @@ async fn main(_spawner: Spawner) {
         manufacturer: "RMK & py32-rs",
         product_name: "RMK Keyboard",
         serial_number: "vial:f64c2b3c:000001",
+        serial_from_chip_id: false,
     };
 
     let _vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, &[(0, 0), (1, 1)]);
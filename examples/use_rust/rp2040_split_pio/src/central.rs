@@ -24,7 +24,7 @@ use rmk::keyboard::Keyboard;
 use rmk::matrix::Matrix;
 use rmk::processor::builtin::wpm::WpmProcessor;
 use rmk::split::SPLIT_MESSAGE_MAX_SIZE;
-use rmk::split::central::run_peripheral_manager;
+use rmk::split::central::run_half_duplex_peripheral_manager;
 use rmk::split::rp::uart::{BufferedUart, UartInterruptHandler};
 use rmk::usb::UsbTransport;
 use rmk::{KeymapData, initialize_keymap_and_storage, run_all};
@@ -60,6 +60,7 @@ async fn main(_spawner: Spawner) {
         manufacturer: "Haobo",
         product_name: "RMK Keyboard",
         serial_number: "vial:f64c2b3c:000001",
+        serial_from_chip_id: false,
     };
 
     let vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, &[(0, 0), (1, 1)]);
@@ -101,7 +102,7 @@ async fn main(_spawner: Spawner) {
     // Start
     join(
         run_all!(matrix, storage, usb_transport, wpm_processor, keyboard, host_service),
-        run_peripheral_manager::<2, 1, 2, 2, _>(0, uart_receiver),
+        run_half_duplex_peripheral_manager::<2, 1, 2, 2, _>(0, uart_receiver),
     )
     .await;
 }
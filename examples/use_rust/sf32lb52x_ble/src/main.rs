@@ -165,6 +165,7 @@ async fn main(_spawner: Spawner) {
         manufacturer: "RMK & SiFli-rs",
         product_name: "RMK SF32LB52",
         serial_number: "vial:f64c2b3c:000002",
+        serial_from_chip_id: false,
     };
 
     let vial_config = VialConfig::new(VIAL_KEYBOARD_ID, VIAL_KEYBOARD_DEF, &[(0, 0), (0, 3)]);
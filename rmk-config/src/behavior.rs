@@ -21,6 +21,7 @@ impl crate::KeyboardTomlConfig {
                 };
                 behavior.one_shot = behavior.one_shot.or(default.one_shot);
                 behavior.one_shot_modifiers = behavior.one_shot_modifiers.or(default.one_shot_modifiers);
+                behavior.swap_ctrl_gui_on_mac = behavior.swap_ctrl_gui_on_mac.or(default.swap_ctrl_gui_on_mac);
                 behavior.combo = behavior.combo.or(default.combo);
                 if let Some(combo) = &behavior.combo {
                     if combo.combos.len() > self.rmk.combo_max_num {
@@ -88,6 +89,34 @@ impl crate::KeyboardTomlConfig {
                             .to_string(),
                     );
                 }
+                if let Some(morse) = &behavior.morse {
+                    for (i, per_key) in morse.per_key.iter().flatten().enumerate() {
+                        if let Some([row, col]) = per_key
+                            .keys
+                            .iter()
+                            .find(|[row, col]| *row >= layout.rows || *col >= layout.cols)
+                        {
+                            return Err(format!(
+                                "keyboard.toml: key [{}, {}] in behavior.morse.per_key #{} is out of [layout.rows] or [layout.cols]",
+                                row, col, i
+                            ));
+                        }
+                        if let Some(name) = &per_key.profile
+                            && !morse.profiles.as_ref().is_some_and(|p| p.contains_key(name))
+                        {
+                            return Err(format!(
+                                "keyboard.toml: profile `{}` in behavior.morse.per_key #{} is not found in behavior.morse.profiles",
+                                name, i
+                            ));
+                        }
+                        if per_key.quick_tap.as_ref().is_some_and(|t| t.0 > u16::MAX as u64) {
+                            return Err(format!(
+                                "keyboard.toml: quick_tap in behavior.morse.per_key #{} is longer than 65535ms",
+                                i
+                            ));
+                        }
+                    }
+                }
                 Ok(behavior)
             }
             None => Ok(default),
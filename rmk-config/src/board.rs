@@ -1,4 +1,4 @@
-use crate::{InputDeviceConfig, KeyboardTomlConfig, MatrixConfig, MatrixType, SplitConfig};
+use crate::{InputDeviceConfig, KeyboardTomlConfig, MatrixConfig, MatrixType, SplitBoardConfig, SplitConfig};
 
 #[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
@@ -68,6 +68,46 @@ impl BoardConfig {
     }
 }
 
+impl SplitConfig {
+    /// Check that the matrix of every split board lies inside the layout and that no two
+    /// boards share a matrix position, otherwise their keys would be mixed up in the keymap.
+    pub(crate) fn validate_matrix_regions(&self, rows: usize, cols: usize) -> Result<(), String> {
+        let boards: Vec<(String, &SplitBoardConfig)> = core::iter::once(("split.central".to_string(), &self.central))
+            .chain(
+                self.peripheral
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| (format!("split.peripheral[{i}]"), p)),
+            )
+            .collect();
+
+        for (name, board) in &boards {
+            if board.row_offset + board.rows > rows || board.col_offset + board.cols > cols {
+                return Err(format!(
+                    "keyboard.toml: [{name}] covers rows {}..{} and cols {}..{}, outside of the {rows}x{cols} layout",
+                    board.row_offset,
+                    board.row_offset + board.rows,
+                    board.col_offset,
+                    board.col_offset + board.cols
+                ));
+            }
+        }
+
+        for (i, (name_a, a)) in boards.iter().enumerate() {
+            for (name_b, b) in &boards[i + 1..] {
+                let rows_overlap = a.row_offset < b.row_offset + b.rows && b.row_offset < a.row_offset + a.rows;
+                let cols_overlap = a.col_offset < b.col_offset + b.cols && b.col_offset < a.col_offset + a.cols;
+                if rows_overlap && cols_overlap {
+                    return Err(format!(
+                        "keyboard.toml: the matrices of [{name_a}] and [{name_b}] overlap, check their row_offset and col_offset"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl KeyboardTomlConfig {
     pub(crate) fn get_board_config(&self) -> Result<BoardConfig, String> {
         let matrix = self.matrix.clone();
@@ -75,6 +115,9 @@ impl KeyboardTomlConfig {
         let input_device = self.input_device.clone();
         match (matrix, split) {
             (None, Some(s)) => {
+                if let Some(layout) = &self.layout {
+                    s.validate_matrix_regions(layout.rows as usize, layout.cols as usize)?;
+                }
                 Ok(BoardConfig::Split(s))
             },
             (Some(m), None) => {
@@ -98,3 +141,36 @@ impl KeyboardTomlConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: usize, cols: usize, row_offset: usize, col_offset: usize) -> SplitBoardConfig {
+        SplitBoardConfig {
+            rows,
+            cols,
+            row_offset,
+            col_offset,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_split_matrix_regions() {
+        // Left and right halves with a numpad below the right half
+        let split = SplitConfig {
+            connection: "ble".to_string(),
+            central: board(4, 6, 0, 0),
+            peripheral: vec![board(4, 6, 0, 6), board(5, 4, 4, 8)],
+        };
+        assert!(split.validate_matrix_regions(9, 12).is_ok());
+        assert!(split.validate_matrix_regions(8, 12).is_err());
+
+        let overlapping = SplitConfig {
+            peripheral: vec![board(4, 6, 0, 6), board(5, 4, 3, 8)],
+            ..split
+        };
+        assert!(overlapping.validate_matrix_regions(9, 12).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::chip::{ChipModel, ChipSeries};
 use crate::usb_interrupt_map::get_usb_info;
-use crate::{BleConfig, KeyboardTomlConfig};
+use crate::{BLE_NAME_MAX_LEN, BleConfig, BleConnMode, KeyboardTomlConfig, UsbConfig};
 
 /// Information about USB interface
 #[derive(Clone, Debug, Default)]
@@ -74,11 +74,33 @@ impl CommunicationConfig {
 }
 
 impl KeyboardTomlConfig {
+    pub(crate) fn get_usb_config(&self) -> UsbConfig {
+        self.usb.unwrap_or_default()
+    }
+
     pub(crate) fn get_communication_config(&self) -> Result<CommunicationConfig, String> {
         let usb_enabled = self.keyboard.clone().unwrap_or_default().usb_enable.unwrap_or(false);
         let chip = self.get_chip_model().unwrap();
         let usb_info = if usb_enabled { get_usb_info(&chip.chip) } else { None };
         let ble_config = self.ble.clone();
+        if let Some(ble_config) = &ble_config {
+            for (mode, params) in [
+                (BleConnMode::LowLatency, &ble_config.low_latency),
+                (BleConnMode::PowerSave, &ble_config.power_save),
+            ] {
+                if let Some(params) = params {
+                    params.validate(mode)?;
+                }
+            }
+            if let Some(name) = &ble_config.name
+                && name.len() > BLE_NAME_MAX_LEN
+            {
+                return Err(format!(
+                    "keyboard.toml: [ble.name] must be at most {BLE_NAME_MAX_LEN} bytes, got {}",
+                    name.len()
+                ));
+            }
+        }
 
         match (usb_info, ble_config) {
             (Some(usb_info), None) => Ok(CommunicationConfig::Usb(usb_info)),
@@ -21,6 +21,8 @@ pub(crate) mod keycode_alias;
 pub(crate) mod layout;
 pub(crate) mod light;
 pub(crate) mod storage;
+pub(crate) mod validation;
+pub(crate) mod vial;
 
 /// Protocol-level capacity ceilings for wire-format Vec sizes.
 ///
@@ -65,6 +67,8 @@ pub struct KeyboardTomlConfig {
     storage: Option<StorageConfig>,
     /// Ble config
     pub(crate) ble: Option<BleConfig>,
+    /// USB config
+    usb: Option<UsbConfig>,
     /// Chip-specific configs (e.g., [chip.nrf52840])
     chip: Option<HashMap<String, ChipConfig>>,
     /// Dependency config
@@ -87,6 +91,9 @@ pub struct KeyboardTomlConfig {
     /// build.rs also loads event defaults via new_from_toml_path_with_event_defaults()
     #[serde(default)]
     pub(crate) event: EventConfig,
+    /// Source text, for the locations of the validation errors
+    #[serde(skip)]
+    source: Option<validation::TomlSource>,
 }
 
 impl KeyboardTomlConfig {
@@ -100,12 +107,17 @@ impl KeyboardTomlConfig {
         if let Some(default_config) = chip_default_config {
             builder = builder.add_source(File::from_str(default_config, FileFormat::Toml));
         }
-        builder
+        let mut config: Self = builder
             .add_source(File::with_name(path_str))
             .build()
             .unwrap_or_else(|e| panic!("Parse {:?} error: {}", path, e))
             .try_deserialize()
-            .unwrap_or_else(|e| panic!("Deserialize {:?} error: {}", path, e))
+            .unwrap_or_else(|e| panic!("Deserialize {:?} error: {}", path, e));
+        config.source = std::fs::read_to_string(path).ok().map(|text| validation::TomlSource {
+            path: path_str.to_string(),
+            text,
+        });
+        config
     }
 
     /// Load keyboard.toml with event defaults only.
@@ -220,18 +232,28 @@ pub(crate) struct RmkConstantsConfig {
     /// Macro space size in bytes for storing sequences
     #[serde_inline_default(256)]
     pub macro_space_size: usize,
+    /// Number of keymap profiles kept in the storage
+    #[serde_inline_default(1)]
+    #[serde(deserialize_with = "check_keymap_profile_num")]
+    pub keymap_profile_num: usize,
     /// Default debounce time in ms
     #[serde_inline_default(20)]
     pub debounce_time: u16,
     /// Report channel size
     #[serde_inline_default(16)]
     pub report_channel_size: usize,
+    /// What to do with a new report when the report channel is full
+    #[serde(default)]
+    pub report_queue_overflow: ReportQueueOverflow,
     /// Vial channel size
     #[serde_inline_default(4)]
     pub vial_channel_size: usize,
     /// Flash channel size
     #[serde_inline_default(4)]
     pub flash_channel_size: usize,
+    /// Number of keymap and setting edits batched before they are saved to flash
+    #[serde_inline_default(8)]
+    pub flash_batch_size: usize,
     /// The number of the split peripherals
     #[serde_inline_default(0)]
     pub split_peripherals_num: usize,
@@ -241,6 +263,15 @@ pub(crate) struct RmkConstantsConfig {
     /// BLE Split Central sleep timeout in minutes (0 = disabled)
     #[serde_inline_default(0)]
     pub split_central_sleep_timeout_seconds: u32,
+    /// Connection interval of the BLE split link in microseconds
+    #[serde_inline_default(7500)]
+    pub split_conn_interval_us: u32,
+    /// Peripheral latency of the BLE split link in connection events
+    #[serde_inline_default(30)]
+    pub split_conn_latency: u16,
+    /// Supervision timeout of the BLE split link in milliseconds
+    #[serde_inline_default(5000)]
+    pub split_supervision_timeout_ms: u32,
     /// Maximum number of key actions in a bulk keymap transfer (protocol).
     /// Smaller values reduce firmware RAM usage but require more round-trips.
     #[serde_inline_default(8)]
@@ -295,6 +326,30 @@ where
     Ok(value)
 }
 
+fn check_keymap_profile_num<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let value = Deserialize::deserialize(deserializer)?;
+    if !(1..=16).contains(&value) {
+        panic!("❌ Parse `keyboard.toml` error: keymap_profile_num must be between 1 and 16, got {value}");
+    }
+    Ok(value)
+}
+
+/// Overflow policy of the HID report queue
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportQueueOverflow {
+    /// Wait until the transport takes a report out of the queue
+    #[default]
+    Block,
+    /// Drop the oldest queued report
+    DropOldest,
+    /// Wait, and merge the queued mouse motion when sending it over BLE
+    Coalesce,
+}
+
 /// This separate Default impl is needed when `[rmk]` section is not set in keyboard.toml
 impl Default for RmkConstantsConfig {
     fn default() -> Self {
@@ -307,13 +362,19 @@ impl Default for RmkConstantsConfig {
             morse_max_num: 8,
             max_patterns_per_key: 8,
             macro_space_size: 256,
+            keymap_profile_num: 1,
             debounce_time: 20,
             report_channel_size: 16,
+            report_queue_overflow: ReportQueueOverflow::Block,
             vial_channel_size: 4,
             flash_channel_size: 4,
+            flash_batch_size: 8,
             split_peripherals_num: 0,
             ble_profiles_num: 3,
             split_central_sleep_timeout_seconds: 0,
+            split_conn_interval_us: 7500,
+            split_conn_latency: 30,
+            split_supervision_timeout_ms: 5000,
             protocol_max_bulk_size: 8,
             protocol_macro_chunk_size: 64,
         }
@@ -374,6 +435,7 @@ macro_rules! define_event_config {
 define_event_config!(
     // Connection events
     connection_status_change,
+    passkey_entry,
     // Input events
     modifier,
     keyboard,
@@ -381,20 +443,27 @@ define_event_config!(
     layer_change,
     wpm_update,
     led_indicator,
+    caps_word,
     sleep_state,
+    brightness,
+    power_state,
     // Power events
     battery_status,
     battery_adc,
     charging_state,
     // Pointing device events
     pointing,
+    pointing_set_cpi,
     // Split events
     peripheral_connected,
     central_connected,
     peripheral_battery,
+    peripheral_pointing_set_cpi,
     clear_peer,
+    central_action,
     // Action events
     action,
+    combo_triggered,
 );
 
 /// Configurations for keyboard layout
@@ -432,6 +501,8 @@ pub(crate) struct KeyboardInfo {
     pub product_name: Option<String>,
     /// Serial number
     pub serial_number: Option<String>,
+    /// Derive the serial number from the chip's unique id, defaults to true
+    pub serial_from_chip_id: Option<bool>,
     /// Board name(if a supported board is used)
     pub board: Option<String>,
     /// Chip model
@@ -462,6 +533,10 @@ pub struct MatrixConfig {
     pub row2col: bool,
     pub debouncer: Option<String>,
     pub bootmagic: Option<(u8, u8)>,
+    /// Key which clears the storage when it's held at boot
+    pub bootmagic_reset: Option<(u8, u8)>,
+    /// Key which boots with the default keymap, ignoring the storage, when it's held at boot
+    pub bootmagic_safe_mode: Option<(u8, u8)>,
 }
 
 /// Config for storage
@@ -494,8 +569,128 @@ pub struct BleConfig {
     pub use_2m_phy: Option<bool>,
     pub passkey_entry: Option<bool>,
     pub passkey_entry_timeout: Option<u32>,
+    /// Connection parameter set used after startup
+    pub conn_mode: Option<BleConnMode>,
+    pub low_latency: Option<BleConnParamsConfig>,
+    pub power_save: Option<BleConnParamsConfig>,
+    /// Advertised name, defaults to the product name
+    pub name: Option<String>,
+    /// Append the active profile number to the advertised name
+    pub name_profile_suffix: Option<bool>,
+    /// Reconnect to the bonded host with directed advertising first
+    pub directed_advertising: Option<bool>,
+    /// Never advertise to other hosts on a bonded profile
+    pub directed_advertising_only: Option<bool>,
+    /// Stop advertising after this many seconds without a connection
+    pub advertising_timeout: Option<u32>,
+    /// Power off instead of sleeping when advertising times out
+    pub advertising_timeout_power_off: Option<bool>,
+}
+
+/// USB HID interfaces, unset interfaces are enabled except the battery report, NKRO and digitizer
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UsbConfig {
+    pub mouse: Option<bool>,
+    pub consumer: Option<bool>,
+    pub system_control: Option<bool>,
+    /// Polling interval of the HID endpoints in milliseconds, 1..=255
+    pub poll_interval: Option<u8>,
+    /// Battery level report, disabled by default
+    pub battery: Option<bool>,
+    /// N-key rollover interface, disabled by default
+    pub nkro: Option<bool>,
+    /// Pen digitizer interface, disabled by default
+    pub digitizer: Option<bool>,
+}
+
+/// BLE connection parameter mode
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BleConnMode {
+    #[default]
+    LowLatency,
+    PowerSave,
+}
+
+/// BLE connection parameters, unset fields keep RMK's defaults for the mode
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BleConnParamsConfig {
+    /// Connection interval in microseconds, 7500..=4000000 in steps of 1250
+    pub interval_us: Option<u32>,
+    /// Peripheral latency in connection events, at most 499
+    pub latency: Option<u16>,
+    /// Supervision timeout in milliseconds, 100..=32000
+    pub supervision_timeout_ms: Option<u32>,
+}
+
+impl BleConnParamsConfig {
+    /// RMK's parameters for `mode`, the same as `rmk::config::BleConnParams::{LOW_LATENCY, POWER_SAVE}`
+    const fn defaults(mode: BleConnMode) -> Self {
+        match mode {
+            BleConnMode::LowLatency => Self {
+                interval_us: Some(7500),
+                latency: Some(30),
+                supervision_timeout_ms: Some(5000),
+            },
+            BleConnMode::PowerSave => Self {
+                interval_us: Some(30_000),
+                latency: Some(30),
+                supervision_timeout_ms: Some(6000),
+            },
+        }
+    }
+
+    /// Check the parameters of `mode` against the limits of the BLE specification.
+    ///
+    /// Unset values are RMK's defaults, which have to fit with the values which are set.
+    pub(crate) fn validate(&self, mode: BleConnMode) -> Result<(), String> {
+        let name = match mode {
+            BleConnMode::LowLatency => "low_latency",
+            BleConnMode::PowerSave => "power_save",
+        };
+        if let Some(interval) = self.interval_us
+            && (!(7500..=4_000_000).contains(&interval) || interval % 1250 != 0)
+        {
+            return Err(format!(
+                "keyboard.toml: [ble.{name}.interval_us] must be a multiple of 1250 between 7500 and 4000000, got {interval}"
+            ));
+        }
+        if let Some(latency) = self.latency
+            && latency > 499
+        {
+            return Err(format!(
+                "keyboard.toml: [ble.{name}.latency] must be at most 499, got {latency}"
+            ));
+        }
+        if let Some(timeout) = self.supervision_timeout_ms
+            && !(100..=32_000).contains(&timeout)
+        {
+            return Err(format!(
+                "keyboard.toml: [ble.{name}.supervision_timeout_ms] must be between 100 and 32000, got {timeout}"
+            ));
+        }
+        // The connection must survive the skipped connection events
+        let defaults = Self::defaults(mode);
+        if let (Some(interval), Some(latency), Some(timeout)) = (
+            self.interval_us.or(defaults.interval_us),
+            self.latency.or(defaults.latency),
+            self.supervision_timeout_ms.or(defaults.supervision_timeout_ms),
+        ) && (timeout as u64) * 1000 <= (1 + latency as u64) * interval as u64 * 2
+        {
+            return Err(format!(
+                "keyboard.toml: [ble.{name}.supervision_timeout_ms] must be longer than twice interval_us * (1 + latency)"
+            ));
+        }
+        Ok(())
+    }
 }
 
+/// Longest BLE name in bytes, it must fit in the advertising packet next to the flags,
+/// services and appearance.
+pub const BLE_NAME_MAX_LEN: usize = 16;
+
 /// Default passkey entry timeout in seconds.
 pub const DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS: u32 = 120;
 
@@ -576,6 +771,8 @@ pub(crate) struct BehaviorConfig {
     pub macros: Option<MacrosConfig>,
     pub fork: Option<ForksConfig>,
     pub morse: Option<MorsesConfig>,
+    /// Swap Ctrl and GUI when the host is detected as macOS or iOS
+    pub swap_ctrl_gui_on_mac: Option<bool>,
 }
 
 /// Per Key configurations profiles for morse, tap-hold, etc.
@@ -710,10 +907,38 @@ pub(crate) struct MorsesConfig {
     /// these can be used to overrides the defaults given above
     pub profiles: Option<HashMap<String, MorseProfile>>,
 
+    /// tap-hold configurations of the keys at given matrix positions
+    pub per_key: Option<Vec<PerKeyMorseConfig>>,
+
     /// the definition of morse / tap dance keys
     pub morses: Option<Vec<MorseConfig>>,
 }
 
+/// Tap-hold configuration of the keys at some matrix positions, whatever their action.
+/// The profile of the action overrides it, and it overrides the defaults
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PerKeyMorseConfig {
+    /// `[row, col]` of the keys
+    pub keys: Vec<[u8; 2]>,
+
+    /// name of a morse profile in `profiles`, the fields below override it
+    pub profile: Option<String>,
+
+    pub unilateral_tap: Option<bool>,
+    pub permissive_hold: Option<bool>,
+    pub hold_on_other_press: Option<bool>,
+    pub normal_mode: Option<bool>,
+    pub hold_timeout: Option<DurationMillis>,
+    pub gap_timeout: Option<DurationMillis>,
+
+    /// if true, a tap-hold key held past `hold_timeout` sends its tap action on release, when no other key was pressed meanwhile
+    pub retro_tap: Option<bool>,
+
+    /// Pressing a tap-hold key again within this time after tapping it holds the tap action (in milliseconds)
+    pub quick_tap: Option<DurationMillis>,
+}
+
 /// Configurations for morse
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -790,6 +1015,10 @@ pub struct SerialConfig {
     pub instance: String,
     pub tx_pin: String,
     pub rx_pin: String,
+    /// DMA channel for TX, STM32 only. Set both DMA channels to receive into a DMA ring buffer
+    pub tx_dma: Option<String>,
+    /// DMA channel for RX, STM32 only
+    pub rx_dma: Option<String>,
 }
 
 /// Duration in milliseconds
@@ -1117,6 +1346,34 @@ pub struct DisplayConfig {
     /// Minimum time in milliseconds between event-driven renders.
     /// Prevents the display from being hammered by rapid events. Default: 10 ms.
     pub min_render_interval: Option<u64>,
+    /// Status widgets drawn at fixed positions, instead of a `renderer`.
+    pub widgets: Option<Vec<DisplayWidgetConfig>>,
+}
+
+/// Kind of a display widget
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayWidgetKind {
+    Layer,
+    Wpm,
+    Modifiers,
+    LockKeys,
+    Battery,
+    Connection,
+    Text,
+}
+
+/// A display widget and the top-left corner where it's drawn
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisplayWidgetConfig {
+    pub widget: DisplayWidgetKind,
+    #[serde(default)]
+    pub x: i32,
+    #[serde(default)]
+    pub y: i32,
+    /// Text of a `text` widget
+    pub text: Option<String>,
 }
 
 /// Configuration for an output pin
@@ -1151,6 +1408,63 @@ impl KeyboardTomlConfig {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ble_conn_params_validation() {
+        let params = BleConnParamsConfig {
+            interval_us: Some(15000),
+            latency: Some(30),
+            supervision_timeout_ms: Some(2000),
+        };
+        assert!(params.validate(BleConnMode::LowLatency).is_ok());
+        assert!(BleConnParamsConfig::default().validate(BleConnMode::LowLatency).is_ok());
+        assert!(BleConnParamsConfig::default().validate(BleConnMode::PowerSave).is_ok());
+
+        let bad_interval = BleConnParamsConfig {
+            interval_us: Some(8000),
+            ..params
+        };
+        assert!(bad_interval.validate(BleConnMode::LowLatency).is_err());
+
+        let short_timeout = BleConnParamsConfig {
+            supervision_timeout_ms: Some(900),
+            ..params
+        };
+        assert!(short_timeout.validate(BleConnMode::PowerSave).is_err());
+
+        // Checked against the default latency and timeout of the mode
+        let long_interval = BleConnParamsConfig {
+            interval_us: Some(100_000),
+            ..Default::default()
+        };
+        assert!(long_interval.validate(BleConnMode::LowLatency).is_err());
+        let short_timeout = BleConnParamsConfig {
+            supervision_timeout_ms: Some(400),
+            ..Default::default()
+        };
+        assert!(short_timeout.validate(BleConnMode::PowerSave).is_err());
+    }
+
+    #[test]
+    fn test_display_widgets() {
+        let config: DisplayConfig = toml::from_str(
+            r#"
+            driver = "ssd1306"
+            size = "128x32"
+            protocol = { i2c = { instance = "I2C1", sda = "PIN_2", scl = "PIN_3" } }
+            widgets = [
+                { widget = "layer" },
+                { widget = "lock_keys", x = 120, y = 2 },
+                { widget = "text", text = "RMK", x = 100 },
+            ]
+            "#,
+        )
+        .unwrap();
+        let widgets = config.widgets.unwrap();
+        assert_eq!(widgets[0].widget, DisplayWidgetKind::Layer);
+        assert_eq!((widgets[1].x, widgets[1].y), (120, 2));
+        assert_eq!(widgets[2].text.as_deref(), Some("RMK"));
+    }
+
     #[test]
     fn test_event_config_default_values() {
         let config = EventConfig::default();
@@ -9,6 +9,7 @@ pub struct Behavior {
     pub macros: Option<Macros>,
     pub forks: Option<Forks>,
     pub morse: Option<Morse>,
+    pub swap_ctrl_gui_on_mac: bool,
 }
 
 pub struct OneShot {
@@ -65,9 +66,19 @@ pub struct Morse {
     pub prior_idle_time_ms: u64,
     pub default_profile: MorseProfile,
     pub profiles: HashMap<String, MorseProfile>,
+    pub per_key: Vec<PerKeyMorse>,
     pub morses: Vec<MorseKey>,
 }
 
+/// Tap-hold configuration of the keys at some matrix positions, its named profile is merged in.
+pub struct PerKeyMorse {
+    /// `(row, col)` of the keys
+    pub keys: Vec<(u8, u8)>,
+    pub profile: MorseProfile,
+    pub retro_tap: bool,
+    pub quick_tap_ms: Option<u64>,
+}
+
 #[derive(Clone)]
 pub struct MorseProfile {
     pub unilateral_tap: Option<bool>,
@@ -168,6 +179,29 @@ impl crate::KeyboardTomlConfig {
                 gap_timeout_ms: Some(m.gap_timeout.as_ref().map(|t| t.0).unwrap_or(250)),
             };
 
+            let per_key = m
+                .per_key
+                .unwrap_or_default()
+                .into_iter()
+                .map(|pk| {
+                    let named = pk.profile.as_ref().and_then(|name| profiles.get(name));
+                    let field = |own: Option<bool>, of: fn(&MorseProfile) -> Option<bool>| own.or(named.and_then(of));
+                    PerKeyMorse {
+                        keys: pk.keys.iter().map(|[row, col]| (*row, *col)).collect(),
+                        profile: MorseProfile {
+                            unilateral_tap: field(pk.unilateral_tap, |p| p.unilateral_tap),
+                            permissive_hold: field(pk.permissive_hold, |p| p.permissive_hold),
+                            hold_on_other_press: field(pk.hold_on_other_press, |p| p.hold_on_other_press),
+                            normal_mode: field(pk.normal_mode, |p| p.normal_mode),
+                            hold_timeout_ms: pk.hold_timeout.map(|t| t.0).or(named.and_then(|p| p.hold_timeout_ms)),
+                            gap_timeout_ms: pk.gap_timeout.map(|t| t.0).or(named.and_then(|p| p.gap_timeout_ms)),
+                        },
+                        retro_tap: pk.retro_tap.unwrap_or(false),
+                        quick_tap_ms: pk.quick_tap.map(|t| t.0),
+                    }
+                })
+                .collect();
+
             let morses = m
                 .morses
                 .unwrap_or_default()
@@ -197,6 +231,7 @@ impl crate::KeyboardTomlConfig {
                 prior_idle_time_ms: m.prior_idle_time.map(|t| t.0).unwrap_or(120),
                 default_profile,
                 profiles,
+                per_key,
                 morses,
             }
         });
@@ -209,6 +244,7 @@ impl crate::KeyboardTomlConfig {
             macros,
             forks,
             morse,
+            swap_ctrl_gui_on_mac: toml_behavior.swap_ctrl_gui_on_mac.unwrap_or(false),
         })
     }
 }
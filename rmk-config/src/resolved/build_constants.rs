@@ -37,15 +37,21 @@ pub struct BuildConstants {
     pub morse_max_num: usize,
     pub max_patterns_per_key: usize,
     pub macro_space_size: usize,
+    pub keymap_profile_num: usize,
     pub debounce_time: u16,
     pub mouse_key_interval: u16,
     pub mouse_wheel_interval: u16,
     pub report_channel_size: usize,
+    pub report_queue_overflow: crate::ReportQueueOverflow,
     pub vial_channel_size: usize,
     pub flash_channel_size: usize,
+    pub flash_batch_size: usize,
     pub split_peripherals_num: usize,
     pub ble_profiles_num: usize,
     pub split_central_sleep_timeout_seconds: u32,
+    pub split_conn_interval_us: u32,
+    pub split_conn_latency: u16,
+    pub split_supervision_timeout_ms: u32,
     pub protocol_max_bulk_size: usize,
     pub protocol_macro_chunk_size: usize,
     pub events: Vec<EventChannel>,
@@ -96,21 +102,29 @@ impl crate::KeyboardTomlConfig {
 
         let mut events = event_channels!(
             connection_status_change,
+            passkey_entry,
             modifier,
             keyboard,
             layer_change,
             wpm_update,
             led_indicator,
+            caps_word,
             sleep_state,
+            brightness,
+            power_state,
             battery_status,
             battery_adc,
             charging_state,
             pointing,
+            pointing_set_cpi,
             peripheral_connected,
             central_connected,
             peripheral_battery,
+            peripheral_pointing_set_cpi,
             clear_peer,
+            central_action,
             action,
+            combo_triggered,
         );
 
         // Auto-bump subscriber counts based on enabled feature flags.
@@ -124,6 +138,28 @@ impl crate::KeyboardTomlConfig {
             None
         };
 
+        // The split link has the limits of any BLE connection, see `BleConnParamsConfig::validate`
+        let interval = rmk.split_conn_interval_us;
+        if !(7500..=4_000_000).contains(&interval) || interval % 1250 != 0 {
+            return Err(format!(
+                "keyboard.toml: [rmk.split_conn_interval_us] must be a multiple of 1250 between 7500 and 4000000, got {interval}"
+            ));
+        }
+        if rmk.split_conn_latency > 499 {
+            return Err(format!(
+                "keyboard.toml: [rmk.split_conn_latency] must be at most 499, got {}",
+                rmk.split_conn_latency
+            ));
+        }
+        let timeout = rmk.split_supervision_timeout_ms;
+        if !(100..=32_000).contains(&timeout)
+            || (timeout as u64) * 1000 <= (1 + rmk.split_conn_latency as u64) * interval as u64 * 2
+        {
+            return Err(format!(
+                "keyboard.toml: [rmk.split_supervision_timeout_ms] must be between 100 and 32000 and longer than twice split_conn_interval_us * (1 + split_conn_latency), got {timeout}"
+            ));
+        }
+
         // Validate that config values do not exceed protocol ceilings.
         use crate::protocol_limits;
         if rmk.combo_max_length > protocol_limits::MAX_COMBO_SIZE {
@@ -162,15 +198,21 @@ impl crate::KeyboardTomlConfig {
             morse_max_num: rmk.morse_max_num,
             max_patterns_per_key: rmk.max_patterns_per_key,
             macro_space_size: rmk.macro_space_size,
+            keymap_profile_num: rmk.keymap_profile_num,
             debounce_time: rmk.debounce_time,
             mouse_key_interval: rmk.mouse_key_interval,
             mouse_wheel_interval: rmk.mouse_wheel_interval,
             report_channel_size: rmk.report_channel_size,
+            report_queue_overflow: rmk.report_queue_overflow,
             vial_channel_size: rmk.vial_channel_size,
             flash_channel_size: rmk.flash_channel_size,
+            flash_batch_size: rmk.flash_batch_size,
             split_peripherals_num,
             ble_profiles_num: rmk.ble_profiles_num,
             split_central_sleep_timeout_seconds: rmk.split_central_sleep_timeout_seconds,
+            split_conn_interval_us: rmk.split_conn_interval_us,
+            split_conn_latency: rmk.split_conn_latency,
+            split_supervision_timeout_ms: rmk.split_supervision_timeout_ms,
             protocol_max_bulk_size: rmk.protocol_max_bulk_size,
             protocol_macro_chunk_size: rmk.protocol_macro_chunk_size,
             events,
@@ -218,7 +260,10 @@ fn resolve_passkey_enabled(ble: &crate::BleConfig) -> Result<Passkey, String> {
 #[cfg(test)]
 mod tests {
     use super::resolve_passkey_enabled;
-    use crate::{BleConfig, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, MIN_PASSKEY_ENTRY_TIMEOUT_SECS};
+    use crate::{
+        BleConfig, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS, KeyboardTomlConfig, MIN_PASSKEY_ENTRY_TIMEOUT_SECS,
+        ReportQueueOverflow,
+    };
 
     #[test]
     fn validates_passkey_timeout() {
@@ -249,4 +294,35 @@ mod tests {
         assert!(!passkey.enabled);
         assert_eq!(passkey.timeout_secs, DEFAULT_PASSKEY_ENTRY_TIMEOUT_SECS);
     }
+
+    #[test]
+    fn validates_split_conn_params() {
+        let config: KeyboardTomlConfig = toml::from_str("").unwrap();
+        let bc = config.build_constants(&[]).unwrap();
+        assert_eq!(bc.split_conn_interval_us, 7500);
+        assert_eq!(bc.split_conn_latency, 30);
+
+        let config: KeyboardTomlConfig = toml::from_str("[rmk]\nsplit_conn_interval_us = 8000").unwrap();
+        assert!(config.build_constants(&[]).is_err());
+
+        // 15ms * (1 + 99) * 2 = 3s
+        let config: KeyboardTomlConfig = toml::from_str(
+            "[rmk]\nsplit_conn_interval_us = 15000\nsplit_conn_latency = 99\nsplit_supervision_timeout_ms = 3000",
+        )
+        .unwrap();
+        assert!(config.build_constants(&[]).is_err());
+    }
+
+    #[test]
+    fn parses_report_queue_overflow() {
+        let config: KeyboardTomlConfig = toml::from_str("").unwrap();
+        let bc = config.build_constants(&[]).unwrap();
+        assert_eq!(bc.report_queue_overflow, ReportQueueOverflow::Block);
+
+        let config: KeyboardTomlConfig = toml::from_str("[rmk]\nreport_queue_overflow = \"drop_oldest\"").unwrap();
+        let bc = config.build_constants(&[]).unwrap();
+        assert_eq!(bc.report_queue_overflow, ReportQueueOverflow::DropOldest);
+
+        assert!(toml::from_str::<KeyboardTomlConfig>("[rmk]\nreport_queue_overflow = \"drop_newest\"").is_err());
+    }
 }
@@ -8,10 +8,11 @@ pub use crate::board::{BoardConfig, UniBodyConfig};
 pub use crate::chip::{ChipModel, ChipSeries};
 pub use crate::communication::{CommunicationConfig, UsbInfo};
 pub use crate::{
-    BleConfig, ChipConfig, CommunicationProtocol, DependencyConfig, DisplayConfig, DisplayDriver, EncoderConfig,
-    EncoderResolution, I2cConfig, InputDeviceConfig, Iqs5xxConfig, Iqs5xxI2cConfig, JoystickConfig, KeyInfo,
-    LightConfig, MatrixConfig, MatrixType, OutputConfig, PinConfig, Pmw33xxConfig, Pmw33xxType, Pmw3610Config,
-    PointingDeviceConfig, SerialConfig, SpiConfig, SplitBoardConfig, SplitConfig,
+    BleConfig, ChipConfig, CommunicationProtocol, DependencyConfig, DisplayConfig, DisplayDriver, DisplayWidgetConfig,
+    DisplayWidgetKind, EncoderConfig, EncoderResolution, I2cConfig, InputDeviceConfig, Iqs5xxConfig, Iqs5xxI2cConfig,
+    JoystickConfig, KeyInfo, LightConfig, MatrixConfig, MatrixType, OutputConfig, PinConfig, Pmw33xxConfig,
+    Pmw33xxType, Pmw3610Config, PointingDeviceConfig, SerialConfig, SpiConfig, SplitBoardConfig, SplitConfig,
+    UsbConfig,
 };
 
 /// Resolved storage hardware config
@@ -27,6 +28,7 @@ pub struct Hardware {
     pub chip: ChipModel,
     pub chip_config: ChipConfig,
     pub communication: CommunicationConfig,
+    pub usb: UsbConfig,
     pub board: BoardConfig,
     pub storage: Option<Storage>,
     pub light: LightConfig,
@@ -41,6 +43,7 @@ impl crate::KeyboardTomlConfig {
         let chip = self.get_chip_model()?;
         let chip_config = self.get_chip_config();
         let communication = self.get_communication_config()?;
+        let usb = self.get_usb_config();
         let board = self.get_board_config()?;
         let storage_toml = self.get_storage_config();
         let storage = if storage_toml.enabled {
@@ -61,6 +64,7 @@ impl crate::KeyboardTomlConfig {
             chip,
             chip_config,
             communication,
+            usb,
             board,
             storage,
             light,
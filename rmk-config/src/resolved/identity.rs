@@ -6,6 +6,7 @@ pub struct Identity {
     pub manufacturer: String,
     pub product_name: String,
     pub serial_number: String,
+    pub serial_from_chip_id: bool,
 }
 
 impl crate::KeyboardTomlConfig {
@@ -28,6 +29,7 @@ impl crate::KeyboardTomlConfig {
                 .serial_number
                 .clone()
                 .unwrap_or_else(|| "vial:f64c2b3c:000001".to_string()),
+            serial_from_chip_id: keyboard.serial_from_chip_id.unwrap_or(true),
         })
     }
 }
@@ -0,0 +1,432 @@
+//! Checks of keyboard.toml for mistakes which would otherwise build a misbehaving firmware.
+//!
+//! Every problem is reported with the line of keyboard.toml it's about, found by searching
+//! the source text, since the parsed config doesn't keep the positions.
+
+use std::collections::HashMap;
+
+use crate::board::BoardConfig;
+use crate::{CommunicationProtocol, InputDeviceConfig, KeyboardTomlConfig, MatrixConfig, OutputConfig, SpiConfig};
+
+/// Source text of keyboard.toml
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TomlSource {
+    pub path: String,
+    pub text: String,
+}
+
+impl TomlSource {
+    /// 1-based line of the `nth` (0-based) occurrence of `needle` as a whole word, comments skipped
+    fn line_of(&self, needle: &str, nth: usize) -> Option<usize> {
+        let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let mut count = 0;
+        for (line_number, line) in self.text.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            for (start, _) in line.match_indices(needle) {
+                let end = start + needle.len();
+                let starts_word = needle.starts_with(is_word) && line[..start].ends_with(is_word);
+                let ends_word = needle.ends_with(is_word) && line[end..].starts_with(is_word);
+                if starts_word || ends_word {
+                    continue;
+                }
+                if count == nth {
+                    return Some(line_number + 1);
+                }
+                count += 1;
+            }
+        }
+        None
+    }
+}
+
+/// Pins taken by one board.
+///
+/// The same pin can appear on several boards of a split keyboard, so the occurrences on the
+/// previous boards are counted to find the right line of keyboard.toml.
+struct PinUsage<'a> {
+    /// Pin, its first user and the index of its occurrence in keyboard.toml
+    pins: HashMap<String, (String, usize)>,
+    /// Pin, both users and both occurrences
+    duplicates: Vec<(String, String, String, usize, usize)>,
+    seen_before: &'a mut HashMap<String, usize>,
+}
+
+impl KeyboardTomlConfig {
+    /// `path:line` of the `nth` (0-based) occurrence of `needle` in keyboard.toml,
+    /// or `keyboard.toml` if it isn't found.
+    pub fn source_location(&self, needle: &str, nth: usize) -> String {
+        match &self.source {
+            Some(source) => match source.line_of(needle, nth) {
+                Some(line) => format!("{}:{}", source.path, line),
+                None => source.path.clone(),
+            },
+            None => "keyboard.toml".to_string(),
+        }
+    }
+
+    /// Check the layers, matrix references and pins of keyboard.toml.
+    ///
+    /// All problems found are returned at once, one per line.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        self.validate_layers(&mut errors);
+        if let Ok(board) = self.get_board_config() {
+            self.validate_bootmagic(&board, &mut errors);
+            self.validate_pins(&board, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("\n\u{274c} keyboard.toml is invalid:\n{}", errors.join("\n")))
+        }
+    }
+
+    /// Every `[[layer]]` has to fill the whole `matrix_map`, and have at most one action pair per encoder
+    fn validate_layers(&self, errors: &mut Vec<String>) {
+        let (Some(layout), Some(layers)) = (&self.layout, &self.layer) else {
+            return;
+        };
+        if layers.len() > layout.layers as usize {
+            errors.push(format!(
+                "{}: there are {} [[layer]] entries, but layout.layers is {}",
+                self.source_location("[[layer]]", layout.layers as usize),
+                layers.len(),
+                layout.layers
+            ));
+        }
+
+        let positions = layout
+            .matrix_map
+            .as_ref()
+            .and_then(|map| Self::parse_matrix_map(map).ok())
+            .map(|map| map.len());
+        let aliases = self.aliases.clone().unwrap_or_default();
+        let layer_names: HashMap<String, u32> = layers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, layer)| layer.name.clone().map(|name| (name, i as u32)))
+            .collect();
+        let num_encoder: usize = self
+            .get_board_config()
+            .map(|board| board.get_num_encoder().iter().sum())
+            .unwrap_or_default();
+
+        for (i, layer) in layers.iter().enumerate() {
+            let name = layer.name.clone().unwrap_or_else(|| format!("#{i}"));
+            if let Some(positions) = positions
+                && let Ok(keys) = Self::keymap_parser(&layer.keys, &aliases, &layer_names)
+                && keys.len() < positions
+            {
+                errors.push(format!(
+                    "{}: layer {} has {} keys, but layout.matrix_map has {} positions, use `No` or `_` for the unused ones",
+                    self.source_location("[[layer]]", i),
+                    name,
+                    keys.len(),
+                    positions
+                ));
+            }
+            if let Some(encoders) = &layer.encoders
+                && encoders.len() > num_encoder
+            {
+                errors.push(format!(
+                    "{}: layer {} has actions for {} encoders, but {} encoders are defined in [[input_device.encoder]]",
+                    self.source_location("[[layer]]", i),
+                    name,
+                    encoders.len(),
+                    num_encoder
+                ));
+            }
+        }
+    }
+
+    /// Bootmagic keys have to be inside the matrix of their board
+    fn validate_bootmagic(&self, board: &BoardConfig, errors: &mut Vec<String>) {
+        let boards: Vec<(&MatrixConfig, usize, usize)> = match board {
+            BoardConfig::UniBody(unibody) => match &self.layout {
+                Some(layout) => vec![(&unibody.matrix, layout.rows as usize, layout.cols as usize)],
+                None => vec![],
+            },
+            BoardConfig::Split(split) => core::iter::once(&split.central)
+                .chain(&split.peripheral)
+                .map(|b| (&b.matrix, b.rows, b.cols))
+                .collect(),
+        };
+
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (matrix, rows, cols) in boards {
+            for (field, key) in [
+                ("bootmagic", matrix.bootmagic),
+                ("bootmagic_reset", matrix.bootmagic_reset),
+                ("bootmagic_safe_mode", matrix.bootmagic_safe_mode),
+            ] {
+                let Some((row, col)) = key else {
+                    continue;
+                };
+                let nth = seen.entry(field).or_default();
+                if row as usize >= rows || col as usize >= cols {
+                    errors.push(format!(
+                        "{}: {} key ({}, {}) is outside of the {}x{} matrix",
+                        self.source_location(field, *nth),
+                        field,
+                        row,
+                        col,
+                        rows,
+                        cols
+                    ));
+                }
+                *nth += 1;
+            }
+        }
+    }
+
+    /// A pin can be used once on each board
+    fn validate_pins(&self, board: &BoardConfig, errors: &mut Vec<String>) {
+        let mut seen_before = HashMap::new();
+        let mut usage = PinUsage::new(&mut seen_before);
+        // Lights and the charging pins are on the unibody board, or on the central
+        if let Some(light) = &self.light {
+            for pin in [&light.capslock, &light.scrolllock, &light.numslock]
+                .into_iter()
+                .flatten()
+            {
+                usage.add(&pin.pin, "light");
+            }
+        }
+        if let Some(ble) = &self.ble {
+            for pin in [&ble.charge_state, &ble.charge_led].into_iter().flatten() {
+                usage.add(&pin.pin, "charging");
+            }
+        }
+        match board {
+            BoardConfig::UniBody(unibody) => {
+                usage.add_matrix(&unibody.matrix);
+                usage.add_input_devices(&unibody.input_device);
+                usage.add_outputs(self.output.as_deref().unwrap_or_default());
+                if let Some(display) = &self.display {
+                    usage.add_protocol(&display.protocol, "display");
+                }
+                if let Some(ble) = &self.ble {
+                    usage.add_opt(&ble.battery_adc_pin, "battery");
+                }
+                usage.finish(self, errors);
+            }
+            BoardConfig::Split(split) => {
+                for (i, b) in core::iter::once(&split.central).chain(&split.peripheral).enumerate() {
+                    if i > 0 {
+                        usage = PinUsage::new(usage.finish(self, errors));
+                    }
+                    usage.add_matrix(&b.matrix);
+                    if let Some(input_device) = &b.input_device {
+                        usage.add_input_devices(input_device);
+                    }
+                    usage.add_outputs(b.output.as_deref().unwrap_or_default());
+                    if let Some(display) = &b.display {
+                        usage.add_protocol(&display.protocol, "display");
+                    }
+                    usage.add_opt(&b.battery_adc_pin, "battery");
+                }
+                usage.finish(self, errors);
+            }
+        }
+    }
+}
+
+impl<'a> PinUsage<'a> {
+    fn new(seen_before: &'a mut HashMap<String, usize>) -> Self {
+        Self {
+            pins: HashMap::new(),
+            duplicates: Vec::new(),
+            seen_before,
+        }
+    }
+
+    fn add(&mut self, pin: &str, user: &str) {
+        // `_` and `trns` are placeholders of direct pins
+        if pin == "_" || pin.eq_ignore_ascii_case("trns") {
+            return;
+        }
+        let nth = self.seen_before.entry(pin.to_string()).or_default();
+        match self.pins.get(pin) {
+            // Devices on the same bus share its pins
+            Some((first, _)) if first == user && user.ends_with(" bus") => {}
+            Some((first, first_nth)) => {
+                self.duplicates
+                    .push((pin.to_string(), first.clone(), user.to_string(), *first_nth, *nth))
+            }
+            None => {
+                self.pins.insert(pin.to_string(), (user.to_string(), *nth));
+            }
+        }
+        *nth += 1;
+    }
+
+    fn add_opt(&mut self, pin: &Option<String>, user: &str) {
+        if let Some(pin) = pin {
+            self.add(pin, user);
+        }
+    }
+
+    fn add_matrix(&mut self, matrix: &MatrixConfig) {
+        for pin in matrix.row_pins.iter().flatten() {
+            self.add(pin, "matrix row");
+        }
+        for pin in matrix.col_pins.iter().flatten() {
+            self.add(pin, "matrix col");
+        }
+        for pin in matrix.direct_pins.iter().flatten().flatten() {
+            self.add(pin, "direct pin");
+        }
+    }
+
+    fn add_protocol(&mut self, protocol: &CommunicationProtocol, user: &str) {
+        match protocol {
+            CommunicationProtocol::I2c(i2c) => {
+                let bus = format!("{} bus", i2c.instance);
+                self.add(&i2c.sda, &bus);
+                self.add(&i2c.scl, &bus);
+            }
+            CommunicationProtocol::Spi(spi) => self.add_spi(spi, user),
+        }
+    }
+
+    fn add_spi(&mut self, spi: &SpiConfig, user: &str) {
+        let bus = format!("{} bus", spi.instance);
+        for pin in [&spi.sck, &spi.mosi, &spi.miso] {
+            self.add(pin, &bus);
+        }
+        self.add_opt(&spi.cs, &format!("{user} cs"));
+    }
+
+    fn add_input_devices(&mut self, input_device: &InputDeviceConfig) {
+        for encoder in input_device.encoder.iter().flatten() {
+            self.add(&encoder.pin_a, "encoder");
+            self.add(&encoder.pin_b, "encoder");
+        }
+        for joystick in input_device.joystick.iter().flatten() {
+            for pin in [&joystick.pin_x, &joystick.pin_y, &joystick.pin_z] {
+                self.add(pin, &joystick.name);
+            }
+        }
+        for sensor in input_device.pmw3610.iter().flatten() {
+            self.add_spi(&sensor.spi, &sensor.name);
+            self.add_opt(&sensor.motion, &sensor.name);
+        }
+        for sensor in input_device.pmw33xx.iter().flatten() {
+            self.add_spi(&sensor.spi, &sensor.name);
+            self.add_opt(&sensor.motion, &sensor.name);
+        }
+        for trackpad in input_device.iqs5xx.iter().flatten() {
+            let bus = format!("{} bus", trackpad.i2c.instance);
+            self.add(&trackpad.i2c.sda, &bus);
+            self.add(&trackpad.i2c.scl, &bus);
+            self.add_opt(&trackpad.rdy, &trackpad.name);
+        }
+        for pointing in input_device.pointing.iter().flatten() {
+            if let Some(interface) = &pointing.interface {
+                self.add_protocol(interface, "pointing device");
+            }
+        }
+    }
+
+    fn add_outputs(&mut self, outputs: &[OutputConfig]) {
+        for output in outputs {
+            self.add(&output.pin, "output");
+        }
+    }
+
+    /// Report the duplicates, and give back the occurrences for the next board
+    fn finish(self, config: &KeyboardTomlConfig, errors: &mut Vec<String>) -> &'a mut HashMap<String, usize> {
+        for (pin, first, second, first_nth, second_nth) in self.duplicates {
+            let needle = format!("\"{pin}\"");
+            errors.push(format!(
+                "{}: pin {} is used by both {} and {}, it's first used at {}",
+                config.source_location(&needle, second_nth),
+                pin,
+                first,
+                second,
+                config.source_location(&needle, first_nth)
+            ));
+        }
+        self.seen_before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> KeyboardTomlConfig {
+        let mut config: KeyboardTomlConfig = toml::from_str(text).unwrap();
+        config.source = Some(TomlSource {
+            path: "keyboard.toml".to_string(),
+            text: text.to_string(),
+        });
+        config
+    }
+
+    const VALID: &str = r#"
+[layout]
+rows = 2
+cols = 2
+layers = 2
+matrix_map = "(0,0) (0,1) (1,0) (1,1)"
+
+[matrix]
+row_pins = ["P0_00", "P0_01"]
+col_pins = ["P0_02", "P0_03"]
+bootmagic = [1, 1]
+
+[[layer]]
+keys = "A B C D"
+"#;
+
+    #[test]
+    fn valid_config_passes() {
+        assert_eq!(parse(VALID).validate(), Ok(()));
+    }
+
+    #[test]
+    fn errors_have_lines() {
+        let config = parse(
+            r#"
+[layout]
+rows = 2
+cols = 2
+layers = 2
+matrix_map = "(0,0) (0,1) (1,0) (1,1)"
+
+[matrix]
+row_pins = ["P0_00", "P0_01"]
+# The second col pin is a typo
+col_pins = ["P0_02", "P0_01"]
+bootmagic = [2, 0]
+
+[[layer]]
+keys = "A B C D"
+
+[[layer]]
+keys = "A B C"
+"#,
+        );
+        let errors = config.validate().unwrap_err();
+        assert!(errors.contains("keyboard.toml:17: layer #1 has 3 keys, but layout.matrix_map has 4 positions"));
+        assert!(errors.contains("keyboard.toml:12: bootmagic key (2, 0) is outside of the 2x2 matrix"));
+        assert!(errors.contains(
+            "keyboard.toml:11: pin P0_01 is used by both matrix row and matrix col, it's first used at keyboard.toml:9"
+        ));
+    }
+
+    #[test]
+    fn line_of_matches_whole_words() {
+        let source = TomlSource {
+            path: "keyboard.toml".to_string(),
+            text: "# bootmagic\nbootmagic_reset = [0, 0]\nbootmagic = [0, 1]\n".to_string(),
+        };
+        assert_eq!(source.line_of("bootmagic", 0), Some(3));
+        assert_eq!(source.line_of("bootmagic_reset", 0), Some(2));
+        assert_eq!(source.line_of("bootmagic", 1), None);
+    }
+}
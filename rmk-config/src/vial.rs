@@ -0,0 +1,156 @@
+use serde_json::{Value, json};
+
+use crate::KeyboardTomlConfig;
+
+/// Characters taken by a `(row,col)` of `matrix_map` when nothing else tells the key width
+const DEFAULT_KEY_WIDTH: usize = 6;
+
+impl KeyboardTomlConfig {
+    /// Vial definition (`vial.json`) of the keyboard.
+    ///
+    /// The keys are laid out like they're written in `matrix_map`: a line of `matrix_map` is a
+    /// row of keys, and the spaces before and between the keys become gaps. The encoders are
+    /// added in a row below the keys, and the BLE keys as custom keycodes when BLE is enabled.
+    pub fn vial_definition(&self) -> Result<String, String> {
+        let identity = self.identity()?;
+        let layout = self
+            .layout
+            .as_ref()
+            .ok_or("keyboard.toml: [layout] is required to generate the Vial definition")?;
+
+        let mut rows = match &layout.matrix_map {
+            Some(matrix_map) => Self::vial_rows(matrix_map)?,
+            // `layout.keymap` has no matrix_map, all keys are on a grid
+            None => (0..layout.rows)
+                .map(|row| (0..layout.cols).map(|col| json!(format!("{row},{col}"))).collect())
+                .collect(),
+        };
+
+        let num_encoder: usize = self.get_board_config().map(|b| b.get_num_encoder().iter().sum())?;
+        if num_encoder > 0 {
+            // Counter-clockwise is direction 0, clockwise is direction 1
+            rows.push(
+                (0..num_encoder)
+                    .flat_map(|e| {
+                        [
+                            json!(format!("{e},0\n\n\n\n\n\n\n\n\ne")),
+                            json!(format!("{e},1\n\n\n\n\n\n\n\n\ne")),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+
+        let mut definition = json!({
+            "name": identity.name,
+            "vendorId": format!("0x{:04X}", identity.vendor_id),
+            "productId": format!("0x{:04X}", identity.product_id),
+            "lighting": "none",
+            "matrix": { "rows": layout.rows, "cols": layout.cols },
+            "layouts": { "keymap": rows },
+        });
+        if self.ble.as_ref().is_some_and(|ble| ble.enabled) {
+            definition["customKeycodes"] = Value::Array(Self::vial_ble_keycodes(self.rmk.ble_profiles_num));
+        }
+        Ok(definition.to_string())
+    }
+
+    /// KLE rows of the keys in `matrix_map`
+    fn vial_rows(matrix_map: &str) -> Result<Vec<Vec<Value>>, String> {
+        // Columns of the `(` of each key, line by line
+        let lines: Vec<(&str, Vec<usize>)> = matrix_map
+            .lines()
+            .map(|line| line.split("//").next().unwrap_or_default())
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                (
+                    line,
+                    line.char_indices().filter(|(_, c)| *c == '(').map(|(i, _)| i).collect(),
+                )
+            })
+            .collect();
+
+        // Indentation of the whole `matrix_map` isn't a gap
+        let indent = lines
+            .iter()
+            .flat_map(|(_, starts)| starts.first())
+            .min()
+            .copied()
+            .unwrap_or_default();
+        // The closest keys of a line are one key apart
+        let key_width = lines
+            .iter()
+            .flat_map(|(_, starts)| starts.windows(2).map(|w| w[1] - w[0]))
+            .min()
+            .unwrap_or(DEFAULT_KEY_WIDTH)
+            .max(1);
+
+        let mut rows = Vec::new();
+        for (line, starts) in lines {
+            let keys = Self::parse_matrix_map(line)?;
+            let mut row = Vec::new();
+            let mut x = 0;
+            for ((key_row, key_col, _), start) in keys.iter().zip(starts) {
+                let key_x = (start - indent + key_width / 2) / key_width;
+                if key_x > x {
+                    row.push(json!({ "x": key_x - x }));
+                }
+                row.push(json!(format!("{key_row},{key_col}")));
+                x = key_x.max(x) + 1;
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Custom keycodes of the BLE user keys, in the order of their user key ids
+    fn vial_ble_keycodes(num_profile: usize) -> Vec<Value> {
+        let keycode = |name: &str, title: &str, short_name: &str| json!({ "name": name, "title": title, "shortName": short_name });
+        let mut keycodes: Vec<Value> = (0..num_profile)
+            .map(|i| keycode(&format!("BT{i}"), &format!("Bluetooth Channel {i}"), &format!("BT{i}")))
+            .collect();
+        keycodes.extend([
+            keycode("NEXT_BT", "Switch to the next Bluetooth channel", "Next\nBT"),
+            keycode("PREV_BT", "Switch to the previous Bluetooth channel", "Prev\nBT"),
+            keycode("CLR_BT", "Clear bond info for current channel", "Clear\nBT"),
+            keycode("SWITCH", "Switch default output mode between USB/BLE", "Switch\nOutput"),
+            keycode(
+                "CLR_PEER",
+                "Forget the current bonded split peer(central or peripheral)",
+                "Clear\nPeer",
+            ),
+            keycode("CLR_ALL_BT", "Clear bond info for all channels", "Clear\nAll BT"),
+        ]);
+        keycodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vial_rows_follow_matrix_map() {
+        let rows = KeyboardTomlConfig::vial_rows(
+            "
+            (0,0) (0,1) (0,2)
+                  (1,0)       (1,1) // thumb keys
+            ",
+        )
+        .unwrap();
+        assert_eq!(rows[0], vec![json!("0,0"), json!("0,1"), json!("0,2")]);
+        assert_eq!(
+            rows[1],
+            vec![json!({ "x": 1 }), json!("1,0"), json!({ "x": 1 }), json!("1,1")]
+        );
+    }
+
+    #[test]
+    fn vial_ble_keycodes_match_user_keys() {
+        let keycodes = KeyboardTomlConfig::vial_ble_keycodes(2);
+        // BT0, BT1, then NEXT_BT at User(NUM_BLE_PROFILE)
+        assert_eq!(keycodes[1]["name"], "BT1");
+        assert_eq!(keycodes[2]["name"], "NEXT_BT");
+        assert_eq!(keycodes[7]["name"], "CLR_ALL_BT");
+    }
+}
@@ -257,6 +257,15 @@ fn parse_action(key: &str) -> TokenStream2 {
     } else if lower.starts_with("pdf(") {
         let layer = parse_layer(key);
         return quote! { ::rmk::types::action::Action::PersistentDefaultLayer(#layer) };
+    } else if lower.starts_with("profile(") {
+        let profile = strip_call(key).trim().parse::<u8>().unwrap_or(255);
+        if profile > 15 {
+            panic!(
+                "\n\u{274c} keyboard.toml: {} is not a valid keymap profile! Profiles are numbered 0-15. Please check the documentation: https://rmk.rs/docs/features/storage.html",
+                key
+            );
+        }
+        return quote! { ::rmk::types::action::Action::ProfileSwitch(#profile) };
     } else if lower.starts_with("df(") {
         let layer = parse_layer(key);
         return quote! { ::rmk::types::action::Action::DefaultLayer(#layer) };
@@ -280,6 +289,31 @@ fn parse_action(key: &str) -> TokenStream2 {
     } else if lower.starts_with("stn(") {
         let key_ident = format_ident!("{}", strip_call(key).trim().to_uppercase());
         return quote! { ::rmk::types::action::Action::Steno(::rmk::types::steno::StenoKey::#key_ident) };
+    } else if lower.starts_with("bt(") {
+        // BLE profile keys are user keys, see the layout of user keys in `Keyboard::process_user`
+        let num_profile = rmk_types::constants::NUM_BLE_PROFILE;
+        let profile = strip_call(key)
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|&p| (p as usize) < num_profile)
+            .unwrap_or_else(|| {
+                panic!(
+                    "\n\u{274c} keyboard.toml: {} is not a valid BLE profile, `ble_profiles_num` is {}! Please check the documentation: https://rmk.rs/docs/features/wireless.html",
+                    key, num_profile
+                )
+            });
+        return quote! { ::rmk::types::action::Action::User(#profile) };
+    } else if let Some(offset) = match lower.as_str() {
+        "btnext" => Some(0u8),
+        "btprev" => Some(1),
+        "btclear" => Some(2),
+        "btclearall" => Some(5),
+        _ => None,
+    } {
+        return quote! {
+            ::rmk::types::action::Action::User(::rmk::types::constants::NUM_BLE_PROFILE as u8 + #offset)
+        };
     } else if lower.starts_with("user") {
         // Support both User(X) and UserX formats
         let number_str = if lower.starts_with("user(") {
@@ -450,6 +484,51 @@ pub(crate) fn get_key_with_alias(key: String) -> Ident {
     format_ident!("{}", key)
 }
 
+/// HID key names in an expanded key which aren't keycodes, with the keycode they probably meant
+pub(crate) fn unknown_key_names(expanded: &TokenStream2) -> Vec<(String, Option<&'static str>)> {
+    let expanded = expanded.to_string();
+    expanded
+        .split("HidKeyCode :: ")
+        .skip(1)
+        .filter_map(|rest| {
+            let name: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect();
+            let hid_keycodes = rmk_types::keycode::HidKeyCode::VARIANTS;
+            if hid_keycodes.contains(&name.as_str()) {
+                return None;
+            }
+            // The closest keycode, if it's at most 2 edits away
+            let suggestion = hid_keycodes
+                .iter()
+                .map(|k| (edit_distance(&k.to_lowercase(), &name.to_lowercase()), *k))
+                .filter(|(distance, _)| *distance <= 2)
+                .min_by_key(|(distance, _)| *distance)
+                .map(|(_, k)| k);
+            Some((name, suggestion))
+        })
+        .collect()
+}
+
+/// Levenshtein distance of two ASCII strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != *cb))
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -479,6 +558,35 @@ mod tests {
         assert!(squash(&expand("OSM(LShift)")).contains("Action::OneShotModifier"));
     }
 
+    #[test]
+    fn ble_profile_keys_expand_to_user_keys() {
+        assert!(squash(&expand("BT(2)")).contains("Action::User(2u8)"));
+        assert!(
+            squash(&expand("BtNext"))
+                .contains("Action::User(::rmk::types::constants::NUM_BLE_PROFILEasu8+0u8)")
+        );
+        assert!(
+            squash(&expand("btprev"))
+                .contains("Action::User(::rmk::types::constants::NUM_BLE_PROFILEasu8+1u8)")
+        );
+        assert!(
+            squash(&expand("BtClearAll"))
+                .contains("Action::User(::rmk::types::constants::NUM_BLE_PROFILEasu8+5u8)")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`ble_profiles_num` is 3")]
+    fn ble_profile_beyond_configured_number_is_rejected() {
+        expand("BT(3)");
+    }
+
+    #[test]
+    fn profile_keys_expand_to_profile_switches() {
+        assert!(squash(&expand("PROFILE(2)")).contains("Action::ProfileSwitch(2u8)"));
+        assert!(squash(&expand("profile(0)")).contains("Action::ProfileSwitch(0u8)"));
+    }
+
     #[test]
     fn mt_accepts_nested_with_modifier_tap() {
         let out = squash(&expand("MT(WM(P, RAlt), LShift)"));
@@ -514,4 +622,20 @@ mod tests {
         );
         assert!(squash(&expand("LT(2, Enter)")).contains("Action::LayerOn(2u8)"));
     }
+
+    #[test]
+    fn unknown_key_names_are_found() {
+        assert!(unknown_key_names(&parse_key("WM(C,LCtrl)".to_string(), &None)).is_empty());
+        assert!(unknown_key_names(&parse_key("LT(1,Space)".to_string(), &None)).is_empty());
+        // Aliases are resolved before the check
+        assert!(unknown_key_names(&parse_key("pgup".to_string(), &None)).is_empty());
+        assert_eq!(
+            unknown_key_names(&parse_key("WM(Foobar,LCtrl)".to_string(), &None)),
+            vec![("Foobar".to_string(), None)]
+        );
+        assert_eq!(
+            unknown_key_names(&parse_key("Spce".to_string(), &None)),
+            vec![("Spce".to_string(), Some("Space"))]
+        );
+    }
 }
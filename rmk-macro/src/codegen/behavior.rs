@@ -496,6 +496,7 @@ pub(crate) fn expand_behavior_config(behavior: &Behavior) -> proc_macro2::TokenS
     let macros = expand_macros(&behavior.macros);
     let forks = expand_forks(&behavior.forks, &profiles);
     let morse = expand_morse(&behavior.morse);
+    let swap_ctrl_gui_on_mac = behavior.swap_ctrl_gui_on_mac;
 
     quote! {
         #[allow(clippy::needless_update)]
@@ -509,6 +510,7 @@ pub(crate) fn expand_behavior_config(behavior: &Behavior) -> proc_macro2::TokenS
             keyboard_macros: #macros,
             mouse_key: ::rmk::config::MouseKeyConfig::default(),
             tap: ::rmk::config::TapConfig::default(),
+            swap_ctrl_gui_on_mac: #swap_ctrl_gui_on_mac,
             ..Default::default()
         };
     }
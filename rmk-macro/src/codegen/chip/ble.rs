@@ -1,94 +1,80 @@
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
+use quote::quote;
 use rmk_config::resolved::Hardware;
-use rmk_config::resolved::hardware::{ChipSeries, CommunicationConfig};
+use rmk_config::{BleConnMode, BleConnParamsConfig};
 
 // Default implementations of ble configuration.
 // Because ble configuration in `config` is enabled by a feature gate, so this function returns two TokenStreams.
 // One for initialization ble config, another one for filling this field into `RmkConfig`.
 pub(crate) fn expand_ble_config(hardware: &Hardware) -> (TokenStream2, TokenStream2) {
-    if !hardware.communication.ble_enabled() {
+    let Some(ble_config) = hardware.communication.get_ble_config() else {
         return (quote! {}, quote! {});
-    }
-    // Advanced parameters are only supported for nrf52(for now)
-    if hardware.chip.series != ChipSeries::Nrf52 {
-        return (
-            quote! {
-                let ble_battery_config = ::rmk::config::BleBatteryConfig::default();
-            },
-            quote! {
-                ble_battery_config,
-            },
-        );
-    }
-
-    // nRF52 configuration
-    // Only nRF52 supports charge_state, charging_led and adc config
-    match &hardware.communication {
-        CommunicationConfig::Ble(ble) | CommunicationConfig::Both(_, ble) => {
-            if ble.enabled {
-                let mut ble_config_tokens = TokenStream2::new();
-                if let Some(charging_state_config) = ble.charge_state.clone() {
-                    let charging_state_pin = format_ident!("{}", charging_state_config.pin);
-                    let low_active = charging_state_config.low_active;
-                    let pull = if low_active {
-                        quote! { ::embassy_nrf::gpio::Pull::Up }
-                    } else {
-                        quote! { ::embassy_nrf::gpio::Pull::Down }
-                    };
-                    ble_config_tokens.extend(quote! {
-                        let is_charging_pin = Some(::embassy_nrf::gpio::Input::new(p.#charging_state_pin, #pull));
-                        let charging_state_low_active = #low_active;
-                    });
-                } else {
-                    ble_config_tokens.extend(quote! {
-                        let charging_state_low_active = false;
-                        let is_charging_pin: ::core::option::Option<::embassy_nrf::gpio::Input<'_>> = None;
-                    })
-                }
-
-                if let Some(charging_led_config) = ble.charge_led.clone() {
-                    let charging_led_pin = format_ident!("{}", charging_led_config.pin);
-                    let charging_led_low_active = charging_led_config.low_active;
-                    let default_level = if charging_led_low_active {
-                        quote! { ::embassy_nrf::gpio::Level::High }
-                    } else {
-                        quote! { ::embassy_nrf::gpio::Level::Low }
-                    };
-                    ble_config_tokens.extend(quote! {
-                        let charge_led_pin = Some(::embassy_nrf::gpio::Output::new(p.#charging_led_pin, #default_level, ::embassy_nrf::gpio::OutputDrive::Standard));
-                        let charge_led_low_active = #charging_led_low_active;
-                    });
-                } else {
-                    ble_config_tokens.extend(quote! {
-                        let charge_led_low_active = false;
-                        let charge_led_pin: ::core::option::Option<::embassy_nrf::gpio::Output<'_>>  = None;
-                    })
-                }
-
-                ble_config_tokens.extend(
-                    quote! {
-                        let ble_battery_config = ::rmk::config::BleBatteryConfig::new(is_charging_pin, charging_state_low_active, charge_led_pin, charge_led_low_active);
-                    }
-                );
+    };
+    let low_latency = expand_conn_params(ble_config.low_latency, quote! { LOW_LATENCY });
+    let power_save = expand_conn_params(ble_config.power_save, quote! { POWER_SAVE });
+    let mode = match ble_config.conn_mode.unwrap_or_default() {
+        BleConnMode::LowLatency => quote! { ::rmk::config::BleConnMode::LowLatency },
+        BleConnMode::PowerSave => quote! { ::rmk::config::BleConnMode::PowerSave },
+    };
+    let name = match &ble_config.name {
+        Some(name) => quote! { Some(#name) },
+        None => quote! { None },
+    };
+    let profile_suffix = ble_config.name_profile_suffix.unwrap_or(false);
+    let directed = ble_config.directed_advertising.unwrap_or(false);
+    let directed_only = ble_config.directed_advertising_only.unwrap_or(false);
+    let adv_timeout = match ble_config.advertising_timeout {
+        Some(secs) => quote! { ::embassy_time::Duration::from_secs(#secs as u64) },
+        None => quote! { ::rmk::config::BleAdvConfig::default().timeout },
+    };
+    let system_off = ble_config.advertising_timeout_power_off.unwrap_or(false);
+    // Charging state and charging LED pins are driven by `ChargingStateReader` and `BatteryLedProcessor`,
+    // which are generated together with the battery ADC, see `expand_adc_device`
+    (
+        quote! {
+            let ble_battery_config = ::rmk::config::BleBatteryConfig::default();
+            let ble_conn_config = ::rmk::config::BleConnConfig {
+                low_latency: #low_latency,
+                power_save: #power_save,
+                mode: #mode,
+            };
+            let ble_adv_config = ::rmk::config::BleAdvConfig {
+                name: #name,
+                profile_suffix: #profile_suffix,
+                directed: #directed,
+                directed_only: #directed_only,
+                timeout: #adv_timeout,
+                system_off: #system_off,
+            };
+        },
+        quote! {
+            ble_battery_config,
+            ble_conn_config,
+            ble_adv_config,
+        },
+    )
+}
 
-                (
-                    ble_config_tokens,
-                    quote! {
-                        ble_battery_config,
-                    },
-                )
-            } else {
-                (
-                    quote! {
-                        let ble_battery_config = ::rmk::config::BleBatteryConfig::default();
-                    },
-                    quote! {
-                        ble_battery_config,
-                    },
-                )
-            }
+/// Expand a connection parameter set, unset fields are taken from RMK's `default` set.
+fn expand_conn_params(params: Option<BleConnParamsConfig>, default: TokenStream2) -> TokenStream2 {
+    let params = params.unwrap_or_default();
+    let interval = match params.interval_us {
+        Some(us) => quote! { ::embassy_time::Duration::from_micros(#us as u64) },
+        None => quote! { ::rmk::config::BleConnParams::#default.interval },
+    };
+    let latency = match params.latency {
+        Some(latency) => quote! { #latency },
+        None => quote! { ::rmk::config::BleConnParams::#default.latency },
+    };
+    let supervision_timeout = match params.supervision_timeout_ms {
+        Some(ms) => quote! { ::embassy_time::Duration::from_millis(#ms as u64) },
+        None => quote! { ::rmk::config::BleConnParams::#default.supervision_timeout },
+    };
+    quote! {
+        ::rmk::config::BleConnParams {
+            interval: #interval,
+            latency: #latency,
+            supervision_timeout: #supervision_timeout,
         }
-        _ => (quote! {}, quote! {}),
     }
 }
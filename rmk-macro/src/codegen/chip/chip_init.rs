@@ -310,6 +310,10 @@ fn get_ble_addr(hardware: &Hardware, peripheral_id: Option<usize>) -> TokenStrea
                     }
                 }
             }
+            // Split halves need known addresses to find each other, uni-body ESP32 keyboards use the MAC address
+            BoardConfig::UniBody(_uni_body) if hardware.chip.series == ChipSeries::Esp32 => {
+                return quote! { ::rmk::ble::esp::ble_address() };
+            }
             // TODO: allow user to set the BLE address for uni-body keyboards
             BoardConfig::UniBody(_uni_body) => [0x18, 0xe2, 0x21, 0x80, 0xc0, 0xc7],
         };
@@ -30,6 +30,34 @@ pub(crate) fn expand_usb_init(hardware: &Hardware, item_mod: &ItemMod) -> TokenS
     }
 }
 
+/// Expand the `usb_config` field of `RmkConfig`, empty if USB isn't enabled.
+pub(crate) fn expand_usb_config(hardware: &Hardware) -> TokenStream2 {
+    if !hardware.communication.usb_enabled() {
+        return quote! {};
+    }
+    let mouse = hardware.usb.mouse.unwrap_or(true);
+    let consumer = hardware.usb.consumer.unwrap_or(true);
+    let system_control = hardware.usb.system_control.unwrap_or(true);
+    let poll_interval = hardware.usb.poll_interval.unwrap_or(1);
+    let battery = hardware.usb.battery.unwrap_or(false);
+    let nkro = hardware.usb.nkro.unwrap_or(false);
+    let digitizer = hardware.usb.digitizer.unwrap_or(false);
+    if poll_interval == 0 {
+        panic!("keyboard.toml: [usb.poll_interval] must be between 1 and 255 ms, got 0");
+    }
+    quote! {
+        usb_config: ::rmk::config::UsbConfig {
+            mouse: #mouse,
+            consumer: #consumer,
+            system_control: #system_control,
+            poll_interval_ms: #poll_interval,
+            battery: #battery,
+            nkro: #nkro,
+            digitizer: #digitizer,
+        },
+    }
+}
+
 /// Default implementation of usb initialization
 pub(crate) fn usb_config_default(hardware: &Hardware) -> TokenStream2 {
     if let Some(usb_info) = hardware.communication.get_usb_info() {
@@ -21,10 +21,9 @@ pub(crate) fn expand_flash_init(hardware: &Hardware) -> TokenStream2 {
     let clear_layout = storage.clear_layout;
     let mut flash_init = quote! {
         let storage_config = ::rmk::config::StorageConfig {
-            num_sectors: #num_sectors,
-            start_addr: #start_addr,
             clear_storage: #clear_storage,
-            clear_layout: #clear_layout
+            clear_layout: #clear_layout,
+            ..::rmk::config::StorageConfig::new(#start_addr, #num_sectors)
         };
     };
     flash_init.extend(
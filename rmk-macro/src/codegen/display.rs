@@ -1,7 +1,8 @@
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use rmk_config::resolved::hardware::{
-    ChipSeries, CommunicationProtocol, DisplayConfig, DisplayDriver, I2cConfig,
+    ChipSeries, CommunicationProtocol, DisplayConfig, DisplayDriver, DisplayWidgetConfig,
+    DisplayWidgetKind, I2cConfig,
 };
 
 use super::input_device::Initializer;
@@ -168,7 +169,18 @@ fn expand_display_driver_init(config: &DisplayConfig) -> TokenStream {
 }
 
 fn expand_display_processor_init(config: &DisplayConfig) -> TokenStream {
-    let constructor = if let Some(renderer_path) = &config.renderer {
+    let constructor = if let Some(widgets) = &config.widgets {
+        if config.renderer.is_some() {
+            panic!("Display `renderer` and `widgets` can't be used together");
+        }
+        let widgets = widgets.iter().map(expand_widget);
+        quote! {
+            ::rmk::display::DisplayProcessor::with_renderer(
+                display,
+                ::rmk::display::WidgetRenderer::new(&[#(#widgets),*]),
+            )
+        }
+    } else if let Some(renderer_path) = &config.renderer {
         // Allow bare names like "OledRenderer" as shorthand for "::rmk::display::OledRenderer".
         let full_path = if renderer_path.contains("::") {
             renderer_path.clone()
@@ -208,6 +220,32 @@ fn expand_display_processor_init(config: &DisplayConfig) -> TokenStream {
     }
 }
 
+fn expand_widget(config: &DisplayWidgetConfig) -> TokenStream {
+    let widget = match config.widget {
+        DisplayWidgetKind::Layer => quote! { Layer },
+        DisplayWidgetKind::Wpm => quote! { Wpm },
+        DisplayWidgetKind::Modifiers => quote! { Modifiers },
+        DisplayWidgetKind::LockKeys => quote! { LockKeys },
+        DisplayWidgetKind::Battery => quote! { Battery },
+        DisplayWidgetKind::Connection => quote! { Connection },
+        DisplayWidgetKind::Text => {
+            let text = config
+                .text
+                .as_ref()
+                .unwrap_or_else(|| panic!("Display `text` widget needs a `text` field"));
+            quote! { Text(#text) }
+        }
+    };
+    let (x, y) = (config.x, config.y);
+    quote! {
+        ::rmk::display::PlacedWidget {
+            widget: ::rmk::display::Widget::#widget,
+            x: #x,
+            y: #y,
+        }
+    }
+}
+
 fn parse_ssd1306_size(size: &str) -> TokenStream {
     match size {
         "128x64" => quote! { ::rmk::display::ssd1306::prelude::DisplaySize128x64 },
@@ -1,11 +1,14 @@
 use darling::FromMeta;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote};
+#[cfg(feature = "interrupt_executor")]
+use rmk_config::resolved::hardware::ChipSeries;
 use rmk_config::resolved::hardware::{BoardConfig, CommunicationConfig};
 use rmk_config::resolved::{Hardware, Host};
 use syn::{ItemFn, ItemMod};
 
 use super::override_helper::Overwritten;
+use super::registered_processor::RegisteredProcessors;
 
 pub(crate) fn expand_rmk_entry(
     hardware: &Hardware,
@@ -13,7 +16,7 @@ pub(crate) fn expand_rmk_entry(
     item_mod: &ItemMod,
     devices: Vec<TokenStream2>,
     processors: Vec<TokenStream2>,
-    registered_processors: Vec<TokenStream2>,
+    registered_processors: RegisteredProcessors,
     watchdog_task: Option<TokenStream2>,
 ) -> TokenStream2 {
     // If there is a function with `#[Overwritten(entry)]`, override the entry
@@ -61,15 +64,12 @@ pub(crate) fn rmk_entry_select(
     host: &Host,
     devices: Vec<TokenStream2>,
     processors: Vec<TokenStream2>,
-    registered_processors: Vec<TokenStream2>,
+    registered_processors: RegisteredProcessors,
     watchdog_task: Option<TokenStream2>,
 ) -> TokenStream2 {
     let devices_task = {
         let mut devs = devices.clone();
         devs.push(quote! {matrix});
-        if hardware.storage.is_some() {
-            devs.push(quote! {storage});
-        }
         quote! {
             ::rmk::run_all! (
                 #(#devs),*
@@ -86,11 +86,22 @@ pub(crate) fn rmk_entry_select(
         }
     };
 
-    let host_service_task = if host.vial_enabled {
-        Some(quote! { host_service.run() })
-    } else {
-        None
-    };
+    // The host service and the processors registered with `critical` run with the key processing
+    let mut service_tasks = registered_processors.critical;
+    if host.vial_enabled {
+        service_tasks.push(quote! { host_service.run() });
+    }
+
+    // Tasks which don't take part in the key processing, see `rmk::priority`
+    let mut background_tasks = vec![quote! { wpm_processor.run() }];
+    if hardware.storage.is_some() {
+        background_tasks.push(quote! { storage.run() });
+    }
+    background_tasks.extend(registered_processors.background);
+    if let Some(t) = watchdog_task {
+        background_tasks.push(t);
+    }
+
     let board = &hardware.board;
     let communication = &hardware.communication;
     let (transport_prelude, transport_tasks) = transport_setup(communication);
@@ -101,14 +112,8 @@ pub(crate) fn rmk_entry_select(
                 keyboard.run(),
             };
             let mut tasks = vec![devices_task, keyboard_task];
-            tasks.extend(registered_processors);
-            if let Some(t) = host_service_task {
-                tasks.push(t);
-            }
+            tasks.extend(service_tasks);
             tasks.extend(transport_tasks);
-            if let Some(t) = &watchdog_task {
-                tasks.push(t.clone());
-            }
             if split_config.connection == "ble" {
                 if !processors.is_empty() {
                     tasks.push(processors_task);
@@ -130,10 +135,10 @@ pub(crate) fn rmk_entry_select(
                     ::rmk::split::ble::central::scan_peripherals(&stack, &peripheral_addrs)
                 };
                 tasks.push(scan_task);
-                let joined = join_all_tasks(tasks);
+                let run = run_prioritized(hardware, tasks, background_tasks);
                 quote! {
                     #transport_prelude
-                    #joined
+                    #run
                 }
             } else if split_config.connection == "serial" {
                 if !processors.is_empty() {
@@ -149,25 +154,24 @@ pub(crate) fn rmk_entry_select(
                     let col = p.cols;
                     let row_offset = p.row_offset;
                     let col_offset = p.col_offset;
-                    let uart_instance = format_ident!(
-                        "{}",
-                        central_serials
-                            .get(idx)
-                            .expect("No or not enough serial defined for peripheral in central")
-                            .instance
-                            .to_lowercase()
-                    );
+                    let serial = central_serials
+                        .get(idx)
+                        .expect("No or not enough serial defined for peripheral in central");
+                    let uart_instance = format_ident!("{}", serial.instance.to_lowercase());
+                    // A single-wire link needs ACKs and retransmits
+                    let manager = if serial.tx_pin == serial.rx_pin {
+                        quote! { run_half_duplex_peripheral_manager }
+                    } else {
+                        quote! { run_peripheral_manager }
+                    };
                     tasks.push(quote! {
-                        ::rmk::split::central::run_peripheral_manager::<#row, #col, #row_offset, #col_offset, _>(
-                            #idx,
-                            #uart_instance,
-                        )
+                        ::rmk::split::central::#manager::<#row, #col, #row_offset, #col_offset, _>(#idx, #uart_instance)
                     });
                 });
-                let joined = join_all_tasks(tasks);
+                let run = run_prioritized(hardware, tasks, background_tasks);
                 quote! {
                     #transport_prelude
-                    #joined
+                    #run
                 }
             } else {
                 panic!(
@@ -177,82 +181,164 @@ pub(crate) fn rmk_entry_select(
             }
         }
         BoardConfig::UniBody(_) => rmk_entry_unibody(
+            hardware,
             transport_prelude,
             transport_tasks,
-            host_service_task,
+            service_tasks,
             devices_task,
             processors_task,
-            registered_processors,
-            watchdog_task,
+            background_tasks,
         ),
     };
 
     quote! {
         use ::rmk::core_traits::Runnable;
+        let mut wpm_processor = ::rmk::processor::builtin::wpm::WpmProcessor::new();
         #entry
     }
 }
 
 pub(crate) fn rmk_entry_unibody(
+    hardware: &Hardware,
     transport_prelude: TokenStream2,
     transport_tasks: Vec<TokenStream2>,
-    host_service_task: Option<TokenStream2>,
+    service_tasks: Vec<TokenStream2>,
     devices_task: TokenStream2,
     processors_task: TokenStream2,
-    registered_processors: Vec<TokenStream2>,
-    watchdog_task: Option<TokenStream2>,
+    background_tasks: Vec<TokenStream2>,
 ) -> TokenStream2 {
     let keyboard_task = quote! {
         keyboard.run()
     };
 
     let mut tasks = vec![devices_task, keyboard_task];
-    if let Some(t) = host_service_task {
-        tasks.push(t);
-    }
+    tasks.extend(service_tasks);
     if !processors_task.is_empty() {
         tasks.push(processors_task);
     }
-    tasks.extend(registered_processors);
     tasks.extend(transport_tasks);
-    if let Some(t) = watchdog_task {
-        tasks.push(t);
-    }
-    let joined = join_all_tasks(tasks);
+    let run = run_prioritized(hardware, tasks, background_tasks);
     quote! {
         #transport_prelude
-        #joined
+        #run
     }
 }
 
+/// Run the critical tasks before the background tasks, on the interrupt executor from
+/// [`expand_critical_executor`] with the `interrupt_executor` feature.
+#[cfg(feature = "interrupt_executor")]
+fn run_prioritized(
+    hardware: &Hardware,
+    critical_tasks: Vec<TokenStream2>,
+    background_tasks: Vec<TokenStream2>,
+) -> TokenStream2 {
+    let critical = expand_tasks(critical_tasks);
+    let background = expand_tasks(background_tasks);
+    let (hal, irq, priority) = critical_executor_interrupt(hardware);
+    quote! {
+        // SAFETY: main never ends, and embassy never drops it
+        unsafe {
+            ::rmk::priority::run_prioritized(#critical, #background, |tasks| {
+                use ::#hal::interrupt::InterruptExt;
+                ::#hal::interrupt::#irq.set_priority(::#hal::interrupt::Priority::#priority);
+                let spawner = critical_executor::EXECUTOR_CRITICAL.start(::#hal::interrupt::#irq);
+                spawner.spawn(critical_executor::critical_task(tasks).unwrap());
+            })
+        }
+        .await
+    }
+}
+
+/// Run the critical tasks before the background tasks, on the current executor.
+#[cfg(not(feature = "interrupt_executor"))]
+fn run_prioritized(
+    _hardware: &Hardware,
+    critical_tasks: Vec<TokenStream2>,
+    background_tasks: Vec<TokenStream2>,
+) -> TokenStream2 {
+    let critical = expand_tasks(critical_tasks);
+    let background = expand_tasks(background_tasks);
+    quote! {
+        ::rmk::priority::run_prioritized(#critical, #background).await
+    }
+}
+
+/// The interrupt executor which runs the critical tasks, see `rmk::priority`.
+///
+/// It's woken by a software interrupt at the lowest interrupt priority, which preempts the
+/// background tasks on the thread executor. Needs the `executor-interrupt` feature of
+/// `embassy-executor`.
+#[cfg(feature = "interrupt_executor")]
+pub(crate) fn expand_critical_executor(hardware: &Hardware) -> TokenStream2 {
+    let (hal, irq, _) = critical_executor_interrupt(hardware);
+    quote! {
+        mod critical_executor {
+            // `#[interrupt]` checks the handler name against `interrupt`
+            use ::#hal::interrupt;
+
+            pub(super) static EXECUTOR_CRITICAL: ::embassy_executor::InterruptExecutor =
+                ::embassy_executor::InterruptExecutor::new();
+
+            #[interrupt]
+            unsafe fn #irq() {
+                unsafe { EXECUTOR_CRITICAL.on_interrupt() }
+            }
+
+            #[::embassy_executor::task]
+            pub(super) async fn critical_task(tasks: ::rmk::priority::CriticalTasks) {
+                tasks.await
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "interrupt_executor"))]
+pub(crate) fn expand_critical_executor(_hardware: &Hardware) -> TokenStream2 {
+    quote! {}
+}
+
+/// (HAL crate, software interrupt, lowest interrupt priority) of the critical executor
+#[cfg(feature = "interrupt_executor")]
+fn critical_executor_interrupt(hardware: &Hardware) -> (syn::Ident, syn::Ident, syn::Ident) {
+    let (hal, irq, priority) = match hardware.chip.series {
+        // EGU0_SWI0 is taken by the MPSL of the BLE stack
+        ChipSeries::Nrf52 if hardware.chip.chip.starts_with("nrf52") => {
+            ("embassy_nrf", "EGU1_SWI1", "P7")
+        }
+        ChipSeries::Rp2040 => ("embassy_rp", "SWI_IRQ_1", "P3"),
+        _ => panic!(
+            "The \"interrupt_executor\" feature is only supported on nRF52 and RP2040, disable it for {}",
+            hardware.chip.chip
+        ),
+    };
+    (
+        format_ident!("{}", hal),
+        format_ident!("{}", irq),
+        format_ident!("{}", priority),
+    )
+}
+
 /// Build (`let mut transport = ...;` prelude, transport `.run()` tasks) for the
 /// active communication config. The prelude must be emitted before the join so
 /// that `transport.run()` can borrow each transport for the lifetime of the
 /// program.
 fn transport_setup(communication: &CommunicationConfig) -> (TokenStream2, Vec<TokenStream2>) {
-    let wpm_prelude = quote! {
-        let mut wpm_processor = ::rmk::processor::builtin::wpm::WpmProcessor::new();
-    };
-    let wpm_task = quote! { wpm_processor.run() };
     match communication {
         CommunicationConfig::Usb(_) => {
             let prelude = quote! {
-                #wpm_prelude
-                let mut usb_transport = ::rmk::usb::UsbTransport::new(driver, rmk_config.device_config);
+                let mut usb_transport = ::rmk::usb::UsbTransport::with_config(driver, rmk_config.device_config, rmk_config.usb_config);
             };
-            (prelude, vec![quote! { usb_transport.run() }, wpm_task])
+            (prelude, vec![quote! { usb_transport.run() }])
         }
         CommunicationConfig::Ble(_) => {
             let prelude = quote! {
-                #wpm_prelude
                 let mut ble_transport = ::rmk::ble::BleTransport::new(&stack, rmk_config).await;
             };
-            (prelude, vec![quote! { ble_transport.run() }, wpm_task])
+            (prelude, vec![quote! { ble_transport.run() }])
         }
         CommunicationConfig::Both(_, _) => {
             let prelude = quote! {
-                #wpm_prelude
-                let mut usb_transport = ::rmk::usb::UsbTransport::new(driver, rmk_config.device_config);
+                let mut usb_transport = ::rmk::usb::UsbTransport::with_config(driver, rmk_config.device_config, rmk_config.usb_config);
                 let mut ble_transport = ::rmk::ble::BleTransport::new(&stack, rmk_config).await;
             };
             (
@@ -260,7 +346,6 @@ fn transport_setup(communication: &CommunicationConfig) -> (TokenStream2, Vec<To
                 vec![
                     quote! { usb_transport.run() },
                     quote! { ble_transport.run() },
-                    wpm_task,
                 ],
             )
         }
@@ -1,6 +1,8 @@
 //! Utilities of check cargo feature
 //!
 
+use rmk_config::resolved::hardware::InputDeviceConfig;
+
 /// Get enabled RMK features list
 pub(crate) fn get_rmk_features() -> Option<Vec<String>> {
     // Use an absolute path. `cargo_toml::Manifest::from_path` resolves the
@@ -31,6 +33,9 @@ pub(crate) fn get_rmk_features() -> Option<Vec<String>> {
                     feature_set.push("storage".to_string());
                     feature_set.push("vial".to_string());
                     feature_set.push("vial_lock".to_string());
+                    feature_set.push("combo".to_string());
+                    feature_set.push("macros".to_string());
+                    feature_set.push("pointing".to_string());
                 }
                 feature_set
             }),
@@ -49,3 +54,34 @@ pub(crate) fn is_feature_enabled(feature_list: &Option<Vec<String>>, feature: &s
     }
     false
 }
+
+/// Whether `input_device` has a device whose driver is behind the `pointing` feature
+pub(crate) fn uses_pointing_driver(input_device: &InputDeviceConfig) -> bool {
+    input_device
+        .joystick
+        .as_ref()
+        .is_some_and(|d| !d.is_empty())
+        || input_device.pmw3610.as_ref().is_some_and(|d| !d.is_empty())
+        || input_device.pmw33xx.as_ref().is_some_and(|d| !d.is_empty())
+        || input_device.iqs5xx.as_ref().is_some_and(|d| !d.is_empty())
+}
+
+/// Check that the features needed by keyboard.toml are enabled.
+///
+/// Each entry of `used` is a feature, the part of keyboard.toml which needs it, and whether that part
+/// is set. Without their feature, RMK ignores combos, macros and pointing devices, which is easy to
+/// miss after `default-features = false`.
+pub(crate) fn validate_feature_usage(
+    feature_list: &Option<Vec<String>>,
+    used: &[(&str, &str, bool)],
+) -> Result<(), String> {
+    match used
+        .iter()
+        .find(|(feature, _, used)| *used && !is_feature_enabled(feature_list, feature))
+    {
+        Some((feature, config, _)) => Err(format!(
+            "{config} in keyboard.toml needs the \"{feature}\" Cargo feature of rmk, enable it in Cargo.toml (it's a default feature, so it's off with `default-features = false`)."
+        )),
+        None => Ok(()),
+    }
+}
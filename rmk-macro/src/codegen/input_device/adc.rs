@@ -22,9 +22,9 @@ pub(crate) fn expand_adc_device(
             let mut devices = vec![];
             let mut processors = vec![];
 
-            if let Some(ble) = ble_config
+            if let Some(ble) = &ble_config
                 && ble.enabled
-                && let Some(adc_pin) = ble.battery_adc_pin
+                && let Some(adc_pin) = ble.battery_adc_pin.clone()
             {
                 let adc_pin_def = if adc_pin == "vddh" {
                     quote! {
@@ -61,6 +61,49 @@ pub(crate) fn expand_adc_device(
                 processors.push(battery_processor);
             }
 
+            if let Some(ble) = &ble_config
+                && ble.enabled
+            {
+                if let Some(charge_state) = &ble.charge_state {
+                    let pin = format_ident!("{}", charge_state.pin);
+                    let low_active = charge_state.low_active;
+                    let pull = if low_active {
+                        quote! { ::embassy_nrf::gpio::Pull::Up }
+                    } else {
+                        quote! { ::embassy_nrf::gpio::Pull::Down }
+                    };
+                    let ident = format_ident!("charging_state_reader");
+                    devices.push(Initializer {
+                        initializer: quote! {
+                            let mut #ident = ::rmk::input_device::battery::ChargingStateReader::new(
+                                ::embassy_nrf::gpio::Input::new(p.#pin, #pull),
+                                #low_active,
+                            );
+                        },
+                        var_name: ident,
+                    });
+                }
+                if let Some(charge_led) = &ble.charge_led {
+                    let pin = format_ident!("{}", charge_led.pin);
+                    let low_active = charge_led.low_active;
+                    let default_level = if low_active {
+                        quote! { ::embassy_nrf::gpio::Level::High }
+                    } else {
+                        quote! { ::embassy_nrf::gpio::Level::Low }
+                    };
+                    let ident = format_ident!("battery_led_processor");
+                    processors.push(Initializer {
+                        initializer: quote! {
+                            let mut #ident = ::rmk::processor::builtin::battery_led::BatteryLedProcessor::new(
+                                ::embassy_nrf::gpio::Output::new(p.#pin, #default_level, ::embassy_nrf::gpio::OutputDrive::Standard),
+                                #low_active,
+                            );
+                        },
+                        var_name: ident,
+                    });
+                }
+            }
+
             // polling interval with joystick
             if !joystick_config.is_empty() {
                 default_polling_interval = 20;
@@ -102,7 +145,7 @@ pub(crate) fn expand_adc_device(
                 processors.push(joystick_processor);
             }
 
-            if !processors.is_empty() {
+            if !channel_cfg.is_empty() {
                 let light_sleep_option = if let Some(light_sleep_interval) = light_sleep {
                     quote! {Some(Duration::from_millis(#light_sleep_interval as u64))}
                 } else {
@@ -134,10 +177,8 @@ pub(crate) fn expand_adc_device(
                     var_name: format_ident!("adc_device"),
                 };
                 devices.push(adc_device);
-                (devices, processors)
-            } else {
-                (Vec::new(), Vec::new())
             }
+            (devices, processors)
         }
         _ => (Vec::new(), Vec::new()),
     }
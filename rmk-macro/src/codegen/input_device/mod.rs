@@ -77,6 +77,8 @@ pub(crate) fn expand_input_device_config(
                     battery_adc_pin: split_config.central.battery_adc_pin.clone(),
                     adc_divider_measured: split_config.central.adc_divider_measured,
                     adc_divider_total: split_config.central.adc_divider_total,
+                    charge_state: ble_config.as_ref().and_then(|c| c.charge_state.clone()),
+                    charge_led: ble_config.as_ref().and_then(|c| c.charge_led.clone()),
                     ..Default::default()
                 })
             } else {
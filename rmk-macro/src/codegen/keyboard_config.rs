@@ -1,3 +1,5 @@
+use std::io::Read;
+
 use quote::quote;
 use rmk_config::KeyboardTomlConfig;
 use rmk_config::resolved::{Host, Identity, Layout};
@@ -19,6 +21,7 @@ pub(crate) fn expand_keyboard_info(
     let product_name = identity.product_name.clone();
     let manufacturer = identity.manufacturer.clone();
     let serial_number = identity.serial_number.clone();
+    let serial_from_chip_id = identity.serial_from_chip_id;
 
     let num_col = layout.cols as usize;
     let num_row = layout.rows as usize;
@@ -36,11 +39,15 @@ pub(crate) fn expand_keyboard_info(
             manufacturer: #manufacturer,
             product_name: #product_name,
             serial_number: #serial_number,
+            serial_from_chip_id: #serial_from_chip_id,
         };
     }
 }
 
-pub(crate) fn expand_vial_config(host: &Host) -> proc_macro2::TokenStream {
+pub(crate) fn expand_vial_config(
+    host: &Host,
+    keyboard_config: &KeyboardTomlConfig,
+) -> proc_macro2::TokenStream {
     if !host.vial_enabled {
         return quote! {};
     }
@@ -59,8 +66,9 @@ pub(crate) fn expand_vial_config(host: &Host) -> proc_macro2::TokenStream {
         quote! { &[] }
     };
     let vial_insecure = host.vial_insecure;
+    let vial_keyboard_def = expand_vial_keyboard_def(keyboard_config);
     quote! {
-        include!(concat!(env!("OUT_DIR"), "/config_generated.rs"));
+        #vial_keyboard_def
         const VIAL_CONFIG: ::rmk::config::VialConfig = ::rmk::config::VialConfig {
             vial_keyboard_id: &VIAL_KEYBOARD_ID,
             vial_keyboard_def: &VIAL_KEYBOARD_DEF,
@@ -69,3 +77,53 @@ pub(crate) fn expand_vial_config(host: &Host) -> proc_macro2::TokenStream {
         };
     }
 }
+
+/// `VIAL_KEYBOARD_DEF` and `VIAL_KEYBOARD_ID`.
+///
+/// A `vial.json` next to `Cargo.toml` is compressed by the build script of the project into
+/// `config_generated.rs`. Without it, the definition is generated from `keyboard.toml`, so it
+/// can't get out of sync with the firmware.
+fn expand_vial_keyboard_def(keyboard_config: &KeyboardTomlConfig) -> proc_macro2::TokenStream {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    if std::path::Path::new(&manifest_dir)
+        .join("vial.json")
+        .exists()
+    {
+        return quote! {
+            include!(concat!(env!("OUT_DIR"), "/config_generated.rs"));
+        };
+    }
+
+    let definition = keyboard_config
+        .vial_definition()
+        .unwrap_or_else(|e| panic!("\n\u{274c} Failed to generate the Vial definition: {e}"));
+    let mut compressed = Vec::new();
+    xz2::read::XzEncoder::new(definition.as_bytes(), 6)
+        .read_to_end(&mut compressed)
+        .expect("Failed to compress the Vial definition");
+
+    // Vial tells keyboards apart by their id, so derive it from what identifies the keyboard
+    let identity = keyboard_config
+        .identity()
+        .expect("failed to resolve identity config");
+    let keyboard_id = fnv1a(
+        format!(
+            "{}:{}:{}",
+            identity.vendor_id, identity.product_id, identity.name
+        )
+        .as_bytes(),
+    )
+    .to_le_bytes();
+    let def_len = compressed.len();
+    quote! {
+        const VIAL_KEYBOARD_DEF: [u8; #def_len] = [#(#compressed), *];
+        const VIAL_KEYBOARD_ID: [u8; 8] = [#(#keyboard_id), *];
+    }
+}
+
+/// 64-bit FNV-1a hash, which doesn't change between Rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
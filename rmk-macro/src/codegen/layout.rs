@@ -3,10 +3,11 @@ use std::collections::HashMap;
 
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
+use rmk_config::KeyboardTomlConfig;
 use rmk_config::resolved::behavior::MorseProfile;
 use rmk_config::resolved::{Behavior, Layout};
 
-use super::action_parser::parse_key;
+use super::action_parser::{parse_key, unknown_key_names};
 
 /// Read the default keymap setting in `keyboard.toml` and add as a `get_default_keymap` function
 /// Also add `get_default_encoder_map`
@@ -49,6 +50,50 @@ pub(crate) fn expand_default_keymap(layout: &Layout, behavior: &Behavior) -> Tok
     }
 }
 
+/// Check that every key of the keymap and the encoder map has a valid key name
+pub(crate) fn validate_key_names(
+    layout: &Layout,
+    behavior: &Behavior,
+    config: &KeyboardTomlConfig,
+) -> Result<(), String> {
+    let profiles: Option<HashMap<String, MorseProfile>> = behavior
+        .morse
+        .as_ref()
+        .map(|m| m.profiles.clone())
+        .filter(|p| !p.is_empty());
+
+    let mut errors = vec![];
+    let mut check = |key: &String, layer: usize| {
+        for (name, suggestion) in unknown_key_names(&parse_key(key.clone(), &profiles)) {
+            let hint = suggestion
+                .map(|s| format!(", did you mean `{s}`?"))
+                .unwrap_or_default();
+            errors.push(format!(
+                "{}: `{}` in layer {} is not a valid key name{}",
+                config.source_location(&name, 0),
+                name,
+                layer,
+                hint
+            ));
+        }
+    };
+    for (i, layer) in layout.keymap.iter().enumerate() {
+        layer.iter().flatten().for_each(|key| check(key, i));
+    }
+    for (i, layer) in layout.encoder_map.iter().enumerate() {
+        layer.iter().flatten().for_each(|key| check(key, i));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "\n\u{274c} keyboard.toml is invalid:\n{}",
+            errors.join("\n")
+        ))
+    }
+}
+
 /// Expand a layer for keymap
 fn expand_layer(
     layer: Vec<Vec<String>>,
@@ -111,18 +111,70 @@ pub(crate) fn expand_matrix_direct_pins(
 /// the dimensions inferred from the matrix configuration itself, so callers
 /// don't need to pass them.
 pub(crate) fn expand_bootmagic_check(matrix: &MatrixConfig) -> proc_macro2::TokenStream {
-    let Some(bm) = matrix.bootmagic else {
+    let Some(key) = matrix.bootmagic else {
         return quote! {};
     };
-    let row = bm.0 as usize;
-    let col = bm.1 as usize;
+    let held = expand_held_key(matrix, key, "bootmagic");
+    quote! {
+        // Bootmagic: drop into the bootloader if the configured
+        // key is held during boot.
+        if #held {
+            ::rmk::boot::jump_to_bootloader();
+        }
+    }
+}
+
+/// Emit the bootmagic scans which recover from a bad stored configuration:
+/// `bootmagic_reset` clears the storage and `bootmagic_safe_mode` boots with
+/// the default keymap, ignoring the storage.
+///
+/// The emitted code shadows `storage_config`, so it must be inserted after
+/// the flash init, with the same pin requirements as [`expand_bootmagic_check`].
+pub(crate) fn expand_storage_bootmagic_check(
+    matrix: &MatrixConfig,
+    has_storage: bool,
+) -> proc_macro2::TokenStream {
+    if matrix.bootmagic_reset.is_none() && matrix.bootmagic_safe_mode.is_none() {
+        return quote! {};
+    }
+    if !has_storage {
+        panic!("bootmagic_reset and bootmagic_safe_mode need the storage to be enabled");
+    }
+    let clear_storage = matrix.bootmagic_reset.map(|key| {
+        let held = expand_held_key(matrix, key, "bootmagic_reset");
+        quote! { clear_storage: storage_config.clear_storage || #held, }
+    });
+    let safe_mode = matrix.bootmagic_safe_mode.map(|key| {
+        let held = expand_held_key(matrix, key, "bootmagic_safe_mode");
+        quote! { safe_mode: storage_config.safe_mode || #held, }
+    });
+    quote! {
+        // Bootmagic: clear or ignore the storage if the configured
+        // key is held during boot.
+        let storage_config = ::rmk::config::StorageConfig {
+            #clear_storage
+            #safe_mode
+            ..storage_config
+        };
+    }
+}
+
+/// Emit an expression which is `true` if the key at `(row, col)` is held,
+/// `name` is the config key reported when the position is invalid.
+fn expand_held_key(
+    matrix: &MatrixConfig,
+    (row, col): (u8, u8),
+    name: &str,
+) -> proc_macro2::TokenStream {
+    let row = row as usize;
+    let col = col as usize;
     match matrix.matrix_type {
         MatrixType::Normal => {
             let num_rows = matrix.row_pins.as_deref().map_or(0, <[_]>::len);
             let num_cols = matrix.col_pins.as_deref().map_or(0, <[_]>::len);
             if row >= num_rows || col >= num_cols {
                 panic!(
-                    "bootmagic key ({row}, {col}) is out of range for matrix {num_rows}×{num_cols}"
+                    "{name} key ({row}, {col}) is out of range for matrix {num_rows}×{num_cols}"
                 );
             }
             let (output_pins, output_idx, input_pins, input_idx) = if matrix.row2col {
@@ -132,15 +184,11 @@ pub(crate) fn expand_bootmagic_check(matrix: &MatrixConfig) -> proc_macro2::Toke
             };
             quote! {
                 {
-                    // Bootmagic: drop into the bootloader if the configured
-                    // key is held during boot.
                     #output_pins[#output_idx].set_high();
                     ::embassy_time::Timer::after_micros(50).await;
-                    let bootmagic_pressed = #input_pins[#input_idx].is_high();
+                    let pressed = #input_pins[#input_idx].is_high();
                     #output_pins[#output_idx].set_low();
-                    if bootmagic_pressed {
-                        ::rmk::boot::jump_to_bootloader();
-                    }
+                    pressed
                 }
             }
         }
@@ -150,9 +198,9 @@ pub(crate) fn expand_bootmagic_check(matrix: &MatrixConfig) -> proc_macro2::Toke
                 .as_deref()
                 .and_then(|pins| pins.get(row))
                 .and_then(|r| r.get(col))
-                .is_none_or(|name| name == "_" || name.eq_ignore_ascii_case("trns"))
+                .is_none_or(|pin| pin == "_" || pin.eq_ignore_ascii_case("trns"))
             {
-                panic!("bootmagic cell ({row}, {col}) has no pin assigned")
+                panic!("{name} cell ({row}, {col}) has no pin assigned")
             }
             let pressed_call = if matrix.direct_pin_low_active {
                 quote! { pin.is_low() }
@@ -160,15 +208,7 @@ pub(crate) fn expand_bootmagic_check(matrix: &MatrixConfig) -> proc_macro2::Toke
                 quote! { pin.is_high() }
             };
             quote! {
-                {
-                    // Bootmagic: drop into the bootloader if the configured
-                    // key is held during boot.
-                    if let Some(ref pin) = direct_pins[#row][#col] {
-                        if #pressed_call {
-                            ::rmk::boot::jump_to_bootloader();
-                        }
-                    }
-                }
+                direct_pins[#row][#col].as_ref().is_some_and(|pin| #pressed_call)
             }
         }
     }
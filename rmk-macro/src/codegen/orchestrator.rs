@@ -1,25 +1,29 @@
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
+use rmk_config::KeyboardTomlConfig;
 use rmk_config::resolved::hardware::{
     BoardConfig, ChipSeries, KeyInfo, MatrixConfig, MatrixType, UniBodyConfig,
 };
 use rmk_config::resolved::{Behavior, Hardware, Host, Identity, Layout};
 
+use super::action_parser::expand_profile;
 use super::behavior::expand_behavior_config;
 use super::chip::bind_interrupt::expand_bind_interrupt;
 use super::chip::ble::expand_ble_config;
 use super::chip::chip_init::expand_chip_init;
-use super::chip::comm::expand_usb_init;
+use super::chip::comm::{expand_usb_config, expand_usb_init};
 use super::chip::flash::expand_flash_init;
 use super::chip::gpio::expand_output_config;
 use super::display::expand_display_config;
-use super::entry::expand_rmk_entry;
-use super::feature::{get_rmk_features, is_feature_enabled};
+use super::entry::{expand_critical_executor, expand_rmk_entry};
+use super::feature::{
+    get_rmk_features, is_feature_enabled, uses_pointing_driver, validate_feature_usage,
+};
 use super::import::expand_custom_imports;
 use super::input_device::expand_input_device_config;
 use super::keyboard_config::{expand_keyboard_info, expand_vial_config, read_keyboard_toml_config};
-use super::layout::expand_default_keymap;
-use super::matrix::{expand_bootmagic_check, expand_matrix_config};
+use super::layout::{expand_default_keymap, validate_key_names};
+use super::matrix::{expand_bootmagic_check, expand_matrix_config, expand_storage_bootmagic_check};
 use super::registered_processor::expand_registered_processor_init;
 use super::split::central::expand_split_central_config;
 use super::watchdog::expand_watchdog_init;
@@ -29,6 +33,9 @@ pub(crate) fn parse_keyboard_mod(item_mod: syn::ItemMod) -> TokenStream2 {
     let rmk_features = get_rmk_features();
 
     let keyboard_config = read_keyboard_toml_config();
+    keyboard_config
+        .validate()
+        .unwrap_or_else(|err| panic!("{err}"));
 
     // Resolve types from keyboard.toml
     let identity = keyboard_config
@@ -44,6 +51,7 @@ pub(crate) fn parse_keyboard_mod(item_mod: syn::ItemMod) -> TokenStream2 {
     let layout = keyboard_config
         .layout()
         .expect("failed to resolve layout config");
+    validate_key_names(&layout, &behavior, &keyboard_config).unwrap_or_else(|err| panic!("{err}"));
 
     validate_feature_config_parity(
         hardware.storage.is_some(),
@@ -53,9 +61,60 @@ pub(crate) fn parse_keyboard_mod(item_mod: syn::ItemMod) -> TokenStream2 {
     )
     .unwrap_or_else(|err| panic!("{err}"));
 
+    let pointing_used = match &hardware.board {
+        BoardConfig::UniBody(UniBodyConfig { input_device, .. }) => {
+            uses_pointing_driver(input_device)
+        }
+        BoardConfig::Split(split) => split
+            .central
+            .input_device
+            .iter()
+            .chain(
+                split
+                    .peripheral
+                    .iter()
+                    .filter_map(|p| p.input_device.as_ref()),
+            )
+            .any(uses_pointing_driver),
+    };
+    if let Err(err) = validate_feature_usage(
+        &rmk_features,
+        &[
+            (
+                "combo",
+                "`[behavior.combo]`",
+                behavior
+                    .combos
+                    .as_ref()
+                    .is_some_and(|c| !c.combos.is_empty()),
+            ),
+            (
+                "macros",
+                "`[behavior.macro]`",
+                behavior
+                    .macros
+                    .as_ref()
+                    .is_some_and(|m| !m.macros.is_empty()),
+            ),
+            (
+                "pointing",
+                "A joystick, PMW3610, PMW33xx or IQS5xx",
+                pointing_used,
+            ),
+        ],
+    ) {
+        return quote! { compile_error!(#err); };
+    }
+
     // Generate imports and statics
-    let imports_and_statics =
-        expand_imports_and_constants(&identity, &host, &hardware, &behavior, &layout);
+    let imports_and_statics = expand_imports_and_constants(
+        &keyboard_config,
+        &identity,
+        &host,
+        &hardware,
+        &behavior,
+        &layout,
+    );
 
     // Generate main function body
     let main_function = expand_main(
@@ -106,6 +165,7 @@ fn validate_feature_config_parity(
 }
 
 pub(crate) fn expand_imports_and_constants(
+    keyboard_config: &KeyboardTomlConfig,
     identity: &Identity,
     host: &Host,
     hardware: &Hardware,
@@ -117,7 +177,7 @@ pub(crate) fn expand_imports_and_constants(
     // Generate default keymap
     let default_keymap = expand_default_keymap(layout, behavior);
     // Generate vial config
-    let vial_static_var = expand_vial_config(host);
+    let vial_static_var = expand_vial_config(host, keyboard_config);
 
     // Generate extra imports, panic handler and logger
     let imports = match hardware.chip.series {
@@ -163,6 +223,7 @@ pub(crate) fn expand_imports_and_constants(
 #[cfg(test)]
 mod tests {
     use super::validate_feature_config_parity;
+    use crate::codegen::feature::validate_feature_usage;
 
     #[test]
     fn accepts_matching_storage_and_vial_feature_states() {
@@ -206,6 +267,28 @@ mod tests {
             "`host.vial_enabled = false` in keyboard.toml requires disabling the \"vial\" Cargo feature for rmk in Cargo.toml (for example with `default-features = false` and explicitly re-enabling the features you need)."
         );
     }
+
+    #[test]
+    fn rejects_combos_without_feature() {
+        let features = Some(vec!["vial".to_string(), "macros".to_string()]);
+        let used = [
+            ("combo", "`[behavior.combo]`", true),
+            ("macros", "`[behavior.macro]`", true),
+        ];
+        let err = validate_feature_usage(&features, &used).unwrap_err();
+        assert!(
+            err.starts_with(
+                "`[behavior.combo]` in keyboard.toml needs the \"combo\" Cargo feature"
+            )
+        );
+
+        // Unused features may be off
+        let used = [
+            ("combo", "`[behavior.combo]`", false),
+            ("macros", "`[behavior.macro]`", true),
+        ];
+        assert!(validate_feature_usage(&features, &used).is_ok());
+    }
 }
 
 fn expand_main(
@@ -219,14 +302,24 @@ fn expand_main(
     // Expand components of main function
     let imports = expand_custom_imports(&item_mod);
     let bind_interrupt = expand_bind_interrupt(hardware, &item_mod);
+    let critical_executor = expand_critical_executor(hardware);
     let chip_init = expand_chip_init(hardware, None, &item_mod);
     let usb_init = expand_usb_init(hardware, &item_mod);
     let flash_init = expand_flash_init(hardware);
+    let storage_bootmagic = match &hardware.board {
+        BoardConfig::UniBody(UniBodyConfig { matrix, .. }) => {
+            expand_storage_bootmagic_check(matrix, hardware.storage.is_some())
+        }
+        BoardConfig::Split(split_config) => {
+            expand_storage_bootmagic_check(&split_config.central.matrix, hardware.storage.is_some())
+        }
+    };
     let behavior_config = expand_behavior_config(behavior);
     let matrix_config = expand_matrix_config(hardware, rmk_features);
     let output_config = expand_output_config(hardware);
     let (ble_config, set_ble_config) = expand_ble_config(hardware);
-    let keymap_and_storage = expand_keymap_and_storage(hardware, layout);
+    let set_usb_config = expand_usb_config(hardware);
+    let keymap_and_storage = expand_keymap_and_storage(hardware, layout, behavior);
     let split_central_config = expand_split_central_config(hardware);
     let (input_device_config, devices, processors) = expand_input_device_config(hardware);
     let matrix_and_keyboard = expand_matrix_and_keyboard_init(hardware);
@@ -242,7 +335,9 @@ fn expand_main(
         let (init, processor) = expand_display_config(&hardware.chip.series, display_config);
         let processor_initializer = processor.initializer;
         let processor_var = processor.var_name;
-        registered_processors.push(quote! { #processor_var.run() });
+        registered_processors
+            .background
+            .push(quote! { #processor_var.run() });
         quote! {
             #init
             #processor_initializer
@@ -286,6 +381,7 @@ fn expand_main(
                 #vial_config
                 storage_config,
                 #set_ble_config
+                #set_usb_config
                 ..Default::default()
             };
         }
@@ -296,6 +392,7 @@ fn expand_main(
                 device_config: KEYBOARD_DEVICE_CONFIG,
                 #vial_config
                 #set_ble_config
+                #set_usb_config
                 ..Default::default()
             };
         }
@@ -318,6 +415,8 @@ fn expand_main(
 
         #bind_interrupt
 
+        #critical_executor
+
         #main_function_sig {
             // Initialize peripherals as `p`
             #chip_init
@@ -337,6 +436,9 @@ fn expand_main(
             // Initialize flash driver as `flash` and storage config as `storage_config`
             #flash_init
 
+            // Clear or ignore the storage if the bootmagic keys are held
+            #storage_bootmagic
+
             // Initialize ble config as `ble_battery_config`
             #ble_config
 
@@ -374,7 +476,11 @@ fn expand_main(
 }
 
 // TODO: move this function to a separate folder
-pub(crate) fn expand_keymap_and_storage(hardware: &Hardware, layout: &Layout) -> TokenStream2 {
+pub(crate) fn expand_keymap_and_storage(
+    hardware: &Hardware,
+    layout: &Layout,
+    behavior: &Behavior,
+) -> TokenStream2 {
     let row = layout.rows as usize;
     let col = layout.cols as usize;
 
@@ -391,10 +497,41 @@ pub(crate) fn expand_keymap_and_storage(hardware: &Hardware, layout: &Layout) ->
         || layout.key_info.len() != row
         || layout.key_info[0].len() != col
     {
-        quote! { let per_key_config = ::rmk::config::PositionalConfig::default(); }
+        quote! { ::rmk::config::PositionalConfig::default() }
     } else {
         let key_info_config = expand_key_info(&layout.key_info);
-        quote! { let per_key_config = ::rmk::config::PositionalConfig::new(#key_info_config); }
+        quote! { ::rmk::config::PositionalConfig::new(#key_info_config) }
+    };
+
+    // Per-key tap-hold configurations from `[[behavior.morse.per_key]]`
+    let per_key_tap_hold: Vec<TokenStream2> = behavior
+        .morse
+        .iter()
+        .flat_map(|morse| &morse.per_key)
+        .flat_map(|per_key| {
+            let profile = expand_profile(&per_key.profile);
+            let retro_tap = per_key.retro_tap;
+            let quick_tap_ms = per_key.quick_tap_ms.unwrap_or(0) as u16;
+            per_key.keys.iter().map(move |(row, col)| {
+                let (row, col) = (*row as usize, *col as usize);
+                quote! {
+                    tap_hold[#row][#col] = ::rmk::config::KeyTapHold {
+                        profile: #profile,
+                        retro_tap: #retro_tap,
+                        quick_tap_ms: #quick_tap_ms,
+                    };
+                }
+            })
+        })
+        .collect();
+    let initialize_positional_config = if per_key_tap_hold.is_empty() {
+        quote! { let per_key_config = #initialize_positional_config; }
+    } else {
+        quote! {
+            let mut tap_hold = [[::rmk::config::KeyTapHold::default(); #col]; #row];
+            #(#per_key_tap_hold)*
+            let per_key_config = #initialize_positional_config.with_tap_hold(tap_hold);
+        }
     };
 
     let total_num_encoders: usize = layout.encoder_counts.iter().sum();
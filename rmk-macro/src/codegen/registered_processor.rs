@@ -6,18 +6,28 @@ use syn::ItemMod;
 
 use super::chip::gpio::convert_gpio_str_to_output_pin;
 
+/// Executors of the registered processors, by task priority, see `rmk::priority`
+pub(crate) struct RegisteredProcessors {
+    /// Processors registered with `critical`, which run with the key processing
+    pub(crate) critical: Vec<TokenStream>,
+    pub(crate) background: Vec<TokenStream>,
+}
+
 /// Expand processor init/exec blocks from keyboard config.
 /// Returns (initializers, executors).
 pub(crate) fn expand_registered_processor_init(
     hardware: &Hardware,
     item_mod: &ItemMod,
-) -> (TokenStream, Vec<TokenStream>) {
+) -> (TokenStream, RegisteredProcessors) {
     let mut initializers = TokenStream::new();
-    let mut executors = vec![];
+    let mut executors = RegisteredProcessors {
+        critical: vec![],
+        background: vec![],
+    };
 
     let (i, e) = expand_light_indicator_processors(hardware);
     initializers.extend(i);
-    executors.extend(e);
+    executors.background.extend(e);
 
     // Custom processors declared in the module.
     if let Some((_, items)) = &item_mod.content {
@@ -35,13 +45,18 @@ pub(crate) fn expand_registered_processor_init(
 
             let (custom_init, custom_exec) = expand_custom_processor(item_fn);
             let mut mode: Option<bool> = None; // Some(true) = event, Some(false) = poll
+            let mut critical = false;
 
             attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("critical") {
+                    critical = true;
+                    return Ok(());
+                }
                 let is_event = meta.path.is_ident("event");
                 let is_poll = meta.path.is_ident("poll");
 
                 if !is_event && !is_poll {
-                    return Err(meta.error("expected `event` or `poll`"));
+                    return Err(meta.error("expected `event`, `poll` or `critical`"));
                 }
                 if mode.is_some() {
                     return Err(meta.error("cannot specify multiple modes"));
@@ -62,7 +77,11 @@ pub(crate) fn expand_registered_processor_init(
             };
 
             initializers.extend(custom_init);
-            executors.push(executor);
+            if critical {
+                executors.critical.push(executor);
+            } else {
+                executors.background.push(executor);
+            }
         }
     }
 
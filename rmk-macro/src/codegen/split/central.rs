@@ -45,11 +45,19 @@ pub(crate) fn expand_serial_init(chip: &ChipModel, serial: Vec<SerialConfig>) ->
         let rx_buf_static = format_ident!("RX_BUF{}", idx);
         let tx_buf_name = format_ident!("tx_buf{}", idx);
         let rx_buf_name = format_ident!("rx_buf{}", idx);
-        let uart_buf_init = quote! {
-            static #tx_buf_static: ::static_cell::StaticCell<[u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE]> = ::static_cell::StaticCell::new();
-            let #tx_buf_name = &mut #tx_buf_static.init([0_u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE])[..];
-            static #rx_buf_static: ::static_cell::StaticCell<[u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE]> = ::static_cell::StaticCell::new();
-            let #rx_buf_name = &mut #rx_buf_static.init([0_u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE])[..];
+        let uart_buf_init = match chip.series {
+            // TX is sent by DMA directly from the frame, so only the RX ring buffer is needed.
+            // The DMA keeps writing while a frame is decoded, so it holds several messages
+            ChipSeries::Stm32 => quote! {
+                static #rx_buf_static: ::static_cell::StaticCell<[u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE * 4]> = ::static_cell::StaticCell::new();
+                let #rx_buf_name = &mut #rx_buf_static.init([0_u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE * 4])[..];
+            },
+            _ => quote! {
+                static #tx_buf_static: ::static_cell::StaticCell<[u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE]> = ::static_cell::StaticCell::new();
+                let #tx_buf_name = &mut #tx_buf_static.init([0_u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE])[..];
+                static #rx_buf_static: ::static_cell::StaticCell<[u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE]> = ::static_cell::StaticCell::new();
+                let #rx_buf_name = &mut #rx_buf_static.init([0_u8; ::rmk::split::SPLIT_MESSAGE_MAX_SIZE])[..];
+            },
         };
         let uart_init = match chip.series {
             ChipSeries::Rp2040 => {
@@ -109,6 +117,50 @@ pub(crate) fn expand_serial_init(chip: &ChipModel, serial: Vec<SerialConfig>) ->
                     _ => panic!("Serial instance {:?} is not recognised", s.instance),
                 }
             }
+            ChipSeries::Stm32 => {
+                let uart_instance = format_ident!("{}", s.instance);
+                let uart_name = format_ident!("{}", s.instance.to_lowercase());
+                let uart_tx_name = format_ident!("{}_tx", s.instance.to_lowercase());
+                let uart_rx_name = format_ident!("{}_rx", s.instance.to_lowercase());
+                let tx_pin = format_ident!("{}", s.tx_pin);
+                let rx_pin = format_ident!("{}", s.rx_pin);
+                let irq_name = format_ident!("IrqsUart{}", idx);
+                if s.tx_pin == s.rx_pin {
+                    panic!(
+                        "Serial {}: half-duplex serial isn't supported on STM32, use separate tx_pin and rx_pin",
+                        s.instance
+                    );
+                }
+                let (Some(tx_dma), Some(rx_dma)) = (&s.tx_dma, &s.rx_dma) else {
+                    panic!(
+                        "Serial {}: both tx_dma and rx_dma are required on STM32",
+                        s.instance
+                    );
+                };
+                let tx_dma = format_ident!("{}", tx_dma);
+                let rx_dma = format_ident!("{}", rx_dma);
+                // RX runs into a DMA ring buffer, TX sends every frame with one DMA transfer
+                quote! {
+                    ::embassy_stm32::bind_interrupts!(struct #irq_name {
+                        #uart_instance => ::embassy_stm32::usart::InterruptHandler<::embassy_stm32::peripherals::#uart_instance>;
+                    });
+                    let (#uart_tx_name, #uart_rx_name) = ::embassy_stm32::usart::Uart::new(
+                        p.#uart_instance,
+                        p.#rx_pin,
+                        p.#tx_pin,
+                        #irq_name,
+                        p.#tx_dma,
+                        p.#rx_dma,
+                        ::embassy_stm32::usart::Config::default(),
+                    )
+                    .expect("Failed to initialize the split serial port")
+                    .split();
+                    let #uart_name = ::rmk::split::serial::full_duplex::FullDuplexSerial::new(
+                        #uart_rx_name.into_ring_buffered(#rx_buf_name),
+                        #uart_tx_name,
+                    );
+                }
+            }
             _ => panic!("Serial for chip {:?} isn't implemented yet", chip.series),
         };
         uart_initializers.extend(quote! {
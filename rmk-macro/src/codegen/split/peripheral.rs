@@ -13,7 +13,9 @@ use crate::codegen::chip::flash::expand_flash_init;
 use crate::codegen::chip::gpio::expand_output_initialization;
 use crate::codegen::display::{expand_display_config, expand_display_interrupt};
 use crate::codegen::entry::join_all_tasks;
-use crate::codegen::feature::{get_rmk_features, is_feature_enabled};
+use crate::codegen::feature::{
+    get_rmk_features, is_feature_enabled, uses_pointing_driver, validate_feature_usage,
+};
 use crate::codegen::import::expand_custom_imports;
 use crate::codegen::input_device::adc::expand_adc_device;
 use crate::codegen::input_device::encoder::expand_encoder_device;
@@ -40,10 +42,29 @@ pub(crate) fn parse_split_peripheral_mod(
     }
 
     let toml_config = read_keyboard_toml_config();
+    toml_config.validate().unwrap_or_else(|err| panic!("{err}"));
     let hardware = toml_config
         .hardware()
         .expect("failed to resolve hardware config");
 
+    let pointing_used = match &hardware.board {
+        BoardConfig::Split(split) => split.peripheral[id]
+            .input_device
+            .as_ref()
+            .is_some_and(uses_pointing_driver),
+        _ => false,
+    };
+    if let Err(err) = validate_feature_usage(
+        &rmk_features,
+        &[(
+            "pointing",
+            "A joystick, PMW3610, PMW33xx or IQS5xx",
+            pointing_used,
+        )],
+    ) {
+        return quote! { compile_error!(#err); };
+    }
+
     let main_function = expand_split_peripheral(id, &hardware, item_mod, &rmk_features);
 
     let bind_interrupts = expand_bind_interrupt_for_split_peripheral(&hardware.chip, &hardware, id);
@@ -256,6 +277,11 @@ fn expand_split_peripheral(
         .peripheral
         .get(id)
         .expect("Missing peripheral config");
+    if peripheral_config.matrix.bootmagic_reset.is_some()
+        || peripheral_config.matrix.bootmagic_safe_mode.is_some()
+    {
+        panic!("bootmagic_reset and bootmagic_safe_mode are only supported on the split central");
+    }
 
     let imports = expand_custom_imports(&item_mod);
     let mut chip_init = expand_chip_init(hardware, Some(id), &item_mod);
@@ -360,9 +386,11 @@ fn expand_split_peripheral(
         quote! {}
     };
 
-    // Add processor support for peripherals
-    let (registered_processor_initializers, mut registered_processors) =
+    // Add processor support for peripherals, all tasks of a peripheral run on one executor
+    let (registered_processor_initializers, registered) =
         expand_registered_processor_init(hardware, &item_mod);
+    let mut registered_processors = registered.critical;
+    registered_processors.extend(registered.background);
 
     // Display configuration for this peripheral
     let display_init = if let Some(display_config) = &peripheral_config.display {
@@ -475,21 +503,15 @@ fn expand_split_peripheral_entry(
         if peripheral_serial.len() != 1 {
             panic!("Peripheral should have only one serial config");
         }
+        let serial = peripheral_serial[0].clone();
         let serial_init = expand_serial_init(chip, peripheral_serial);
 
-        let uart_instance = format_ident!(
-            "{}",
-            peripheral_config
-                .serial
-                .as_ref()
-                .expect("Missing peripheral serial config")
-                .first()
-                .expect("Peripheral should have only one serial config")
-                .instance
-                .to_lowercase()
-        );
-        let peripheral_run = quote! {
-            ::rmk::split::peripheral::run_rmk_split_peripheral(#uart_instance)
+        let uart_instance = format_ident!("{}", serial.instance.to_lowercase());
+        // A single-wire link needs ACKs and retransmits
+        let peripheral_run = if serial.tx_pin == serial.rx_pin {
+            quote! { ::rmk::split::peripheral::run_rmk_half_duplex_split_peripheral(#uart_instance) }
+        } else {
+            quote! { ::rmk::split::peripheral::run_rmk_split_peripheral(#uart_instance) }
         };
         let mut tasks = vec![device_task, peripheral_run];
         tasks.extend(registered_processors);
@@ -52,11 +52,24 @@ fn generate_constants(bc: &BuildConstants) -> String {
     lines.push(format!("pub const COMBO_MAX_LENGTH: usize = {};", bc.combo_max_length));
     lines.push(format!("pub const MACRO_SPACE_SIZE: usize = {};", bc.macro_space_size));
     lines.push(format!("pub const FORK_MAX_NUM: usize = {};", bc.fork_max_num));
+    lines.push(format!(
+        "pub const KEYMAP_PROFILE_NUM: usize = {};",
+        bc.keymap_profile_num
+    ));
     lines.push(format!("pub const DEBOUNCE_THRESHOLD: u16 = {};", bc.debounce_time));
     lines.push(format!(
         "pub const REPORT_CHANNEL_SIZE: usize = {};",
         bc.report_channel_size
     ));
+    // Spelled out, so that a variant added in rmk-config or renamed here fails the build
+    let report_queue_overflow = match bc.report_queue_overflow {
+        rmk_config::ReportQueueOverflow::Block => "Block",
+        rmk_config::ReportQueueOverflow::DropOldest => "DropOldest",
+        rmk_config::ReportQueueOverflow::Coalesce => "Coalesce",
+    };
+    lines.push(format!(
+        "pub const REPORT_QUEUE_OVERFLOW: crate::connection::ReportQueueOverflow = crate::connection::ReportQueueOverflow::{report_queue_overflow};"
+    ));
     lines.push(format!(
         "pub const VIAL_CHANNEL_SIZE: usize = {};",
         bc.vial_channel_size
@@ -65,15 +78,32 @@ fn generate_constants(bc: &BuildConstants) -> String {
         "pub const FLASH_CHANNEL_SIZE: usize = {};",
         bc.flash_channel_size
     ));
+    lines.push(format!("pub const FLASH_BATCH_SIZE: usize = {};", bc.flash_batch_size));
     lines.push(format!(
         "pub const SPLIT_PERIPHERALS_NUM: usize = {};",
         bc.split_peripherals_num
     ));
     lines.push(format!("pub const NUM_BLE_PROFILE: usize = {};", bc.ble_profiles_num));
+    lines.push(format!(
+        "pub const BLE_NAME_MAX_LEN: usize = {};",
+        rmk_config::BLE_NAME_MAX_LEN
+    ));
     lines.push(format!(
         "pub const SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS: u32 = {};",
         bc.split_central_sleep_timeout_seconds
     ));
+    lines.push(format!(
+        "pub const SPLIT_CONN_INTERVAL_US: u64 = {};",
+        bc.split_conn_interval_us
+    ));
+    lines.push(format!(
+        "pub const SPLIT_CONN_LATENCY: u16 = {};",
+        bc.split_conn_latency
+    ));
+    lines.push(format!(
+        "pub const SPLIT_SUPERVISION_TIMEOUT_MS: u64 = {};",
+        bc.split_supervision_timeout_ms
+    ));
     lines.push(format!("pub const MORSE_MAX_NUM: usize = {};", bc.morse_max_num));
     lines.push(format!(
         "pub const MAX_PATTERNS_PER_KEY: usize = {};",
@@ -23,4 +23,22 @@ pub enum KeyboardAction {
     ComboOff,
     ComboToggle,
     CapsWordToggle,
+    AudioOn,
+    AudioOff,
+    AudioToggle,
+    ClickyToggle,
+    HapticOn,
+    HapticOff,
+    HapticToggle,
+    AutoBrightnessToggle,
+    /// Detect the host OS automatically, clears the manual host OS
+    HostOsAuto,
+    HostOsLinux,
+    HostOsWindows,
+    HostOsMacOs,
+    HostOsIos,
+    /// Send reports to both the USB and the BLE host when both are connected
+    OutputMirrorToggle,
+    /// Type a status report of the keyboard into the focused text field of the host
+    Diagnostics,
 }
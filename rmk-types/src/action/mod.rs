@@ -84,4 +84,9 @@ pub enum Action {
     /// sent to the host as a vendor HID report.
     #[cfg(feature = "steno")]
     Steno(StenoKey),
+    /// Switch to the keymap profile at the index, loading its keymap from storage.
+    ///
+    /// The active profile is persisted and restored on next boot (requires the `storage` and
+    /// `host` features).
+    ProfileSwitch(u8),
 }
@@ -19,6 +19,11 @@ pub enum ConnectionType {
 /// USB device lifecycle. `Suspended` is distinct from `Configured` because
 /// the bus is enumerated but transmission is gated on remote wakeup — the
 /// first key still needs to reach the USB writer to trigger that wakeup.
+///
+/// `PowerOnly` means Vbus is present but the bus went idle without a host
+/// ever configuring the device, e.g. when charging from a power brick. It's
+/// not routable, so output stays on BLE instead of queueing for a host that
+/// doesn't exist.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
 #[cfg_attr(feature = "rmk_protocol", derive(Schema))]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -29,6 +34,35 @@ pub enum UsbState {
     Enabled,
     Configured,
     Suspended,
+    PowerOnly,
+}
+
+/// Operating system of the USB host, detected from how it enumerates the keyboard
+/// or set manually with the `HostOs*` keyboard actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, MaxSize)]
+#[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Default)]
+#[repr(u8)]
+pub enum HostOs {
+    #[default]
+    Unknown = 0,
+    Linux = 1,
+    Windows = 2,
+    MacOs = 3,
+    Ios = 4,
+}
+
+impl From<u8> for HostOs {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => HostOs::Linux,
+            2 => HostOs::Windows,
+            3 => HostOs::MacOs,
+            4 => HostOs::Ios,
+            _ => HostOs::Unknown,
+        }
+    }
 }
 
 /// Unified connection status: the single source of truth for transport
@@ -42,6 +76,10 @@ pub struct ConnectionStatus {
     pub ble: BleStatus,
     /// Tiebreaker when both transports are ready.
     pub preferred: ConnectionType,
+    /// Route to `preferred` only, never falling back to the other transport.
+    pub forced: bool,
+    /// Also send every report to the other transport when both are ready.
+    pub mirror: bool,
 }
 
 impl ConnectionStatus {
@@ -53,6 +91,8 @@ impl ConnectionStatus {
                 state: BleState::Inactive,
             },
             preferred: ConnectionType::Usb,
+            forced: false,
+            mirror: false,
         }
     }
 }
@@ -74,8 +114,16 @@ impl ConnectionStatus {
 
     /// Pick the active transport from current readiness + preference. Suspended
     /// USB remains routable for remote wakeup, so it participates in the same
-    /// preference tie-break as configured USB.
+    /// preference tie-break as configured USB. When `forced` is set, only the
+    /// preferred transport is considered.
     pub fn decide_active(&self) -> Option<ConnectionType> {
+        if self.forced {
+            let ready = match self.preferred {
+                ConnectionType::Usb => self.usb_ready(),
+                ConnectionType::Ble => self.ble_ready(),
+            };
+            return ready.then_some(self.preferred);
+        }
         match (self.usb_ready(), self.ble_ready()) {
             (true, false) => Some(ConnectionType::Usb),
             (false, true) => Some(ConnectionType::Ble),
@@ -83,6 +131,33 @@ impl ConnectionStatus {
             (false, false) => None,
         }
     }
+
+    /// Pick the transport receiving a copy of the reports sent to the active one.
+    /// Only when mirroring is on and both transports are ready; a forced output
+    /// isn't mirrored.
+    pub fn decide_mirror(&self) -> Option<ConnectionType> {
+        if !self.mirror || self.forced || !(self.usb_ready() && self.ble_ready()) {
+            return None;
+        }
+        match self.decide_active()? {
+            ConnectionType::Usb => Some(ConnectionType::Ble),
+            ConnectionType::Ble => Some(ConnectionType::Usb),
+        }
+    }
+}
+
+/// What happens to a HID report sent to a transport whose report queue is full,
+/// set by `report_queue_overflow` in `keyboard.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportQueueOverflow {
+    /// Wait for the transport to take a report, stalling the key processing.
+    Block,
+    /// Drop the oldest queued report to make room.
+    DropOldest,
+    /// Wait like `Block`, and let the wireless writers merge the queued mouse
+    /// motion into one report, like the USB writer always does.
+    Coalesce,
 }
 
 #[cfg(test)]
@@ -98,6 +173,8 @@ mod tests {
                 state: ble_state,
             },
             preferred,
+            forced: false,
+            mirror: false,
         }
     }
 
@@ -146,4 +223,50 @@ mod tests {
         assert!(s.usb_ready());
         assert!(s.ble_ready());
     }
+
+    #[test]
+    fn power_only_usb_keeps_ble_active() {
+        let s = status(UsbState::PowerOnly, BleState::Connected, ConnectionType::Usb);
+        assert_eq!(s.decide_active(), Some(ConnectionType::Ble));
+        assert!(!s.usb_ready());
+
+        let s = status(UsbState::PowerOnly, BleState::Advertising, ConnectionType::Usb);
+        assert_eq!(s.decide_active(), None);
+    }
+
+    #[test]
+    fn forced_transport_never_falls_back() {
+        let mut s = status(UsbState::Disabled, BleState::Connected, ConnectionType::Usb);
+        s.forced = true;
+        assert_eq!(s.decide_active(), None);
+        s.usb = UsbState::Configured;
+        assert_eq!(s.decide_active(), Some(ConnectionType::Usb));
+
+        let mut s = status(UsbState::Configured, BleState::Advertising, ConnectionType::Ble);
+        s.forced = true;
+        assert_eq!(s.decide_active(), None);
+        s.ble.state = BleState::Connected;
+        assert_eq!(s.decide_active(), Some(ConnectionType::Ble));
+    }
+
+    #[test]
+    fn mirror_copies_reports_to_the_other_ready_transport() {
+        let mut s = status(UsbState::Configured, BleState::Connected, ConnectionType::Usb);
+        assert_eq!(s.decide_mirror(), None);
+        s.mirror = true;
+        assert_eq!(s.decide_active(), Some(ConnectionType::Usb));
+        assert_eq!(s.decide_mirror(), Some(ConnectionType::Ble));
+        s.preferred = ConnectionType::Ble;
+        assert_eq!(s.decide_mirror(), Some(ConnectionType::Usb));
+
+        // Nothing to mirror to
+        s.ble.state = BleState::Advertising;
+        assert_eq!(s.decide_active(), Some(ConnectionType::Usb));
+        assert_eq!(s.decide_mirror(), None);
+
+        // A forced output isn't mirrored
+        s.ble.state = BleState::Connected;
+        s.forced = true;
+        assert_eq!(s.decide_mirror(), None);
+    }
 }
@@ -15,6 +15,7 @@ use crate::modifier::ModifierCombination;
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Ord, FromRepr, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[cfg_attr(feature = "rmk_protocol", derive(Schema))]
+#[cfg_attr(feature = "_codegen", derive(strum::VariantNames))]
 pub enum HidKeyCode {
     /// Reserved, no-key.
     No = 0x0000,
@@ -25,6 +25,8 @@ pub enum SpecialKey {
     GraveEscape,
     // Repeat
     Repeat,
+    // Fn/Globe key of Apple keyboards, reported with the `apple_fn` feature of RMK
+    AppleFn,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Ord, MaxSize)]
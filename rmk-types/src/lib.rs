@@ -19,7 +19,10 @@
 //! - [`led_indicator`] — `LedIndicator` bitfield
 //! - [`battery`] — `BatteryStatus`, `ChargeState`
 //! - [`ble`] — `BleStatus`, `BleState`
-//! - [`connection`] — `ConnectionType` (USB/BLE), `UsbState`, `ConnectionStatus`
+//! - [`connection`] — `ConnectionType` (USB/BLE), `UsbState`, `ConnectionStatus`, `HostOs`
+//!
+//! ### Diagnostics
+//! - [`logging`] — `LogLevel`, `LogModule`: runtime log levels
 //!
 //! ### Protocol
 //! - [`protocol::vial`] — Vial/Via protocol types
@@ -40,6 +43,7 @@ pub mod fmt;
 pub mod fork;
 pub mod keycode;
 pub mod led_indicator;
+pub mod logging;
 pub mod modifier;
 pub mod morse;
 pub mod mouse_button;
@@ -0,0 +1,110 @@
+//! Log levels and the modules they're set for at runtime.
+
+use strum::FromRepr;
+
+/// Verbosity of the logs, from nothing to everything.
+///
+/// A level shows the messages of its own level and of the levels above it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=Self::Trace as u8)
+            .filter_map(Self::from_repr)
+            .find(|level| level.name() == name)
+    }
+}
+
+/// Part of the firmware the log level is set for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LogModule {
+    /// Key processing and behaviors
+    Keyboard = 0,
+    /// Keymap and its layers
+    Keymap = 1,
+    /// Matrix scanning and debouncing
+    Matrix = 2,
+    /// Input devices like encoders and pointing devices
+    Input = 3,
+    /// BLE, ESB and the other wireless transports
+    Wireless = 4,
+    Usb = 5,
+    /// The link between the halves of a split keyboard
+    Split = 6,
+    /// Vial and the other host configurators
+    Host = 7,
+    Storage = 8,
+    /// Lights, displays, buzzers and haptics
+    Output = 9,
+    /// Everything else
+    Other = 10,
+}
+
+impl LogModule {
+    /// Number of modules, the ids go from 0 to `COUNT - 1`
+    pub const COUNT: usize = Self::Other as usize + 1;
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard => "keyboard",
+            Self::Keymap => "keymap",
+            Self::Matrix => "matrix",
+            Self::Input => "input",
+            Self::Wireless => "wireless",
+            Self::Usb => "usb",
+            Self::Split => "split",
+            Self::Host => "host",
+            Self::Storage => "storage",
+            Self::Output => "output",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().find(|module| module.name() == name)
+    }
+
+    /// All modules, in the order of their ids
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..Self::COUNT as u8).filter_map(Self::from_repr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        assert_eq!(LogModule::all().count(), LogModule::COUNT);
+        for module in LogModule::all() {
+            assert_eq!(LogModule::from_name(module.name()), Some(module));
+        }
+        assert_eq!(LogLevel::from_name("debug"), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_name("Debug"), None);
+        assert!(LogLevel::Warn < LogLevel::Info);
+    }
+}
@@ -163,6 +163,16 @@ impl MorseProfile {
         }
         MorseProfile(v)
     }
+
+    /// The fields set in this profile, the others taken from `fallback`
+    pub fn or(self, fallback: MorseProfile) -> Self {
+        MorseProfile::new(
+            self.unilateral_tap().or(fallback.unilateral_tap()),
+            self.mode().or(fallback.mode()),
+            self.hold_timeout_ms().or(fallback.hold_timeout_ms()),
+            self.gap_timeout_ms().or(fallback.gap_timeout_ms()),
+        )
+    }
 }
 
 impl Default for MorseProfile {
@@ -678,4 +688,16 @@ mod tests {
         assert_eq!(actions.len(), 1);
         assert_eq!(actions[0], (0b11u16, Action::No));
     }
+
+    #[test]
+    fn profile_or_fills_unset_fields() {
+        let key = MorseProfile::new(None, Some(MorseMode::PermissiveHold), Some(180), None);
+        let fallback = MorseProfile::new(Some(true), Some(MorseMode::Normal), Some(250), Some(300));
+        let merged = key.or(fallback);
+        assert_eq!(merged.unilateral_tap(), Some(true));
+        assert_eq!(merged.mode(), Some(MorseMode::PermissiveHold));
+        assert_eq!(merged.hold_timeout_ms(), Some(180));
+        assert_eq!(merged.gap_timeout_ms(), Some(300));
+        assert_eq!(MorseProfile::const_default().or(fallback), fallback);
+    }
 }
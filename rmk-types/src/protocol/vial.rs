@@ -10,6 +10,7 @@ pub const VIAL_EP_SIZE: usize = 32;
 
 /// Via communication commands.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum ViaCommand {
     GetProtocolVersion = 0x01, // always 0x01
@@ -33,6 +34,14 @@ pub enum ViaCommand {
     DynamicKeymapSetBuffer = 0x13,
     DynamicKeymapGetEncoder = 0x14,
     DynamicKeymapSetEncoder = 0x15,
+    // RMK extension: snapshot of the runtime state, see `RUNTIME_STATE_NONE`
+    RuntimeState = 0xFA,
+    // RMK extension: the last crash, see `CRASH_CHUNK_SIZE`
+    CrashRecord = 0xFB,
+    // RMK extension: runtime log levels, see `LOG_LEVEL_ALL`
+    LogLevel = 0xFC,
+    // RMK extension: backup and restore of the stored configuration, see `BackupCommand`
+    Backup = 0xFD,
     Vial = 0xFE,
     Unhandled = 0xFF,
 }
@@ -45,6 +54,7 @@ impl From<u8> for ViaCommand {
 
 /// Information of a via keyboard.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum ViaKeyboardInfo {
     Uptime = 0x01,
@@ -63,6 +73,25 @@ impl TryFrom<u8> for ViaKeyboardInfo {
     }
 }
 
+/// Error code of a rejected Via or Vial request.
+///
+/// The reply to a rejected request is all zeros, except byte 0 which is
+/// [`ViaCommand::Unhandled`] and byte 1 which is the error code. Vial replies with a return code
+/// in byte 0, like the dynamic entry ops, read it as a failure.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ViaError {
+    /// The command or subcommand is unknown
+    UnknownCommand = 0x01,
+    /// A length field is larger than the packet can carry, or a field doesn't fit in the packet
+    InvalidLength = 0x02,
+    /// A layer, key position or entry index is outside of the keyboard
+    OutOfRange = 0x03,
+    /// The command needs an unlocked keyboard
+    Locked = 0x04,
+}
+
 /// Vial communication commands.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -133,3 +162,92 @@ impl From<u8> for VialDynamic {
         Self::from_repr(value).unwrap_or(Self::Unhandled)
     }
 }
+
+/// Module byte of a [`ViaCommand::LogLevel`] report which sets the level of every module, and
+/// level byte which only reads the levels.
+///
+/// | Request bytes                                                           | Reply bytes                       |
+/// |-------------------------------------------------------------------------|-----------------------------------|
+/// | 1: `LogModule` id or `LOG_LEVEL_ALL`, 2: `LogLevel` or `LOG_LEVEL_ALL`  | 3..: level of every module, by id |
+///
+/// See [`crate::logging`] for the ids of the modules and levels. With `vial_lock`, setting a level
+/// is rejected with [`ViaError::Locked`] until the keyboard is unlocked.
+pub const LOG_LEVEL_ALL: u8 = 0xFF;
+
+/// Size of the message chunk in the reply to a [`ViaCommand::CrashRecord`] report.
+///
+/// The host reads the last crash record of the keyboard, and continues at the next offset until
+/// it has the whole message.
+///
+/// | Request bytes            | Reply bytes                                                                                   |
+/// |--------------------------|-----------------------------------------------------------------------------------------------|
+/// | 1: offset in the message | 2: 1 if there's a record, 3: reason, 4: task, 5..9: uptime in ms, 9: message length, 10..: chunk |
+///
+/// The reason is 0 for a panic and 1 for a stalled task. The task is 0 for the BLE writer, 1 for
+/// the host service, 2 for the keyboard and 0xFF for none. The uptime is big endian, like in Via.
+pub const CRASH_CHUNK_SIZE: usize = 22;
+
+/// Byte of a [`ViaCommand::RuntimeState`] reply for no one-shot layer and no active transport.
+///
+/// | Request bytes | Reply bytes                                                                                              |
+/// |---------------|----------------------------------------------------------------------------------------------------------|
+/// | none          | 2..6: layer mask, 6: default layer, 7: top layer, 8: pending tap-holds, 9: held tap-holds, 10: pending combo keys, 11: one-shot layer, 12: one-shot modifiers, 13: held modifiers, 14: flags, 15: combos in progress, 16: triggered combos, 17: USB state, 18: BLE profile, 19: BLE state, 20: active transport |
+///
+/// The layer mask is big endian, like in Via, with bit `n` for layer `n`. The modifiers are
+/// `ModifierCombination` bits. The flags are bit 0 for Caps Word and bit 1 for combos turned on.
+/// The USB state is 0 for disabled, 1 for enabled, 2 for configured, 3 for suspended and 4 for
+/// power only, the BLE state 0 for advertising, 1 for connected and 2 for inactive, and the
+/// transport 0 for USB and 1 for BLE.
+pub const RUNTIME_STATE_NONE: u8 = 0xFF;
+
+/// Size of the record chunks carried by backup commands.
+pub const BACKUP_CHUNK_SIZE: usize = 26;
+
+/// Backup commands, the second byte of a [`ViaCommand::Backup`] report.
+///
+/// A backup is a list of records, each one a postcard serialized storage key followed by its
+/// value. The host reads record `n` in chunks with `ReadItem`, until the status is
+/// [`BackupStatus::End`], and restores them by writing every record in chunks with `WriteItem`
+/// and saving it with `CommitItem`. Records which were [`BackupStatus::Empty`] are removed from
+/// the keyboard with `ClearItem`. The restored configuration is loaded after a `Reboot`.
+///
+/// | Command      | Request bytes                                           | Reply bytes                                                   |
+/// |--------------|---------------------------------------------------------|---------------------------------------------------------------|
+/// | `ReadItem`   | 2..4: record index, 4..6: offset in the record          | 2: status, 3..5: record length, 5: chunk length, 6..: chunk   |
+/// | `WriteItem`  | 2..4: offset in the record, 4: chunk length, 5..: chunk | 2: status                                                     |
+/// | `CommitItem` |                                                         | 2: status                                                     |
+/// | `ClearItem`  | 2..4: record index                                      | 2: status                                                     |
+/// | `Reboot`     |                                                         | 2: status, only when the keyboard is locked                   |
+///
+/// Offsets and lengths are big endian, like in Via. Writing at offset 0 starts a new record.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum BackupCommand {
+    ReadItem = 0x00,
+    WriteItem = 0x01,
+    CommitItem = 0x02,
+    Reboot = 0x03,
+    ClearItem = 0x04,
+    Unhandled = 0xFF,
+}
+
+impl From<u8> for BackupCommand {
+    fn from(value: u8) -> Self {
+        Self::from_repr(value).unwrap_or(Self::Unhandled)
+    }
+}
+
+/// Status byte of the replies to backup commands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, FromRepr)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum BackupStatus {
+    Ok = 0x00,
+    /// Nothing is saved for this record, continue with the next one
+    Empty = 0x01,
+    /// The index is past the last record
+    End = 0x02,
+    /// The chunk or record was rejected
+    Invalid = 0x03,
+}
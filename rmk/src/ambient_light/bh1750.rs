@@ -0,0 +1,69 @@
+//! ROHM BH1750 ambient light sensor.
+//!
+//! The sensor is used in continuous high resolution mode: a new 1 lx
+//! resolution measurement is available every 120 ms.
+//!
+//! # References
+//!
+//! * [datasheet](https://www.mouser.com/datasheet/2/348/bh1750fvi-e-186247.pdf)
+
+use embedded_hal_async::i2c::I2c;
+
+use super::AmbientLightSensor;
+use crate::fmt::Debug;
+
+/// I2C address with the ADDR pin low
+const DEFAULT_ADDR: u8 = 0x23;
+
+// Instruction set
+const POWER_ON: u8 = 0x01;
+const CONTINUOUS_HIGH_RES_MODE: u8 = 0x10;
+
+/// BH1750 connected over I2C.
+pub struct Bh1750<I: I2c> {
+    i2c: I,
+    addr: u8,
+}
+
+impl<I: I2c> Bh1750<I>
+where
+    I::Error: Debug,
+{
+    /// Create a driver for a sensor with the ADDR pin low (address 0x23).
+    pub fn new(i2c: I) -> Self {
+        Self::with_address(i2c, DEFAULT_ADDR)
+    }
+
+    /// Create a driver for a sensor at `addr`, 0x5C when the ADDR pin is high.
+    pub fn with_address(i2c: I, addr: u8) -> Self {
+        Self { i2c, addr }
+    }
+
+    async fn try_init(&mut self) -> Result<(), I::Error> {
+        self.i2c.write(self.addr, &[POWER_ON]).await?;
+        self.i2c.write(self.addr, &[CONTINUOUS_HIGH_RES_MODE]).await
+    }
+}
+
+impl<I: I2c> AmbientLightSensor for Bh1750<I>
+where
+    I::Error: Debug,
+{
+    async fn init(&mut self) {
+        if let Err(e) = self.try_init().await {
+            error!("BH1750 init error: {:?}", e);
+        }
+    }
+
+    async fn read_lux(&mut self) -> Option<u32> {
+        let mut buf = [0u8; 2];
+        match self.i2c.read(self.addr, &mut buf).await {
+            // Illuminance is the raw count divided by 1.2
+            Ok(()) => Some(u16::from_be_bytes(buf) as u32 * 5 / 6),
+            Err(e) => {
+                error!("BH1750 read error: {:?}", e);
+                None
+            }
+        }
+    }
+}
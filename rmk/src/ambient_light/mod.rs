@@ -0,0 +1,198 @@
+//! Ambient light sensor driven auto-brightness.
+//!
+//! [`AutoBrightness`] periodically reads an [`AmbientLightSensor`], maps the
+//! illuminance to a brightness level and publishes it as [`BrightnessEvent`]
+//! for displays, backlights and RGB to follow. Small changes within the
+//! configured hysteresis are ignored so the brightness doesn't flicker around
+//! a threshold. RMK ships drivers for the [`Bh1750`] and the [`Veml7700`].
+//!
+//! The `AutoBrightnessToggle` keycode turns automatic control off so the
+//! brightness can be set manually, and back on.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use rmk::ambient_light::{AutoBrightness, AutoBrightnessConfig, Veml7700};
+//!
+//! let mut auto_brightness = AutoBrightness::new(Veml7700::new(i2c), AutoBrightnessConfig::default());
+//! run_all!(matrix, auto_brightness);
+//! ```
+
+mod bh1750;
+mod veml7700;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub use bh1750::Bh1750;
+use embassy_time::{Duration, Timer};
+use rmk_macro::input_device;
+pub use veml7700::Veml7700;
+
+use crate::event::BrightnessEvent;
+
+static AUTO_BRIGHTNESS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Whether brightness follows the ambient light sensor.
+pub fn auto_brightness_enabled() -> bool {
+    AUTO_BRIGHTNESS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turn automatic brightness control on or off.
+///
+/// While off, [`AutoBrightness`] doesn't publish any [`BrightnessEvent`] and
+/// the brightness stays wherever it was set manually.
+pub fn set_auto_brightness(enabled: bool) {
+    info!("Auto brightness: {}", enabled);
+    AUTO_BRIGHTNESS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Chip-agnostic ambient light sensor driver.
+pub trait AmbientLightSensor {
+    /// Initialize the sensor hardware.
+    async fn init(&mut self);
+    /// Read the illuminance in lux, `None` if the reading failed.
+    async fn read_lux(&mut self) -> Option<u32>;
+}
+
+/// Mapping from illuminance to brightness.
+///
+/// Illuminance between `dark_lux` and `bright_lux` is mapped linearly onto
+/// `min_brightness..=max_brightness`, values outside that range are clamped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutoBrightnessConfig {
+    /// Illuminance at or below which `min_brightness` is used.
+    pub dark_lux: u32,
+    /// Illuminance at or above which `max_brightness` is used.
+    pub bright_lux: u32,
+    pub min_brightness: u8,
+    pub max_brightness: u8,
+    /// A new level is published only if it differs from the last one by more than this.
+    pub hysteresis: u8,
+    /// Time between two sensor readings.
+    pub interval: Duration,
+}
+
+impl Default for AutoBrightnessConfig {
+    fn default() -> Self {
+        Self {
+            dark_lux: 5,
+            bright_lux: 500,
+            min_brightness: 16,
+            max_brightness: 255,
+            hysteresis: 12,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl AutoBrightnessConfig {
+    fn brightness(&self, lux: u32) -> u8 {
+        if lux <= self.dark_lux || self.bright_lux <= self.dark_lux {
+            return self.min_brightness;
+        }
+        if lux >= self.bright_lux {
+            return self.max_brightness;
+        }
+        let span = self.max_brightness.saturating_sub(self.min_brightness) as u32;
+        let offset = (lux - self.dark_lux) * span / (self.bright_lux - self.dark_lux);
+        self.min_brightness + offset as u8
+    }
+}
+
+/// Input device publishing [`BrightnessEvent`] from an [`AmbientLightSensor`].
+#[input_device(publish = BrightnessEvent)]
+pub struct AutoBrightness<S: AmbientLightSensor> {
+    sensor: S,
+    config: AutoBrightnessConfig,
+    initialized: bool,
+    /// Last published level, `None` forces the next reading to be published.
+    last: Option<u8>,
+}
+
+impl<S: AmbientLightSensor> AutoBrightness<S> {
+    pub fn new(sensor: S, config: AutoBrightnessConfig) -> Self {
+        Self {
+            sensor,
+            config,
+            initialized: false,
+            last: None,
+        }
+    }
+
+    /// Whether `level` should be published, given the last published level.
+    fn should_publish(&self, level: u8) -> bool {
+        match self.last {
+            Some(last) => last.abs_diff(level) > self.config.hysteresis,
+            None => true,
+        }
+    }
+
+    async fn read_brightness_event(&mut self) -> BrightnessEvent {
+        if !self.initialized {
+            self.sensor.init().await;
+            self.initialized = true;
+        }
+
+        loop {
+            Timer::after(self.config.interval).await;
+
+            if !auto_brightness_enabled() {
+                // Publish the current level as soon as auto brightness is turned back on
+                self.last = None;
+                continue;
+            }
+
+            let Some(lux) = self.sensor.read_lux().await else {
+                continue;
+            };
+            let level = self.config.brightness(lux);
+            trace!("Ambient light: {} lux, brightness {}", lux, level);
+            if self.should_publish(level) {
+                self.last = Some(level);
+                return BrightnessEvent::new(level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoSensor;
+
+    impl AmbientLightSensor for NoSensor {
+        async fn init(&mut self) {}
+
+        async fn read_lux(&mut self) -> Option<u32> {
+            None
+        }
+    }
+
+    #[test]
+    fn brightness_is_clamped_and_linear() {
+        let config = AutoBrightnessConfig {
+            dark_lux: 100,
+            bright_lux: 200,
+            min_brightness: 50,
+            max_brightness: 150,
+            ..Default::default()
+        };
+        assert_eq!(config.brightness(0), 50);
+        assert_eq!(config.brightness(100), 50);
+        assert_eq!(config.brightness(150), 100);
+        assert_eq!(config.brightness(200), 150);
+        assert_eq!(config.brightness(10_000), 150);
+    }
+
+    #[test]
+    fn small_changes_are_within_hysteresis() {
+        let mut device = AutoBrightness::new(NoSensor, AutoBrightnessConfig::default());
+        assert!(device.should_publish(100));
+        device.last = Some(100);
+        assert!(!device.should_publish(100 + device.config.hysteresis));
+        assert!(!device.should_publish(100 - device.config.hysteresis));
+        assert!(device.should_publish(101 + device.config.hysteresis));
+        assert!(device.should_publish(99 - device.config.hysteresis));
+    }
+}
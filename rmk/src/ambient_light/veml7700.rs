@@ -0,0 +1,58 @@
+//! Vishay VEML7700 ambient light sensor.
+//!
+//! The sensor is used with gain x1 and 100 ms integration time, which covers
+//! 0 to ~3770 lx at 0.0576 lx per count.
+//!
+//! # References
+//!
+//! * [datasheet](https://www.vishay.com/docs/84286/veml7700.pdf)
+//! * [application note](https://www.vishay.com/docs/84323/designingveml7700.pdf)
+
+use embedded_hal_async::i2c::I2c;
+
+use super::AmbientLightSensor;
+use crate::fmt::Debug;
+
+const I2C_ADDR: u8 = 0x10;
+
+// Command codes
+const REG_ALS_CONF: u8 = 0x00;
+const REG_ALS: u8 = 0x04;
+
+/// VEML7700 connected over I2C.
+pub struct Veml7700<I: I2c> {
+    i2c: I,
+}
+
+impl<I: I2c> Veml7700<I>
+where
+    I::Error: Debug,
+{
+    pub fn new(i2c: I) -> Self {
+        Self { i2c }
+    }
+}
+
+impl<I: I2c> AmbientLightSensor for Veml7700<I>
+where
+    I::Error: Debug,
+{
+    async fn init(&mut self) {
+        // ALS_CONF = 0: gain x1, 100 ms integration time, interrupt disabled, powered on.
+        // Registers are 16 bit, LSB first
+        if let Err(e) = self.i2c.write(I2C_ADDR, &[REG_ALS_CONF, 0x00, 0x00]).await {
+            error!("VEML7700 init error: {:?}", e);
+        }
+    }
+
+    async fn read_lux(&mut self) -> Option<u32> {
+        let mut buf = [0u8; 2];
+        match self.i2c.write_read(I2C_ADDR, &[REG_ALS], &mut buf).await {
+            Ok(()) => Some(u16::from_le_bytes(buf) as u32 * 576 / 10000),
+            Err(e) => {
+                error!("VEML7700 read error: {:?}", e);
+                None
+            }
+        }
+    }
+}
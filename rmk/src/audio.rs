@@ -0,0 +1,250 @@
+//! Piezo buzzer audio feedback.
+//!
+//! [`AudioProcessor`] plays short tones on startup, layer change, low battery
+//! and (optionally) every key press, and plays melodies requested from user
+//! code via [`play_melody`]. The buzzer itself is driven through the
+//! chip-agnostic [`Buzzer`] trait, usually backed by a PWM channel.
+//!
+//! Audio can be muted with `AudioToggle`/`AudioOn`/`AudioOff`, and the key
+//! click toggled with `ClickyToggle`. Both settings are persisted when the
+//! `storage` feature is enabled.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use rmk::audio::{AudioConfig, AudioProcessor, Buzzer};
+//!
+//! struct PwmBuzzer<'d>(embassy_nrf::pwm::SimplePwm<'d>);
+//!
+//! impl Buzzer for PwmBuzzer<'_> {
+//!     fn tone(&mut self, freq_hz: u32) {
+//!         self.0.set_period(freq_hz);
+//!         self.0.set_duty(0, self.0.max_duty() / 2);
+//!     }
+//!
+//!     fn off(&mut self) {
+//!         self.0.set_duty(0, 0);
+//!     }
+//! }
+//!
+//! let mut audio = AudioProcessor::new(PwmBuzzer(pwm), AudioConfig::default());
+//! run_all!(matrix, audio);
+//! ```
+
+use embassy_futures::select::{Either, select};
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use rmk_macro::processor;
+use rmk_types::action::{Action, KeyboardAction};
+use rmk_types::battery::BatteryStatus;
+use serde::{Deserialize, Serialize};
+
+use crate::RawMutex;
+use crate::core_traits::Runnable;
+use crate::event::{ActionEvent, BatteryStatusEvent, EventSubscriber, KeyboardEvent, LayerChangeEvent};
+use crate::processor::Processor;
+
+static MELODY_SIGNAL: Signal<RawMutex, Melody> = Signal::new();
+
+/// Chip-agnostic piezo buzzer driver.
+pub trait Buzzer {
+    /// Start a square wave at `freq_hz`.
+    fn tone(&mut self, freq_hz: u32);
+    /// Silence the buzzer.
+    fn off(&mut self);
+}
+
+/// A single note. A frequency of 0 is a rest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Note {
+    pub freq_hz: u16,
+    pub duration_ms: u16,
+}
+
+impl Note {
+    pub const fn new(freq_hz: u16, duration_ms: u16) -> Self {
+        Self { freq_hz, duration_ms }
+    }
+
+    pub const fn rest(duration_ms: u16) -> Self {
+        Self::new(0, duration_ms)
+    }
+}
+
+/// A sequence of notes played one after another.
+pub type Melody = &'static [Note];
+
+/// Built-in melodies.
+pub mod melodies {
+    use super::{Melody, Note};
+
+    pub const STARTUP: Melody = &[Note::new(1047, 80), Note::new(1319, 80), Note::new(1568, 120)];
+    pub const LAYER_CHANGE: Melody = &[Note::new(1760, 30)];
+    pub const LOW_BATTERY: Melody = &[Note::new(880, 150), Note::rest(100), Note::new(659, 250)];
+    pub const AUDIO_ON: Melody = &[Note::new(1319, 60), Note::new(1760, 60)];
+    pub const CLICK: Melody = &[Note::new(4000, 4)];
+}
+
+/// Audio feedback configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioConfig {
+    /// Played once when the processor starts.
+    pub startup: Option<Melody>,
+    /// Played on every layer change.
+    pub layer_change: Option<Melody>,
+    /// Played once when the battery level drops to `low_battery_level`.
+    pub low_battery: Option<Melody>,
+    /// Battery level in percent at or below which `low_battery` is played.
+    pub low_battery_level: u8,
+    /// Played on every key press while the key click is enabled.
+    pub click: Melody,
+    /// Whether the key click is enabled at first boot, before any setting is persisted.
+    pub clicky: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            startup: Some(melodies::STARTUP),
+            layer_change: None,
+            low_battery: Some(melodies::LOW_BATTERY),
+            low_battery_level: 10,
+            click: melodies::CLICK,
+            clicky: false,
+        }
+    }
+}
+
+/// Runtime audio settings changed by keycodes, persisted in storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct AudioSettings {
+    pub(crate) enabled: bool,
+    pub(crate) clicky: bool,
+}
+
+/// Play `melody` on the buzzer after the current one finishes.
+///
+/// Can be called from any task. If several melodies are requested while one
+/// is playing, only the latest is played. Ignored while audio is muted.
+pub fn play_melody(melody: Melody) {
+    MELODY_SIGNAL.signal(melody);
+}
+
+/// Processor driving a [`Buzzer`] from keyboard events.
+#[processor(subscribe = [KeyboardEvent, LayerChangeEvent, BatteryStatusEvent, ActionEvent])]
+#[::rmk::macros::runnable_generated]
+pub struct AudioProcessor<B: Buzzer> {
+    buzzer: B,
+    config: AudioConfig,
+    settings: AudioSettings,
+    /// Set once the low battery melody is played, cleared when the battery recovers.
+    low_battery_warned: bool,
+}
+
+impl<B: Buzzer> AudioProcessor<B> {
+    pub fn new(buzzer: B, config: AudioConfig) -> Self {
+        Self {
+            buzzer,
+            config,
+            settings: AudioSettings {
+                enabled: true,
+                clicky: config.clicky,
+            },
+            low_battery_warned: false,
+        }
+    }
+
+    async fn play(&mut self, melody: Melody) {
+        if !self.settings.enabled {
+            return;
+        }
+        for note in melody {
+            if note.freq_hz == 0 {
+                self.buzzer.off();
+            } else {
+                self.buzzer.tone(note.freq_hz as u32);
+            }
+            Timer::after_millis(note.duration_ms as u64).await;
+        }
+        self.buzzer.off();
+    }
+
+    async fn update_settings(&mut self, settings: AudioSettings) {
+        if settings == self.settings {
+            return;
+        }
+        self.settings = settings;
+        #[cfg(feature = "storage")]
+        crate::channel::FLASH_CHANNEL
+            .send(crate::storage::FlashOperationMessage::AudioSettings(settings))
+            .await;
+        if settings.enabled {
+            self.play(melodies::AUDIO_ON).await;
+        }
+    }
+
+    async fn on_keyboard_event(&mut self, event: KeyboardEvent) {
+        if event.pressed && self.settings.clicky {
+            self.play(self.config.click).await;
+        }
+    }
+
+    async fn on_layer_change_event(&mut self, _event: LayerChangeEvent) {
+        if let Some(melody) = self.config.layer_change {
+            self.play(melody).await;
+        }
+    }
+
+    async fn on_battery_status_event(&mut self, event: BatteryStatusEvent) {
+        let BatteryStatus::Available { level: Some(level), .. } = event.0 else {
+            return;
+        };
+        if level > self.config.low_battery_level {
+            self.low_battery_warned = false;
+        } else if !self.low_battery_warned {
+            self.low_battery_warned = true;
+            if let Some(melody) = self.config.low_battery {
+                self.play(melody).await;
+            }
+        }
+    }
+
+    async fn on_action_event(&mut self, event: ActionEvent) {
+        if !event.keyboard_event.pressed {
+            return;
+        }
+        let mut settings = self.settings;
+        match event.action {
+            Action::KeyboardControl(KeyboardAction::AudioOn) => settings.enabled = true,
+            Action::KeyboardControl(KeyboardAction::AudioOff) => settings.enabled = false,
+            Action::KeyboardControl(KeyboardAction::AudioToggle) => settings.enabled = !settings.enabled,
+            Action::KeyboardControl(KeyboardAction::ClickyToggle) => settings.clicky = !settings.clicky,
+            _ => return,
+        }
+        self.update_settings(settings).await;
+    }
+}
+
+impl<B: Buzzer> Runnable for AudioProcessor<B> {
+    async fn run(&mut self) -> ! {
+        let mut sub = <Self as Processor>::subscriber();
+
+        #[cfg(feature = "storage")]
+        if let Some(settings) = crate::storage::read_audio_settings().await {
+            self.settings = settings;
+        }
+
+        if let Some(melody) = self.config.startup {
+            self.play(melody).await;
+        }
+
+        loop {
+            match select(sub.next_event(), MELODY_SIGNAL.wait()).await {
+                Either::First(event) => self.process(event).await,
+                Either::Second(melody) => self.play(melody).await,
+            }
+        }
+    }
+}
@@ -10,6 +10,8 @@ use trouble_host::prelude::*;
 use super::ble_server::Server;
 use crate::ble::SLEEPING_STATE;
 use crate::core_traits::Runnable;
+#[cfg(feature = "split")]
+use crate::event::PeripheralBatteryEvent;
 use crate::event::{BatteryStatusEvent, SubscribableEvent};
 use crate::keyboard::LAST_KEY_TIMESTAMP;
 
@@ -22,6 +24,17 @@ pub(crate) struct BatteryService {
     pub(crate) level: u8,
 }
 
+/// Second battery service of a split central, with the level of its peripherals
+#[cfg(feature = "split")]
+#[gatt_service(uuid = service::BATTERY)]
+pub(crate) struct PeripheralBatteryService {
+    /// Lowest battery level of the peripherals
+    #[descriptor(uuid = "2901", read, value = "Peripheral")]
+    #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 100])]
+    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify)]
+    pub(crate) level: u8,
+}
+
 pub(crate) struct BleBatteryServer<'stack, 'server, 'conn, P: PacketPool> {
     battery_level: Characteristic<u8>,
     conn: &'conn GattConnection<'stack, 'server, P>,
@@ -133,3 +146,53 @@ impl<P: PacketPool> BleBatteryServer<'_, '_, '_, P> {
         }
     }
 }
+
+/// Reports the battery level of the peripherals to the host, in the second battery service.
+///
+/// Peripherals report their level only when it changes, so every report is forwarded.
+#[cfg(feature = "split")]
+pub(crate) struct BlePeripheralBatteryServer<'stack, 'server, 'conn, P: PacketPool> {
+    battery_level: Characteristic<u8>,
+    conn: &'conn GattConnection<'stack, 'server, P>,
+    sub: Subscriber<
+        'static,
+        crate::RawMutex,
+        PeripheralBatteryEvent,
+        { crate::PERIPHERAL_BATTERY_EVENT_CHANNEL_SIZE },
+        { crate::PERIPHERAL_BATTERY_EVENT_SUB_SIZE },
+        { crate::PERIPHERAL_BATTERY_EVENT_PUB_SIZE },
+    >,
+}
+
+#[cfg(feature = "split")]
+impl<'stack, 'server, 'conn, P: PacketPool> BlePeripheralBatteryServer<'stack, 'server, 'conn, P> {
+    pub(crate) fn new(server: &Server, conn: &'conn GattConnection<'stack, 'server, P>) -> Self {
+        Self {
+            battery_level: server.peripheral_battery_service.level,
+            conn,
+            sub: PeripheralBatteryEvent::subscriber(),
+        }
+    }
+}
+
+#[cfg(feature = "split")]
+impl<P: PacketPool> Runnable for BlePeripheralBatteryServer<'_, '_, '_, P> {
+    async fn run(&mut self) -> ! {
+        // Wait 2 seconds, ensure that gatt server has been started
+        Timer::after_secs(2).await;
+
+        let mut reported = None;
+        loop {
+            // The cache holds the levels reported before the host connected as well
+            if let Some(level) = crate::split::ble::lowest_peripheral_battery_level()
+                && reported != Some(level)
+            {
+                match self.battery_level.notify(self.conn, &level).await {
+                    Ok(_) => reported = Some(level),
+                    Err(e) => error!("Failed to notify peripheral battery level: {:?}", e),
+                }
+            }
+            self.sub.next_message_pure().await;
+        }
+    }
+}
@@ -2,10 +2,16 @@ use trouble_host::prelude::*;
 use usbd_hid::descriptor::{AsInputReport, SerializedDescriptor};
 
 use super::battery_service::BatteryService;
+#[cfg(feature = "split")]
+use super::battery_service::PeripheralBatteryService;
 use super::device_info::DeviceConfigurationService;
+#[cfg(feature = "ble_dfu")]
+use super::dfu::DfuService;
 #[cfg(feature = "host")]
 use crate::hid::ViaReport;
-use crate::hid::{CompositeReport, CompositeReportType, HidError, HidWriterTrait, KeyboardReport, Report};
+use crate::hid::{
+    CompositeReport, CompositeReportType, HidError, HidWriterTrait, KEYBOARD_DESCRIPTOR_LEN, KeyboardReport, Report,
+};
 
 // Used for saving the client attribute (CCCD) table. Tracks the trouble-host
 // per-connection client-specific attribute buffer size.
@@ -13,17 +19,39 @@ pub(crate) const CCCD_TABLE_SIZE: usize = trouble_host::config::CLIENT_ATT_TABLE
 
 // `gatt_server` compiles every member regardless of the surrounding `cfg` —
 // gating an individual field with `#[cfg(feature = "host")]` doesn't work. So
-// the whole struct is duplicated, with and without `host_service`.
-#[cfg(feature = "host")]
-#[gatt_server]
-pub(crate) struct Server {
-    pub(crate) battery_service: BatteryService,
-    pub(crate) hid_service: HidService,
-    pub(crate) host_service: VialService,
-    pub(crate) composite_service: CompositeService,
-    pub(crate) device_config_service: DeviceConfigurationService,
+// the struct is generated once per feature combination, with the optional
+// services passed in. Split centrals also report the level of their
+// peripherals, in a second battery service.
+macro_rules! define_server {
+    ($($name:ident: $service:ty),* $(,)?) => {
+        #[gatt_server]
+        pub(crate) struct Server {
+            pub(crate) battery_service: BatteryService,
+            pub(crate) hid_service: HidService,
+            $(pub(crate) $name: $service,)*
+            pub(crate) composite_service: CompositeService,
+            pub(crate) device_config_service: DeviceConfigurationService,
+        }
+    };
 }
 
+#[cfg(all(feature = "host", not(feature = "ble_dfu"), not(feature = "split")))]
+define_server!(host_service: VialService);
+#[cfg(all(feature = "host", feature = "ble_dfu", not(feature = "split")))]
+define_server!(host_service: VialService, dfu_service: DfuService);
+#[cfg(all(not(feature = "host"), feature = "ble_dfu", not(feature = "split")))]
+define_server!(dfu_service: DfuService);
+#[cfg(all(not(feature = "host"), not(feature = "ble_dfu"), not(feature = "split")))]
+define_server!();
+#[cfg(all(feature = "host", not(feature = "ble_dfu"), feature = "split"))]
+define_server!(peripheral_battery_service: PeripheralBatteryService, host_service: VialService);
+#[cfg(all(feature = "host", feature = "ble_dfu", feature = "split"))]
+define_server!(peripheral_battery_service: PeripheralBatteryService, host_service: VialService, dfu_service: DfuService);
+#[cfg(all(not(feature = "host"), feature = "ble_dfu", feature = "split"))]
+define_server!(peripheral_battery_service: PeripheralBatteryService, dfu_service: DfuService);
+#[cfg(all(not(feature = "host"), not(feature = "ble_dfu"), feature = "split"))]
+define_server!(peripheral_battery_service: PeripheralBatteryService);
+
 /// GATT service exposing the Vial-over-HID protocol. The keyboard writes replies via
 /// `input_data` notify; hosts push requests through `output_data`. `gatt_events_task`
 /// forwards `output_data` writes into `HOST_REQUEST_CHANNEL`, and `host::run_ble_host`
@@ -47,21 +75,12 @@ pub(crate) struct VialService {
     pub(crate) output_data: [u8; 32],
 }
 
-#[cfg(not(feature = "host"))]
-#[gatt_server]
-pub(crate) struct Server {
-    pub(crate) battery_service: BatteryService,
-    pub(crate) hid_service: HidService,
-    pub(crate) composite_service: CompositeService,
-    pub(crate) device_config_service: DeviceConfigurationService,
-}
-
 #[gatt_service(uuid = service::HUMAN_INTERFACE_DEVICE)]
 pub(crate) struct HidService {
     #[characteristic(uuid = "2a4a", read, value = [0x01, 0x01, 0x00, 0x03])]
     pub(crate) hid_info: [u8; 4],
-    #[characteristic(uuid = "2a4b", read, value = KeyboardReport::desc().try_into().expect("Failed to convert KeyboardReport to [u8; 67]"))]
-    pub(crate) report_map: [u8; 67],
+    #[characteristic(uuid = "2a4b", read, value = KeyboardReport::desc().try_into().expect("Failed to convert KeyboardReport to [u8; KEYBOARD_DESCRIPTOR_LEN]"))]
+    pub(crate) report_map: [u8; KEYBOARD_DESCRIPTOR_LEN],
     #[characteristic(uuid = "2a4c", write_without_response)]
     pub(crate) hid_control_point: u8,
     #[characteristic(uuid = "2a4e", read, write_without_response, value = 1)]
@@ -134,7 +153,12 @@ impl<P: PacketPool> HidWriterTrait for BleHidServer<'_, '_, '_, P> {
 
     async fn write_report(&mut self, report: &Self::ReportType) -> Result<usize, HidError> {
         match report {
-            Report::KeyboardReport(r) => self.notify_report(self.input_keyboard, r).await,
+            Report::KeyboardReport(r) => {
+                let n = self.notify_report(self.input_keyboard, r).await?;
+                #[cfg(feature = "latency")]
+                crate::latency::record_submitted();
+                Ok(n)
+            }
             Report::MouseReport(r) => self.notify_report(self.mouse_report, r).await,
             Report::MediaKeyboardReport(r) => self.notify_report(self.media_report, r).await,
             Report::SystemControlReport(r) => self.notify_report(self.system_report, r).await,
@@ -145,6 +169,12 @@ impl<P: PacketPool> HidWriterTrait for BleHidServer<'_, '_, '_, P> {
                 debug!("Steno chord dropped: Plover HID over BLE is not supported");
                 Ok(0)
             }
+            // The HID service only has the 6-key keyboard report and no digitizer, like steno
+            // they're dropped here, the keyboard report still holds the first 6 keys.
+            Report::NkroKeyboardReport(_) | Report::DigitizerReport(_) => {
+                debug!("NKRO or digitizer report dropped: not supported over BLE");
+                Ok(0)
+            }
         }
     }
 }
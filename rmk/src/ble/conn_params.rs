@@ -0,0 +1,47 @@
+//! Runtime switching of the BLE connection parameters.
+//!
+//! The parameter sets come from [`BleConnConfig`](crate::config::BleConnConfig).
+//! Switching the mode re-requests the parameters on the current connection, so
+//! the keyboard can drop into power save when idle and get back to low latency
+//! for gaming without reconnecting.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
+use trouble_host::prelude::RequestedConnParams;
+
+use crate::RawMutex;
+use crate::config::{BleConnMode, BleConnParams};
+
+static CONN_MODE: Mutex<RawMutex, Cell<BleConnMode>> = Mutex::new(Cell::new(BleConnMode::LowLatency));
+pub(crate) static CONN_MODE_CHANGED: Signal<RawMutex, ()> = Signal::new();
+
+/// Current connection parameter mode.
+pub fn conn_mode() -> BleConnMode {
+    CONN_MODE.lock(|m| m.get())
+}
+
+/// Switch the connection parameter mode.
+///
+/// The new parameters are requested from the host right away if connected,
+/// otherwise on the next connection. The mode isn't persisted.
+pub fn set_conn_mode(mode: BleConnMode) {
+    info!("BLE connection mode: {:?}", mode);
+    CONN_MODE.lock(|m| m.set(mode));
+    CONN_MODE_CHANGED.signal(());
+}
+
+impl From<BleConnParams> for RequestedConnParams {
+    fn from(params: BleConnParams) -> Self {
+        RequestedConnParams {
+            min_connection_interval: params.interval,
+            max_connection_interval: params.interval,
+            max_latency: params.latency,
+            min_event_length: Duration::from_secs(0),
+            max_event_length: Duration::from_secs(0),
+            supervision_timeout: params.supervision_timeout,
+        }
+    }
+}
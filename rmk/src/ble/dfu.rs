@@ -0,0 +1,614 @@
+//! Over-the-air firmware update over BLE.
+//!
+//! The DFU GATT service has two characteristics:
+//!
+//! - control point (write, notify): commands from the host, and a status notification for each of them
+//! - data (write without response): firmware chunks, each prefixed with its offset as little endian `u32`
+//!
+//! Commands on the control point:
+//!
+//! | Opcode | Payload                                    | Action                                      |
+//! | ------ | ------------------------------------------ | ------------------------------------------- |
+//! | `0x01` | image size `u32`, CRC32 of the image `u32` | Start an update                             |
+//! | `0x02` | ed25519 signature of the image `[u8; 64]`  | Verify the image and its signature, reboot  |
+//! | `0x03` |                                            | Abort the update                            |
+//!
+//! The status notification is `[opcode, status, next_offset: u32]`. A data chunk
+//! whose offset doesn't match the expected one is answered with `BadOffset` and the
+//! offset to resume from.
+//!
+//! The image is written into a [`DfuBank`], the bootloader is in charge of
+//! swapping it in on the next boot.
+//!
+//! # Security
+//!
+//! The CRC32 sent by the host only catches transfer errors. The image is booted only if
+//! [`DfuBank::verify_and_mark_updated`] accepts its signature, checked against the public key
+//! built into the running firmware, so a bonded host can't flash an image it didn't get signed.
+//! Writes are rejected until the link is encrypted, so an unbonded host can't start an update.
+
+use core::fmt::Debug;
+
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+use trouble_host::prelude::*;
+
+use crate::RawMutex;
+
+/// Maximum number of firmware bytes in one data write, fits in the largest ATT MTU
+pub const DFU_CHUNK_SIZE: usize = 240;
+// Offset prefix + chunk
+const DFU_PACKET_SIZE: usize = DFU_CHUNK_SIZE + 4;
+/// Size of the ed25519 signature of the image, sent with the finish command
+pub const DFU_SIGNATURE_SIZE: usize = 64;
+
+const OP_START: u8 = 0x01;
+const OP_FINISH: u8 = 0x02;
+const OP_ABORT: u8 = 0x03;
+const OP_DATA: u8 = 0x04;
+
+pub(crate) static DFU_REQUEST_CHANNEL: Channel<RawMutex, DfuRequest, 2> = Channel::new();
+pub(crate) static DFU_RESPONSE_CHANNEL: Channel<RawMutex, DfuResponse, 2> = Channel::new();
+
+/// Flash bank receiving the new firmware image.
+///
+/// With embassy-boot, this maps to the `FirmwareUpdater`: `write` is `write_firmware`
+/// and `verify_and_mark_updated` is `verify_and_mark_updated`.
+pub trait DfuBank {
+    type Error: Debug;
+
+    /// Size of the bank in bytes
+    fn capacity(&self) -> usize;
+
+    /// Write a block at `offset`, erasing the flash first if needed.
+    ///
+    /// Blocks are written in order, aligned to and sized as the `BLOCK_SIZE` of [`BleDfu`].
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Read back the written image.
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Check the ed25519 `signature` of the first `size` bytes of the image against the public key
+    /// built into the firmware, and tell the bootloader to boot the image if it matches.
+    ///
+    /// Returns `Ok(false)` without marking the image when the signature doesn't match.
+    async fn verify_and_mark_updated(
+        &mut self,
+        signature: &[u8; DFU_SIGNATURE_SIZE],
+        size: u32,
+    ) -> Result<bool, Self::Error>;
+}
+
+/// Status code in the control point notification
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DfuStatus {
+    Ok = 0,
+    /// Command not valid in the current state, e.g. data before start
+    InvalidState = 1,
+    /// The image doesn't fit in the bank
+    TooLarge = 2,
+    /// Chunk out of order, continue from `next_offset`
+    BadOffset = 3,
+    /// Writing to or reading from the bank failed
+    FlashError = 4,
+    /// The written image doesn't match the announced CRC
+    CrcMismatch = 5,
+    /// Malformed command
+    InvalidCommand = 6,
+    /// The previous requests are still being processed, and this one was dropped
+    Busy = 7,
+    /// The signature of the image doesn't match the key of the firmware
+    SignatureMismatch = 8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DfuRequest {
+    Start { size: u32, crc: u32 },
+    Data { offset: u32, data: Vec<u8, DFU_CHUNK_SIZE> },
+    Finish { signature: [u8; DFU_SIGNATURE_SIZE] },
+    Abort,
+}
+
+impl DfuRequest {
+    fn parse_control(data: &[u8]) -> Option<Self> {
+        match data {
+            [OP_START, rest @ ..] if rest.len() == 8 => Some(Self::Start {
+                size: u32::from_le_bytes(rest[0..4].try_into().ok()?),
+                crc: u32::from_le_bytes(rest[4..8].try_into().ok()?),
+            }),
+            [OP_FINISH, signature @ ..] => Some(Self::Finish {
+                signature: signature.try_into().ok()?,
+            }),
+            [OP_ABORT] => Some(Self::Abort),
+            _ => None,
+        }
+    }
+
+    fn parse_data(data: &[u8]) -> Option<Self> {
+        if data.len() < 5 {
+            return None;
+        }
+        Some(Self::Data {
+            offset: u32::from_le_bytes(data[0..4].try_into().ok()?),
+            data: Vec::from_slice(&data[4..]).ok()?,
+        })
+    }
+
+    fn opcode(&self) -> u8 {
+        match self {
+            Self::Start { .. } => OP_START,
+            Self::Data { .. } => OP_DATA,
+            Self::Finish { .. } => OP_FINISH,
+            Self::Abort => OP_ABORT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DfuResponse {
+    opcode: u8,
+    status: DfuStatus,
+    next_offset: u32,
+}
+
+impl DfuResponse {
+    fn to_bytes(self) -> [u8; 6] {
+        let mut buf = [self.opcode, self.status as u8, 0, 0, 0, 0];
+        buf[2..].copy_from_slice(&self.next_offset.to_le_bytes());
+        buf
+    }
+}
+
+/// Variable length value of the DFU characteristics
+#[derive(Clone, Copy)]
+pub(crate) struct DfuPacket {
+    len: usize,
+    buf: [u8; DFU_PACKET_SIZE],
+}
+
+impl Default for DfuPacket {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            buf: [0; DFU_PACKET_SIZE],
+        }
+    }
+}
+
+impl AsGatt for DfuPacket {
+    const MIN_SIZE: usize = 0;
+
+    const MAX_SIZE: usize = DFU_PACKET_SIZE;
+
+    fn as_gatt(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl FromGatt for DfuPacket {
+    fn from_gatt(data: &[u8]) -> Result<Self, FromGattError> {
+        if data.len() > DFU_PACKET_SIZE {
+            return Err(FromGattError::InvalidLength);
+        }
+        let mut packet = Self::default();
+        packet.buf[..data.len()].copy_from_slice(data);
+        packet.len = data.len();
+        Ok(packet)
+    }
+}
+
+#[gatt_service(uuid = "8d1e0001-3b6c-4f3a-9d2e-5a7c4b1f6e01")]
+pub(crate) struct DfuService {
+    #[characteristic(uuid = "8d1e0002-3b6c-4f3a-9d2e-5a7c4b1f6e01", write, notify)]
+    pub(crate) control_point: DfuPacket,
+    #[characteristic(uuid = "8d1e0003-3b6c-4f3a-9d2e-5a7c4b1f6e01", write_without_response)]
+    pub(crate) data: DfuPacket,
+}
+
+/// Forward a write to one of the DFU characteristics to [`BleDfu`].
+///
+/// Doesn't wait for [`BleDfu`], which may be busy writing the flash, as that would block the
+/// GATT task and every other event of the connection. The request is answered with `Busy` instead.
+pub(crate) fn forward_write(control: bool, data: &[u8]) {
+    let request = if control {
+        DfuRequest::parse_control(data)
+    } else {
+        DfuRequest::parse_data(data)
+    };
+    let (opcode, status) = match request {
+        Some(request) => {
+            let opcode = request.opcode();
+            if DFU_REQUEST_CHANNEL.try_send(request).is_ok() {
+                return;
+            }
+            warn!("[dfu] Busy, request dropped");
+            (opcode, DfuStatus::Busy)
+        }
+        None => {
+            warn!("[dfu] Malformed packet: {:?}", data);
+            (data.first().copied().unwrap_or(0), DfuStatus::InvalidCommand)
+        }
+    };
+    let _ = DFU_RESPONSE_CHANNEL.try_send(DfuResponse {
+        opcode,
+        status,
+        next_offset: 0,
+    });
+}
+
+/// Notify DFU responses on the control point of the current connection.
+pub(crate) async fn run_ble_dfu_notify<P: PacketPool>(
+    control_point: Characteristic<DfuPacket>,
+    conn: &GattConnection<'_, '_, P>,
+) -> ! {
+    DFU_RESPONSE_CHANNEL.clear();
+    loop {
+        let response = DFU_RESPONSE_CHANNEL.receive().await;
+        let packet = DfuPacket::from_gatt(&response.to_bytes()).unwrap_or_default();
+        if let Err(e) = control_point.notify(conn, &packet).await {
+            error!("[dfu] Failed to notify status: {:?}", e);
+        }
+    }
+}
+
+struct DfuSession {
+    size: u32,
+    crc: u32,
+    /// Bytes received so far
+    received: u32,
+}
+
+/// Receiver of the BLE firmware update, writing the image to a [`DfuBank`].
+///
+/// `BLOCK_SIZE` is the size of each write to the bank, set it to the erase size
+/// of the flash (or a multiple of it).
+pub struct BleDfu<B: DfuBank, const BLOCK_SIZE: usize = 4096> {
+    bank: B,
+    session: Option<DfuSession>,
+    block: [u8; BLOCK_SIZE],
+}
+
+impl<B: DfuBank, const BLOCK_SIZE: usize> BleDfu<B, BLOCK_SIZE> {
+    pub fn new(bank: B) -> Self {
+        Self {
+            bank,
+            session: None,
+            block: [0xFF; BLOCK_SIZE],
+        }
+    }
+
+    /// Run the updater, reboots the keyboard after a successful update.
+    pub async fn run(&mut self) -> ! {
+        loop {
+            let request = DFU_REQUEST_CHANNEL.receive().await;
+            let finish = matches!(request, DfuRequest::Finish { .. });
+            let response = self.process(request).await;
+            DFU_RESPONSE_CHANNEL.send(response).await;
+            if finish && response.status == DfuStatus::Ok {
+                // Let the status notification go out before rebooting
+                Timer::after(Duration::from_millis(500)).await;
+                crate::boot::flush_storage().await;
+                crate::boot::reboot_keyboard();
+            }
+        }
+    }
+
+    async fn process(&mut self, request: DfuRequest) -> DfuResponse {
+        let opcode = request.opcode();
+        let status = match request {
+            DfuRequest::Start { size, crc } => {
+                if size as usize > self.bank.capacity() {
+                    DfuStatus::TooLarge
+                } else {
+                    info!("[dfu] Start, image size: {}", size);
+                    self.block.fill(0xFF);
+                    self.session = Some(DfuSession { size, crc, received: 0 });
+                    DfuStatus::Ok
+                }
+            }
+            DfuRequest::Data { offset, data } => self.write_chunk(offset, &data).await,
+            DfuRequest::Finish { signature } => self.finish(&signature).await,
+            DfuRequest::Abort => {
+                info!("[dfu] Aborted");
+                self.session = None;
+                DfuStatus::Ok
+            }
+        };
+        if status != DfuStatus::Ok && status != DfuStatus::BadOffset {
+            self.session = None;
+        }
+        DfuResponse {
+            opcode,
+            status,
+            next_offset: self.session.as_ref().map_or(0, |s| s.received),
+        }
+    }
+
+    async fn write_chunk(&mut self, offset: u32, data: &[u8]) -> DfuStatus {
+        let Some(session) = self.session.as_mut() else {
+            return DfuStatus::InvalidState;
+        };
+        if offset != session.received {
+            return DfuStatus::BadOffset;
+        }
+        if session.received as usize + data.len() > session.size as usize {
+            return DfuStatus::TooLarge;
+        }
+
+        for &byte in data {
+            let pos = session.received as usize % BLOCK_SIZE;
+            self.block[pos] = byte;
+            session.received += 1;
+            if pos == BLOCK_SIZE - 1 {
+                let block_offset = session.received - BLOCK_SIZE as u32;
+                if self.bank.write(block_offset, &self.block).await.is_err() {
+                    error!("[dfu] Failed to write block at {}", block_offset);
+                    return DfuStatus::FlashError;
+                }
+                self.block.fill(0xFF);
+            }
+        }
+        DfuStatus::Ok
+    }
+
+    async fn finish(&mut self, signature: &[u8; DFU_SIGNATURE_SIZE]) -> DfuStatus {
+        let Some(session) = self.session.take() else {
+            return DfuStatus::InvalidState;
+        };
+        if session.received != session.size {
+            self.session = Some(session);
+            return DfuStatus::BadOffset;
+        }
+
+        // Flush the last partial block, padded with erased bytes
+        let tail = session.size as usize % BLOCK_SIZE;
+        if tail != 0 {
+            let block_offset = session.size - tail as u32;
+            if self.bank.write(block_offset, &self.block).await.is_err() {
+                return DfuStatus::FlashError;
+            }
+        }
+
+        // Verify what's actually in the bank
+        let mut crc = Crc32::new();
+        let mut buf = [0u8; 64];
+        let mut offset = 0;
+        while offset < session.size {
+            let len = buf.len().min((session.size - offset) as usize);
+            if self.bank.read(offset, &mut buf[..len]).await.is_err() {
+                return DfuStatus::FlashError;
+            }
+            crc.update(&buf[..len]);
+            offset += len as u32;
+        }
+        if crc.finish() != session.crc {
+            error!("[dfu] CRC mismatch");
+            return DfuStatus::CrcMismatch;
+        }
+
+        match self.bank.verify_and_mark_updated(signature, session.size).await {
+            Ok(true) => {
+                info!("[dfu] Image verified, rebooting into the new firmware");
+                DfuStatus::Ok
+            }
+            Ok(false) => {
+                error!("[dfu] Signature mismatch");
+                DfuStatus::SignatureMismatch
+            }
+            Err(_) => DfuStatus::FlashError,
+        }
+    }
+}
+
+/// CRC32 (IEEE 802.3), as computed by `zlib` and most host tools
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::block_on;
+
+    struct FakeBank {
+        flash: [u8; 64],
+        marked: bool,
+    }
+
+    impl DfuBank for FakeBank {
+        type Error = ();
+
+        fn capacity(&self) -> usize {
+            self.flash.len()
+        }
+
+        async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            self.flash
+                .get_mut(offset..offset + data.len())
+                .ok_or(())?
+                .copy_from_slice(data);
+            Ok(())
+        }
+
+        async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), ()> {
+            let offset = offset as usize;
+            buf.copy_from_slice(self.flash.get(offset..offset + buf.len()).ok_or(())?);
+            Ok(())
+        }
+
+        async fn verify_and_mark_updated(
+            &mut self,
+            signature: &[u8; DFU_SIGNATURE_SIZE],
+            _size: u32,
+        ) -> Result<bool, ()> {
+            self.marked = *signature == SIGNATURE;
+            Ok(self.marked)
+        }
+    }
+
+    /// The only signature the fake bank accepts
+    const SIGNATURE: [u8; DFU_SIGNATURE_SIZE] = [0xA5; DFU_SIGNATURE_SIZE];
+
+    fn finish(signature: [u8; DFU_SIGNATURE_SIZE]) -> DfuRequest {
+        DfuRequest::Finish { signature }
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finish()
+    }
+
+    fn data(offset: u32, data: &[u8]) -> DfuRequest {
+        DfuRequest::Data {
+            offset,
+            data: Vec::from_slice(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_parse_requests() {
+        assert_eq!(
+            DfuRequest::parse_control(&[0x01, 10, 0, 0, 0, 0x78, 0x56, 0x34, 0x12]),
+            Some(DfuRequest::Start {
+                size: 10,
+                crc: 0x1234_5678
+            })
+        );
+        let mut finish_command = [0xA5; DFU_SIGNATURE_SIZE + 1];
+        finish_command[0] = 0x02;
+        assert_eq!(DfuRequest::parse_control(&finish_command), Some(finish(SIGNATURE)));
+        // The signature is required
+        assert_eq!(DfuRequest::parse_control(&[0x02]), None);
+        assert_eq!(DfuRequest::parse_control(&[0x01, 10]), None);
+        assert_eq!(DfuRequest::parse_data(&[4, 0, 0, 0, 0xAA]), Some(data(4, &[0xAA])));
+        assert_eq!(DfuRequest::parse_data(&[4, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_forward_write_busy() {
+        DFU_REQUEST_CHANNEL.clear();
+        DFU_RESPONSE_CHANNEL.clear();
+        forward_write(true, &[OP_ABORT]);
+        forward_write(true, &[OP_ABORT]);
+        assert!(DFU_RESPONSE_CHANNEL.is_empty());
+
+        // The updater hasn't taken the first two yet
+        forward_write(false, &[4, 0, 0, 0, 0xAA]);
+        let resp = DFU_RESPONSE_CHANNEL.try_receive().unwrap();
+        assert_eq!((resp.opcode, resp.status), (OP_DATA, DfuStatus::Busy));
+        assert_eq!(DFU_REQUEST_CHANNEL.try_receive(), Ok(DfuRequest::Abort));
+        DFU_REQUEST_CHANNEL.clear();
+    }
+
+    #[test]
+    fn test_update_flow() {
+        block_on(async {
+            let image: [u8; 20] = core::array::from_fn(|i| i as u8);
+            let mut dfu = BleDfu::<_, 16>::new(FakeBank {
+                flash: [0; 64],
+                marked: false,
+            });
+
+            // Data before start is rejected
+            let resp = dfu.process(data(0, &image[..8])).await;
+            assert_eq!(resp.status, DfuStatus::InvalidState);
+
+            let resp = dfu
+                .process(DfuRequest::Start {
+                    size: 20,
+                    crc: crc32(&image),
+                })
+                .await;
+            assert_eq!(resp.status, DfuStatus::Ok);
+            assert_eq!(dfu.process(data(0, &image[..12])).await.status, DfuStatus::Ok);
+
+            // Duplicate chunk, the host is told where to continue
+            let resp = dfu.process(data(0, &image[..12])).await;
+            assert_eq!(resp.status, DfuStatus::BadOffset);
+            assert_eq!(resp.next_offset, 12);
+
+            assert_eq!(dfu.process(data(12, &image[12..])).await.status, DfuStatus::Ok);
+            assert_eq!(dfu.process(finish(SIGNATURE)).await.status, DfuStatus::Ok);
+            assert!(dfu.bank.marked);
+            assert_eq!(&dfu.bank.flash[..20], &image);
+            // Tail of the last block is padded with erased bytes
+            assert_eq!(&dfu.bank.flash[20..32], &[0xFF; 12]);
+        });
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        block_on(async {
+            let mut dfu = BleDfu::<_, 16>::new(FakeBank {
+                flash: [0; 64],
+                marked: false,
+            });
+            dfu.process(DfuRequest::Start { size: 4, crc: 0 }).await;
+            dfu.process(data(0, &[1, 2, 3, 4])).await;
+            assert_eq!(dfu.process(finish(SIGNATURE)).await.status, DfuStatus::CrcMismatch);
+            assert!(!dfu.bank.marked);
+            // The session is gone after a failure
+            assert_eq!(dfu.process(finish(SIGNATURE)).await.status, DfuStatus::InvalidState);
+        });
+    }
+
+    #[test]
+    fn test_signature_mismatch() {
+        block_on(async {
+            let image = [1, 2, 3, 4];
+            let mut dfu = BleDfu::<_, 16>::new(FakeBank {
+                flash: [0; 64],
+                marked: false,
+            });
+            dfu.process(DfuRequest::Start {
+                size: 4,
+                crc: crc32(&image),
+            })
+            .await;
+            dfu.process(data(0, &image)).await;
+            let resp = dfu.process(finish([0; DFU_SIGNATURE_SIZE])).await;
+            assert_eq!(resp.status, DfuStatus::SignatureMismatch);
+            assert!(!dfu.bank.marked);
+        });
+    }
+
+    #[test]
+    fn test_image_too_large() {
+        block_on(async {
+            let mut dfu = BleDfu::<_, 16>::new(FakeBank {
+                flash: [0; 64],
+                marked: false,
+            });
+            let resp = dfu.process(DfuRequest::Start { size: 65, crc: 0 }).await;
+            assert_eq!(resp.status, DfuStatus::TooLarge);
+        });
+    }
+}
@@ -0,0 +1,36 @@
+//! Identity of ESP32 chips, derived from the factory MAC address in eFuse.
+
+use embassy_sync::once_lock::OnceLock;
+use heapless::String;
+
+/// Static random BLE address derived from the chip's MAC address, different on every chip.
+///
+/// Pass it to [`crate::ble::build_ble_stack`], so that keyboards flashed with the same
+/// firmware don't share one address.
+pub fn ble_address() -> [u8; 6] {
+    let mac = esp_hal::efuse::Efuse::mac_address();
+    // Little endian, the two most significant bits of a static random address are set
+    [mac[5], mac[4], mac[3], mac[2], mac[1], mac[0] | 0xC0]
+}
+
+pub(crate) fn get_serial_number() -> &'static str {
+    static SERIAL: OnceLock<String<20>> = OnceLock::new();
+
+    let serial = SERIAL.get_or_init(|| {
+        let mac = esp_hal::efuse::Efuse::mac_address();
+
+        let mut result = String::new();
+        let _ = result.push_str("vial:f64c2b3c:");
+
+        // The last 3 bytes of the MAC address are specific to the chip, the first 3 are the vendor's
+        const HEX_TABLE: &[u8] = b"0123456789abcdef";
+        for byte in &mac[3..] {
+            let _ = result.push(HEX_TABLE[(byte >> 4) as usize] as char);
+            let _ = result.push(HEX_TABLE[(byte & 0xF) as usize] as char);
+        }
+
+        result
+    });
+
+    serial.as_str()
+}
@@ -1,38 +1,52 @@
 use core::sync::atomic::AtomicBool;
 
 use bt_hci::cmd::le::{LeReadLocalSupportedFeatures, LeSetPhy};
+#[cfg(feature = "ble_rssi")]
+use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
+use bt_hci::param::AddrKind;
 use embassy_futures::join::join;
 use embassy_futures::select::{Either, Either3, select, select3};
-use embassy_time::{Duration, Timer, with_timeout};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
 use rand_core::{CryptoRng, RngCore};
 use rmk_types::ble::BleState;
-use rmk_types::connection::ConnectionType;
+use rmk_types::connection::{ConnectionType, ReportQueueOverflow};
+use rmk_types::constants::BLE_NAME_MAX_LEN;
 use rmk_types::led_indicator::LedIndicator;
 use trouble_host::prelude::appearance::human_interface_device::KEYBOARD;
 use trouble_host::prelude::service::{BATTERY, HUMAN_INTERFACE_DEVICE};
 use trouble_host::prelude::*;
 
+use crate::REPORT_QUEUE_OVERFLOW;
 use crate::ble::battery_service::BleBatteryServer;
+#[cfg(feature = "split")]
+use crate::ble::battery_service::BlePeripheralBatteryServer;
 use crate::ble::ble_server::{BleHidServer, Server};
 use crate::ble::device_info::{PnPID, VidSource};
 use crate::ble::led::BleLedReader;
 #[cfg(feature = "passkey_entry")]
 use crate::ble::passkey::{PasskeyInputState, next_gatt_event};
 use crate::ble::profile::{ProfileInfo, ProfileManager, UPDATED_CCCD_TABLE, UPDATED_PROFILE};
-use crate::channel::{BLE_REPORT_CHANNEL, LED_SIGNAL};
-use crate::config::RmkConfig;
+use crate::channel::{BLE_REPORT_CHANNEL, LED_SIGNAL, ReportReceiver};
+use crate::config::{BleAdvConfig, BleConnConfig, RmkConfig};
 use crate::core_traits::Runnable;
-use crate::event::SubscribableEvent;
+use crate::event::{PowerStateEvent, SubscribableEvent, publish_event};
 use crate::hid::{HidWriterTrait, run_led_reader};
+use crate::link_stats::{record_packet, reset_link_stats, update_link_stats};
 #[cfg(feature = "split")]
 use crate::split::ble::central::CENTRAL_SLEEP;
 use crate::state::set_ble_state;
 
 pub(crate) mod battery_service;
 pub(crate) mod ble_server;
+pub mod conn_params;
 pub(crate) mod device_info;
+#[cfg(feature = "ble_dfu")]
+pub mod dfu;
+#[cfg(feature = "_esp_ble")]
+pub mod esp;
 pub(crate) mod led;
+pub mod name;
 #[cfg(feature = "_nrf_ble")]
 pub(crate) mod nrf;
 pub mod passkey;
@@ -46,6 +60,47 @@ pub(crate) static SLEEPING_STATE: AtomicBool = AtomicBool::new(false);
 /// Max number of connections
 pub(crate) const CONNECTIONS_MAX: usize = crate::SPLIT_PERIPHERALS_NUM + 1;
 
+/// How long the bonded host gets to reconnect before advertising is opened to all hosts
+const DIRECTED_ADV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval of the RSSI measurements of the connection, see [`crate::link_stats`]
+#[cfg(feature = "ble_rssi")]
+const RSSI_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Controller commands used by [`BleTransport`]. With the `ble_rssi` feature, the controller
+/// must also read the RSSI of the connection, for the link statistics.
+#[cfg(not(feature = "ble_rssi"))]
+pub trait BleController:
+    Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>
+{
+}
+
+#[cfg(not(feature = "ble_rssi"))]
+impl<C> BleController for C where
+    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>
+{
+}
+
+/// Controller commands used by [`BleTransport`]. With the `ble_rssi` feature, the controller
+/// must also read the RSSI of the connection, for the link statistics.
+#[cfg(feature = "ble_rssi")]
+pub trait BleController:
+    Controller
+    + ControllerCmdAsync<LeSetPhy>
+    + ControllerCmdSync<LeReadLocalSupportedFeatures>
+    + ControllerCmdSync<ReadRssi>
+{
+}
+
+#[cfg(feature = "ble_rssi")]
+impl<C> BleController for C where
+    C: Controller
+        + ControllerCmdAsync<LeSetPhy>
+        + ControllerCmdSync<LeReadLocalSupportedFeatures>
+        + ControllerCmdSync<ReadRssi>
+{
+}
+
 /// Max number of L2CAP channels
 pub(crate) const L2CAP_CHANNELS_MAX: usize = CONNECTIONS_MAX * 4; // Signal + att + smp + hid
 
@@ -75,30 +130,34 @@ pub async fn build_ble_stack<
 pub struct BleTransport<'b, 's, C>
 where
     's: 'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: BleController,
 {
     stack: &'b Stack<'s, C, DefaultPacketPool>,
     server: Server<'static>,
     profile_manager: ProfileManager<'b, 's, C, DefaultPacketPool>,
-    product_name: &'static str,
+    adv_config: BleAdvConfig<'static>,
+    conn_config: BleConnConfig,
 }
 
 impl<'b, 's, C> BleTransport<'b, 's, C>
 where
     's: 'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: BleController,
 {
     pub async fn new(stack: &'b Stack<'s, C, DefaultPacketPool>, rmk_config: RmkConfig<'static>) -> Self {
-        #[cfg(feature = "_nrf_ble")]
-        let serial_number = crate::ble::nrf::get_serial_number();
-        #[cfg(not(feature = "_nrf_ble"))]
-        let serial_number = rmk_config.device_config.serial_number;
+        let serial_number = rmk_config.device_config.serial();
+        let ble_name = rmk_config
+            .ble_adv_config
+            .name
+            .unwrap_or(rmk_config.device_config.product_name);
+        name::init_ble_name(ble_name, rmk_config.ble_adv_config.profile_suffix);
 
         let profile_manager = ProfileManager::new(stack);
+        conn_params::set_conn_mode(rmk_config.ble_conn_config.mode);
 
         info!("Starting advertising and GATT service");
         let server = Server::new_with_config(GapConfig::Peripheral(PeripheralConfig {
-            name: rmk_config.device_config.product_name,
+            name: ble_name,
             appearance: &appearance::human_interface_device::KEYBOARD,
         }))
         .unwrap();
@@ -131,7 +190,8 @@ where
             stack,
             server,
             profile_manager,
-            product_name: rmk_config.device_config.product_name,
+            adv_config: rmk_config.ble_adv_config,
+            conn_config: rmk_config.ble_conn_config,
         }
     }
 }
@@ -139,12 +199,16 @@ where
 impl<'b, 's, C> Runnable for BleTransport<'b, 's, C>
 where
     's: 'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
+    C: BleController,
 {
     async fn run(&mut self) -> ! {
         // Load the preferred connection from storage
         let preferred = crate::state::load_preferred_connection().await;
         crate::state::set_preferred_connection(preferred);
+        // A name set at runtime replaces the configured one
+        if let Some(ble_name) = crate::storage::read_ble_name().await {
+            name::init_ble_name(&ble_name, self.adv_config.profile_suffix);
+        }
         // Load the bonded devices from storage
         #[cfg(feature = "storage")]
         self.profile_manager.load_bonded_devices().await;
@@ -157,17 +221,53 @@ where
 
         let server = &self.server;
         let profile_manager = &mut self.profile_manager;
-        let product_name = self.product_name;
+        let conn_config = &self.conn_config;
+        let adv_config = self.adv_config;
 
         let connection_loop = async {
             loop {
-                match select(
-                    advertise(product_name, &mut peripheral, server),
+                let ble_name = name::advertised_name(crate::state::current_profile());
+                // Hosts read the name from the GAP service once connected, keep it the same as the advertised one
+                if let Err(e) = server
+                    .table()
+                    .find_characteristic_by_uuid::<heapless::String<BLE_NAME_MAX_LEN>>(&Uuid::from(
+                        characteristic::DEVICE_NAME,
+                    ))
+                    .and_then(|device_name| server.set(&device_name, &ble_name))
+                {
+                    warn!("Failed to update the GAP device name: {:?}", e);
+                }
+                // A profile without a bonded host is in pairing mode, advertise to all hosts
+                let directed_peer = if adv_config.directed {
+                    profile_manager.active_bond_info().and_then(|b| {
+                        // A host using resolvable private addresses, which is the case when it
+                        // gave its IRK, reconnects from a new address and doesn't answer
+                        // directed advertising, so advertise to all hosts instead
+                        let addr = b.info.identity.addr;
+                        let static_addr = addr.kind == AddrKind::PUBLIC || addr.addr.raw()[5] >> 6 == 0b11;
+                        (b.info.identity.irk.is_none() && static_addr).then_some(addr)
+                    })
+                } else {
+                    None
+                };
+                name::BLE_NAME_CHANGED.reset();
+                let advertised = select3(
+                    advertise(
+                        &ble_name,
+                        directed_peer,
+                        adv_config.directed_only,
+                        adv_config.timeout,
+                        &mut peripheral,
+                        server,
+                    ),
                     profile_manager.update_profile(),
+                    name::BLE_NAME_CHANGED.wait(),
                 )
-                .await
-                {
-                    Either::First(Ok(conn)) => {
+                .await;
+                // `advertise` is also cancelled by a profile or name change
+                publish_event(PowerStateEvent::Advertising(false));
+                match advertised {
+                    Either3::First(Ok(conn)) => {
                         // Do NOT emit BleState::Connected here. gatt_events_task emits
                         // Connected when it sees GattConnectionEvent::Encrypted.
                         #[cfg(feature = "storage")]
@@ -177,6 +277,7 @@ where
                                 server,
                                 &conn,
                                 stack,
+                                conn_config,
                                 #[cfg(feature = "storage")]
                                 active_bond_info,
                             ),
@@ -195,12 +296,34 @@ where
                             }
                         }
                     }
-                    Either::First(Err(BleHostError::BleHost(Error::Timeout))) => {
-                        warn!("Advertising timeout, sleep and wait for any key");
+                    Either3::First(Err(BleHostError::BleHost(Error::Timeout))) => {
                         set_ble_state(BleState::Inactive);
 
+                        // A pressed key wakes the chip through the pin sense of the matrix, which resets it
+                        #[cfg(all(
+                            feature = "_nrf_ble",
+                            feature = "async_matrix",
+                            not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble"))
+                        ))]
+                        if adv_config.system_off
+                            && !matches!(
+                                crate::state::current_usb_state(),
+                                rmk_types::connection::UsbState::Configured
+                                    | rmk_types::connection::UsbState::Suspended
+                            )
+                        {
+                            warn!("Advertising timeout, power off and wait for any key");
+                            crate::boot::flush_storage().await;
+                            nrf::system_off();
+                        }
+
+                        warn!("Advertising timeout, sleep and wait for any key");
+
+                        // The sleep manager of the split central publishes the sleep
                         #[cfg(feature = "split")]
                         CENTRAL_SLEEP.signal(true);
+                        #[cfg(not(feature = "split"))]
+                        publish_event(PowerStateEvent::Sleep(true));
 
                         // Wake on key or pointing activity after the advertising timeout.
                         let mut key_wake = crate::event::KeyboardEvent::subscriber();
@@ -209,14 +332,17 @@ where
 
                         #[cfg(feature = "split")]
                         CENTRAL_SLEEP.signal(false);
+                        #[cfg(not(feature = "split"))]
+                        publish_event(PowerStateEvent::Sleep(false));
                     }
-                    Either::First(Err(e)) => {
+                    Either3::First(Err(e)) => {
                         #[cfg(feature = "defmt")]
                         let e = defmt::Debug2Format(&e);
                         error!("Advertise error: {:?}", e);
                         Timer::after_millis(200).await;
                     }
-                    Either::Second(()) => {}
+                    // Restart advertising with the new profile or name
+                    Either3::Second(()) | Either3::Third(()) => {}
                 };
 
                 // Skip the Inactive transition if we never moved off Advertising
@@ -263,6 +389,8 @@ pub(crate) async fn ble_task<C: Controller + ControllerCmdAsync<LeSetPhy>, P: Pa
 /// This is how we interact with read and write requests.
 async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, DefaultPacketPool>) -> Result<(), Error> {
     let level = server.battery_service.level;
+    #[cfg(feature = "split")]
+    let peripheral_level = server.peripheral_battery_service.level;
     let output_keyboard = server.hid_service.output_keyboard;
     let hid_control_point = server.hid_service.hid_control_point;
     let input_keyboard = server.hid_service.input_keyboard;
@@ -272,6 +400,8 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
         server.host_service.input_data,
         server.host_service.hid_control_point,
     );
+    #[cfg(feature = "ble_dfu")]
+    let (dfu_control_point, dfu_data) = (server.dfu_service.control_point, server.dfu_service.data);
     let mouse = server.composite_service.mouse_report;
     let media = server.composite_service.media_report;
     let media_control_point = server.composite_service.hid_control_point;
@@ -341,12 +471,36 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                         }
                     }
                     GattEvent::Write(event) => {
+                        let encrypted = conn.raw().security_level().is_ok_and(|level| level.encrypted());
+
                         #[cfg(feature = "host")]
                         let host_control_point_match = event.handle() == host_control_point.handle;
                         #[cfg(not(feature = "host"))]
                         let host_control_point_match = false;
 
-                        if event.handle() == output_keyboard.handle {
+                        #[cfg(feature = "ble_dfu")]
+                        let dfu_match = event.handle() == dfu_control_point.handle || event.handle() == dfu_data.handle;
+                        #[cfg(not(feature = "ble_dfu"))]
+                        let dfu_match = false;
+
+                        #[cfg(feature = "split")]
+                        let peripheral_level_cccd_match = event.handle()
+                            == peripheral_level
+                                .cccd_handle
+                                .expect("No CCCD for peripheral battery level");
+                        #[cfg(not(feature = "split"))]
+                        let peripheral_level_cccd_match = false;
+
+                        if dfu_match {
+                            // Firmware updates are only accepted over an encrypted link, the write is
+                            // rejected with `INSUFFICIENT_ENCRYPTION` below otherwise
+                            #[cfg(feature = "ble_dfu")]
+                            if encrypted {
+                                dfu::forward_write(event.handle() == dfu_control_point.handle, event.data());
+                            } else {
+                                warn!("[dfu] Write over an unencrypted link rejected");
+                            }
+                        } else if event.handle() == output_keyboard.handle {
                             if event.data().len() == 1 {
                                 let led_indicator = LedIndicator::from_bits(event.data()[0]);
                                 debug!("Got keyboard state: {:?}", led_indicator);
@@ -359,6 +513,7 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             || event.handle() == media.cccd_handle.expect("No CCCD for media report")
                             || event.handle() == system_control.cccd_handle.expect("No CCCD for system report")
                             || event.handle() == level.cccd_handle.expect("No CCCD for battery level")
+                            || peripheral_level_cccd_match
                         {
                             cccd_updated = true;
                         } else if event.handle() == hid_control_point.handle
@@ -401,7 +556,7 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                             debug!("Write GATT Event to Unknown: {:?}", event.handle());
                         }
 
-                        if conn.raw().security_level()?.encrypted() {
+                        if encrypted {
                             None
                         } else {
                             Some(AttErrorCode::INSUFFICIENT_ENCRYPTION)
@@ -451,6 +606,10 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                     peripheral_latency,
                     supervision_timeout.as_millis()
                 );
+                update_link_stats(|s| {
+                    s.conn_interval_us = conn_interval.as_micros() as u32;
+                    s.peripheral_latency = peripheral_latency;
+                });
             }
             GattConnectionEvent::RequestConnectionParams(req) => info!(
                 "[gatt] RequestConnectionParams: interval: ({:?}, {:?})ms, {:?}, {:?}ms",
@@ -496,6 +655,10 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
                     continuation_number,
                     supervision_timeout.as_millis()
                 );
+                update_link_stats(|s| {
+                    s.conn_interval_us = conn_interval.as_micros() as u32;
+                    s.peripheral_latency = peripheral_latency;
+                });
             }
             GattConnectionEvent::PassKeyDisplay(pass_key) => info!("[gatt] PassKeyDisplay: {:?}", pass_key),
             GattConnectionEvent::PassKeyConfirm(pass_key) => info!("[gatt] PassKeyConfirm: {:?}", pass_key),
@@ -522,60 +685,94 @@ async fn gatt_events_task(server: &Server<'_>, conn: &GattConnection<'_, '_, Def
 }
 
 /// Create an advertiser to use to connect to a BLE Central, and wait for it to connect.
+///
+/// With a `directed_peer`, only that host is invited to connect first. If it doesn't
+/// reconnect within [`DIRECTED_ADV_TIMEOUT`], all hosts are, unless `directed_only` is set.
+/// Without a connection after `timeout`, `Error::Timeout` is returned.
 async fn advertise<'a, 'b, C: Controller>(
-    name: &'a str,
+    name: &str,
+    directed_peer: Option<Address>,
+    directed_only: bool,
+    timeout: Duration,
     peripheral: &mut Peripheral<'a, C, DefaultPacketPool>,
     server: &'b Server<'_>,
 ) -> Result<GattConnection<'a, 'b, DefaultPacketPool>, BleHostError<C::Error>> {
     // Wait for 10ms to ensure the USB is checked
     embassy_time::Timer::after_millis(10).await;
-    let mut advertiser_data = [0; 31];
-    AdStructure::encode_slice(
-        &[
-            AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
-            AdStructure::CompleteServiceUuids16(&[BATTERY.to_le_bytes(), HUMAN_INTERFACE_DEVICE.to_le_bytes()]),
-            AdStructure::CompleteLocalName(name.as_bytes()),
-            AdStructure::Unknown {
-                ty: 0x19, // Appearance
-                data: &KEYBOARD.to_le_bytes(),
-            },
-        ],
-        &mut advertiser_data[..],
-    )?;
-
-    let advertise_config = AdvertisementParameters {
-        primary_phy: PhyKind::Le2M,
-        secondary_phy: PhyKind::Le2M,
-        tx_power: TxPower::Plus8dBm,
-        interval_min: Duration::from_millis(200),
-        interval_max: Duration::from_millis(200),
-        ..Default::default()
-    };
-
-    info!("[adv] advertising");
     set_ble_state(BleState::Advertising);
-    let advertiser = peripheral
-        .advertise(
-            &advertise_config,
-            Advertisement::ConnectableScannableUndirected {
-                adv_data: &advertiser_data[..],
-                scan_data: &[],
-            },
-        )
-        .await?;
-
-    // Timeout for advertising is 300s
-    match with_timeout(Duration::from_secs(300), advertiser.accept()).await {
-        Ok(conn_res) => {
-            let conn = conn_res?.with_attribute_server(server)?;
-            info!("[adv] connection established");
-            if let Err(e) = conn.raw().set_bondable(true) {
-                error!("Set bondable error: {:?}", e);
-            };
-            Ok(conn)
+    publish_event(PowerStateEvent::Advertising(true));
+
+    let mut accepted = None;
+    if let Some(peer) = directed_peer {
+        let directed_config = AdvertisementParameters {
+            tx_power: TxPower::Plus8dBm,
+            interval_min: Duration::from_millis(20),
+            interval_max: Duration::from_millis(30),
+            ..Default::default()
+        };
+        info!("[adv] directed advertising to the bonded host");
+        let advertiser = peripheral
+            .advertise(
+                &directed_config,
+                Advertisement::ConnectableNonscannableDirected { peer },
+            )
+            .await?;
+        let directed_timeout = if directed_only { timeout } else { DIRECTED_ADV_TIMEOUT };
+        match with_timeout(directed_timeout, advertiser.accept()).await {
+            Ok(conn_res) => accepted = Some(conn_res),
+            Err(_) if directed_only => return Err(BleHostError::BleHost(Error::Timeout)),
+            Err(_) => info!("[adv] bonded host didn't reconnect, advertising to all hosts"),
         }
-        Err(_) => Err(BleHostError::BleHost(Error::Timeout)),
     }
+
+    let conn_res = match accepted {
+        Some(conn_res) => conn_res,
+        None => {
+            let mut advertiser_data = [0; 31];
+            AdStructure::encode_slice(
+                &[
+                    AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED),
+                    AdStructure::CompleteServiceUuids16(&[BATTERY.to_le_bytes(), HUMAN_INTERFACE_DEVICE.to_le_bytes()]),
+                    AdStructure::CompleteLocalName(name.as_bytes()),
+                    AdStructure::Unknown {
+                        ty: 0x19, // Appearance
+                        data: &KEYBOARD.to_le_bytes(),
+                    },
+                ],
+                &mut advertiser_data[..],
+            )?;
+
+            let advertise_config = AdvertisementParameters {
+                primary_phy: PhyKind::Le2M,
+                secondary_phy: PhyKind::Le2M,
+                tx_power: TxPower::Plus8dBm,
+                interval_min: Duration::from_millis(200),
+                interval_max: Duration::from_millis(200),
+                ..Default::default()
+            };
+
+            info!("[adv] advertising");
+            let advertiser = peripheral
+                .advertise(
+                    &advertise_config,
+                    Advertisement::ConnectableScannableUndirected {
+                        adv_data: &advertiser_data[..],
+                        scan_data: &[],
+                    },
+                )
+                .await?;
+            with_timeout(timeout, advertiser.accept())
+                .await
+                .map_err(|_| BleHostError::BleHost(Error::Timeout))?
+        }
+    };
+
+    let conn = conn_res?.with_attribute_server(server)?;
+    info!("[adv] connection established");
+    if let Err(e) = conn.raw().set_bondable(true) {
+        error!("Set bondable error: {:?}", e);
+    };
+    Ok(conn)
 }
 
 pub(crate) async fn set_conn_params<
@@ -586,6 +783,7 @@ pub(crate) async fn set_conn_params<
 >(
     stack: &Stack<'_, C, P>,
     conn: &GattConnection<'a, 'b, P>,
+    conn_config: &BleConnConfig,
 ) {
     // Wait for 5 seconds before setting connection parameters to avoid connection drop
     embassy_time::Timer::after_secs(5).await;
@@ -608,45 +806,37 @@ pub(crate) async fn set_conn_params<
 
     embassy_time::Timer::after_secs(5).await;
 
-    // Setting the conn param the second time ensures that we have best performance on all platforms
-    update_conn_params(
-        stack,
-        conn.raw(),
-        &RequestedConnParams {
-            min_connection_interval: Duration::from_micros(7500),
-            max_connection_interval: Duration::from_micros(7500),
-            max_latency: 30,
-            min_event_length: Duration::from_secs(0),
-            max_event_length: Duration::from_secs(0),
-            supervision_timeout: Duration::from_secs(5),
-        },
-    )
-    .await;
-
-    // Wait forever. This is because we want the conn params setting can be interrupted when the connection is lost.
-    // So this task shouldn't quit after setting the conn params.
-    core::future::pending::<()>().await;
+    // Setting the conn param the second time ensures that we have best performance on all platforms.
+    // Then re-request whenever the mode is switched.
+    //
+    // This loop never quits, because we want the conn params setting can be interrupted when the connection is lost.
+    loop {
+        conn_params::CONN_MODE_CHANGED.reset();
+        let params = conn_config.params(conn_params::conn_mode());
+        update_conn_params(stack, conn.raw(), &params.into()).await;
+        conn_params::CONN_MODE_CHANGED.wait().await;
+    }
 }
 
 /// Run BLE keyboard for one connection.
 ///
 /// Returns when the GATT events task ends (i.e. the connection drops).
-/// `writer_task`, `led_task`, and `host_task` are all infinite, so the outer
+/// `writer_task`, `led_task`, `host_task` and `dfu_task` are all infinite, so the outer
 /// `select(communication_task, inner)` cancels them as a side-effect of
 /// `communication_task` returning. `inner` itself never completes.
-async fn run_ble_keyboard<
-    'a,
-    'b,
-    C: Controller + ControllerCmdAsync<LeSetPhy> + ControllerCmdSync<LeReadLocalSupportedFeatures>,
->(
+async fn run_ble_keyboard<'a, 'b, C: BleController>(
     server: &'b Server<'_>,
     conn: &GattConnection<'a, 'b, DefaultPacketPool>,
     stack: &Stack<'_, C, DefaultPacketPool>,
+    conn_config: &BleConnConfig,
     #[cfg(feature = "storage")] active_bond_info: Option<crate::ble::profile::ProfileInfo>,
 ) {
     let mut ble_hid_server = BleHidServer::new(server, conn);
     let mut ble_led_reader = BleLedReader {};
     let mut ble_battery_server = BleBatteryServer::new(server, conn);
+    #[cfg(feature = "split")]
+    let mut ble_peripheral_battery_server = BlePeripheralBatteryServer::new(server, conn);
+    reset_link_stats();
 
     // CCCD lookup uses cached bond info to avoid a cancellable flash read while
     // this future is racing other arms of an outer `select`.
@@ -667,8 +857,13 @@ async fn run_ble_keyboard<
     let communication_task = async {
         if let Either3::First(e) = select3(
             gatt_events_task(server, conn),
-            set_conn_params(stack, conn),
-            ble_battery_server.run(),
+            set_conn_params(stack, conn, conn_config),
+            async {
+                #[cfg(feature = "split")]
+                select(ble_battery_server.run(), ble_peripheral_battery_server.run()).await;
+                #[cfg(not(feature = "split"))]
+                ble_battery_server.run().await;
+            },
         )
         .await
         {
@@ -677,14 +872,38 @@ async fn run_ble_keyboard<
     };
 
     let writer_task = async {
+        let mut reports = ReportReceiver::new(
+            &BLE_REPORT_CHANNEL,
+            REPORT_QUEUE_OVERFLOW == ReportQueueOverflow::Coalesce,
+        );
         loop {
-            let report = BLE_REPORT_CHANNEL.receive().await;
-            if let Err(e) = ble_hid_server.write_report(&report).await {
-                error!("Failed to send report: {:?}", e);
+            let report = reports.next().await;
+            #[cfg(feature = "watchdog")]
+            let _busy = crate::watchdog::busy(crate::watchdog::CoreTask::BleWriter);
+            let start = Instant::now();
+            match ble_hid_server.write_report(&report).await {
+                Ok(_) => record_packet(start.elapsed(), 0),
+                Err(e) => {
+                    error!("Failed to send report: {:?}", e);
+                    update_link_stats(|s| s.dropped = s.dropped.wrapping_add(1));
+                }
             }
         }
     };
 
+    #[cfg(feature = "ble_rssi")]
+    let rssi_task = async {
+        loop {
+            Timer::after(RSSI_INTERVAL).await;
+            match conn.raw().rssi(stack).await {
+                Ok(rssi) => update_link_stats(|s| s.rssi = Some(rssi)),
+                Err(_) => debug!("Failed to read the RSSI"),
+            }
+        }
+    };
+    #[cfg(not(feature = "ble_rssi"))]
+    let rssi_task = core::future::pending::<()>();
+
     let led_task = run_led_reader(&mut ble_led_reader, ConnectionType::Ble);
 
     #[cfg(feature = "host")]
@@ -692,7 +911,12 @@ async fn run_ble_keyboard<
     #[cfg(not(feature = "host"))]
     let host_task = core::future::pending::<()>();
 
-    let inner = embassy_futures::join::join3(writer_task, led_task, host_task);
+    #[cfg(feature = "ble_dfu")]
+    let dfu_task = dfu::run_ble_dfu_notify(server.dfu_service.control_point, conn);
+    #[cfg(not(feature = "ble_dfu"))]
+    let dfu_task = core::future::pending::<()>();
+
+    let inner = embassy_futures::join::join4(writer_task, led_task, host_task, join(dfu_task, rssi_task));
     select(communication_task, inner).await;
 }
 
@@ -769,8 +993,8 @@ mod tests {
     use rmk_types::ble::{BleState, BleStatus};
 
     use crate::event::{Axis, AxisEvent, AxisValType, KeyboardEvent, PointingEvent, SubscribableEvent, publish_event};
+    use crate::simulation::block_on;
     use crate::state::{current_ble_status, set_ble_profile, set_ble_state};
-    use crate::test_support::test_block_on as block_on;
 
     fn ble_status_test_lock() -> &'static Mutex<()> {
         static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
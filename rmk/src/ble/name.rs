@@ -0,0 +1,97 @@
+//! Advertised BLE name.
+//!
+//! The name starts as [`crate::config::BleAdvConfig::name`], or the product name,
+//! and can be changed at runtime with [`set_ble_name`]. A changed name is saved
+//! to storage and restored on the next boot. The Device Name of the GAP service
+//! follows the advertised name, but hosts that are already bonded may keep
+//! showing the name they saw when pairing.
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use heapless::String;
+use rmk_types::constants::BLE_NAME_MAX_LEN;
+
+use crate::RawMutex;
+use crate::channel::FLASH_CHANNEL;
+use crate::storage::FlashOperationMessage;
+
+static BLE_NAME: Mutex<RawMutex, RefCell<String<BLE_NAME_MAX_LEN>>> = Mutex::new(RefCell::new(String::new()));
+static PROFILE_SUFFIX: AtomicBool = AtomicBool::new(false);
+/// Signaled when the name changes, so that advertising restarts with the new name
+pub(crate) static BLE_NAME_CHANGED: Signal<RawMutex, ()> = Signal::new();
+
+/// Current BLE name, without the profile suffix.
+pub fn ble_name() -> String<BLE_NAME_MAX_LEN> {
+    BLE_NAME.lock(|n| n.borrow().clone())
+}
+
+/// Change the BLE name and save it to storage.
+///
+/// Names longer than [`BLE_NAME_MAX_LEN`] bytes are truncated.
+pub async fn set_ble_name(name: &str) {
+    let name = truncate(name, BLE_NAME_MAX_LEN);
+    info!("Set BLE name: {}", name.as_str());
+    BLE_NAME.lock(|n| *n.borrow_mut() = name.clone());
+    BLE_NAME_CHANGED.signal(());
+    FLASH_CHANNEL.send(FlashOperationMessage::BleName(name)).await;
+}
+
+/// Set the name from the config, without saving it.
+pub(crate) fn init_ble_name(name: &str, profile_suffix: bool) {
+    BLE_NAME.lock(|n| *n.borrow_mut() = truncate(name, BLE_NAME_MAX_LEN));
+    PROFILE_SUFFIX.store(profile_suffix, Ordering::Release);
+}
+
+/// Name in the advertising packet of the given profile.
+pub(crate) fn advertised_name(profile: u8) -> String<BLE_NAME_MAX_LEN> {
+    let name = ble_name();
+    if !PROFILE_SUFFIX.load(Ordering::Acquire) {
+        return name;
+    }
+    with_profile_suffix(&name, profile)
+}
+
+fn with_profile_suffix(name: &str, profile: u8) -> String<BLE_NAME_MAX_LEN> {
+    let mut suffix: String<5> = String::new();
+    let _ = write!(suffix, " #{}", profile + 1);
+    // Shorten the name instead of dropping the suffix, so every profile stays distinguishable
+    let mut name = truncate(name, BLE_NAME_MAX_LEN - suffix.len());
+    name.truncate(name.trim_end().len());
+    let _ = name.push_str(&suffix);
+    name
+}
+
+/// Copy the longest prefix of `name` that fits in `max_len` bytes, without splitting a character.
+fn truncate(name: &str, max_len: usize) -> String<BLE_NAME_MAX_LEN> {
+    let mut truncated = String::new();
+    for c in name.chars() {
+        if truncated.len() + c.len_utf8() > max_len || truncated.push(c).is_err() {
+            break;
+        }
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_name() {
+        assert_eq!(truncate("RMK Keyboard", BLE_NAME_MAX_LEN), "RMK Keyboard");
+        assert_eq!(truncate("RMK Keyboard Pro Max", BLE_NAME_MAX_LEN), "RMK Keyboard Pro");
+        // "é" is 2 bytes and must not be split
+        assert_eq!(truncate("RMK Keyboard Pré", BLE_NAME_MAX_LEN), "RMK Keyboard Pr");
+    }
+
+    #[test]
+    fn test_profile_suffix() {
+        assert_eq!(with_profile_suffix("RMK Kbd", 1), "RMK Kbd #2");
+        assert_eq!(with_profile_suffix("RMK Keyboard Pro", 0), "RMK Keyboard #1");
+        assert_eq!(with_profile_suffix("RMK Keyboard Pro", 9), "RMK Keyboard #10");
+    }
+}
@@ -29,3 +29,16 @@ pub(crate) fn get_serial_number() -> &'static str {
 
     serial.as_str()
 }
+
+/// Enter System OFF, the chip resets when a pin with sense configured changes.
+#[cfg(all(
+    feature = "async_matrix",
+    not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble"))
+))]
+pub(crate) fn system_off() -> ! {
+    embassy_nrf::pac::POWER.systemoff().write(|w| w.set_systemoff(true));
+    // In debug mode the chip only emulates System OFF and keeps running
+    loop {
+        cortex_m::asm::wfe();
+    }
+}
@@ -9,6 +9,8 @@ use rmk_types::keycode::HidKeyCode;
 #[cfg(feature = "passkey_entry")]
 use trouble_host::prelude::{DefaultPacketPool, GattConnection, GattConnectionEvent};
 
+use crate::event::{PasskeyEntryEvent, publish_event};
+
 #[doc(hidden)]
 pub fn passkey_entry_enabled() -> bool {
     #[cfg(feature = "passkey_entry")]
@@ -108,11 +110,22 @@ pub static PASSKEY_RESPONSE: Signal<crate::RawMutex, Option<u32>> = Signal::new(
 pub fn begin_passkey_entry_session() {
     PASSKEY_RESPONSE.reset();
     PASSKEY_ENTRY_MODE.store(true, Ordering::Release);
+    publish_event(PasskeyEntryEvent {
+        active: true,
+        digits: 0,
+    });
 }
 
 /// End the current passkey entry session.
 pub fn end_passkey_entry_session() {
-    PASSKEY_ENTRY_MODE.store(false, Ordering::Release);
+    // The cleanup guard ends sessions that were never started, only report real transitions
+    if PASSKEY_ENTRY_MODE.load(Ordering::Acquire) {
+        PASSKEY_ENTRY_MODE.store(false, Ordering::Release);
+        publish_event(PasskeyEntryEvent {
+            active: false,
+            digits: 0,
+        });
+    }
 }
 
 /// Drop guard that clears passkey mode whenever the surrounding task exits.
@@ -312,7 +325,7 @@ pub fn hid_keycode_to_digit(k: HidKeyCode) -> Option<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
 
     #[test]
     fn test_passkey_entry_state_basic() {
@@ -1,5 +1,7 @@
 //! Manage BLE profiles and bonding information
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 #[cfg(feature = "_ble")]
 use bt_hci::{cmd::le::LeSetPhy, controller::ControllerCmdAsync};
 use embassy_futures::select::{Either3, select3};
@@ -7,7 +9,7 @@ use embassy_sync::signal::Signal;
 use trouble_host::prelude::*;
 use trouble_host::{BondInformation, LongTermKey};
 #[cfg(feature = "storage")]
-use {crate::channel::FLASH_CHANNEL, crate::storage::FLASH_OPERATION_FINISHED};
+use {crate::channel::FLASH_CHANNEL, crate::storage::BLE_PROFILE_SAVED};
 
 use super::ble_server::CCCD_TABLE_SIZE;
 use crate::NUM_BLE_PROFILE;
@@ -16,6 +18,16 @@ use crate::state::{current_profile, set_ble_profile};
 
 pub(crate) static UPDATED_PROFILE: Signal<crate::RawMutex, ProfileInfo> = Signal::new();
 pub(crate) static UPDATED_CCCD_TABLE: Signal<crate::RawMutex, heapless::Vec<u8, CCCD_TABLE_SIZE>> = Signal::new();
+/// Whether the active profile has a bonded host, kept in sync by `ProfileManager::update_stack_bonds`
+static ACTIVE_PROFILE_BONDED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the active profile has a bonded host.
+///
+/// Advertising on a bonded profile is a reconnection attempt, otherwise the
+/// keyboard is waiting for a new host to pair.
+pub(crate) fn active_profile_bonded() -> bool {
+    ACTIVE_PROFILE_BONDED.load(Ordering::Relaxed)
+}
 
 /// BLE profile info
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -76,6 +88,7 @@ pub(crate) enum BleProfileAction {
     Previous,
     Next,
     ClearBond,
+    ClearAllBonds,
 }
 
 /// Manage BLE profiles and bonding information
@@ -156,7 +169,9 @@ where
             }
         }
 
-        if let Some(info) = self.active_bond_info() {
+        let active_bond_info = self.active_bond_info();
+        ACTIVE_PROFILE_BONDED.store(active_bond_info.is_some(), Ordering::Relaxed);
+        if let Some(info) = active_bond_info {
             debug!("Add bond info of profile {}: {:?}", info.slot_num, info);
             if let Err(e) = self.stack.add_bond_information(info.info) {
                 debug!("Add bond info error: {:?}", e);
@@ -245,6 +260,28 @@ where
             .await;
     }
 
+    /// Clear bonding information of all profiles
+    pub(crate) async fn clear_all_bonds(&mut self) {
+        info!("Clearing bonding information on all profiles");
+
+        for bond_info in self.bonded_devices.iter_mut() {
+            bond_info.removed = true;
+        }
+
+        self.update_stack_bonds();
+
+        #[cfg(feature = "storage")]
+        for slot_num in 0..NUM_BLE_PROFILE as u8 {
+            // Clear slots one by one, the caller waits for the last one to finish
+            if slot_num > 0 {
+                BLE_PROFILE_SAVED.wait().await;
+            }
+            FLASH_CHANNEL
+                .send(crate::storage::FlashOperationMessage::ClearSlot(slot_num))
+                .await;
+        }
+    }
+
     /// Switch to the specified profile, return true if the profile is switched
     pub(crate) async fn switch_profile(&mut self, profile: u8) -> bool {
         let current = current_profile();
@@ -284,7 +321,7 @@ where
             {
                 Either3::First(action) => {
                     #[cfg(feature = "storage")]
-                    FLASH_OPERATION_FINISHED.reset();
+                    BLE_PROFILE_SAVED.reset();
                     match action {
                         BleProfileAction::Switch(profile) => {
                             if !self.switch_profile(profile).await {
@@ -300,20 +337,27 @@ where
                                 profile - 1
                             };
 
-                            self.switch_profile(profile).await;
+                            if !self.switch_profile(profile).await {
+                                continue;
+                            }
                         }
                         BleProfileAction::Next => {
                             let mut profile = current_profile() + 1;
                             profile %= NUM_BLE_PROFILE as u8;
 
-                            self.switch_profile(profile).await;
+                            if !self.switch_profile(profile).await {
+                                continue;
+                            }
                         }
                         BleProfileAction::ClearBond => {
                             self.clear_bond(current_profile()).await;
                         }
+                        BleProfileAction::ClearAllBonds => {
+                            self.clear_all_bonds().await;
+                        }
                     }
                     #[cfg(feature = "storage")]
-                    FLASH_OPERATION_FINISHED.wait().await;
+                    BLE_PROFILE_SAVED.wait().await;
                     info!("Update profile done");
                     break;
                 }
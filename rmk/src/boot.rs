@@ -49,3 +49,9 @@ pub(crate) fn reboot_keyboard() {
     #[cfg(feature = "_esp_ble")]
     esp_hal::system::software_reset();
 }
+
+/// Save the storage edits which are still batched, a reset or power off would lose them
+pub(crate) async fn flush_storage() {
+    #[cfg(feature = "storage")]
+    crate::storage::flush().await;
+}
@@ -1,21 +1,22 @@
 //! Exposed channels which can be used to share data across devices & processors
 
 use core::future::poll_fn;
+use core::sync::atomic::Ordering;
 
 use embassy_sync::channel::{Channel, TrySendError};
 #[cfg(feature = "_ble")]
 use embassy_sync::signal::Signal;
 pub use embassy_sync::{blocking_mutex, channel, pubsub, zerocopy_channel};
-use rmk_types::connection::ConnectionType;
+use rmk_types::connection::{ConnectionType, ReportQueueOverflow};
 #[cfg(feature = "_ble")]
 use {crate::ble::profile::BleProfileAction, rmk_types::led_indicator::LedIndicator};
 
 #[cfg(feature = "host")]
 use crate::VIAL_CHANNEL_SIZE;
-use crate::hid::{KeyboardReport, Report};
+use crate::hid::{KeyboardReport, NKRO_ENABLED, NkroKeyboardReport, Report, merge_mouse_report};
 #[cfg(feature = "storage")]
 use crate::{FLASH_CHANNEL_SIZE, storage::FlashOperationMessage};
-use crate::{REPORT_CHANNEL_SIZE, RawMutex};
+use crate::{REPORT_CHANNEL_SIZE, REPORT_QUEUE_OVERFLOW, RawMutex};
 
 type ReportChannel = Channel<RawMutex, Report, REPORT_CHANNEL_SIZE>;
 
@@ -28,51 +29,145 @@ pub(crate) static LED_SIGNAL: Signal<RawMutex, LedIndicator> = Signal::new();
 #[cfg(not(feature = "_no_usb"))]
 pub static USB_REPORT_CHANNEL: ReportChannel = Channel::new();
 
-/// Drained by the BLE HID writer task. Routed through `send_hid_report`.
-#[cfg(feature = "_ble")]
+/// Drained by the BLE HID writer task, or by a custom wireless transport. Routed through `send_hid_report`.
+#[cfg(any(feature = "_ble", feature = "wireless_transport"))]
 pub static BLE_REPORT_CHANNEL: ReportChannel = Channel::new();
 
 fn report_channel(transport: ConnectionType) -> Option<&'static ReportChannel> {
     match transport {
         #[cfg(not(feature = "_no_usb"))]
         ConnectionType::Usb => Some(&USB_REPORT_CHANNEL),
-        #[cfg(feature = "_ble")]
+        #[cfg(any(feature = "_ble", feature = "wireless_transport"))]
         ConnectionType::Ble => Some(&BLE_REPORT_CHANNEL),
         #[allow(unreachable_patterns)]
         _ => None,
     }
 }
 
-fn active_report_channel() -> Option<(ConnectionType, &'static ReportChannel)> {
-    let transport = crate::state::active_transport()?;
-    report_channel(transport).map(|ch| (transport, ch))
+/// Reports generated while no transport is selected are dropped on the floor.
+///
+/// With output mirroring, the report is sent to the active transport first,
+/// then to the mirror without waiting, so that a slow mirror, like BLE next to
+/// USB, doesn't hold the active transport back. When the mirror's queue is
+/// still full, its oldest report is dropped: keyboard, media and system
+/// reports carry the whole state, so the mirror's host still gets the held keys.
+pub(crate) async fn send_hid_report(report: Report) {
+    let Some(transport) = crate::state::active_transport() else {
+        return;
+    };
+    if let Some(ch) = crate::state::mirror_transport().and_then(report_channel) {
+        send_to_transport(transport, report.clone()).await;
+        if let Err(report) = queue_report(ch, report) {
+            debug!("Report queue of the mirror transport full, dropping the oldest report");
+            let _ = ch.try_receive();
+            let _ = ch.try_send(report);
+        }
+    } else {
+        send_to_transport(transport, report).await;
+    }
 }
 
-/// Reports generated while no transport is selected are dropped on the floor.
-pub(crate) async fn send_hid_report(mut report: Report) {
-    let Some((transport, ch)) = active_report_channel() else {
+/// Queue `report` for `transport`, waiting for room as long as the transport is still an output.
+async fn send_to_transport(transport: ConnectionType, mut report: Report) {
+    let Some(ch) = report_channel(transport) else {
         return;
     };
 
     loop {
-        match ch.try_send(report) {
+        match queue_report(ch, report) {
             Ok(()) => return,
-            Err(TrySendError::Full(r)) => report = r,
+            Err(r) => report = r,
         }
 
         poll_fn(|cx| ch.poll_ready_to_send(cx)).await;
-        if crate::state::active_transport() != Some(transport) {
+        if crate::state::active_transport() != Some(transport) && crate::state::mirror_transport() != Some(transport) {
             return;
         }
     }
 }
 
+/// Queue `report` without waiting, applying `REPORT_QUEUE_OVERFLOW` when the queue is full.
+/// Gives the report back when there's still no room for it.
+fn queue_report(ch: &ReportChannel, report: Report) -> Result<(), Report> {
+    let report = match ch.try_send(report) {
+        Ok(()) => return Ok(()),
+        Err(TrySendError::Full(r)) => r,
+    };
+    match REPORT_QUEUE_OVERFLOW {
+        // With `Coalesce`, the writers merge the queued mouse motion, see `merge_queued_mouse_reports`
+        ReportQueueOverflow::Block | ReportQueueOverflow::Coalesce => Err(report),
+        ReportQueueOverflow::DropOldest => {
+            warn!("Report queue full, dropping the oldest report");
+            let _ = ch.try_receive();
+            ch.try_send(report).map_err(|TrySendError::Full(r)| r)
+        }
+    }
+}
+
+/// Receiving end of a report channel, for the writer of a transport.
+///
+/// With `merge`, the mouse motion queued behind a mouse report is merged into it, see
+/// [`merge_queued_mouse_reports`]. [`ReportReceiver::next`] is cancel safe.
+pub(crate) struct ReportReceiver<'a> {
+    ch: &'a ReportChannel,
+    merge: bool,
+    /// Report taken from the channel while merging mouse reports, to be sent next
+    pending: Option<Report>,
+}
+
+impl<'a> ReportReceiver<'a> {
+    pub(crate) const fn new(ch: &'a ReportChannel, merge: bool) -> Self {
+        Self {
+            ch,
+            merge,
+            pending: None,
+        }
+    }
+
+    /// Next report to send
+    pub(crate) async fn next(&mut self) -> Report {
+        let mut report = match self.pending.take() {
+            Some(report) => report,
+            None => self.ch.receive().await,
+        };
+        if self.merge {
+            self.pending = merge_queued_mouse_reports(self.ch, &mut report);
+        }
+        report
+    }
+}
+
+/// Merge the mouse motion queued in `ch` behind `report` into it, so that a pointing device
+/// reporting faster than the host takes reports doesn't build up a backlog.
+///
+/// Keyboard reports are never merged, the host would lose the order of the keys. Returns the
+/// first queued report which can't be merged, to be sent next.
+fn merge_queued_mouse_reports(ch: &ReportChannel, report: &mut Report) -> Option<Report> {
+    let Report::MouseReport(mouse_report) = report else {
+        return None;
+    };
+    while let Ok(next) = ch.try_receive() {
+        match next {
+            Report::MouseReport(next) if merge_mouse_report(mouse_report, &next) => {}
+            next => return Some(next),
+        }
+    }
+    None
+}
+
 /// Drops the report when the active transport's queue is full or no
 /// transport is selected. Use for producers where back-pressure would block
 /// the matrix scan (e.g. steno chord output).
 pub(crate) fn try_send_hid_report(report: Report) {
-    if let Some((_, ch)) = active_report_channel() {
-        let _ = ch.try_send(report);
+    if let Some(ch) = crate::state::mirror_transport().and_then(report_channel)
+        && queue_report(ch, report.clone()).is_err()
+    {
+        warn!("Report queue of the mirror transport full, dropping report");
+    }
+    if let Some(ch) = crate::state::active_transport().and_then(report_channel)
+        && queue_report(ch, report).is_err()
+    {
+        warn!("Report queue full, dropping report");
     }
 }
 
@@ -83,6 +178,9 @@ pub(crate) fn clear_and_release_report_channel(transport: ConnectionType) {
     if let Some(ch) = report_channel(transport) {
         ch.clear();
         let _ = ch.try_send(Report::KeyboardReport(KeyboardReport::default()));
+        if NKRO_ENABLED.load(Ordering::Relaxed) {
+            let _ = ch.try_send(Report::NkroKeyboardReport(NkroKeyboardReport::default()));
+        }
     }
 }
 
@@ -136,3 +234,78 @@ pub(crate) fn try_send_host_reply(transport: ConnectionType, reply: [u8; 32]) {
 pub(crate) async fn enqueue_host_request(transport: ConnectionType, data: [u8; 32]) {
     HOST_REQUEST_CHANNEL.send((transport, data)).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::MouseReport;
+
+    use super::*;
+    use crate::simulation::block_on;
+
+    fn mouse(buttons: u8, x: i8, y: i8) -> Report {
+        Report::MouseReport(MouseReport {
+            buttons,
+            x,
+            y,
+            wheel: 0,
+            pan: 0,
+        })
+    }
+
+    #[test]
+    fn test_merge_queued_mouse_reports() {
+        let ch = ReportChannel::new();
+        ch.try_send(mouse(0, 2, -1)).unwrap();
+        ch.try_send(mouse(0, 3, 0)).unwrap();
+        ch.try_send(Report::KeyboardReport(KeyboardReport::default())).unwrap();
+        ch.try_send(mouse(0, 1, 1)).unwrap();
+
+        let mut report = mouse(0, 1, 1);
+        let next = merge_queued_mouse_reports(&ch, &mut report);
+        assert!(matches!(report, Report::MouseReport(MouseReport { x: 6, y: 0, .. })));
+        // The keyboard report stops the merge, the motion after it stays queued
+        assert!(matches!(next, Some(Report::KeyboardReport(_))));
+        assert_eq!(ch.len(), 1);
+
+        // Keyboard reports are sent as they are
+        let mut report = Report::KeyboardReport(KeyboardReport::default());
+        assert!(merge_queued_mouse_reports(&ch, &mut report).is_none());
+        assert_eq!(ch.len(), 1);
+    }
+
+    #[test]
+    fn test_report_receiver() {
+        let ch = ReportChannel::new();
+        let queue = || {
+            ch.try_send(mouse(0, 1, 0)).unwrap();
+            ch.try_send(mouse(0, 2, 0)).unwrap();
+            ch.try_send(Report::KeyboardReport(KeyboardReport::default())).unwrap();
+        };
+
+        queue();
+        let mut receiver = ReportReceiver::new(&ch, true);
+        block_on(async {
+            assert!(matches!(
+                receiver.next().await,
+                Report::MouseReport(MouseReport { x: 3, .. })
+            ));
+            // Taken from the channel while merging, it's still sent
+            assert!(matches!(receiver.next().await, Report::KeyboardReport(_)));
+        });
+
+        // Without merging, every report is sent on its own
+        queue();
+        let mut receiver = ReportReceiver::new(&ch, false);
+        block_on(async {
+            assert!(matches!(
+                receiver.next().await,
+                Report::MouseReport(MouseReport { x: 1, .. })
+            ));
+            assert!(matches!(
+                receiver.next().await,
+                Report::MouseReport(MouseReport { x: 2, .. })
+            ));
+            assert!(matches!(receiver.next().await, Report::KeyboardReport(_)));
+        });
+    }
+}
@@ -20,6 +20,8 @@ pub struct BehaviorConfig {
     pub morse: MorsesConfig,
     pub keyboard_macros: KeyboardMacrosConfig,
     pub mouse_key: MouseKeyConfig,
+    /// Swap Ctrl and GUI in the reports when the host is detected as macOS or iOS
+    pub swap_ctrl_gui_on_mac: bool,
 }
 
 /// Configurations for tap behavior
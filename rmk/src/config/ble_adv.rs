@@ -0,0 +1,44 @@
+use embassy_time::Duration;
+
+/// BLE advertising configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct BleAdvConfig<'a> {
+    /// Advertised name, `DeviceConfig::product_name` if not set.
+    ///
+    /// The name can be changed at runtime with [`crate::ble::name::set_ble_name`],
+    /// a name set at runtime is saved and takes precedence over this one.
+    pub name: Option<&'a str>,
+    /// Append the number of the active profile to the name, e.g. "RMK Keyboard #2",
+    /// so that every profile shows up as a separate device on the host.
+    pub profile_suffix: bool,
+    /// Invite only the bonded host of the active profile to reconnect, with directed advertising,
+    /// before advertising to all hosts. Profiles without a bonded host always advertise to all hosts
+    /// so that a new host can pair.
+    pub directed: bool,
+    /// Keep advertising to the bonded host only, so that other hosts can't connect to a bonded profile.
+    ///
+    /// Hosts using resolvable private addresses can't be advertised to directly, all hosts are invited for them.
+    pub directed_only: bool,
+    /// Stop advertising after this long without a connection. The keyboard sleeps until a key is
+    /// pressed, then advertises again.
+    pub timeout: Duration,
+    /// Power the chip off when advertising times out, instead of sleeping. A key press resets the
+    /// chip, which starts advertising again.
+    ///
+    /// Only nRF52 chips with the `async_matrix` feature support this, because the matrix has to
+    /// wait for a key with pin sense. The chip stays on while a USB host is connected.
+    pub system_off: bool,
+}
+
+impl Default for BleAdvConfig<'_> {
+    fn default() -> Self {
+        Self {
+            name: None,
+            profile_suffix: false,
+            directed: false,
+            directed_only: false,
+            timeout: Duration::from_secs(300),
+            system_off: false,
+        }
+    }
+}
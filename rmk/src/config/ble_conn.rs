@@ -0,0 +1,70 @@
+use embassy_time::Duration;
+
+/// Connection parameters requested from the BLE host.
+///
+/// The host has the final say and may pick other values within its own limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BleConnParams {
+    /// Connection interval, between 7.5ms and 4s in steps of 1.25ms
+    pub interval: Duration,
+    /// Number of connection events the keyboard may skip while it has nothing to send
+    pub latency: u16,
+    /// Time without any packet after which the connection is considered lost
+    pub supervision_timeout: Duration,
+}
+
+impl BleConnParams {
+    /// Shortest interval allowed by BLE, for the lowest input latency.
+    pub const LOW_LATENCY: Self = Self {
+        interval: Duration::from_micros(7500),
+        latency: 30,
+        supervision_timeout: Duration::from_secs(5),
+    };
+
+    /// Longer interval, the radio wakes up less often and the battery lasts longer.
+    pub const POWER_SAVE: Self = Self {
+        interval: Duration::from_millis(30),
+        latency: 30,
+        supervision_timeout: Duration::from_secs(6),
+    };
+}
+
+/// Which parameter set of [`BleConnConfig`] is requested.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BleConnMode {
+    #[default]
+    LowLatency,
+    PowerSave,
+}
+
+/// BLE connection parameter configuration.
+///
+/// The mode can be switched at runtime with [`crate::ble::conn_params::set_conn_mode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BleConnConfig {
+    pub low_latency: BleConnParams,
+    pub power_save: BleConnParams,
+    /// Mode used after startup
+    pub mode: BleConnMode,
+}
+
+impl Default for BleConnConfig {
+    fn default() -> Self {
+        Self {
+            low_latency: BleConnParams::LOW_LATENCY,
+            power_save: BleConnParams::POWER_SAVE,
+            mode: BleConnMode::LowLatency,
+        }
+    }
+}
+
+impl BleConnConfig {
+    pub fn params(&self, mode: BleConnMode) -> BleConnParams {
+        match mode {
+            BleConnMode::LowLatency => self.low_latency,
+            BleConnMode::PowerSave => self.power_save,
+        }
+    }
+}
@@ -11,6 +11,28 @@ pub struct DeviceConfig<'a> {
     pub product_name: &'a str,
     /// Serial number
     pub serial_number: &'a str,
+    /// Derive the serial number from the chip's unique id instead of using `serial_number`,
+    /// so that every board enumerates with its own serial number.
+    ///
+    /// Supported on nRF and ESP32 chips, the serial number is `vial:f64c2b3c:` followed by
+    /// 6 hex digits of the FICR device id or of the MAC address. Other chips fall back to `serial_number`.
+    pub serial_from_chip_id: bool,
+}
+
+impl<'a> DeviceConfig<'a> {
+    /// Serial number reported over USB and BLE
+    pub(crate) fn serial(&self) -> &'a str {
+        #[cfg(feature = "_nrf_ble")]
+        if self.serial_from_chip_id {
+            return crate::ble::nrf::get_serial_number();
+        }
+        #[cfg(feature = "_esp_ble")]
+        if self.serial_from_chip_id {
+            return crate::ble::esp::get_serial_number();
+        }
+
+        self.serial_number
+    }
 }
 
 impl Default for DeviceConfig<'_> {
@@ -21,6 +43,7 @@ impl Default for DeviceConfig<'_> {
             manufacturer: "RMK",
             product_name: "RMK Keyboard",
             serial_number: "vial:f64c2b3c:000001",
+            serial_from_chip_id: true,
         }
     }
 }
@@ -1,9 +1,15 @@
 mod behavior;
 #[cfg(feature = "_ble")]
+mod ble_adv;
+#[cfg(feature = "_ble")]
 mod ble_battery;
+#[cfg(feature = "_ble")]
+mod ble_conn;
 mod device;
 mod positional;
 mod storage;
+#[cfg(not(feature = "_no_usb"))]
+mod usb;
 mod vial;
 
 pub use behavior::{
@@ -11,10 +17,16 @@ pub use behavior::{
     OneShotModifiersConfig, TapConfig,
 };
 #[cfg(feature = "_ble")]
+pub use ble_adv::BleAdvConfig;
+#[cfg(feature = "_ble")]
 pub use ble_battery::BleBatteryConfig;
+#[cfg(feature = "_ble")]
+pub use ble_conn::{BleConnConfig, BleConnMode, BleConnParams};
 pub use device::DeviceConfig;
-pub use positional::{Hand, PositionalConfig};
+pub use positional::{Hand, KeyTapHold, PositionalConfig};
 pub use storage::StorageConfig;
+#[cfg(not(feature = "_no_usb"))]
+pub use usb::UsbConfig;
 pub use vial::VialConfig;
 
 /// Internal configurations for RMK keyboard.
@@ -27,4 +39,10 @@ pub struct RmkConfig<'a> {
     pub storage_config: StorageConfig,
     #[cfg(feature = "_ble")]
     pub ble_battery_config: BleBatteryConfig<'a>,
+    #[cfg(feature = "_ble")]
+    pub ble_conn_config: BleConnConfig,
+    #[cfg(feature = "_ble")]
+    pub ble_adv_config: BleAdvConfig<'a>,
+    #[cfg(not(feature = "_no_usb"))]
+    pub usb_config: UsbConfig,
 }
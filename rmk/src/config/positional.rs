@@ -1,3 +1,5 @@
+use rmk_types::morse::MorseProfile;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 #[derive(Default)]
@@ -11,7 +13,7 @@ pub enum Hand {
 
 /// Configuration that's only related to the key's position.
 ///
-/// Now only the hand information is included.
+/// Now the hand and the tap-hold configuration of the keys are included.
 /// In the future more fields can be added here for the future configurator GUI, such as
 /// - physical key position and orientation
 /// - key size,
@@ -22,19 +24,42 @@ pub enum Hand {
 #[derive(Debug)]
 pub struct PositionalConfig<const ROW: usize, const COL: usize> {
     pub hand: [[Hand; COL]; ROW],
+    /// Set with [`PositionalConfig::with_tap_hold`]
+    pub(crate) tap_hold: [[KeyTapHold; COL]; ROW],
+}
+
+/// Tap-hold configuration of the key at a position, used by the tap-hold keys placed there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyTapHold {
+    /// Profile used for the fields which the profile of the action doesn't set
+    pub profile: MorseProfile,
+    /// Send the tap action when the key is released after a hold, if no other key was pressed meanwhile
+    pub retro_tap: bool,
+    /// Pressing the key again within this time after a tap holds the tap action, 0 disables it
+    pub quick_tap_ms: u16,
 }
 
 impl<const ROW: usize, const COL: usize> Default for PositionalConfig<ROW, COL> {
     fn default() -> Self {
         Self {
             hand: [[Hand::default(); COL]; ROW],
+            tap_hold: [[KeyTapHold::default(); COL]; ROW],
         }
     }
 }
 
 impl<const ROW: usize, const COL: usize> PositionalConfig<ROW, COL> {
     pub fn new(hand: [[Hand; COL]; ROW]) -> Self {
-        Self { hand }
+        Self {
+            hand,
+            tap_hold: [[KeyTapHold::default(); COL]; ROW],
+        }
+    }
+
+    /// Set the tap-hold configuration of the keys
+    pub fn with_tap_hold(mut self, tap_hold: [[KeyTapHold; COL]; ROW]) -> Self {
+        self.tap_hold = tap_hold;
+        self
     }
 }
 
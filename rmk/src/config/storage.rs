@@ -8,6 +8,9 @@ pub struct StorageConfig {
     pub num_sectors: u8,
     pub clear_storage: bool,
     pub clear_layout: bool,
+    /// Boot with the default keymap and behavior config, ignoring the ones in the storage.
+    /// Changes made while booted in safe mode are still saved.
+    pub safe_mode: bool,
 }
 
 impl Default for StorageConfig {
@@ -17,6 +20,18 @@ impl Default for StorageConfig {
             num_sectors: 2,
             clear_storage: false,
             clear_layout: false,
+            safe_mode: false,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Storage in `num_sectors` sectors from `start_addr`, the other fields are the default ones
+    pub fn new(start_addr: usize, num_sectors: u8) -> Self {
+        Self {
+            start_addr,
+            num_sectors,
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,40 @@
+/// HID interfaces exposed by [`UsbTransport`](crate::usb::UsbTransport)
+///
+/// The keyboard interface is always present. Mouse, consumer, system control and battery
+/// reports share one interface whose report descriptor is assembled from the
+/// enabled reports, it's left out completely if none of them is enabled. The NKRO and
+/// digitizer interfaces are added when enabled here.
+/// Vial, steno and raw HID interfaces are enabled by their cargo features.
+#[derive(Clone, Copy, Debug)]
+pub struct UsbConfig {
+    /// Mouse report, used by mouse keys and pointing devices
+    pub mouse: bool,
+    /// Consumer control report, used by media keys
+    pub consumer: bool,
+    /// System control report, used by power, sleep and wake keys
+    pub system_control: bool,
+    /// Polling interval of the keyboard, mouse, consumer and system control
+    /// endpoints in milliseconds, 1 polls at 1000Hz
+    pub poll_interval_ms: u8,
+    /// Battery strength report, so the host shows the battery level while the keyboard is
+    /// plugged in. Only reported on BLE keyboards, which read the battery level
+    pub battery: bool,
+    /// N-key rollover interface, for the keys held beyond the 6 of the keyboard report
+    pub nkro: bool,
+    /// Pen digitizer interface, for [`DigitizerReport`](crate::hid::DigitizerReport)s sent by custom input devices
+    pub digitizer: bool,
+}
+
+impl Default for UsbConfig {
+    fn default() -> Self {
+        Self {
+            mouse: true,
+            consumer: true,
+            system_control: true,
+            poll_interval_ms: 1,
+            battery: false,
+            nkro: false,
+            digitizer: false,
+        }
+    }
+}
@@ -68,6 +68,8 @@ impl<const ROW: usize, const COL: usize> DebouncerTrait<ROW, COL> for DefaultDeb
                 // Detected a new potential state change.
                 // Record the start time and enter the debouncing state.
                 *counter = DebounceCounter::Debouncing(now);
+                #[cfg(feature = "latency")]
+                crate::latency::record_edge(row_idx, col_idx);
                 DebounceState::InProgress
             }
             DebounceCounter::Debouncing(start_time) => {
@@ -52,6 +52,8 @@ impl<const ROW: usize, const COL: usize> DebouncerTrait<ROW, COL> for FastDeboun
             self.last_ms = Instant::now();
             // Change debouncing state
             self.debouncing[col_idx][row_idx] = true;
+            #[cfg(feature = "latency")]
+            crate::latency::record_edge(row_idx, col_idx);
             DebounceState::Debounced
         } else {
             DebounceState::Ignored
@@ -0,0 +1,136 @@
+//! Status report typed by the `Diagnostics` key.
+//!
+//! Pressing `kbctrl!(Diagnostics)` types a line like
+//!
+//! ```text
+//! rmk 0.8.2, battery 87%, scan 2451Hz, profile 1 connected, usb configured, output ble
+//! ```
+//!
+//! into the focused text field of the host, so a user can paste the state of their keyboard
+//! into a bug report without any host tool. The text is typed with the US layout.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_time::Instant;
+use rmk_types::ble::BleState;
+use rmk_types::connection::{ConnectionType, UsbState};
+
+/// Longest status report
+pub(crate) const REPORT_LEN: usize = 128;
+
+/// Full matrix scans counted in the current second
+static SCANS: AtomicU32 = AtomicU32::new(0);
+/// Start of the current second, in ms since boot, wrapping
+static WINDOW_START_MS: AtomicU32 = AtomicU32::new(0);
+/// Scans of the last complete second
+static SCAN_RATE: AtomicU32 = AtomicU32::new(0);
+
+/// Full scans of the matrix per second, during the last second it was scanned continuously.
+///
+/// It's 0 until the matrix has been scanned for a second.
+pub fn scan_rate() -> u32 {
+    SCAN_RATE.load(Ordering::Relaxed)
+}
+
+/// The matrix finished a scan of all its keys.
+///
+/// It's called on every scan, so it doesn't take a lock. Only the matrix task calls it, so loads
+/// and stores are enough, which chips without atomic read-modify-write like the RP2040 have too.
+pub(crate) fn record_scan() {
+    let now = Instant::now().as_millis() as u32;
+    let scans = SCANS.load(Ordering::Relaxed) + 1;
+    let elapsed = now.wrapping_sub(WINDOW_START_MS.load(Ordering::Relaxed));
+    if elapsed >= 1000 {
+        // A matrix waiting for a key press doesn't scan, don't count the wait
+        if elapsed < 2000 {
+            SCAN_RATE.store(scans, Ordering::Relaxed);
+        }
+        WINDOW_START_MS.store(now, Ordering::Relaxed);
+        SCANS.store(0, Ordering::Relaxed);
+    } else {
+        SCANS.store(scans, Ordering::Relaxed);
+    }
+}
+
+/// Status report typed by the `Diagnostics` key, in lowercase ASCII.
+pub(crate) fn status_report() -> heapless::String<REPORT_LEN> {
+    let mut report = heapless::String::new();
+    let _ = write!(report, "rmk {}", env!("CARGO_PKG_VERSION"));
+
+    #[cfg(feature = "_ble")]
+    {
+        use rmk_types::battery::{BatteryStatus, ChargeState};
+        let _ = match crate::input_device::battery::current_battery_status() {
+            BatteryStatus::Available {
+                level: Some(level),
+                charge_state,
+            } => write!(
+                report,
+                ", battery {}%{}",
+                level,
+                if charge_state == ChargeState::Charging {
+                    " charging"
+                } else {
+                    ""
+                }
+            ),
+            BatteryStatus::Available { level: None, .. } => write!(report, ", battery full"),
+            BatteryStatus::Unavailable => write!(report, ", battery unknown"),
+        };
+    }
+
+    let _ = write!(report, ", scan {}Hz", scan_rate());
+
+    let status = crate::state::current_connection_status();
+    #[cfg(feature = "_ble")]
+    let _ = write!(
+        report,
+        ", profile {} {}",
+        status.ble.profile,
+        match status.ble.state {
+            BleState::Advertising => "advertising",
+            BleState::Connected => "connected",
+            BleState::Inactive => "inactive",
+        }
+    );
+    #[cfg(not(feature = "_no_usb"))]
+    let _ = write!(
+        report,
+        ", usb {}",
+        match status.usb {
+            UsbState::Disabled => "disabled",
+            UsbState::Enabled => "enabled",
+            UsbState::Configured => "configured",
+            UsbState::Suspended => "suspended",
+            UsbState::PowerOnly => "power only",
+        }
+    );
+    let _ = write!(
+        report,
+        ", output {}",
+        match crate::state::active_transport() {
+            Some(ConnectionType::Usb) => "usb",
+            Some(ConnectionType::Ble) => "ble",
+            None => "none",
+        }
+    );
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_report_is_typeable() {
+        let report = status_report();
+        assert!(report.starts_with("rmk "));
+        assert!(report.contains(", output "));
+        assert!(
+            report
+                .bytes()
+                .all(|c| rmk_types::keycode::from_ascii(c).0 != rmk_types::keycode::HidKeyCode::No)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! User-pushed display content.
+//!
+//! User code can override whatever the active [`DisplayRenderer`](super::DisplayRenderer)
+//! draws by calling [`set_display_content`] from any task. The
+//! [`DisplayProcessor`](super::DisplayProcessor) picks the content up on its next
+//! loop iteration and keeps showing it until [`DisplayContent::Default`] is pushed.
+
+use embassy_sync::signal::Signal;
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::{Baseline, Text};
+
+use crate::RawMutex;
+
+/// Maximum length of a [`DisplayContent::Text`] string.
+pub const DISPLAY_TEXT_MAX_LEN: usize = 64;
+
+pub(crate) static DISPLAY_CONTENT: Signal<RawMutex, DisplayContent> = Signal::new();
+
+/// A 1-bit-per-pixel image stored in flash.
+///
+/// `data` is row-major with each row padded to a whole byte, MSB first — the
+/// layout of [`ImageRaw<BinaryColor>`], which is what most "image to C array"
+/// converters emit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageAsset {
+    pub data: &'static [u8],
+    pub width: u32,
+    /// Top-left corner of the image on the display.
+    pub position: Point,
+}
+
+impl ImageAsset {
+    pub const fn new(data: &'static [u8], width: u32) -> Self {
+        Self {
+            data,
+            width,
+            position: Point::zero(),
+        }
+    }
+
+    /// Place the image at `position` instead of the top-left corner.
+    pub const fn at(mut self, position: Point) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Draw the image, mapping `On` pixels to the display's "on" color.
+    pub fn draw<C, D>(&self, display: &mut D)
+    where
+        C: PixelColor + From<BinaryColor>,
+        D: DrawTarget<Color = C>,
+    {
+        let raw = ImageRaw::<BinaryColor>::new(self.data, self.width);
+        Image::new(&raw, self.position)
+            .draw(&mut display.color_converted())
+            .ok();
+    }
+}
+
+/// Content pushed by user code via [`set_display_content`].
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum DisplayContent {
+    /// Hand the display back to the configured renderer.
+    #[default]
+    Default,
+    /// Blank the display.
+    Clear,
+    /// Show a text message, starting from the top-left corner. `\n` starts a new line.
+    Text(heapless::String<DISPLAY_TEXT_MAX_LEN>),
+    /// Show a bitmap image.
+    Image(ImageAsset),
+}
+
+impl DisplayContent {
+    /// Build a [`DisplayContent::Text`], truncating `text` to [`DISPLAY_TEXT_MAX_LEN`] bytes.
+    pub fn text(text: &str) -> Self {
+        let mut s = heapless::String::new();
+        for c in text.chars() {
+            if s.push(c).is_err() {
+                break;
+            }
+        }
+        DisplayContent::Text(s)
+    }
+
+    /// Draw the content onto `display`, clearing it first.
+    ///
+    /// [`DisplayContent::Default`] draws nothing; the processor renders it
+    /// with its renderer instead.
+    pub(crate) fn draw<C, D>(&self, display: &mut D)
+    where
+        C: PixelColor + From<BinaryColor>,
+        D: DrawTarget<Color = C>,
+    {
+        display.clear(BinaryColor::Off.into()).ok();
+        match self {
+            DisplayContent::Default | DisplayContent::Clear => {}
+            DisplayContent::Text(text) => {
+                let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On.into());
+                Text::with_baseline(text, Point::zero(), style, Baseline::Top)
+                    .draw(display)
+                    .ok();
+            }
+            DisplayContent::Image(image) => image.draw(display),
+        }
+    }
+}
+
+/// Replace what the display shows with `content`.
+///
+/// Can be called from any task. Pushing [`DisplayContent::Default`] returns
+/// the display to the configured renderer. If several updates are pushed
+/// before the display task runs, only the latest one is drawn.
+pub fn set_display_content(content: DisplayContent) {
+    DISPLAY_CONTENT.signal(content);
+}
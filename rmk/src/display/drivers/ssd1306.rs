@@ -4,6 +4,7 @@ use display_interface::AsyncWriteOnlyDataCommand;
 use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::prelude::*;
 use ssd1306::mode::{BufferedGraphicsModeAsync, DisplayConfigAsync};
+use ssd1306::prelude::Brightness;
 use ssd1306::size::DisplaySizeAsync;
 
 use super::super::DisplayDriver;
@@ -21,4 +22,11 @@ where
     async fn flush(&mut self) {
         ssd1306::Ssd1306Async::flush(self).await.ok();
     }
+
+    async fn set_brightness(&mut self, brightness: u8) {
+        // Keep the default precharge period, only the contrast follows the brightness
+        ssd1306::Ssd1306Async::set_brightness(self, Brightness::custom(2, brightness))
+            .await
+            .ok();
+    }
 }
@@ -7,9 +7,15 @@
 //!
 //! The processor is generic over a [`DisplayDriver`] and a [`DisplayRenderer`].
 //! The built-in [`LogoRenderer`] displays the RMK logo on startup.  For a
-//! full-featured keyboard status display, use [`OledRenderer`] instead.  To draw your own content implement [`DisplayRenderer<C>`]
+//! full-featured keyboard status display, use [`OledRenderer`] instead, or [`WidgetRenderer`]
+//! to place the status widgets yourself.  To draw your own content implement [`DisplayRenderer<C>`]
 //! for your color type and pass it via [`DisplayProcessor::with_renderer`].
 //!
+//! Frame-based animations (e.g. bongo cat) don't need a hand-written renderer:
+//! [`AnimationRenderer`] takes a callback that picks an [`ImageAsset`] per frame.
+//! To temporarily replace the renderer's output from user code — a message, an
+//! image, or a blank screen — call [`set_display_content`] from any task.
+//!
 //! # Feature flags
 //!
 //! - `display` — base traits and processor (requires `embedded-graphics`)
@@ -53,19 +59,22 @@
 //! run_all!(matrix, oled);
 //! ```
 
+mod content;
 pub mod drivers;
 mod renderers;
 
+pub use content::{DISPLAY_TEXT_MAX_LEN, DisplayContent, ImageAsset, set_display_content};
 #[cfg(feature = "oled_async")]
 pub use display_interface_i2c;
 use embassy_futures::select::{Either, Either3, select, select3};
 use embassy_time::{Duration, Instant, Ticker, Timer};
+use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::prelude::*;
 #[cfg(feature = "lcd_async")]
 pub use lcd_async;
 #[cfg(feature = "oled_async")]
 pub use oled_async;
-pub use renderers::{LogoRenderer, OledRenderer};
+pub use renderers::{AnimationRenderer, LogoRenderer, OledRenderer, PlacedWidget, Widget, WidgetRenderer};
 use rmk_macro::processor;
 #[cfg(feature = "_ble")]
 use rmk_types::ble::BleStatus;
@@ -74,16 +83,20 @@ use rmk_types::modifier::ModifierCombination;
 pub use ssd1306;
 
 use crate::core_traits::Runnable;
+use crate::display::content::DISPLAY_CONTENT;
+#[cfg(any(feature = "ambient_light", feature = "split"))]
+use crate::event::BrightnessEvent;
 #[cfg(feature = "_ble")]
 use crate::event::ConnectionStatusChangeEvent;
 #[cfg(all(feature = "split", feature = "_ble"))]
 use crate::event::PeripheralBatteryEvent;
 use crate::event::{
-    BatteryStatusEvent, KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, ModifierEvent, SleepStateEvent,
-    WpmUpdateEvent,
+    BatteryStatusEvent, KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, ModifierEvent, PowerStateEvent,
+    SleepStateEvent, WpmUpdateEvent, publish_event,
 };
 #[cfg(feature = "split")]
 use crate::event::{CentralConnectedEvent, PeripheralConnectedEvent};
+use crate::event::EventSubscriber;
 use crate::processor::Processor;
 
 /// Snapshot of keyboard state passed to renderers on every redraw.
@@ -171,6 +184,13 @@ pub trait DisplayDriver: DrawTarget {
     fn init(&mut self) -> impl core::future::Future<Output = ()>;
     /// Flush the framebuffer to the display.
     fn flush(&mut self) -> impl core::future::Future<Output = ()>;
+    /// Set the display brightness, 0 is the dimmest and 255 the brightest.
+    ///
+    /// Called on every [`BrightnessEvent`](crate::event::BrightnessEvent).
+    /// Does nothing by default.
+    fn set_brightness(&mut self, _brightness: u8) -> impl core::future::Future<Output = ()> {
+        async {}
+    }
 }
 
 /// Trait for custom display renderers.
@@ -223,19 +243,27 @@ pub trait DisplayRenderer<C: PixelColor> {
 ///
 /// - `D` — display driver, must implement [`DisplayDriver`].
 /// - `R` — the renderer, defaults to [`LogoRenderer`].
+///
+/// The display color must be convertible from [`BinaryColor`] so that content
+/// pushed with [`set_display_content`] can be drawn on any display; this holds
+/// for all `embedded-graphics` color types.
 #[processor(subscribe = [KeyboardEvent, LayerChangeEvent, WpmUpdateEvent, LedIndicatorEvent, ModifierEvent, BatteryStatusEvent, SleepStateEvent])]
 #[cfg_attr(feature = "_ble", processor(subscribe = [ConnectionStatusChangeEvent]))]
 #[cfg_attr(feature = "split", processor(subscribe = [PeripheralConnectedEvent, CentralConnectedEvent]))]
 #[cfg_attr(all(feature = "split", feature = "_ble"), processor(subscribe = [PeripheralBatteryEvent]))]
+#[cfg_attr(any(feature = "ambient_light", feature = "split"), processor(subscribe = [BrightnessEvent]))]
 #[::rmk::macros::runnable_generated]
 pub struct DisplayProcessor<D, R = LogoRenderer>
 where
     D: DisplayDriver,
+    D::Color: From<BinaryColor>,
     R: DisplayRenderer<D::Color>,
 {
     display: D,
     renderer: R,
     ctx: RenderContext,
+    /// Content pushed by user code, overrides the renderer unless `Default`.
+    content: DisplayContent,
     initialized: bool,
     last_render: Instant,
     pending_render: bool,
@@ -248,6 +276,7 @@ where
 impl<D> DisplayProcessor<D, LogoRenderer>
 where
     D: DisplayDriver,
+    D::Color: From<BinaryColor>,
     LogoRenderer: DisplayRenderer<D::Color>,
 {
     /// Create a new display processor with the built-in [`LogoRenderer`].
@@ -263,6 +292,7 @@ where
 impl<D, R> DisplayProcessor<D, R>
 where
     D: DisplayDriver,
+    D::Color: From<BinaryColor>,
     R: DisplayRenderer<D::Color>,
 {
     /// Create a new display processor with a custom [`DisplayRenderer`].
@@ -274,6 +304,7 @@ where
             display,
             renderer,
             ctx: RenderContext::default(),
+            content: DisplayContent::Default,
             initialized: false,
             last_render: Instant::from_ticks(0),
             pending_render: false,
@@ -300,6 +331,17 @@ where
         self
     }
 
+    /// Handle a run-loop wake-up: a subscribed event or content from [`set_display_content`].
+    async fn handle_input(&mut self, input: Either<<Self as Processor>::Event, DisplayContent>) {
+        match input {
+            Either::First(event) => self.process(event).await,
+            Either::Second(content) => {
+                self.content = content;
+                self.render().await;
+            }
+        }
+    }
+
     /// Periodic poll — drives animations even when no events arrive.
     async fn poll(&mut self) {
         self.pending_render = true;
@@ -334,7 +376,12 @@ where
             self.initialized = true;
         }
 
-        self.renderer.render(&self.ctx, &mut self.display);
+        match &self.content {
+            DisplayContent::Default => self.renderer.render(&self.ctx, &mut self.display),
+            // Keep the screen dark while sleeping, like the built-in renderers do.
+            _ if self.ctx.sleeping => DisplayContent::Clear.draw(&mut self.display),
+            content => content.draw(&mut self.display),
+        }
         self.ctx.key_press_latch = false;
         self.display.flush().await;
 
@@ -376,6 +423,9 @@ where
     }
 
     async fn on_sleep_state_event(&mut self, event: SleepStateEvent) {
+        if self.ctx.sleeping != event.0 {
+            publish_event(PowerStateEvent::Lighting(!event.0));
+        }
         self.ctx.sleeping = event.0;
         self.render().await;
     }
@@ -407,15 +457,20 @@ where
         }
         self.render().await;
     }
+
+    #[cfg(any(feature = "ambient_light", feature = "split"))]
+    async fn on_brightness_event(&mut self, event: BrightnessEvent) {
+        self.display.set_brightness(event.0).await;
+    }
 }
 
 impl<D, R> Runnable for DisplayProcessor<D, R>
 where
     D: DisplayDriver,
+    D::Color: From<BinaryColor>,
     R: DisplayRenderer<D::Color>,
 {
     async fn run(&mut self) -> ! {
-        use crate::event::EventSubscriber;
         let mut sub = <Self as Processor>::subscriber();
 
         // Prime from current state after subscribing, so the first render
@@ -430,41 +485,43 @@ where
         let mut ticker = self.render_interval.map(Ticker::every);
 
         loop {
+            // Wakes on the next subscribed event or user content update.
+            let input = select(sub.next_event(), DISPLAY_CONTENT.wait());
             if !self.ctx.sleeping {
                 match (ticker.as_mut(), self.next_render_wait()) {
                     // Polling enabled and a redraw is pending: wait for whichever
                     // happens first — the next animation tick, the deferred redraw,
-                    // or a new event.
+                    // a new event or new user content.
                     (Some(ticker), Some(wait)) => {
-                        match select3(ticker.next(), Timer::after(wait), sub.next_event()).await {
+                        match select3(ticker.next(), Timer::after(wait), input).await {
                             Either3::First(_) => self.poll().await,
                             Either3::Second(_) => self.render().await,
-                            Either3::Third(event) => self.process(event).await,
+                            Either3::Third(input) => self.handle_input(input).await,
                         }
                     }
                     // Polling enabled and nothing pending: only animation ticks or
                     // new events can wake the loop.
-                    (Some(ticker), None) => match select(ticker.next(), sub.next_event()).await {
+                    (Some(ticker), None) => match select(ticker.next(), input).await {
                         Either::First(_) => self.poll().await,
-                        Either::Second(event) => self.process(event).await,
+                        Either::Second(input) => self.handle_input(input).await,
                     },
                     // Event-driven mode with a deferred redraw: wait until the
                     // rate-limit window closes, unless a new event arrives first.
-                    (None, Some(wait)) => match select(Timer::after(wait), sub.next_event()).await {
+                    (None, Some(wait)) => match select(Timer::after(wait), input).await {
                         Either::First(_) => self.render().await,
-                        Either::Second(event) => self.process(event).await,
+                        Either::Second(input) => self.handle_input(input).await,
                     },
                     // Event-driven mode with nothing pending: just block on events.
                     (None, None) => {
-                        let event = sub.next_event().await;
-                        self.process(event).await;
+                        let input = input.await;
+                        self.handle_input(input).await;
                     }
                 }
             } else {
                 // While sleeping, ignore timers and wait only for state changes.
                 let was_sleeping = self.ctx.sleeping;
-                let event = sub.next_event().await;
-                self.process(event).await;
+                let input = input.await;
+                self.handle_input(input).await;
 
                 if was_sleeping
                     && !self.ctx.sleeping
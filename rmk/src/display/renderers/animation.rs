@@ -0,0 +1,60 @@
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+
+use crate::display::{DisplayRenderer, ImageAsset, RenderContext};
+
+/// Renderer driven by a user animation callback.
+///
+/// On every redraw the callback receives the current [`RenderContext`] and a
+/// frame counter (incremented once per render) and returns the [`ImageAsset`]
+/// to show. Pair it with [`DisplayProcessor::with_render_interval`] so frames
+/// advance even without keyboard events.
+///
+/// # Example — bongo cat
+///
+/// ```rust,ignore
+/// use rmk::display::{AnimationRenderer, DisplayProcessor, ImageAsset};
+///
+/// static IDLE: [ImageAsset; 2] = [ImageAsset::new(&IDLE_0, 128), ImageAsset::new(&IDLE_1, 128)];
+/// static TAP: [ImageAsset; 2] = [ImageAsset::new(&TAP_0, 128), ImageAsset::new(&TAP_1, 128)];
+///
+/// let renderer = AnimationRenderer::new(|ctx, frame| {
+///     let frames = if ctx.key_press_latch { &TAP } else { &IDLE };
+///     frames[frame as usize % frames.len()]
+/// });
+/// let mut oled = DisplayProcessor::with_renderer(display, renderer)
+///     .with_render_interval(Duration::from_millis(200));
+/// ```
+///
+/// [`DisplayProcessor::with_render_interval`]: crate::display::DisplayProcessor::with_render_interval
+pub struct AnimationRenderer<F>
+where
+    F: FnMut(&RenderContext, u32) -> ImageAsset,
+{
+    callback: F,
+    frame: u32,
+}
+
+impl<F> AnimationRenderer<F>
+where
+    F: FnMut(&RenderContext, u32) -> ImageAsset,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback, frame: 0 }
+    }
+}
+
+impl<C, F> DisplayRenderer<C> for AnimationRenderer<F>
+where
+    C: PixelColor + From<BinaryColor>,
+    F: FnMut(&RenderContext, u32) -> ImageAsset,
+{
+    fn render<D: DrawTarget<Color = C>>(&mut self, ctx: &RenderContext, display: &mut D) {
+        display.clear(BinaryColor::Off.into()).ok();
+        if ctx.sleeping {
+            return;
+        }
+        (self.callback)(ctx, self.frame).draw(display);
+        self.frame = self.frame.wrapping_add(1);
+    }
+}
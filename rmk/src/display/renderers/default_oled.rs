@@ -208,7 +208,7 @@ fn draw_status_zone<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, dis
     draw_battery_icon(ctx.battery, display, layout);
 }
 
-fn draw_lock_dots<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, display: &mut D, x: i32, y: i32) {
+pub(super) fn draw_lock_dots<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, display: &mut D, x: i32, y: i32) {
     if ctx.caps_lock {
         Circle::new(Point::new(x, y), LOCK_DOT_DIAMETER)
             .into_styled(FILL)
@@ -351,7 +351,7 @@ fn draw_ble_indicator<D: DrawTarget<Color = BinaryColor>>(connected: bool, displ
 }
 
 /// Draw a small 5x5 checkmark (connected) or cross (disconnected).
-fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(connected: bool, display: &mut D, x: i32, y: i32) {
+pub(super) fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(connected: bool, display: &mut D, x: i32, y: i32) {
     if connected {
         Line::new(Point::new(x, y + 2), Point::new(x + 2, y + 4))
             .into_styled(STROKE)
@@ -374,7 +374,7 @@ fn draw_status_mark<D: DrawTarget<Color = BinaryColor>>(connected: bool, display
 }
 
 /// Returns true when the keyboard considers itself connected.
-fn is_connected(_ctx: &RenderContext) -> bool {
+pub(super) fn is_connected(_ctx: &RenderContext) -> bool {
     // Split + BLE:
     // - peripheral: connected when paired to the central
     // - central: connected when host BLE is connected
@@ -1,6 +1,10 @@
+mod animation;
 mod default_oled;
 pub(crate) mod icons;
 mod logo;
+mod widgets;
 
+pub use animation::AnimationRenderer;
 pub use default_oled::OledRenderer;
 pub use logo::LogoRenderer;
+pub use widgets::{PlacedWidget, Widget, WidgetRenderer};
@@ -0,0 +1,156 @@
+use core::fmt::Write as _;
+
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_5X8;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+use embedded_graphics::text::{Baseline, Text};
+use rmk_types::battery::{BatteryStatus, ChargeState};
+
+use super::default_oled::{draw_lock_dots, draw_status_mark, is_connected};
+use super::icons;
+use crate::display::{DisplayRenderer, RenderContext};
+
+const FONT_STYLE: MonoTextStyle<'_, BinaryColor> = MonoTextStyle::new(&FONT_5X8, BinaryColor::On);
+const STROKE: PrimitiveStyle<BinaryColor> = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+
+const ICON_SZ: i32 = icons::ICON_SIZE as i32;
+const MOD_GAP: i32 = 3;
+
+/// Piece of keyboard state drawn by a [`WidgetRenderer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Widget {
+    /// Active layer, as `L:1`
+    Layer,
+    /// Words per minute, as `WPM:042`
+    Wpm,
+    /// Shift, Ctrl, Alt and GUI icons in a row, the active ones underlined
+    Modifiers,
+    /// A dot for Caps Lock and one below it for Num Lock, when they're on
+    LockKeys,
+    /// Battery level, as `87%`
+    Battery,
+    /// A check mark when the keyboard is connected, a cross otherwise
+    Connection,
+    /// Fixed text
+    Text(&'static str),
+}
+
+/// A [`Widget`] and the top-left corner where it's drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlacedWidget {
+    pub widget: Widget,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Renderer which draws a list of widgets at fixed positions.
+///
+/// It's what `widgets` of `[display]` in `keyboard.toml` generates, for a status screen
+/// laid out by hand without writing a renderer:
+///
+/// ```rust,ignore
+/// use rmk::display::{DisplayProcessor, PlacedWidget, Widget, WidgetRenderer};
+///
+/// static WIDGETS: [PlacedWidget; 2] = [
+///     PlacedWidget { widget: Widget::Layer, x: 0, y: 0 },
+///     PlacedWidget { widget: Widget::Modifiers, x: 0, y: 12 },
+/// ];
+/// let mut oled = DisplayProcessor::with_renderer(display, WidgetRenderer::new(&WIDGETS));
+/// ```
+pub struct WidgetRenderer {
+    widgets: &'static [PlacedWidget],
+}
+
+impl WidgetRenderer {
+    pub const fn new(widgets: &'static [PlacedWidget]) -> Self {
+        Self { widgets }
+    }
+}
+
+impl DisplayRenderer<BinaryColor> for WidgetRenderer {
+    fn render<D: DrawTarget<Color = BinaryColor>>(&mut self, ctx: &RenderContext, display: &mut D) {
+        display.clear(BinaryColor::Off).ok();
+        if ctx.sleeping {
+            return;
+        }
+        for placed in self.widgets {
+            draw_widget(placed, ctx, display);
+        }
+    }
+}
+
+fn draw_widget<D: DrawTarget<Color = BinaryColor>>(placed: &PlacedWidget, ctx: &RenderContext, display: &mut D) {
+    let origin = Point::new(placed.x, placed.y);
+    let mut text: heapless::String<16> = heapless::String::new();
+    match placed.widget {
+        Widget::Layer => {
+            write!(text, "L:{}", ctx.layer).ok();
+        }
+        Widget::Wpm => {
+            write!(text, "WPM:{:03}", ctx.wpm).ok();
+        }
+        Widget::Battery => {
+            match *ctx.battery {
+                BatteryStatus::Available {
+                    charge_state: ChargeState::Charging,
+                    level: Some(pct),
+                } => write!(text, "{}%+", pct).ok(),
+                BatteryStatus::Available {
+                    charge_state: ChargeState::Charging,
+                    level: None,
+                } => write!(text, "CHG").ok(),
+                BatteryStatus::Available { level: Some(pct), .. } => write!(text, "{}%", pct).ok(),
+                BatteryStatus::Available { level: None, .. } => write!(text, "FULL").ok(),
+                BatteryStatus::Unavailable => write!(text, "N/A").ok(),
+            };
+        }
+        Widget::Text(s) => {
+            Text::with_baseline(s, origin, FONT_STYLE, Baseline::Top)
+                .draw(display)
+                .ok();
+            return;
+        }
+        Widget::Modifiers => {
+            draw_modifiers(ctx, display, origin);
+            return;
+        }
+        Widget::LockKeys => {
+            draw_lock_dots(ctx, display, placed.x, placed.y);
+            return;
+        }
+        Widget::Connection => {
+            draw_status_mark(is_connected(ctx), display, placed.x, placed.y);
+            return;
+        }
+    }
+    Text::with_baseline(&text, origin, FONT_STYLE, Baseline::Top)
+        .draw(display)
+        .ok();
+}
+
+fn draw_modifiers<D: DrawTarget<Color = BinaryColor>>(ctx: &RenderContext, display: &mut D, origin: Point) {
+    let m = ctx.modifiers;
+    let mods: [(&[u8; 8], bool); 4] = [
+        (&icons::SHIFT, m.left_shift() || m.right_shift()),
+        (&icons::CTRL, m.left_ctrl() || m.right_ctrl()),
+        (&icons::ALT, m.left_alt() || m.right_alt()),
+        (&icons::GUI, m.left_gui() || m.right_gui()),
+    ];
+
+    for (i, (icon_data, active)) in mods.iter().enumerate() {
+        let x = origin.x + i as i32 * (ICON_SZ + MOD_GAP);
+        let raw: ImageRaw<BinaryColor> = ImageRaw::new(*icon_data, icons::ICON_SIZE);
+        Image::new(&raw, Point::new(x, origin.y)).draw(display).ok();
+
+        if *active {
+            let underline_y = origin.y + ICON_SZ + 1;
+            Line::new(Point::new(x, underline_y), Point::new(x + ICON_SZ - 1, underline_y))
+                .into_styled(STROKE)
+                .draw(display)
+                .ok();
+        }
+    }
+}
@@ -0,0 +1,697 @@
+//! 2.4 GHz proprietary dongle mode over Enhanced ShockBurst (ESB).
+//!
+//! The keyboard talks to an RMK based USB dongle over Nordic's ESB protocol
+//! instead of BLE. ESB has no connection events, so a key press is on the air
+//! as soon as it's scanned. Every packet is acknowledged by the dongle and
+//! retransmitted if the ACK doesn't arrive. When a channel keeps failing, the
+//! keyboard hops to the next channel of [`EsbConfig::channels`], and the
+//! dongle follows when it doesn't hear anything for a while.
+//!
+//! The link reuses the split keyboard protocol: the keyboard runs like a split
+//! peripheral with [`run_esb_keyboard`], and the dongle runs the keymap and
+//! USB like a split central, with [`run_esb_dongle`] as its peripheral
+//! manager. Messages from the dongle to the keyboard, like the LED indicator,
+//! are carried in the ACK payloads.
+//!
+//! A split keyboard can stream both halves to one dongle, instead of relaying
+//! the events of one half through the other: every half runs
+//! [`run_esb_keyboard_half`] with its own id, and the dongle runs
+//! [`run_esb_split_dongle`]. Every packet starts with the id of the half which
+//! sent it, so that the dongle answers each half with its own ACK payloads.
+//! The ACK echoes the id with [`ESB_ACK_FLAG`] and the packet id, so that a
+//! half doesn't take the packet of the other half for its ACK.
+//! The halves share the channel, a half which collided with the other one
+//! retransmits after a delay depending on its id.
+//!
+//! The radio is abstracted by [`EsbPhy`]. With the `nrf_esb` feature, RMK
+//! ships [`NrfEsbPhy`], which drives the RADIO peripheral of nRF52 chips. The
+//! radio can't be shared with the BLE stack, ESB firmware doesn't use BLE.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use rmk::esb::{EsbConfig, NrfEsbInterruptHandler, NrfEsbPhy, run_esb_dongle, run_esb_keyboard};
+//!
+//! bind_interrupts!(struct Irqs {
+//!     RADIO => NrfEsbInterruptHandler;
+//! });
+//!
+//! // Both sides must use the same address, pick a random one for each keyboard
+//! let config = EsbConfig {
+//!     address: [0x4b, 0x21, 0x9a, 0x07, 0xe3],
+//!     ..Default::default()
+//! };
+//!
+//! // Keyboard
+//! let phy = NrfEsbPhy::new(p.RADIO, Irqs, config.address);
+//! join(run_all!(matrix), run_esb_keyboard(phy, config)).await;
+//!
+//! // Dongle, the keyboard matrix is mapped at (0, 0) of the keymap
+//! let phy = NrfEsbPhy::new(p.RADIO, Irqs, config.address);
+//! join(
+//!     run_rmk(&keymap, usb_driver, &mut storage, rmk_config),
+//!     run_esb_dongle::<ROW, COL, 0, 0, _>(phy, config),
+//! )
+//! .await;
+//!
+//! // Split dongle, the left half has id 0 and the right half id 1
+//! join(
+//!     run_rmk(&keymap, usb_driver, &mut storage, rmk_config),
+//!     run_esb_split_dongle::<ROW, COL, 0, 0, ROW, COL, 0, COL, _>(phy, config),
+//! )
+//! .await;
+//! ```
+
+#[cfg(feature = "nrf_esb")]
+mod nrf;
+
+use core::cell::RefCell;
+
+use embassy_futures::join::join3;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::{Deque, Vec};
+#[cfg(feature = "nrf_esb")]
+pub use nrf::{NrfEsbInterruptHandler, NrfEsbPhy};
+
+use crate::RawMutex;
+use crate::link_stats::{record_packet, reset_link_stats, update_link_stats};
+use crate::split::SplitMessage;
+use crate::split::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
+use crate::split::peripheral::SplitPeripheral;
+
+/// Maximum payload length of an ESB packet: the id of the half and a split message.
+///
+/// ACKs carry the id of the half with [`ESB_ACK_FLAG`] set instead.
+pub const ESB_MAX_PAYLOAD: usize = SplitMessage::POSTCARD_MAX_SIZE + 1;
+
+const _: () = assert!(ESB_MAX_PAYLOAD <= 252, "Split messages don't fit in an ESB packet");
+
+/// Set in the first byte of an ACK payload, which is the id of the half the ACK is for.
+pub const ESB_ACK_FLAG: u8 = 0x80;
+
+/// Number of messages queued for the keyboard until the next ACK, and received from the dongle.
+const QUEUE_SIZE: usize = 4;
+
+/// Delay of the retransmits of a half per id, so that halves which collided don't collide again
+const RETRANSMIT_DELAY: Duration = Duration::from_micros(250);
+
+/// ESB link configuration, the keyboard and the dongle must use the same.
+#[derive(Clone, Copy, Debug)]
+pub struct EsbConfig {
+    /// Radio address, a 1 byte prefix followed by a 4 byte base address
+    pub address: [u8; 5],
+    /// RF channels to hop between, the frequency is 2400 + channel MHz
+    pub channels: &'static [u8],
+    /// Retransmits of a packet on one channel before hopping to the next
+    pub retransmits: u8,
+    /// Time the keyboard waits for the ACK of each transmit
+    pub ack_timeout: Duration,
+    /// Interval of the keyboard polling the dongle for messages while no key is pressed
+    pub poll_interval: Duration,
+    /// The dongle hops to the next channel after this long without any packet.
+    ///
+    /// The keyboard keeps hopping for this long per channel before it drops a packet, so that the
+    /// dongle has listened on every channel by then. Once a packet was dropped, the keyboard tries
+    /// every channel once per packet until the dongle answers again.
+    pub channel_timeout: Duration,
+}
+
+impl Default for EsbConfig {
+    fn default() -> Self {
+        Self {
+            address: [0xe7, 0xe7, 0xe7, 0xe7, 0xe7],
+            // Between the usual Wi-Fi channels 1, 6 and 11
+            channels: &[2, 24, 49, 74, 80],
+            retransmits: 3,
+            ack_timeout: Duration::from_millis(1),
+            poll_interval: Duration::from_millis(50),
+            channel_timeout: Duration::from_millis(500),
+        }
+    }
+}
+
+/// An ESB packet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EsbPacket {
+    /// Packet id, 2 bits. A retransmitted packet keeps its id, so the receiver can drop duplicates.
+    pub pid: u8,
+    pub payload: Vec<u8, ESB_MAX_PAYLOAD>,
+}
+
+/// Chip-agnostic ESB radio.
+pub trait EsbPhy {
+    /// Send `packet` on `channel`, then listen for the ACK for up to `ack_timeout`.
+    ///
+    /// Returns the ACK, `None` if it didn't arrive.
+    async fn transmit(&mut self, channel: u8, packet: &EsbPacket, ack_timeout: Duration) -> Option<EsbPacket>;
+    /// Listen on `channel` for up to `timeout`, `None` if nothing was received.
+    async fn receive(&mut self, channel: u8, timeout: Duration) -> Option<EsbPacket>;
+    /// Send the ACK of the packet just received, on the same channel.
+    async fn ack(&mut self, ack: &EsbPacket);
+}
+
+/// Keyboard side of the link, the primary transmitter (PTX).
+pub(crate) struct EsbPtx<P: EsbPhy> {
+    phy: P,
+    config: EsbConfig,
+    /// Id of the keyboard half, 0 for a single keyboard
+    id: u8,
+    /// Index of the current channel in `config.channels`
+    channel: usize,
+    pid: u8,
+    /// The last packet wasn't acknowledged, the dongle is off or out of range
+    lost: bool,
+    /// Messages received from the dongle in ACK payloads
+    inbox: Deque<SplitMessage, QUEUE_SIZE>,
+}
+
+impl<P: EsbPhy> EsbPtx<P> {
+    pub(crate) fn new(phy: P, config: EsbConfig, id: u8) -> Self {
+        Self {
+            phy,
+            config,
+            id,
+            channel: 0,
+            pid: 0,
+            lost: false,
+            inbox: Deque::new(),
+        }
+    }
+
+    /// Send `payload` until it's acknowledged, hopping channels when needed.
+    async fn send(&mut self, payload: &[u8]) -> Result<(), SplitDriverError> {
+        self.pid = (self.pid + 1) & 0b11;
+        let mut packet = EsbPacket {
+            pid: self.pid,
+            payload: Vec::new(),
+        };
+        packet.payload.push(self.id).ok();
+        packet
+            .payload
+            .extend_from_slice(payload)
+            .map_err(|_| SplitDriverError::SerializeError)?;
+        let start = Instant::now();
+        // Give the dongle the time to follow through every channel, dropping the packet earlier loses keys.
+        // A lost dongle rests on a channel for `channel_timeout`, so one pass over the channels finds it
+        // without blocking every key press.
+        let deadline = start + self.config.channel_timeout * self.config.channels.len() as u32;
+        let mut hops = 0;
+        let mut attempts: u32 = 0;
+        loop {
+            let channel = self.config.channels[self.channel];
+            for _ in 0..=self.config.retransmits {
+                attempts += 1;
+                let ack = self.phy.transmit(channel, &packet, self.config.ack_timeout).await;
+                // The halves share the address, so the packet of the other half or the ACK of its
+                // packet can be heard as well
+                if let Some(ack) = ack
+                    && ack.pid == packet.pid
+                    && ack.payload.first() == Some(&(ESB_ACK_FLAG | self.id))
+                {
+                    record_packet(start.elapsed(), attempts - 1);
+                    self.lost = false;
+                    if ack.payload.len() > 1 {
+                        match postcard::from_bytes::<SplitMessage>(&ack.payload[1..]) {
+                            Ok(message) => {
+                                if self.inbox.push_back(message).is_err() {
+                                    warn!("ESB inbox full, dropping message from the dongle");
+                                }
+                            }
+                            Err(e) => error!("Postcard deserialize ESB ACK payload error: {}", e),
+                        }
+                    }
+                    return Ok(());
+                }
+                if self.id > 0 {
+                    Timer::after(RETRANSMIT_DELAY * self.id as u32).await;
+                }
+            }
+            self.channel = (self.channel + 1) % self.config.channels.len();
+            debug!("ESB hopping to channel {}", self.config.channels[self.channel]);
+            hops += 1;
+            if Instant::now() >= deadline || (self.lost && hops >= self.config.channels.len()) {
+                update_link_stats(|s| {
+                    s.retransmits = s.retransmits.wrapping_add(attempts - 1);
+                    s.dropped = s.dropped.wrapping_add(1);
+                });
+                self.lost = true;
+                return Err(SplitDriverError::Disconnected);
+            }
+        }
+    }
+}
+
+impl<P: EsbPhy> SplitReader for EsbPtx<P> {
+    /// Messages arrive in the ACKs of writes, the dongle is polled by the heartbeats of
+    /// `SplitPeripheral::with_poll`.
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        match self.inbox.pop_front() {
+            Some(message) => Ok(message),
+            None => core::future::pending().await,
+        }
+    }
+}
+
+impl<P: EsbPhy> SplitWriter for EsbPtx<P> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        // A heartbeat only polls the dongle for messages, which is what an empty packet does
+        if let SplitMessage::Heartbeat = message {
+            self.send(&[]).await?;
+            return Ok(0);
+        }
+        let mut buf = [0_u8; ESB_MAX_PAYLOAD];
+        let bytes = postcard::to_slice(message, &mut buf).map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?;
+        let len = bytes.len();
+        self.send(&buf[..len]).await?;
+        Ok(len)
+    }
+}
+
+/// Messages of the dongle to every keyboard half, waiting for an ACK to carry them
+pub(crate) struct EsbOutbox<const N: usize> {
+    pending: Mutex<RawMutex, RefCell<[Deque<Vec<u8, ESB_MAX_PAYLOAD>, QUEUE_SIZE>; N]>>,
+}
+
+impl<const N: usize> EsbOutbox<N> {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: Mutex::new(RefCell::new(core::array::from_fn(|_| Deque::new()))),
+        }
+    }
+
+    /// Queue `message` for the half `id`.
+    fn push(&self, id: usize, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut buf = [0_u8; ESB_MAX_PAYLOAD];
+        let bytes = postcard::to_slice(message, &mut buf).map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?;
+        let len = bytes.len();
+        self.pending.lock(|p| {
+            let pending = &mut p.borrow_mut()[id];
+            if pending.is_full() {
+                warn!("ESB ACK queue of half {} full, dropping the oldest message", id);
+                pending.pop_front();
+            }
+            // The payload length is checked by `to_slice` already
+            pending.push_back(Vec::from_slice(&buf[..len]).unwrap()).ok();
+        });
+        Ok(len)
+    }
+
+    fn pop(&self, id: usize) -> Option<Vec<u8, ESB_MAX_PAYLOAD>> {
+        self.pending.lock(|p| p.borrow_mut()[id].pop_front())
+    }
+}
+
+/// Dongle side of the link, the primary receiver (PRX), for `N` keyboard halves.
+pub(crate) struct EsbPrx<'a, P: EsbPhy, const N: usize = 1> {
+    phy: P,
+    config: EsbConfig,
+    /// Index of the current channel in `config.channels`
+    channel: usize,
+    /// Last received packet of every half, to detect retransmits
+    last_rx: [Option<EsbPacket>; N],
+    /// ACK of the last received packet of every half, sent again for its retransmits
+    last_ack: [EsbPacket; N],
+    outbox: &'a EsbOutbox<N>,
+}
+
+impl<'a, P: EsbPhy, const N: usize> EsbPrx<'a, P, N> {
+    pub(crate) fn new(phy: P, config: EsbConfig, outbox: &'a EsbOutbox<N>) -> Self {
+        Self {
+            phy,
+            config,
+            channel: 0,
+            last_rx: core::array::from_fn(|_| None),
+            last_ack: core::array::from_fn(|_| EsbPacket::default()),
+            outbox,
+        }
+    }
+
+    /// Receive the next message, and the id of the half which sent it.
+    async fn receive(&mut self) -> (usize, Result<SplitMessage, SplitDriverError>) {
+        loop {
+            let channel = self.config.channels[self.channel];
+            let Some(packet) = self.phy.receive(channel, self.config.channel_timeout).await else {
+                // Nothing heard, the keyboard may have hopped to another channel
+                self.channel = (self.channel + 1) % self.config.channels.len();
+                continue;
+            };
+
+            let id = match packet.payload.first() {
+                Some(&id) if (id as usize) < N => id as usize,
+                // Not a packet of a keyboard half
+                Some(&id) if id & ESB_ACK_FLAG != 0 => continue,
+                _ => {
+                    warn!("ESB packet from an unknown keyboard half, dropping it");
+                    let mut payload = Vec::new();
+                    if let Some(&id) = packet.payload.first() {
+                        payload.push(ESB_ACK_FLAG | id).ok();
+                    }
+                    self.phy
+                        .ack(&EsbPacket {
+                            pid: packet.pid,
+                            payload,
+                        })
+                        .await;
+                    continue;
+                }
+            };
+
+            // A retransmit means the ACK was lost, so send the same ACK payload again
+            let duplicate = self.last_rx[id].as_ref() == Some(&packet);
+            if !duplicate {
+                let mut payload = Vec::new();
+                payload.push(ESB_ACK_FLAG | id as u8).ok();
+                if let Some(message) = self.outbox.pop(id) {
+                    // Split messages fit in `ESB_MAX_PAYLOAD` with the id
+                    payload.extend_from_slice(&message).ok();
+                }
+                self.last_ack[id] = EsbPacket {
+                    pid: packet.pid,
+                    payload,
+                };
+            }
+            self.phy.ack(&self.last_ack[id]).await;
+
+            if duplicate || packet.payload.len() == 1 {
+                self.last_rx[id] = Some(packet);
+                continue;
+            }
+            let message = postcard::from_bytes::<SplitMessage>(&packet.payload[1..]).map_err(|e| {
+                error!("Postcard deserialize ESB packet error: {}", e);
+                SplitDriverError::DeserializeError
+            });
+            self.last_rx[id] = Some(packet);
+            return (id, message);
+        }
+    }
+}
+
+impl<P: EsbPhy> SplitReader for EsbPrx<'_, P> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        self.receive().await.1
+    }
+}
+
+impl<P: EsbPhy> SplitWriter for EsbPrx<'_, P> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        self.outbox.push(0, message)
+    }
+}
+
+/// One keyboard half of a split dongle, its messages are received by the radio task of
+/// [`run_esb_split_dongle`].
+struct EsbHalf<'a, const N: usize> {
+    id: usize,
+    inbox: &'a Channel<RawMutex, SplitMessage, QUEUE_SIZE>,
+    outbox: &'a EsbOutbox<N>,
+}
+
+impl<const N: usize> SplitReader for EsbHalf<'_, N> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        Ok(self.inbox.receive().await)
+    }
+}
+
+impl<const N: usize> SplitWriter for EsbHalf<'_, N> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        self.outbox.push(self.id, message)
+    }
+}
+
+/// Run the keyboard side of the ESB link.
+///
+/// Key and pointing events are sent to the dongle, which runs the keymap.
+pub async fn run_esb_keyboard<P: EsbPhy>(phy: P, config: EsbConfig) -> ! {
+    run_esb_keyboard_half(0, phy, config).await
+}
+
+/// Run a keyboard half of a split keyboard with a dongle, see [`run_esb_split_dongle`].
+///
+/// # Arguments
+/// * `id` - id of the half, 0 or 1, the halves must use different ids
+pub async fn run_esb_keyboard_half<P: EsbPhy>(id: u8, phy: P, config: EsbConfig) -> ! {
+    reset_link_stats();
+    let mut peripheral = SplitPeripheral::new(EsbPtx::new(phy, config, id)).with_poll(config.poll_interval);
+    loop {
+        peripheral.run().await;
+    }
+}
+
+/// Run the dongle side of the ESB link.
+///
+/// Generic parameters:
+/// - `const ROW`: row number of the keyboard's matrix
+/// - `const COL`: column number of the keyboard's matrix
+/// - `const ROW_OFFSET`: row offset of the keyboard's matrix in the dongle's keymap
+/// - `const COL_OFFSET`: column offset of the keyboard's matrix in the dongle's keymap
+pub async fn run_esb_dongle<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    P: EsbPhy,
+>(
+    phy: P,
+    config: EsbConfig,
+) {
+    let outbox = EsbOutbox::new();
+    let manager = PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(EsbPrx::new(phy, config, &outbox), 0);
+    info!("Running ESB dongle");
+    manager.run().await;
+}
+
+/// Run the dongle side of a split keyboard whose halves both use ESB.
+///
+/// Both halves stream their events to the dongle, which runs the keymap, so neither half
+/// relays the other one. The halves run [`run_esb_keyboard_half`] with the ids 0 and 1,
+/// they're peripherals 0 and 1, e.g. in `PeripheralConnectedEvent`.
+///
+/// Generic parameters:
+/// - `const ROW0`, `const COL0`: row and column number of the matrix of half 0
+/// - `const ROW_OFFSET0`, `const COL_OFFSET0`: offset of the matrix of half 0 in the dongle's keymap
+/// - `const ROW1`, `const COL1`, `const ROW_OFFSET1`, `const COL_OFFSET1`: the same for half 1
+pub async fn run_esb_split_dongle<
+    const ROW0: usize,
+    const COL0: usize,
+    const ROW_OFFSET0: usize,
+    const COL_OFFSET0: usize,
+    const ROW1: usize,
+    const COL1: usize,
+    const ROW_OFFSET1: usize,
+    const COL_OFFSET1: usize,
+    P: EsbPhy,
+>(
+    phy: P,
+    config: EsbConfig,
+) {
+    let outbox = EsbOutbox::<2>::new();
+    let inboxes: [Channel<RawMutex, SplitMessage, QUEUE_SIZE>; 2] = [Channel::new(), Channel::new()];
+    let radio = async {
+        let mut prx = EsbPrx::new(phy, config, &outbox);
+        loop {
+            match prx.receive().await {
+                // Wait for the manager when the inbox is full, the half retransmits meanwhile
+                (id, Ok(message)) => inboxes[id].send(message).await,
+                (id, Err(e)) => debug!("ESB message from half {} dropped: {:?}", id, e),
+            }
+        }
+    };
+    let half = |id| EsbHalf {
+        id,
+        inbox: &inboxes[id],
+        outbox: &outbox,
+    };
+    let half0 = PeripheralManager::<ROW0, COL0, ROW_OFFSET0, COL_OFFSET0, _>::new(half(0), 0);
+    let half1 = PeripheralManager::<ROW1, COL1, ROW_OFFSET1, COL_OFFSET1, _>::new(half(1), 1);
+    info!("Running ESB split dongle");
+    join3(radio, half0.run(), half1.run()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// Scripted radio: returns the queued results in order and records what was sent.
+    #[derive(Default)]
+    struct FakePhy {
+        acks: VecDeque<Option<EsbPacket>>,
+        received: VecDeque<Option<EsbPacket>>,
+        tx_channels: std::vec::Vec<u8>,
+        sent_acks: std::vec::Vec<EsbPacket>,
+    }
+
+    impl EsbPhy for FakePhy {
+        async fn transmit(&mut self, channel: u8, _packet: &EsbPacket, _ack_timeout: Duration) -> Option<EsbPacket> {
+            self.tx_channels.push(channel);
+            self.acks.pop_front().flatten()
+        }
+
+        async fn receive(&mut self, _channel: u8, _timeout: Duration) -> Option<EsbPacket> {
+            self.received.pop_front().expect("unexpected receive")
+        }
+
+        async fn ack(&mut self, ack: &EsbPacket) {
+            self.sent_acks.push(ack.clone());
+        }
+    }
+
+    fn encode(message: &SplitMessage) -> Vec<u8, ESB_MAX_PAYLOAD> {
+        let mut buf = [0_u8; ESB_MAX_PAYLOAD];
+        Vec::from_slice(postcard::to_slice(message, &mut buf).unwrap()).unwrap()
+    }
+
+    /// Packet of the half `id` to the dongle
+    fn from_half(id: u8, pid: u8, message: &SplitMessage) -> EsbPacket {
+        let mut payload = Vec::new();
+        payload.push(id).unwrap();
+        payload.extend_from_slice(&encode(message)).unwrap();
+        EsbPacket { pid, payload }
+    }
+
+    /// ACK of the dongle to the half `id`
+    fn ack_to(id: u8, pid: u8, message: Option<&SplitMessage>) -> EsbPacket {
+        let mut payload = Vec::new();
+        payload.push(ESB_ACK_FLAG | id).unwrap();
+        if let Some(message) = message {
+            payload.extend_from_slice(&encode(message)).unwrap();
+        }
+        EsbPacket { pid, payload }
+    }
+
+    #[test]
+    fn ptx_retransmits_then_hops_channel() {
+        let config = EsbConfig {
+            retransmits: 1,
+            ..Default::default()
+        };
+        let mut phy = FakePhy::default();
+        // Both transmits on the first channel fail, the next channel is acknowledged
+        phy.acks.extend([None, None, Some(ack_to(0, 1, None))]);
+        let mut ptx = EsbPtx::new(phy, config, 0);
+
+        block_on(ptx.write(&SplitMessage::LedState(true))).unwrap();
+        assert_eq!(
+            ptx.phy.tx_channels,
+            [config.channels[0], config.channels[0], config.channels[1]]
+        );
+        assert_eq!(ptx.channel, 1);
+    }
+
+    #[test]
+    fn ptx_receives_messages_in_ack_payloads() {
+        let mut phy = FakePhy::default();
+        phy.acks.push_back(Some(ack_to(0, 1, Some(&SplitMessage::Layer(2)))));
+        let mut ptx = EsbPtx::new(phy, EsbConfig::default(), 0);
+
+        block_on(ptx.write(&SplitMessage::LedState(true))).unwrap();
+        assert!(matches!(block_on(ptx.read()), Ok(SplitMessage::Layer(2))));
+    }
+
+    #[test]
+    fn ptx_only_accepts_its_own_ack() {
+        let mut phy = FakePhy::default();
+        phy.acks.extend([
+            // A packet of the other half, the ACK of the other half, and an ACK of an older packet
+            Some(from_half(1, 1, &SplitMessage::LedState(true))),
+            Some(ack_to(1, 1, Some(&SplitMessage::Layer(1)))),
+            Some(ack_to(0, 0, Some(&SplitMessage::Layer(1)))),
+            Some(ack_to(0, 1, Some(&SplitMessage::Layer(0)))),
+        ]);
+        let mut ptx = EsbPtx::new(phy, EsbConfig::default(), 0);
+
+        block_on(ptx.write(&SplitMessage::LedState(false))).unwrap();
+        assert_eq!(ptx.phy.tx_channels.len(), 4);
+        assert_eq!(ptx.inbox.len(), 1);
+        assert!(matches!(block_on(ptx.read()), Ok(SplitMessage::Layer(0))));
+    }
+
+    #[test]
+    fn ptx_tries_every_channel_once_when_dongle_is_lost() {
+        let config = EsbConfig {
+            retransmits: 0,
+            ..Default::default()
+        };
+        let mut ptx = EsbPtx::new(FakePhy::default(), config, 0);
+        ptx.lost = true;
+
+        assert!(matches!(
+            block_on(ptx.write(&SplitMessage::Heartbeat)),
+            Err(SplitDriverError::Disconnected)
+        ));
+        assert_eq!(ptx.phy.tx_channels, config.channels);
+        assert!(ptx.lost);
+    }
+
+    #[test]
+    fn prx_drops_retransmits_and_repeats_ack() {
+        let packet = from_half(0, 1, &SplitMessage::LedState(true));
+        let next = from_half(0, 2, &SplitMessage::LedState(false));
+        let mut phy = FakePhy::default();
+        phy.received
+            .extend([Some(packet.clone()), None, Some(packet), Some(next)]);
+        let outbox = EsbOutbox::new();
+        let mut prx = EsbPrx::new(phy, EsbConfig::default(), &outbox);
+        block_on(prx.write(&SplitMessage::Layer(3))).unwrap();
+
+        assert!(matches!(block_on(prx.read()), Ok(SplitMessage::LedState(true))));
+        // The retransmit of the first packet is skipped, after a hop due to the timeout
+        assert!(matches!(block_on(prx.read()), Ok(SplitMessage::LedState(false))));
+        assert_eq!(prx.channel, 1);
+
+        let acks = &prx.phy.sent_acks;
+        assert_eq!(acks.len(), 3);
+        assert_eq!(acks[0], ack_to(0, 1, Some(&SplitMessage::Layer(3))));
+        assert_eq!(acks[1], acks[0]);
+        assert_eq!(acks[2], ack_to(0, 2, None));
+    }
+
+    #[test]
+    fn prx_answers_every_half_with_its_own_messages() {
+        let mut phy = FakePhy::default();
+        phy.received.extend([
+            Some(from_half(1, 1, &SplitMessage::LedState(true))),
+            Some(from_half(0, 1, &SplitMessage::LedState(false))),
+            // Poll of an unknown half, and a packet without a half id
+            Some(EsbPacket {
+                pid: 2,
+                payload: Vec::from_slice(&[2]).unwrap(),
+            }),
+            Some(EsbPacket::default()),
+            // ACK of the other half, which isn't answered
+            Some(ack_to(0, 1, None)),
+            Some(from_half(1, 2, &SplitMessage::LedState(true))),
+        ]);
+        let outbox = EsbOutbox::<2>::new();
+        outbox.push(0, &SplitMessage::Layer(0)).unwrap();
+        outbox.push(1, &SplitMessage::Layer(1)).unwrap();
+        let mut prx = EsbPrx::new(phy, EsbConfig::default(), &outbox);
+
+        assert!(matches!(block_on(prx.receive()), (1, Ok(SplitMessage::LedState(true)))));
+        assert!(matches!(
+            block_on(prx.receive()),
+            (0, Ok(SplitMessage::LedState(false)))
+        ));
+        assert!(matches!(block_on(prx.receive()), (1, Ok(SplitMessage::LedState(true)))));
+
+        let acks = &prx.phy.sent_acks;
+        assert_eq!(acks.len(), 5);
+        assert_eq!(acks[0], ack_to(1, 1, Some(&SplitMessage::Layer(1))));
+        assert_eq!(acks[1], ack_to(0, 1, Some(&SplitMessage::Layer(0))));
+        assert_eq!(acks[2], ack_to(2, 2, None));
+        assert_eq!(acks[3], EsbPacket::default());
+        assert_eq!(acks[4], ack_to(1, 2, None));
+    }
+}
@@ -0,0 +1,178 @@
+use core::future::poll_fn;
+use core::sync::atomic::{Ordering, compiler_fence};
+use core::task::Poll;
+
+use embassy_nrf::Peri;
+use embassy_nrf::interrupt::typelevel::{self, Binding, Handler, Interrupt};
+use embassy_nrf::pac::RADIO;
+use embassy_nrf::pac::radio::vals;
+use embassy_nrf::peripherals;
+use embassy_sync::waitqueue::AtomicWaker;
+use embassy_time::{Duration, Instant, with_deadline};
+use heapless::Vec;
+
+use super::{ESB_MAX_PAYLOAD, EsbPacket, EsbPhy};
+
+/// Waker of the task waiting for the radio to be disabled
+static RADIO_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Interrupt handler of the RADIO for [`NrfEsbPhy`], bind it with `bind_interrupts!`.
+pub struct NrfEsbInterruptHandler {
+    _private: (),
+}
+
+impl Handler<typelevel::RADIO> for NrfEsbInterruptHandler {
+    unsafe fn on_interrupt() {
+        // The waiting task checks the event and enables the interrupt again
+        RADIO.intenclr().write(|w| w.set_disabled(true));
+        RADIO_WAKER.wake();
+    }
+}
+
+/// ESB radio on the RADIO peripheral of nRF52 chips, at 2Mbps with dynamic payload length.
+///
+/// The radio needs the external high frequency crystal, set
+/// `config.hfclk_source = HfclkSource::ExternalXtal` in `embassy_nrf::init`.
+pub struct NrfEsbPhy<'d> {
+    _radio: Peri<'d, peripherals::RADIO>,
+    /// Packet in RAM: length, S1 (packet id and no-ACK bit), payload
+    buffer: [u8; ESB_MAX_PAYLOAD + 2],
+}
+
+impl<'d> NrfEsbPhy<'d> {
+    /// Create the radio with the 5 byte ESB `address`, a prefix followed by the base address.
+    pub fn new(
+        radio: Peri<'d, peripherals::RADIO>,
+        _irq: impl Binding<typelevel::RADIO, NrfEsbInterruptHandler> + 'd,
+        address: [u8; 5],
+    ) -> Self {
+        let r = RADIO;
+        r.mode().write(|w| w.set_mode(vals::Mode::NRF_2MBIT));
+        r.pcnf0().write(|w| {
+            w.set_lflen(8);
+            w.set_s0len(false);
+            w.set_s1len(3);
+        });
+        r.pcnf1().write(|w| {
+            w.set_maxlen(ESB_MAX_PAYLOAD as u8);
+            w.set_statlen(0);
+            w.set_balen(4);
+            w.set_endian(vals::Endian::BIG);
+            w.set_whiteen(false);
+        });
+        r.base0()
+            .write_value(u32::from_be_bytes([address[1], address[2], address[3], address[4]]));
+        r.prefix0().write(|w| w.set_ap(0, address[0]));
+        r.txaddress().write(|w| w.set_txaddress(0));
+        r.rxaddresses().write(|w| w.set_addr(0, true));
+        r.crccnf().write(|w| {
+            w.set_len(vals::Len::TWO);
+            w.set_skipaddr(vals::Skipaddr::INCLUDE);
+        });
+        r.crcinit().write(|w| w.set_crcinit(0xFFFF));
+        r.crcpoly().write(|w| w.set_crcpoly(0x11021));
+        // Start right after ramp up, disable right after the packet
+        r.shorts().write(|w| {
+            w.set_ready_start(true);
+            w.set_end_disable(true);
+        });
+        r.intenclr().write(|w| w.set_disabled(true));
+        typelevel::RADIO::unpend();
+        unsafe { typelevel::RADIO::enable() };
+
+        Self {
+            _radio: radio,
+            buffer: [0; ESB_MAX_PAYLOAD + 2],
+        }
+    }
+
+    async fn disable(&mut self) {
+        let r = RADIO;
+        if r.state().read().state() == vals::State::DISABLED {
+            return;
+        }
+        r.events_disabled().write_value(0);
+        r.tasks_disable().write_value(1);
+        Self::wait_disabled().await;
+    }
+
+    /// Wait for the DISABLED event, which ends every operation of the radio.
+    async fn wait_disabled() {
+        poll_fn(|cx| {
+            RADIO_WAKER.register(cx.waker());
+            if RADIO.events_disabled().read() != 0 {
+                compiler_fence(Ordering::SeqCst);
+                return Poll::Ready(());
+            }
+            RADIO.intenset().write(|w| w.set_disabled(true));
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Send or receive one packet, returns false if `deadline` passed before.
+    async fn run(&mut self, rx: bool, deadline: Option<Instant>) -> bool {
+        let r = RADIO;
+        r.packetptr().write_value(self.buffer.as_mut_ptr() as u32);
+        r.events_disabled().write_value(0);
+        compiler_fence(Ordering::SeqCst);
+        if rx {
+            r.tasks_rxen().write_value(1);
+        } else {
+            r.tasks_txen().write_value(1);
+        }
+        let Some(deadline) = deadline else {
+            Self::wait_disabled().await;
+            return true;
+        };
+        if with_deadline(deadline, Self::wait_disabled()).await.is_ok() {
+            return true;
+        }
+        self.disable().await;
+        false
+    }
+
+    fn write_packet(&mut self, packet: &EsbPacket) {
+        let len = packet.payload.len();
+        self.buffer[0] = len as u8;
+        self.buffer[1] = (packet.pid & 0b11) << 1;
+        self.buffer[2..2 + len].copy_from_slice(&packet.payload);
+    }
+
+    async fn receive_until(&mut self, deadline: Instant) -> Option<EsbPacket> {
+        while self.run(true, Some(deadline)).await {
+            if RADIO.crcstatus().read().crcstatus() != vals::Crcstatus::CRCOK {
+                continue;
+            }
+            let len = (self.buffer[0] as usize).min(ESB_MAX_PAYLOAD);
+            return Some(EsbPacket {
+                pid: (self.buffer[1] >> 1) & 0b11,
+                payload: Vec::from_slice(&self.buffer[2..2 + len]).ok()?,
+            });
+        }
+        None
+    }
+}
+
+impl EsbPhy for NrfEsbPhy<'_> {
+    async fn transmit(&mut self, channel: u8, packet: &EsbPacket, ack_timeout: Duration) -> Option<EsbPacket> {
+        // A cancelled operation may have left the radio running
+        self.disable().await;
+        RADIO.frequency().write(|w| w.set_frequency(channel));
+        self.write_packet(packet);
+        self.run(false, None).await;
+        self.receive_until(Instant::now() + ack_timeout).await
+    }
+
+    async fn receive(&mut self, channel: u8, timeout: Duration) -> Option<EsbPacket> {
+        self.disable().await;
+        RADIO.frequency().write(|w| w.set_frequency(channel));
+        self.receive_until(Instant::now() + timeout).await
+    }
+
+    async fn ack(&mut self, ack: &EsbPacket) {
+        self.disable().await;
+        self.write_packet(ack);
+        self.run(false, None).await;
+    }
+}
@@ -1,6 +1,6 @@
 use postcard::experimental::max_size::MaxSize;
 use rmk_macro::event;
-use rmk_types::action::Action;
+use rmk_types::action::{Action, KeyAction};
 use serde::{Deserialize, Serialize};
 
 use crate::event::KeyboardEvent;
@@ -16,3 +16,21 @@ pub struct ActionEvent {
     pub action: Action,
     pub keyboard_event: KeyboardEvent,
 }
+
+/// A combo fired, carrying the combo's output action
+#[event(
+    channel_size = crate::COMBO_TRIGGERED_EVENT_CHANNEL_SIZE,
+    pubs = crate::COMBO_TRIGGERED_EVENT_PUB_SIZE,
+    subs = crate::COMBO_TRIGGERED_EVENT_SUB_SIZE
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ComboTriggeredEvent(pub KeyAction);
+
+impl ComboTriggeredEvent {
+    pub fn new(output: KeyAction) -> Self {
+        Self(output)
+    }
+}
+
+impl_payload_wrapper!(ComboTriggeredEvent, KeyAction);
@@ -1,6 +1,7 @@
 //! Connection related events
 //!
-//! Single event published whenever the `ConnectionStatus` changes
+//! `ConnectionStatusChangeEvent` is published whenever the `ConnectionStatus` changes,
+//! `PasskeyEntryEvent` reports the progress of BLE passkey pairing
 
 use rmk_macro::event;
 pub use rmk_types::connection::{ConnectionStatus, ConnectionType};
@@ -13,3 +14,18 @@ pub use rmk_types::connection::{ConnectionStatus, ConnectionType};
 pub struct ConnectionStatusChangeEvent(pub ConnectionStatus);
 
 impl_payload_wrapper!(ConnectionStatusChangeEvent, ConnectionStatus);
+
+/// BLE passkey entry state changed event.
+///
+/// Fires when the host requests a passkey, on every digit typed or removed, and when
+/// the entry is submitted, cancelled or timed out. Displays and LEDs can use it to
+/// tell the user that keystrokes are captured for pairing.
+#[event(channel_size = crate::PASSKEY_ENTRY_EVENT_CHANNEL_SIZE, pubs = crate::PASSKEY_ENTRY_EVENT_PUB_SIZE, subs = crate::PASSKEY_ENTRY_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PasskeyEntryEvent {
+    /// Whether the keyboard is capturing the passkey
+    pub active: bool,
+    /// Number of digits entered so far
+    pub digits: u8,
+}
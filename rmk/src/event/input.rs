@@ -149,9 +149,15 @@ pub enum Axis {
 }
 
 /// Set the CPI (Resolution) of the pointing device
-/// TODO: Make the channel size configurable
-#[event(channel_size = 8, pubs = 2, subs = 2)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// It only reaches the pointing devices of the keyboard it's published on. To change the CPI of
+/// a pointing device on a split peripheral, publish a `PeripheralPointingSetCpiEvent` on the central.
+#[event(
+    channel_size = crate::POINTING_SET_CPI_EVENT_CHANNEL_SIZE,
+    pubs = crate::POINTING_SET_CPI_EVENT_PUB_SIZE,
+    subs = crate::POINTING_SET_CPI_EVENT_SUB_SIZE
+)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct PointingSetCpiEvent {
     pub device_id: u8,
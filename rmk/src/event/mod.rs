@@ -10,7 +10,7 @@
 //! ## Module organization
 //!
 //! - `input`: Input events (keyboard, modifier, pointing device)
-//! - `state`: Keyboard state events (layer, WPM, LED indicator, sleep)
+//! - `state`: Keyboard state events (layer, WPM, LED indicator, sleep, power state)
 //! - `battery`: Battery events (ADC, charging, battery status)
 //! - `connection`: Connection events (USB/BLE, BLE status)
 //! - `split`: Split keyboard events (peripheral/central connection)
@@ -52,18 +52,23 @@ mod input;
 mod split;
 mod state;
 
-pub use action::ActionEvent;
+pub use action::{ActionEvent, ComboTriggeredEvent};
 pub use battery::{BatteryAdcEvent, BatteryStatusEvent, ChargingStateEvent};
-pub use connection::{ConnectionStatus, ConnectionStatusChangeEvent, ConnectionType};
+pub use connection::{ConnectionStatus, ConnectionStatusChangeEvent, ConnectionType, PasskeyEntryEvent};
 pub use input::{
     Axis, AxisEvent, AxisValType, KeyPos, KeyboardEvent, KeyboardEventPos, ModifierEvent, PointingEvent,
     PointingProcessorEvent, PointingSetCpiEvent, RotaryEncoderPos,
 };
+#[cfg(feature = "split_lighting")]
+pub use split::CentralActionEvent;
 #[cfg(feature = "split")]
-pub use split::{CentralConnectedEvent, PeripheralConnectedEvent};
+pub use split::{CentralConnectedEvent, PeripheralConnectedEvent, PeripheralPointingSetCpiEvent};
 #[cfg(all(feature = "split", feature = "_ble"))]
 pub use split::{ClearPeerEvent, PeripheralBatteryEvent};
-pub use state::{LayerChangeEvent, LedIndicatorEvent, SleepStateEvent, WpmUpdateEvent};
+pub use state::{
+    BrightnessEvent, CapsWordEvent, LayerChangeEvent, LedIndicatorEvent, PowerStateEvent, SleepStateEvent,
+    WpmUpdateEvent,
+};
 
 /// Trait for event publishers
 pub trait EventPublisher {
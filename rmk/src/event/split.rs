@@ -2,6 +2,8 @@
 
 use rmk_macro::event;
 
+#[cfg(feature = "split_lighting")]
+use super::action::ActionEvent;
 use super::battery::BatteryStatusEvent;
 
 /// Peripheral connected state changed event
@@ -30,9 +32,37 @@ pub struct PeripheralBatteryEvent {
     pub state: BatteryStatusEvent,
 }
 
+/// Set the CPI of a pointing device on a split peripheral.
+///
+/// `device_id` is the id of the pointing device on the peripheral `peripheral_id`. The central
+/// forwards it to that peripheral only, where it's published as [`PointingSetCpiEvent`].
+///
+/// [`PointingSetCpiEvent`]: super::PointingSetCpiEvent
+#[event(channel_size = crate::PERIPHERAL_POINTING_SET_CPI_EVENT_CHANNEL_SIZE, pubs = crate::PERIPHERAL_POINTING_SET_CPI_EVENT_PUB_SIZE, subs = crate::PERIPHERAL_POINTING_SET_CPI_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PeripheralPointingSetCpiEvent {
+    pub peripheral_id: usize,
+    pub device_id: u8,
+    pub cpi: u16,
+}
+
 /// Clear BLE peer information event
 #[cfg(feature = "_ble")]
 #[event(channel_size = crate::CLEAR_PEER_EVENT_CHANNEL_SIZE, pubs = crate::CLEAR_PEER_EVENT_PUB_SIZE, subs = crate::CLEAR_PEER_EVENT_SUB_SIZE)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ClearPeerEvent;
+
+/// Action of a key on the central, published on the peripherals for their lighting effects.
+///
+/// It's separate from [`ActionEvent`], so that the audio and haptic feedback of a peripheral
+/// only follow its own keys.
+#[cfg(feature = "split_lighting")]
+#[event(channel_size = crate::CENTRAL_ACTION_EVENT_CHANNEL_SIZE, pubs = crate::CENTRAL_ACTION_EVENT_PUB_SIZE, subs = crate::CENTRAL_ACTION_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CentralActionEvent(pub ActionEvent);
+
+#[cfg(feature = "split_lighting")]
+impl_payload_wrapper!(CentralActionEvent, ActionEvent);
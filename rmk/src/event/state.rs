@@ -45,6 +45,20 @@ impl LedIndicatorEvent {
 
 impl_payload_wrapper!(LedIndicatorEvent, LedIndicator);
 
+/// Caps Word activated or deactivated event
+#[event(channel_size = crate::CAPS_WORD_EVENT_CHANNEL_SIZE, pubs = crate::CAPS_WORD_EVENT_PUB_SIZE, subs = crate::CAPS_WORD_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapsWordEvent(pub bool);
+
+impl CapsWordEvent {
+    pub fn new(active: bool) -> Self {
+        Self(active)
+    }
+}
+
+impl_payload_wrapper!(CapsWordEvent, bool);
+
 /// Sleep state changed event
 #[event(channel_size = crate::SLEEP_STATE_EVENT_CHANNEL_SIZE, pubs = crate::SLEEP_STATE_EVENT_PUB_SIZE, subs = crate::SLEEP_STATE_EVENT_SUB_SIZE)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -58,3 +72,38 @@ impl SleepStateEvent {
 }
 
 impl_payload_wrapper!(SleepStateEvent, bool);
+
+/// Brightness level changed event, 0 is the dimmest and 255 the brightest
+#[event(channel_size = crate::BRIGHTNESS_EVENT_CHANNEL_SIZE, pubs = crate::BRIGHTNESS_EVENT_PUB_SIZE, subs = crate::BRIGHTNESS_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BrightnessEvent(pub u8);
+
+impl BrightnessEvent {
+    pub fn new(brightness: u8) -> Self {
+        Self(brightness)
+    }
+}
+
+impl_payload_wrapper!(BrightnessEvent, u8);
+
+/// Power state of a subsystem changed.
+///
+/// Lets current measurements, e.g. with a Power Profiler Kit, be lined up with what the firmware
+/// was doing. Nothing in RMK subscribes to it, so it isn't published until `[event] power_state.subs`
+/// is raised in `keyboard.toml`.
+#[event(channel_size = crate::POWER_STATE_EVENT_CHANNEL_SIZE, pubs = crate::POWER_STATE_EVENT_PUB_SIZE, subs = crate::POWER_STATE_EVENT_SUB_SIZE)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerStateEvent {
+    /// The matrix resumed scanning (`true`), or stopped to wait for a key interrupt with `async_matrix`
+    MatrixScan(bool),
+    /// BLE advertising started (`true`) or stopped
+    Advertising(bool),
+    /// A lighting processor, e.g. the display or an indicator LED, was turned back on (`true`) or
+    /// off for sleep. Each of them publishes its own change
+    Lighting(bool),
+    /// The keyboard went to sleep (`true`) or woke up, on a USB suspend, a split central sleep or
+    /// after the BLE advertising timeout
+    Sleep(bool),
+}
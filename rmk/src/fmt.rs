@@ -122,10 +122,19 @@ macro_rules! panic {
 macro_rules! trace {
     ($s:literal $(, $x:expr)* $(,)?) => {
         {
-            #[cfg(feature = "log")]
-            ::log::trace!($s $(, $x)*);
-            #[cfg(feature = "defmt")]
-            ::defmt::trace!($s $(, $x)*);
+            #[cfg(any(feature = "log", feature = "defmt"))]
+            if $crate::logging::enabled(
+                {
+                    const MODULE: ::rmk_types::logging::LogModule = $crate::logging::module_of(module_path!());
+                    MODULE
+                },
+                ::rmk_types::logging::LogLevel::Trace,
+            ) {
+                #[cfg(feature = "log")]
+                ::log::trace!($s $(, $x)*);
+                #[cfg(feature = "defmt")]
+                ::defmt::trace!($s $(, $x)*);
+            }
             #[cfg(not(any(feature = "log", feature="defmt")))]
             let _ = ($( & $x ),*);
         }
@@ -136,10 +145,19 @@ macro_rules! trace {
 macro_rules! debug {
     ($s:literal $(, $x:expr)* $(,)?) => {
         {
-            #[cfg(feature = "log")]
-            ::log::debug!($s $(, $x)*);
-            #[cfg(feature = "defmt")]
-            ::defmt::debug!($s $(, $x)*);
+            #[cfg(any(feature = "log", feature = "defmt"))]
+            if $crate::logging::enabled(
+                {
+                    const MODULE: ::rmk_types::logging::LogModule = $crate::logging::module_of(module_path!());
+                    MODULE
+                },
+                ::rmk_types::logging::LogLevel::Debug,
+            ) {
+                #[cfg(feature = "log")]
+                ::log::debug!($s $(, $x)*);
+                #[cfg(feature = "defmt")]
+                ::defmt::debug!($s $(, $x)*);
+            }
             #[cfg(not(any(feature = "log", feature="defmt")))]
             let _ = ($( & $x ),*);
         }
@@ -150,10 +168,19 @@ macro_rules! debug {
 macro_rules! info {
     ($s:literal $(, $x:expr)* $(,)?) => {
         {
-            #[cfg(feature = "log")]
-            ::log::info!($s $(, $x)*);
-            #[cfg(feature = "defmt")]
-            ::defmt::info!($s $(, $x)*);
+            #[cfg(any(feature = "log", feature = "defmt"))]
+            if $crate::logging::enabled(
+                {
+                    const MODULE: ::rmk_types::logging::LogModule = $crate::logging::module_of(module_path!());
+                    MODULE
+                },
+                ::rmk_types::logging::LogLevel::Info,
+            ) {
+                #[cfg(feature = "log")]
+                ::log::info!($s $(, $x)*);
+                #[cfg(feature = "defmt")]
+                ::defmt::info!($s $(, $x)*);
+            }
             #[cfg(not(any(feature = "log", feature="defmt")))]
             let _ = ($( & $x ),*);
         }
@@ -164,10 +191,19 @@ macro_rules! info {
 macro_rules! warn {
     ($s:literal $(, $x:expr)* $(,)?) => {
         {
-            #[cfg(feature = "log")]
-            ::log::warn!($s $(, $x)*);
-            #[cfg(feature = "defmt")]
-            ::defmt::warn!($s $(, $x)*);
+            #[cfg(any(feature = "log", feature = "defmt"))]
+            if $crate::logging::enabled(
+                {
+                    const MODULE: ::rmk_types::logging::LogModule = $crate::logging::module_of(module_path!());
+                    MODULE
+                },
+                ::rmk_types::logging::LogLevel::Warn,
+            ) {
+                #[cfg(feature = "log")]
+                ::log::warn!($s $(, $x)*);
+                #[cfg(feature = "defmt")]
+                ::defmt::warn!($s $(, $x)*);
+            }
             #[cfg(not(any(feature = "log", feature="defmt")))]
             let _ = ($( & $x ),*);
         }
@@ -178,10 +214,19 @@ macro_rules! warn {
 macro_rules! error {
     ($s:literal $(, $x:expr)* $(,)?) => {
         {
-            #[cfg(feature = "log")]
-            ::log::error!($s $(, $x)*);
-            #[cfg(feature = "defmt")]
-            ::defmt::error!($s $(, $x)*);
+            #[cfg(any(feature = "log", feature = "defmt"))]
+            if $crate::logging::enabled(
+                {
+                    const MODULE: ::rmk_types::logging::LogModule = $crate::logging::module_of(module_path!());
+                    MODULE
+                },
+                ::rmk_types::logging::LogLevel::Error,
+            ) {
+                #[cfg(feature = "log")]
+                ::log::error!($s $(, $x)*);
+                #[cfg(feature = "defmt")]
+                ::defmt::error!($s $(, $x)*);
+            }
             #[cfg(not(any(feature = "log", feature="defmt")))]
             let _ = ($( & $x ),*);
         }
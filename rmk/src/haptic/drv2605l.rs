@@ -0,0 +1,108 @@
+//! TI DRV2605L haptic driver.
+//!
+//! The DRV2605L drives an ERM or LRA motor from a built-in ROM library of 123
+//! waveforms (§11.2). This driver uses internal-trigger mode (§8.3.5.1): an
+//! effect id is written to the first waveform sequencer slot and `GO` starts
+//! playback.
+//!
+//! # References
+//!
+//! * [datasheet](https://www.ti.com/lit/ds/symlink/drv2605l.pdf). Section markers
+//!   (§) in comments refer to the datasheet.
+
+use embedded_hal_async::i2c::I2c;
+
+use super::HapticDriver;
+use crate::fmt::Debug;
+
+const I2C_ADDR: u8 = 0x5A; // §8.5.3.1
+
+// Register map, §8.6
+const REG_STATUS: u8 = 0x00;
+const REG_MODE: u8 = 0x01;
+const REG_RTP_INPUT: u8 = 0x02;
+const REG_LIBRARY: u8 = 0x03;
+const REG_WAVESEQ1: u8 = 0x04;
+const REG_WAVESEQ2: u8 = 0x05;
+const REG_GO: u8 = 0x0C;
+const REG_FEEDBACK: u8 = 0x1A;
+
+/// Bit 7 of FEEDBACK selects LRA (1) or ERM (0) mode.
+const FEEDBACK_LRA: u8 = 1 << 7;
+
+/// Type of the motor attached to the DRV2605L.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Actuator {
+    /// Eccentric rotating mass motor, uses ROM library A (§11.2).
+    Erm,
+    /// Linear resonant actuator, uses the LRA ROM library.
+    Lra,
+}
+
+/// DRV2605L connected over I2C.
+pub struct Drv2605l<I: I2c> {
+    i2c: I,
+    actuator: Actuator,
+}
+
+impl<I: I2c> Drv2605l<I>
+where
+    I::Error: Debug,
+{
+    pub fn new(i2c: I, actuator: Actuator) -> Self {
+        Self { i2c, actuator }
+    }
+
+    async fn write_reg(&mut self, reg: u8, value: u8) -> Result<(), I::Error> {
+        self.i2c.write(I2C_ADDR, &[reg, value]).await
+    }
+
+    async fn read_reg(&mut self, reg: u8) -> Result<u8, I::Error> {
+        let mut buf = [0u8];
+        self.i2c.write_read(I2C_ADDR, &[reg], &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    async fn try_init(&mut self) -> Result<(), I::Error> {
+        // STATUS bits 7:5 hold the device id, 7 for DRV2605L (§8.6.1)
+        let device_id = self.read_reg(REG_STATUS).await? >> 5;
+        if device_id != 7 {
+            warn!("DRV2605L: unexpected device id {}", device_id);
+        }
+        // Leave standby, internal trigger mode (§8.6.2)
+        self.write_reg(REG_MODE, 0x00).await?;
+        self.write_reg(REG_RTP_INPUT, 0x00).await?;
+        let feedback = self.read_reg(REG_FEEDBACK).await?;
+        let (library, feedback) = match self.actuator {
+            Actuator::Erm => (1, feedback & !FEEDBACK_LRA),
+            Actuator::Lra => (6, feedback | FEEDBACK_LRA),
+        };
+        self.write_reg(REG_FEEDBACK, feedback).await?;
+        self.write_reg(REG_LIBRARY, library).await
+    }
+
+    async fn try_play(&mut self, effect: u8) -> Result<(), I::Error> {
+        self.write_reg(REG_WAVESEQ1, effect).await?;
+        // A zero in the next slot ends the sequence
+        self.write_reg(REG_WAVESEQ2, 0).await?;
+        self.write_reg(REG_GO, 1).await
+    }
+}
+
+impl<I: I2c> HapticDriver for Drv2605l<I>
+where
+    I::Error: Debug,
+{
+    async fn init(&mut self) {
+        if let Err(e) = self.try_init().await {
+            error!("DRV2605L init error: {:?}", e);
+        }
+    }
+
+    async fn play(&mut self, effect: u8) {
+        if let Err(e) = self.try_play(effect).await {
+            error!("DRV2605L play error: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Haptic feedback.
+//!
+//! [`HapticProcessor`] plays a haptic effect on configured keyboard events:
+//! layer changes, combo triggers, Caps Word toggles and key presses. The
+//! motor driver is abstracted by [`HapticDriver`]; RMK ships a driver for the
+//! TI [`Drv2605l`]. For typewriter-style clickers, [`SolenoidProcessor`]
+//! pulses a GPIO-driven solenoid on key presses.
+//!
+//! Haptics can be turned on and off with the `HapticOn`, `HapticOff` and
+//! `HapticToggle` keycodes.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use rmk::haptic::{Actuator, Drv2605l, HapticConfig, HapticProcessor, effects};
+//!
+//! let drv = Drv2605l::new(i2c, Actuator::Lra);
+//! let config = HapticConfig {
+//!     layer_change: Some(effects::DOUBLE_CLICK),
+//!     combo: Some(effects::SHARP_CLICK),
+//!     ..Default::default()
+//! };
+//! let mut haptic = HapticProcessor::new(drv, config);
+//! run_all!(matrix, haptic);
+//! ```
+
+mod drv2605l;
+mod solenoid;
+
+pub use drv2605l::{Actuator, Drv2605l};
+pub use solenoid::SolenoidProcessor;
+use rmk_macro::processor;
+use rmk_types::action::{Action, KeyboardAction};
+
+use crate::core_traits::Runnable;
+use crate::event::{ActionEvent, ComboTriggeredEvent, KeyboardEvent, LayerChangeEvent};
+use crate::processor::Processor;
+
+/// Effect ids of the DRV2605L ROM library (§11.2 of the datasheet).
+pub mod effects {
+    pub const STRONG_CLICK: u8 = 1;
+    pub const SHARP_CLICK: u8 = 4;
+    pub const SOFT_BUMP: u8 = 7;
+    pub const DOUBLE_CLICK: u8 = 10;
+    pub const TRIPLE_CLICK: u8 = 12;
+    pub const SOFT_FUZZ: u8 = 13;
+    pub const STRONG_BUZZ: u8 = 14;
+    pub const SHARP_TICK: u8 = 24;
+    pub const BUZZ: u8 = 47;
+    pub const PULSING_STRONG: u8 = 52;
+    pub const TRANSITION_CLICK: u8 = 58;
+}
+
+/// Chip-agnostic haptic motor driver.
+pub trait HapticDriver {
+    /// Initialize the driver hardware.
+    async fn init(&mut self);
+    /// Start playing `effect`. Returns without waiting for the effect to finish.
+    async fn play(&mut self, effect: u8);
+}
+
+/// Effect played for each keyboard event, `None` disables feedback for that event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HapticConfig {
+    pub layer_change: Option<u8>,
+    pub combo: Option<u8>,
+    pub caps_word: Option<u8>,
+    pub key_press: Option<u8>,
+}
+
+impl Default for HapticConfig {
+    fn default() -> Self {
+        Self {
+            layer_change: Some(effects::DOUBLE_CLICK),
+            combo: Some(effects::SHARP_CLICK),
+            caps_word: Some(effects::SOFT_BUMP),
+            key_press: None,
+        }
+    }
+}
+
+/// Processor playing haptic effects on keyboard events.
+#[processor(subscribe = [KeyboardEvent, LayerChangeEvent, ComboTriggeredEvent, ActionEvent])]
+#[::rmk::macros::runnable_generated]
+pub struct HapticProcessor<H: HapticDriver> {
+    driver: H,
+    config: HapticConfig,
+    enabled: bool,
+}
+
+impl<H: HapticDriver> HapticProcessor<H> {
+    pub fn new(driver: H, config: HapticConfig) -> Self {
+        Self {
+            driver,
+            config,
+            enabled: true,
+        }
+    }
+
+    async fn play(&mut self, effect: Option<u8>) {
+        if self.enabled
+            && let Some(effect) = effect
+        {
+            self.driver.play(effect).await;
+        }
+    }
+
+    async fn on_keyboard_event(&mut self, event: KeyboardEvent) {
+        if event.pressed {
+            self.play(self.config.key_press).await;
+        }
+    }
+
+    async fn on_layer_change_event(&mut self, _event: LayerChangeEvent) {
+        self.play(self.config.layer_change).await;
+    }
+
+    async fn on_combo_triggered_event(&mut self, _event: ComboTriggeredEvent) {
+        self.play(self.config.combo).await;
+    }
+
+    async fn on_action_event(&mut self, event: ActionEvent) {
+        if !event.keyboard_event.pressed {
+            return;
+        }
+        match event.action {
+            Action::KeyboardControl(KeyboardAction::CapsWordToggle) => self.play(self.config.caps_word).await,
+            Action::KeyboardControl(KeyboardAction::HapticOn) => self.enabled = true,
+            Action::KeyboardControl(KeyboardAction::HapticOff) => self.enabled = false,
+            Action::KeyboardControl(KeyboardAction::HapticToggle) => self.enabled = !self.enabled,
+            _ => {}
+        }
+    }
+}
+
+impl<H: HapticDriver> Runnable for HapticProcessor<H> {
+    async fn run(&mut self) -> ! {
+        self.driver.init().await;
+        self.process_loop().await
+    }
+}
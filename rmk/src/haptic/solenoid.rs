@@ -0,0 +1,76 @@
+use embassy_time::{Duration, Timer};
+use embedded_hal::digital::StatefulOutputPin;
+use rmk_macro::processor;
+use rmk_types::action::{Action, KeyboardAction};
+
+use crate::driver::gpio::OutputController;
+use crate::event::{ActionEvent, KeyboardEvent, KeyboardEventPos};
+
+/// Processor pulsing a GPIO-driven solenoid (or relay) on every key press.
+///
+/// The pin is held active for the dwell time, then released. Keys can be
+/// excluded with [`with_key_mask`](Self::with_key_mask), and the clicker is
+/// switched with the `HapticOn`, `HapticOff` and `HapticToggle` keycodes.
+#[processor(subscribe = [KeyboardEvent, ActionEvent])]
+pub struct SolenoidProcessor<P: StatefulOutputPin> {
+    pin: OutputController<P>,
+    dwell: Duration,
+    /// One bitmask per row, bit `n` enables column `n`. `None` enables all keys.
+    key_mask: Option<&'static [u32]>,
+    enabled: bool,
+}
+
+impl<P: StatefulOutputPin> SolenoidProcessor<P> {
+    pub fn new(pin: P, low_active: bool) -> Self {
+        Self {
+            pin: OutputController::new(pin, low_active),
+            dwell: Duration::from_millis(12),
+            key_mask: None,
+            enabled: true,
+        }
+    }
+
+    /// Set how long the solenoid is energized per key press. Default: 12 ms.
+    pub fn with_dwell(mut self, dwell: Duration) -> Self {
+        self.dwell = dwell;
+        self
+    }
+
+    /// Only fire for keys whose bit is set in `mask`: one `u32` per row, bit
+    /// `n` for column `n`. Rows missing from `mask` are disabled.
+    pub fn with_key_mask(mut self, mask: &'static [u32]) -> Self {
+        self.key_mask = Some(mask);
+        self
+    }
+
+    fn key_enabled(&self, pos: KeyboardEventPos) -> bool {
+        match (self.key_mask, pos) {
+            (None, _) => true,
+            (Some(mask), KeyboardEventPos::Key(key)) => mask
+                .get(key.row as usize)
+                .is_some_and(|bits| key.col < 32 && bits & (1 << key.col) != 0),
+            // Encoders have no matrix position to look up
+            (Some(_), _) => false,
+        }
+    }
+
+    async fn on_keyboard_event(&mut self, event: KeyboardEvent) {
+        if self.enabled && event.pressed && self.key_enabled(event.pos) {
+            self.pin.activate();
+            Timer::after(self.dwell).await;
+            self.pin.deactivate();
+        }
+    }
+
+    async fn on_action_event(&mut self, event: ActionEvent) {
+        if !event.keyboard_event.pressed {
+            return;
+        }
+        match event.action {
+            Action::KeyboardControl(KeyboardAction::HapticOn) => self.enabled = true,
+            Action::KeyboardControl(KeyboardAction::HapticOff) => self.enabled = false,
+            Action::KeyboardControl(KeyboardAction::HapticToggle) => self.enabled = !self.enabled,
+            _ => {}
+        }
+    }
+}
@@ -1,6 +1,6 @@
 /// Traits and types for HID message reporting and listening.
 use core::future::Future;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use embassy_usb::class::hid::ReadError;
 use embassy_usb::driver::EndpointError;
@@ -16,7 +16,11 @@ use crate::keyboard::LOCK_LED_STATES;
 /// KeyboardReport describes a report and its companion descriptor that can be
 /// used to send keyboard button presses to a host and receive the status of the
 /// keyboard LEDs.
-#[gen_hid_descriptor(
+///
+/// With the `apple_fn` feature, the descriptor also declares the Apple Fn key, see `APPLE_FN_KEYBOARD_DESCRIPTOR`.
+#[cfg_attr(
+    not(feature = "apple_fn"),
+    gen_hid_descriptor(
     (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = KEYBOARD) = {
         (usage_page = KEYBOARD, usage_min = 0xE0, usage_max = 0xE7) = {
             #[packed_bits = 8] #[item_settings(data,variable,absolute)] modifier=input;
@@ -31,17 +35,89 @@ use crate::keyboard::LOCK_LED_STATES;
             #[item_settings(data,array,absolute)] keycodes=input;
         };
     }
-)]
+))]
 #[allow(dead_code)]
 #[derive(Default)]
+// Derived by `gen_hid_descriptor` without `apple_fn`
+#[cfg_attr(feature = "apple_fn", derive(Debug, Clone, Copy, PartialEq, Eq))]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct KeyboardReport {
     pub modifier: u8, // ModifierCombination
-    pub reserved: u8,
-    pub leds: u8, // LedIndicator
+    pub reserved: u8, // Bit 0 is the Apple Fn key with the `apple_fn` feature
+    pub leds: u8,     // LedIndicator
     pub keycodes: [u8; 6],
 }
 
+/// Length of the descriptor of [`KeyboardReport`]
+#[cfg(not(feature = "apple_fn"))]
+pub(crate) const KEYBOARD_DESCRIPTOR_LEN: usize = 67;
+#[cfg(feature = "apple_fn")]
+pub(crate) const KEYBOARD_DESCRIPTOR_LEN: usize = APPLE_FN_KEYBOARD_DESCRIPTOR.len();
+
+/// Boot keyboard descriptor with the Apple Fn key in bit 0 of the reserved byte.
+///
+/// macOS handles usage `0x03` of the Apple vendor usage page `0xFF` as the Fn/Globe key,
+/// like on Apple keyboards. The report layout doesn't change, so the keyboard still
+/// works in boot protocol and other hosts ignore the bit.
+#[cfg(feature = "apple_fn")]
+pub(crate) const APPLE_FN_KEYBOARD_DESCRIPTOR: [u8; 68] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xA1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0xE0, //   Usage Minimum (Left Control)
+    0x29, 0xE7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute), modifiers
+    0x05, 0xFF, //   Usage Page (Apple vendor top case)
+    0x09, 0x03, //   Usage (Keyboard Fn)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x02, //   Input (Data, Variable, Absolute), Fn
+    0x75, 0x07, //   Report Size (7)
+    0x81, 0x03, //   Input (Constant), padding
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x05, //   Report Count (5)
+    0x91, 0x02, //   Output (Data, Variable, Absolute), LEDs
+    0x75, 0x03, //   Report Size (3)
+    0x95, 0x01, //   Report Count (1)
+    0x91, 0x03, //   Output (Constant), padding
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0xDD, //   Usage Maximum (0xDD)
+    0x26, 0xDD, 0x00, // Logical Maximum (0xDD)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x06, //   Report Count (6)
+    0x81, 0x00, //   Input (Data, Array, Absolute), keycodes
+    0xC0, // End Collection
+];
+
+#[cfg(feature = "apple_fn")]
+impl SerializedDescriptor for KeyboardReport {
+    fn desc() -> &'static [u8] {
+        &APPLE_FN_KEYBOARD_DESCRIPTOR
+    }
+}
+
+// Same input report as the generated one, the LEDs are an output
+#[cfg(feature = "apple_fn")]
+impl AsInputReport for KeyboardReport {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<usize, usbd_hid::descriptor::BufferOverflow> {
+        if buffer.len() < 8 {
+            return Err(usbd_hid::descriptor::BufferOverflow);
+        }
+        buffer[0] = self.modifier;
+        buffer[1] = self.reserved;
+        buffer[2..8].copy_from_slice(&self.keycodes);
+        Ok(8)
+    }
+}
+
 #[gen_hid_descriptor(
     (collection = APPLICATION, usage_page = 0xFF60, usage = 0x61) = {
         (usage = 0x62, logical_min = 0x0) = {
@@ -58,6 +134,26 @@ pub struct ViaReport {
     pub(crate) output_data: [u8; 32],
 }
 
+/// Raw HID report for host companion apps, see [`crate::usb::raw_hid`].
+///
+/// Uses a different vendor usage page than [`ViaReport`], so the host can tell both interfaces apart.
+#[cfg(feature = "raw_hid")]
+#[gen_hid_descriptor(
+    (collection = APPLICATION, usage_page = 0xFF00, usage = 0x01) = {
+        (usage = 0x02, logical_min = 0x0) = {
+            #[item_settings(data,variable,absolute)] input_data=input;
+        };
+        (usage = 0x03, logical_min = 0x0) = {
+            #[item_settings(data,variable,absolute)] output_data=output;
+        };
+    }
+)]
+#[derive(Default)]
+pub struct RawHidReport {
+    pub(crate) input_data: [u8; 32],
+    pub(crate) output_data: [u8; 32],
+}
+
 /// Predefined report ids for composite hid report.
 /// Should be same with `#[gen_hid_descriptor]`
 /// DO NOT EDIT
@@ -70,6 +166,7 @@ pub enum CompositeReportType {
     Mouse = 0x01,
     Media = 0x02,
     System = 0x03,
+    Battery = 0x04,
 }
 
 /// Plover HID stenography report.
@@ -117,6 +214,25 @@ impl usbd_hid::descriptor::AsInputReport for StenoReport {
     }
 }
 
+#[cfg(all(test, feature = "apple_fn"))]
+mod apple_fn_tests {
+    use super::*;
+
+    #[test]
+    fn apple_fn_report_keeps_boot_layout() {
+        let report = KeyboardReport {
+            modifier: 0x02,
+            reserved: 0x01,
+            leds: 0x1F,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        };
+        let mut buf = [0u8; 8];
+        assert_eq!(report.serialize(&mut buf).ok(), Some(8));
+        assert_eq!(buf, [0x02, 0x01, 0x04, 0x05, 0, 0, 0, 0]);
+        assert!(KeyboardReport::desc().windows(4).any(|w| w == [0x05, 0xFF, 0x09, 0x03]));
+    }
+}
+
 #[cfg(all(test, feature = "steno"))]
 mod steno_tests {
     use usbd_hid::descriptor::SerializedDescriptor;
@@ -141,64 +257,351 @@ mod steno_tests {
     }
 }
 
-/// A composite hid report which contains mouse, consumer, system reports.
-/// Report id is used to distinguish from them.
-#[gen_hid_descriptor(
-    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = MOUSE) = {
-        (collection = PHYSICAL, usage = POINTER) = {
-            (report_id = 0x01,) = {
-                (usage_page = BUTTON, usage_min = BUTTON_1, usage_max = BUTTON_8) = {
-                    #[packed_bits = 8] #[item_settings(data,variable,absolute)] buttons=input;
-                };
-                (usage_page = GENERIC_DESKTOP,) = {
-                    (usage = X,) = {
-                        #[item_settings(data,variable,relative)] x=input;
-                    };
-                    (usage = Y,) = {
-                        #[item_settings(data,variable,relative)] y=input;
+/// Declare `CompositeReport` and the descriptor of each of its parts from a single copy of
+/// each part's descriptor.
+///
+/// The parts are used by the USB interface, which only contains the enabled reports, see
+/// `UsbConfig`. Each one is a complete descriptor on its own, so they can be concatenated in
+/// any combination.
+macro_rules! composite_report {
+    (
+        mouse: { $($mouse:tt)* },
+        media: { $($media:tt)* },
+        system: { $($system:tt)* } $(,)?
+    ) => {
+        /// A composite hid report which contains mouse, consumer, system reports.
+        /// Report id is used to distinguish from them.
+        #[gen_hid_descriptor($($mouse)*, $($media)*, $($system)*)]
+        #[derive(Default, Serialize)]
+        pub struct CompositeReport {
+            pub(crate) buttons: u8, // MouseButtons
+            pub(crate) x: i8,
+            pub(crate) y: i8,
+            pub(crate) wheel: i8, // Scroll down (negative) or up (positive) this many units
+            pub(crate) pan: i8,   // Scroll left (negative) or right (positive) this many units
+            pub(crate) media_usage_id: u16,
+            pub(crate) system_usage_id: u8,
+        }
+
+        #[gen_hid_descriptor($($mouse)*)]
+        #[allow(dead_code)]
+        pub(crate) struct CompositeMouseDescriptor {
+            buttons: u8,
+            x: i8,
+            y: i8,
+            wheel: i8,
+            pan: i8,
+        }
+
+        #[gen_hid_descriptor($($media)*)]
+        #[allow(dead_code)]
+        pub(crate) struct CompositeMediaDescriptor {
+            media_usage_id: u16,
+        }
+
+        #[gen_hid_descriptor($($system)*)]
+        #[allow(dead_code)]
+        pub(crate) struct CompositeSystemDescriptor {
+            system_usage_id: u8,
+        }
+    };
+}
+
+composite_report! {
+    mouse: {
+        (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = MOUSE) = {
+            (collection = PHYSICAL, usage = POINTER) = {
+                (report_id = 0x01,) = {
+                    (usage_page = BUTTON, usage_min = BUTTON_1, usage_max = BUTTON_8) = {
+                        #[packed_bits = 8] #[item_settings(data,variable,absolute)] buttons=input;
                     };
-                    (usage = WHEEL,) = {
-                        #[item_settings(data,variable,relative)] wheel=input;
+                    (usage_page = GENERIC_DESKTOP,) = {
+                        (usage = X,) = {
+                            #[item_settings(data,variable,relative)] x=input;
+                        };
+                        (usage = Y,) = {
+                            #[item_settings(data,variable,relative)] y=input;
+                        };
+                        (usage = WHEEL,) = {
+                            #[item_settings(data,variable,relative)] wheel=input;
+                        };
                     };
-                };
-                (usage_page = CONSUMER,) = {
-                    (usage = AC_PAN,) = {
-                        #[item_settings(data,variable,relative)] pan=input;
+                    (usage_page = CONSUMER,) = {
+                        (usage = AC_PAN,) = {
+                            #[item_settings(data,variable,relative)] pan=input;
+                        };
                     };
                 };
             };
-        };
+        }
     },
-    (collection = APPLICATION, usage_page = CONSUMER, usage = CONSUMER_CONTROL) = {
-        (report_id = 0x02,) = {
-            (usage_page = CONSUMER, usage_min = 0x00, usage_max = 0x514) = {
-            #[item_settings(data,array,absolute,not_null)] media_usage_id=input;
-            }
-        };
+    media: {
+        (collection = APPLICATION, usage_page = CONSUMER, usage = CONSUMER_CONTROL) = {
+            (report_id = 0x02,) = {
+                (usage_page = CONSUMER, usage_min = 0x00, usage_max = 0x514) = {
+                #[item_settings(data,array,absolute,not_null)] media_usage_id=input;
+                }
+            };
+        }
     },
-    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = SYSTEM_CONTROL) = {
-        (report_id = 0x03,) = {
-            (usage_min = 0x01, usage_max = 0xB7, logical_min = 1) = {
-                #[item_settings(data,array,absolute,not_null)] system_usage_id=input;
+    system: {
+        (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = SYSTEM_CONTROL) = {
+            (report_id = 0x03,) = {
+                (usage_min = 0x01, usage_max = 0xB7, logical_min = 1) = {
+                    #[item_settings(data,array,absolute,not_null)] system_usage_id=input;
+                };
             };
+        }
+    },
+}
+
+/// Battery strength on the Generic Device Controls page, the usage hosts like Linux
+/// show as the battery of the keyboard. Written by hand because the logical maximum
+/// must be 100 for the host to read the value as a percentage.
+pub(crate) const COMPOSITE_BATTERY_DESCRIPTOR: [u8; 21] = [
+    0x05, 0x06, // Usage Page (Generic Device Controls)
+    0x09, 0x20, // Usage (Battery Strength)
+    0xA1, 0x01, // Collection (Application)
+    0x85, 0x04, //   Report ID (CompositeReportType::Battery)
+    0x09, 0x20, //   Usage (Battery Strength)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x64, //   Logical Maximum (100)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xC0, // End Collection
+];
+
+/// Battery level in percent, see `COMPOSITE_BATTERY_DESCRIPTOR`
+#[cfg(feature = "_ble")]
+pub(crate) struct BatteryReport {
+    pub(crate) level: u8,
+}
+
+#[cfg(feature = "_ble")]
+impl AsInputReport for BatteryReport {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<usize, usbd_hid::descriptor::BufferOverflow> {
+        let Some(byte) = buffer.first_mut() else {
+            return Err(usbd_hid::descriptor::BufferOverflow);
+        };
+        *byte = self.level.min(100);
+        Ok(1)
+    }
+}
+
+/// Whether the USB transport has the NKRO interface, see `UsbConfig::nkro`. Without it, the
+/// keyboard drops keys beyond the 6 of the boot keyboard report.
+pub(crate) static NKRO_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Keys held beyond the 6 of [`KeyboardReport`], one bit for each key of the keyboard usage
+/// page up to `0xDF`.
+///
+/// It's sent on the NKRO interface next to the keyboard interface, so hosts in boot protocol,
+/// like a BIOS, still get the first 6 keys from the keyboard report. Both reports never hold
+/// the same key, so the host doesn't see a key pressed twice.
+#[gen_hid_descriptor(
+    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = KEYBOARD) = {
+        (usage_page = KEYBOARD, usage_min = 0x00, usage_max = 0xDF) = {
+            #[packed_bits = 224] #[item_settings(data,variable,absolute)] keys=input;
         };
     }
 )]
-#[derive(Default, Serialize)]
-pub struct CompositeReport {
-    pub(crate) buttons: u8, // MouseButtons
-    pub(crate) x: i8,
-    pub(crate) y: i8,
-    pub(crate) wheel: i8, // Scroll down (negative) or up (positive) this many units
-    pub(crate) pan: i8,   // Scroll left (negative) or right (positive) this many units
-    pub(crate) media_usage_id: u16,
-    pub(crate) system_usage_id: u8,
+#[derive(Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NkroKeyboardReport {
+    pub keys: [u8; 28],
+}
+
+impl NkroKeyboardReport {
+    /// Whether the key with HID usage `key` is held
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys
+            .get(key as usize / 8)
+            .is_some_and(|byte| byte & (1 << (key % 8)) != 0)
+    }
+
+    /// Whether no key is held
+    pub fn is_empty(&self) -> bool {
+        self.keys.iter().all(|&byte| byte == 0)
+    }
+
+    pub(crate) fn press(&mut self, key: u8) {
+        if let Some(byte) = self.keys.get_mut(key as usize / 8) {
+            *byte |= 1 << (key % 8);
+        }
+    }
+
+    pub(crate) fn release(&mut self, key: u8) {
+        if let Some(byte) = self.keys.get_mut(key as usize / 8) {
+            *byte &= !(1 << (key % 8));
+        }
+    }
+}
+
+/// Largest `x` and `y` of a [`DigitizerReport`]
+pub const DIGITIZER_MAX: u16 = 0x7FFF;
+
+/// Pen digitizer, written by hand because the tip switch and in range usages aren't
+/// consecutive, so they can't be one field of `gen_hid_descriptor`.
+const DIGITIZER_DESCRIPTOR: [u8; 45] = [
+    0x05, 0x0D, // Usage Page (Digitizer)
+    0x09, 0x02, // Usage (Pen)
+    0xA1, 0x01, // Collection (Application)
+    0x09, 0x20, //   Usage (Stylus)
+    0xA1, 0x00, //   Collection (Physical)
+    0x09, 0x42, //     Usage (Tip Switch)
+    0x09, 0x32, //     Usage (In Range)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x25, 0x01, //     Logical Maximum (1)
+    0x75, 0x01, //     Report Size (1)
+    0x95, 0x02, //     Report Count (2)
+    0x81, 0x02, //     Input (Data, Variable, Absolute), tip switch and in range
+    0x95, 0x06, //     Report Count (6)
+    0x81, 0x03, //     Input (Constant), padding
+    0x05, 0x01, //     Usage Page (Generic Desktop)
+    0x09, 0x30, //     Usage (X)
+    0x09, 0x31, //     Usage (Y)
+    0x26, 0xFF, 0x7F, // Logical Maximum (DIGITIZER_MAX)
+    0x75, 0x10, //     Report Size (16)
+    0x95, 0x02, //     Report Count (2)
+    0x81, 0x02, //     Input (Data, Variable, Absolute), x and y
+    0xC0, //   End Collection
+    0xC0, // End Collection
+];
+
+/// Absolute position of a pen, like on a drawing tablet, sent on the digitizer interface,
+/// see `UsbConfig::digitizer`.
+///
+/// Nothing in RMK generates it, custom input devices queue it to
+/// [`crate::channel::USB_REPORT_CHANNEL`]. It isn't sent over BLE.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DigitizerReport {
+    /// The pen touches the surface
+    pub tip: bool,
+    /// The pen is close enough to the surface to be tracked
+    pub in_range: bool,
+    /// Horizontal position, `0..=DIGITIZER_MAX`
+    pub x: u16,
+    /// Vertical position, `0..=DIGITIZER_MAX`
+    pub y: u16,
+}
+
+impl SerializedDescriptor for DigitizerReport {
+    fn desc() -> &'static [u8] {
+        &DIGITIZER_DESCRIPTOR
+    }
+}
+
+impl AsInputReport for DigitizerReport {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<usize, usbd_hid::descriptor::BufferOverflow> {
+        if buffer.len() < 5 {
+            return Err(usbd_hid::descriptor::BufferOverflow);
+        }
+        buffer[0] = (self.tip as u8) | ((self.in_range as u8) << 1);
+        buffer[1..3].copy_from_slice(&self.x.min(DIGITIZER_MAX).to_le_bytes());
+        buffer[3..5].copy_from_slice(&self.y.min(DIGITIZER_MAX).to_le_bytes());
+        Ok(5)
+    }
+}
+
+#[cfg(test)]
+mod composite_tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+
+    #[test]
+    fn composite_parts_keep_report_ids() {
+        let parts = [
+            (CompositeMouseDescriptor::desc(), CompositeReportType::Mouse),
+            (CompositeMediaDescriptor::desc(), CompositeReportType::Media),
+            (CompositeSystemDescriptor::desc(), CompositeReportType::System),
+            (&COMPOSITE_BATTERY_DESCRIPTOR[..], CompositeReportType::Battery),
+        ];
+        for (desc, kind) in parts {
+            assert!(
+                desc.windows(2).any(|w| w == [0x85, kind as u8]),
+                "missing ReportID {:?}",
+                kind
+            );
+            // Every part is a complete top-level collection
+            assert_eq!(desc.last(), Some(&0xC0));
+        }
+        // Fits the buffer in `usb::composite_descriptor`
+        assert!(parts.iter().map(|(desc, _)| desc.len()).sum::<usize>() <= 192);
+    }
+
+    #[test]
+    fn composite_report_is_made_of_its_parts() {
+        let parts = [
+            CompositeMouseDescriptor::desc(),
+            CompositeMediaDescriptor::desc(),
+            CompositeSystemDescriptor::desc(),
+        ]
+        .concat();
+        assert_eq!(CompositeReport::desc(), &parts[..]);
+    }
+
+    #[test]
+    fn nkro_report_keeps_one_bit_per_key() {
+        let mut report = NkroKeyboardReport::default();
+        for key in [0x04, 0x05, 0xDF] {
+            report.press(key);
+        }
+        report.release(0x05);
+        // Beyond the keyboard page usages of the report
+        report.press(0xE0);
+        assert!(report.is_pressed(0x04) && report.is_pressed(0xDF));
+        assert!(!report.is_pressed(0x05) && !report.is_pressed(0xE0));
+        let mut buf = [0u8; 28];
+        assert_eq!(report.serialize(&mut buf).ok(), Some(28));
+        assert_eq!((buf[0], buf[27]), (0x10, 0x80));
+    }
+
+    #[test]
+    fn digitizer_report_layout() {
+        let report = DigitizerReport {
+            tip: true,
+            in_range: true,
+            x: 0x1234,
+            y: 0xFFFF,
+        };
+        let mut buf = [0u8; 5];
+        assert_eq!(report.serialize(&mut buf).ok(), Some(5));
+        assert_eq!(buf, [0x03, 0x34, 0x12, 0xFF, 0x7F]);
+    }
+}
+
+/// Add the motion of `next` to `report`, returns false if the buttons differ or the motion overflows.
+pub(crate) fn merge_mouse_report(report: &mut MouseReport, next: &MouseReport) -> bool {
+    if report.buttons != next.buttons {
+        return false;
+    }
+    let (Some(x), Some(y), Some(wheel), Some(pan)) = (
+        report.x.checked_add(next.x),
+        report.y.checked_add(next.y),
+        report.wheel.checked_add(next.wheel),
+        report.pan.checked_add(next.pan),
+    ) else {
+        return false;
+    };
+    *report = MouseReport {
+        buttons: report.buttons,
+        x,
+        y,
+        wheel,
+        pan,
+    };
+    true
 }
 
 #[derive(Debug, Clone)]
 pub enum Report {
     /// Normal keyboard hid report
     KeyboardReport(KeyboardReport),
+    /// Keys held beyond the 6 of the keyboard report
+    NkroKeyboardReport(NkroKeyboardReport),
     /// Mouse hid report
     MouseReport(MouseReport),
     /// Media keyboard report
@@ -208,17 +611,21 @@ pub enum Report {
     /// Plover HID stenography chord report
     #[cfg(feature = "steno")]
     StenoReport(StenoReport),
+    /// Pen digitizer report
+    DigitizerReport(DigitizerReport),
 }
 
 impl AsInputReport for Report {
     fn serialize(&self, buffer: &mut [u8]) -> Result<usize, usbd_hid::descriptor::BufferOverflow> {
         match self {
             Report::KeyboardReport(r) => r.serialize(buffer),
+            Report::NkroKeyboardReport(r) => r.serialize(buffer),
             Report::MouseReport(r) => r.serialize(buffer),
             Report::MediaKeyboardReport(r) => r.serialize(buffer),
             Report::SystemControlReport(r) => r.serialize(buffer),
             #[cfg(feature = "steno")]
             Report::StenoReport(r) => r.serialize(buffer),
+            Report::DigitizerReport(r) => r.serialize(buffer),
         }
     }
 }
@@ -229,7 +636,11 @@ pub enum HidError {
     UsbReadError(ReadError),
     UsbEndpointError(EndpointError),
     ReportSerializeError,
+    /// The report's interface or report id isn't enabled in the transport, see `UsbConfig`
+    ReportDisabled,
     BleError,
+    /// Error of a custom [`crate::transport::WirelessTransport`]
+    TransportError,
 }
 
 /// HidWriter trait is used for reporting HID messages to the host, via USB, BLE, etc.
@@ -253,8 +664,11 @@ pub trait HidReaderTrait {
     fn read_report(&mut self) -> impl Future<Output = Result<Self::ReportType, HidError>>;
 }
 
-/// Drain LED indicator OUT reports from `reader` and republish them as
-/// [`LedIndicatorEvent`]s whenever `kind` is the active output transport.
+/// Last LED indicator state sent by each host, indexed by [`ConnectionType`]
+pub(crate) static HOST_LED_STATES: [AtomicU8; 2] = [AtomicU8::new(0), AtomicU8::new(0)];
+
+/// Drain LED indicator OUT reports from `reader`. The state is kept per host,
+/// and published as [`LedIndicatorEvent`] when `kind` is the active output transport.
 pub(crate) async fn run_led_reader<R: HidReaderTrait<ReportType = LedIndicator>>(
     reader: &mut R,
     kind: ConnectionType,
@@ -263,10 +677,7 @@ pub(crate) async fn run_led_reader<R: HidReaderTrait<ReportType = LedIndicator>>
         match reader.read_report().await {
             Ok(led_indicator) => {
                 info!("Got led indicator");
-                if crate::state::active_transport() == Some(kind) {
-                    LOCK_LED_STATES.store(led_indicator.into_bits(), Ordering::Relaxed);
-                    publish_event(LedIndicatorEvent::new(led_indicator));
-                }
+                set_host_led_indicator(kind, led_indicator);
             }
             Err(e) => {
                 debug!("Read HID LED indicator error: {:?}", e);
@@ -275,3 +686,27 @@ pub(crate) async fn run_led_reader<R: HidReaderTrait<ReportType = LedIndicator>>
         }
     }
 }
+
+/// Keep the LED state sent by the host on `kind`, and show it if `kind` is the active output transport.
+pub(crate) fn set_host_led_indicator(kind: ConnectionType, led_indicator: LedIndicator) {
+    HOST_LED_STATES[kind as usize].store(led_indicator.into_bits(), Ordering::Relaxed);
+    if crate::state::active_transport() == Some(kind) {
+        set_led_indicator(led_indicator);
+    }
+}
+
+/// Show the LED state of the new active host, called when the active transport changes.
+///
+/// Without an active host all LEDs are off.
+pub(crate) fn sync_led_indicator(active: Option<ConnectionType>) {
+    let bits = active.map_or(0, |t| HOST_LED_STATES[t as usize].load(Ordering::Relaxed));
+    set_led_indicator(LedIndicator::from_bits(bits));
+}
+
+/// Update the current LED state, publish [`LedIndicatorEvent`] only when it changed
+fn set_led_indicator(led_indicator: LedIndicator) {
+    let old = LOCK_LED_STATES.swap(led_indicator.into_bits(), Ordering::Relaxed);
+    if old != led_indicator.into_bits() {
+        publish_event(LedIndicatorEvent::new(led_indicator));
+    }
+}
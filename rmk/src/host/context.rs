@@ -9,7 +9,7 @@
 //! The context does not subscribe to events — the underlying statics it
 //! reads from are kept in sync by the relevant event handlers
 //! (`BatteryProcessor::commit`, `state.rs::update_status`,
-//! `keyboard::run_led_reader`).
+//! `keyboard::run_led_reader`, `PeripheralManager::process_peripheral_message`).
 
 use embassy_time::Duration;
 use rmk_types::action::{EncoderAction, KeyAction};
@@ -314,6 +314,12 @@ impl<'a> KeyboardContext<'a> {
         crate::input_device::battery::current_battery_status()
     }
 
+    /// Battery status of the peripheral `id` of a wireless split, as last reported by it.
+    #[cfg(all(feature = "split", feature = "_ble"))]
+    pub fn peripheral_battery_status(&self, id: usize) -> BatteryStatus {
+        crate::split::ble::current_peripheral_battery_status(id)
+    }
+
     pub fn active_layer(&self) -> u8 {
         self.keymap.active_layer()
     }
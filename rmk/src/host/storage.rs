@@ -1,12 +1,39 @@
 use embedded_storage_async::nor_flash::NorFlash as AsyncNorFlash;
 use rmk_types::fork::Fork;
 use rmk_types::morse::Morse;
+use sequential_storage::Error as SSError;
+use sequential_storage::map::SerializationError;
 use serde::de::{Error as DeError, SeqAccess, Visitor};
 use serde::{Deserializer, Serializer};
 
 use crate::keyboard::combo::Combo;
-use crate::storage::{Storage, StorageData, StorageKey, print_storage_error};
-use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, MACRO_SPACE_SIZE, MORSE_MAX_NUM};
+use crate::storage::{PROFILE_KEY_CHANNEL, Storage, StorageData, StorageKey, get_buffer_size, print_storage_error};
+use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, KEYMAP_PROFILE_NUM, MACRO_SPACE_SIZE, MORSE_MAX_NUM};
+
+/// Record of a backup: a postcard serialized `StorageKey`, followed by its postcard serialized `StorageData`
+pub(crate) type BackupRecord = heapless::Vec<u8, { get_buffer_size() }>;
+
+/// Item of a backup, read by its index
+#[derive(Clone, Debug)]
+pub(crate) enum BackupItem {
+    Saved(BackupRecord),
+    /// Nothing is saved for the key of this index
+    Empty,
+    /// The index is past the last item
+    End,
+}
+
+/// Settings in a backup, which are saved once
+const BACKUP_SETTINGS: &[StorageKey] = &[
+    StorageKey::LayoutConfig,
+    StorageKey::BehaviorConfig,
+    StorageKey::MacroData,
+    StorageKey::KeymapProfile,
+    #[cfg(feature = "audio")]
+    StorageKey::AudioSettings,
+    #[cfg(feature = "_ble")]
+    StorageKey::BleName,
+];
 
 pub(crate) mod macro_bytes_serde {
     use super::*;
@@ -73,8 +100,13 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     pub(crate) async fn read_keymap(
         &mut self,
         data: &mut crate::keymap::KeymapData<ROW, COL, NUM_LAYER, NUM_ENCODER>,
-        behavior: &mut crate::config::BehaviorConfig,
     ) -> Result<(), ()> {
+        if let Some(StorageData::KeymapProfile(profile)) = self.fetch_checked(StorageKey::KeymapProfile).await?
+            && (profile as usize) < KEYMAP_PROFILE_NUM
+        {
+            self.profile = profile;
+        }
+
         // Use fetch_all_items to speed up the keymap reading
         let mut key_iterator = self
             .flash
@@ -83,18 +115,31 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
             .map_err(|e| print_storage_error::<F>(e))?;
 
         // Read all keymap keys and encoder configs
-        while let Some((key, item)) = key_iterator
-            .next::<StorageData>(&mut self.buffer)
-            .await
-            .map_err(|e| print_storage_error::<F>(e))?
-        {
+        loop {
+            let (key, item) = match key_iterator.next::<StorageData>(&mut self.buffer).await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                // Items torn by a power loss fail the CRC and are skipped by sequential-storage,
+                // skip items which don't decode too, instead of loading garbage actions
+                Err(SSError::SerializationError(_)) => {
+                    warn!("Skipping a corrupted item of the keymap");
+                    continue;
+                }
+                Err(e) => {
+                    print_storage_error::<F>(e);
+                    return Err(());
+                }
+            };
             match (key, item) {
-                (StorageKey::Keymap { layer, row, col }, StorageData::KeyAction(action)) => {
-                    let layer = layer as usize;
-                    let row = row as usize;
-                    let col = col as usize;
-                    if layer < NUM_LAYER && row < ROW && col < COL {
-                        data.keymap[layer][row][col] = action;
+                // Only the keys of the active profile are loaded
+                (StorageKey::Keymap { .. } | StorageKey::ProfileKeymap { .. }, StorageData::KeyAction(action)) => {
+                    if let Some((profile, layer, row, col)) = key.keymap_position()
+                        && profile == self.profile
+                        && (layer as usize) < NUM_LAYER
+                        && (row as usize) < ROW
+                        && (col as usize) < COL
+                    {
+                        data.keymap[layer as usize][row as usize][col as usize] = action;
                     }
                 }
                 (StorageKey::Encoder { layer, idx }, StorageData::EncoderAction(action)) => {
@@ -104,10 +149,6 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
                         data.encoder_map[layer][idx] = action;
                     }
                 }
-                (StorageKey::LayoutConfig, StorageData::LayoutConfig(config)) => {
-                    // Restore the default (base) layer set via a `PDF` key
-                    behavior.default_layer = config.default_layer;
-                }
                 _ => continue,
             }
         }
@@ -115,14 +156,37 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         Ok(())
     }
 
-    pub(crate) async fn read_macro_cache(&mut self, macro_cache: &mut [u8]) -> Result<(), ()> {
-        let read_data = self
-            .flash
-            .fetch_item(&mut self.buffer, &StorageKey::MacroData)
-            .await
-            .map_err(|e| print_storage_error::<F>(e))?;
+    /// Make `profile` the active keymap profile, and send its keys to the keyboard.
+    pub(crate) async fn send_keymap_profile(&mut self, profile: u8) -> Result<(), SSError<F::Error>> {
+        if profile as usize >= KEYMAP_PROFILE_NUM {
+            warn!("Keymap profile {} is not configured, see `keymap_profile_num`", profile);
+            return Ok(());
+        }
+        self.store_data(StorageKey::KeymapProfile, &StorageData::KeymapProfile(profile))
+            .await?;
+        self.profile = profile;
+        info!("Switching to keymap profile {}", profile);
+
+        // Only the keys of the profile are read, instead of every item in the flash
+        for layer in 0..NUM_LAYER as u8 {
+            for row in 0..ROW as u8 {
+                for col in 0..COL as u8 {
+                    let key = StorageKey::profile_keymap(profile, layer, row, col);
+                    match self.flash.fetch_item::<StorageData>(&mut self.buffer, &key).await {
+                        Ok(Some(StorageData::KeyAction(action))) => {
+                            PROFILE_KEY_CHANNEL.send(Some((layer, row, col, action))).await;
+                        }
+                        Ok(_) | Err(SSError::SerializationError(_)) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 
-        if let Some(StorageData::MacroData(data)) = read_data {
+    pub(crate) async fn read_macro_cache(&mut self, macro_cache: &mut [u8]) -> Result<(), ()> {
+        if let Some(StorageData::MacroData(data)) = self.fetch_checked(StorageKey::MacroData).await? {
             macro_cache.copy_from_slice(&data);
         }
 
@@ -134,11 +198,7 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
 
         for (i, item) in combos.iter_mut().enumerate() {
             let key = StorageKey::combo(i as u8);
-            let read_data = self
-                .flash
-                .fetch_item(&mut self.buffer, &key)
-                .await
-                .map_err(|e| print_storage_error::<F>(e))?;
+            let read_data = self.fetch_checked(key).await?;
 
             if let Some(StorageData::Combo(config)) = read_data {
                 debug!("Read combo config: {:?}", config);
@@ -152,11 +212,7 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     pub(crate) async fn read_forks(&mut self, forks: &mut heapless::Vec<Fork, FORK_MAX_NUM>) -> Result<(), ()> {
         for (i, item) in forks.iter_mut().enumerate() {
             let key = StorageKey::fork(i as u8);
-            let read_data = self
-                .flash
-                .fetch_item(&mut self.buffer, &key)
-                .await
-                .map_err(|e| print_storage_error::<F>(e))?;
+            let read_data = self.fetch_checked(key).await?;
 
             if let Some(StorageData::Fork(fork)) = read_data {
                 *item = fork;
@@ -169,11 +225,7 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     pub(crate) async fn read_morses(&mut self, morses: &mut heapless::Vec<Morse, MORSE_MAX_NUM>) -> Result<(), ()> {
         for (i, item) in morses.iter_mut().enumerate() {
             let key = StorageKey::morse(i as u8);
-            let read_data = self
-                .flash
-                .fetch_item(&mut self.buffer, &key)
-                .await
-                .map_err(|e| print_storage_error::<F>(e))?;
+            let read_data = self.fetch_checked(key).await?;
 
             if let Some(StorageData::Morse(morse)) = read_data {
                 *item = morse;
@@ -184,15 +236,259 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     }
 }
 
+/// Most values of user code in a backup
+const BACKUP_USER_DATA_MAX: usize = 64;
+
+/// What a backup index refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BackupIndex {
+    Key(StorageKey),
+    /// The `n`th value of user code, in the order of the flash
+    UserData(usize),
+}
+
+/// Item of the backup at `index`.
+///
+/// Backups leave out the storage config, the connection type and BLE bonds. They are indexed by
+/// these keys instead of the items in the flash, which would list every old value too: first the
+/// settings, then the keymaps of every profile, the encoders, the combos, the forks, the morses,
+/// and last the values of user code, whose keys are only known from the flash.
+fn backup_index<const ROW: usize, const COL: usize, const NUM_LAYER: usize, const NUM_ENCODER: usize>(
+    index: usize,
+) -> BackupIndex {
+    let Some(mut index) = index.checked_sub(BACKUP_SETTINGS.len()) else {
+        return BackupIndex::Key(BACKUP_SETTINGS[index]);
+    };
+    if index < KEYMAP_PROFILE_NUM * NUM_LAYER * ROW * COL {
+        let (profile, index) = (index / (NUM_LAYER * ROW * COL), index % (NUM_LAYER * ROW * COL));
+        let (layer, key) = (index / (ROW * COL), index % (ROW * COL));
+        return BackupIndex::Key(StorageKey::profile_keymap(
+            profile as u8,
+            layer as u8,
+            (key / COL) as u8,
+            (key % COL) as u8,
+        ));
+    }
+    index -= KEYMAP_PROFILE_NUM * NUM_LAYER * ROW * COL;
+    if index < NUM_LAYER * NUM_ENCODER {
+        return BackupIndex::Key(StorageKey::encoder(
+            (index % NUM_ENCODER) as u8,
+            (index / NUM_ENCODER) as u8,
+        ));
+    }
+    index -= NUM_LAYER * NUM_ENCODER;
+    if index < COMBO_MAX_NUM {
+        return BackupIndex::Key(StorageKey::combo(index as u8));
+    }
+    index -= COMBO_MAX_NUM;
+    if index < FORK_MAX_NUM {
+        return BackupIndex::Key(StorageKey::fork(index as u8));
+    }
+    index -= FORK_MAX_NUM;
+    if index < MORSE_MAX_NUM {
+        return BackupIndex::Key(StorageKey::morse(index as u8));
+    }
+    BackupIndex::UserData(index - MORSE_MAX_NUM)
+}
+
+/// Decode a backup record, `None` if it isn't a valid item of a backup for this keyboard.
+fn decode_backup_record<const ROW: usize, const COL: usize, const NUM_LAYER: usize, const NUM_ENCODER: usize>(
+    record: &[u8],
+) -> Option<(StorageKey, StorageData)> {
+    let (key, data): (StorageKey, StorageData) = postcard::from_bytes(record).ok()?;
+    let valid = match (&key, &data) {
+        (StorageKey::LayoutConfig, StorageData::LayoutConfig(_))
+        | (StorageKey::BehaviorConfig, StorageData::BehaviorConfig(_))
+        | (StorageKey::MacroData, StorageData::MacroData(_)) => true,
+        #[cfg(feature = "audio")]
+        (StorageKey::AudioSettings, StorageData::AudioSettings(_)) => true,
+        #[cfg(feature = "_ble")]
+        (StorageKey::BleName, StorageData::BleName(_)) => true,
+        (StorageKey::Keymap { .. } | StorageKey::ProfileKeymap { .. }, StorageData::KeyAction(_)) => {
+            key.keymap_position().is_some_and(|(profile, layer, row, col)| {
+                (profile as usize) < KEYMAP_PROFILE_NUM
+                    && (layer as usize) < NUM_LAYER
+                    && (row as usize) < ROW
+                    && (col as usize) < COL
+            })
+        }
+        (StorageKey::KeymapProfile, StorageData::KeymapProfile(profile)) => (*profile as usize) < KEYMAP_PROFILE_NUM,
+        (StorageKey::Encoder { layer, idx }, StorageData::EncoderAction(_)) => {
+            (*layer as usize) < NUM_LAYER && (*idx as usize) < NUM_ENCODER
+        }
+        (StorageKey::Combo(idx), StorageData::Combo(_)) => (*idx as usize) < COMBO_MAX_NUM,
+        (StorageKey::Fork(idx), StorageData::Fork(_)) => (*idx as usize) < FORK_MAX_NUM,
+        (StorageKey::Morse(idx), StorageData::Morse(_)) => (*idx as usize) < MORSE_MAX_NUM,
+        (StorageKey::User { .. }, StorageData::UserData(_)) => true,
+        _ => false,
+    };
+    valid.then_some((key, data))
+}
+
+impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usize, const NUM_ENCODER: usize>
+    Storage<F, ROW, COL, NUM_LAYER, NUM_ENCODER>
+{
+    pub(crate) async fn fetch_backup_item(&mut self, index: u16) -> BackupItem {
+        let key = match backup_index::<ROW, COL, NUM_LAYER, NUM_ENCODER>(index as usize) {
+            BackupIndex::Key(key) => key,
+            BackupIndex::UserData(n) => match self.user_data_key(n).await {
+                Some(key) => key,
+                None => return BackupItem::End,
+            },
+        };
+        let data = match self.flash.fetch_item::<StorageData>(&mut self.buffer, &key).await {
+            Ok(Some(data)) => data,
+            Ok(None) => return BackupItem::Empty,
+            Err(e) => {
+                print_storage_error::<F>(e);
+                return BackupItem::Empty;
+            }
+        };
+        match postcard::to_slice(&(key, data), &mut self.buffer) {
+            Ok(record) => BackupRecord::from_slice(record).map_or(BackupItem::Empty, BackupItem::Saved),
+            Err(e) => {
+                error!("Failed to serialize backup item {:?}: {:?}", key, e);
+                BackupItem::Empty
+            }
+        }
+    }
+
+    /// Key of the `n`th value of user code in the flash, `None` if there are fewer values.
+    ///
+    /// The flash lists the old values of a key too, so the keys found before are kept to skip them.
+    async fn user_data_key(&mut self, n: usize) -> Option<StorageKey> {
+        let mut keys: heapless::Vec<StorageKey, BACKUP_USER_DATA_MAX> = heapless::Vec::new();
+        let mut iterator = self
+            .flash
+            .fetch_all_items(&mut self.buffer)
+            .await
+            .map_err(|e| print_storage_error::<F>(e))
+            .ok()?;
+        loop {
+            let key = match iterator.next::<StorageData>(&mut self.buffer).await {
+                Ok(Some((key @ StorageKey::User { .. }, _))) => key,
+                Ok(Some(_)) | Err(SSError::SerializationError(_)) => continue,
+                Ok(None) => return None,
+                Err(e) => {
+                    print_storage_error::<F>(e);
+                    return None;
+                }
+            };
+            if keys.contains(&key) {
+                continue;
+            }
+            if keys.push(key).is_err() {
+                warn!(
+                    "More than {} user values, the others aren't backed up",
+                    BACKUP_USER_DATA_MAX
+                );
+                return None;
+            }
+            if keys.len() > n {
+                return Some(key);
+            }
+        }
+    }
+
+    /// Save a backup record, records of other keys or of a bigger keyboard are rejected.
+    pub(crate) async fn store_backup_item(&mut self, record: &[u8]) -> Result<(), SSError<F::Error>> {
+        let (key, data) = decode_backup_record::<ROW, COL, NUM_LAYER, NUM_ENCODER>(record)
+            .ok_or(SSError::SerializationError(SerializationError::InvalidData))?;
+        info!("Restoring backup item {:?}", key);
+        self.store_data(key, &data).await
+    }
+
+    /// Remove the saved item of a backup index, which was empty in the restored backup.
+    ///
+    /// Values of user code have no fixed index, so they can't be cleared.
+    pub(crate) async fn clear_backup_item(&mut self, index: u16) -> Result<(), SSError<F::Error>> {
+        let BackupIndex::Key(key) = backup_index::<ROW, COL, NUM_LAYER, NUM_ENCODER>(index as usize) else {
+            return Err(SSError::SerializationError(SerializationError::InvalidData));
+        };
+        info!("Clearing backup item {:?}", key);
+        self.flash.remove_item(&mut self.buffer, &key).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use rmk_types::action::Action;
+    use rmk_types::action::{Action, KeyAction};
     use rmk_types::keycode::{HidKeyCode, KeyCode};
     use rmk_types::morse::{HOLD, MorseMode, MorsePattern, MorseProfile, TAP};
     use sequential_storage::map::Value;
 
     use super::*;
 
+    #[test]
+    fn backup_keys_cover_the_keyboard_once() {
+        let settings = BACKUP_SETTINGS.len();
+        let keymaps = KEYMAP_PROFILE_NUM * 12;
+        assert_eq!(
+            backup_index::<2, 3, 2, 1>(0),
+            BackupIndex::Key(StorageKey::LayoutConfig)
+        );
+        assert_eq!(
+            backup_index::<2, 3, 2, 1>(settings + 6 + 4),
+            BackupIndex::Key(StorageKey::keymap(1, 1, 1))
+        );
+        assert_eq!(
+            backup_index::<2, 3, 2, 1>(settings + keymaps - 1),
+            BackupIndex::Key(StorageKey::profile_keymap(KEYMAP_PROFILE_NUM as u8 - 1, 1, 1, 2))
+        );
+        assert_eq!(
+            backup_index::<2, 3, 2, 1>(settings + keymaps + 1),
+            BackupIndex::Key(StorageKey::encoder(0, 1))
+        );
+        let len = settings + keymaps + 2 + COMBO_MAX_NUM + FORK_MAX_NUM + MORSE_MAX_NUM;
+        assert!(matches!(
+            backup_index::<2, 3, 2, 1>(len - 1),
+            BackupIndex::Key(StorageKey::Morse(_))
+        ));
+        assert_eq!(backup_index::<2, 3, 2, 1>(len), BackupIndex::UserData(0));
+        assert_eq!(backup_index::<2, 3, 2, 1>(len + 3), BackupIndex::UserData(3));
+    }
+
+    #[test]
+    fn backup_records_are_checked_against_the_keyboard() {
+        let mut buffer = [0u8; 64];
+        let key = StorageKey::keymap(1, 1, 2);
+        let record = postcard::to_slice(&(key, StorageData::KeyAction(KeyAction::No)), &mut buffer).unwrap();
+        assert!(matches!(
+            decode_backup_record::<2, 3, 2, 1>(record),
+            Some((
+                StorageKey::Keymap {
+                    layer: 1,
+                    row: 1,
+                    col: 2
+                },
+                StorageData::KeyAction(KeyAction::No)
+            ))
+        ));
+        // The keymap of a smaller keyboard has no such key
+        assert!(decode_backup_record::<2, 2, 2, 1>(record).is_none());
+
+        // BLE bonds and other items are never restored
+        let record = postcard::to_slice(
+            &(
+                StorageKey::ConnectionType,
+                StorageData::ConnectionType(rmk_types::connection::ConnectionType::Usb),
+            ),
+            &mut buffer,
+        )
+        .unwrap();
+        assert!(decode_backup_record::<2, 3, 2, 1>(record).is_none());
+        assert!(decode_backup_record::<2, 3, 2, 1>(&[0xFF; 4]).is_none());
+
+        // Values of user code and the active profile are restored, profiles which aren't configured aren't
+        let value = crate::storage::user::UserValue::from_slice(&[0x40, 0x06]).unwrap();
+        let key = StorageKey::User { namespace: 1, key: 2 };
+        let record = postcard::to_slice(&(key, StorageData::UserData(value)), &mut buffer).unwrap();
+        assert!(decode_backup_record::<2, 3, 2, 1>(record).is_some());
+        let profile = StorageData::KeymapProfile(KEYMAP_PROFILE_NUM as u8);
+        let record = postcard::to_slice(&(StorageKey::KeymapProfile, profile), &mut buffer).unwrap();
+        assert!(decode_backup_record::<2, 3, 2, 1>(record).is_none());
+    }
+
     #[test]
     fn test_morse_serialization_deserialization() {
         let morse = Morse::new_from_vial(
@@ -0,0 +1,98 @@
+//! Backup and restore of the stored configuration over the Via channel, see `BackupCommand`.
+
+use byteorder::{BigEndian, ByteOrder};
+use rmk_types::protocol::vial::{BACKUP_CHUNK_SIZE, BackupCommand, BackupStatus};
+
+use crate::hid::ViaReport;
+use crate::host::storage::{BackupItem, BackupRecord};
+use crate::storage::{clear_backup_item, read_backup_item, restore_backup_item};
+
+/// Records of a backup or restore in progress, which are moved in chunks
+#[derive(Default)]
+pub(crate) struct BackupTransfer {
+    /// Item read last, with its index
+    read: Option<(u16, BackupItem)>,
+    /// Record the host is writing
+    write: BackupRecord,
+}
+
+impl BackupTransfer {
+    pub(crate) async fn process(&mut self, report: &mut ViaReport, unlocked: bool) {
+        // report.output_data[0] == 0xFD -> backup commands
+        let command = report.output_data[1].into();
+        debug!("Received backup command: {:?}", command);
+        match command {
+            BackupCommand::ReadItem => {
+                let index = BigEndian::read_u16(&report.output_data[2..4]);
+                let offset = BigEndian::read_u16(&report.output_data[4..6]) as usize;
+                // Later chunks are read from the item fetched for the first one
+                if offset == 0 || self.read.as_ref().is_none_or(|(i, _)| *i != index) {
+                    self.read = Some((index, read_backup_item(index).await));
+                }
+                let status = match &self.read {
+                    Some((_, BackupItem::Saved(record))) => {
+                        let chunk = record.get(offset..).unwrap_or_default();
+                        let len = chunk.len().min(BACKUP_CHUNK_SIZE);
+                        BigEndian::write_u16(&mut report.input_data[3..5], record.len() as u16);
+                        report.input_data[5] = len as u8;
+                        report.input_data[6..6 + len].copy_from_slice(&chunk[..len]);
+                        BackupStatus::Ok
+                    }
+                    Some((_, BackupItem::Empty)) => BackupStatus::Empty,
+                    _ => BackupStatus::End,
+                };
+                report.input_data[2] = status as u8;
+            }
+            BackupCommand::WriteItem => {
+                let offset = BigEndian::read_u16(&report.output_data[2..4]) as usize;
+                let len = report.output_data[4] as usize;
+                if offset == 0 {
+                    self.write.clear();
+                }
+                // Chunks have to come in order
+                let status = if len <= BACKUP_CHUNK_SIZE
+                    && offset == self.write.len()
+                    && self.write.extend_from_slice(&report.output_data[5..5 + len]).is_ok()
+                {
+                    BackupStatus::Ok
+                } else {
+                    BackupStatus::Invalid
+                };
+                report.input_data[2] = status as u8;
+            }
+            BackupCommand::CommitItem => {
+                let record = core::mem::take(&mut self.write);
+                // A restore rewrites the keymap and the macros, so it needs an unlocked keyboard
+                let status = if unlocked && restore_backup_item(record).await {
+                    BackupStatus::Ok
+                } else {
+                    BackupStatus::Invalid
+                };
+                report.input_data[2] = status as u8;
+            }
+            BackupCommand::ClearItem => {
+                let index = BigEndian::read_u16(&report.output_data[2..4]);
+                let status = if unlocked && clear_backup_item(index).await {
+                    BackupStatus::Ok
+                } else {
+                    BackupStatus::Invalid
+                };
+                report.input_data[2] = status as u8;
+            }
+            BackupCommand::Reboot => {
+                // The restored configuration is loaded at boot. A locked keyboard doesn't reboot,
+                // so that the host can't reset it over and over
+                if unlocked {
+                    crate::boot::flush_storage().await;
+                    crate::boot::reboot_keyboard();
+                } else {
+                    report.input_data[2] = BackupStatus::Invalid as u8;
+                }
+            }
+            BackupCommand::Unhandled => {
+                info!("Unknown backup cmd: {:?}", report.output_data);
+                report.input_data[2] = BackupStatus::Invalid as u8;
+            }
+        }
+    }
+}
@@ -7,44 +7,11 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
         KeyAction::No => 0x0000,
         KeyAction::Transparent => 0x0001,
         KeyAction::Single(a) => match a {
-            Action::Key(k) => match k {
-                KeyCode::Hid(hid_keycode) => hid_keycode as u16,
-                // Consumer and SystemControl keys are automatically converted to HID keycodes
-                // which are natively supported in VIA protocol
-                KeyCode::Consumer(consumer_key) => {
-                    if let Some(hid_keycode) = consumer_key.to_hid_keycode() {
-                        hid_keycode as u16
-                    } else {
-                        warn!(
-                            "Consumer key {:?} has no corresponding HID keycode for VIA",
-                            consumer_key
-                        );
-                        0
-                    }
-                }
-                KeyCode::SystemControl(system_key) => {
-                    if let Some(hid_keycode) = system_key.to_hid_keycode() {
-                        hid_keycode as u16
-                    } else {
-                        warn!(
-                            "SystemControl key {:?} has no corresponding HID keycode for VIA",
-                            system_key
-                        );
-                        0
-                    }
-                }
-                _ => {
-                    warn!("KeyCode variant {:?} not supported by via", k);
-                    0
-                }
+            Action::Key(k) => to_via_basic_keycode(k),
+            Action::KeyWithModifier(k, m) => match to_via_basic_keycode(k) {
+                0 => 0,
+                keycode => ((m.into_packed_bits() as u16) << 8) | keycode,
             },
-            Action::KeyWithModifier(k, m) => {
-                if let KeyCode::Hid(hid_keycode) = k {
-                    ((m.into_packed_bits() as u16) << 8) | hid_keycode as u16
-                } else {
-                    0
-                }
-            }
             Action::LayerToggleOnly(l) => 0x5200 | l as u16,
             Action::LayerOn(l) => 0x5220 | l as u16,
             Action::DefaultLayer(l) => 0x5240 | l as u16,
@@ -82,6 +49,17 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
                 KeyboardAction::ComboOff => 0x7c51,
                 KeyboardAction::ComboToggle => 0x7c52,
                 KeyboardAction::CapsWordToggle => 0x7c73,
+                KeyboardAction::AudioOn => 0x7480,
+                KeyboardAction::AudioOff => 0x7481,
+                KeyboardAction::AudioToggle => 0x7482,
+                KeyboardAction::ClickyToggle => 0x748a,
+                KeyboardAction::HapticOn => 0x7c40,
+                KeyboardAction::HapticOff => 0x7c41,
+                KeyboardAction::HapticToggle => 0x7c42,
+                KeyboardAction::AutoBrightnessToggle => 0x7c7d,
+                KeyboardAction::OutputAuto => 0x7c20,
+                KeyboardAction::OutputUsb => 0x7c21,
+                KeyboardAction::OutputBluetooth => 0x7c22,
                 _ => {
                     warn!("KeyboardAction: {:?} vial is not supported yet", c);
                     0
@@ -90,6 +68,7 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
             Action::Special(special_key) => match special_key {
                 SpecialKey::GraveEscape => 0x7c16,
                 SpecialKey::Repeat => 0x7c79,
+                SpecialKey::AppleFn => 0x7c7c,
                 _ => {
                     warn!("SpecialKey variant {:?} not supported by via", special_key);
                     0
@@ -111,7 +90,7 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
                     0
                 } else {
                     let keycode = match tap {
-                        Action::Key(KeyCode::Hid(k)) => k as u16,
+                        Action::Key(k) => to_via_basic_keycode(k),
                         _ => 0,
                     };
                     0x4000 | ((l as u16) << 8) | keycode
@@ -119,7 +98,7 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
             }
             Action::Modifier(m) => {
                 let keycode = match tap {
-                    Action::Key(KeyCode::Hid(k)) => k as u16,
+                    Action::Key(k) => to_via_basic_keycode(k),
                     _ => 0,
                 };
                 0x2000 | ((m.into_packed_bits() as u16) << 8) | keycode
@@ -137,6 +116,44 @@ pub(crate) fn to_via_keycode(key_action: KeyAction) -> u16 {
     }
 }
 
+/// Convert a key to the basic (8-bit) via keycode, which is also used as the tap key of
+/// tap-hold and as the key of key-with-modifier keycodes.
+///
+/// Consumer and SystemControl keys are converted to the corresponding HID keycodes,
+/// which are natively supported in VIA protocol and processed as consumer/system
+/// control keys again when pressed.
+fn to_via_basic_keycode(key: KeyCode) -> u16 {
+    match key {
+        KeyCode::Hid(hid_keycode) => hid_keycode as u16,
+        KeyCode::Consumer(consumer_key) => {
+            if let Some(hid_keycode) = consumer_key.to_hid_keycode() {
+                hid_keycode as u16
+            } else {
+                warn!(
+                    "Consumer key {:?} has no corresponding HID keycode for VIA",
+                    consumer_key
+                );
+                0
+            }
+        }
+        KeyCode::SystemControl(system_key) => {
+            if let Some(hid_keycode) = system_key.to_hid_keycode() {
+                hid_keycode as u16
+            } else {
+                warn!(
+                    "SystemControl key {:?} has no corresponding HID keycode for VIA",
+                    system_key
+                );
+                0
+            }
+        }
+        _ => {
+            warn!("KeyCode variant {:?} not supported by via", key);
+            0
+        }
+    }
+}
+
 /// Convert via keycode to KeyAction.
 pub(crate) fn from_via_keycode(via_keycode: u16) -> KeyAction {
     match via_keycode {
@@ -216,6 +233,10 @@ pub(crate) fn from_via_keycode(via_keycode: u16) -> KeyAction {
             warn!("QMK functions {:#X} not supported", via_keycode);
             KeyAction::No
         }
+        0x7480 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::AudioOn)),
+        0x7481 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::AudioOff)),
+        0x7482 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::AudioToggle)),
+        0x748A => KeyAction::Single(Action::KeyboardControl(KeyboardAction::ClickyToggle)),
         0x7700..=0x771F => {
             // Macro
             let id = via_keycode as u8 & 0x1F;
@@ -232,10 +253,20 @@ pub(crate) fn from_via_keycode(via_keycode: u16) -> KeyAction {
         0x7C51 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::ComboOff)),
         0x7C52 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::ComboToggle)),
         0x7C16 => KeyAction::Single(Action::Special(SpecialKey::GraveEscape)),
+        0x7C20 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::OutputAuto)),
+        0x7C21 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::OutputUsb)),
+        0x7C22 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::OutputBluetooth)),
+        0x7C40 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::HapticOn)),
+        0x7C41 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::HapticOff)),
+        0x7C42 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::HapticToggle)),
         0x7C73 => KeyAction::Single(Action::KeyboardControl(KeyboardAction::CapsWordToggle)),
         0x7C77 => KeyAction::Single(Action::TriLayerLower),
         0x7C78 => KeyAction::Single(Action::TriLayerUpper),
         0x7C79 => KeyAction::Single(Action::Special(SpecialKey::Repeat)),
+        // Not used by QMK, add it as a custom keycode to the Vial definition
+        0x7C7C => KeyAction::Single(Action::Special(SpecialKey::AppleFn)),
+        // Not used by QMK either
+        0x7C7D => KeyAction::Single(Action::KeyboardControl(KeyboardAction::AutoBrightnessToggle)),
         0x7C02..=0x7C5F => {
             // TODO: Reset/Space Cadet/Haptic/Auto shift(AS)/Dynamic macro
             // - [Space Cadet](https://docs.qmk.fm/#/feature_space_cadet)
@@ -463,6 +494,13 @@ mod test {
             from_via_keycode(via_keycode)
         );
 
+        // OutputBluetooth
+        let via_keycode = 0x7C22;
+        assert_eq!(
+            KeyAction::Single(Action::KeyboardControl(KeyboardAction::OutputBluetooth)),
+            from_via_keycode(via_keycode)
+        );
+
         // RepeatKey
         let via_keycode = 0x7C79;
         assert_eq!(
@@ -470,6 +508,20 @@ mod test {
             from_via_keycode(via_keycode)
         );
 
+        // AppleFn
+        let via_keycode = 0x7C7C;
+        assert_eq!(
+            KeyAction::Single(Action::Special(SpecialKey::AppleFn)),
+            from_via_keycode(via_keycode)
+        );
+
+        // AutoBrightnessToggle
+        let via_keycode = 0x7C7D;
+        assert_eq!(
+            KeyAction::Single(Action::KeyboardControl(KeyboardAction::AutoBrightnessToggle)),
+            from_via_keycode(via_keycode)
+        );
+
         // Morse(0)
         let via_keycode = 0x5700;
         assert_eq!(KeyAction::Morse(0), from_via_keycode(via_keycode));
@@ -620,6 +672,14 @@ mod test {
         let a = KeyAction::Single(Action::Special(SpecialKey::Repeat));
         assert_eq!(0x7C79, to_via_keycode(a));
 
+        // AppleFn
+        let a = KeyAction::Single(Action::Special(SpecialKey::AppleFn));
+        assert_eq!(0x7C7C, to_via_keycode(a));
+
+        // AutoBrightnessToggle
+        let a = KeyAction::Single(Action::KeyboardControl(KeyboardAction::AutoBrightnessToggle));
+        assert_eq!(0x7C7D, to_via_keycode(a));
+
         // Morse
         let a = KeyAction::Morse(0);
         assert_eq!(0x5700, to_via_keycode(a));
@@ -660,6 +720,27 @@ mod test {
         // WakeUp (SystemControlKey::WakeUp) -> HidKeyCode::SystemWake (0xA7)
         let a = KeyAction::Single(Action::Key(KeyCode::SystemControl(SystemControlKey::WakeUp)));
         assert_eq!(0xA7, to_via_keycode(a));
+
+        // Consumer keys as tap key of tap-hold
+        // LT2(VolumeIncrement) -> 0x42A9
+        let a = KeyAction::TapHold(
+            Action::Key(KeyCode::Consumer(ConsumerKey::VolumeIncrement)),
+            Action::LayerOn(2),
+            Default::default(),
+        );
+        assert_eq!(0x42A9, to_via_keycode(a));
+
+        // LSFT_T(Mute) -> 0x22A8
+        let a = KeyAction::TapHold(
+            Action::Key(KeyCode::Consumer(ConsumerKey::Mute)),
+            Action::Modifier(ModifierCombination::LSHIFT),
+            Default::default(),
+        );
+        assert_eq!(0x22A8, to_via_keycode(a));
+
+        // Consumer keys without a HID keycode can't be shown in VIA
+        let a = KeyAction::Single(Action::Key(KeyCode::Consumer(ConsumerKey::Undo)));
+        assert_eq!(0x0000, to_via_keycode(a));
     }
 
     #[test]
@@ -1,6 +1,9 @@
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use embassy_time::Instant;
-use rmk_types::protocol::vial::{VIA_FIRMWARE_VERSION, VIA_PROTOCOL_VERSION, ViaCommand, ViaKeyboardInfo};
+use rmk_types::logging::{LogLevel, LogModule};
+use rmk_types::protocol::vial::{
+    LOG_LEVEL_ALL, VIA_FIRMWARE_VERSION, VIA_PROTOCOL_VERSION, ViaCommand, ViaError, ViaKeyboardInfo,
+};
 use vial::process_vial;
 
 use crate::channel::{HOST_REQUEST_CHANNEL, try_send_host_reply};
@@ -9,8 +12,11 @@ use crate::core_traits::Runnable;
 use crate::hid::ViaReport;
 use crate::host::context::KeyboardContext;
 use crate::host::via::keycode_convert::{from_via_keycode, to_via_keycode};
+use crate::logging::{log_level, set_all_log_levels, set_log_level};
 use crate::{MACRO_SPACE_SIZE, boot};
 
+#[cfg(feature = "storage")]
+mod backup;
 pub(crate) mod keycode_convert;
 mod vial;
 #[cfg(feature = "vial_lock")]
@@ -21,6 +27,8 @@ pub struct VialService<'a> {
     vial_config: VialConfig<'static>,
     #[cfg(feature = "vial_lock")]
     locker: vial_lock::VialLock<'a>,
+    #[cfg(feature = "storage")]
+    backup: backup::BackupTransfer,
 }
 
 impl<'a> VialService<'a> {
@@ -34,78 +42,84 @@ impl<'a> VialService<'a> {
                 ctx.keymap,
                 config.vial_config.vial_insecure,
             ),
+            #[cfg(feature = "storage")]
+            backup: backup::BackupTransfer::default(),
         }
     }
 
     async fn process_via_packet(&mut self, report: &mut ViaReport) {
-        let command_id = report.output_data[0];
+        // The request borrows a copy, so the handler can write the reply into the report
+        let output_data = report.output_data;
+        let result = match parse_via_request(&output_data) {
+            Ok(request) => self.handle_via_request(request, report).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("Rejected via request {:?}: {:?}", ViaCommand::from(output_data[0]), e);
+            // See `ViaError` for the layout of the error reply
+            report.input_data.fill(0);
+            report.input_data[0] = ViaCommand::Unhandled as u8;
+            report.input_data[1] = e as u8;
+        }
+    }
 
+    async fn handle_via_request(&mut self, request: ViaRequest<'_>, report: &mut ViaReport) -> Result<(), ViaError> {
         // Caller pre-fills `input_data` from `output_data`, so individual arms
         // only need to overwrite the bytes they actually change.
-        match command_id.into() {
-            ViaCommand::GetProtocolVersion => {
+        match request {
+            ViaRequest::GetProtocolVersion => {
                 BigEndian::write_u16(&mut report.input_data[1..3], VIA_PROTOCOL_VERSION);
             }
-            ViaCommand::GetKeyboardValue => {
-                // Check the second u8
-                match report.output_data[1].try_into() {
-                    Ok(v) => match v {
-                        ViaKeyboardInfo::Uptime => {
-                            let value = Instant::now().as_millis() as u32;
-                            BigEndian::write_u32(&mut report.input_data[2..6], value);
-                        }
-                        ViaKeyboardInfo::LayoutOptions => {
-                            // TODO: retrieve layout option from storage
-                            let layout_option: u32 = 0;
-                            BigEndian::write_u32(&mut report.input_data[2..6], layout_option);
-                        }
-                        #[cfg(not(feature = "vial_lock"))]
-                        ViaKeyboardInfo::SwitchMatrixState => {
-                            error!("It is not secure to use matrix tester without vial lock");
-                        }
-                        #[cfg(feature = "vial_lock")]
-                        ViaKeyboardInfo::SwitchMatrixState if self.locker.is_unlocked() => {
-                            self.ctx.read_matrix_state(&mut report.input_data[2..]);
-                        }
-                        ViaKeyboardInfo::FirmwareVersion => {
-                            BigEndian::write_u32(&mut report.input_data[2..6], VIA_FIRMWARE_VERSION);
-                        }
-                        _ => (),
-                    },
-                    Err(e) => error!("Invalid subcommand: {} of GetKeyboardValue", e),
+            ViaRequest::GetKeyboardValue(info) => match info {
+                ViaKeyboardInfo::Uptime => {
+                    let value = Instant::now().as_millis() as u32;
+                    BigEndian::write_u32(&mut report.input_data[2..6], value);
                 }
-            }
-            ViaCommand::SetKeyboardValue => {
-                // Check the second u8
-                match report.output_data[1].try_into() {
-                    Ok(v) => match v {
-                        ViaKeyboardInfo::LayoutOptions => {
-                            let layout_option = BigEndian::read_u32(&report.output_data[2..6]);
-                            self.ctx.set_layout_options(layout_option).await;
-                        }
-                        ViaKeyboardInfo::DeviceIndication => {
-                            let _device_indication = report.output_data[2];
-                            warn!("SetKeyboardValue - DeviceIndication")
-                        }
-                        _ => (),
-                    },
-                    Err(e) => error!("Invalid subcommand: {} of GetKeyboardValue", e),
+                ViaKeyboardInfo::LayoutOptions => {
+                    // TODO: retrieve layout option from storage
+                    let layout_option: u32 = 0;
+                    BigEndian::write_u32(&mut report.input_data[2..6], layout_option);
                 }
-            }
-            ViaCommand::DynamicKeymapGetKeyCode => {
-                let layer = report.output_data[1];
-                let row = report.output_data[2];
-                let col = report.output_data[3];
+                #[cfg(not(feature = "vial_lock"))]
+                ViaKeyboardInfo::SwitchMatrixState => {
+                    error!("It is not secure to use matrix tester without vial lock");
+                    return Err(ViaError::Locked);
+                }
+                #[cfg(feature = "vial_lock")]
+                ViaKeyboardInfo::SwitchMatrixState => {
+                    if !self.locker.is_unlocked() {
+                        return Err(ViaError::Locked);
+                    }
+                    self.ctx.read_matrix_state(&mut report.input_data[2..]);
+                }
+                ViaKeyboardInfo::FirmwareVersion => {
+                    BigEndian::write_u32(&mut report.input_data[2..6], VIA_FIRMWARE_VERSION);
+                }
+                ViaKeyboardInfo::DeviceIndication => (),
+            },
+            ViaRequest::SetKeyboardValue(info, value) => match info {
+                ViaKeyboardInfo::LayoutOptions => {
+                    self.ctx.set_layout_options(value).await;
+                }
+                ViaKeyboardInfo::DeviceIndication => {
+                    warn!("SetKeyboardValue - DeviceIndication")
+                }
+                _ => (),
+            },
+            ViaRequest::GetKeyCode { layer, row, col } => {
+                self.check_key_position(layer, row, col)?;
                 let action = self.ctx.get_action(layer, row, col);
                 let keycode = to_via_keycode(action);
                 info!("Getting keycode: {:02X} at ({},{}), layer {}", keycode, row, col, layer);
                 BigEndian::write_u16(&mut report.input_data[4..6], keycode);
             }
-            ViaCommand::DynamicKeymapSetKeyCode => {
-                let layer = report.output_data[1];
-                let row = report.output_data[2];
-                let col = report.output_data[3];
-                let keycode = BigEndian::read_u16(&report.output_data[4..6]);
+            ViaRequest::SetKeyCode {
+                layer,
+                row,
+                col,
+                keycode,
+            } => {
+                self.check_key_position(layer, row, col)?;
                 let action = from_via_keycode(keycode);
                 info!(
                     "Setting keycode: 0x{:02X} at ({},{}), layer {} as {:?}",
@@ -113,123 +127,369 @@ impl<'a> VialService<'a> {
                 );
                 self.ctx.set_action(layer, row, col, action).await;
             }
-            ViaCommand::DynamicKeymapReset => {
-                warn!("Dynamic keymap reset -- not supported")
+            ViaRequest::Unsupported(command) => {
+                // Dynamic keymap reset, via encoders and the backlight/rgblight/rgb matrix/led matrix/audio settings
+                warn!("{:?} -- not supported", command)
             }
-            ViaCommand::CustomSetValue => {
-                // backlight/rgblight/rgb matrix/led matrix/audio settings here
-                warn!("Custom set value -- not supported")
-            }
-            ViaCommand::CustomGetValue => {
-                // backlight/rgblight/rgb matrix/led matrix/audio settings here
-                warn!("Custom get value -- not supported")
-            }
-            ViaCommand::CustomSave => {
-                // backlight/rgblight/rgb matrix/led matrix/audio settings here
-                warn!("Custom get value -- not supported")
-            }
-            ViaCommand::EepromReset => {
+            ViaRequest::EepromReset => {
                 warn!("Resetting storage..");
                 self.ctx.reset_storage().await;
                 // TODO: Reboot after a eeprom reset?
             }
-            ViaCommand::BootloaderJump => {
+            ViaRequest::BootloaderJump => {
                 warn!("Bootloader jumping");
+                boot::flush_storage().await;
                 boot::jump_to_bootloader();
             }
-            ViaCommand::DynamicKeymapMacroGetCount => {
-                report.input_data[1] = 32;
+            ViaRequest::MacroGetCount => {
+                // Without the `macros` feature, Via hides the macro editor
+                report.input_data[1] = if cfg!(feature = "macros") { 32 } else { 0 };
                 warn!("Macro get count -- to be implemented")
             }
-            ViaCommand::DynamicKeymapMacroGetBufferSize => {
+            ViaRequest::MacroGetBufferSize => {
                 report.input_data[1] = (MACRO_SPACE_SIZE as u16 >> 8) as u8;
                 report.input_data[2] = (MACRO_SPACE_SIZE & 0xFF) as u8;
             }
-            ViaCommand::DynamicKeymapMacroGetBuffer => {
-                let offset = BigEndian::read_u16(&report.output_data[1..3]) as usize;
-                let size = report.output_data[3] as usize;
-                if size <= 28 {
-                    self.ctx.read_macro_buffer(offset, &mut report.input_data[4..4 + size]);
-                    debug!("Get macro buffer: offset: {}, data: {:?}", offset, report.input_data);
-                } else {
-                    report.input_data[0] = 0xFF;
-                }
+            ViaRequest::MacroGetBuffer { offset, size } => {
+                self.ctx
+                    .read_macro_buffer(offset as usize, &mut report.input_data[4..4 + size]);
+                debug!("Get macro buffer: offset: {}, data: {:?}", offset, report.input_data);
             }
-            ViaCommand::DynamicKeymapMacroSetBuffer => {
+            ViaRequest::MacroSetBuffer { offset, data } => {
                 // Every write writes all buffer space of the macro(if it's not empty)
-                let offset = BigEndian::read_u16(&report.output_data[1..3]);
-                // Current sequence size, <= 28
-                let size = report.output_data[3];
-                // End of current sequence in the macro cache
                 // The first sequence, reset the macro cache
                 if offset == 0 {
                     self.ctx.reset_macro_buffer();
                 }
 
                 // Update macro cache + flush full buffer to storage
-                info!("Setting macro buffer, offset: {}, size: {}", offset, size);
-                self.ctx
-                    .write_macro_buffer(offset as usize, &report.output_data[4..4 + size as usize])
-                    .await;
+                info!("Setting macro buffer, offset: {}, size: {}", offset, data.len());
+                self.ctx.write_macro_buffer(offset as usize, data).await;
             }
-            ViaCommand::DynamicKeymapMacroReset => {
+            ViaRequest::MacroReset => {
                 warn!("Macro reset -- to be implemented")
             }
-            ViaCommand::DynamicKeymapGetLayerCount => {
+            ViaRequest::GetLayerCount => {
                 report.input_data[1] = self.ctx.keymap_dimensions().2 as u8;
             }
-            ViaCommand::DynamicKeymapGetBuffer => {
-                let offset = BigEndian::read_u16(&report.output_data[1..3]);
-                // size <= 28
-                let size = report.output_data[3];
+            ViaRequest::GetBuffer { offset, size } => {
                 debug!("Getting keymap buffer, offset: {}, size: {}", offset, size);
-                let mut idx = 4;
+                self.check_buffer_range(offset, size)?;
                 let start = (offset / 2) as usize;
-                let count = (size / 2) as usize;
-                for i in 0..count {
+                for (i, chunk) in report.input_data[4..4 + size].chunks_exact_mut(2).enumerate() {
                     let a = self.ctx.get_action_flat(start + i);
-                    let kc = to_via_keycode(a);
-                    BigEndian::write_u16(&mut report.input_data[idx..idx + 2], kc);
-                    idx += 2;
+                    BigEndian::write_u16(chunk, to_via_keycode(a));
                 }
             }
-            ViaCommand::DynamicKeymapSetBuffer => {
+            ViaRequest::SetBuffer { offset, data } => {
                 debug!("Dynamic keymap set buffer");
-                let offset = BigEndian::read_u16(&report.output_data[1..3]);
-                // size <= 28
-                let size = report.output_data[3];
-                let mut idx = 4;
+                self.check_buffer_range(offset, data.len())?;
                 let (rows, cols, _) = self.ctx.keymap_dimensions();
-                for i in 0..(size as usize) {
-                    let via_keycode = LittleEndian::read_u16(&report.output_data[idx..idx + 2]);
-                    let action = from_via_keycode(via_keycode);
-                    let flat_index = offset as usize + i;
-                    self.ctx.try_set_action_flat(flat_index, action, rows, cols);
-                    idx += 2;
+                let start = (offset / 2) as usize;
+                for (i, chunk) in data.chunks_exact(2).enumerate() {
+                    let action = from_via_keycode(BigEndian::read_u16(chunk));
+                    self.ctx.try_set_action_flat(start + i, action, rows, cols);
+                }
+            }
+            ViaRequest::LogLevel { module, level } => {
+                // Reading the levels is always allowed, setting them needs the keyboard unlocked
+                #[cfg(feature = "vial_lock")]
+                if level.is_some() && !self.locker.is_unlocked() {
+                    return Err(ViaError::Locked);
+                }
+                match (module, level) {
+                    (None, Some(level)) => set_all_log_levels(level),
+                    (Some(module), Some(level)) => set_log_level(module, level),
+                    (_, None) => {}
+                }
+                for (module, byte) in LogModule::all().zip(report.input_data[3..].iter_mut()) {
+                    *byte = log_level(module) as u8;
                 }
             }
-            ViaCommand::DynamicKeymapGetEncoder => {
-                warn!("Keymap get encoder -- not supported");
+            #[cfg(feature = "introspection")]
+            ViaRequest::RuntimeState => {
+                use rmk_types::protocol::vial::RUNTIME_STATE_NONE;
+                let state = crate::introspection::runtime_state();
+                let data = &mut report.input_data;
+                data[2..].fill(0);
+                BigEndian::write_u32(&mut data[2..6], state.layers);
+                data[6] = state.default_layer;
+                data[7] = state.top_layer;
+                data[8] = state.pending_tap_hold;
+                data[9] = state.holding;
+                data[10] = state.pending_combo;
+                data[11] = state.oneshot_layer.unwrap_or(RUNTIME_STATE_NONE);
+                data[12] = state.oneshot_modifiers;
+                data[13] = state.held_modifiers;
+                data[14] = state.caps_word as u8 | ((state.combos_enabled as u8) << 1);
+                data[15] = state.combos_in_progress;
+                data[16] = state.combos_triggered;
+                data[17] = state.connection.usb as u8;
+                data[18] = state.connection.ble.profile;
+                data[19] = state.connection.ble.state as u8;
+                data[20] = state.connection.decide_active().map_or(RUNTIME_STATE_NONE, |t| t as u8);
+            }
+            #[cfg(feature = "crash_dump")]
+            ViaRequest::CrashRecord { offset } => {
+                report.input_data[2..].fill(0);
+                if let Some(record) = crate::watchdog::crash::last_crash() {
+                    let message = record.message.as_bytes();
+                    let chunk = message.get(offset as usize..).unwrap_or_default();
+                    let chunk = &chunk[..chunk.len().min(rmk_types::protocol::vial::CRASH_CHUNK_SIZE)];
+                    report.input_data[2] = 1;
+                    report.input_data[3] = record.reason as u8;
+                    report.input_data[4] = record.task.map_or(0xFF, |t| t as u8);
+                    BigEndian::write_u32(&mut report.input_data[5..9], record.uptime_ms);
+                    report.input_data[9] = message.len() as u8;
+                    report.input_data[10..10 + chunk.len()].copy_from_slice(chunk);
+                }
             }
-            ViaCommand::DynamicKeymapSetEncoder => {
-                warn!("Keymap set encoder -- not supported");
+            #[cfg(feature = "storage")]
+            ViaRequest::Backup => {
+                #[cfg(feature = "vial_lock")]
+                let unlocked = self.locker.is_unlocked();
+                #[cfg(not(feature = "vial_lock"))]
+                let unlocked = true;
+                self.backup.process(report, unlocked).await
             }
-            ViaCommand::Vial => {
-                process_vial(
+            ViaRequest::Vial => {
+                return process_vial(
                     report,
                     &self.vial_config,
                     #[cfg(feature = "vial_lock")]
                     &mut self.locker,
                     self.ctx,
                 )
-                .await
-            }
-            ViaCommand::Unhandled => {
-                info!("Unknown cmd: {:?}", report.output_data);
-                report.input_data[0] = ViaCommand::Unhandled as u8
+                .await;
             }
         }
+        Ok(())
+    }
+
+    /// Check a key position from the host against the keymap, which would otherwise index out of bounds
+    fn check_key_position(&self, layer: u8, row: u8, col: u8) -> Result<(), ViaError> {
+        let (rows, cols, layers) = self.ctx.keymap_dimensions();
+        if (layer as usize) < layers && (row as usize) < rows && (col as usize) < cols {
+            Ok(())
+        } else {
+            Err(ViaError::OutOfRange)
+        }
     }
+
+    /// Check a keymap buffer range from the host, 2 bytes per key, against the flat keymap
+    fn check_buffer_range(&self, offset: u16, size: usize) -> Result<(), ViaError> {
+        let (rows, cols, layers) = self.ctx.keymap_dimensions();
+        if offset as usize / 2 + size / 2 <= rows * cols * layers {
+            Ok(())
+        } else {
+            Err(ViaError::OutOfRange)
+        }
+    }
+}
+
+/// Largest keymap or macro chunk which fits after the 4 header bytes of a report
+const VIA_MAX_CHUNK_SIZE: usize = 28;
+
+/// A Via request decoded from a report, with every field checked against the report size.
+///
+/// Fields which depend on the keyboard, like key positions, are checked by the handler.
+#[derive(Debug, PartialEq)]
+enum ViaRequest<'d> {
+    GetProtocolVersion,
+    GetKeyboardValue(ViaKeyboardInfo),
+    SetKeyboardValue(ViaKeyboardInfo, u32),
+    GetKeyCode {
+        layer: u8,
+        row: u8,
+        col: u8,
+    },
+    SetKeyCode {
+        layer: u8,
+        row: u8,
+        col: u8,
+        keycode: u16,
+    },
+    /// Known commands which RMK doesn't implement
+    Unsupported(ViaCommand),
+    EepromReset,
+    BootloaderJump,
+    MacroGetCount,
+    MacroGetBufferSize,
+    /// `size` is at most [`VIA_MAX_CHUNK_SIZE`]
+    MacroGetBuffer {
+        offset: u16,
+        size: usize,
+    },
+    MacroSetBuffer {
+        offset: u16,
+        data: &'d [u8],
+    },
+    MacroReset,
+    GetLayerCount,
+    /// `offset` and `size` are in bytes, `size` is even and at most [`VIA_MAX_CHUNK_SIZE`]
+    GetBuffer {
+        offset: u16,
+        size: usize,
+    },
+    /// `offset` is in bytes, `data` holds big endian keycodes
+    SetBuffer {
+        offset: u16,
+        data: &'d [u8],
+    },
+    /// `None` module sets every module, `None` level only reads the levels
+    LogLevel {
+        module: Option<LogModule>,
+        level: Option<LogLevel>,
+    },
+    /// See [`rmk_types::protocol::vial::RUNTIME_STATE_NONE`]
+    #[cfg(feature = "introspection")]
+    RuntimeState,
+    /// `offset` in the crash message, see [`rmk_types::protocol::vial::CRASH_CHUNK_SIZE`]
+    #[cfg(feature = "crash_dump")]
+    CrashRecord {
+        offset: u8,
+    },
+    /// Decoded by [`backup::BackupTransfer`]
+    #[cfg(feature = "storage")]
+    Backup,
+    /// Decoded by [`process_vial`]
+    Vial,
+}
+
+fn parse_via_request(data: &[u8]) -> Result<ViaRequest<'_>, ViaError> {
+    let command = ViaCommand::from(read_u8(data, 0)?);
+    let request = match command {
+        ViaCommand::GetProtocolVersion => ViaRequest::GetProtocolVersion,
+        ViaCommand::GetKeyboardValue => ViaRequest::GetKeyboardValue(read_keyboard_info(data)?),
+        ViaCommand::SetKeyboardValue => {
+            let info = read_keyboard_info(data)?;
+            let value = match info {
+                ViaKeyboardInfo::LayoutOptions => BigEndian::read_u32(read_bytes(data, 2, 4)?),
+                _ => read_u8(data, 2)? as u32,
+            };
+            ViaRequest::SetKeyboardValue(info, value)
+        }
+        ViaCommand::DynamicKeymapGetKeyCode => ViaRequest::GetKeyCode {
+            layer: read_u8(data, 1)?,
+            row: read_u8(data, 2)?,
+            col: read_u8(data, 3)?,
+        },
+        ViaCommand::DynamicKeymapSetKeyCode => ViaRequest::SetKeyCode {
+            layer: read_u8(data, 1)?,
+            row: read_u8(data, 2)?,
+            col: read_u8(data, 3)?,
+            keycode: read_u16_be(data, 4)?,
+        },
+        ViaCommand::DynamicKeymapReset
+        | ViaCommand::CustomSetValue
+        | ViaCommand::CustomGetValue
+        | ViaCommand::CustomSave
+        | ViaCommand::DynamicKeymapGetEncoder
+        | ViaCommand::DynamicKeymapSetEncoder => ViaRequest::Unsupported(command),
+        ViaCommand::EepromReset => ViaRequest::EepromReset,
+        ViaCommand::BootloaderJump => ViaRequest::BootloaderJump,
+        ViaCommand::DynamicKeymapMacroGetCount => ViaRequest::MacroGetCount,
+        ViaCommand::DynamicKeymapMacroGetBufferSize => ViaRequest::MacroGetBufferSize,
+        ViaCommand::DynamicKeymapMacroGetBuffer => ViaRequest::MacroGetBuffer {
+            offset: read_u16_be(data, 1)?,
+            size: read_chunk_size(data)?,
+        },
+        ViaCommand::DynamicKeymapMacroSetBuffer => ViaRequest::MacroSetBuffer {
+            offset: read_u16_be(data, 1)?,
+            data: read_bytes(data, 4, read_chunk_size(data)?)?,
+        },
+        ViaCommand::DynamicKeymapMacroReset => ViaRequest::MacroReset,
+        ViaCommand::DynamicKeymapGetLayerCount => ViaRequest::GetLayerCount,
+        ViaCommand::DynamicKeymapGetBuffer => ViaRequest::GetBuffer {
+            offset: read_u16_be(data, 1)?,
+            size: read_keycode_chunk_size(data)?,
+        },
+        ViaCommand::DynamicKeymapSetBuffer => ViaRequest::SetBuffer {
+            offset: read_u16_be(data, 1)?,
+            data: read_bytes(data, 4, read_keycode_chunk_size(data)?)?,
+        },
+        ViaCommand::LogLevel => ViaRequest::LogLevel {
+            module: match read_u8(data, 1)? {
+                LOG_LEVEL_ALL => None,
+                id => Some(LogModule::from_repr(id).ok_or(ViaError::OutOfRange)?),
+            },
+            level: LogLevel::from_repr(read_u8(data, 2)?),
+        },
+        #[cfg(feature = "introspection")]
+        ViaCommand::RuntimeState => ViaRequest::RuntimeState,
+        #[cfg(not(feature = "introspection"))]
+        ViaCommand::RuntimeState => {
+            warn!("Runtime state -- not supported without introspection");
+            return Err(ViaError::UnknownCommand);
+        }
+        #[cfg(feature = "crash_dump")]
+        ViaCommand::CrashRecord => ViaRequest::CrashRecord {
+            offset: read_u8(data, 1)?,
+        },
+        #[cfg(not(feature = "crash_dump"))]
+        ViaCommand::CrashRecord => {
+            warn!("Crash record -- not supported without crash_dump");
+            return Err(ViaError::UnknownCommand);
+        }
+        #[cfg(feature = "storage")]
+        ViaCommand::Backup => ViaRequest::Backup,
+        #[cfg(not(feature = "storage"))]
+        ViaCommand::Backup => {
+            warn!("Backup -- not supported without storage");
+            return Err(ViaError::UnknownCommand);
+        }
+        ViaCommand::Vial => ViaRequest::Vial,
+        ViaCommand::Unhandled => {
+            info!("Unknown cmd: {:?}", data);
+            return Err(ViaError::UnknownCommand);
+        }
+    };
+    Ok(request)
+}
+
+fn read_keyboard_info(data: &[u8]) -> Result<ViaKeyboardInfo, ViaError> {
+    ViaKeyboardInfo::try_from(read_u8(data, 1)?).map_err(|e| {
+        error!("Invalid subcommand: {} of Get/SetKeyboardValue", e);
+        ViaError::UnknownCommand
+    })
+}
+
+/// Size byte of the buffer commands, which is at most [`VIA_MAX_CHUNK_SIZE`]
+fn read_chunk_size(data: &[u8]) -> Result<usize, ViaError> {
+    let size = read_u8(data, 3)? as usize;
+    if size <= VIA_MAX_CHUNK_SIZE {
+        Ok(size)
+    } else {
+        Err(ViaError::InvalidLength)
+    }
+}
+
+/// Size byte of the keymap buffer commands, which also has to hold whole keycodes
+fn read_keycode_chunk_size(data: &[u8]) -> Result<usize, ViaError> {
+    let size = read_chunk_size(data)?;
+    if size.is_multiple_of(2) {
+        Ok(size)
+    } else {
+        Err(ViaError::InvalidLength)
+    }
+}
+
+fn read_u8(data: &[u8], at: usize) -> Result<u8, ViaError> {
+    data.get(at).copied().ok_or(ViaError::InvalidLength)
+}
+
+fn read_bytes(data: &[u8], at: usize, len: usize) -> Result<&[u8], ViaError> {
+    data.get(at..at + len).ok_or(ViaError::InvalidLength)
+}
+
+/// Big endian, like every Via field
+fn read_u16_be(data: &[u8], at: usize) -> Result<u16, ViaError> {
+    read_bytes(data, at, 2).map(BigEndian::read_u16)
+}
+
+/// Little endian, like most Vial fields
+fn read_u16_le(data: &[u8], at: usize) -> Result<u16, ViaError> {
+    read_bytes(data, at, 2).map(LittleEndian::read_u16)
 }
 
 impl Runnable for VialService<'_> {
@@ -240,8 +500,80 @@ impl Runnable for VialService<'_> {
                 input_data: output_data,
                 output_data,
             };
+            #[cfg(feature = "watchdog")]
+            let _busy = crate::watchdog::busy(crate::watchdog::CoreTask::HostService);
             self.process_via_packet(&mut report).await;
             try_send_host_reply(transport, report.input_data);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(bytes: &[u8]) -> [u8; 32] {
+        let mut data = [0; 32];
+        data[..bytes.len()].copy_from_slice(bytes);
+        data
+    }
+
+    #[test]
+    fn test_parse_keycode_requests() {
+        let data = packet(&[0x05, 1, 2, 3, 0x00, 0x04]);
+        assert_eq!(
+            parse_via_request(&data),
+            Ok(ViaRequest::SetKeyCode {
+                layer: 1,
+                row: 2,
+                col: 3,
+                keycode: 0x0004
+            })
+        );
+        // A truncated packet is rejected instead of read out of bounds
+        assert_eq!(parse_via_request(&data[..5]), Err(ViaError::InvalidLength));
+        assert_eq!(parse_via_request(&[]), Err(ViaError::InvalidLength));
+    }
+
+    #[test]
+    fn test_parse_buffer_sizes() {
+        let data = packet(&[0x12, 0x00, 0x04, 28]);
+        assert_eq!(
+            parse_via_request(&data),
+            Ok(ViaRequest::GetBuffer { offset: 4, size: 28 })
+        );
+        // Larger than the report, or not whole keycodes
+        assert_eq!(
+            parse_via_request(&packet(&[0x12, 0, 0, 29])),
+            Err(ViaError::InvalidLength)
+        );
+        assert_eq!(
+            parse_via_request(&packet(&[0x13, 0, 0, 255])),
+            Err(ViaError::InvalidLength)
+        );
+        assert_eq!(
+            parse_via_request(&packet(&[0x13, 0, 0, 3])),
+            Err(ViaError::InvalidLength)
+        );
+        assert_eq!(
+            parse_via_request(&packet(&[0x0F, 0, 0, 200])),
+            Err(ViaError::InvalidLength)
+        );
+
+        let data = packet(&[0x0F, 0x00, 0x10, 2, 0xAA, 0xBB]);
+        assert_eq!(
+            parse_via_request(&data),
+            Ok(ViaRequest::MacroSetBuffer {
+                offset: 0x10,
+                data: &[0xAA, 0xBB]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_requests() {
+        assert_eq!(parse_via_request(&packet(&[0x42])), Err(ViaError::UnknownCommand));
+        assert_eq!(parse_via_request(&packet(&[0x02, 0x42])), Err(ViaError::UnknownCommand));
+        assert_eq!(parse_via_request(&packet(&[0xFC, 0x42, 0])), Err(ViaError::OutOfRange));
+    }
+}
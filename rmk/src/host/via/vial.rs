@@ -2,12 +2,133 @@ use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use rmk_types::action::KeyAction;
 use rmk_types::constants::{COMBO_MAX_LENGTH, COMBO_MAX_NUM, MORSE_MAX_NUM};
 use rmk_types::morse::{DOUBLE_TAP, HOLD, HOLD_AFTER_TAP, Morse, MorseMode, TAP};
-use rmk_types::protocol::vial::{SettingKey, VIAL_EP_SIZE, VIAL_PROTOCOL_VERSION, VialCommand, VialDynamic};
+use rmk_types::protocol::vial::{SettingKey, VIAL_EP_SIZE, VIAL_PROTOCOL_VERSION, ViaError, VialCommand, VialDynamic};
 
 use crate::config::VialConfig;
 use crate::hid::ViaReport;
 use crate::host::context::KeyboardContext;
 use crate::host::via::keycode_convert::{from_via_keycode, to_via_keycode};
+use crate::host::via::{read_u8, read_u16_be, read_u16_le};
+
+/// A Vial request decoded from a report, with every field checked against the report size.
+///
+/// Indices of entries, like morses and encoders, are checked by the handler.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum VialRequest {
+    GetKeyboardId,
+    GetSize,
+    GetKeyboardDef {
+        page: u16,
+    },
+    GetEncoder {
+        layer: u8,
+        index: u8,
+    },
+    SetEncoder {
+        layer: u8,
+        index: u8,
+        clockwise: bool,
+        keycode: u16,
+    },
+    GetUnlockStatus,
+    UnlockStart,
+    UnlockPoll,
+    Lock,
+    BehaviorSettingQuery {
+        page: u16,
+    },
+    GetBehaviorSetting(SettingKey),
+    /// Flags are set when the low byte of `value` is 1
+    SetBehaviorSetting {
+        key: SettingKey,
+        value: u16,
+    },
+    QmkSettingsReset,
+    GetNumberOfEntries,
+    MorseGet(u8),
+    MorseSet {
+        index: u8,
+        /// Tap, hold, double tap and hold after tap
+        keycodes: [u16; 4],
+        timeout_ms: u16,
+    },
+    ComboGet(u8),
+    ComboSet {
+        index: u8,
+        keycodes: [u16; COMBO_MAX_LENGTH],
+        output: u16,
+    },
+    KeyOverrideGet,
+    KeyOverrideSet,
+}
+
+fn parse_vial_request(data: &[u8]) -> Result<VialRequest, ViaError> {
+    let request = match VialCommand::from(read_u8(data, 1)?) {
+        VialCommand::GetKeyboardId => VialRequest::GetKeyboardId,
+        VialCommand::GetSize => VialRequest::GetSize,
+        VialCommand::GetKeyboardDef => VialRequest::GetKeyboardDef {
+            page: read_u16_le(data, 2)?,
+        },
+        VialCommand::GetEncoder => VialRequest::GetEncoder {
+            layer: read_u8(data, 2)?,
+            index: read_u8(data, 3)?,
+        },
+        VialCommand::SetEncoder => VialRequest::SetEncoder {
+            layer: read_u8(data, 2)?,
+            index: read_u8(data, 3)?,
+            clockwise: read_u8(data, 4)? == 1,
+            keycode: read_u16_be(data, 5)?,
+        },
+        VialCommand::GetUnlockStatus => VialRequest::GetUnlockStatus,
+        VialCommand::UnlockStart => VialRequest::UnlockStart,
+        VialCommand::UnlockPoll => VialRequest::UnlockPoll,
+        VialCommand::Lock => VialRequest::Lock,
+        VialCommand::BehaviorSettingQuery => VialRequest::BehaviorSettingQuery {
+            page: read_u16_le(data, 2)?,
+        },
+        VialCommand::GetBehaviorSetting => VialRequest::GetBehaviorSetting(read_u16_le(data, 2)?.into()),
+        VialCommand::SetBehaviorSetting => VialRequest::SetBehaviorSetting {
+            key: read_u16_le(data, 2)?.into(),
+            value: read_u16_le(data, 4)?,
+        },
+        VialCommand::QmkSettingsReset => VialRequest::QmkSettingsReset,
+        VialCommand::DynamicEntryOp => match VialDynamic::from(read_u8(data, 2)?) {
+            VialDynamic::DynamicVialGetNumberOfEntries => VialRequest::GetNumberOfEntries,
+            VialDynamic::DynamicVialMorseGet => VialRequest::MorseGet(read_u8(data, 3)?),
+            VialDynamic::DynamicVialMorseSet => VialRequest::MorseSet {
+                index: read_u8(data, 3)?,
+                keycodes: [
+                    read_u16_le(data, 4)?,
+                    read_u16_le(data, 6)?,
+                    read_u16_le(data, 8)?,
+                    read_u16_le(data, 10)?,
+                ],
+                timeout_ms: read_u16_le(data, 12)?,
+            },
+            VialDynamic::DynamicVialComboGet => VialRequest::ComboGet(read_u8(data, 3)?),
+            VialDynamic::DynamicVialComboSet => {
+                let mut keycodes = [0; COMBO_MAX_LENGTH];
+                for (i, keycode) in keycodes.iter_mut().enumerate() {
+                    *keycode = read_u16_le(data, 4 + i * 2)?;
+                }
+                VialRequest::ComboSet {
+                    index: read_u8(data, 3)?,
+                    keycodes,
+                    output: read_u16_le(data, 4 + COMBO_MAX_LENGTH * 2)?,
+                }
+            }
+            VialDynamic::DynamicVialKeyOverrideGet => VialRequest::KeyOverrideGet,
+            VialDynamic::DynamicVialKeyOverrideSet => VialRequest::KeyOverrideSet,
+            VialDynamic::Unhandled => {
+                warn!("DynamicEntryOp - Unhandled -- subcommand not recognized");
+                return Err(ViaError::UnknownCommand);
+            }
+        },
+        VialCommand::Unhandled => return Err(ViaError::UnknownCommand),
+    };
+    Ok(request)
+}
 
 /// Note: vial uses little endian, while via uses big endian
 pub(crate) async fn process_vial<'a>(
@@ -15,40 +136,34 @@ pub(crate) async fn process_vial<'a>(
     vial_config: &VialConfig<'a>,
     #[cfg(feature = "vial_lock")] locker: &mut super::vial_lock::VialLock<'_>,
     ctx: &KeyboardContext<'_>,
-) {
+) -> Result<(), ViaError> {
     // report.output_data[0] == 0xFE -> vial commands
-    let vial_command = report.output_data[1].into();
-    debug!("Received vial command: {:?}", vial_command);
-    match vial_command {
-        VialCommand::GetKeyboardId => {
+    let request = parse_vial_request(&report.output_data)?;
+    debug!("Received vial request: {:?}", request);
+    match request {
+        VialRequest::GetKeyboardId => {
             // Returns vial protocol version + vial keyboard id
             LittleEndian::write_u32(&mut report.input_data[0..4], VIAL_PROTOCOL_VERSION);
             report.input_data[4..12].clone_from_slice(vial_config.vial_keyboard_id);
             debug!("Vial return: {:?}", report.input_data);
         }
-        VialCommand::GetSize => {
+        VialRequest::GetSize => {
             LittleEndian::write_u32(&mut report.input_data[0..4], vial_config.vial_keyboard_def.len() as u32);
         }
-        VialCommand::GetKeyboardDef => {
-            let page = LittleEndian::read_u16(&report.output_data[2..4]) as usize;
-            let start = page * VIAL_EP_SIZE;
-            let mut end = start + VIAL_EP_SIZE;
+        VialRequest::GetKeyboardDef { page } => {
+            let start = page as usize * VIAL_EP_SIZE;
             let vial_keyboard_def = &vial_config.vial_keyboard_def;
-            if end < start || start >= vial_keyboard_def.len() {
-                return;
-            }
-            if end > vial_keyboard_def.len() {
-                end = vial_keyboard_def.len();
+            if start >= vial_keyboard_def.len() {
+                return Err(ViaError::OutOfRange);
             }
-            vial_keyboard_def[start..end].iter().enumerate().for_each(|(i, v)| {
-                report.input_data[i] = *v;
-            });
+            let end = (start + VIAL_EP_SIZE).min(vial_keyboard_def.len());
+            report.input_data[..end - start].copy_from_slice(&vial_keyboard_def[start..end]);
             debug!(
                 "Vial return: page:{} start:{} end: {}, data: {:?}",
                 page, start, end, report.input_data
             );
         }
-        VialCommand::GetUnlockStatus => {
+        VialRequest::GetUnlockStatus => {
             // Reset all data to 0xFF(it's required!)
             report.input_data.fill(0xFF);
             #[cfg(feature = "vial_lock")]
@@ -57,10 +172,14 @@ pub(crate) async fn process_vial<'a>(
                 report.input_data[0] = locker.is_unlocked() as u8;
                 // Unlock in progress
                 report.input_data[1] = locker.is_unlocking() as u8;
-                // Unlock keys
-                for (idx, (row, col)) in vial_config.unlock_keys.iter().enumerate() {
-                    report.input_data[2 + idx * 2] = *row;
-                    report.input_data[3 + idx * 2] = *col;
+                // Unlock keys, as many as the report can carry
+                for ((row, col), chunk) in vial_config
+                    .unlock_keys
+                    .iter()
+                    .zip(report.input_data[2..].chunks_exact_mut(2))
+                {
+                    chunk[0] = *row;
+                    chunk[1] = *col;
                 }
             }
             #[cfg(not(feature = "vial_lock"))]
@@ -72,13 +191,13 @@ pub(crate) async fn process_vial<'a>(
                 warn!("Vial lock feature is not enabled");
             }
         }
-        VialCommand::UnlockStart => {
+        VialRequest::UnlockStart => {
             #[cfg(feature = "vial_lock")]
             locker.unlocking();
             #[cfg(not(feature = "vial_lock"))]
             error!("Vial lock feature is not enabled");
         }
-        VialCommand::UnlockPoll => {
+        VialRequest::UnlockPoll => {
             #[cfg(feature = "vial_lock")]
             {
                 locker.unlocking();
@@ -89,16 +208,15 @@ pub(crate) async fn process_vial<'a>(
             #[cfg(not(feature = "vial_lock"))]
             error!("Vial lock feature is not enabled");
         }
-        VialCommand::Lock => {
+        VialRequest::Lock => {
             #[cfg(feature = "vial_lock")]
             locker.lock();
             #[cfg(not(feature = "vial_lock"))]
             error!("Vial lock feature is not enabled");
         }
-        VialCommand::BehaviorSettingQuery => {
+        VialRequest::BehaviorSettingQuery { page } => {
             report.input_data.fill(0xFF);
-            let value = u16::from_le_bytes([report.output_data[2], report.output_data[3]]);
-            if value <= 8 {
+            if page <= 8 {
                 LittleEndian::write_u16(&mut report.input_data[0..2], 0x02);
                 LittleEndian::write_u16(&mut report.input_data[2..4], 0x06);
                 LittleEndian::write_u16(&mut report.input_data[4..6], 0x07);
@@ -110,11 +228,10 @@ pub(crate) async fn process_vial<'a>(
                 LittleEndian::write_u16(&mut report.input_data[16..18], 0x1B);
             }
         }
-        VialCommand::GetBehaviorSetting => {
+        VialRequest::GetBehaviorSetting(key) => {
             report.input_data.fill(0xFF);
-            let value = u16::from_le_bytes([report.output_data[2], report.output_data[3]]);
             report.input_data[0] = 0;
-            match value.into() {
+            match key {
                 SettingKey::None => report.input_data[0] = 0xFF,
                 SettingKey::ComboTimeout => {
                     let combo_timeout = ctx.combo_timeout().as_millis() as u16;
@@ -168,34 +285,28 @@ pub(crate) async fn process_vial<'a>(
                 }
             }
         }
-        VialCommand::SetBehaviorSetting => {
-            let key = u16::from_le_bytes([report.output_data[2], report.output_data[3]]);
-            match key.into() {
+        VialRequest::SetBehaviorSetting { key, value } => {
+            let enabled = value as u8 == 1;
+            match key {
                 SettingKey::None => (),
                 SettingKey::ComboTimeout => {
-                    let combo_timeout = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    ctx.set_combo_timeout(combo_timeout).await;
+                    ctx.set_combo_timeout(value).await;
                 }
                 SettingKey::MorseTimeout => {
-                    let timeout_time = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    let new_profile = ctx.morse_default_profile().with_hold_timeout_ms(Some(timeout_time));
+                    let new_profile = ctx.morse_default_profile().with_hold_timeout_ms(Some(value));
                     ctx.set_morse_default_profile(new_profile).await;
                 }
                 SettingKey::OneShotTimeout => {
-                    let timeout_time = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    ctx.set_one_shot_timeout(timeout_time).await;
+                    ctx.set_one_shot_timeout(value).await;
                 }
                 SettingKey::TapInterval => {
-                    let tap_interval = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    ctx.set_tap_interval(tap_interval).await;
+                    ctx.set_tap_interval(value).await;
                 }
                 SettingKey::TapCapslockInterval => {
-                    let tap_capslock_interval = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    ctx.set_tap_capslock_interval(tap_capslock_interval).await;
+                    ctx.set_tap_capslock_interval(value).await;
                 }
 
                 SettingKey::PermissiveHold => {
-                    let enabled = report.output_data[4] == 1;
                     let old = ctx.morse_default_profile();
                     let new_mode = if enabled {
                         // Hold On Other Key Press has higher priority
@@ -216,7 +327,6 @@ pub(crate) async fn process_vial<'a>(
                     ctx.set_morse_default_profile(old.with_mode(new_mode)).await;
                 }
                 SettingKey::HoldOnOtherKeyPress => {
-                    let enabled = report.output_data[4] == 1;
                     let old = ctx.morse_default_profile();
                     let new_mode = if enabled {
                         // Enable: Set to HoldOnOtherPress (will override other modes)
@@ -232,155 +342,130 @@ pub(crate) async fn process_vial<'a>(
                     ctx.set_morse_default_profile(old.with_mode(new_mode)).await;
                 }
                 SettingKey::UnilateralTap => {
-                    let new_profile = ctx
-                        .morse_default_profile()
-                        .with_unilateral_tap(Some(report.output_data[4] == 1));
+                    let new_profile = ctx.morse_default_profile().with_unilateral_tap(Some(enabled));
                     ctx.set_morse_default_profile(new_profile).await;
                 }
                 SettingKey::PriorIdleTime => {
-                    let prior_idle_time = u16::from_le_bytes([report.output_data[4], report.output_data[5]]);
-                    ctx.set_morse_prior_idle_time(prior_idle_time).await;
+                    ctx.set_morse_prior_idle_time(value).await;
                 }
             }
         }
-        VialCommand::DynamicEntryOp => {
-            let vial_dynamic = report.output_data[2].into();
-            match vial_dynamic {
-                VialDynamic::DynamicVialGetNumberOfEntries => {
-                    debug!("DynamicEntryOp - DynamicVialGetNumberOfEntries");
-                    report.input_data[0] = core::cmp::min(MORSE_MAX_NUM, 255) as u8; // Tap dance entries
-                    report.input_data[1] = core::cmp::min(COMBO_MAX_NUM, 255) as u8; // Combo entries
-                    // TODO: Support dynamic key override
-                    report.input_data[2] = 0; // Key override entries
-                    report.input_data[31] = 1 // Enable caps word
-                }
-                VialDynamic::DynamicVialMorseGet => {
-                    debug!("DynamicEntryOp - DynamicVialMorseGet");
-                    report.input_data[0] = 0; // Index 0 is the return code, 0 means success
+        VialRequest::QmkSettingsReset => (),
+        VialRequest::GetNumberOfEntries => {
+            debug!("DynamicEntryOp - DynamicVialGetNumberOfEntries");
+            report.input_data[0] = core::cmp::min(MORSE_MAX_NUM, 255) as u8; // Tap dance entries
+            // Combo entries, none without the `combo` feature so that Vial hides the combo editor
+            report.input_data[1] = if cfg!(feature = "combo") {
+                core::cmp::min(COMBO_MAX_NUM, 255) as u8
+            } else {
+                0
+            };
+            // TODO: Support dynamic key override
+            report.input_data[2] = 0; // Key override entries
+            report.input_data[31] = 1 // Enable caps word
+        }
+        VialRequest::MorseGet(index) => {
+            debug!("DynamicEntryOp - DynamicVialMorseGet");
+            report.input_data[0] = 0; // Index 0 is the return code, 0 means success
 
-                    let morse_idx = report.output_data[3];
-                    if let Some(morse) = ctx.get_morse(morse_idx) {
-                        // Pack morse data into report
-                        LittleEndian::write_u16(
-                            &mut report.input_data[1..3],
-                            to_via_keycode(morse.get(TAP).map_or(KeyAction::No, KeyAction::Single)),
-                        );
-                        LittleEndian::write_u16(
-                            &mut report.input_data[3..5],
-                            to_via_keycode(morse.get(HOLD).map_or(KeyAction::No, KeyAction::Single)),
-                        );
-                        LittleEndian::write_u16(
-                            &mut report.input_data[5..7],
-                            to_via_keycode(morse.get(DOUBLE_TAP).map_or(KeyAction::No, KeyAction::Single)),
-                        );
-                        LittleEndian::write_u16(
-                            &mut report.input_data[7..9],
-                            to_via_keycode(morse.get(HOLD_AFTER_TAP).map_or(KeyAction::No, KeyAction::Single)),
-                        );
-                        let timeout_ms = morse.profile.hold_timeout_ms().unwrap_or(250);
-                        LittleEndian::write_u16(&mut report.input_data[9..11], timeout_ms);
-                    } else {
-                        report.input_data[1..11].fill(0);
-                    }
+            if let Some(morse) = ctx.get_morse(index) {
+                // Pack morse data into report
+                for (chunk, pattern) in
+                    report.input_data[1..9]
+                        .chunks_exact_mut(2)
+                        .zip([TAP, HOLD, DOUBLE_TAP, HOLD_AFTER_TAP])
+                {
+                    let action = morse.get(pattern).map_or(KeyAction::No, KeyAction::Single);
+                    LittleEndian::write_u16(chunk, to_via_keycode(action));
                 }
-                VialDynamic::DynamicVialMorseSet => {
-                    debug!("DynamicEntryOp - DynamicVialMorseSet");
-                    report.input_data[0] = 0; // Index 0 is the return code, 0 means success
+                let timeout_ms = morse.profile.hold_timeout_ms().unwrap_or(250);
+                LittleEndian::write_u16(&mut report.input_data[9..11], timeout_ms);
+            } else {
+                report.input_data[1..11].fill(0);
+            }
+        }
+        VialRequest::MorseSet {
+            index,
+            keycodes: [tap, hold, double_tap, hold_after_tap],
+            timeout_ms,
+        } => {
+            debug!("DynamicEntryOp - DynamicVialMorseSet");
+            if index as usize >= ctx.morses_len() {
+                return Err(ViaError::OutOfRange);
+            }
+            report.input_data[0] = 0; // Index 0 is the return code, 0 means success
 
-                    let morse_idx = report.output_data[3];
+            // Extract morse (also known as "tap dance" in vial)
+            let tap = from_via_keycode(tap);
+            let hold = from_via_keycode(hold);
+            let double_tap = from_via_keycode(double_tap);
+            let hold_after_tap = from_via_keycode(hold_after_tap);
 
-                    if (morse_idx as usize) < ctx.morses_len() {
-                        // Extract morse (also known as "tap dance" in vial)
-                        let tap = from_via_keycode(LittleEndian::read_u16(&report.output_data[4..6]));
-                        let hold = from_via_keycode(LittleEndian::read_u16(&report.output_data[6..8]));
-                        let double_tap = from_via_keycode(LittleEndian::read_u16(&report.output_data[8..10]));
-                        let hold_after_tap = from_via_keycode(LittleEndian::read_u16(&report.output_data[10..12]));
-                        let timeout_ms = LittleEndian::read_u16(&report.output_data[12..14]);
+            ctx.update_morse(index, |morse: &mut Morse| {
+                let _ = morse.put(TAP, tap.to_action());
+                let _ = morse.put(DOUBLE_TAP, double_tap.to_action());
+                let _ = morse.put(HOLD, hold.to_action());
+                let _ = morse.put(HOLD_AFTER_TAP, hold_after_tap.to_action());
+                morse.profile.set_hold_timeout_ms(timeout_ms);
+                morse.profile.set_gap_timeout_ms(timeout_ms);
+            })
+            .await;
+        }
+        VialRequest::ComboGet(index) => {
+            debug!("DynamicEntryOp - DynamicVialComboGet");
+            report.input_data[0] = 0; // Index 0 is the return code, 0 means success
 
-                        ctx.update_morse(morse_idx, |morse: &mut Morse| {
-                            let _ = morse.put(TAP, tap.to_action());
-                            let _ = morse.put(DOUBLE_TAP, double_tap.to_action());
-                            let _ = morse.put(HOLD, hold.to_action());
-                            let _ = morse.put(HOLD_AFTER_TAP, hold_after_tap.to_action());
-                            morse.profile.set_hold_timeout_ms(timeout_ms);
-                            morse.profile.set_gap_timeout_ms(timeout_ms);
-                        })
-                        .await;
+            ctx.with_combos(|combos| {
+                let end = (3 + COMBO_MAX_LENGTH * 2).min(report.input_data.len());
+                let data = &mut report.input_data[1..end];
+                if let Some(Some(combo)) = combos.get(index as usize) {
+                    // Combo components, followed by the combo output
+                    let actions = (0..COMBO_MAX_LENGTH)
+                        .map(|i| combo.config.actions.get(i).copied().unwrap_or(KeyAction::No))
+                        .chain([combo.config.output]);
+                    for (chunk, action) in data.chunks_exact_mut(2).zip(actions) {
+                        LittleEndian::write_u16(chunk, to_via_keycode(action));
                     }
+                } else {
+                    data.fill(0);
                 }
-                VialDynamic::DynamicVialComboGet => {
-                    debug!("DynamicEntryOp - DynamicVialComboGet");
-                    report.input_data[0] = 0; // Index 0 is the return code, 0 means success
-
-                    let combo_idx = report.output_data[3] as usize;
-                    ctx.with_combos(|combos| {
-                        if let Some(Some(combo)) = combos.get(combo_idx) {
-                            // Combo components
-                            for i in 0..COMBO_MAX_LENGTH {
-                                let kc = combo.config.actions.get(i).copied().unwrap_or(KeyAction::No);
-                                LittleEndian::write_u16(
-                                    &mut report.input_data[1 + i * 2..3 + i * 2],
-                                    to_via_keycode(kc),
-                                );
-                            }
-                            // Combo output
-                            LittleEndian::write_u16(
-                                &mut report.input_data[1 + COMBO_MAX_LENGTH * 2..3 + COMBO_MAX_LENGTH * 2],
-                                to_via_keycode(combo.config.output),
-                            );
-                        } else {
-                            report.input_data[1..3 + COMBO_MAX_LENGTH * 2].fill(0);
-                        }
-                    });
-                }
-                VialDynamic::DynamicVialComboSet => {
-                    debug!("DynamicEntryOp - DynamicVialComboSet");
-                    report.input_data[0] = 0; // Index 0 is the return code, 0 means success
-
-                    use rmk_types::combo::Combo as ComboConfig;
+            });
+        }
+        VialRequest::ComboSet {
+            index,
+            keycodes,
+            output,
+        } => {
+            debug!("DynamicEntryOp - DynamicVialComboSet");
+            if index as usize >= ctx.with_combos(|combos| combos.len()) {
+                return Err(ViaError::OutOfRange);
+            }
+            report.input_data[0] = 0; // Index 0 is the return code, 0 means success
 
-                    let combo_idx = report.output_data[3];
+            use rmk_types::combo::Combo as ComboConfig;
 
-                    let mut actions = heapless::Vec::<KeyAction, COMBO_MAX_LENGTH>::new();
-                    let mut overflow = false;
-                    for i in 0..COMBO_MAX_LENGTH {
-                        let action =
-                            from_via_keycode(LittleEndian::read_u16(&report.output_data[4 + i * 2..6 + i * 2]));
-                        if !action.is_empty() && actions.push(action).is_err() {
-                            overflow = true;
-                            break;
-                        }
-                    }
-                    if overflow {
-                        return;
-                    }
-                    let output = from_via_keycode(LittleEndian::read_u16(
-                        &report.output_data[4 + COMBO_MAX_LENGTH * 2..6 + COMBO_MAX_LENGTH * 2],
-                    ));
-                    let config = ComboConfig {
-                        actions,
-                        output,
-                        layer: None,
-                    };
-                    ctx.set_combo(combo_idx, config).await;
-                }
-                VialDynamic::DynamicVialKeyOverrideGet => {
-                    warn!("DynamicEntryOp - DynamicVialKeyOverrideGet -- to be implemented");
-                    report.input_data.fill(0x00);
-                }
-                VialDynamic::DynamicVialKeyOverrideSet => {
-                    warn!("DynamicEntryOp - DynamicVialKeyOverrideSet -- to be implemented");
-                    report.input_data.fill(0x00);
-                }
-                VialDynamic::Unhandled => {
-                    warn!("DynamicEntryOp - Unhandled -- subcommand not recognized");
-                    report.input_data.fill(0x00);
-                }
-            }
+            // `keycodes` holds at most `COMBO_MAX_LENGTH` actions, so the pushes can't fail
+            let actions = keycodes
+                .into_iter()
+                .map(from_via_keycode)
+                .filter(|action| !action.is_empty())
+                .collect();
+            let config = ComboConfig {
+                actions,
+                output: from_via_keycode(output),
+                layer: None,
+            };
+            ctx.set_combo(index, config).await;
+        }
+        VialRequest::KeyOverrideGet => {
+            warn!("DynamicEntryOp - DynamicVialKeyOverrideGet -- to be implemented");
+            report.input_data.fill(0x00);
+        }
+        VialRequest::KeyOverrideSet => {
+            warn!("DynamicEntryOp - DynamicVialKeyOverrideSet -- to be implemented");
+            report.input_data.fill(0x00);
         }
-        VialCommand::GetEncoder => {
-            let layer = report.output_data[2];
-            let index = report.output_data[3];
+        VialRequest::GetEncoder { layer, index } => {
             debug!("Received Vial - GetEncoder, encoder idx: {} at layer: {}", index, layer);
 
             // Get encoder value
@@ -389,23 +474,27 @@ pub(crate) async fn process_vial<'a>(
                 let counter_clockwise = to_via_keycode(encoder_action.counter_clockwise);
                 BigEndian::write_u16(&mut report.input_data[0..2], counter_clockwise);
                 BigEndian::write_u16(&mut report.input_data[2..4], clockwise);
-                return;
+                return Ok(());
             }
 
             // Clear returned value, aka `KeyAction::No`
             report.input_data.fill(0x0);
         }
-        VialCommand::SetEncoder => {
-            let layer = report.output_data[2];
-            let index = report.output_data[3];
-            let clockwise = report.output_data[4];
+        VialRequest::SetEncoder {
+            layer,
+            index,
+            clockwise,
+            keycode,
+        } => {
             debug!(
                 "Received Vial - SetEncoder, encoder idx: {} clockwise: {} at layer: {}",
                 index, clockwise, layer
             );
-            let keycode = BigEndian::read_u16(&report.output_data[5..7]);
+            if ctx.get_encoder(layer, index).is_none() {
+                return Err(ViaError::OutOfRange);
+            }
             let action = from_via_keycode(keycode);
-            if clockwise == 1 {
+            if clockwise {
                 info!("Setting clockwise action: {:?}", action);
                 ctx.set_encoder_clockwise(layer, index, action).await;
             } else {
@@ -413,8 +502,8 @@ pub(crate) async fn process_vial<'a>(
                 ctx.set_encoder_counter_clockwise(layer, index, action).await;
             }
         }
-        _ => (),
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -428,6 +517,25 @@ mod tests {
     use super::*;
     use crate::COMBO_MAX_LENGTH;
     use crate::storage::StorageData;
+
+    #[test]
+    fn test_parse_dynamic_entry_requests() {
+        let mut data = [0u8; 32];
+        data[..14].copy_from_slice(&[0xFE, 0x0D, 0x02, 3, 0x04, 0x00, 0x05, 0x00, 0, 0, 0, 0, 0xC8, 0x00]);
+        assert_eq!(
+            parse_vial_request(&data),
+            Ok(VialRequest::MorseSet {
+                index: 3,
+                keycodes: [0x0004, 0x0005, 0, 0],
+                timeout_ms: 200
+            })
+        );
+        // A truncated packet is rejected instead of read out of bounds
+        assert_eq!(parse_vial_request(&data[..13]), Err(ViaError::InvalidLength));
+        data[2] = 0x42;
+        assert_eq!(parse_vial_request(&data), Err(ViaError::UnknownCommand));
+    }
+
     #[test]
     fn test_combo_serialization_deserialization() {
         let mut actions = heapless::Vec::<KeyAction, COMBO_MAX_LENGTH>::new();
@@ -0,0 +1,57 @@
+//! Operating system of the host.
+//!
+//! The USB transport guesses the OS from the string descriptor requests made
+//! while the host enumerates the keyboard. The guess can be overridden with the
+//! `HostOs*` keyboard actions or [`set_host_os_override`], for hosts that aren't
+//! recognized or when the keyboard is connected over BLE.
+//!
+//! Code depending on the host OS reads [`host_os`] when it needs it, like the
+//! Ctrl and Cmd swap of the keyboard.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+pub use rmk_types::connection::HostOs;
+
+static DETECTED_HOST_OS: AtomicU8 = AtomicU8::new(HostOs::Unknown as u8);
+/// `HostOs::Unknown` means no override
+static HOST_OS_OVERRIDE: AtomicU8 = AtomicU8::new(HostOs::Unknown as u8);
+
+/// Current host OS, the manual override if set, otherwise the detected one.
+pub fn host_os() -> HostOs {
+    match HostOs::from(HOST_OS_OVERRIDE.load(Ordering::Acquire)) {
+        HostOs::Unknown => HostOs::from(DETECTED_HOST_OS.load(Ordering::Acquire)),
+        os => os,
+    }
+}
+
+/// Set the host OS manually, `None` goes back to the detected OS.
+///
+/// The override isn't persisted, only the `HostOs*` keys save it in the storage.
+pub fn set_host_os_override(os: Option<HostOs>) {
+    let old = host_os();
+    HOST_OS_OVERRIDE.store(os.unwrap_or(HostOs::Unknown) as u8, Ordering::Release);
+    notify_change(old);
+}
+
+/// Host OS set manually, `None` if the detected OS is used.
+pub(crate) fn host_os_override() -> Option<HostOs> {
+    match HostOs::from(HOST_OS_OVERRIDE.load(Ordering::Acquire)) {
+        HostOs::Unknown => None,
+        os => Some(os),
+    }
+}
+
+/// Record the OS detected by the transport.
+#[cfg(not(feature = "_no_usb"))]
+pub(crate) fn set_detected_host_os(os: HostOs) {
+    let old = host_os();
+    DETECTED_HOST_OS.store(os as u8, Ordering::Release);
+    notify_change(old);
+}
+
+fn notify_change(old: HostOs) {
+    let new = host_os();
+    if new != old {
+        info!("Host OS: {:?}", new);
+    }
+}
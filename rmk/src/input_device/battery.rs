@@ -93,12 +93,38 @@ impl<I: InputPin> ChargingStateReader<I> {
     }
 }
 
+/// Battery discharge curve: `(millivolts, percent)` points sorted from full to empty.
+pub type BatteryCurve = &'static [(u16, u8)];
+
+/// Typical discharge curve of a single cell LiPo battery.
+pub const LIPO_CURVE: BatteryCurve = &[
+    (4200, 100),
+    (4100, 90),
+    (4000, 80),
+    (3900, 65),
+    (3800, 50),
+    (3750, 40),
+    (3700, 30),
+    (3650, 20),
+    (3600, 10),
+    (3500, 5),
+    (3300, 0),
+];
+
+/// Linear curve between 3.564v and 4.179v, the mapping of earlier versions, used by default.
+pub const LINEAR_CURVE: BatteryCurve = &[(4179, 100), (3564, 0)];
+
 /// BatteryProcessor processes battery adc value and charging state,
 /// emits `BatteryStatusEvent` when battery status changes.
+///
+/// The battery voltage is mapped to a percentage with a [`BatteryCurve`],
+/// [`LINEAR_CURVE`] by default.
 #[processor(subscribe = [BatteryAdcEvent, ChargingStateEvent])]
 pub struct BatteryProcessor {
     adc_divider_measured: u32,
     adc_divider_total: u32,
+    /// Voltage to percentage mapping
+    curve: BatteryCurve,
     /// Current battery status
     battery_status: BatteryStatus,
 }
@@ -108,10 +134,17 @@ impl BatteryProcessor {
         BatteryProcessor {
             adc_divider_measured,
             adc_divider_total,
+            curve: LINEAR_CURVE,
             battery_status: BatteryStatus::Unavailable,
         }
     }
 
+    /// Use `curve` to map the battery voltage to a percentage.
+    pub fn with_curve(mut self, curve: BatteryCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
     /// Apply a new battery status: persist on the processor, mirror into
     /// [`BATTERY_STATUS`] for synchronous readers, and broadcast via
     /// [`BatteryStatusEvent`].
@@ -124,21 +157,22 @@ impl BatteryProcessor {
 
     #[cfg(feature = "_ble")]
     fn get_battery_percent(&self, val: u16) -> u8 {
-        // Avoid overflow
-        let val = val as i32;
+        battery_percent(self.curve, self.get_battery_millivolts(val))
+    }
 
+    /// Convert a raw ADC reading to the battery voltage in millivolts.
+    #[cfg(feature = "_ble")]
+    fn get_battery_millivolts(&self, val: u16) -> u32 {
         // According to nRF52840's datasheet, for single_ended saadc:
         // val = v_adc * (gain / reference) * 2^(resolution)
         //
         // When using default setting, gain = 1/6, reference = 0.6v, resolution = 12bits, so:
-        // val = v_adc * 1137.8
+        // v_adc = val * 3.6v / 4096
         //
         // For example, rmk-ble-keyboard uses two resistors 820K and 2M adjusting the v_adc, then,
-        // v_adc = v_bat * measured / total => val = v_bat * 1137.8 * measured / total
-        //
-        // If the battery voltage range is 3.6v ~ 4.2v, the adc val range should be (4096 ~ 4755) * measured / total
-        let mut measured = self.adc_divider_measured as i32;
-        let mut total = self.adc_divider_total as i32;
+        // v_adc = v_bat * measured / total => v_bat = val * 3.6v / 4096 * total / measured
+        let mut measured = self.adc_divider_measured.max(1);
+        let mut total = self.adc_divider_total;
         if 500 < val && val < 1000 {
             // Thing becomes different when using vddh as reference
             // The adc value for vddh pin is actually vddh/5,
@@ -146,17 +180,34 @@ impl BatteryProcessor {
             measured = 1;
             total = 5;
         }
-        if val > 4755_i32 * measured / total {
-            // 4755 ~= 4.2v * 1137.8
-            100_u8
-        } else if val < 4055_i32 * measured / total {
-            // 4096 ~= 3.6v * 1137.8
-            // To simplify the calculation, we use 4055 here
-            0_u8
-        } else {
-            ((val * total / measured - 4055) / 7) as u8
+        (val as u64 * 3600 * total as u64 / measured as u64 / 4096) as u32
+    }
+}
+
+/// Look up the charge percentage of a battery at `millivolts` on a discharge `curve`.
+///
+/// Voltages between two points are interpolated linearly, voltages outside the
+/// curve are clamped to its first or last point.
+#[cfg(feature = "_ble")]
+fn battery_percent(curve: BatteryCurve, millivolts: u32) -> u8 {
+    let (Some(&(high_mv, high_pct)), Some(&(low_mv, low_pct))) = (curve.first(), curve.last()) else {
+        return 0;
+    };
+    if millivolts >= high_mv as u32 {
+        return high_pct;
+    }
+    if millivolts <= low_mv as u32 {
+        return low_pct;
+    }
+    for pair in curve.windows(2) {
+        let ((upper_mv, upper_pct), (lower_mv, lower_pct)) = (pair[0], pair[1]);
+        if millivolts >= lower_mv as u32 && upper_mv > lower_mv {
+            let span_pct = upper_pct.saturating_sub(lower_pct) as u32;
+            let offset = (millivolts - lower_mv as u32) * span_pct / (upper_mv - lower_mv) as u32;
+            return lower_pct + offset as u8;
         }
     }
+    low_pct
 }
 
 impl BatteryProcessor {
@@ -220,3 +271,31 @@ impl BatteryProcessor {
         }
     }
 }
+
+#[cfg(all(test, feature = "_ble"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_is_clamped_and_interpolated() {
+        assert_eq!(battery_percent(LIPO_CURVE, 4300), 100);
+        assert_eq!(battery_percent(LIPO_CURVE, 4200), 100);
+        assert_eq!(battery_percent(LIPO_CURVE, 4150), 95);
+        assert_eq!(battery_percent(LIPO_CURVE, 3800), 50);
+        assert_eq!(battery_percent(LIPO_CURVE, 3400), 2);
+        assert_eq!(battery_percent(LIPO_CURVE, 3000), 0);
+        assert_eq!(battery_percent(LINEAR_CURVE, 3900), 54);
+        assert_eq!(battery_percent(LINEAR_CURVE, 3564), 0);
+        assert_eq!(battery_percent(&[], 3900), 0);
+    }
+
+    #[test]
+    fn adc_value_is_converted_with_divider() {
+        // 820K/2M divider, 4.2v battery
+        let processor = BatteryProcessor::new(2000, 2820);
+        let val = (4200u64 * 4096 * 2000 / 2820 / 3600) as u16;
+        assert!(processor.get_battery_millivolts(val).abs_diff(4200) < 5);
+        // VDDH/5 input
+        assert!(BatteryProcessor::new(1, 5).get_battery_millivolts(910).abs_diff(4000) < 5);
+    }
+}
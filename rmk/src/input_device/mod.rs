@@ -8,9 +8,13 @@ use crate::core_traits::Runnable;
 pub mod adc;
 #[cfg(feature = "_ble")]
 pub mod battery;
+#[cfg(feature = "pointing")]
 pub mod iqs5xx;
+#[cfg(feature = "pointing")]
 pub mod joystick;
+#[cfg(feature = "pointing")]
 pub mod pmw33xx;
+#[cfg(feature = "pointing")]
 pub mod pmw3610;
 pub mod pointing;
 pub mod rotary_encoder;
@@ -793,7 +793,7 @@ mod tests {
     use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTrans};
 
     use super::*;
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
 
     // Init logger for tests
     #[ctor::ctor(unsafe)]
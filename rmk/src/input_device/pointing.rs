@@ -756,7 +756,7 @@ mod tests {
 
     use super::*;
     use crate::input_device::InputDevice;
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
 
     // Init logger for tests
     #[ctor::ctor(unsafe)]
@@ -0,0 +1,82 @@
+//! Snapshot of the runtime state of the keyboard.
+//!
+//! With the `introspection` feature, the keyboard task keeps a snapshot of its state after every
+//! key it processes: the active layers, the keys waiting for a tap-hold or combo decision, the
+//! one-shot keys, Caps Word and the combos which are in progress. Read it with [`runtime_state`],
+//! the `state` command of the USB console, or the `RuntimeState` Via command, see
+//! `rmk_types::protocol::vial::RUNTIME_STATE_NONE`, to see why a key did what it did when the
+//! bug can't be reproduced with a debug probe attached.
+//!
+//! The connection status is read when the snapshot is, so it's always the current one.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use rmk_types::connection::ConnectionStatus;
+
+use crate::RawMutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RuntimeState {
+    /// Bit `n` is set when layer `n` is activated, the default layer isn't included
+    pub layers: u32,
+    pub default_layer: u8,
+    /// Highest active layer, which keys are looked up in first
+    pub top_layer: u8,
+    /// Keys waiting for a tap-hold or morse decision
+    pub pending_tap_hold: u8,
+    /// Keys held for a tap-hold or morse action after the decision
+    pub holding: u8,
+    /// Keys waiting for the other keys of a combo
+    pub pending_combo: u8,
+    pub oneshot_layer: Option<u8>,
+    /// Bits of the one-shot `ModifierCombination`, 0 when there's none
+    pub oneshot_modifiers: u8,
+    /// Bits of the `ModifierCombination` held by modifier keys
+    pub held_modifiers: u8,
+    pub caps_word: bool,
+    /// Whether combos are turned on, they're turned off with the `ComboOff` key
+    pub combos_enabled: bool,
+    /// Combos with some, but not all of their keys pressed
+    pub combos_in_progress: u8,
+    /// Combos which are triggered and not released yet
+    pub combos_triggered: u8,
+    pub connection: ConnectionStatus,
+}
+
+impl RuntimeState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            layers: 0,
+            default_layer: 0,
+            top_layer: 0,
+            pending_tap_hold: 0,
+            holding: 0,
+            pending_combo: 0,
+            oneshot_layer: None,
+            oneshot_modifiers: 0,
+            held_modifiers: 0,
+            caps_word: false,
+            combos_enabled: false,
+            combos_in_progress: 0,
+            combos_triggered: 0,
+            connection: ConnectionStatus::new(),
+        }
+    }
+}
+
+/// Last snapshot of the keyboard task
+static STATE: Mutex<RawMutex, Cell<RuntimeState>> = Mutex::new(Cell::new(RuntimeState::new()));
+
+/// Keep the snapshot of the keyboard task, the connection status is ignored.
+pub(crate) fn update(state: RuntimeState) {
+    STATE.lock(|s| s.set(state));
+}
+
+/// The runtime state after the last processed key, with the current connection status.
+pub fn runtime_state() -> RuntimeState {
+    let mut state = STATE.lock(Cell::get);
+    state.connection = crate::state::current_connection_status();
+    state
+}
@@ -8,6 +8,7 @@ use embassy_sync::signal::Signal;
 use embassy_time::{Duration, Instant, Timer, with_deadline};
 use heapless::Vec;
 use rmk_types::action::{Action, KeyAction, KeyboardAction};
+use rmk_types::connection::HostOs;
 use rmk_types::fork::StateBits;
 use rmk_types::keycode::{ConsumerKey, HidKeyCode, KeyCode, SpecialKey, SystemControlKey};
 use rmk_types::led_indicator::LedIndicator;
@@ -16,24 +17,33 @@ use rmk_types::morse::{MorseMode, MorsePattern, TAP};
 use rmk_types::mouse_button::MouseButtons;
 use usbd_hid::descriptor::{MediaKeyboardReport, SystemControlReport};
 
+#[cfg(feature = "combo")]
+use crate::COMBO_MAX_NUM;
+#[cfg(feature = "macros")]
+use crate::MACRO_SPACE_SIZE;
 use crate::channel::send_hid_report;
 use crate::core_traits::Runnable;
 #[cfg(all(feature = "split", feature = "_ble"))]
 use crate::event::ClearPeerEvent;
+#[cfg(feature = "combo")]
+use crate::event::ComboTriggeredEvent;
 use crate::event::{
-    ActionEvent, KeyboardEvent, KeyboardEventPos, ModifierEvent, SubscribableEvent, publish_event, publish_event_async,
+    ActionEvent, CapsWordEvent, KeyboardEvent, KeyboardEventPos, ModifierEvent, SubscribableEvent, publish_event,
+    publish_event_async,
 };
-use crate::hid::{KeyboardReport, Report};
+use crate::hid::{KeyboardReport, NKRO_ENABLED, NkroKeyboardReport, Report};
+#[cfg(feature = "combo")]
 use crate::keyboard::combo::Combo;
 use crate::keyboard::fork::ActiveFork;
 use crate::keyboard::held_buffer::{HeldBuffer, HeldKey, KeyState};
 use crate::keyboard::mouse::{MouseAction, MouseState};
 use crate::keyboard::oneshot::OneShotState;
+#[cfg(feature = "macros")]
 use crate::keyboard_macros::MacroOperation;
 use crate::keymap::KeyMap;
 #[cfg(all(feature = "split", feature = "_ble"))]
 use crate::split::ble::central::update_activity_time;
-use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, MACRO_SPACE_SIZE, boot};
+use crate::{FORK_MAX_NUM, boot};
 
 pub mod combo;
 pub(crate) mod fork;
@@ -50,16 +60,28 @@ use crate::keymap::HOLD_BUFFER_SIZE;
 #[cfg(feature = "_ble")]
 pub(crate) static LAST_KEY_TIMESTAMP: Signal<crate::RawMutex, u32> = Signal::new();
 
+/// Time of the last key action in ms since the boot, the storage task saves edits in the pauses of typing
+#[cfg(feature = "storage")]
+static LAST_KEY_ACTION_MS: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+/// Whether a key action happened within the last `duration`.
+#[cfg(feature = "storage")]
+pub(crate) fn typed_within(duration: Duration) -> bool {
+    let last = LAST_KEY_ACTION_MS.load(core::sync::atomic::Ordering::Relaxed);
+    (Instant::now().as_millis() as u32).wrapping_sub(last) < duration.as_millis() as u32
+}
+
 /// Led states for the keyboard hid report (its value is received by by the light service in a hid report)
 /// LedIndicator type would be nicer, but that does not have const expr constructor
 pub(crate) static LOCK_LED_STATES: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0u8);
 
 /// Read the current host-driven lock LED state as a typed [`LedIndicator`].
 ///
-/// Updated by `run_led_reader` whenever the host sends a SET_REPORT for LEDs;
-/// host services read it synchronously instead of subscribing to
-/// [`LedIndicatorEvent`](crate::event::LedIndicatorEvent).
-pub(crate) fn current_led_indicator() -> LedIndicator {
+/// This is the state of the active host, updated whenever it sends a SET_REPORT
+/// for LEDs and when the active transport changes. Use it to read the state
+/// synchronously, e.g. when a display is drawn for the first time, and subscribe
+/// to [`LedIndicatorEvent`](crate::event::LedIndicatorEvent) to be notified of changes.
+pub fn current_led_indicator() -> LedIndicator {
     LedIndicator::from_bits(LOCK_LED_STATES.load(core::sync::atomic::Ordering::Relaxed))
 }
 
@@ -88,11 +110,13 @@ impl CapsWordState {
             timer: Instant::now(),
             shift_current: false,
         };
+        publish_event(CapsWordEvent::new(true));
     }
 
     /// Deactivate Caps Word
     fn deactivate(&mut self) {
         *self = CapsWordState::Deactivated;
+        publish_event(CapsWordEvent::new(false));
     }
 
     /// Toggle Caps Word
@@ -103,6 +127,22 @@ impl CapsWordState {
         }
     }
 
+    /// Time Caps Word times out at, if it's active
+    fn deadline(&self) -> Option<Instant> {
+        if let CapsWordState::Activated { timer, .. } = self {
+            Some(*timer + Self::TIMEOUT)
+        } else {
+            None
+        }
+    }
+
+    /// Deactivate Caps Word if it timed out, so that its indicator goes off
+    fn check_timeout(&mut self) {
+        if self.deadline().is_some_and(|deadline| Instant::now() >= deadline) {
+            self.deactivate();
+        }
+    }
+
     /// Return whether Caps Word is active (and has not timed out)
     fn is_active(&self) -> bool {
         if let CapsWordState::Activated { timer, .. } = self {
@@ -141,6 +181,9 @@ impl Runnable for Keyboard<'_> {
     /// The report is sent using `send_report`.
     async fn run(&mut self) -> ! {
         loop {
+            #[cfg(feature = "introspection")]
+            crate::introspection::update(self.runtime_state());
+
             // TODO: Now the unprocessed_events is only used in one-shot keys and clear peer key.
             // Maybe it can be removed in the future?
             if !self.unprocessed_events.is_empty() {
@@ -152,12 +195,17 @@ impl Runnable for Keyboard<'_> {
                 // Process buffered held key
                 self.process_buffered_key(key).await
             } else {
-                // If mouse repeat is pending, race subscriber against deadline
-                let event = if let Some(deadline) = self.mouse.next_deadline() {
+                // If mouse repeat or the Caps Word timeout is pending, race subscriber against deadline
+                let deadline = match (self.mouse.next_deadline(), self.caps_word.deadline()) {
+                    (Some(mouse), Some(caps_word)) => Some(mouse.min(caps_word)),
+                    (mouse, caps_word) => mouse.or(caps_word),
+                };
+                let event = if let Some(deadline) = deadline {
                     match with_deadline(deadline, self.keyboard_event_subscriber.next_message_pure()).await {
                         Ok(event) => event,
                         Err(_) => {
-                            // Repeat deadline expired, fire repeat
+                            // Deadline expired, fire the repeat or end Caps Word, whichever is due
+                            self.caps_word.check_timeout();
                             self.fire_mouse_repeat().await;
                             continue;
                         }
@@ -196,6 +244,12 @@ pub struct Keyboard<'a> {
     /// It's used in tap-hold prior-idle-time check.
     last_press_time: Instant,
 
+    /// Position of the last pressed key, used in retro tap
+    last_pressed_pos: Option<KeyboardEventPos>,
+
+    /// Position and release time of the last tap-hold key resolved as tap, used in quick tap
+    last_tap: Option<(KeyboardEventPos, Instant)>,
+
     /// stores the last KeyCode executed, to be repeated if the repeat key os pressed
     /// Used in repeat-key
     last_key_code: KeyCode,
@@ -226,6 +280,12 @@ pub struct Keyboard<'a> {
     /// The held keys for the keyboard hid report, except the modifiers
     held_keycodes: [HidKeyCode; 6],
 
+    /// Keys held beyond the 6 of `held_keycodes`, only kept with the NKRO interface
+    overflow_keys: NkroKeyboardReport,
+
+    /// Whether the last NKRO report had any key held
+    overflow_reported: bool,
+
     /// Registered key position.
     /// This is still needed besides `held_keycodes` because multiple keys with same keycode can be registered.
     registered_keys: [Option<KeyboardEvent>; 6],
@@ -240,8 +300,12 @@ pub struct Keyboard<'a> {
     system_control_report: SystemControlReport,
 
     /// Used for temporarily disabling combos
+    #[cfg(feature = "combo")]
     combo_on: bool,
 
+    /// Whether the Apple Fn key is held, reported in the reserved byte of the keyboard report
+    apple_fn_held: bool,
+
     /// Plover HID stenography chord accumulator
     #[cfg(feature = "steno")]
     steno: crate::keyboard::steno::StenoChord,
@@ -257,6 +321,8 @@ impl<'a> Keyboard<'a> {
             keymap,
             keyboard_event_subscriber: KeyboardEvent::subscriber(),
             last_press_time: Instant::now(),
+            last_pressed_pos: None,
+            last_tap: None,
             osl_state: OneShotState::default(),
             osm_state: OneShotState::default(),
             caps_word: CapsWordState::default(),
@@ -270,11 +336,15 @@ impl<'a> Keyboard<'a> {
             registered_keys: [None; 6],
             held_modifiers: ModifierCombination::default(),
             held_keycodes: [HidKeyCode::No; 6],
+            overflow_keys: NkroKeyboardReport::default(),
+            overflow_reported: false,
             mouse: MouseState::new(),
             media_report: MediaKeyboardReport { usage_id: 0 },
             system_control_report: SystemControlReport { usage_id: 0 },
             last_key_code: KeyCode::Hid(HidKeyCode::No),
+            #[cfg(feature = "combo")]
             combo_on: true,
+            apple_fn_held: false,
             #[cfg(feature = "steno")]
             steno: crate::keyboard::steno::StenoChord::new(),
             #[cfg(feature = "passkey_entry")]
@@ -282,6 +352,41 @@ impl<'a> Keyboard<'a> {
         }
     }
 
+    /// Snapshot of the keyboard state, see [`crate::introspection`]
+    #[cfg(feature = "introspection")]
+    pub(crate) fn runtime_state(&self) -> crate::introspection::RuntimeState {
+        let mut state = crate::introspection::RuntimeState::new();
+        state.layers = self.keymap.layer_mask();
+        state.default_layer = self.keymap.get_default_layer();
+        state.top_layer = self.keymap.get_activated_layer();
+        for key in self.held_buffer.keys.iter() {
+            match key.state {
+                #[cfg(feature = "combo")]
+                KeyState::WaitingCombo => state.pending_combo += 1,
+                KeyState::Pressed(_) | KeyState::Released(_) => state.pending_tap_hold += 1,
+                _ => state.holding += 1,
+            }
+        }
+        state.oneshot_layer = self.osl_state.value().copied();
+        state.oneshot_modifiers = self.osm_state.value().map_or(0, |m| m.into_bits());
+        state.held_modifiers = self.held_modifiers.into_bits();
+        state.caps_word = self.caps_word.is_active();
+        #[cfg(feature = "combo")]
+        {
+            state.combos_enabled = self.combo_on;
+            self.keymap.with_combos(|combos| {
+                for combo in combos.iter().flatten() {
+                    if combo.is_triggered() {
+                        state.combos_triggered += 1;
+                    } else if combo.keys_pressed() > 0 {
+                        state.combos_in_progress += 1;
+                    }
+                }
+            });
+        }
+        state
+    }
+
     /// Send a keyboard report to the host.
     async fn send_report(&self, report: Report) {
         // Do not report keypresses to Host in passkey mode
@@ -290,6 +395,15 @@ impl<'a> Keyboard<'a> {
             return;
         }
 
+        #[cfg(feature = "latency")]
+        if matches!(report, Report::KeyboardReport(_)) {
+            crate::latency::record_report_queued();
+        }
+
+        // Waiting for room in the report queue is up to the host, not a stalled keyboard
+        #[cfg(feature = "watchdog")]
+        crate::watchdog::wait_on_host(crate::watchdog::CoreTask::Keyboard, send_hid_report(report)).await;
+        #[cfg(not(feature = "watchdog"))]
         send_hid_report(report).await;
     }
 
@@ -297,11 +411,12 @@ impl<'a> Keyboard<'a> {
     /// which is either a combo component that is waiting for other combo keys,
     /// or a morse key that is in the pressed or released state.
     pub fn next_buffered_key(&mut self) -> Option<HeldKey> {
-        self.held_buffer.next_timeout(|k| {
-            matches!(
-                k.state,
-                KeyState::Released(_) | KeyState::EarlyFired(_) | KeyState::WaitingCombo
-            ) || (matches!(k.state, KeyState::Pressed(_)) && k.action.is_morse())
+        self.held_buffer.next_timeout(|k| match k.state {
+            #[cfg(feature = "combo")]
+            KeyState::WaitingCombo => true,
+            KeyState::Released(_) | KeyState::EarlyFired(_) => true,
+            KeyState::Pressed(_) => k.action.is_morse(),
+            _ => false,
         })
     }
 
@@ -314,6 +429,7 @@ impl<'a> Keyboard<'a> {
             key.event, key.state
         );
         match key.state {
+            #[cfg(feature = "combo")]
             KeyState::WaitingCombo => {
                 debug!(
                     "[Combo] Waiting combo, timeout in: {:?}ms",
@@ -352,6 +468,11 @@ impl<'a> Keyboard<'a> {
 
     /// Process key changes at (row, col)
     pub async fn process_inner(&mut self, event: KeyboardEvent) {
+        #[cfg(feature = "watchdog")]
+        let _busy = crate::watchdog::busy(crate::watchdog::CoreTask::Keyboard);
+        #[cfg(feature = "latency")]
+        crate::latency::record_processing(event.pos);
+
         // Check for mode transitions (e.g., entering/exiting passkey entry)
         #[cfg(feature = "passkey_entry")]
         self.passkey_entry_state.check_mode_transition();
@@ -363,12 +484,28 @@ impl<'a> Keyboard<'a> {
         #[cfg(all(feature = "split", feature = "_ble"))]
         update_activity_time();
 
+        // Any key press wakes a suspended USB host, even if the key doesn't send a report,
+        // e.g. a layer key
+        #[cfg(not(feature = "_no_usb"))]
+        if event.pressed && crate::state::active_transport() == Some(rmk_types::connection::ConnectionType::Usb) {
+            crate::usb::request_remote_wakeup();
+        }
+
         // Capture the event time once per event and thread it through.
         let event_time = Instant::now();
 
+        if event.pressed {
+            // Quick tap only follows a tap of the same key
+            if self.last_tap.is_some_and(|(pos, _)| pos != event.pos) {
+                self.last_tap = None;
+            }
+            self.last_pressed_pos = Some(event.pos);
+        }
+
         // Process key
         let key_action = &self.keymap.get_action_with_layer_cache(event);
 
+        #[cfg(feature = "combo")]
         if self.combo_on {
             if let (Some(key_action), is_combo) = self.process_combo(key_action, event, event_time).await {
                 self.process_key_action(&key_action, event, is_combo, event_time).await
@@ -376,6 +513,11 @@ impl<'a> Keyboard<'a> {
         } else {
             self.process_key_action(key_action, event, false, event_time).await
         }
+        #[cfg(not(feature = "combo"))]
+        self.process_key_action(key_action, event, false, event_time).await;
+
+        #[cfg(feature = "latency")]
+        crate::latency::record_processed();
     }
 
     async fn process_key_action(
@@ -414,7 +556,7 @@ impl<'a> Keyboard<'a> {
             KeyBehaviorDecision::Buffer => {
                 debug!("Current key is buffered");
                 let timeout_time = if key_action.is_morse() {
-                    event_time + Self::morse_timeout(self.keymap, key_action, true)
+                    event_time + Self::morse_timeout_at(self.keymap, key_action, event.pos, true)
                 } else {
                     event_time
                 };
@@ -446,7 +588,7 @@ impl<'a> Keyboard<'a> {
                 // first via find_pos_mut, skip the release report, and leave the key stuck down.
                 self.held_buffer.remove_if(|k| k.event.pos == event.pos);
                 let now = Instant::now();
-                let time_out = now + Self::morse_timeout(self.keymap, key_action, true);
+                let time_out = now + Self::morse_timeout_at(self.keymap, key_action, event.pos, true);
                 self.held_buffer.push(HeldKey::new(
                     event,
                     *key_action,
@@ -700,7 +842,7 @@ impl<'a> Keyboard<'a> {
 
                 // The remaining keys are not same as the current key, check only morse keys
                 if held_key.event.pos != event.pos && held_key.action.is_morse() {
-                    let mode = Self::tap_hold_mode(self.keymap, &held_key.action);
+                    let mode = Self::tap_hold_mode_at(self.keymap, &held_key.action, held_key.event.pos);
 
                     if event.pressed {
                         // The current key is being pressed
@@ -732,7 +874,11 @@ impl<'a> Keyboard<'a> {
                                 // Normal mode: resolve a same-hand HRM as tap on press when
                                 // unilateral_tap is enabled, so the roll fires in the correct
                                 // order (HRM tap first, then the new key).
-                                let unilateral_tap = Self::is_unilateral_tap_enabled(self.keymap, &held_key.action);
+                                let unilateral_tap = Self::is_unilateral_tap_enabled_at(
+                                    self.keymap,
+                                    &held_key.action,
+                                    held_key.event.pos,
+                                );
                                 if unilateral_tap
                                     && matches!(held_key.state, KeyState::Pressed(_))
                                     && let KeyboardEventPos::Key(pos1) = held_key.event.pos
@@ -752,7 +898,8 @@ impl<'a> Keyboard<'a> {
                             }
                         }
                     } else {
-                        let unilateral_tap = Self::is_unilateral_tap_enabled(self.keymap, &held_key.action);
+                        let unilateral_tap =
+                            Self::is_unilateral_tap_enabled_at(self.keymap, &held_key.action, held_key.event.pos);
 
                         // 1. Check unilateral tap of held key
                         // Note: `decision for current key == Release` means that current held key is pressed AFTER the current releasing key,
@@ -804,6 +951,8 @@ impl<'a> Keyboard<'a> {
 
         #[cfg(feature = "_ble")]
         LAST_KEY_TIMESTAMP.signal(Instant::now().as_secs() as u32);
+        #[cfg(feature = "storage")]
+        LAST_KEY_ACTION_MS.store(Instant::now().as_millis() as u32, core::sync::atomic::Ordering::Relaxed);
 
         if !key_action.is_morse() {
             match key_action {
@@ -967,6 +1116,7 @@ impl<'a> Keyboard<'a> {
     /// - `key_action`: The action of the key that triggered this function
     /// - `event`: The keyboard event. When pressing (interrupting), trigger any delayed combo.
     ///   When releasing, only trigger combos that contain the key_action.
+    #[cfg(feature = "combo")]
     async fn trigger_delayed_combo(&mut self, key_action: &KeyAction, event: KeyboardEvent) {
         // First, find the delayed combo and trigger it
         let triggered_combo = self.keymap.with_combos_mut(|combos| {
@@ -1001,6 +1151,7 @@ impl<'a> Keyboard<'a> {
 
             let mut new_event = event;
             new_event.pressed = true;
+            publish_event(ComboTriggeredEvent::new(action));
             self.process_key_action(&action, new_event, true, Instant::now()).await;
             debug!("[Combo] {:?} triggered", action);
             embassy_time::Timer::after_millis(20).await;
@@ -1010,6 +1161,7 @@ impl<'a> Keyboard<'a> {
     }
 
     // Reset combos that contain a key_action but not triggered yet
+    #[cfg(feature = "combo")]
     fn reset_combo(&mut self, key_action: &KeyAction) {
         // Reset other sub-combo states
         self.keymap.with_combos_mut(|combos| {
@@ -1025,6 +1177,7 @@ impl<'a> Keyboard<'a> {
     /// Check combo before process keys.
     ///
     /// This function returns key action after processing combo, and a boolean indicates that if current returned key action is a combo output
+    #[cfg(feature = "combo")]
     async fn process_combo(
         &mut self,
         key_action: &KeyAction,
@@ -1112,6 +1265,7 @@ impl<'a> Keyboard<'a> {
 
             if let Some(next_action) = next_action {
                 debug!("[Combo] {:?} triggered", next_action);
+                publish_event(ComboTriggeredEvent::new(next_action));
                 self.held_buffer
                     .keys
                     .retain(|item| item.state != KeyState::WaitingCombo);
@@ -1167,6 +1321,7 @@ impl<'a> Keyboard<'a> {
     }
 
     // Dispatch combo keys buffered in the held buffer when the combo isn't being triggered.
+    #[cfg(feature = "combo")]
     async fn dispatch_combos(&mut self, key_action: &KeyAction, event: KeyboardEvent) {
         self.trigger_delayed_combo(key_action, event).await;
 
@@ -1257,7 +1412,10 @@ impl<'a> Keyboard<'a> {
                 self.send_keyboard_report_with_resolved_modifiers(event.pressed).await;
                 self.update_osl(event);
             }
+            #[cfg(feature = "macros")]
             Action::TriggerMacro(macro_idx) => self.execute_macro(macro_idx, event).await,
+            #[cfg(not(feature = "macros"))]
+            Action::TriggerMacro(macro_idx) => warn!("Macro {} needs the `macros` feature", macro_idx),
             Action::KeyWithModifier(key_code, modifiers) => {
                 if event.pressed {
                     // These modifiers will be combined into the hid report, so
@@ -1308,6 +1466,14 @@ impl<'a> Keyboard<'a> {
                 self.process_action_layer_switch(2, event);
                 self.keymap.update_fn_layer_state();
             }
+            Action::ProfileSwitch(profile) => {
+                #[cfg(all(feature = "storage", feature = "host"))]
+                if event.pressed {
+                    crate::storage::switch_keymap_profile(profile, self.keymap).await;
+                }
+                #[cfg(not(all(feature = "storage", feature = "host")))]
+                warn!("Keymap profile {} needs the `storage` and `host` features", profile);
+            }
             #[cfg(feature = "steno")]
             Action::Steno(key) => {
                 if let Some(report) = self.steno.on_event(key, event.pressed) {
@@ -1407,6 +1573,16 @@ impl<'a> Keyboard<'a> {
             result |= ModifierCombination::new().with_left_shift(true);
         }
 
+        // Ctrl and GUI are swapped for Apple hosts, so the same layout works on every OS
+        if self.keymap.swap_ctrl_gui_on_mac() && matches!(crate::host_os::host_os(), HostOs::MacOs | HostOs::Ios) {
+            let ctrl_gui = result;
+            result = result
+                .with_left_ctrl(ctrl_gui.left_gui())
+                .with_left_gui(ctrl_gui.left_ctrl())
+                .with_right_ctrl(ctrl_gui.right_gui())
+                .with_right_gui(ctrl_gui.right_ctrl());
+        }
+
         result
     }
 
@@ -1427,6 +1603,12 @@ impl<'a> Keyboard<'a> {
                         info!("[passkey] Cancelled");
                         PASSKEY_RESPONSE.signal(None);
                     }
+                    PasskeyAction::DigitAdded(_) | PasskeyAction::Backspaced => {
+                        publish_event(crate::event::PasskeyEntryEvent {
+                            active: true,
+                            digits: self.passkey_entry_state.digit_count() as u8,
+                        });
+                    }
                     _ => {
                         // Ignore other states
                     }
@@ -1461,6 +1643,10 @@ impl<'a> Keyboard<'a> {
                 let key = self.last_key_code;
                 self.process_action_key(key, event).await;
             }
+            SpecialKey::AppleFn => {
+                self.apple_fn_held = event.pressed;
+                self.send_keyboard_report_with_resolved_modifiers(event.pressed).await;
+            }
             _ => warn!("SpecialKey variant not supported: {:?}", key),
         };
     }
@@ -1473,21 +1659,88 @@ impl<'a> Keyboard<'a> {
                     self.caps_word.toggle();
                 };
             }
+            #[cfg(feature = "combo")]
             KeyboardAction::ComboOn => self.combo_on = true,
+            #[cfg(feature = "combo")]
             KeyboardAction::ComboOff => self.combo_on = false,
+            #[cfg(feature = "combo")]
             KeyboardAction::ComboToggle => self.combo_on = !self.combo_on,
             KeyboardAction::Bootloader => {
                 // When releasing the key, process the boot action
                 if !event.pressed {
+                    boot::flush_storage().await;
                     boot::jump_to_bootloader();
                 }
             }
             KeyboardAction::Reboot => {
                 // When releasing the key, process the boot action
                 if !event.pressed {
+                    boot::flush_storage().await;
                     boot::reboot_keyboard();
                 }
             }
+            // Handled by `AudioProcessor`, which subscribes to `ActionEvent`
+            #[cfg(feature = "audio")]
+            KeyboardAction::AudioOn
+            | KeyboardAction::AudioOff
+            | KeyboardAction::AudioToggle
+            | KeyboardAction::ClickyToggle => {}
+            // Handled by `HapticProcessor`, which subscribes to `ActionEvent`
+            #[cfg(feature = "haptic")]
+            KeyboardAction::HapticOn | KeyboardAction::HapticOff | KeyboardAction::HapticToggle => {}
+            #[cfg(feature = "ambient_light")]
+            KeyboardAction::AutoBrightnessToggle => {
+                if event.pressed {
+                    crate::ambient_light::set_auto_brightness(!crate::ambient_light::auto_brightness_enabled());
+                    #[cfg(feature = "storage")]
+                    crate::storage::save_runtime_state().await;
+                }
+            }
+            #[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+            KeyboardAction::OutputAuto | KeyboardAction::OutputUsb | KeyboardAction::OutputBluetooth => {
+                if event.pressed {
+                    let output = match keyboard_control {
+                        KeyboardAction::OutputUsb => Some(rmk_types::connection::ConnectionType::Usb),
+                        KeyboardAction::OutputBluetooth => Some(rmk_types::connection::ConnectionType::Ble),
+                        _ => None,
+                    };
+                    crate::state::select_output(output).await;
+                    #[cfg(feature = "storage")]
+                    crate::storage::save_runtime_state().await;
+                }
+            }
+            #[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+            KeyboardAction::OutputMirrorToggle => {
+                if event.pressed {
+                    crate::state::toggle_mirror();
+                    #[cfg(feature = "storage")]
+                    crate::storage::save_runtime_state().await;
+                }
+            }
+
+            KeyboardAction::Diagnostics => {
+                // Type the report once the key is released, so it isn't typed with the key
+                if !event.pressed {
+                    self.type_status_report(event).await;
+                }
+            }
+            KeyboardAction::HostOsAuto
+            | KeyboardAction::HostOsLinux
+            | KeyboardAction::HostOsWindows
+            | KeyboardAction::HostOsMacOs
+            | KeyboardAction::HostOsIos => {
+                if event.pressed {
+                    crate::host_os::set_host_os_override(match keyboard_control {
+                        KeyboardAction::HostOsLinux => Some(HostOs::Linux),
+                        KeyboardAction::HostOsWindows => Some(HostOs::Windows),
+                        KeyboardAction::HostOsMacOs => Some(HostOs::MacOs),
+                        KeyboardAction::HostOsIos => Some(HostOs::Ios),
+                        _ => None,
+                    });
+                    #[cfg(feature = "storage")]
+                    crate::storage::save_runtime_state().await;
+                }
+            }
 
             _ => warn!("KeyboardAction: {:?} is not supported yet", keyboard_control),
         }
@@ -1648,8 +1901,8 @@ impl<'a> Keyboard<'a> {
                 }
             } else {
                 // Other user keys are processed when released.
-                // Slots 0..NUM_BLE_PROFILE select a profile directly; the next four are
-                // fixed actions stacked on top.
+                // Slots 0..NUM_BLE_PROFILE select a profile directly; the next six are
+                // fixed actions stacked on top (the fifth, clear peer, is handled on press).
                 if id < NUM_BLE_PROFILE as u8 {
                     info!("Switch to profile: {}", id);
                     BLE_PROFILE_CHANNEL.send(BleProfileAction::Switch(id)).await;
@@ -1667,11 +1920,15 @@ impl<'a> Keyboard<'a> {
                     // only meaningful when both transports exist in this build.
                     #[cfg(not(feature = "_no_usb"))]
                     crate::state::toggle_preferred().await;
+                } else if id == NUM_BLE_PROFILE as u8 + 5 {
+                    // Clear bonds on all profiles
+                    BLE_PROFILE_CHANNEL.send(BleProfileAction::ClearAllBonds).await;
                 }
             }
         }
     }
 
+    #[cfg(feature = "macros")]
     async fn execute_macro(&mut self, macro_idx: u8, event: KeyboardEvent) {
         // Execute the macro only when releasing the key
         if event.pressed {
@@ -1705,24 +1962,7 @@ impl<'a> Keyboard<'a> {
                         self.unregister_key(k, event);
                         self.send_keyboard_report_with_resolved_modifiers(false).await;
                     }
-                    MacroOperation::Text(k, is_cap) => {
-                        self.macro_texting = true;
-                        self.macro_caps = is_cap;
-                        if is_cap {
-                            self.send_keyboard_report_with_resolved_modifiers(true).await;
-                            embassy_time::Timer::after_millis(12).await;
-                        }
-                        self.register_keycode(k, event);
-                        self.send_keyboard_report_with_resolved_modifiers(true).await;
-                        embassy_time::Timer::after_millis(12).await;
-                        self.unregister_keycode(k, event);
-                        self.send_keyboard_report_with_resolved_modifiers(false).await;
-                        if is_cap {
-                            self.macro_caps = false;
-                            embassy_time::Timer::after_millis(12).await;
-                            self.send_keyboard_report_with_resolved_modifiers(false).await;
-                        }
-                    }
+                    MacroOperation::Text(k, is_cap) => self.type_text_key(k, is_cap, event).await,
                     MacroOperation::Delay(t) => {
                         embassy_time::Timer::after_millis(t as u64).await;
                     }
@@ -1747,6 +1987,40 @@ impl<'a> Keyboard<'a> {
         }
     }
 
+    /// Tap `k` for a text, with Shift if `is_cap`, ignoring the held modifiers.
+    ///
+    /// The held modifiers stay ignored until `macro_texting` is cleared and a report is sent.
+    async fn type_text_key(&mut self, k: HidKeyCode, is_cap: bool, event: KeyboardEvent) {
+        self.macro_texting = true;
+        self.macro_caps = is_cap;
+        if is_cap {
+            self.send_keyboard_report_with_resolved_modifiers(true).await;
+            embassy_time::Timer::after_millis(12).await;
+        }
+        self.register_keycode(k, event);
+        self.send_keyboard_report_with_resolved_modifiers(true).await;
+        embassy_time::Timer::after_millis(12).await;
+        self.unregister_keycode(k, event);
+        self.send_keyboard_report_with_resolved_modifiers(false).await;
+        if is_cap {
+            self.macro_caps = false;
+            embassy_time::Timer::after_millis(12).await;
+            self.send_keyboard_report_with_resolved_modifiers(false).await;
+        }
+    }
+
+    /// Type the status report of [`crate::diagnostics`].
+    async fn type_status_report(&mut self, event: KeyboardEvent) {
+        for c in crate::diagnostics::status_report().bytes() {
+            let (k, is_cap) = rmk_types::keycode::from_ascii(c);
+            self.type_text_key(k, is_cap, event).await;
+            embassy_time::Timer::after_millis(1).await;
+        }
+        // Restore the held modifiers, like at the end of a text macro
+        self.macro_texting = false;
+        self.send_keyboard_report_with_resolved_modifiers(false).await;
+    }
+
     pub(crate) async fn send_keyboard_report_with_resolved_modifiers(&mut self, pressed: bool) {
         // all modifier related effects are combined here to be sent with the hid report:
         let modifiers = self.resolve_modifiers(pressed);
@@ -1756,11 +2030,16 @@ impl<'a> Keyboard<'a> {
         );
         self.send_report(Report::KeyboardReport(KeyboardReport {
             modifier: modifiers.into_bits(),
-            reserved: 0,
+            reserved: self.apple_fn_held as u8,
             leds: LOCK_LED_STATES.load(core::sync::atomic::Ordering::Relaxed),
             keycodes: self.held_keycodes.map(|k| k as u8),
         }))
         .await;
+        // Also send the last change of the keys beyond the 6 of the keyboard report
+        if !self.overflow_keys.is_empty() || self.overflow_reported {
+            self.overflow_reported = !self.overflow_keys.is_empty();
+            self.send_report(Report::NkroKeyboardReport(self.overflow_keys)).await;
+        }
 
         // Yield once after sending the report to channel
         yield_now().await;
@@ -1827,6 +2106,8 @@ impl<'a> Keyboard<'a> {
             if let Some(index) = self.held_keycodes.iter().position(|&k| k == HidKeyCode::No) {
                 self.held_keycodes[index] = key;
                 self.registered_keys[index] = Some(event);
+            } else if NKRO_ENABLED.load(core::sync::atomic::Ordering::Relaxed) {
+                self.overflow_keys.press(key as u8);
             }
         }
     }
@@ -1853,6 +2134,8 @@ impl<'a> Keyboard<'a> {
         if let Some(index) = slot {
             self.held_keycodes[index] = HidKeyCode::No;
             self.registered_keys[index] = None;
+        } else {
+            self.overflow_keys.release(key as u8);
         }
     }
 
@@ -1948,7 +2231,7 @@ mod test {
     use super::*;
     use crate::config::{BehaviorConfig, ForksConfig, PositionalConfig};
     use crate::event::{KeyPos, KeyboardEvent, KeyboardEventPos};
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
     use crate::{a, k, layer, mo, th, thp};
 
     // Init logger for tests
@@ -2011,6 +2294,27 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_caps_word_timeout_publishes_deactivation() {
+        use crate::event::EventSubscriber;
+
+        let main = async {
+            let mut caps_word = CapsWordState::default();
+            caps_word.activate();
+            let mut sub = CapsWordEvent::subscriber();
+
+            caps_word.check_timeout();
+            assert!(caps_word.is_active());
+
+            embassy_time::MockDriver::get().advance(CapsWordState::TIMEOUT);
+            caps_word.check_timeout();
+            assert!(!caps_word.is_active());
+            assert_eq!(caps_word.deadline(), None);
+            assert_eq!(sub.next_event().await, CapsWordEvent(false));
+        };
+        block_on(main);
+    }
+
     #[test]
     fn test_register_key() {
         let main = async {
@@ -2021,6 +2325,37 @@ mod test {
         block_on(main);
     }
 
+    #[test]
+    fn test_keys_beyond_six_need_nkro() {
+        let main = async {
+            let mut keyboard = create_test_keyboard();
+            let keys = [
+                HidKeyCode::A,
+                HidKeyCode::B,
+                HidKeyCode::C,
+                HidKeyCode::D,
+                HidKeyCode::E,
+                HidKeyCode::F,
+            ];
+            for (col, key) in keys.into_iter().enumerate() {
+                keyboard.register_key(key, KeyboardEvent::key(1, col as u8, true));
+            }
+
+            // Dropped without the NKRO interface
+            keyboard.register_key(HidKeyCode::G, KeyboardEvent::key(1, 6, true));
+            assert!(keyboard.overflow_keys.is_empty());
+
+            NKRO_ENABLED.store(true, core::sync::atomic::Ordering::Relaxed);
+            keyboard.register_key(HidKeyCode::G, KeyboardEvent::key(1, 6, true));
+            assert!(keyboard.overflow_keys.is_pressed(HidKeyCode::G as u8));
+            assert!(!keyboard.held_keycodes.contains(&HidKeyCode::G));
+
+            keyboard.unregister_key(HidKeyCode::G, KeyboardEvent::key(1, 6, false));
+            assert!(keyboard.overflow_keys.is_empty());
+        };
+        block_on(main);
+    }
+
     #[test]
     fn test_basic_key_press_release() {
         let main = async {
@@ -2084,6 +2419,35 @@ mod test {
         block_on(main);
     }
 
+    #[test]
+    fn test_runtime_state() {
+        let main = async {
+            let mut keyboard = create_test_keyboard();
+            assert_eq!(keyboard.runtime_state().layers, 0);
+
+            // Tap-hold key waits for a decision
+            keyboard.process_inner(KeyboardEvent::key(2, 2, true)).await;
+            let state = keyboard.runtime_state();
+            assert_eq!((state.pending_tap_hold, state.holding), (1, 0));
+
+            // Resolved as hold after the timeout
+            force_timeout_first_hold(&mut keyboard).await;
+            let state = keyboard.runtime_state();
+            assert_eq!((state.pending_tap_hold, state.holding), (0, 1));
+            keyboard.process_inner(KeyboardEvent::key(2, 2, false)).await;
+            assert_eq!(keyboard.runtime_state().holding, 0);
+
+            // MO(1)
+            keyboard.process_inner(KeyboardEvent::key(4, 9, true)).await;
+            let state = keyboard.runtime_state();
+            assert_eq!(state.layers, 0b10);
+            assert_eq!(state.top_layer, 1);
+            assert_eq!(state.default_layer, 0);
+            assert!(state.combos_enabled);
+        };
+        block_on(main);
+    }
+
     #[test]
     fn test_repeat_key_single() {
         let main = async {
@@ -2336,6 +2700,41 @@ mod test {
 
         block_on(main);
     }
+
+    #[test]
+    fn test_swap_ctrl_gui_on_mac() {
+        let main = async {
+            let mut keyboard = create_test_keyboard_with_config(BehaviorConfig {
+                swap_ctrl_gui_on_mac: true,
+                ..BehaviorConfig::default()
+            });
+
+            // Press LCtrl, it's sent as is until the host is a Mac
+            keyboard.process_inner(KeyboardEvent::key(4, 0, true)).await;
+            assert_eq!(
+                keyboard.resolve_modifiers(true),
+                ModifierCombination::new().with_left_ctrl(true)
+            );
+
+            crate::host_os::set_host_os_override(Some(HostOs::MacOs));
+            assert_eq!(
+                keyboard.resolve_modifiers(true),
+                ModifierCombination::new().with_left_gui(true)
+            );
+
+            // Press RGui as well, it becomes RCtrl
+            keyboard.process_inner(KeyboardEvent::key(4, 12, true)).await;
+            assert_eq!(
+                keyboard.resolve_modifiers(true),
+                ModifierCombination::new().with_left_gui(true).with_right_ctrl(true)
+            );
+
+            crate::host_os::set_host_os_override(None);
+        };
+
+        block_on(main);
+    }
+
     #[test]
     fn test_fork_with_held_mouse_button() {
         let main = async {
@@ -1,9 +1,11 @@
+#[cfg(feature = "combo")]
 use rmk_types::action::KeyAction;
 use rmk_types::constants::COMBO_MAX_LENGTH;
 
 /// Combo config instantiated with firmware's combo Vec capacity.
 pub type ComboConfig = rmk_types::combo::Combo;
 
+#[cfg(feature = "combo")]
 use crate::event::KeyboardEvent;
 
 // Combo.state is a u16 bitmask, so combos are limited to 16 keys.
@@ -46,6 +48,7 @@ impl Combo {
 
     /// Update the combo's state when a key is pressed.
     /// Returns true if the combo is updated.
+    #[cfg(feature = "combo")]
     pub(crate) fn update(&mut self, key_action: &KeyAction, key_event: KeyboardEvent, active_layer: u8) -> bool {
         if !key_event.pressed || self.config.size() == 0 || self.is_triggered {
             // Ignore combo that without actions
@@ -76,6 +79,7 @@ impl Combo {
     ///
     /// Returns true iff this combo is triggered and `key_action` is one of its
     /// actions, i.e. the caller should swallow the press.
+    #[cfg(feature = "combo")]
     pub(crate) fn reassert_if_triggered(&mut self, key_action: &KeyAction) -> bool {
         if !self.is_triggered {
             return false;
@@ -89,6 +93,7 @@ impl Combo {
 
     /// Update the combo's state when a key is released
     /// When the combo is fully released from triggered state, this function returns true
+    #[cfg(feature = "combo")]
     pub(crate) fn update_released(&mut self, key_action: &KeyAction) -> bool {
         if let Some(i) = self.config.find_key_action_index(key_action) {
             self.state &= !(1 << i);
@@ -106,6 +111,7 @@ impl Combo {
     }
 
     /// Mark the combo as done, if all actions are satisfied
+    #[cfg(feature = "combo")]
     pub(crate) fn trigger(&mut self) -> KeyAction {
         if self.is_triggered() {
             return self.config.output;
@@ -122,25 +128,30 @@ impl Combo {
     }
 
     // Check if the combo is dispatched into key event
+    #[cfg(feature = "combo")]
     pub(crate) fn is_triggered(&self) -> bool {
         self.is_triggered
     }
 
     // Check if all keys of this combo are pressed, but it does not mean the combo key event is sent
+    #[cfg(feature = "combo")]
     pub(crate) fn is_all_pressed(&self) -> bool {
         let cnt = self.config.size();
         cnt > 0 && self.keys_pressed() == cnt as u32
     }
 
     // The size of the current combo
+    #[cfg(feature = "combo")]
     pub(crate) fn size(&self) -> usize {
         self.config.size()
     }
 
+    #[cfg(feature = "combo")]
     pub(crate) fn keys_pressed(&self) -> u32 {
         self.state.count_ones()
     }
 
+    #[cfg(feature = "combo")]
     pub(crate) fn reset(&mut self) {
         self.state = 0;
         self.is_triggered = false;
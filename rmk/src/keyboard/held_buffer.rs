@@ -91,6 +91,7 @@ impl HeldBuffer {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum KeyState {
     /// The current key is a component of a combo, and it's waiting for other combo components
+    #[cfg(feature = "combo")]
     WaitingCombo,
 
     /// After a press event is received.
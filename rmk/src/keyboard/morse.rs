@@ -1,8 +1,8 @@
 use embassy_time::{Duration, Instant};
 use rmk_types::action::{Action, KeyAction};
-use rmk_types::morse::{HOLD, MorseMode, MorsePattern, TAP};
+use rmk_types::morse::{HOLD, MorseMode, MorsePattern, MorseProfile, TAP};
 
-use crate::event::KeyboardEvent;
+use crate::event::{KeyboardEvent, KeyboardEventPos};
 use crate::keyboard::Keyboard;
 use crate::keyboard::held_buffer::{HeldKey, KeyState};
 use crate::keymap::KeyMap;
@@ -66,8 +66,31 @@ impl<'a> Keyboard<'a> {
 
         // Process the morse key
         if event.pressed {
+            // Quick tap: pressing a tap-hold key again right after tapping it holds the tap action
+            if let KeyAction::TapHold(tap_action, _, _) = key_action {
+                let quick_tap_ms = self.keymap.tap_hold_at(event.pos).quick_tap_ms;
+                if quick_tap_ms > 0
+                    && self.last_tap.is_some_and(|(pos, time)| {
+                        pos == event.pos && event_time.duration_since(time) < Duration::from_millis(quick_tap_ms as u64)
+                    })
+                {
+                    debug!("Quick tap, holding the tap action {:?}", tap_action);
+                    self.last_tap = None;
+                    self.held_buffer.remove_if(|k| k.event.pos == event.pos);
+                    self.process_key_action_normal(*tap_action, event).await;
+                    self.held_buffer.push(HeldKey::new(
+                        event,
+                        *key_action,
+                        KeyState::ProcessedButReleaseNotReportedYet(*tap_action),
+                        event_time,
+                        event_time,
+                    ));
+                    return;
+                }
+            }
+
             // Pressed, check the held buffer, update the tap state
-            let timeout_time = event_time + Self::morse_timeout(self.keymap, key_action, true);
+            let timeout_time = event_time + Self::morse_timeout_at(self.keymap, key_action, event.pos, true);
             match self.held_buffer.find_pos_mut(event.pos) {
                 Some(k) => {
                     // The current key is already in the buffer, update its state
@@ -134,6 +157,9 @@ impl<'a> Keyboard<'a> {
                         if let Some(action) = final_action {
                             debug!("released prediction {:?} -> {:?}", pattern, action);
                             // Reached the longest configured morse pattern, trigger the corresponding action immediately
+                            if matches!(k.action, KeyAction::TapHold(..)) && pattern == TAP {
+                                self.last_tap = Some((event.pos, released_time));
+                            }
                             self.held_buffer.remove(event.pos); // Remove the key from the held buffer, is like setting to an idle state
 
                             debug!(
@@ -153,7 +179,7 @@ impl<'a> Keyboard<'a> {
                             k.state = KeyState::Released(pattern);
                             // Use current release time for `IdleAfterTap` state
                             k.press_time = released_time; // Use release time as the "press_time"
-                            let timeout = Self::morse_timeout(self.keymap, &k.action, false);
+                            let timeout = Self::morse_timeout_at(self.keymap, &k.action, k.event.pos, false);
                             k.timeout_time = k.press_time + timeout;
 
                             // Fire the tap immediately if the hold continuation has the same action
@@ -180,7 +206,8 @@ impl<'a> Keyboard<'a> {
                         k.state = KeyState::Released(pattern);
                         // Use current release time for `IdleAfterTap` state
                         k.press_time = released_time; // Use release time as the "press_time"
-                        k.timeout_time = k.press_time + Self::morse_timeout(self.keymap, &k.action, false);
+                        k.timeout_time =
+                            k.press_time + Self::morse_timeout_at(self.keymap, &k.action, k.event.pos, false);
                     }
                     KeyState::ProcessedButReleaseNotReportedYet(action) => {
                         // Releasing a tap-hold action whose pressed HID report is already sent
@@ -189,6 +216,17 @@ impl<'a> Keyboard<'a> {
                         // Process the release action
                         debug!("[morse] Releasing morse key: {:?}", event);
                         self.process_key_action_normal(action, event).await;
+                        // Retro tap: a tap-hold key held alone sends its tap action on release
+                        if let KeyAction::TapHold(tap_action, _, _) = key_action
+                            && *tap_action != action
+                            && self.last_pressed_pos == Some(event.pos)
+                            && self.keymap.tap_hold_at(event.pos).retro_tap
+                        {
+                            debug!("Retro tap {:?}", tap_action);
+                            let mut press_event = event;
+                            press_event.pressed = true;
+                            self.process_key_action_tap(*tap_action, press_event).await;
+                        }
                     }
                     KeyState::FlowTapped(action) => {
                         // Flow-tap fired the tap action and is holding it down; release it now.
@@ -200,7 +238,7 @@ impl<'a> Keyboard<'a> {
                         // tap leaves no trace and the next press-and-hold resolves as a fresh hold.
                         if Self::action_from_pattern(self.keymap, key_action, TAP.followed_by_hold()) != Action::No {
                             let now = Instant::now();
-                            let timeout = Self::morse_timeout(self.keymap, key_action, false);
+                            let timeout = Self::morse_timeout_at(self.keymap, key_action, event.pos, false);
                             if let Some(k) = self.held_buffer.find_pos_mut(event.pos) {
                                 k.state = KeyState::EarlyFired(TAP);
                                 k.press_time = now;
@@ -258,94 +296,81 @@ impl<'a> Keyboard<'a> {
         }
     }
 
-    pub fn morse_timeout(keymap: &KeyMap, key_action: &KeyAction, hold_timeout_needed: bool) -> Duration {
-        // Check per-key profile config first
-        match key_action {
-            KeyAction::TapHold(_, _, profile) => {
-                let timeout = if hold_timeout_needed {
-                    profile.hold_timeout_ms()
-                } else {
-                    profile.gap_timeout_ms()
-                };
-
-                if let Some(timeout) = timeout {
-                    return Duration::from_millis(timeout as u64);
-                }
-            }
-            KeyAction::Morse(index) => {
-                if let Some(morse) = keymap.get_morse(*index as usize) {
-                    let timeout = if hold_timeout_needed {
-                        morse.profile.hold_timeout_ms()
-                    } else {
-                        morse.profile.gap_timeout_ms()
-                    };
-
-                    if let Some(timeout) = timeout {
-                        return Duration::from_millis(timeout as u64);
-                    }
-                }
-            }
-            _ => {}
-        }
+    /// Profile of a morse key: the profile of the action first, then the tap-hold configuration
+    /// at the key's position if it's given, and the global default profile
+    fn morse_profile(keymap: &KeyMap, key_action: &KeyAction, pos: Option<KeyboardEventPos>) -> MorseProfile {
+        let action_profile = match key_action {
+            KeyAction::TapHold(_, _, profile) => *profile,
+            KeyAction::Morse(index) => keymap
+                .get_morse(*index as usize)
+                .map(|morse| morse.profile)
+                .unwrap_or_default(),
+            _ => MorseProfile::const_default(),
+        };
+        let action_profile = match pos {
+            Some(pos) => action_profile.or(keymap.tap_hold_at(pos).profile),
+            None => action_profile,
+        };
+        action_profile.or(keymap.morse_default_profile())
+    }
 
-        // If no per-key config, use the global default profile
-        let default_profile = keymap.morse_default_profile();
+    fn profile_timeout(profile: MorseProfile, hold_timeout_needed: bool) -> Duration {
         let timeout = if hold_timeout_needed {
-            default_profile.hold_timeout_ms()
+            profile.hold_timeout_ms()
         } else {
-            default_profile.gap_timeout_ms()
+            profile.gap_timeout_ms()
         }
         .unwrap_or(250u16);
 
         Duration::from_millis(if timeout == 0 { 250u16 } else { timeout } as u64)
     }
 
+    /// Timeout of a morse key, without the tap-hold configuration of its position,
+    /// see [`Self::morse_timeout_at`]
+    pub fn morse_timeout(keymap: &KeyMap, key_action: &KeyAction, hold_timeout_needed: bool) -> Duration {
+        Self::profile_timeout(Self::morse_profile(keymap, key_action, None), hold_timeout_needed)
+    }
+
+    /// Timeout of the morse key at `pos`
+    pub fn morse_timeout_at(
+        keymap: &KeyMap,
+        key_action: &KeyAction,
+        pos: KeyboardEventPos,
+        hold_timeout_needed: bool,
+    ) -> Duration {
+        Self::profile_timeout(Self::morse_profile(keymap, key_action, Some(pos)), hold_timeout_needed)
+    }
+
     /// Decides and returns the morse mode
-    /// based on configuration for the given key action / key position
+    /// based on configuration for the given key action, see [`Self::tap_hold_mode_at`]
     pub fn tap_hold_mode(keymap: &KeyMap, key_action: &KeyAction) -> MorseMode {
-        // Check per-key profile config first
-        match key_action {
-            KeyAction::TapHold(_, _, profile) => {
-                if let Some(mode) = profile.mode() {
-                    return mode;
-                }
-            }
-            KeyAction::Morse(index) => {
-                if let Some(morse) = keymap.get_morse(*index as usize)
-                    && let Some(mode) = morse.profile.mode()
-                {
-                    return mode;
-                }
-            }
-            _ => {}
-        }
-
-        // If no per-key config, use the global default profile
-        keymap.morse_default_profile().mode().unwrap_or(MorseMode::Normal)
+        Self::morse_profile(keymap, key_action, None)
+            .mode()
+            .unwrap_or(MorseMode::Normal)
     }
 
     /// Decides and returns the morse mode
     /// based on configuration for the given key action / key position
+    pub fn tap_hold_mode_at(keymap: &KeyMap, key_action: &KeyAction, pos: KeyboardEventPos) -> MorseMode {
+        Self::morse_profile(keymap, key_action, Some(pos))
+            .mode()
+            .unwrap_or(MorseMode::Normal)
+    }
+
+    /// Decides and returns whether unilateral tap is enabled
+    /// based on configuration for the given key action, see [`Self::is_unilateral_tap_enabled_at`]
     pub fn is_unilateral_tap_enabled(keymap: &KeyMap, key_action: &KeyAction) -> bool {
-        // try to look for a per-key profile config
-        match key_action {
-            KeyAction::TapHold(_, _, profile) => {
-                if let Some(enabled) = profile.unilateral_tap() {
-                    return enabled;
-                }
-            }
-            KeyAction::Morse(index) => {
-                if let Some(morse) = keymap.get_morse(*index as usize)
-                    && let Some(enabled) = morse.profile.unilateral_tap()
-                {
-                    return enabled;
-                }
-            }
-            _ => {}
-        }
+        Self::morse_profile(keymap, key_action, None)
+            .unilateral_tap()
+            .unwrap_or(false)
+    }
 
-        // Use the global default
-        keymap.morse_default_profile().unilateral_tap().unwrap_or(false)
+    /// Decides and returns whether unilateral tap is enabled
+    /// based on configuration for the given key action / key position
+    pub fn is_unilateral_tap_enabled_at(keymap: &KeyMap, key_action: &KeyAction, pos: KeyboardEventPos) -> bool {
+        Self::morse_profile(keymap, key_action, Some(pos))
+            .unilateral_tap()
+            .unwrap_or(false)
     }
 
     /// Checks if the given pattern can fire its action early even though longer
@@ -31,6 +31,7 @@ pub enum MacroOperation {
 impl MacroOperation {
     /// Get the next macro operation starting from given index and offset (=position in the sequence)
     /// Return current macro operation and the next operations's offset
+    #[cfg(feature = "macros")]
     pub(crate) fn get_next_macro_operation(
         macro_sequences: &[u8],
         macro_start_idx: usize,
@@ -88,6 +89,7 @@ impl MacroOperation {
     }
 
     /// finds the start of a macro sequence by providing a guessed start index
+    #[cfg(feature = "macros")]
     pub(crate) fn get_macro_sequence_start(macro_sequences: &[u8], guessed_macro_start_idx: u8) -> Option<usize> {
         let mut idx = 0;
         // Find idx until the macro start of given index
@@ -11,10 +11,11 @@ use {
 };
 
 use crate::MACRO_SPACE_SIZE;
-use crate::config::{BehaviorConfig, Hand, MouseKeyConfig, OneShotModifiersConfig, PositionalConfig};
+use crate::config::{BehaviorConfig, Hand, KeyTapHold, MouseKeyConfig, OneShotModifiersConfig, PositionalConfig};
 use crate::event::{KeyboardEvent, KeyboardEventPos, LayerChangeEvent, publish_event};
 use crate::input_device::rotary_encoder::Direction;
 use crate::keyboard::combo::Combo;
+#[cfg(feature = "macros")]
 use crate::keyboard_macros::MacroOperation;
 #[cfg(feature = "host_security")]
 use crate::matrix::MatrixState;
@@ -100,6 +101,8 @@ struct KeyMapInner<'a> {
     behavior: &'a mut BehaviorConfig,
     /// Hand info: row * col (read-only)
     hand: &'a [Hand],
+    /// Tap-hold configuration of the keys: row * col (read-only)
+    tap_hold: &'a [KeyTapHold],
     /// Mouse button state
     mouse_buttons: u8,
     /// Matrix state for vial lock
@@ -115,9 +118,11 @@ impl KeyMapInner<'_> {
         layer * self.row * self.col + row * self.col + col
     }
 
+    /// `None` for an unknown encoder id, e.g. from a misconfigured split peripheral,
+    /// which would otherwise alias an encoder of the next layer.
     #[inline]
-    fn encoder_index(&self, layer: usize, id: usize) -> usize {
-        layer * self.num_encoder + id
+    fn encoder_index(&self, layer: usize, id: usize) -> Option<usize> {
+        (id < self.num_encoder).then(|| layer * self.num_encoder + id)
     }
 
     #[inline]
@@ -126,8 +131,8 @@ impl KeyMapInner<'_> {
     }
 
     #[inline]
-    fn encoder_cache_index(&self, id: usize, direction: usize) -> usize {
-        id * 2 + direction
+    fn encoder_cache_index(&self, id: usize, direction: usize) -> Option<usize> {
+        (id < self.num_encoder).then(|| id * 2 + direction)
     }
 }
 
@@ -164,8 +169,10 @@ impl KeyMapInner<'_> {
                 if let Some(encoders) = &self.encoders
                     && encoder_pos.direction != Direction::None
                 {
-                    let idx = self.encoder_index(layer_num, encoder_pos.id as usize);
-                    if let Some(encoder_action) = encoders.get(idx) {
+                    if let Some(encoder_action) = self
+                        .encoder_index(layer_num, encoder_pos.id as usize)
+                        .and_then(|idx| encoders.get(idx))
+                    {
                         return match encoder_pos.direction {
                             Direction::Clockwise => encoder_action.clockwise,
                             Direction::CounterClockwise => encoder_action.counter_clockwise,
@@ -189,7 +196,7 @@ impl KeyMapInner<'_> {
             KeyboardEventPos::RotaryEncoder(encoder_pos) => {
                 let idx = self.encoder_index(layer_num, encoder_pos.id as usize);
                 if let Some(encoders) = &mut self.encoders
-                    && let Some(encoder_action) = encoders.get_mut(idx)
+                    && let Some(encoder_action) = idx.and_then(|idx| encoders.get_mut(idx))
                 {
                     match encoder_pos.direction {
                         Direction::Clockwise => encoder_action.clockwise = action,
@@ -246,7 +253,7 @@ impl KeyMapInner<'_> {
             KeyboardEventPos::RotaryEncoder(encoder_pos) => {
                 if encoder_pos.direction != Direction::None {
                     let ci = self.encoder_cache_index(encoder_pos.id as usize, encoder_pos.direction as usize);
-                    if let Some(cache) = self.encoder_layer_cache.get_mut(ci) {
+                    if let Some(cache) = ci.and_then(|ci| self.encoder_layer_cache.get_mut(ci)) {
                         let layer = *cache;
                         *cache = self.behavior.default_layer;
                         return layer;
@@ -268,7 +275,7 @@ impl KeyMapInner<'_> {
             KeyboardEventPos::RotaryEncoder(encoder_pos) => {
                 if encoder_pos.direction != Direction::None {
                     let ci = self.encoder_cache_index(encoder_pos.id as usize, encoder_pos.direction as usize);
-                    if let Some(cache) = self.encoder_layer_cache.get_mut(ci) {
+                    if let Some(cache) = ci.and_then(|ci| self.encoder_layer_cache.get_mut(ci)) {
                         *cache = layer_num;
                     }
                 }
@@ -352,6 +359,7 @@ impl<'a> KeyMap<'a> {
         let layer_cache = data.layer_cache.as_mut_slice().as_flattened_mut();
         let encoder_layer_cache = data.encoder_layer_cache.as_mut_slice().as_flattened_mut();
         let hand = positional_config.hand.as_slice().as_flattened();
+        let tap_hold = positional_config.tap_hold.as_slice().as_flattened();
 
         KeyMap {
             inner: RefCell::new(KeyMapInner {
@@ -366,6 +374,7 @@ impl<'a> KeyMap<'a> {
                 encoder_layer_cache,
                 behavior,
                 hand,
+                tap_hold,
                 mouse_buttons: 0,
                 #[cfg(feature = "host_security")]
                 matrix_state: MatrixState::new(ROW, COL),
@@ -404,7 +413,7 @@ impl<'a> KeyMap<'a> {
         if let Some(storage) = storage
             && {
                 Ok(())
-                    .and(storage.read_keymap(data, behavior).await)
+                    .and(storage.read_keymap(data).await)
                     .and(storage.read_behavior_config(behavior).await)
                     .and(
                         storage
@@ -478,6 +487,17 @@ impl<'a> KeyMap<'a> {
         self.inner.borrow().get_default_layer()
     }
 
+    /// Activated layers, bit `n` is layer `n`. Layers above 31 aren't included
+    pub(crate) fn layer_mask(&self) -> u32 {
+        let inner = self.inner.borrow();
+        inner
+            .layer_state
+            .iter()
+            .take(32)
+            .enumerate()
+            .fold(0, |mask, (i, &on)| mask | ((on as u32) << i))
+    }
+
     pub(crate) fn set_default_layer(&self, layer_num: u8) {
         self.inner.borrow_mut().set_default_layer(layer_num);
     }
@@ -502,6 +522,16 @@ impl<'a> KeyMap<'a> {
         }
     }
 
+    /// Tap-hold configuration of the key at `pos`, the default one for encoders
+    pub(crate) fn tap_hold_at(&self, pos: KeyboardEventPos) -> KeyTapHold {
+        let KeyboardEventPos::Key(key_pos) = pos else {
+            return KeyTapHold::default();
+        };
+        let inner = self.inner.borrow();
+        let idx = inner.cache_index(key_pos.row as usize, key_pos.col as usize);
+        inner.tap_hold.get(idx).copied().unwrap_or_default()
+    }
+
     // ── Behavior getters (borrow scoped inside each method) ──
 
     pub(crate) fn combo_timeout(&self) -> Duration {
@@ -544,6 +574,10 @@ impl<'a> KeyMap<'a> {
         self.inner.borrow().behavior.mouse_key
     }
 
+    pub(crate) fn swap_ctrl_gui_on_mac(&self) -> bool {
+        self.inner.borrow().behavior.swap_ctrl_gui_on_mac
+    }
+
     pub(crate) fn forks_is_empty(&self) -> bool {
         self.inner.borrow().behavior.fork.forks.is_empty()
     }
@@ -607,10 +641,12 @@ impl<'a> KeyMap<'a> {
 
     // ── Macros ──
 
+    #[cfg(feature = "macros")]
     pub(crate) fn get_macro_sequence_start(&self, idx: u8) -> Option<usize> {
         MacroOperation::get_macro_sequence_start(&self.inner.borrow().behavior.keyboard_macros.macro_sequences, idx)
     }
 
+    #[cfg(feature = "macros")]
     pub(crate) fn get_next_macro_operation(&self, start: usize, offset: usize) -> (MacroOperation, usize) {
         MacroOperation::get_next_macro_operation(
             &self.inner.borrow().behavior.keyboard_macros.macro_sequences,
@@ -652,14 +688,14 @@ impl<'a> KeyMap<'a> {
     pub(crate) fn get_encoder_action(&self, layer: usize, id: usize) -> Option<EncoderAction> {
         let inner = self.inner.borrow();
         inner.encoders.as_ref().and_then(|encoders| {
-            let idx = inner.encoder_index(layer, id);
+            let idx = inner.encoder_index(layer, id)?;
             encoders.get(idx).copied()
         })
     }
 
     pub(crate) fn set_encoder_clockwise(&self, layer: usize, id: usize, action: KeyAction) -> Option<EncoderAction> {
         let mut inner = self.inner.borrow_mut();
-        let idx = inner.encoder_index(layer, id);
+        let idx = inner.encoder_index(layer, id)?;
         if let Some(encoders) = &mut inner.encoders
             && let Some(encoder_action) = encoders.get_mut(idx)
         {
@@ -676,7 +712,7 @@ impl<'a> KeyMap<'a> {
         action: KeyAction,
     ) -> Option<EncoderAction> {
         let mut inner = self.inner.borrow_mut();
-        let idx = inner.encoder_index(layer, id);
+        let idx = inner.encoder_index(layer, id)?;
         if let Some(encoders) = &mut inner.encoders
             && let Some(encoder_action) = encoders.get_mut(idx)
         {
@@ -737,9 +773,13 @@ mod test {
     use rmk_types::fork::{Fork, StateBits};
     use rmk_types::modifier::ModifierCombination;
 
+    use super::{KeyMap, KeymapData};
+    use crate::config::{BehaviorConfig, PositionalConfig};
+    use crate::event::KeyboardEventPos;
+    use crate::input_device::rotary_encoder::Direction;
     use crate::keyboard::combo::{Combo, ComboConfig};
     use crate::keymap::fill_vec;
-    use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, k};
+    use crate::{COMBO_MAX_NUM, FORK_MAX_NUM, a, k};
 
     #[test]
     fn test_fill_vec() {
@@ -789,4 +829,22 @@ mod test {
 
         assert_eq!(forks.len(), FORK_MAX_NUM);
     }
+
+    #[test]
+    fn test_unknown_encoder_id() {
+        let encoder_map = [
+            [rmk_types::action::EncoderAction::new(k!(A), k!(B))],
+            [rmk_types::action::EncoderAction::new(k!(C), k!(D))],
+        ];
+        let data = Box::leak(Box::new(KeymapData::new_with_encoder([[[a!(No)]]; 2], encoder_map)));
+        let behavior = Box::leak(Box::new(BehaviorConfig::default()));
+        let positional = Box::leak(Box::new(PositionalConfig::default()));
+        let keymap = embassy_futures::block_on(KeyMap::new(data, behavior, positional));
+
+        assert!(keymap.get_encoder_action(0, 0).is_some());
+        // Encoder 1 doesn't exist, it must not resolve to encoder 0 of layer 1
+        assert!(keymap.get_encoder_action(0, 1).is_none());
+        let pos = KeyboardEventPos::rotary_encoder_pos(1, Direction::Clockwise);
+        assert_eq!(keymap.inner.borrow().get_action_at(pos, 0), a!(No));
+    }
 }
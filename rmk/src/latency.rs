@@ -0,0 +1,294 @@
+//! End-to-end latency of key presses and releases.
+//!
+//! With the `latency` feature, a key change is timestamped at every stage of the pipeline:
+//!
+//! 1. the debouncer sees the edge on the matrix,
+//! 2. the matrix sends the debounced event,
+//! 3. the keyboard starts processing it,
+//! 4. the keyboard report it causes is handed to the transport,
+//! 5. USB or BLE takes the report.
+//!
+//! The time spent in every stage of the last [`SAMPLES`] key changes is kept, read the
+//! percentiles with [`latency_stats`] or with the `latency` command of the USB console.
+//!
+//! Only key changes of the keyboard's own matrix which send a keyboard report while they're
+//! processed are sampled: keys of split peripherals, layer keys, and keys buffered for a
+//! tap-hold or combo decision aren't. ESB and custom transports don't report when they take a
+//! report, so their key changes are not sampled either.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::RawMutex;
+use crate::event::KeyboardEventPos;
+
+/// Number of key changes the percentiles are computed over
+pub const SAMPLES: usize = 64;
+/// Key changes which can be in the pipeline at once, the oldest is forgotten when it's full
+const IN_FLIGHT: usize = 8;
+
+/// Percentiles of the time spent in a stage of the pipeline, in µs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StageLatency {
+    /// Number of key changes the percentiles are computed over, at most [`SAMPLES`]
+    pub samples: u16,
+    pub p50_us: u32,
+    pub p90_us: u32,
+    pub p99_us: u32,
+    pub max_us: u32,
+}
+
+/// Latency of the last key changes, per stage of the pipeline
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LatencyStats {
+    /// From the edge on the matrix until the debouncer accepted it
+    pub debounce: StageLatency,
+    /// From the debounced event until the keyboard started processing it
+    pub queue: StageLatency,
+    /// From the start of the processing until the keyboard report was sent to the transport
+    pub processing: StageLatency,
+    /// From the keyboard report until USB or BLE took it
+    pub transport: StageLatency,
+    /// From the edge on the matrix until USB or BLE took the report
+    pub total: StageLatency,
+}
+
+/// Stage a key change in the pipeline has reached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    /// Edge seen by the debouncer, the position is the one of the debouncer
+    Edge,
+    Debounced(Instant),
+    Processing(Instant, Instant),
+    Queued(Instant, Instant, Instant),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct InFlight {
+    row: u8,
+    col: u8,
+    edge: Instant,
+    stage: Stage,
+}
+
+/// Last samples of a stage, in µs
+struct Samples {
+    buf: [u32; SAMPLES],
+    len: usize,
+    next: usize,
+}
+
+impl Samples {
+    const fn new() -> Self {
+        Self {
+            buf: [0; SAMPLES],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, from: Instant, to: Instant) {
+        self.buf[self.next] = (to - from).as_micros().min(u32::MAX as u64) as u32;
+        self.next = (self.next + 1) % SAMPLES;
+        self.len = (self.len + 1).min(SAMPLES);
+    }
+
+    fn stats(&self) -> StageLatency {
+        let mut sorted = self.buf;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        StageLatency {
+            samples: self.len as u16,
+            p50_us: percentile(sorted, 50),
+            p90_us: percentile(sorted, 90),
+            p99_us: percentile(sorted, 99),
+            max_us: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+struct Tracker {
+    in_flight: heapless::Vec<InFlight, IN_FLIGHT>,
+    debounce: Samples,
+    queue: Samples,
+    processing: Samples,
+    transport: Samples,
+    total: Samples,
+}
+
+static TRACKER: Mutex<RawMutex, RefCell<Tracker>> = Mutex::new(RefCell::new(Tracker {
+    in_flight: heapless::Vec::new(),
+    debounce: Samples::new(),
+    queue: Samples::new(),
+    processing: Samples::new(),
+    transport: Samples::new(),
+    total: Samples::new(),
+}));
+
+/// Latency of the last key changes.
+pub fn latency_stats() -> LatencyStats {
+    TRACKER.lock(|t| {
+        let t = t.borrow();
+        LatencyStats {
+            debounce: t.debounce.stats(),
+            queue: t.queue.stats(),
+            processing: t.processing.stats(),
+            transport: t.transport.stats(),
+            total: t.total.stats(),
+        }
+    })
+}
+
+/// Forget the samples, e.g. before measuring a change of the firmware.
+pub fn reset_latency_stats() {
+    TRACKER.lock(|t| {
+        let mut t = t.borrow_mut();
+        t.debounce = Samples::new();
+        t.queue = Samples::new();
+        t.processing = Samples::new();
+        t.transport = Samples::new();
+        t.total = Samples::new();
+    });
+}
+
+/// The debouncer saw the key at (`row_idx`, `col_idx`) of its matrix change.
+///
+/// A bounce restarts the debouncing, and the measure with it.
+pub(crate) fn record_edge(row_idx: usize, col_idx: usize) {
+    let now = Instant::now();
+    let (row, col) = (row_idx as u8, col_idx as u8);
+    TRACKER.lock(|t| {
+        let in_flight = &mut t.borrow_mut().in_flight;
+        in_flight.retain(|k| !(k.stage == Stage::Edge && k.row == row && k.col == col));
+        if in_flight.is_full() {
+            in_flight.remove(0);
+        }
+        let _ = in_flight.push(InFlight {
+            row,
+            col,
+            edge: now,
+            stage: Stage::Edge,
+        });
+    });
+}
+
+/// The matrix sends the debounced key change at (`row_idx`, `col_idx`) of the debouncer, as the
+/// event of the key at (`row`, `col`) of the keyboard.
+pub(crate) fn record_debounced(row_idx: usize, col_idx: usize, row: u8, col: u8) {
+    let now = Instant::now();
+    let (row_idx, col_idx) = (row_idx as u8, col_idx as u8);
+    TRACKER.lock(|t| {
+        let mut t = t.borrow_mut();
+        if let Some(k) = t
+            .in_flight
+            .iter_mut()
+            .find(|k| k.stage == Stage::Edge && k.row == row_idx && k.col == col_idx)
+        {
+            k.row = row;
+            k.col = col;
+            k.stage = Stage::Debounced(now);
+        }
+    });
+}
+
+/// The keyboard starts processing the event at `pos`.
+pub(crate) fn record_processing(pos: KeyboardEventPos) {
+    let KeyboardEventPos::Key(pos) = pos else {
+        return;
+    };
+    let now = Instant::now();
+    TRACKER.lock(|t| {
+        for k in t.borrow_mut().in_flight.iter_mut() {
+            if let Stage::Debounced(debounced) = k.stage
+                && k.row == pos.row
+                && k.col == pos.col
+            {
+                k.stage = Stage::Processing(debounced, now);
+                break;
+            }
+        }
+    });
+}
+
+/// The keyboard is done with the event: if it didn't send a keyboard report, it isn't sampled.
+pub(crate) fn record_processed() {
+    TRACKER.lock(|t| {
+        t.borrow_mut()
+            .in_flight
+            .retain(|k| !matches!(k.stage, Stage::Processing(..)));
+    });
+}
+
+/// The keyboard sends a keyboard report to the transport.
+pub(crate) fn record_report_queued() {
+    let now = Instant::now();
+    TRACKER.lock(|t| {
+        for k in t.borrow_mut().in_flight.iter_mut() {
+            if let Stage::Processing(debounced, processing) = k.stage {
+                k.stage = Stage::Queued(debounced, processing, now);
+            }
+        }
+    });
+}
+
+/// The transport took a keyboard report, which completes the samples of the key changes in it.
+pub(crate) fn record_submitted() {
+    let now = Instant::now();
+    TRACKER.lock(|t| {
+        let mut t = t.borrow_mut();
+        let t = &mut *t;
+        t.in_flight.retain(|k| {
+            let Stage::Queued(debounced, processing, queued) = k.stage else {
+                return true;
+            };
+            t.debounce.push(k.edge, debounced);
+            t.queue.push(debounced, processing);
+            t.processing.push(processing, queued);
+            t.transport.push(queued, now);
+            t.total.push(k.edge, now);
+            false
+        });
+    });
+}
+
+/// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[u32], p: usize) -> u32 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50), 0);
+        assert_eq!(percentile(&[7], 99), 7);
+        let sorted: [u32; 10] = core::array::from_fn(|i| i as u32 + 1);
+        assert_eq!(percentile(&sorted, 50), 5);
+        assert_eq!(percentile(&sorted, 90), 9);
+        assert_eq!(percentile(&sorted, 99), 10);
+    }
+
+    #[test]
+    fn test_samples_keep_the_last_ones() {
+        let mut samples = Samples::new();
+        let start = Instant::from_micros(0);
+        for us in 1..=(SAMPLES as u64 + 10) {
+            samples.push(start, Instant::from_micros(us));
+        }
+        let stats = samples.stats();
+        assert_eq!(stats.samples, SAMPLES as u16);
+        // 11 µs to 74 µs are left
+        assert_eq!(stats.p50_us, 42);
+        assert_eq!(stats.max_us, SAMPLES as u32 + 10);
+    }
+}
@@ -477,6 +477,25 @@ macro_rules! pdf {
     };
 }
 
+/// Create a keymap profile switch action.
+///
+/// Loads the keymap of the profile from storage and keeps it active after a
+/// reboot (requires the `storage` and `host` features).
+///
+/// # Parameters
+/// - `$x`: Profile number, below `keymap_profile_num` in `keyboard.toml`
+///
+/// # Example
+/// ```ignore
+/// profile!(1)  // Switch to the keymap of profile 1
+/// ```
+#[macro_export]
+macro_rules! profile {
+    ($x: literal) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::ProfileSwitch($x))
+    };
+}
+
 /// Create a shifted key action.
 ///
 /// This is a convenience macro that creates a key with left shift applied.
@@ -606,6 +625,48 @@ macro_rules! user {
     };
 }
 
+/// Create a BLE profile action.
+///
+/// BLE profile actions are user keys; the user key number is derived from
+/// `NUM_BLE_PROFILE`, so the keymap stays valid when the number of profiles changes.
+///
+/// # Available Actions
+/// - `bt!(n)`: Switch to profile `n`, `n` must be less than `NUM_BLE_PROFILE`
+/// - `bt!(Next)`: Switch to the next profile
+/// - `bt!(Prev)`: Switch to the previous profile
+/// - `bt!(Clear)`: Clear the bond of the current profile
+/// - `bt!(ClearAll)`: Clear the bonds of all profiles
+#[macro_export]
+macro_rules! bt {
+    (Next) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::User(
+            $crate::types::constants::NUM_BLE_PROFILE as u8,
+        ))
+    };
+    (Prev) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::User(
+            $crate::types::constants::NUM_BLE_PROFILE as u8 + 1,
+        ))
+    };
+    (Clear) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::User(
+            $crate::types::constants::NUM_BLE_PROFILE as u8 + 2,
+        ))
+    };
+    (ClearAll) => {
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::User(
+            $crate::types::constants::NUM_BLE_PROFILE as u8 + 5,
+        ))
+    };
+    ($profile: literal) => {{
+        const _: () = ::core::assert!(
+            ($profile as usize) < $crate::types::constants::NUM_BLE_PROFILE,
+            "bt!(n) needs n < NUM_BLE_PROFILE, see `ble_profiles_num` in keyboard.toml"
+        );
+        $crate::types::action::KeyAction::Single($crate::types::action::Action::User($profile))
+    }};
+}
+
 /// Create a keyboard control action.
 ///
 /// This macro creates actions for system-level keyboard operations.
@@ -618,10 +679,17 @@ macro_rules! user {
 /// - OutputAuto: Auto-select output (USB/Bluetooth)
 /// - OutputUsb: Force USB output
 /// - OutputBluetooth: Force Bluetooth output
+/// - OutputMirrorToggle: Toggle sending to both USB and Bluetooth
 /// - ComboOn: Enable combos
 /// - ComboOff: Disable combos
 /// - ComboToggle: Toggle combos
 /// - CapsWordToggle: Toggle caps word mode
+/// - AudioOn / AudioOff / AudioToggle: Enable, mute or toggle buzzer audio
+/// - ClickyToggle: Toggle the buzzer key click
+/// - HapticOn / HapticOff / HapticToggle: Enable, disable or toggle haptic feedback
+/// - AutoBrightnessToggle: Toggle ambient light driven auto-brightness
+/// - HostOsAuto / HostOsLinux / HostOsWindows / HostOsMacOs / HostOsIos: Detect the host OS or set it manually
+/// - Diagnostics: Type a status report of the keyboard on the host
 ///
 /// # Example (internal use only)
 /// ```ignore
@@ -20,6 +20,8 @@
 // Mutual exclusivity guard
 #[cfg(all(feature = "rmk_protocol", feature = "vial"))]
 compile_error!("features `rmk_protocol` and `vial` are mutually exclusive");
+#[cfg(all(feature = "_ble", feature = "wireless_transport"))]
+compile_error!("a custom wireless transport replaces the BLE stack, disable the BLE chip feature");
 
 // Re-export self as ::rmk for macro-generated code to work both inside and outside the crate
 extern crate self as rmk;
@@ -35,7 +37,10 @@ pub(crate) mod fmt;
 pub use embassy_futures;
 #[cfg(not(any(cortex_m)))]
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex as RawMutex;
-#[cfg(cortex_m)]
+// Channels are shared between the thread and the interrupt executor, see `priority`
+#[cfg(all(cortex_m, feature = "interrupt_executor"))]
+pub use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex as RawMutex;
+#[cfg(all(cortex_m, not(feature = "interrupt_executor")))]
 pub use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex as RawMutex;
 pub use embassy_time;
 pub use futures;
@@ -53,6 +58,10 @@ use {embedded_storage_async::nor_flash::NorFlash as AsyncNorFlash, storage::Stor
 
 use crate::config::PositionalConfig;
 
+#[cfg(feature = "ambient_light")]
+pub mod ambient_light;
+#[cfg(feature = "audio")]
+pub mod audio;
 #[cfg(feature = "_ble")]
 pub mod ble;
 pub mod boot;
@@ -60,36 +69,52 @@ pub mod channel;
 pub mod config;
 pub mod core_traits;
 pub mod debounce;
+pub mod diagnostics;
 #[cfg(feature = "display")]
 pub mod display;
 pub mod driver;
+#[cfg(feature = "esb")]
+pub mod esb;
 pub mod event;
+#[cfg(feature = "haptic")]
+pub mod haptic;
 pub mod helper_macro;
 pub mod hid;
 #[cfg(feature = "host")]
 pub mod host;
+pub mod host_os;
 pub mod input_device;
+#[cfg(feature = "introspection")]
+pub mod introspection;
 pub mod keyboard;
 pub mod keyboard_macros;
 pub mod keymap;
+#[cfg(feature = "latency")]
+pub mod latency;
 pub mod layout_macro;
 pub mod light;
+#[cfg(any(feature = "_ble", feature = "esb"))]
+pub mod link_stats;
+pub mod logging;
 pub mod matrix;
+pub mod priority;
 pub mod processor;
+#[cfg(feature = "std")]
+pub mod simulation;
 #[cfg(feature = "split")]
 pub mod split;
 pub mod state;
 #[cfg(feature = "storage")]
 pub mod storage;
+#[cfg(feature = "wireless_transport")]
+pub mod transport;
 #[cfg(not(feature = "_no_usb"))]
 pub mod usb;
 #[cfg(feature = "watchdog")]
 pub mod watchdog;
 
-// Test-only helper that drives `embassy-time/mock-driver` from the
-// `#[cfg(test)]` modules under `src/`. Mirrors the same helper at
-// `tests/common/test_block_on.rs` (which is invisible to lib unit tests
-// because integration tests are a separate compilation target).
+// Test-only setup of the `#[cfg(test)]` modules under `src/`, which run
+// with `simulation::block_on`.
 #[cfg(test)]
 pub(crate) mod test_support;
 
@@ -133,13 +158,23 @@ pub async fn initialize_keymap_and_storage<
             Storage::new(flash, &data.keymap, &encoder_opt, storage_config, behavior_config).await
         };
 
-        let keymap = KeyMap::new_from_storage(data, Some(&mut storage), behavior_config, positional_config).await;
+        let storage_opt = if storage_config.safe_mode {
+            warn!("Safe mode, the keymap in the storage is ignored");
+            None
+        } else {
+            storage.read_runtime_state(behavior_config).await;
+            Some(&mut storage)
+        };
+        let keymap = KeyMap::new_from_storage(data, storage_opt, behavior_config, positional_config).await;
         (keymap, storage)
     }
 
     #[cfg(not(feature = "host"))]
     {
-        let storage = Storage::new(flash, storage_config, behavior_config).await;
+        let mut storage = Storage::new(flash, storage_config, behavior_config).await;
+        if !storage_config.safe_mode {
+            storage.read_runtime_state(behavior_config).await;
+        }
         let keymap = KeyMap::new(data, behavior_config, positional_config).await;
         (keymap, storage)
     }
@@ -0,0 +1,96 @@
+//! Quality of the wireless link to the host.
+//!
+//! The BLE transport records the signal strength and the connection parameters
+//! of the current connection, the ESB keyboard records retransmissions, dropped
+//! packets and the time until the dongle acknowledges a packet. Read them with
+//! [`link_stats`] to find out why a wireless setup feels laggy, or with the
+//! `stats` command of the USB console.
+//!
+//! The statistics are reset when a new connection starts.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Duration;
+
+use crate::RawMutex;
+
+/// Statistics of the current wireless link.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkStats {
+    /// Last measured signal strength in dBm, `None` if the radio doesn't report it, or on BLE without `ble_rssi`
+    pub rssi: Option<i8>,
+    /// Connection interval in µs, 0 on links without connection events like ESB
+    pub conn_interval_us: u32,
+    /// Number of connection events the keyboard may skip when it has nothing to send
+    pub peripheral_latency: u16,
+    /// Reports and packets sent
+    pub packets: u32,
+    /// Retransmissions because the receiver didn't acknowledge, not reported by BLE controllers
+    pub retransmits: u32,
+    /// Packets given up after all retransmissions failed
+    pub dropped: u32,
+    /// Smoothed time from sending a report until the link took it, in µs: until the ACK of
+    /// the dongle on ESB, until the BLE stack queued the notification on BLE. The report
+    /// reaches a BLE host within one more `conn_interval_us`.
+    pub round_trip_us: u32,
+}
+
+static LINK_STATS: Mutex<RawMutex, Cell<LinkStats>> = Mutex::new(Cell::new(LinkStats {
+    rssi: None,
+    conn_interval_us: 0,
+    peripheral_latency: 0,
+    packets: 0,
+    retransmits: 0,
+    dropped: 0,
+    round_trip_us: 0,
+}));
+
+/// Statistics of the current wireless link.
+pub fn link_stats() -> LinkStats {
+    LINK_STATS.lock(|s| s.get())
+}
+
+/// Forget the statistics of the previous connection.
+pub(crate) fn reset_link_stats() {
+    LINK_STATS.lock(|s| s.set(LinkStats::default()));
+}
+
+pub(crate) fn update_link_stats(f: impl FnOnce(&mut LinkStats)) {
+    LINK_STATS.lock(|s| {
+        let mut stats = s.get();
+        f(&mut stats);
+        s.set(stats);
+    });
+}
+
+/// Count a sent packet and add its round trip to the average.
+pub(crate) fn record_packet(round_trip: Duration, retransmits: u32) {
+    update_link_stats(|s| {
+        s.packets = s.packets.wrapping_add(1);
+        s.retransmits = s.retransmits.wrapping_add(retransmits);
+        s.round_trip_us = smooth(s.round_trip_us, round_trip.as_micros().min(u32::MAX as u64) as u32);
+    });
+}
+
+/// Moving average over about the last 8 samples, like the smoothed RTT of TCP
+fn smooth(average: u32, sample: u32) -> u32 {
+    if average == 0 {
+        return sample;
+    }
+    ((average as u64 * 7 + sample as u64) / 8) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth_round_trip() {
+        assert_eq!(smooth(0, 800), 800);
+        assert_eq!(smooth(800, 800), 800);
+        assert_eq!(smooth(800, 1600), 900);
+        assert_eq!(smooth(u32::MAX, u32::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,152 @@
+//! Log levels of every part of the firmware, which can be changed at runtime.
+//!
+//! Every log message of RMK is tagged with the [`LogModule`] it comes from, and only
+//! printed when its level is enabled for that module, with defmt as well as with `log` and
+//! the USB console. Change the levels with [`set_log_level`], with the `log` command of the
+//! USB console, or with the `LogLevel` command of Via, e.g. to debug the split link without
+//! drowning in the logs of the matrix.
+//!
+//! The levels can't go above what the logger is built with: `DEFMT_LOG` for defmt, the max
+//! level given to the logger for `log`. By default, everything the logger lets through is
+//! printed. Logs of the firmware itself, outside of RMK, aren't filtered.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use rmk_types::logging::{LogLevel, LogModule};
+
+/// Level of every module, everything until the logger filters it
+static LEVELS: [AtomicU8; LogModule::COUNT] = [const { AtomicU8::new(LogLevel::Trace as u8) }; LogModule::COUNT];
+
+/// Log level of `module`.
+pub fn log_level(module: LogModule) -> LogLevel {
+    LogLevel::from_repr(LEVELS[module as usize].load(Ordering::Relaxed)).unwrap_or(LogLevel::Trace)
+}
+
+/// Print the messages of `module` up to `level`.
+pub fn set_log_level(module: LogModule, level: LogLevel) {
+    LEVELS[module as usize].store(level as u8, Ordering::Relaxed);
+}
+
+/// Set the log level of every module.
+pub fn set_all_log_levels(level: LogLevel) {
+    for l in &LEVELS {
+        l.store(level as u8, Ordering::Relaxed);
+    }
+}
+
+/// Whether messages of `level` are printed for `module`, checked by the log macros.
+#[cfg_attr(not(any(feature = "log", feature = "defmt")), allow(dead_code))]
+pub(crate) fn enabled(module: LogModule, level: LogLevel) -> bool {
+    level as u8 <= LEVELS[module as usize].load(Ordering::Relaxed)
+}
+
+/// Module of the code at `path`, as given by `module_path!()`.
+///
+/// It's `const` so the log macros resolve it at compile time.
+#[cfg_attr(not(any(feature = "log", feature = "defmt")), allow(dead_code))]
+pub(crate) const fn module_of(path: &str) -> LogModule {
+    const MODULES: &[(&str, LogModule)] = &[
+        ("keyboard", LogModule::Keyboard),
+        ("keyboard_macros", LogModule::Keyboard),
+        ("hid", LogModule::Keyboard),
+        ("host_os", LogModule::Keyboard),
+        ("keymap", LogModule::Keymap),
+        ("layout_macro", LogModule::Keymap),
+        ("matrix", LogModule::Matrix),
+        ("debounce", LogModule::Matrix),
+        ("input_device", LogModule::Input),
+        ("ambient_light", LogModule::Input),
+        ("ble", LogModule::Wireless),
+        ("esb", LogModule::Wireless),
+        ("transport", LogModule::Wireless),
+        ("link_stats", LogModule::Wireless),
+        ("usb", LogModule::Usb),
+        ("split", LogModule::Split),
+        ("host", LogModule::Host),
+        ("storage", LogModule::Storage),
+        ("light", LogModule::Output),
+        ("display", LogModule::Output),
+        ("audio", LogModule::Output),
+        ("haptic", LogModule::Output),
+    ];
+
+    let path = path.as_bytes();
+    let mut i = 0;
+    while i < MODULES.len() {
+        if is_top_module(path, MODULES[i].0.as_bytes()) {
+            return MODULES[i].1;
+        }
+        i += 1;
+    }
+    LogModule::Other
+}
+
+/// Whether `path` is in `rmk::<name>`
+const fn is_top_module(path: &[u8], name: &[u8]) -> bool {
+    const PREFIX: &[u8] = b"rmk::";
+    let end = PREFIX.len() + name.len();
+    if path.len() < end || (path.len() > end && path[end] != b':') {
+        return false;
+    }
+    let mut i = 0;
+    while i < end {
+        let expected = if i < PREFIX.len() {
+            PREFIX[i]
+        } else {
+            name[i - PREFIX.len()]
+        };
+        if path[i] != expected {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_of() {
+        assert_eq!(module_of("rmk::keyboard"), LogModule::Keyboard);
+        assert_eq!(module_of("rmk::keyboard::combo"), LogModule::Keyboard);
+        assert_eq!(module_of("rmk::keyboard_macros"), LogModule::Keyboard);
+        assert_eq!(module_of("rmk::split::ble::central"), LogModule::Split);
+        assert_eq!(module_of("rmk::ble::ble_server"), LogModule::Wireless);
+        assert_eq!(module_of("rmk::blevel"), LogModule::Other);
+        assert_eq!(module_of("rmk::boot"), LogModule::Other);
+        assert_eq!(module_of("my_keyboard::ble"), LogModule::Other);
+    }
+
+    /// Puts the levels back when the test ends, even if it fails, so that other tests keep logging
+    struct RestoreLevels([LogLevel; LogModule::COUNT]);
+
+    impl RestoreLevels {
+        fn new() -> Self {
+            Self(core::array::from_fn(|i| {
+                LogLevel::from_repr(LEVELS[i].load(Ordering::Relaxed)).unwrap_or(LogLevel::Trace)
+            }))
+        }
+    }
+
+    impl Drop for RestoreLevels {
+        fn drop(&mut self) {
+            for (l, level) in LEVELS.iter().zip(self.0) {
+                l.store(level as u8, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_runtime_level() {
+        let _restore = RestoreLevels::new();
+        set_log_level(LogModule::Matrix, LogLevel::Warn);
+        assert!(enabled(LogModule::Matrix, LogLevel::Error));
+        assert!(!enabled(LogModule::Matrix, LogLevel::Info));
+        assert!(enabled(LogModule::Usb, LogLevel::Trace));
+        set_all_log_levels(LogLevel::Off);
+        assert!(!enabled(LogModule::Usb, LogLevel::Error));
+        assert_eq!(log_level(LogModule::Split), LogLevel::Off);
+    }
+}
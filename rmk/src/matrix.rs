@@ -9,6 +9,8 @@ use {embassy_futures::select::select_slice, embedded_hal_async::digital::Wait, h
 
 use crate::core_traits::Runnable;
 use crate::debounce::{DebounceState, DebouncerTrait};
+#[cfg(feature = "async_matrix")]
+use crate::event::{PowerStateEvent, publish_event};
 use crate::event::{KeyboardEvent, publish_event_async};
 use crate::input_device::InputDevice;
 pub mod bidirectional_matrix;
@@ -420,6 +422,13 @@ where
                         if let Some(out_pin) = self.get_output_pins_mut().get_mut(out_idx) {
                             out_pin.set_low().ok();
                         }
+                        #[cfg(feature = "latency")]
+                        crate::latency::record_debounced(
+                            row_idx,
+                            col_idx,
+                            (row_idx + ROW_OFFSET) as u8,
+                            (col_idx + COL_OFFSET) as u8,
+                        );
                         return KeyboardEvent::key(
                             (row_idx + ROW_OFFSET) as u8,
                             (col_idx + COL_OFFSET) as u8,
@@ -447,6 +456,7 @@ where
                 }
                 self.rescan_needed = false;
             }
+            crate::diagnostics::record_scan();
             self.scan_pos = (0, 0);
         }
     }
@@ -502,7 +512,9 @@ where
         }
 
         // Wait for any key press
+        publish_event(PowerStateEvent::MatrixScan(false));
         self.wait_input_pins().await;
+        publish_event(PowerStateEvent::MatrixScan(true));
 
         // Set all output pins back to low
         for out in self.get_output_pins_mut().iter_mut() {
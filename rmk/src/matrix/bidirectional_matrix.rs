@@ -80,6 +80,13 @@ impl<Pin: FlexPin, D: DebouncerTrait<ROW, COL>, const PIN_NUM: usize, const ROW:
                             // Pull output pin back to low before returning
                             out_pin.set_low().ok();
                             out_pin.set_as_input();
+                            #[cfg(feature = "latency")]
+                            crate::latency::record_debounced(
+                                scan_y_idx,
+                                scan_x_idx,
+                                scan_y_idx as u8,
+                                scan_x_idx as u8,
+                            );
                             return KeyboardEvent::key(
                                 scan_y_idx as u8,
                                 scan_x_idx as u8,
@@ -94,6 +101,7 @@ impl<Pin: FlexPin, D: DebouncerTrait<ROW, COL>, const PIN_NUM: usize, const ROW:
                     }
                 }
             }
+            crate::diagnostics::record_scan();
             self.scan_pos = (0, 0);
         }
     }
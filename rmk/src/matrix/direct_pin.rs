@@ -8,6 +8,8 @@ use {embassy_futures::select::select_slice, embassy_time::Instant, embedded_hal_
 use super::{KeyState, MatrixTrait};
 use crate::debounce::{DebounceState, DebouncerTrait};
 use crate::event::KeyboardEvent;
+#[cfg(feature = "async_matrix")]
+use crate::event::{PowerStateEvent, publish_event};
 
 /// DirectPinMartex only has input pins.
 #[input_device(publish = KeyboardEvent)]
@@ -106,6 +108,13 @@ impl<
                             let key_state = self.key_states[row_idx][col_idx];
 
                             self.scan_pos = (row_idx, col_idx);
+                            #[cfg(feature = "latency")]
+                            crate::latency::record_debounced(
+                                row_idx,
+                                col_idx,
+                                (row_idx + ROW_OFFSET) as u8,
+                                (col_idx + COL_OFFSET) as u8,
+                            );
                             return KeyboardEvent::key(
                                 (row_idx + ROW_OFFSET) as u8,
                                 (col_idx + COL_OFFSET) as u8,
@@ -122,6 +131,7 @@ impl<
                 }
             }
 
+            crate::diagnostics::record_scan();
             self.scan_pos = (0, 0);
 
             Timer::after_micros(100).await;
@@ -154,6 +164,7 @@ impl<
         }
         Timer::after_micros(1).await;
         info!("Waiting for active level");
+        publish_event(PowerStateEvent::MatrixScan(false));
 
         if self.low_active {
             let mut futs: Vec<_, SIZE> = Vec::new();
@@ -172,6 +183,7 @@ impl<
             }
             let _ = select_slice(pin!(futs.as_mut_slice())).await;
         }
+        publish_event(PowerStateEvent::MatrixScan(true));
         self.scan_start = Some(Instant::now());
     }
 }
@@ -153,6 +153,13 @@ impl<
                             self.rescan_needed = true;
                         }
                         self.clear_columns().await;
+                        #[cfg(feature = "latency")]
+                        crate::latency::record_debounced(
+                            row_idx,
+                            col_idx,
+                            (row_idx + ROW_OFFSET) as u8,
+                            (col_idx + COL_OFFSET) as u8,
+                        );
                         return KeyboardEvent::key(
                             (row_idx + ROW_OFFSET) as u8,
                             (col_idx + COL_OFFSET) as u8,
@@ -179,6 +186,7 @@ impl<
 
             Timer::after_millis(1).await;
 
+            crate::diagnostics::record_scan();
             self.scan_pos = (0, 0);
         }
     }
@@ -0,0 +1,110 @@
+//! Priorities of the keyboard's tasks.
+//!
+//! The tasks are split in two groups:
+//!
+//! - critical: the matrix and the input devices, the keyboard, the input processors, the host
+//!   service, the split links and the transports. That's everything between a key press and its
+//!   report, and every task which uses the keymap. Processors registered with
+//!   `#[register_processor(event, critical)]` join them.
+//! - background: the storage, lights, displays, other registered processors and the watchdog.
+//!
+//! Without the `interrupt_executor` feature, both groups run on the thread executor and the
+//! critical tasks are polled first. A background task which blocks, like a flash erase or a
+//! display refresh, still delays the key presses until it yields.
+//!
+//! With the `interrupt_executor` feature, the critical tasks run on an interrupt executor, at the
+//! lowest interrupt priority. It preempts the background tasks on the thread executor, so their
+//! blocking work doesn't delay the key presses. The channels between the groups are interrupt
+//! safe with this feature, but other state isn't: a custom task which shares state with the
+//! critical tasks, like the keymap, has to be a critical task as well.
+//!
+//! `#[rmk_keyboard]` sets up the interrupt executor on nRF52 and RP2040. With the Rust API, start
+//! one like in embassy's `multiprio` examples and spawn [`CriticalTasks`] on it:
+//!
+//! ```rust,ignore
+//! static EXECUTOR_CRITICAL: InterruptExecutor = InterruptExecutor::new();
+//!
+//! #[interrupt]
+//! unsafe fn EGU1_SWI1() {
+//!     unsafe { EXECUTOR_CRITICAL.on_interrupt() }
+//! }
+//!
+//! #[embassy_executor::task]
+//! async fn critical_task(tasks: CriticalTasks) {
+//!     tasks.await
+//! }
+//!
+//! // In main
+//! let critical = join_all!(matrix.run(), keyboard.run(), usb_transport.run(), host_service.run());
+//! let background = join_all!(storage.run(), wpm_processor.run());
+//! // SAFETY: main never ends, and isn't dropped
+//! unsafe {
+//!     run_prioritized(critical, background, |tasks| {
+//!         interrupt::EGU1_SWI1.set_priority(Priority::P7);
+//!         let spawner = EXECUTOR_CRITICAL.start(interrupt::EGU1_SWI1);
+//!         spawner.spawn(critical_task(tasks).unwrap());
+//!     })
+//! }
+//! .await
+//! ```
+
+use core::future::Future;
+#[cfg(feature = "interrupt_executor")]
+use core::pin::Pin;
+#[cfg(feature = "interrupt_executor")]
+use core::task::{Context, Poll};
+
+/// The critical tasks, to be spawned on the interrupt executor, see [`run_prioritized`]
+#[cfg(feature = "interrupt_executor")]
+pub struct CriticalTasks(Pin<&'static mut dyn Future<Output = ()>>);
+
+// SAFETY: the tasks are only polled by the interrupt executor after they're spawned, and the
+// channels they share with the background tasks use a critical section mutex
+#[cfg(feature = "interrupt_executor")]
+unsafe impl Send for CriticalTasks {}
+
+#[cfg(feature = "interrupt_executor")]
+impl Future for CriticalTasks {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+/// Run the `critical` tasks on the interrupt executor `spawn` starts, and the `background` tasks
+/// on the current executor.
+///
+/// The critical tasks borrow from the caller, so they can be spawned although they aren't
+/// `'static`.
+///
+/// # Safety
+///
+/// The returned future never completes, and it must not be dropped, or the interrupt executor
+/// would keep polling the critical tasks after they're gone. Awaiting it at the end of the main
+/// task, which embassy never drops, satisfies this.
+#[cfg(feature = "interrupt_executor")]
+pub async unsafe fn run_prioritized<C: Future, B: Future>(
+    critical: C,
+    background: B,
+    spawn: impl FnOnce(CriticalTasks),
+) -> ! {
+    let critical = core::pin::pin!(async {
+        critical.await;
+    });
+    let critical: Pin<&mut dyn Future<Output = ()>> = critical;
+    // SAFETY: `critical` lives in this future, which the caller never drops, and this future
+    // never completes
+    let critical: Pin<&'static mut dyn Future<Output = ()>> = unsafe { core::mem::transmute(critical) };
+    spawn(CriticalTasks(critical));
+    background.await;
+    core::future::pending().await
+}
+
+/// Run the `critical` and the `background` tasks on the current executor, the critical tasks are
+/// polled first.
+#[cfg(not(feature = "interrupt_executor"))]
+pub async fn run_prioritized<C: Future, B: Future>(critical: C, background: B) -> ! {
+    embassy_futures::join::join(critical, background).await;
+    core::future::pending().await
+}
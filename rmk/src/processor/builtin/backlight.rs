@@ -0,0 +1,92 @@
+//! The processor driving a PWM backlight from the brightness level.
+use embedded_hal::pwm::SetDutyCycle;
+use rmk_macro::processor;
+
+use crate::event::{BrightnessEvent, SleepStateEvent};
+
+/// Processor driving a single color backlight with a PWM channel.
+///
+/// The duty cycle follows [`BrightnessEvent`], published by the ambient light sensor or synced
+/// from the central on split peripherals. The backlight is turned off while the keyboard sleeps.
+#[processor(subscribe = [BrightnessEvent, SleepStateEvent])]
+pub struct PwmBacklightProcessor<P: SetDutyCycle> {
+    pwm: P,
+    brightness: u8,
+    sleeping: bool,
+}
+
+impl<P: SetDutyCycle> PwmBacklightProcessor<P> {
+    /// Create the processor, the backlight is at `brightness` until the first [`BrightnessEvent`].
+    pub fn new(pwm: P, brightness: u8) -> Self {
+        let mut backlight = Self {
+            pwm,
+            brightness,
+            sleeping: false,
+        };
+        backlight.update_duty_cycle();
+        backlight
+    }
+
+    async fn on_brightness_event(&mut self, event: BrightnessEvent) {
+        self.brightness = event.0;
+        self.update_duty_cycle();
+    }
+
+    async fn on_sleep_state_event(&mut self, event: SleepStateEvent) {
+        self.sleeping = event.0;
+        self.update_duty_cycle();
+    }
+
+    fn update_duty_cycle(&mut self) {
+        let brightness = if self.sleeping { 0 } else { self.brightness };
+        if self
+            .pwm
+            .set_duty_cycle_fraction(brightness as u16, u8::MAX as u16)
+            .is_err()
+        {
+            warn!("Failed to set the backlight duty cycle");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+    use crate::simulation::block_on;
+
+    struct FakePwm {
+        duty: u16,
+    }
+
+    impl embedded_hal::pwm::ErrorType for FakePwm {
+        type Error = Infallible;
+    }
+
+    impl SetDutyCycle for FakePwm {
+        fn max_duty_cycle(&self) -> u16 {
+            1000
+        }
+
+        fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+            self.duty = duty;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn duty_cycle_follows_brightness_and_sleep() {
+        let mut backlight = PwmBacklightProcessor::new(FakePwm { duty: 0 }, u8::MAX);
+        assert_eq!(backlight.pwm.duty, 1000);
+
+        block_on(backlight.on_brightness_event(BrightnessEvent(51)));
+        assert_eq!(backlight.pwm.duty, 200);
+
+        block_on(backlight.on_sleep_state_event(SleepStateEvent(true)));
+        assert_eq!(backlight.pwm.duty, 0);
+
+        block_on(backlight.on_sleep_state_event(SleepStateEvent(false)));
+        assert_eq!(backlight.pwm.duty, 200);
+    }
+}
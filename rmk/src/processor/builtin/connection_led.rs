@@ -0,0 +1,181 @@
+//! Connection status LED processor for RMK
+use embedded_hal::digital::StatefulOutputPin;
+use rmk_macro::processor;
+use rmk_types::ble::{BleState, BleStatus};
+
+use crate::ble::profile::active_profile_bonded;
+use crate::driver::gpio::OutputController;
+use crate::event::{ConnectionStatusChangeEvent, PowerStateEvent, SleepStateEvent, publish_event};
+
+/// Number of 100ms ticks each blink of [`ConnectionPattern::Connected`] stays on, and off
+const CONNECTED_BLINK_TICKS: u32 = 2;
+
+/// What the wireless stack is doing, as shown by a connection status LED.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConnectionPattern {
+    /// BLE is not in use. The LED is off.
+    Off,
+    /// Advertising on a profile without a bonded host, waiting for a new host to pair. Fast blink.
+    Pairing,
+    /// Advertising on a profile with a bonded host, waiting for it to reconnect. Slow blink.
+    Reconnecting,
+    /// Connected on the given profile. Blinks `profile + 1` times, then the LED is off.
+    Connected(u8),
+}
+
+impl ConnectionPattern {
+    fn from_status(status: BleStatus, bonded: bool) -> Self {
+        match status.state {
+            BleState::Inactive => ConnectionPattern::Off,
+            BleState::Advertising if bonded => ConnectionPattern::Reconnecting,
+            BleState::Advertising => ConnectionPattern::Pairing,
+            BleState::Connected => ConnectionPattern::Connected(status.profile),
+        }
+    }
+
+    /// Whether the LED is lit `tick` 100ms ticks after the pattern started.
+    pub fn is_lit(&self, tick: u32) -> bool {
+        match self {
+            ConnectionPattern::Off => false,
+            ConnectionPattern::Pairing => tick % 2 == 0,
+            ConnectionPattern::Reconnecting => tick % 10 < 5,
+            ConnectionPattern::Connected(profile) => {
+                let period = CONNECTED_BLINK_TICKS * 2;
+                tick < period * (*profile as u32 + 1) && tick % period < CONNECTED_BLINK_TICKS
+            }
+        }
+    }
+}
+
+/// Status LED showing a [`ConnectionPattern`].
+///
+/// Implemented by [`GpioStatusLed`] for a single LED on a GPIO. Implement it
+/// for an RGB LED to pick a color per pattern, for example one color per profile.
+pub trait StatusLed {
+    /// Light up (`lit`) or turn off the LED while showing `pattern`.
+    fn set(&mut self, pattern: ConnectionPattern, lit: bool);
+}
+
+/// A single status LED driven by a GPIO pin.
+pub struct GpioStatusLed<P: StatefulOutputPin> {
+    pin: OutputController<P>,
+}
+
+impl<P: StatefulOutputPin> GpioStatusLed<P> {
+    pub fn new(pin: P, low_active: bool) -> Self {
+        Self {
+            pin: OutputController::new(pin, low_active),
+        }
+    }
+}
+
+impl<P: StatefulOutputPin> StatusLed for GpioStatusLed<P> {
+    fn set(&mut self, _pattern: ConnectionPattern, lit: bool) {
+        if lit {
+            self.pin.activate();
+        } else {
+            self.pin.deactivate();
+        }
+    }
+}
+
+/// Processor blinking a status LED according to the BLE connection state.
+///
+/// Patterns restart whenever the connection status changes, see [`ConnectionPattern`].
+/// The LED is off while the keyboard sleeps.
+#[processor(subscribe = [ConnectionStatusChangeEvent, SleepStateEvent], poll_interval = 100)]
+pub struct ConnectionLedProcessor<L: StatusLed> {
+    led: L,
+    status: BleStatus,
+    pattern: ConnectionPattern,
+    tick: u32,
+    sleeping: bool,
+}
+
+impl<L: StatusLed> ConnectionLedProcessor<L> {
+    pub fn new(led: L) -> Self {
+        Self {
+            led,
+            status: BleStatus::default(),
+            pattern: ConnectionPattern::Off,
+            tick: 0,
+            sleeping: false,
+        }
+    }
+
+    async fn on_connection_status_change_event(&mut self, event: ConnectionStatusChangeEvent) {
+        self.status = event.0.ble;
+    }
+
+    async fn on_sleep_state_event(&mut self, event: SleepStateEvent) {
+        if self.sleeping != event.0 {
+            publish_event(PowerStateEvent::Lighting(!event.0));
+        }
+        self.sleeping = event.0;
+    }
+
+    async fn poll(&mut self) {
+        if self.sleeping {
+            self.led.set(ConnectionPattern::Off, false);
+            return;
+        }
+        // The bond flag isn't part of the connection status, so re-evaluate the pattern on every tick
+        let pattern = ConnectionPattern::from_status(self.status, active_profile_bonded());
+        if pattern != self.pattern {
+            self.pattern = pattern;
+            self.tick = 0;
+        }
+        self.led.set(self.pattern, self.pattern.is_lit(self.tick));
+        self.tick = self.tick.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit_ticks(pattern: ConnectionPattern, ticks: u32) -> usize {
+        (0..ticks).filter(|&t| pattern.is_lit(t)).count()
+    }
+
+    #[test]
+    fn pattern_from_status() {
+        let status = |state| BleStatus { profile: 2, state };
+        assert_eq!(
+            ConnectionPattern::from_status(status(BleState::Inactive), true),
+            ConnectionPattern::Off
+        );
+        assert_eq!(
+            ConnectionPattern::from_status(status(BleState::Advertising), false),
+            ConnectionPattern::Pairing
+        );
+        assert_eq!(
+            ConnectionPattern::from_status(status(BleState::Advertising), true),
+            ConnectionPattern::Reconnecting
+        );
+        assert_eq!(
+            ConnectionPattern::from_status(status(BleState::Connected), true),
+            ConnectionPattern::Connected(2)
+        );
+    }
+
+    #[test]
+    fn connected_blinks_once_per_profile_number_then_stays_off() {
+        for profile in 0..4u8 {
+            let pattern = ConnectionPattern::Connected(profile);
+            let lit = lit_ticks(pattern, 1000);
+            assert_eq!(lit, (profile as usize + 1) * CONNECTED_BLINK_TICKS as usize);
+            assert!(!pattern.is_lit(999));
+        }
+    }
+
+    #[test]
+    fn pairing_blinks_faster_than_reconnecting() {
+        let transitions =
+            |pattern: ConnectionPattern| (1..100).filter(|&t| pattern.is_lit(t) != pattern.is_lit(t - 1)).count();
+        assert!(transitions(ConnectionPattern::Pairing) > transitions(ConnectionPattern::Reconnecting));
+        assert!(transitions(ConnectionPattern::Reconnecting) > 0);
+        assert_eq!(lit_ticks(ConnectionPattern::Off, 100), 0);
+    }
+}
@@ -4,12 +4,15 @@ use rmk_macro::processor;
 use rmk_types::led_indicator::LedIndicatorType;
 
 use crate::driver::gpio::OutputController;
-use crate::event::LedIndicatorEvent;
+use crate::event::{LedIndicatorEvent, PowerStateEvent, SleepStateEvent, publish_event};
 
-#[processor(subscribe = [LedIndicatorEvent])]
+/// Processor lighting a LED for one of the lock indicators. The LED is turned off while the keyboard sleeps.
+#[processor(subscribe = [LedIndicatorEvent, SleepStateEvent])]
 pub struct KeyboardIndicatorProcessor<P: StatefulOutputPin> {
     pin: OutputController<P>,
     indicator: LedIndicatorType,
+    activated: bool,
+    sleeping: bool,
 }
 
 impl<P: StatefulOutputPin> KeyboardIndicatorProcessor<P> {
@@ -17,6 +20,8 @@ impl<P: StatefulOutputPin> KeyboardIndicatorProcessor<P> {
         Self {
             pin: OutputController::new(pin, low_active),
             indicator: lock_name,
+            activated: false,
+            sleeping: false,
         }
     }
 
@@ -29,7 +34,21 @@ impl<P: StatefulOutputPin> KeyboardIndicatorProcessor<P> {
             LedIndicatorType::Kana => event.kana(),
         };
         info!("Activating {:?} {}", self.indicator, activated);
-        if activated {
+        self.activated = activated;
+        self.update_pin();
+    }
+
+    async fn on_sleep_state_event(&mut self, event: SleepStateEvent) {
+        // Only a lit LED changes
+        if self.activated && self.sleeping != event.0 {
+            publish_event(PowerStateEvent::Lighting(!event.0));
+        }
+        self.sleeping = event.0;
+        self.update_pin();
+    }
+
+    fn update_pin(&mut self) {
+        if self.activated && !self.sleeping {
             self.pin.activate();
         } else {
             self.pin.deactivate();
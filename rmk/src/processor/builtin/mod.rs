@@ -2,7 +2,11 @@
 //!
 //! This module contains built-in processor implementations for output devices.
 
+#[cfg(any(feature = "ambient_light", feature = "split"))]
+pub mod backlight;
 #[cfg(feature = "_ble")]
 pub mod battery_led;
+#[cfg(feature = "_ble")]
+pub mod connection_led;
 pub mod led_indicator;
 pub mod wpm;
@@ -1,17 +1,34 @@
 //! WPM (Words Per Minute) processor for RMK
+use core::sync::atomic::{AtomicU16, Ordering};
+
 use rmk_macro::processor;
 
 use crate::event::{KeyboardEvent, WpmUpdateEvent, publish_event};
 
 const CHARS_PER_WORD: u8 = 5;
-const SAMPLES: u8 = 5;
+const DEFAULT_SAMPLES: u8 = 5;
+
+/// Latest WPM estimate, shared with displays and effects.
+static CURRENT_WPM: AtomicU16 = AtomicU16::new(0);
+
+/// Current words-per-minute estimate published by [`WpmProcessor`].
+///
+/// Returns 0 if no `WpmProcessor` is running.
+pub fn current_wpm() -> u16 {
+    CURRENT_WPM.load(Ordering::Relaxed)
+}
 
 /// Processor to estimate typing speed in words per minute (WPM)
+///
+/// Key releases are counted every second and averaged over a smoothing
+/// window of the last few seconds, see [`with_smoothing_window`](Self::with_smoothing_window).
+/// The estimate is published as [`WpmUpdateEvent`] and available via [`current_wpm`].
 #[processor(subscribe = [KeyboardEvent], poll_interval = 1000)]
 pub struct WpmProcessor {
     keys_pressed: u8,
     wpm: u16,
     update_count: u8,
+    samples: u8,
 }
 
 impl Default for WpmProcessor {
@@ -26,22 +43,31 @@ impl WpmProcessor {
             keys_pressed: 0,
             wpm: 0,
             update_count: 0,
+            samples: DEFAULT_SAMPLES,
         }
     }
 
+    /// Average the estimate over `seconds` one-second samples. Default: 5.
+    ///
+    /// Larger windows give a steadier value, `1` disables smoothing.
+    pub fn with_smoothing_window(mut self, seconds: u8) -> Self {
+        self.samples = seconds.max(1);
+        self
+    }
+
     async fn on_keyboard_event(&mut self, event: KeyboardEvent) {
         if let KeyboardEvent { pressed: false, .. } = event {
-            self.keys_pressed += 1
+            self.keys_pressed = self.keys_pressed.saturating_add(1);
         }
     }
 
     async fn poll(&mut self) {
-        self.update_count = SAMPLES.min(self.update_count + 1);
+        self.update_count = self.samples.min(self.update_count.saturating_add(1));
 
         let instant_wpm = self.keys_pressed as u16 * 60 / CHARS_PER_WORD as u16;
 
         let avg_wpm = if instant_wpm > 0 {
-            (self.wpm * (self.update_count - 1) as u16 + instant_wpm) / self.update_count as u16
+            ((self.wpm as u32 * (self.update_count - 1) as u32 + instant_wpm as u32) / self.update_count as u32) as u16
         } else {
             self.update_count = 0;
             0
@@ -49,9 +75,47 @@ impl WpmProcessor {
 
         if avg_wpm != self.wpm {
             self.wpm = avg_wpm;
+            CURRENT_WPM.store(self.wpm, Ordering::Relaxed);
             publish_event(WpmUpdateEvent::new(self.wpm));
         }
 
         self.keys_pressed = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::block_on;
+
+    fn type_keys(p: &mut WpmProcessor, n: u8) {
+        for _ in 0..n {
+            block_on(p.on_keyboard_event(KeyboardEvent::key(0, 0, false)));
+        }
+    }
+
+    #[test]
+    fn smoothing_window_averages_samples() {
+        let mut p = WpmProcessor::new().with_smoothing_window(2);
+        // 10 releases in one second = 120 WPM.
+        type_keys(&mut p, 10);
+        block_on(p.poll());
+        assert_eq!(p.wpm, 120);
+        assert_eq!(current_wpm(), 120);
+
+        // 5 releases = 60 WPM instantaneous, averaged with the previous sample.
+        type_keys(&mut p, 5);
+        block_on(p.poll());
+        assert_eq!(p.wpm, 90);
+    }
+
+    #[test]
+    fn idle_second_resets_wpm() {
+        let mut p = WpmProcessor::new().with_smoothing_window(1);
+        type_keys(&mut p, 5);
+        block_on(p.poll());
+        assert_eq!(p.wpm, 60);
+        block_on(p.poll());
+        assert_eq!(p.wpm, 0);
+    }
+}
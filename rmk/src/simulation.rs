@@ -0,0 +1,258 @@
+//! Simulation of the key processing on the host, for regression tests of a keymap.
+//!
+//! With the `std` feature, the key processing runs on the host against a simulated clock, which
+//! only moves forward when everything waits for it. A [`Timeline`] of key presses and releases
+//! is fed to a [`Keyboard`](crate::keyboard::Keyboard), and the HID reports it sends are collected with the time they were
+//! sent at, so timing sensitive behaviors like tap-hold, combos and one-shot keys can be checked
+//! to the millisecond:
+//!
+//! ```rust,ignore
+//! use rmk::simulation::{Timeline, keymap, simulate};
+//!
+//! let mut keyboard = Keyboard::new(keymap(get_default_keymap(), BehaviorConfig::default()));
+//! let timeline = Timeline::new().tap(0, 1, 50).press(2, 1).wait(300).release(2, 1).wait(100);
+//! let result = simulate(&mut keyboard, &timeline);
+//! assert_eq!(result.keyboard_reports()[0].keycodes[0], HidKeyCode::Q as u8);
+//! ```
+//!
+//! The clock is global, so run each simulation in a process of its own, like `cargo nextest`
+//! does for every test. `simulate` routes the reports to USB, so it's not available on chips
+//! without USB, but [`block_on`] is.
+
+extern crate std;
+
+#[cfg(not(feature = "_no_usb"))]
+use core::cell::RefCell;
+use core::future::Future;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::boxed::Box;
+use std::vec::Vec;
+
+#[cfg(not(feature = "_no_usb"))]
+use embassy_futures::select::select;
+use embassy_time::{Duration, MockDriver};
+#[cfg(not(feature = "_no_usb"))]
+use embassy_time::{Instant, Timer};
+
+#[cfg(not(feature = "_no_usb"))]
+use crate::channel::USB_REPORT_CHANNEL;
+use crate::config::{BehaviorConfig, PositionalConfig};
+#[cfg(not(feature = "_no_usb"))]
+use crate::core_traits::Runnable;
+#[cfg(not(feature = "_no_usb"))]
+use crate::event::{AsyncEventPublisher, AsyncPublishableEvent, KeyboardEvent};
+use crate::hid::{KeyboardReport, Report};
+#[cfg(not(feature = "_no_usb"))]
+use crate::keyboard::Keyboard;
+use crate::keymap::{KeyMap, KeymapData};
+use crate::types::action::KeyAction;
+#[cfg(not(feature = "_no_usb"))]
+use crate::types::connection::UsbState;
+
+/// Time the simulated clock moves forward by when everything is waiting
+const STEP: Duration = Duration::from_micros(100);
+
+/// Polls before [`block_on`] gives up, 60s of simulated time. A future which takes longer waits
+/// for something which never happens, like an event nobody publishes.
+const MAX_ITERS: usize = 60_000_000;
+
+/// A key press or release, at a time since the start of the simulation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedKeyEvent {
+    pub at_ms: u64,
+    pub row: u8,
+    pub col: u8,
+    pub pressed: bool,
+}
+
+/// Key events of a simulation, in the order they happen
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    events: Vec<TimedKeyEvent>,
+    /// Time of the next event, and the end of the simulation
+    now_ms: u64,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let `ms` milliseconds pass.
+    ///
+    /// The simulation ends at the end of the timeline, so finish it with a wait
+    /// which is longer than the timeouts which are still running.
+    pub fn wait(mut self, ms: u64) -> Self {
+        self.now_ms += ms;
+        self
+    }
+
+    pub fn press(self, row: u8, col: u8) -> Self {
+        self.event(row, col, true)
+    }
+
+    pub fn release(self, row: u8, col: u8) -> Self {
+        self.event(row, col, false)
+    }
+
+    /// Press the key, and release it `hold_ms` milliseconds later.
+    pub fn tap(self, row: u8, col: u8, hold_ms: u64) -> Self {
+        self.press(row, col).wait(hold_ms).release(row, col)
+    }
+
+    pub fn events(&self) -> &[TimedKeyEvent] {
+        &self.events
+    }
+
+    /// Length of the timeline in milliseconds
+    pub fn duration_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    fn event(mut self, row: u8, col: u8, pressed: bool) -> Self {
+        self.events.push(TimedKeyEvent {
+            at_ms: self.now_ms,
+            row,
+            col,
+            pressed,
+        });
+        self
+    }
+}
+
+/// A report sent by the keyboard, at a time since the start of the simulation
+#[derive(Clone, Debug)]
+pub struct TimedReport {
+    pub at_ms: u64,
+    pub report: Report,
+}
+
+/// Reports sent during a simulation
+#[derive(Clone, Debug, Default)]
+pub struct SimulationResult {
+    pub reports: Vec<TimedReport>,
+}
+
+impl SimulationResult {
+    /// Keyboard reports, without the times they were sent at
+    pub fn keyboard_reports(&self) -> Vec<KeyboardReport> {
+        self.timed_keyboard_reports().into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Keyboard reports, with the times they were sent at in milliseconds
+    pub fn timed_keyboard_reports(&self) -> Vec<(u64, KeyboardReport)> {
+        self.reports
+            .iter()
+            .filter_map(|r| match r.report.clone() {
+                Report::KeyboardReport(report) => Some((r.at_ms, report)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Keymap for a simulated keyboard, which lives until the end of the process.
+pub fn keymap<const ROW: usize, const COL: usize, const NUM_LAYER: usize>(
+    keymap: [[[KeyAction; COL]; ROW]; NUM_LAYER],
+    behavior_config: BehaviorConfig,
+) -> &'static KeyMap<'static> {
+    keymap_with_positional_config(keymap, behavior_config, PositionalConfig::default())
+}
+
+/// Keymap for a simulated keyboard with per-key settings, which lives until the end of the process.
+pub fn keymap_with_positional_config<const ROW: usize, const COL: usize, const NUM_LAYER: usize>(
+    keymap: [[[KeyAction; COL]; ROW]; NUM_LAYER],
+    behavior_config: BehaviorConfig,
+    positional_config: PositionalConfig<ROW, COL>,
+) -> &'static KeyMap<'static> {
+    let data = Box::leak(Box::new(KeymapData::new(keymap)));
+    let behavior_config = Box::leak(Box::new(behavior_config));
+    let positional_config = Box::leak(Box::new(positional_config));
+    Box::leak(Box::new(block_on(KeyMap::new(
+        data,
+        behavior_config,
+        positional_config,
+    ))))
+}
+
+/// Feed the `timeline` to the `keyboard`, and collect the reports it sends.
+///
+/// The simulated clock starts at 0, and the reports are routed to USB.
+#[cfg(not(feature = "_no_usb"))]
+pub fn simulate(keyboard: &mut Keyboard<'_>, timeline: &Timeline) -> SimulationResult {
+    block_on(async {
+        let publisher = KeyboardEvent::publisher_async();
+        publisher.clear();
+        USB_REPORT_CHANNEL.clear();
+        crate::state::set_usb_state(UsbState::Configured);
+
+        let reports = RefCell::new(Vec::new());
+        let feed = async {
+            for event in &timeline.events {
+                Timer::at(Instant::from_millis(event.at_ms)).await;
+                publisher
+                    .publish_async(KeyboardEvent::key(event.row, event.col, event.pressed))
+                    .await;
+            }
+            Timer::at(Instant::from_millis(timeline.now_ms)).await;
+        };
+        let collect = async {
+            loop {
+                let report = USB_REPORT_CHANNEL.receive().await;
+                reports.borrow_mut().push(TimedReport {
+                    at_ms: Instant::now().as_millis(),
+                    report,
+                });
+            }
+        };
+        // The keyboard and the collector run forever, the simulation ends with the timeline
+        select(feed, select(keyboard.run(), collect)).await;
+
+        // Reports sent at the very end of the timeline are still in the channel
+        while let Ok(report) = USB_REPORT_CHANNEL.try_receive() {
+            reports.borrow_mut().push(TimedReport {
+                at_ms: Instant::now().as_millis(),
+                report,
+            });
+        }
+        SimulationResult {
+            reports: reports.into_inner(),
+        }
+    })
+}
+
+/// Run `fut` to completion against the simulated clock, which starts at 0.
+///
+/// The clock moves forward whenever `fut` is waiting. The tests of RMK use it as well.
+///
+/// # Panics
+///
+/// When `fut` isn't done after 60s of simulated time, it's waiting for something other than the
+/// time, which never comes.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    MockDriver::get().reset();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = Box::pin(fut);
+    for _ in 0..MAX_ITERS {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+        MockDriver::get().advance(STEP);
+    }
+    panic!(
+        "block_on: future did not resolve within {} iterations ({} s of simulated time)",
+        MAX_ITERS,
+        (MAX_ITERS as u64 * STEP.as_micros()) / 1_000_000,
+    );
+}
+
+fn noop_waker() -> Waker {
+    // Safety: every function of the vtable is a no-op, the data pointer is never used
+    unsafe { Waker::from_raw(RAW) }
+}
+
+const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+
+const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
@@ -10,15 +10,17 @@ use embassy_time::{Duration, Timer, with_timeout};
 use heapless::VecView;
 use trouble_host::prelude::*;
 
-use crate::SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS;
 use crate::ble::{SLEEPING_STATE, update_ble_phy, update_conn_params};
 use crate::channel::FLASH_CHANNEL;
-use crate::event::{PeripheralConnectedEvent, SleepStateEvent, publish_event};
+use crate::event::{PeripheralConnectedEvent, PowerStateEvent, SleepStateEvent, publish_event};
 #[cfg(feature = "storage")]
 use crate::split::ble::PeerAddress;
 use crate::split::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
 use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
 use crate::storage::FlashOperationMessage;
+use crate::{
+    SPLIT_CENTRAL_SLEEP_TIMEOUT_SECONDS, SPLIT_CONN_INTERVAL_US, SPLIT_CONN_LATENCY, SPLIT_SUPERVISION_TIMEOUT_MS,
+};
 
 pub(crate) static STACK_STARTED: Signal<crate::RawMutex, bool> = Signal::new();
 pub(crate) static PERIPHERAL_FOUND: Signal<crate::RawMutex, (u8, BdAddr)> = Signal::new();
@@ -28,6 +30,9 @@ static START_SCANNING: Signal<crate::RawMutex, ()> = Signal::new();
 static STOP_SCANNING: Signal<crate::RawMutex, ()> = Signal::new();
 static SCANNING_MUTEX: Mutex<crate::RawMutex, ()> = Mutex::new(());
 
+/// Time for a peripheral to pair or to encrypt the split link after connecting
+const SPLIT_SECURITY_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Sleep management signal for BLE Split Central
 ///
 /// This signal serves dual purposes for sleep management:
@@ -179,9 +184,9 @@ pub(crate) async fn run_ble_peripheral_manager<
 
     loop {
         // Check until the address is available
-        let address = loop {
+        let peer_addr = loop {
             if let Some(Some(addr)) = addrs.borrow().get(peri_id) {
-                break Address::random(*addr);
+                break *addr;
             }
             if !START_SCANNING.signaled() {
                 START_SCANNING.signal(());
@@ -189,11 +194,20 @@ pub(crate) async fn run_ble_peripheral_manager<
             // Check again after 500ms
             embassy_time::Timer::after_millis(500).await;
         };
+        let address = Address::random(peer_addr);
         info!("Peripheral peer address: {:?}", address);
+        let peer = crate::split::ble::saved_peer(peri_id, peer_addr);
+        // The key of the split link, saved when the halves paired. It's added before every
+        // connection, because switching the host profile resets the bonds of the stack.
+        if let Some(bond) = peer.bond()
+            && let Err(e) = stack.add_bond_information(bond)
+        {
+            debug!("Add peripheral bond info error: {:?}", e);
+        }
 
         let mut central = stack.central();
         let config = ConnectConfig {
-            connect_params: defaul_central_conn_param(),
+            connect_params: split_conn_params(),
             scan_config: ScanConfig {
                 filter_accept_list: &[address],
                 ..Default::default()
@@ -230,7 +244,10 @@ pub(crate) async fn run_ble_peripheral_manager<
                     connected: true,
                 });
 
-                if let Err(e) =
+                if !secure_split_link(&conn, &peer).await {
+                    warn!("Split link to peripheral {} isn't encrypted, disconnecting", peri_id);
+                    conn.disconnect();
+                } else if let Err(e) =
                     run_central_manager_task::<_, _, ROW, COL, ROW_OFFSET, COL_OFFSET>(peri_id, stack, &conn).await
                 {
                     #[cfg(feature = "defmt")]
@@ -256,16 +273,69 @@ pub(crate) async fn run_ble_peripheral_manager<
     }
 }
 
-fn defaul_central_conn_param() -> RequestedConnParams {
+/// Connection parameters of the split link, set by `split_conn_*` in `[rmk]`.
+///
+/// The peripheral sends a key event at the next connection event, so the interval is the
+/// input latency the split link adds. The peripheral latency lets an idle peripheral skip
+/// connection events, it only delays the messages from the central like layer changes.
+fn split_conn_params() -> RequestedConnParams {
     RequestedConnParams {
-        min_connection_interval: Duration::from_micros(7500),
-        max_connection_interval: Duration::from_micros(7500),
-        max_latency: 30, // 225ms
-        supervision_timeout: Duration::from_secs(5),
+        min_connection_interval: Duration::from_micros(SPLIT_CONN_INTERVAL_US),
+        max_connection_interval: Duration::from_micros(SPLIT_CONN_INTERVAL_US),
+        max_latency: SPLIT_CONN_LATENCY,
+        supervision_timeout: Duration::from_millis(SPLIT_SUPERVISION_TIMEOUT_MS),
         ..Default::default()
     }
 }
 
+/// Encrypt the split link before any split message is exchanged, return whether it's encrypted.
+///
+/// A new peripheral pairs first, and the key is saved on both halves. A saved peripheral must
+/// encrypt the link with the saved key instead: pairing again would let any device that claims
+/// its address inject keys. Hold the `ClearPeer` key to pair a new peripheral.
+async fn secure_split_link<P: PacketPool>(conn: &Connection<'_, P>, peer: &PeerAddress) -> bool {
+    if let Err(e) = conn.set_bondable(true) {
+        error!("Set bondable error: {:?}", e);
+    }
+    if let Err(e) = conn.request_security() {
+        #[cfg(feature = "defmt")]
+        let e = defmt::Debug2Format(&e);
+        error!("Request split link security error: {:?}", e);
+        return false;
+    }
+    let paired = peer.ltk.is_some();
+    let secured = async {
+        loop {
+            match conn.next().await {
+                ConnectionEvent::Encrypted { .. } if paired => return true,
+                ConnectionEvent::PairingComplete { security_level, bond } if !paired => {
+                    info!("Paired with peripheral {}: {:?}", peer.peer_id, security_level);
+                    if let Some(bond) = bond {
+                        let peer = PeerAddress {
+                            ltk: Some(bond.ltk.0),
+                            ..peer.clone()
+                        };
+                        crate::split::ble::update_saved_peer(&peer);
+                        FLASH_CHANNEL.send(FlashOperationMessage::PeerAddress(peer)).await;
+                    }
+                    return true;
+                }
+                ConnectionEvent::PairingComplete { .. } => {
+                    error!("Saved peripheral {} paired again, ignoring it", peer.peer_id);
+                    return false;
+                }
+                ConnectionEvent::PairingFailed(e) => {
+                    error!("Pairing with peripheral {} failed: {:?}", peer.peer_id, e);
+                    return false;
+                }
+                ConnectionEvent::Disconnected { .. } => return false,
+                _ => (),
+            }
+        }
+    };
+    with_timeout(SPLIT_SECURITY_TIMEOUT, secured).await.unwrap_or(false)
+}
+
 async fn run_central_manager_task<
     'b,
     's: 'b,
@@ -286,7 +356,7 @@ async fn run_central_manager_task<
     update_ble_phy(stack, conn).await;
 
     info!("Updating connection parameters for peripheral");
-    update_conn_params(stack, conn, &defaul_central_conn_param()).await;
+    update_conn_params(stack, conn, &split_conn_params()).await;
 
     match select3(
         ble_central_task(&client, conn),
@@ -523,6 +593,7 @@ async fn sleep_manager_task<
             SLEEPING_STATE.store(true, Ordering::Release);
 
             publish_event(SleepStateEvent::new(true));
+            publish_event(PowerStateEvent::Sleep(true));
         } else {
             // Wait for activity to wake up (false signal means activity/wakeup)
             let signal_value = CENTRAL_SLEEP.wait().await;
@@ -531,9 +602,10 @@ async fn sleep_manager_task<
                 SLEEPING_STATE.store(false, Ordering::Release);
 
                 publish_event(SleepStateEvent::new(false));
+                publish_event(PowerStateEvent::Sleep(false));
 
                 // Restore normal connection parameters
-                update_conn_params(stack, conn, &defaul_central_conn_param()).await;
+                update_conn_params(stack, conn, &split_conn_params()).await;
             }
         }
     }
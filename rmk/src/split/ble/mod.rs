@@ -1,8 +1,49 @@
 pub mod central;
 pub mod peripheral;
 
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
 use postcard::experimental::max_size::MaxSize;
+use rmk_types::battery::BatteryStatus;
 use serde::{Deserialize, Serialize};
+use trouble_host::prelude::{Address, Identity, SecurityLevel};
+use trouble_host::{BondInformation, LongTermKey};
+
+use crate::RawMutex;
+
+/// Cached battery status of every peripheral, updated alongside every
+/// [`PeripheralBatteryEvent`](crate::event::PeripheralBatteryEvent) publish so host services can
+/// read the current values synchronously without subscribing to the event stream.
+static PERIPHERAL_BATTERY_STATUS: Mutex<RawMutex, Cell<[BatteryStatus; crate::SPLIT_PERIPHERALS_NUM]>> =
+    Mutex::new(Cell::new([BatteryStatus::Unavailable; crate::SPLIT_PERIPHERALS_NUM]));
+
+pub(crate) fn current_peripheral_battery_status(id: usize) -> BatteryStatus {
+    PERIPHERAL_BATTERY_STATUS.lock(|c| c.get().get(id).copied().unwrap_or(BatteryStatus::Unavailable))
+}
+
+pub(crate) fn update_peripheral_battery_status(id: usize, status: BatteryStatus) {
+    PERIPHERAL_BATTERY_STATUS.lock(|c| {
+        let mut statuses = c.get();
+        if let Some(slot) = statuses.get_mut(id) {
+            *slot = status;
+            c.set(statuses);
+        }
+    });
+}
+
+/// Lowest battery level among the peripherals, i.e. of the peripheral that needs charging first.
+pub(crate) fn lowest_peripheral_battery_level() -> Option<u8> {
+    PERIPHERAL_BATTERY_STATUS.lock(|c| {
+        c.get()
+            .iter()
+            .filter_map(|status| match status {
+                BatteryStatus::Available { level, .. } => *level,
+                BatteryStatus::Unavailable => None,
+            })
+            .min()
+    })
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -10,6 +51,8 @@ pub struct PeerAddress {
     pub peer_id: u8,
     pub is_valid: bool,
     pub address: [u8; 6],
+    /// Long term key of the encrypted split link, `None` until the halves have paired
+    pub ltk: Option<u128>,
 }
 
 impl PeerAddress {
@@ -18,6 +61,54 @@ impl PeerAddress {
             peer_id,
             is_valid,
             address,
+            ltk: None,
         }
     }
+
+    /// Bond of the split link for the BLE stack, if the halves have paired
+    pub(crate) fn bond(&self) -> Option<BondInformation> {
+        let ltk = self.ltk.filter(|_| self.is_valid)?;
+        Some(BondInformation::new(
+            Identity {
+                addr: Address::random(self.address),
+                irk: None,
+            },
+            LongTermKey(ltk),
+            SecurityLevel::Encrypted,
+            true,
+        ))
+    }
+}
+
+/// Saved peers of the central, with the key of every paired peripheral.
+///
+/// Filled from storage at startup and kept in sync with every pairing, so that the key
+/// can be handed to the BLE stack before the central connects to the peripheral.
+static PEERS: Mutex<RawMutex, Cell<[Option<([u8; 6], u128)>; crate::SPLIT_PERIPHERALS_NUM]>> =
+    Mutex::new(Cell::new([None; crate::SPLIT_PERIPHERALS_NUM]));
+
+/// The saved peer `id` of the central, with its key if it's the peripheral at `address`.
+pub(crate) fn saved_peer(id: usize, address: [u8; 6]) -> PeerAddress {
+    let ltk = PEERS.lock(|c| match c.get().get(id) {
+        Some(Some((addr, ltk))) if *addr == address => Some(*ltk),
+        _ => None,
+    });
+    PeerAddress {
+        ltk,
+        ..PeerAddress::new(id as u8, true, address)
+    }
+}
+
+/// Remember the key of the peripheral `peer.peer_id` of the central.
+pub(crate) fn update_saved_peer(peer: &PeerAddress) {
+    PEERS.lock(|c| {
+        let mut peers = c.get();
+        if let Some(slot) = peers.get_mut(peer.peer_id as usize) {
+            *slot = match peer.ltk {
+                Some(ltk) if peer.is_valid => Some((peer.address, ltk)),
+                _ => None,
+            };
+            c.set(peers);
+        }
+    });
 }
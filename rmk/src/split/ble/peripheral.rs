@@ -7,7 +7,8 @@ use trouble_host::prelude::*;
 
 #[cfg(feature = "storage")]
 use super::PeerAddress;
-use crate::event::{CentralConnectedEvent, KeyboardEvent, SubscribableEvent, publish_event};
+use crate::channel::FLASH_CHANNEL;
+use crate::event::{CentralConnectedEvent, KeyboardEvent, PowerStateEvent, SubscribableEvent, publish_event};
 use crate::split::driver::{SplitDriverError, SplitReader, SplitWriter};
 use crate::split::peripheral::SplitPeripheral;
 use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
@@ -29,21 +30,49 @@ pub(crate) struct BleSplitPeripheralServer {
     pub(crate) service: SplitBleService,
 }
 
+/// Queue the central to be saved, `false` if the flash queue is full.
+///
+/// Doesn't wait, as the driver's `read` is cancelled whenever the peripheral has a message to
+/// send, which could drop the save.
+fn save_central(central: PeerAddress) -> bool {
+    let saved = FLASH_CHANNEL
+        .try_send(crate::storage::FlashOperationMessage::PeerAddress(central))
+        .is_ok();
+    if !saved {
+        error!("Flash queue is full, the central isn't saved");
+    }
+    saved
+}
+
 /// BLE driver for split peripheral
+///
+/// Split messages are only exchanged over an encrypted link, the central encrypts it right
+/// after connecting.
 pub(crate) struct BleSplitPeripheralDriver<'stack, 'server, 'c, P: PacketPool> {
     message_to_peripheral: Characteristic<[u8; SPLIT_MESSAGE_MAX_SIZE]>,
     message_to_central: Characteristic<[u8; SPLIT_MESSAGE_MAX_SIZE]>,
     conn: &'c GattConnection<'stack, 'server, P>,
+    /// Whether the central has paired before, then it must not pair again
+    paired: bool,
 }
 
 impl<'stack, 'server, 'c, P: PacketPool> BleSplitPeripheralDriver<'stack, 'server, 'c, P> {
-    pub(crate) fn new(server: &'server BleSplitPeripheralServer, conn: &'c GattConnection<'stack, 'server, P>) -> Self {
+    pub(crate) fn new(
+        server: &'server BleSplitPeripheralServer,
+        conn: &'c GattConnection<'stack, 'server, P>,
+        paired: bool,
+    ) -> Self {
         Self {
             message_to_central: server.service.message_to_central,
             message_to_peripheral: server.service.message_to_peripheral,
             conn,
+            paired,
         }
     }
+
+    fn encrypted(&self) -> bool {
+        self.conn.raw().security_level().is_ok_and(|level| level.encrypted())
+    }
 }
 
 impl<'stack, 'server, 'c, P: PacketPool> SplitReader for BleSplitPeripheralDriver<'stack, 'server, 'c, P> {
@@ -55,6 +84,33 @@ impl<'stack, 'server, 'c, P: PacketPool> SplitReader for BleSplitPeripheralDrive
                     update_status(|c| *c = ConnectionStatus::new());
                     return Err(SplitDriverError::Disconnected);
                 }
+                GattConnectionEvent::PairingComplete { security_level, bond } => {
+                    if self.paired {
+                        // The central lost its key, like after a reflash which cleared its storage.
+                        // Forget the saved key too, so that the halves pair anew at the next connection
+                        warn!("Central paired again, forgetting its saved key");
+                        save_central(PeerAddress::new(0, false, [0; 6]));
+                        self.conn.raw().disconnect();
+                        return Err(SplitDriverError::Disconnected);
+                    }
+                    info!("Paired with the central: {:?}", security_level);
+                    if let Some(bond) = bond {
+                        self.paired = save_central(PeerAddress {
+                            ltk: Some(bond.ltk.0),
+                            ..PeerAddress::new(0, true, self.conn.raw().peer_address().addr.into_inner())
+                        });
+                    }
+                }
+                GattConnectionEvent::PairingFailed(e) => {
+                    error!("Pairing with the central failed: {:?}", e);
+                    if self.paired {
+                        // The keys of the halves don't match, forget the saved one to pair anew
+                        warn!("Forgetting the saved key of the central");
+                        save_central(PeerAddress::new(0, false, [0; 6]));
+                        self.conn.raw().disconnect();
+                        return Err(SplitDriverError::Disconnected);
+                    }
+                }
                 GattConnectionEvent::Gatt { event: gatt_event } => {
                     match &gatt_event {
                         GattEvent::Read(event) => {
@@ -62,7 +118,9 @@ impl<'stack, 'server, 'c, P: PacketPool> SplitReader for BleSplitPeripheralDrive
                         }
                         GattEvent::Write(event) => {
                             // Write to peripheral
-                            if event.handle() == self.message_to_peripheral.handle {
+                            if event.handle() == self.message_to_peripheral.handle && !self.encrypted() {
+                                warn!("Dropping split message over an unencrypted link");
+                            } else if event.handle() == self.message_to_peripheral.handle {
                                 trace!("Got message from central: {:?}", event.data());
                                 match postcard::from_bytes::<SplitMessage>(event.data()) {
                                     Ok(message) => {
@@ -106,6 +164,9 @@ impl<'stack, 'server, 'c, P: PacketPool> SplitReader for BleSplitPeripheralDrive
 
 impl<'stack, 'server, 'c, P: PacketPool> SplitWriter for BleSplitPeripheralDriver<'stack, 'server, 'c, P> {
     async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        if !self.encrypted() {
+            return Err(SplitDriverError::Unencrypted);
+        }
         let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
         postcard::to_slice(message, &mut buf).map_err(|e| {
             error!("Postcard serialize split message error: {}", e);
@@ -137,43 +198,56 @@ pub async fn initialize_nrf_ble_split_peripheral_and_run<'b, 's: 'b, C: Controll
     let runner = stack.runner();
 
     // First, read central address from storage
-    let mut central_addr = crate::storage::read_peer_address(0)
-        .await
-        .filter(|a| a.is_valid)
-        .map(|a| a.address);
+    let mut central = crate::storage::read_peer_address(0).await.filter(|a| a.is_valid);
+    // The key of the split link, saved when the halves paired
+    if let Some(bond) = central.as_ref().and_then(PeerAddress::bond)
+        && let Err(e) = stack.add_bond_information(bond)
+    {
+        error!("Add central bond info error: {:?}", e);
+    }
 
     let peri_task = async {
         let server = BleSplitPeripheralServer::new_default("rmk").unwrap();
         loop {
             update_status(|c| *c = ConnectionStatus::new());
             publish_event(CentralConnectedEvent { connected: false });
-            match split_peripheral_advertise(id, central_addr, &mut peripheral, &server).await {
+            let central_addr = central.as_ref().map(|c| c.address);
+            publish_event(PowerStateEvent::Advertising(true));
+            let advertised = split_peripheral_advertise(id, central_addr, &mut peripheral, &server).await;
+            publish_event(PowerStateEvent::Advertising(false));
+            match advertised {
                 Ok(conn) => {
                     info!("Connected to the central");
                     publish_event(CentralConnectedEvent { connected: true });
-                    let mut peripheral = SplitPeripheral::new(BleSplitPeripheralDriver::new(&server, &conn));
+                    if let Err(e) = conn.raw().set_bondable(true) {
+                        error!("Set bondable error: {:?}", e);
+                    }
                     let new_addr = conn.raw().peer_address().addr.into_inner();
                     if central_addr != Some(new_addr) {
                         info!("Saving central address to storage");
-                        if crate::storage::write_peer_address(PeerAddress {
-                            peer_id: 0,
-                            is_valid: true,
-                            address: new_addr,
-                        })
-                        .await
-                        {
-                            central_addr = Some(new_addr);
-                        }
+                        let new_central = PeerAddress::new(0, true, new_addr);
+                        FLASH_CHANNEL
+                            .send(crate::storage::FlashOperationMessage::PeerAddress(new_central.clone()))
+                            .await;
+                        central = Some(new_central);
                     }
+                    let paired = central
+                        .as_ref()
+                        .is_some_and(|c| c.address == new_addr && c.ltk.is_some());
+                    let mut peripheral = SplitPeripheral::new(BleSplitPeripheralDriver::new(&server, &conn, paired));
                     peripheral.run().await;
+                    // Pick up the key saved by a pairing, or the peer cleared by `ClearPeer`
+                    central = crate::storage::read_peer_address(0).await.filter(|a| a.is_valid);
                     info!("Disconnected from the central");
                 }
                 Err(BleHostError::BleHost(Error::Timeout)) => {
                     // Timeout, wait new keys to continue
                     error!("Connect to central timeout");
+                    publish_event(PowerStateEvent::Sleep(true));
                     let mut sub = KeyboardEvent::subscriber();
                     sub.clear();
                     let _ = sub.next_message_pure().await;
+                    publish_event(PowerStateEvent::Sleep(false));
                     continue;
                 }
                 Err(e) => {
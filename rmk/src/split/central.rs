@@ -1,6 +1,8 @@
 #[cfg(feature = "_ble")]
 use core::cell::RefCell;
 
+#[cfg(not(feature = "_ble"))]
+use embedded_hal_async::i2c::I2c;
 #[cfg(not(feature = "_ble"))]
 use embedded_io_async::{Read, Write};
 #[cfg(feature = "_ble")]
@@ -11,6 +13,8 @@ use {
     trouble_host::prelude::*,
 };
 
+use crate::split::transport::SplitTransport;
+
 /// Run central's peripheral manager task.
 ///
 /// # Arguments
@@ -50,3 +54,70 @@ pub async fn run_peripheral_manager<
         run_serial_peripheral_manager::<ROW, COL, ROW_OFFSET, COL_OFFSET, S>(id, receiver).await;
     };
 }
+
+/// Run central's peripheral manager task over a half-duplex single-wire serial port.
+///
+/// Messages are acknowledged and retransmitted, because both halves send on the same wire.
+/// The peripheral must run [`crate::split::peripheral::run_rmk_half_duplex_split_peripheral`].
+///
+/// # Arguments
+/// * `id` - peripheral id
+/// * `serial` - half-duplex serial port, e.g. `rmk::split::rp::uart::BufferedUart::new_half_duplex`
+#[cfg(not(feature = "_ble"))]
+pub async fn run_half_duplex_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    S: Read + Write,
+>(
+    id: usize,
+    serial: S,
+) {
+    crate::split::serial::run_half_duplex_peripheral_manager::<ROW, COL, ROW_OFFSET, COL_OFFSET, S>(id, serial).await;
+}
+
+/// Run central's peripheral manager task over I2C.
+///
+/// The central is the I2C controller and polls the peripheral every millisecond.
+/// The peripheral must run [`crate::split::peripheral::run_rmk_i2c_split_peripheral`].
+///
+/// # Arguments
+/// * `id` - peripheral id
+/// * `i2c` - I2C bus to the peripheral
+/// * `address` - 7-bit I2C address of the peripheral
+#[cfg(not(feature = "_ble"))]
+pub async fn run_i2c_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    I: I2c,
+>(
+    id: usize,
+    i2c: I,
+    address: u8,
+) {
+    crate::split::i2c::run_i2c_peripheral_manager::<ROW, COL, ROW_OFFSET, COL_OFFSET, I>(id, i2c, address).await;
+}
+
+/// Run central's peripheral manager task over a custom link.
+///
+/// The peripheral must run [`crate::split::peripheral::run_rmk_transport_split_peripheral`].
+///
+/// # Arguments
+/// * `id` - peripheral id
+/// * `transport` - link to the peripheral, see [`crate::split::transport::SplitTransport`]
+pub async fn run_transport_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    T: SplitTransport,
+>(
+    id: usize,
+    transport: T,
+) {
+    crate::split::transport::run_transport_peripheral_manager::<ROW, COL, ROW_OFFSET, COL_OFFSET, T>(id, transport)
+        .await;
+}
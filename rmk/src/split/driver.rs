@@ -1,10 +1,18 @@
 //! The abstracted driver layer of the split keyboard.
 //!
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either3, select3};
+use embassy_time::{Duration, Instant, Ticker};
 use futures::FutureExt;
 
 use super::SplitMessage;
-use crate::event::{KeyboardEvent, KeyboardEventPos, SubscribableEvent, publish_event, publish_event_async};
+use crate::event::{
+    KeyboardEvent, KeyboardEventPos, PeripheralConnectedEvent, SubscribableEvent, publish_event, publish_event_async,
+};
+
+/// Time between two heartbeats on links with heartbeats
+pub(crate) const SPLIT_HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+/// The other half is disconnected when nothing was received from it for this long
+pub(crate) const SPLIT_LINK_TIMEOUT: Duration = Duration::from_millis(1000);
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -15,9 +23,15 @@ pub(crate) enum SplitDriverError {
     SerializeError,
     BleError(u8),
     Disconnected,
+    /// The link isn't encrypted yet, so the message wasn't sent
+    Unencrypted,
 }
 
 /// Split message reader from other split devices
+///
+/// `read` is polled in a `select` with the messages to send and the heartbeat, and dropped
+/// whenever one of them is ready first, so it must be cancel-safe: the bytes of a partial
+/// message stay in the reader for the next call, like `SerialSplitDriver` keeps them.
 pub(crate) trait SplitReader {
     async fn read(&mut self) -> Result<SplitMessage, SplitDriverError>;
 }
@@ -45,13 +59,38 @@ pub(crate) struct PeripheralManager<
     transceiver: T,
     /// Peripheral id
     id: usize,
+    /// Send heartbeats and track the link with them, for links which don't notice a lost peer
+    heartbeat: bool,
+    /// The peripheral was heard from within `SPLIT_LINK_TIMEOUT`, only tracked with `heartbeat`
+    connected: bool,
+    /// Time of the last message from the peripheral
+    last_seen: Instant,
+    /// Keys of the peripheral which are pressed, released when the link is lost
+    pressed: [[bool; COL]; ROW],
+    /// Last indicator, layer, Caps Word and brightness sent, sent again when the link comes back
+    synced: [Option<SplitMessage>; 4],
 }
 
 impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFSET: usize, T: SplitReader + SplitWriter>
     PeripheralManager<ROW, COL, ROW_OFFSET, COL_OFFSET, T>
 {
     pub(crate) fn new(transceiver: T, id: usize) -> Self {
-        Self { transceiver, id }
+        Self {
+            transceiver,
+            id,
+            heartbeat: false,
+            connected: false,
+            last_seen: Instant::now(),
+            pressed: [[false; COL]; ROW],
+            synced: [None; 4],
+        }
+    }
+
+    /// Exchange heartbeats with the peripheral and publish `PeripheralConnectedEvent` when the
+    /// link comes and goes, for wired links.
+    pub(crate) fn with_heartbeat(mut self) -> Self {
+        self.heartbeat = true;
+        self
     }
 
     /// Send a message to the peripheral, returning Err on disconnect.
@@ -71,12 +110,16 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
     ///
     /// The manager receives from the peripheral and publishes input events.
     /// It also syncs the central's `ConnectionStatus` to the peripheral on every
-    /// change as an informational signal
+    /// change as an informational signal, and sends the synced state again when the
+    /// peripheral comes back after the link was lost.
     pub(crate) async fn run(mut self) {
         use crate::event::EventSubscriber;
 
         let mut indicator_sub = crate::event::LedIndicatorEvent::subscriber();
         let mut layer_sub = crate::event::LayerChangeEvent::subscriber();
+        let mut caps_word_sub = crate::event::CapsWordEvent::subscriber();
+        let mut brightness_sub = crate::event::BrightnessEvent::subscriber();
+        let mut cpi_sub = crate::event::PeripheralPointingSetCpiEvent::subscriber();
         // Subscribe before the initial send so any change racing past the
         // snapshot is still delivered to us.
         let mut connection_sub = crate::event::ConnectionStatusChangeEvent::subscriber();
@@ -90,6 +133,11 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
         #[cfg(feature = "display")]
         let mut sleep_sub = crate::event::SleepStateEvent::subscriber();
 
+        #[cfg(feature = "split_lighting")]
+        let mut action_sub = crate::event::ActionEvent::subscriber();
+        #[cfg(feature = "split_lighting")]
+        let mut animation_ticker = Ticker::every(crate::split::lighting::ANIMATION_TIME_SYNC_INTERVAL);
+
         // Send the current state once on startup so the peripheral matches us
         // even when no transition has happened since the central booted.
         if self
@@ -102,53 +150,155 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
             return;
         }
 
+        let id = self.id;
+        let heartbeat_enabled = self.heartbeat;
+        let mut ticker = Ticker::every(SPLIT_HEARTBEAT_INTERVAL);
         loop {
             // Use select_biased_with_feature to handle feature-gated subscriber arms
             let next_event_to_peri = async {
+                // Only the CPI changes of this peripheral's pointing devices are forwarded
+                let next_cpi = async {
+                    loop {
+                        let e = cpi_sub.next_event().await;
+                        if e.peripheral_id == id {
+                            break e;
+                        }
+                    }
+                };
                 crate::select_biased_with_feature! {
                     e = indicator_sub.next_event().fuse() => SplitMessage::KeyboardIndicator(e.0.into_bits()),
                     e = layer_sub.next_event().fuse() => SplitMessage::Layer(e.0),
+                    e = caps_word_sub.next_event().fuse() => SplitMessage::CapsWord(e.0),
+                    e = brightness_sub.next_event().fuse() => SplitMessage::Brightness(e.0),
+                    e = next_cpi.fuse() => SplitMessage::PointingSetCpi(crate::event::PointingSetCpiEvent {
+                        device_id: e.device_id,
+                        cpi: e.cpi,
+                    }),
                     e = connection_sub.next_event().fuse() => SplitMessage::ConnectionStatus(e.0),
                     with_feature("_ble"): _ = clear_peer_sub.next_event().fuse() => {
                         #[cfg(feature = "storage")]
                         {
                             use {crate::channel::FLASH_CHANNEL, crate::split::ble::PeerAddress, crate::storage::FlashOperationMessage};
-                            FLASH_CHANNEL
-                                .send(FlashOperationMessage::PeerAddress(PeerAddress::new(
-                                    self.id as u8,
-                                    false,
-                                    [0; 6],
-                                )))
-                                .await;
+                            // Forget the key of the split link as well, so that a new peripheral can pair
+                            let peer = PeerAddress::new(id as u8, false, [0; 6]);
+                            crate::split::ble::update_saved_peer(&peer);
+                            FLASH_CHANNEL.send(FlashOperationMessage::PeerAddress(peer)).await;
                         }
                         SplitMessage::ClearPeer
                     },
                     with_feature("display"): e = wpm_sub.next_event().fuse() => SplitMessage::Wpm(e.0),
                     with_feature("display"): e = modifier_sub.next_event().fuse() => SplitMessage::Modifier(e.modifier.into_bits()),
                     with_feature("display"): e = sleep_sub.next_event().fuse() => SplitMessage::SleepState(e.0),
+                    with_feature("split_lighting"): e = action_sub.next_event().fuse() => SplitMessage::Action(e),
+                    with_feature("split_lighting"): _ = animation_ticker.next().fuse() => {
+                        SplitMessage::AnimationTime(crate::split::lighting::animation_time().as_micros())
+                    },
+                }
+            };
+
+            let heartbeat = async {
+                if heartbeat_enabled {
+                    ticker.next().await
+                } else {
+                    core::future::pending().await
                 }
             };
 
-            match select(self.transceiver.read(), next_event_to_peri).await {
-                Either::First(read_result) => match read_result {
+            match select3(self.transceiver.read(), next_event_to_peri, heartbeat).await {
+                Either3::First(read_result) => match read_result {
                     Ok(split_message) => {
+                        if self.link_up().await.is_err() {
+                            break;
+                        }
                         self.process_peripheral_message(split_message).await;
                     }
                     Err(e) => {
                         error!("Peripheral message read error: {:?}", e);
                     }
                 },
-                Either::Second(msg) => {
+                Either3::Second(msg) => {
+                    self.remember(msg);
                     if self.send(&msg).await.is_err() {
-                        return;
+                        break;
+                    }
+                }
+                Either3::Third(()) => {
+                    if self.connected && self.last_seen.elapsed() > SPLIT_LINK_TIMEOUT {
+                        warn!("No message from peripheral {}, the link is lost", self.id);
+                        self.link_lost().await;
+                    }
+                    if self.send(&SplitMessage::Heartbeat).await.is_err() {
+                        break;
                     }
                 }
             }
         }
+        self.release_pressed_keys().await;
+    }
+
+    /// Keep the synced state in `msg`, to send it again when the link comes back.
+    fn remember(&mut self, msg: SplitMessage) {
+        let slot = match msg {
+            SplitMessage::KeyboardIndicator(_) => 0,
+            SplitMessage::Layer(_) => 1,
+            SplitMessage::CapsWord(_) => 2,
+            SplitMessage::Brightness(_) => 3,
+            _ => return,
+        };
+        self.synced[slot] = Some(msg);
+    }
+
+    /// Note a message from the peripheral. If the link was lost, the peripheral may have
+    /// restarted, so it gets the synced state again.
+    async fn link_up(&mut self) -> Result<(), ()> {
+        self.last_seen = Instant::now();
+        if !self.heartbeat || self.connected {
+            return Ok(());
+        }
+        info!("Peripheral {} connected", self.id);
+        self.connected = true;
+        publish_event(PeripheralConnectedEvent {
+            id: self.id,
+            connected: true,
+        });
+        self.send(&SplitMessage::ConnectionStatus(
+            crate::state::current_connection_status(),
+        ))
+        .await?;
+        for msg in self.synced.into_iter().flatten() {
+            self.send(&msg).await?;
+        }
+        Ok(())
+    }
+
+    async fn link_lost(&mut self) {
+        self.connected = false;
+        publish_event(PeripheralConnectedEvent {
+            id: self.id,
+            connected: false,
+        });
+        self.release_pressed_keys().await;
+    }
+
+    /// Release the keys of the peripheral which are still pressed, their releases were lost
+    /// with the link.
+    async fn release_pressed_keys(&mut self) {
+        for row in 0..ROW {
+            for col in 0..COL {
+                if core::mem::take(&mut self.pressed[row][col]) {
+                    publish_event_async(KeyboardEvent::key(
+                        (row + ROW_OFFSET) as u8,
+                        (col + COL_OFFSET) as u8,
+                        false,
+                    ))
+                    .await;
+                }
+            }
+        }
     }
 
     /// Process a single message from the peripheral.
-    async fn process_peripheral_message(&self, split_message: SplitMessage) {
+    async fn process_peripheral_message(&mut self, split_message: SplitMessage) {
         trace!("Got message from peripheral: {:?}", split_message);
         match split_message {
             SplitMessage::Key(e) => match e.pos {
@@ -158,6 +308,7 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
                         error!("Invalid peripheral row/col: {} {}", key_pos.row, key_pos.col);
                         return;
                     }
+                    self.pressed[key_pos.row as usize][key_pos.col as usize] = e.pressed;
 
                     let adjusted_key_event = KeyboardEvent::key(
                         key_pos.row + ROW_OFFSET as u8,
@@ -176,8 +327,14 @@ impl<const ROW: usize, const COL: usize, const ROW_OFFSET: usize, const COL_OFFS
             #[cfg(feature = "_ble")]
             SplitMessage::BatteryStatus(state) => {
                 use crate::event::PeripheralBatteryEvent;
+                crate::split::ble::update_peripheral_battery_status(self.id, state.0);
                 publish_event(PeripheralBatteryEvent { id: self.id, state })
             }
+            // Late messages of the role negotiation
+            #[cfg(not(feature = "_ble"))]
+            SplitMessage::RoleClaim(_) | SplitMessage::RoleDecided => (),
+            // Noted by `link_up` already
+            SplitMessage::Heartbeat => (),
             _ => warn!("{:?} should not come from peripheral", split_message),
         }
     }
@@ -0,0 +1,344 @@
+//! Split link over I2C, for TRRS boards wired for I2C instead of serial.
+//!
+//! The central is the I2C controller and polls the peripheral, the peripheral is an I2C
+//! target that answers every read with the next message to the central. Both directions
+//! carry the same [`SplitMessage`]s as the serial transports, in frames of a fixed size:
+//!
+//! | Byte      | Content                                             |
+//! |-----------|-----------------------------------------------------|
+//! | 0         | Length of the message, 0 if the peripheral has none |
+//! | 1..=len   | Postcard serialized message                         |
+//!
+//! I2C frames are checked by the bus itself, so neither COBS nor sequence numbers are needed.
+
+use core::future::Future;
+
+use embassy_futures::join::join;
+use embassy_futures::select::{Either, select};
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+use heapless::Deque;
+use postcard::experimental::max_size::MaxSize;
+
+use crate::split::SplitMessage;
+use crate::split::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
+
+/// Size of every frame read from or written to the peripheral
+pub const I2C_FRAME_SIZE: usize = SplitMessage::POSTCARD_MAX_SIZE + 1;
+/// Time between two polls of the peripheral
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Time before polling again when the peripheral doesn't answer, e.g. the cable is unplugged
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+/// Messages from the central kept by the peripheral while it waits for a poll to send its own
+const INBOX_SIZE: usize = 4;
+/// Messages queued between the I2C bus and the peripheral manager on the central
+const CENTRAL_QUEUE_SIZE: usize = 4;
+
+/// Request of the I2C controller to the peripheral
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum I2cTargetRequest {
+    /// The controller wants to read a frame
+    Read,
+    /// The controller wrote this many bytes into the buffer passed to [`I2cTarget::listen`]
+    Write(usize),
+}
+
+/// I2C target of a split peripheral.
+///
+/// embedded-hal has no trait for the target side of I2C, so implement this one for the I2C
+/// driver of your chip. It's implemented for `embassy_rp::i2c_slave::I2cSlave` with the
+/// `rp2040` feature.
+pub trait I2cTarget {
+    type Error: core::fmt::Debug;
+
+    /// Wait for the next request addressed to this target.
+    fn listen(&mut self, buf: &mut [u8]) -> impl Future<Output = Result<I2cTargetRequest, Self::Error>>;
+
+    /// Answer a [`I2cTargetRequest::Read`] with `data`.
+    fn respond(&mut self, data: &[u8]) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+#[cfg(feature = "rp2040")]
+impl<'d, T: embassy_rp::i2c::Instance> I2cTarget for embassy_rp::i2c_slave::I2cSlave<'d, T> {
+    type Error = embassy_rp::i2c_slave::Error;
+
+    async fn listen(&mut self, buf: &mut [u8]) -> Result<I2cTargetRequest, Self::Error> {
+        use embassy_rp::i2c_slave::Command;
+        loop {
+            match embassy_rp::i2c_slave::I2cSlave::listen(self, buf).await? {
+                Command::Read => return Ok(I2cTargetRequest::Read),
+                Command::Write(len) => return Ok(I2cTargetRequest::Write(len)),
+                // The central never sends a write-read or a general call
+                Command::WriteRead(_) | Command::GeneralCall(_) => (),
+            }
+        }
+    }
+
+    async fn respond(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.respond_and_fill(data, 0).await.map(|_| ())
+    }
+}
+
+/// Put a message into a frame.
+fn encode(message: &SplitMessage, frame: &mut [u8; I2C_FRAME_SIZE]) -> Result<(), SplitDriverError> {
+    let len = postcard::to_slice(message, &mut frame[1..])
+        .map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?
+        .len();
+    frame[0] = len as u8;
+    Ok(())
+}
+
+/// Take the message out of a frame, `None` for an empty frame.
+fn decode(frame: &[u8]) -> Result<Option<SplitMessage>, SplitDriverError> {
+    let len = match frame.first() {
+        None | Some(0) => return Ok(None),
+        Some(&len) => len as usize,
+    };
+    let payload = frame.get(1..=len).ok_or(SplitDriverError::DeserializeError)?;
+    postcard::from_bytes(payload)
+        .map(Some)
+        .map_err(|_| SplitDriverError::DeserializeError)
+}
+
+/// Queues between the I2C bus, which only [`poll_peripheral`] touches, and the peripheral manager
+struct CentralQueues {
+    received: Channel<crate::RawMutex, SplitMessage, CENTRAL_QUEUE_SIZE>,
+    /// Encoded frames
+    to_send: Channel<crate::RawMutex, [u8; I2C_FRAME_SIZE], CENTRAL_QUEUE_SIZE>,
+}
+
+/// Poll the peripheral and write the messages to it.
+///
+/// A dropped I2C transfer loses the frame which the peripheral already handed over, so the bus is
+/// driven here rather than in [`SplitReader::read`], which the peripheral manager cancels whenever
+/// it has something to send.
+async fn poll_peripheral<I: I2c>(mut i2c: I, address: u8, queues: &CentralQueues) -> ! {
+    let mut frame = [0_u8; I2C_FRAME_SIZE];
+    loop {
+        match select(queues.to_send.receive(), Timer::after(POLL_INTERVAL)).await {
+            Either::First(out) => {
+                let len = out[0] as usize + 1;
+                if i2c.write(address, &out[..len]).await.is_err() {
+                    warn!("Failed to write a split message to the peripheral");
+                }
+            }
+            // Waiting for room in `received` here would keep the frames in `to_send` from being
+            // written, which the peripheral manager may be waiting for, so the poll is skipped
+            Either::Second(()) if queues.received.is_full() => (),
+            Either::Second(()) => {
+                if i2c.read(address, &mut frame).await.is_err() {
+                    Timer::after(RETRY_INTERVAL).await;
+                    continue;
+                }
+                match decode(&frame) {
+                    // Only this task sends to `received`, which had room
+                    Ok(Some(message)) => {
+                        let _ = queues.received.try_send(message);
+                    }
+                    Ok(None) => (),
+                    Err(e) => error!("Peripheral message decode error: {:?}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Split driver of the central, exchanging messages with [`poll_peripheral`]
+struct I2cSplitCentralDriver<'a> {
+    queues: &'a CentralQueues,
+}
+
+impl SplitReader for I2cSplitCentralDriver<'_> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        Ok(self.queues.received.receive().await)
+    }
+}
+
+impl SplitWriter for I2cSplitCentralDriver<'_> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        encode(message, &mut frame)?;
+        self.queues.to_send.send(frame).await;
+        Ok(frame[0] as usize + 1)
+    }
+}
+
+/// I2C target side of the split link, used by the peripheral
+pub(crate) struct I2cSplitPeripheralDriver<T> {
+    target: T,
+    /// Messages written by the central while waiting to send a message
+    inbox: Deque<SplitMessage, INBOX_SIZE>,
+}
+
+impl<T: I2cTarget> I2cSplitPeripheralDriver<T> {
+    pub(crate) fn new(target: T) -> Self {
+        Self {
+            target,
+            inbox: Deque::new(),
+        }
+    }
+}
+
+impl<T: I2cTarget> SplitReader for I2cSplitPeripheralDriver<T> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        if let Some(message) = self.inbox.pop_front() {
+            return Ok(message);
+        }
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        loop {
+            let request = self
+                .target
+                .listen(&mut frame)
+                .await
+                .map_err(|_| SplitDriverError::SerialError)?;
+            match request {
+                I2cTargetRequest::Write(len) => {
+                    if let Some(message) = decode(&frame[..len.min(I2C_FRAME_SIZE)])? {
+                        return Ok(message);
+                    }
+                }
+                // Nothing to send, answer the poll with an empty frame
+                I2cTargetRequest::Read => self
+                    .target
+                    .respond(&[0])
+                    .await
+                    .map_err(|_| SplitDriverError::SerialError)?,
+            }
+        }
+    }
+}
+
+impl<T: I2cTarget> SplitWriter for I2cSplitPeripheralDriver<T> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        encode(message, &mut frame)?;
+        let mut buf = [0_u8; I2C_FRAME_SIZE];
+        // Wait for the next poll of the central
+        loop {
+            let request = self
+                .target
+                .listen(&mut buf)
+                .await
+                .map_err(|_| SplitDriverError::SerialError)?;
+            match request {
+                I2cTargetRequest::Read => {
+                    self.target
+                        .respond(&frame)
+                        .await
+                        .map_err(|_| SplitDriverError::SerialError)?;
+                    return Ok(frame[0] as usize + 1);
+                }
+                I2cTargetRequest::Write(len) => {
+                    if let Some(message) = decode(&buf[..len.min(I2C_FRAME_SIZE)])? {
+                        if let Err(message) = self.inbox.push_back(message) {
+                            // The central writes faster than the peripheral reads, keep the newest
+                            let dropped = self.inbox.pop_front();
+                            warn!("Split inbox full, dropping {:?}", dropped);
+                            let _ = self.inbox.push_back(message);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Receive split messages from a peripheral by polling it over I2C, and process them.
+///
+/// Generic parameters:
+/// - `const ROW`: row number of the peripheral's matrix
+/// - `const COL`: column number of the peripheral's matrix
+/// - `const ROW_OFFSET`: row offset of the peripheral's matrix in the whole matrix
+/// - `const COL_OFFSET`: column offset of the peripheral's matrix in the whole matrix
+/// - `I`: an I2C bus that implements `I2c` trait in embedded-hal-async
+pub(crate) async fn run_i2c_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    I: I2c,
+>(
+    id: usize,
+    i2c: I,
+    address: u8,
+) {
+    let queues = CentralQueues {
+        received: Channel::new(),
+        to_send: Channel::new(),
+    };
+    let split_driver = I2cSplitCentralDriver { queues: &queues };
+    let peripheral_manager =
+        PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_driver, id).with_heartbeat();
+    info!("Running I2C peripheral manager {} at address {:#x}", id, address);
+
+    join(poll_peripheral(i2c, address, &queues), peripheral_manager.run()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_round_trip() {
+        let mut frame = [0_u8; I2C_FRAME_SIZE];
+        encode(&SplitMessage::Layer(3), &mut frame).unwrap();
+        assert!(frame[0] > 0);
+        assert!(matches!(decode(&frame), Ok(Some(SplitMessage::Layer(3)))));
+    }
+
+    /// Replays the requests of a central, keeping the responses
+    struct FakeTarget {
+        requests: heapless::Deque<(I2cTargetRequest, SplitMessage), 8>,
+        responses: usize,
+    }
+
+    impl I2cTarget for FakeTarget {
+        type Error = ();
+
+        async fn listen(&mut self, buf: &mut [u8]) -> Result<I2cTargetRequest, ()> {
+            let (request, message) = self.requests.pop_front().ok_or(())?;
+            let mut frame = [0_u8; I2C_FRAME_SIZE];
+            encode(&message, &mut frame).unwrap();
+            buf.copy_from_slice(&frame);
+            Ok(request)
+        }
+
+        async fn respond(&mut self, _data: &[u8]) -> Result<(), ()> {
+            self.responses += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn peripheral_keeps_messages_written_while_sending() {
+        let mut requests = heapless::Deque::new();
+        for message in [SplitMessage::Layer(1), SplitMessage::CapsWord(true)] {
+            requests
+                .push_back((I2cTargetRequest::Write(I2C_FRAME_SIZE), message))
+                .unwrap();
+        }
+        requests
+            .push_back((I2cTargetRequest::Read, SplitMessage::Heartbeat))
+            .unwrap();
+        let mut driver = I2cSplitPeripheralDriver::new(FakeTarget { requests, responses: 0 });
+
+        crate::simulation::block_on(async {
+            driver.write(&SplitMessage::Heartbeat).await.unwrap();
+            assert_eq!(driver.target.responses, 1);
+            assert!(matches!(driver.read().await, Ok(SplitMessage::Layer(1))));
+            assert!(matches!(driver.read().await, Ok(SplitMessage::CapsWord(true))));
+        });
+    }
+
+    #[test]
+    fn empty_and_truncated_frames() {
+        assert!(matches!(decode(&[0; I2C_FRAME_SIZE]), Ok(None)));
+        assert!(matches!(decode(&[]), Ok(None)));
+        assert!(matches!(decode(&[4, 1]), Err(SplitDriverError::DeserializeError)));
+    }
+}
@@ -0,0 +1,37 @@
+//! Shared state for lighting effects which span the split halves.
+//!
+//! Lighting processors run on every half, each with its own LEDs. To render one effect
+//! instead of drifting ones, they animate with [`animation_time`] instead of their local
+//! clock: the central sends its time to the peripherals every [`ANIMATION_TIME_SYNC_INTERVAL`],
+//! so that all halves share it. The central also forwards every [`crate::event::ActionEvent`]
+//! to the peripherals as a [`crate::event::CentralActionEvent`], so that effect changes from
+//! `LightAction`s and the keys for reactive effects, in keymap coordinates, reach the lighting
+//! processors of all halves which subscribe to it.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::RawMutex;
+
+/// Time between two animation time syncs from the central
+pub(crate) const ANIMATION_TIME_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time of the central minus the local time in µs, 0 on the central
+static CENTRAL_TIME_OFFSET: Mutex<RawMutex, Cell<i64>> = Mutex::new(Cell::new(0));
+
+/// Time of the animations, the same on every half.
+///
+/// It's the uptime of the central, the peripherals follow it within the latency of the split link.
+pub fn animation_time() -> Duration {
+    let local = Instant::now().as_micros() as i64;
+    let offset = CENTRAL_TIME_OFFSET.lock(|o| o.get());
+    Duration::from_micros(local.saturating_add(offset).max(0) as u64)
+}
+
+/// Follow the animation time of the central, `central_us` is its time in µs.
+pub(crate) fn sync_animation_time(central_us: u64) {
+    let offset = central_us as i64 - Instant::now().as_micros() as i64;
+    CENTRAL_TIME_OFFSET.lock(|o| o.set(offset));
+}
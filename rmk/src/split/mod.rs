@@ -2,20 +2,29 @@ use postcard::experimental::max_size::MaxSize;
 use rmk_types::connection::ConnectionStatus;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "split_lighting")]
+use crate::event::ActionEvent;
 #[cfg(feature = "_ble")]
 use crate::event::BatteryStatusEvent;
-use crate::event::{KeyboardEvent, PointingEvent};
+use crate::event::{KeyboardEvent, PointingEvent, PointingSetCpiEvent};
 
 #[cfg(feature = "_ble")]
 pub mod ble;
 pub mod central;
 /// Common abstraction layer of split driver
 pub(crate) mod driver;
+#[cfg(not(feature = "_ble"))]
+pub mod i2c;
+#[cfg(feature = "split_lighting")]
+pub mod lighting;
 pub mod peripheral;
+#[cfg(not(feature = "_ble"))]
+pub mod role;
 #[cfg(feature = "rp2040")]
 pub mod rp;
 #[cfg(not(feature = "_ble"))]
 pub mod serial;
+pub mod transport;
 
 /// Maximum size of a split message
 pub const SPLIT_MESSAGE_MAX_SIZE: usize = SplitMessage::POSTCARD_MAX_SIZE + 4;
@@ -42,6 +51,12 @@ pub(crate) enum SplitMessage {
     KeyboardIndicator(u8),
     /// Layer number from central to peripheral
     Layer(u8),
+    /// Caps Word state from central to peripheral
+    CapsWord(bool),
+    /// Brightness level from central to peripheral
+    Brightness(u8),
+    /// CPI of a pointing device on the peripheral, from central to peripheral
+    PointingSetCpi(PointingSetCpiEvent),
     /// WPM from central to peripheral
     #[cfg(feature = "display")]
     Wpm(u16),
@@ -54,4 +69,18 @@ pub(crate) enum SplitMessage {
     /// Battery status, from peripheral to central
     #[cfg(feature = "_ble")]
     BatteryStatus(BatteryStatusEvent),
+    /// Action of a key on the central, from central to peripheral for lighting effects
+    #[cfg(feature = "split_lighting")]
+    Action(ActionEvent),
+    /// Animation time of the central in µs, from central to peripheral
+    #[cfg(feature = "split_lighting")]
+    AnimationTime(u64),
+    /// Sent by both halves of wired links to tell that they are alive
+    Heartbeat,
+    /// Role claim of a half, in the role negotiation at boot
+    #[cfg(not(feature = "_ble"))]
+    RoleClaim(role::RoleClaim),
+    /// The half picked its role, ends the role negotiation
+    #[cfg(not(feature = "_ble"))]
+    RoleDecided,
 }
@@ -1,6 +1,7 @@
 #[cfg(feature = "_ble")]
 use bt_hci::{cmd::le::LeSetPhy, controller::ControllerCmdAsync};
-use embassy_futures::select::{Either, select};
+use embassy_futures::select::{Either3, select3};
+use embassy_time::{Duration, Instant, Ticker};
 #[cfg(not(feature = "_ble"))]
 use embedded_io_async::{Read, Write};
 use futures::FutureExt;
@@ -14,14 +15,18 @@ use {
 };
 
 use super::SplitMessage;
-use super::driver::{SplitReader, SplitWriter};
+use super::driver::{SPLIT_HEARTBEAT_INTERVAL, SPLIT_LINK_TIMEOUT, SplitReader, SplitWriter};
 use crate::event::{
-    KeyboardEvent, LayerChangeEvent, LedIndicatorEvent, PointingEvent, SubscribableEvent, publish_event,
+    BrightnessEvent, CapsWordEvent, CentralConnectedEvent, KeyboardEvent, LayerChangeEvent, LedIndicatorEvent,
+    PointingEvent, SubscribableEvent, publish_event,
 };
 #[cfg(feature = "display")]
-use crate::event::{ModifierEvent, SleepStateEvent, WpmUpdateEvent};
+use crate::event::{ModifierEvent, PowerStateEvent, SleepStateEvent, WpmUpdateEvent};
 #[cfg(not(feature = "_ble"))]
-use crate::split::serial::SerialSplitDriver;
+use crate::split::i2c::{I2cSplitPeripheralDriver, I2cTarget};
+#[cfg(not(feature = "_ble"))]
+use crate::split::serial::{SerialSplitDriver, half_duplex::HalfDuplexSplitDriver};
+use crate::split::transport::{SplitTransport, TransportSplitDriver};
 use crate::state::update_status;
 
 /// Run the split peripheral service.
@@ -47,7 +52,7 @@ pub async fn run_rmk_split_peripheral<
 {
     #[cfg(not(feature = "_ble"))]
     {
-        let mut peripheral = SplitPeripheral::new(SerialSplitDriver::new(serial));
+        let mut peripheral = SplitPeripheral::new(SerialSplitDriver::new(serial)).with_heartbeat();
         loop {
             peripheral.run().await;
         }
@@ -57,14 +62,84 @@ pub async fn run_rmk_split_peripheral<
     crate::split::ble::peripheral::initialize_nrf_ble_split_peripheral_and_run(id, stack).await;
 }
 
+/// Run the split peripheral service over a half-duplex single-wire serial port.
+///
+/// The central must run [`crate::split::central::run_half_duplex_peripheral_manager`].
+#[cfg(not(feature = "_ble"))]
+pub async fn run_rmk_half_duplex_split_peripheral<S: Write + Read>(serial: S) {
+    let mut peripheral = SplitPeripheral::new(HalfDuplexSplitDriver::new(serial, false)).with_heartbeat();
+    loop {
+        peripheral.run().await;
+    }
+}
+
+/// Run the split peripheral service as an I2C target, polled by the central.
+///
+/// The central must run [`crate::split::central::run_i2c_peripheral_manager`] with the
+/// address `target` listens on, e.g. an `embassy_rp::i2c_slave::I2cSlave`.
+#[cfg(not(feature = "_ble"))]
+pub async fn run_rmk_i2c_split_peripheral<T: I2cTarget>(target: T) {
+    let mut peripheral = SplitPeripheral::new(I2cSplitPeripheralDriver::new(target)).with_heartbeat();
+    loop {
+        peripheral.run().await;
+    }
+}
+
+/// Run the split peripheral service over a custom link.
+///
+/// The central must run [`crate::split::central::run_transport_peripheral_manager`].
+pub async fn run_rmk_transport_split_peripheral<T: SplitTransport>(transport: T) {
+    let mut peripheral = SplitPeripheral::new(TransportSplitDriver::new(transport)).with_heartbeat();
+    loop {
+        peripheral.run().await;
+    }
+}
+
 /// The split peripheral instance.
 pub(crate) struct SplitPeripheral<S: SplitWriter + SplitReader> {
     split_driver: S,
+    /// Send heartbeats and track the link with them, for links which don't notice a lost peer
+    heartbeat: bool,
+    /// Send heartbeats at this interval without tracking the link, for links where the central
+    /// can only answer the writes of the peripheral
+    poll_interval: Option<Duration>,
+    /// The central was heard from within `SPLIT_LINK_TIMEOUT`, only tracked with `heartbeat`
+    connected: bool,
+    /// Time of the last message from the central
+    last_seen: Instant,
 }
 
 impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
     pub(crate) fn new(split_driver: S) -> Self {
-        Self { split_driver }
+        Self {
+            split_driver,
+            heartbeat: false,
+            poll_interval: None,
+            connected: false,
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Exchange heartbeats with the central and publish `CentralConnectedEvent` when the
+    /// link comes and goes, for wired links.
+    pub(crate) fn with_heartbeat(mut self) -> Self {
+        self.heartbeat = true;
+        self
+    }
+
+    /// Poll the central with heartbeats every `interval`, for links like ESB where the central
+    /// answers in the ACKs of the peripheral's packets.
+    pub(crate) fn with_poll(mut self, interval: Duration) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    fn set_connected(&mut self, connected: bool) {
+        if self.connected != connected {
+            info!("Central connected: {}", connected);
+            self.connected = connected;
+            publish_event(CentralConnectedEvent { connected });
+        }
     }
 
     /// Run the peripheral keyboard service.
@@ -79,6 +154,13 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
         #[cfg(feature = "_ble")]
         let mut battery_sub = BatteryStatusEvent::subscriber();
 
+        let heartbeat_enabled = self.heartbeat;
+        let mut ticker = if heartbeat_enabled {
+            Some(SPLIT_HEARTBEAT_INTERVAL)
+        } else {
+            self.poll_interval
+        }
+        .map(Ticker::every);
         loop {
             let read_message_to_send = async {
                 crate::select_biased_with_feature! {
@@ -94,49 +176,77 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                 }
             };
 
-            match select(self.split_driver.read(), read_message_to_send).await {
-                Either::First(m) => match m {
+            let heartbeat = async {
+                match ticker.as_mut() {
+                    Some(ticker) => ticker.next().await,
+                    None => core::future::pending().await,
+                }
+            };
+
+            match select3(self.split_driver.read(), read_message_to_send, heartbeat).await {
+                Either3::First(m) => match m {
                     // Process split messages from the central
-                    Ok(split_message) => match split_message {
-                        SplitMessage::ConnectionStatus(status) => {
-                            trace!("Received central connection status: {:?}", status);
-                            update_status(|c| *c = status);
-                        }
-                        #[cfg(all(feature = "_ble", feature = "storage"))]
-                        SplitMessage::ClearPeer => {
-                            // Clear the peer address
-                            FLASH_CHANNEL
-                                .send(crate::storage::FlashOperationMessage::PeerAddress(PeerAddress::new(
-                                    0, false, [0; 6],
-                                )))
-                                .await;
+                    Ok(split_message) => {
+                        if heartbeat_enabled {
+                            self.last_seen = Instant::now();
+                            self.set_connected(true);
                         }
-                        SplitMessage::KeyboardIndicator(indicator) => {
-                            // Publish KeyboardIndicator event
-                            publish_event(LedIndicatorEvent::new(
-                                rmk_types::led_indicator::LedIndicator::from_bits(indicator),
-                            ));
+                        match split_message {
+                            SplitMessage::ConnectionStatus(status) => {
+                                trace!("Received central connection status: {:?}", status);
+                                update_status(|c| *c = status);
+                            }
+                            #[cfg(all(feature = "_ble", feature = "storage"))]
+                            SplitMessage::ClearPeer => {
+                                // Clear the peer address
+                                FLASH_CHANNEL
+                                    .send(crate::storage::FlashOperationMessage::PeerAddress(PeerAddress::new(
+                                        0, false, [0; 6],
+                                    )))
+                                    .await;
+                            }
+                            SplitMessage::KeyboardIndicator(indicator) => {
+                                // Publish KeyboardIndicator event
+                                publish_event(LedIndicatorEvent::new(
+                                    rmk_types::led_indicator::LedIndicator::from_bits(indicator),
+                                ));
+                            }
+                            SplitMessage::Layer(layer) => {
+                                // Publish Layer event
+                                publish_event(LayerChangeEvent::new(layer));
+                            }
+                            SplitMessage::CapsWord(active) => {
+                                publish_event(CapsWordEvent::new(active));
+                            }
+                            SplitMessage::Brightness(brightness) => {
+                                publish_event(BrightnessEvent::new(brightness));
+                            }
+                            SplitMessage::PointingSetCpi(e) => {
+                                // Reaches the pointing devices of the peripheral
+                                publish_event(e);
+                            }
+                            #[cfg(feature = "display")]
+                            SplitMessage::Wpm(wpm) => {
+                                publish_event(WpmUpdateEvent::new(wpm));
+                            }
+                            #[cfg(feature = "display")]
+                            SplitMessage::Modifier(bits) => {
+                                publish_event(ModifierEvent {
+                                    modifier: rmk_types::modifier::ModifierCombination::from_bits(bits),
+                                });
+                            }
+                            #[cfg(feature = "display")]
+                            SplitMessage::SleepState(sleeping) => {
+                                publish_event(SleepStateEvent::new(sleeping));
+                                publish_event(PowerStateEvent::Sleep(sleeping));
+                            }
+                            #[cfg(feature = "split_lighting")]
+                            SplitMessage::Action(e) => publish_event(crate::event::CentralActionEvent(e)),
+                            #[cfg(feature = "split_lighting")]
+                            SplitMessage::AnimationTime(us) => crate::split::lighting::sync_animation_time(us),
+                            _ => (),
                         }
-                        SplitMessage::Layer(layer) => {
-                            // Publish Layer event
-                            publish_event(LayerChangeEvent::new(layer));
-                        }
-                        #[cfg(feature = "display")]
-                        SplitMessage::Wpm(wpm) => {
-                            publish_event(WpmUpdateEvent::new(wpm));
-                        }
-                        #[cfg(feature = "display")]
-                        SplitMessage::Modifier(bits) => {
-                            publish_event(ModifierEvent {
-                                modifier: rmk_types::modifier::ModifierCombination::from_bits(bits),
-                            });
-                        }
-                        #[cfg(feature = "display")]
-                        SplitMessage::SleepState(sleeping) => {
-                            publish_event(SleepStateEvent::new(sleeping));
-                        }
-                        _ => (),
-                    },
+                    }
                     Err(e) => {
                         error!("Split message read error: {:?}", e);
                         if let crate::split::driver::SplitDriverError::Disconnected = e {
@@ -144,9 +254,18 @@ impl<S: SplitWriter + SplitReader> SplitPeripheral<S> {
                         }
                     }
                 },
-                Either::Second(e) => {
+                Either3::Second(e) => {
                     debug!("Writing split message {:?} to central", e);
-                    self.split_driver.write(&e).await.ok();
+                    if let Err(err) = self.split_driver.write(&e).await {
+                        warn!("Failed to write split message {:?} to central: {:?}", e, err);
+                    }
+                }
+                Either3::Third(()) => {
+                    if self.connected && self.last_seen.elapsed() > SPLIT_LINK_TIMEOUT {
+                        warn!("No message from the central, the link is lost");
+                        self.set_connected(false);
+                    }
+                    self.split_driver.write(&SplitMessage::Heartbeat).await.ok();
                 }
             }
         }
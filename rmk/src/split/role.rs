@@ -0,0 +1,138 @@
+//! Role negotiation of wired split halves at boot.
+//!
+//! Instead of fixing the central at compile time, both halves can run the same firmware and
+//! pick their roles over the split link: the half with USB plugged in becomes the central, the
+//! other one the peripheral. Both halves send a role claim until they have the claim of the
+//! other half, then a `RoleDecided` message until the other half has decided too:
+//!
+//! | Claims                       | Central                         |
+//! |------------------------------|---------------------------------|
+//! | One half has USB             | The half with USB               |
+//! | Both or neither half has USB | The half with the higher `seed` |
+//!
+//! A half that gets no claim within [`NEGOTIATION_TIMEOUT`], e.g. because it restarted while the
+//! other half kept running, decides alone: it's the central if it has USB. A half that decided
+//! but didn't hear from the other half again keeps the decided role.
+
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_io_async::{Read, Write};
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+use crate::split::SplitMessage;
+use crate::split::driver::{SplitReader, SplitWriter};
+use crate::split::serial::SerialSplitDriver;
+
+/// Time between two claims
+const CLAIM_INTERVAL: Duration = Duration::from_millis(20);
+/// Time to wait for the other half before deciding alone
+pub const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Role of a split half
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SplitRole {
+    Central,
+    Peripheral,
+}
+
+/// Claim of a half during the role negotiation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct RoleClaim {
+    /// The half has USB plugged in
+    usb: bool,
+    /// Tie breaker when both halves have the same `usb`
+    seed: u32,
+}
+
+impl RoleClaim {
+    /// Role of the half which claimed `self`, `None` if the claims tie.
+    fn role(&self, other: &RoleClaim) -> Option<SplitRole> {
+        let wins = match (self.usb, other.usb) {
+            (true, false) => true,
+            (false, true) => false,
+            _ if self.seed == other.seed => return None,
+            _ => self.seed > other.seed,
+        };
+        Some(if wins {
+            SplitRole::Central
+        } else {
+            SplitRole::Peripheral
+        })
+    }
+}
+
+/// Pick the role of this half with the other half, over the serial port of the split link.
+///
+/// Call it on both halves before running either the central or the peripheral on `serial`:
+///
+/// ```ignore
+/// match negotiate_split_role(&mut uart, vbus.is_high(), chip_id).await {
+///     SplitRole::Central => { /* run_rmk and run_peripheral_manager */ }
+///     SplitRole::Peripheral => { /* run_rmk_split_peripheral */ }
+/// }
+/// ```
+///
+/// # Arguments
+/// * `serial` - full-duplex serial port of the split link
+/// * `usb` - this half has USB plugged in, e.g. read from a Vbus detection pin
+/// * `seed` - number which differs between the halves, e.g. from the unique ID of the chip
+pub async fn negotiate_split_role<S: Read + Write>(serial: &mut S, usb: bool, seed: u32) -> SplitRole {
+    let mine = RoleClaim { usb, seed };
+    let mut driver = SerialSplitDriver::new(serial);
+    let deadline = Instant::now() + NEGOTIATION_TIMEOUT;
+    let mut role = None;
+    loop {
+        let message = match role {
+            Some(_) => SplitMessage::RoleDecided,
+            None => SplitMessage::RoleClaim(mine),
+        };
+        if let Err(e) = driver.write(&message).await {
+            debug!("Split role message write error: {:?}", e);
+        }
+
+        match with_timeout(CLAIM_INTERVAL, driver.read()).await {
+            Ok(Ok(SplitMessage::RoleClaim(theirs))) => match mine.role(&theirs) {
+                Some(r) => role = Some(r),
+                None => error!("Both split halves claimed the same seed {}", seed),
+            },
+            // Our `RoleDecided` was sent after the claim which decided, so the other half has it
+            Ok(Ok(SplitMessage::RoleDecided)) => {
+                if let Some(r) = role {
+                    info!("Split role negotiated: {:?}", r);
+                    return r;
+                }
+            }
+            Ok(Ok(_)) | Ok(Err(_)) | Err(_) => (),
+        }
+
+        if Instant::now() >= deadline {
+            let r = role.unwrap_or(if usb { SplitRole::Central } else { SplitRole::Peripheral });
+            warn!("Split role negotiation timed out, running as {:?}", r);
+            return r;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usb_half_is_central() {
+        let usb = RoleClaim { usb: true, seed: 1 };
+        let battery = RoleClaim { usb: false, seed: 2 };
+        assert_eq!(usb.role(&battery), Some(SplitRole::Central));
+        assert_eq!(battery.role(&usb), Some(SplitRole::Peripheral));
+    }
+
+    #[test]
+    fn seed_breaks_ties() {
+        let a = RoleClaim { usb: true, seed: 7 };
+        let b = RoleClaim { usb: true, seed: 3 };
+        assert_eq!(a.role(&b), Some(SplitRole::Central));
+        assert_eq!(b.role(&a), Some(SplitRole::Peripheral));
+        assert_eq!(a.role(&a), None);
+    }
+}
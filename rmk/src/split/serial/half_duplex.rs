@@ -0,0 +1,308 @@
+//! Split link over a single-wire half-duplex UART, like the TRRS cable of most split PCBs.
+//!
+//! Both halves transmit on the same wire, so their messages can collide. Every message
+//! is sent in a frame with a sequence number and a CRC-16, and it's sent again until the
+//! other half acknowledges it:
+//!
+//! | Field          | Content                                                         |
+//! |----------------|-----------------------------------------------------------------|
+//! | `from_central` | Sender of the frame, frames echoed back by the wire are dropped |
+//! | `seq`          | Sequence number of the message, repeated by its ACK             |
+//! | `kind`         | The message, an ACK, or a reset of the sequence numbers         |
+//! | `crc`          | CRC-16/CCITT of the fields above                                |
+//!
+//! Frames are postcard serialized and COBS encoded like the messages of the full-duplex
+//! driver. A retransmitted message keeps its sequence number, so the receiver can drop
+//! duplicates when only the ACK was lost.
+//!
+//! The sequence numbers restart when a half reboots, so before its first message, and after a
+//! message is given up, a half sends a reset frame. The other half forgets the last sequence
+//! number it received, otherwise the first message after a reboot could be taken for a duplicate.
+
+use embassy_time::{Duration, Timer, with_timeout};
+use embedded_io_async::{Read, Write};
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+use super::SerialSplitDriver;
+use crate::split::SplitMessage;
+use crate::split::driver::{SplitDriverError, SplitReader, SplitWriter};
+
+/// How long to wait for the ACK of a frame
+const ACK_TIMEOUT: Duration = Duration::from_millis(10);
+/// Retransmissions of a frame before the message is given up
+const RETRANSMITS: u32 = 5;
+/// Largest encoded frame: the message, the frame fields and the COBS overhead
+const FRAME_MAX_SIZE: usize = Frame::POSTCARD_MAX_SIZE + 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, MaxSize)]
+enum FrameKind {
+    Message(SplitMessage),
+    Ack,
+    /// The sender starts a new sequence, acknowledged like a message
+    Reset,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, MaxSize)]
+struct Frame {
+    from_central: bool,
+    seq: u8,
+    kind: FrameKind,
+    crc: u16,
+}
+
+impl Frame {
+    fn new(from_central: bool, seq: u8, kind: FrameKind) -> Self {
+        let mut frame = Self {
+            from_central,
+            seq,
+            kind,
+            crc: 0,
+        };
+        frame.crc = frame.checksum();
+        frame
+    }
+
+    /// CRC of the serialized fields, computed on the decoded values so that
+    /// the receiver doesn't need the raw bytes.
+    fn checksum(&self) -> u16 {
+        let mut buf = [0_u8; Frame::POSTCARD_MAX_SIZE];
+        match postcard::to_slice(&(self.from_central, self.seq, &self.kind), &mut buf) {
+            Ok(bytes) => crc16(bytes),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Split driver over a half-duplex serial port, for BOTH split central and peripheral
+pub(crate) struct HalfDuplexSplitDriver<S> {
+    serial: SerialSplitDriver<S, FRAME_MAX_SIZE>,
+    central: bool,
+    /// Sequence number of the last sent message
+    tx_seq: u8,
+    /// Sequence number of the last received message, to drop retransmits
+    rx_seq: Option<u8>,
+    /// The other half acknowledged the reset of the sequence numbers
+    synced: bool,
+    /// Message received while waiting for an ACK, or not read yet
+    inbox: Option<SplitMessage>,
+}
+
+impl<S: Read + Write> HalfDuplexSplitDriver<S> {
+    pub(crate) fn new(serial: S, central: bool) -> Self {
+        Self {
+            serial: SerialSplitDriver::with_frame_size(serial),
+            central,
+            tx_seq: 0,
+            rx_seq: None,
+            synced: false,
+            inbox: None,
+        }
+    }
+
+    /// Read the next valid frame from the other half.
+    async fn read_frame(&mut self) -> Result<Frame, SplitDriverError> {
+        loop {
+            let frame: Frame = match self.serial.read_frame().await {
+                Ok(frame) => frame,
+                // Garbled by a collision, the sender retransmits it
+                Err(SplitDriverError::SerializeError) => continue,
+                Err(e) => return Err(e),
+            };
+            if frame.from_central == self.central {
+                // Our own frame, echoed back by the wire
+                continue;
+            }
+            if frame.crc != frame.checksum() {
+                warn!("Split frame CRC mismatch, dropping it");
+                continue;
+            }
+            return Ok(frame);
+        }
+    }
+
+    /// Keep a message from the other half and acknowledge it.
+    ///
+    /// A message that doesn't fit in the inbox isn't acknowledged, so it's sent again later.
+    async fn receive_message(&mut self, seq: u8, message: SplitMessage) -> Result<(), SplitDriverError> {
+        if self.rx_seq != Some(seq) {
+            if self.inbox.is_some() {
+                return Ok(());
+            }
+            self.inbox = Some(message);
+            self.rx_seq = Some(seq);
+        }
+        self.serial
+            .write_frame(&Frame::new(self.central, seq, FrameKind::Ack))
+            .await
+            .map(|_| ())
+    }
+
+    /// Forget the last received sequence number, the other half starts a new sequence.
+    async fn receive_reset(&mut self, seq: u8) -> Result<(), SplitDriverError> {
+        self.rx_seq = None;
+        self.serial
+            .write_frame(&Frame::new(self.central, seq, FrameKind::Ack))
+            .await
+            .map(|_| ())
+    }
+
+    /// Wait for the ACK of `seq`, keeping the messages received meanwhile.
+    async fn wait_ack(&mut self, seq: u8) -> Result<(), SplitDriverError> {
+        loop {
+            let frame = self.read_frame().await?;
+            match frame.kind {
+                FrameKind::Ack if frame.seq == seq => return Ok(()),
+                FrameKind::Ack => (),
+                FrameKind::Message(message) => self.receive_message(frame.seq, message).await?,
+                FrameKind::Reset => self.receive_reset(frame.seq).await?,
+            }
+        }
+    }
+
+    /// Send a frame until it's acknowledged, return the number of bytes written.
+    async fn send(&mut self, kind: FrameKind) -> Result<usize, SplitDriverError> {
+        self.tx_seq = self.tx_seq.wrapping_add(1);
+        let frame = Frame::new(self.central, self.tx_seq, kind);
+        for attempt in 0..=RETRANSMITS {
+            let len = self.serial.write_frame(&frame).await?;
+            match with_timeout(ACK_TIMEOUT, self.wait_ack(frame.seq)).await {
+                Ok(Ok(())) => return Ok(len),
+                Ok(Err(e)) => debug!("Split ACK read error: {:?}", e),
+                Err(_) => (),
+            }
+            Timer::after(self.backoff(attempt)).await;
+        }
+        warn!("No ACK from the other half after {} retransmits", RETRANSMITS);
+        // The other half may have rebooted, start a new sequence with the next message
+        self.synced = false;
+        Err(SplitDriverError::SerialError)
+    }
+
+    /// Time to wait before a retransmission. It differs between the halves, so
+    /// that frames which collided once don't collide again.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let step = if self.central { 1 } else { 3 };
+        Duration::from_millis(step * (attempt as u64 + 1))
+    }
+}
+
+impl<S: Read + Write> SplitReader for HalfDuplexSplitDriver<S> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        loop {
+            if let Some(message) = self.inbox.take() {
+                return Ok(message);
+            }
+            let frame = self.read_frame().await?;
+            match frame.kind {
+                FrameKind::Message(message) => self.receive_message(frame.seq, message).await?,
+                FrameKind::Reset => self.receive_reset(frame.seq).await?,
+                FrameKind::Ack => (),
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> SplitWriter for HalfDuplexSplitDriver<S> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        if !self.synced {
+            self.send(FrameKind::Reset).await?;
+            self.synced = true;
+        }
+        self.send(FrameKind::Message(*message)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+
+    use embassy_futures::block_on;
+    use embedded_io_async::ErrorType;
+
+    use super::*;
+
+    /// Fake serial port: reads return the scripted frames, writes are dropped
+    struct FakeSerial {
+        frames: VecDeque<Vec<u8>>,
+    }
+
+    impl ErrorType for FakeSerial {
+        type Error = Infallible;
+    }
+
+    impl Read for FakeSerial {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let frame = self.frames.pop_front().expect("no more scripted frames");
+            buf[..frame.len()].copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+    }
+
+    impl Write for FakeSerial {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+    }
+
+    fn encode(frame: &Frame) -> Vec<u8> {
+        let mut buf = [0u8; FRAME_MAX_SIZE];
+        postcard::to_slice_cobs(frame, &mut buf).unwrap().to_vec()
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn corrupted_frame_fails_checksum() {
+        let frame = Frame::new(true, 7, FrameKind::Message(SplitMessage::Layer(2)));
+        assert_eq!(frame.crc, frame.checksum());
+
+        let mut corrupted = frame;
+        corrupted.kind = FrameKind::Message(SplitMessage::Layer(3));
+        assert_ne!(corrupted.crc, corrupted.checksum());
+
+        let mut wrong_seq = frame;
+        wrong_seq.seq = 8;
+        assert_ne!(wrong_seq.crc, wrong_seq.checksum());
+    }
+
+    #[test]
+    fn reset_accepts_same_seq_after_reboot() {
+        let frames = [
+            Frame::new(false, 2, FrameKind::Message(SplitMessage::Layer(1))),
+            // Retransmit of the same message, dropped
+            Frame::new(false, 2, FrameKind::Message(SplitMessage::Layer(1))),
+            // The peripheral rebooted and restarts its sequence
+            Frame::new(false, 1, FrameKind::Reset),
+            Frame::new(false, 2, FrameKind::Message(SplitMessage::Layer(2))),
+        ];
+        let serial = FakeSerial {
+            frames: frames.iter().map(encode).collect(),
+        };
+        let mut driver = HalfDuplexSplitDriver::new(serial, true);
+
+        assert!(matches!(block_on(driver.read()), Ok(SplitMessage::Layer(1))));
+        assert!(matches!(block_on(driver.read()), Ok(SplitMessage::Layer(2))));
+        assert!(driver.serial.serial.frames.is_empty());
+    }
+}
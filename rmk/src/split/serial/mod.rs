@@ -1,9 +1,14 @@
 use embedded_io_async::{Read, Write};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
 use super::driver::SplitDriverError;
 use crate::split::driver::{PeripheralManager, SplitReader, SplitWriter};
 use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
 
+pub mod full_duplex;
+pub(crate) mod half_duplex;
+
 /// Receive split message from peripheral via serial and process it
 ///
 /// Generic parameters:
@@ -23,31 +28,64 @@ pub(crate) async fn run_serial_peripheral_manager<
     receiver: S,
 ) {
     let split_serial_driver: SerialSplitDriver<S> = SerialSplitDriver::new(receiver);
-    let peripheral_manager = PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_serial_driver, id);
+    let peripheral_manager =
+        PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_serial_driver, id).with_heartbeat();
     info!("Running peripheral manager {}", id);
 
     peripheral_manager.run().await;
 }
 
+/// Like [`run_serial_peripheral_manager`], over a half-duplex single-wire UART,
+/// see [`half_duplex::HalfDuplexSplitDriver`].
+pub(crate) async fn run_half_duplex_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    S: Read + Write,
+>(
+    id: usize,
+    serial: S,
+) {
+    let split_driver = half_duplex::HalfDuplexSplitDriver::new(serial, true);
+    let peripheral_manager =
+        PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_driver, id).with_heartbeat();
+    info!("Running half-duplex peripheral manager {}", id);
+
+    peripheral_manager.run().await;
+}
+
 /// Serial driver for BOTH split central and peripheral
-pub(crate) struct SerialSplitDriver<S> {
+///
+/// Messages are COBS encoded postcard frames of at most `N` bytes.
+pub(crate) struct SerialSplitDriver<S, const N: usize = SPLIT_MESSAGE_MAX_SIZE> {
     serial: S,
-    buffer: [u8; SPLIT_MESSAGE_MAX_SIZE],
+    buffer: [u8; N],
     n_bytes_part: usize,
 }
 
 impl<S> SerialSplitDriver<S> {
     pub(crate) fn new(serial: S) -> Self {
+        Self::with_frame_size(serial)
+    }
+}
+
+impl<S, const N: usize> SerialSplitDriver<S, N> {
+    /// Create a driver for frames of at most `N` bytes
+    pub(crate) fn with_frame_size(serial: S) -> Self {
         Self {
             serial,
-            buffer: [0_u8; SPLIT_MESSAGE_MAX_SIZE],
+            buffer: [0_u8; N],
             n_bytes_part: 0,
         }
     }
 }
 
-impl<S: Read> SplitReader for SerialSplitDriver<S> {
-    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+impl<S: Read, const N: usize> SerialSplitDriver<S, N> {
+    /// Read the next frame from the serial port.
+    ///
+    /// Cancel safe: the bytes of a partially received frame are kept for the next call.
+    pub(crate) async fn read_frame<T: DeserializeOwned>(&mut self) -> Result<T, SplitDriverError> {
         const SENTINEL: u8 = 0x00;
         // Check the buffer *before* reading: a prior read() call may have
         // pulled in more than one complete message, and the next one is
@@ -68,18 +106,18 @@ impl<S: Read> SplitReader for SerialSplitDriver<S> {
             self.n_bytes_part += n_bytes;
         }
 
-        let (result, n_bytes_unused) =
-            match postcard::take_from_bytes_cobs::<SplitMessage>(&mut self.buffer[..self.n_bytes_part]) {
-                Ok((message, unused_bytes)) => (Ok(message), unused_bytes.len()),
-                Err(e) => {
-                    error!("Postcard deserialize split message error: {}", e);
-                    let n_bytes_unused = self.buffer[..self.n_bytes_part]
-                        .iter()
-                        .position(|&x| x == SENTINEL)
-                        .map_or(0, |index| self.n_bytes_part - index - 1);
-                    (Err(SplitDriverError::SerializeError), n_bytes_unused)
-                }
-            };
+        let (result, n_bytes_unused) = match postcard::take_from_bytes_cobs::<T>(&mut self.buffer[..self.n_bytes_part])
+        {
+            Ok((message, unused_bytes)) => (Ok(message), unused_bytes.len()),
+            Err(e) => {
+                error!("Postcard deserialize split message error: {}", e);
+                let n_bytes_unused = self.buffer[..self.n_bytes_part]
+                    .iter()
+                    .position(|&x| x == SENTINEL)
+                    .map_or(0, |index| self.n_bytes_part - index - 1);
+                (Err(SplitDriverError::SerializeError), n_bytes_unused)
+            }
+        };
 
         self.buffer
             .copy_within(self.n_bytes_part - n_bytes_unused..self.n_bytes_part, 0);
@@ -89,10 +127,11 @@ impl<S: Read> SplitReader for SerialSplitDriver<S> {
     }
 }
 
-impl<S: Write> SplitWriter for SerialSplitDriver<S> {
-    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
-        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
-        let bytes = postcard::to_slice_cobs(message, &mut buf).map_err(|e| {
+impl<S: Write, const N: usize> SerialSplitDriver<S, N> {
+    /// Write a frame to the serial port, return the number of bytes written.
+    pub(crate) async fn write_frame<T: Serialize>(&mut self, frame: &T) -> Result<usize, SplitDriverError> {
+        let mut buf = [0_u8; N];
+        let bytes = postcard::to_slice_cobs(frame, &mut buf).map_err(|e| {
             error!("Postcard serialize split message error: {}", e);
             SplitDriverError::SerializeError
         })?;
@@ -109,6 +148,18 @@ impl<S: Write> SplitWriter for SerialSplitDriver<S> {
     }
 }
 
+impl<S: Read> SplitReader for SerialSplitDriver<S> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        self.read_frame().await
+    }
+}
+
+impl<S: Write> SplitWriter for SerialSplitDriver<S> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        self.write_frame(message).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::VecDeque;
@@ -0,0 +1,187 @@
+//! Custom split links.
+//!
+//! Implement [`SplitTransport`] for a link RMK doesn't ship, like RS-485, CAN or an optical
+//! link, then run [`crate::split::central::run_transport_peripheral_manager`] on the central
+//! and [`crate::split::peripheral::run_rmk_transport_split_peripheral`] on the peripheral.
+//!
+//! The transport carries frames, every frame is one split message serialized by RMK, of at
+//! most [`SPLIT_MESSAGE_MAX_SIZE`] bytes. The transport has to deliver frames whole, and drop
+//! the ones it can't deliver intact: RMK doesn't add a checksum, because most links have one
+//! already. Both halves send a heartbeat every 250ms, so a lost link is noticed even when the
+//! transport can't tell.
+
+use core::future::Future;
+
+use crate::split::driver::{PeripheralManager, SplitDriverError, SplitReader, SplitWriter};
+use crate::split::{SPLIT_MESSAGE_MAX_SIZE, SplitMessage};
+
+/// Link between the split halves, which sends and receives whole frames.
+pub trait SplitTransport {
+    type Error: core::fmt::Debug;
+
+    /// Send `frame` to the other half.
+    fn send(&mut self, frame: &[u8]) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// Wait for the next frame from the other half, copy it into `buf` and return its length.
+    ///
+    /// `buf` holds [`SPLIT_MESSAGE_MAX_SIZE`] bytes, which fits every frame.
+    ///
+    /// # Cancel safety
+    ///
+    /// RMK drops the future whenever it has a message or a heartbeat to send, so this has to be
+    /// cancel-safe: keep the bytes of a partial frame in the transport, not in the future, and
+    /// go on with them in the next call. Only return a frame once it's complete.
+    fn receive(&mut self, buf: &mut [u8]) -> impl Future<Output = Result<usize, Self::Error>>;
+}
+
+/// Split driver over a [`SplitTransport`], for BOTH split central and peripheral
+pub(crate) struct TransportSplitDriver<T> {
+    transport: T,
+}
+
+impl<T: SplitTransport> TransportSplitDriver<T> {
+    pub(crate) fn new(transport: T) -> Self {
+        Self { transport }
+    }
+}
+
+impl<T: SplitTransport> SplitReader for TransportSplitDriver<T> {
+    async fn read(&mut self) -> Result<SplitMessage, SplitDriverError> {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let len = self.transport.receive(&mut buf).await.map_err(|e| {
+            error!("Split transport receive error: {:?}", e);
+            SplitDriverError::SerialError
+        })?;
+        if len == 0 {
+            return Err(SplitDriverError::EmptyMessage);
+        }
+        let frame = buf.get(..len).ok_or(SplitDriverError::DeserializeError)?;
+        postcard::from_bytes(frame).map_err(|_| SplitDriverError::DeserializeError)
+    }
+}
+
+impl<T: SplitTransport> SplitWriter for TransportSplitDriver<T> {
+    async fn write(&mut self, message: &SplitMessage) -> Result<usize, SplitDriverError> {
+        let mut buf = [0_u8; SPLIT_MESSAGE_MAX_SIZE];
+        let frame = postcard::to_slice(message, &mut buf).map_err(|e| {
+            error!("Postcard serialize split message error: {}", e);
+            SplitDriverError::SerializeError
+        })?;
+        self.transport.send(frame).await.map_err(|e| {
+            error!("Split transport send error: {:?}", e);
+            SplitDriverError::SerialError
+        })?;
+        Ok(frame.len())
+    }
+}
+
+/// Receive split messages from a peripheral over a custom link, and process them.
+///
+/// Generic parameters:
+/// - `const ROW`: row number of the peripheral's matrix
+/// - `const COL`: column number of the peripheral's matrix
+/// - `const ROW_OFFSET`: row offset of the peripheral's matrix in the whole matrix
+/// - `const COL_OFFSET`: column offset of the peripheral's matrix in the whole matrix
+/// - `T`: the link to the peripheral
+pub(crate) async fn run_transport_peripheral_manager<
+    const ROW: usize,
+    const COL: usize,
+    const ROW_OFFSET: usize,
+    const COL_OFFSET: usize,
+    T: SplitTransport,
+>(
+    id: usize,
+    transport: T,
+) {
+    let split_driver = TransportSplitDriver::new(transport);
+    let peripheral_manager =
+        PeripheralManager::<ROW, COL, ROW_OFFSET, COL_OFFSET, _>::new(split_driver, id).with_heartbeat();
+    info!("Running peripheral manager {} over a custom transport", id);
+
+    peripheral_manager.run().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+
+    use embassy_futures::block_on;
+    use embassy_futures::select::{Either, select};
+    use embassy_futures::yield_now;
+
+    use super::*;
+
+    /// Transport which receives the frames it sent
+    #[derive(Default)]
+    struct Loopback {
+        frames: VecDeque<Vec<u8>>,
+    }
+
+    impl SplitTransport for Loopback {
+        type Error = Infallible;
+
+        async fn send(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+            self.frames.push_back(frame.to_vec());
+            Ok(())
+        }
+
+        async fn receive(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let frame = self.frames.pop_front().unwrap_or_default();
+            buf[..frame.len()].copy_from_slice(&frame);
+            Ok(frame.len())
+        }
+    }
+
+    /// Transport which receives one byte per poll, and keeps the partial frame itself
+    #[derive(Default)]
+    struct ByteByByte {
+        frame: Vec<u8>,
+        received: Vec<u8>,
+    }
+
+    impl SplitTransport for ByteByByte {
+        type Error = Infallible;
+
+        async fn send(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+            self.frame = frame.to_vec();
+            Ok(())
+        }
+
+        async fn receive(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            while self.received.len() < self.frame.len() {
+                self.received.push(self.frame[self.received.len()]);
+                yield_now().await;
+            }
+            let len = self.received.len();
+            buf[..len].copy_from_slice(&self.received);
+            self.received.clear();
+            self.frame.clear();
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn cancelled_read_keeps_partial_frame() {
+        let mut drv = TransportSplitDriver::new(ByteByByte::default());
+        block_on(drv.write(&SplitMessage::Layer(4))).unwrap();
+
+        // Cancel the read after its first byte, like a heartbeat would
+        assert!(matches!(block_on(select(drv.read(), async {})), Either::Second(())));
+        assert_eq!(drv.transport.received.len(), 1);
+
+        assert!(matches!(block_on(drv.read()), Ok(SplitMessage::Layer(4))));
+    }
+
+    #[test]
+    fn messages_round_trip_in_frames() {
+        let mut drv = TransportSplitDriver::new(Loopback::default());
+        block_on(drv.write(&SplitMessage::Layer(4))).unwrap();
+        block_on(drv.write(&SplitMessage::CapsWord(true))).unwrap();
+        assert_eq!(drv.transport.frames.len(), 2);
+
+        assert!(matches!(block_on(drv.read()), Ok(SplitMessage::Layer(4))));
+        assert!(matches!(block_on(drv.read()), Ok(SplitMessage::CapsWord(true))));
+        assert!(matches!(block_on(drv.read()), Err(SplitDriverError::EmptyMessage)));
+    }
+}
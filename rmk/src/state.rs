@@ -19,6 +19,11 @@ pub(crate) fn active_transport() -> Option<ConnectionType> {
     CONNECTION_STATUS.lock(|c| c.get().decide_active())
 }
 
+/// Transport receiving a copy of the reports, see [`ConnectionStatus::decide_mirror`].
+pub(crate) fn mirror_transport() -> Option<ConnectionType> {
+    CONNECTION_STATUS.lock(|c| c.get().decide_mirror())
+}
+
 pub(crate) fn current_connection_status() -> ConnectionStatus {
     CONNECTION_STATUS.lock(|c| c.get())
 }
@@ -49,14 +54,19 @@ pub(crate) fn update_status(f: impl FnOnce(&mut ConnectionStatus)) {
 
     let prev_active = prev.decide_active();
     let new_active = new.decide_active();
-
-    if prev_active != new_active
-        && let Some(prev_active) = prev_active
-    {
-        // Drain after the commit so any producer racing past the mutex reads
-        // the new state and routes to the new channel rather than the one
-        // about to be cleared.
-        crate::channel::clear_and_release_report_channel(prev_active);
+    let new_mirror = new.decide_mirror();
+
+    for transport in [prev_active, prev.decide_mirror()].into_iter().flatten() {
+        if new_active != Some(transport) && new_mirror != Some(transport) {
+            // Drain after the commit so any producer racing past the mutex reads
+            // the new state and routes to the new channel rather than the one
+            // about to be cleared.
+            crate::channel::clear_and_release_report_channel(transport);
+        }
+    }
+    if prev_active != new_active {
+        // Every host has its own lock LED state
+        crate::hid::sync_led_indicator(new_active);
     }
 
     publish_event(ConnectionStatusChangeEvent(new));
@@ -121,6 +131,51 @@ pub(crate) async fn toggle_preferred() {
         .await;
 }
 
+/// Force reports onto `output`, or with `None` go back to automatic selection:
+/// the connected output, the preferred one when both are connected.
+///
+/// With the storage, the keyboard saves the forced flag and the
+/// transport, and is back on the same output after a reboot. Going back to
+/// automatic selection keeps the saved preferred transport.
+#[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+pub(crate) async fn select_output(output: Option<ConnectionType>) {
+    update_status(|c| {
+        if let Some(preferred) = output {
+            c.preferred = preferred;
+        }
+        c.forced = output.is_some();
+    });
+    info!("Selecting output: {:?}", output);
+    #[cfg(feature = "storage")]
+    if let Some(preferred) = output {
+        crate::channel::FLASH_CHANNEL
+            .send(crate::storage::FlashOperationMessage::ConnectionType(preferred))
+            .await;
+    }
+}
+
+/// Toggle sending the reports to both the USB and the BLE host, when both are connected.
+///
+/// Mirroring has no effect while an output is forced.
+#[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+pub(crate) fn toggle_mirror() {
+    let mut mirror = false;
+    update_status(|c| {
+        c.mirror = !c.mirror;
+        mirror = c.mirror;
+    });
+    info!("Output mirroring: {}", mirror);
+}
+
+/// Restore the forced output and mirroring saved in the storage, at boot.
+#[cfg(all(feature = "_ble", not(feature = "_no_usb"), feature = "storage"))]
+pub(crate) fn restore_output(forced: bool, mirror: bool) {
+    update_status(|c| {
+        c.forced = forced;
+        c.mirror = mirror;
+    });
+}
+
 #[cfg(feature = "_ble")]
 pub(crate) fn current_profile() -> u8 {
     CONNECTION_STATUS.lock(|c| c.get().ble.profile)
@@ -133,12 +188,14 @@ mod tests {
     use embassy_futures::select::{Either, select};
     use embassy_time::{Duration, Timer};
 
+    use rmk_types::led_indicator::LedIndicator;
+
     use super::{
         CONNECTION_STATUS, ConnectionStatus, ConnectionType, UsbState, set_preferred_connection, set_usb_state,
     };
     use crate::event::{ConnectionStatusChangeEvent, EventSubscriber, SubscribableEvent};
     use crate::hid::{KeyboardReport, Report};
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
 
     fn state_test_lock() -> &'static Mutex<()> {
         static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
@@ -249,6 +306,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn active_transport_change_publishes_its_led_state() {
+        use core::sync::atomic::Ordering;
+
+        use crate::event::LedIndicatorEvent;
+        use crate::hid::HOST_LED_STATES;
+        use crate::keyboard::{LOCK_LED_STATES, current_led_indicator};
+
+        let _guard = state_test_lock().lock().unwrap();
+        reset_state();
+        LOCK_LED_STATES.store(0, Ordering::Relaxed);
+        let caps_lock = LedIndicator::new().with_caps_lock(true);
+        HOST_LED_STATES[ConnectionType::Usb as usize].store(caps_lock.into_bits(), Ordering::Relaxed);
+        let mut sub = LedIndicatorEvent::subscriber();
+
+        set_usb_state(UsbState::Configured);
+        assert_eq!(block_on(sub.next_event()).0, caps_lock);
+        assert_eq!(current_led_indicator(), caps_lock);
+
+        // No host left, the LEDs turn off
+        set_usb_state(UsbState::Disabled);
+        assert_eq!(block_on(sub.next_event()).0, LedIndicator::new());
+
+        HOST_LED_STATES[ConnectionType::Usb as usize].store(0, Ordering::Relaxed);
+    }
+
     #[cfg(not(feature = "_no_usb"))]
     #[test]
     fn blocked_send_drops_report_after_transport_change() {
@@ -314,6 +397,32 @@ mod tests {
         );
     }
 
+    #[cfg(all(not(feature = "_no_usb"), feature = "_ble"))]
+    #[test]
+    fn mirrored_reports_reach_both_transports() {
+        use crate::channel::{BLE_REPORT_CHANNEL, USB_REPORT_CHANNEL, send_hid_report};
+        use crate::state::{BleState, set_ble_state, toggle_mirror};
+
+        let _guard = state_test_lock().lock().unwrap();
+        reset_state();
+        set_usb_state(UsbState::Configured);
+        set_ble_state(BleState::Connected);
+        toggle_mirror();
+
+        block_on(send_hid_report(pressed_keyboard_report()));
+        assert!(USB_REPORT_CHANNEL.try_receive().is_ok());
+        assert!(BLE_REPORT_CHANNEL.try_receive().is_ok());
+
+        // Turning mirroring off releases the keys on the mirror only
+        toggle_mirror();
+        assert_all_up_keyboard_report(
+            BLE_REPORT_CHANNEL
+                .try_receive()
+                .expect("BLE_REPORT_CHANNEL should contain keyboard all-up report"),
+        );
+        assert!(USB_REPORT_CHANNEL.try_receive().is_err());
+    }
+
     #[cfg(not(feature = "_no_usb"))]
     #[test]
     fn blocked_send_enqueues_when_transport_stays_active() {
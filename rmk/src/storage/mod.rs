@@ -1,35 +1,60 @@
 use core::fmt::Debug;
 
 use embassy_embedded_hal::adapter::BlockingAsync;
+#[cfg(feature = "host")]
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
 use embassy_sync::signal::Signal;
-use embassy_time::Duration;
+use embassy_time::{Duration, Instant, with_timeout};
 use embedded_storage::nor_flash::NorFlash;
 use embedded_storage_async::nor_flash::NorFlash as AsyncNorFlash;
 use postcard::experimental::max_size::MaxSize;
-use rmk_types::connection::ConnectionType;
+use rmk_types::connection::{ConnectionType, HostOs};
+#[cfg(feature = "_ble")]
+use rmk_types::constants::BLE_NAME_MAX_LEN;
 use rmk_types::morse::MorseProfile;
 use sequential_storage::Error as SSError;
 use sequential_storage::cache::NoCache;
 use sequential_storage::map::{Key, MapConfig, MapStorage, PostcardValue, SerializationError};
 #[cfg(feature = "host")]
 use {
-    crate::{MACRO_SPACE_SIZE, keyboard::combo::ComboConfig},
+    crate::host::storage::{BackupItem, BackupRecord},
+    crate::{KEYMAP_PROFILE_NUM, MACRO_SPACE_SIZE, keyboard::combo::ComboConfig},
+    crate::{event::KeyboardEventPos, keymap::KeyMap},
     rmk_types::action::{EncoderAction, KeyAction},
     rmk_types::fork::Fork,
     rmk_types::morse::Morse,
 };
 
+#[cfg(feature = "audio")]
+use crate::audio::AudioSettings;
 #[cfg(feature = "_ble")]
 use crate::ble::profile::ProfileInfo;
 use crate::channel::FLASH_CHANNEL;
 use crate::config::StorageConfig;
 #[cfg(all(feature = "_ble", feature = "split"))]
 use crate::split::ble::PeerAddress;
-use crate::{BUILD_HASH, config};
-
-/// Signal to synchronize the flash operation status, usually used outside of the flash task.
-/// True if the flash operation is finished correctly, false if the flash operation is finished with error.
-pub(crate) static FLASH_OPERATION_FINISHED: Signal<crate::RawMutex, bool> = Signal::new();
+use crate::storage::user::UserValue;
+#[cfg(feature = "crash_dump")]
+use crate::watchdog::crash::CrashRecord;
+use crate::{BUILD_HASH, FLASH_BATCH_SIZE, config};
+
+pub mod spi_flash;
+pub mod user;
+
+/// Time without new edits and key actions, after which the batched edits are saved
+const FLASH_BATCH_QUIET_TIME: Duration = Duration::from_millis(500);
+/// Longest time an edit waits in the batch while the user keeps typing
+const FLASH_BATCH_MAX_DELAY: Duration = Duration::from_secs(10);
+
+// Completion of the operations which a task waits for, one `Signal` per waiting task, so that
+// a task isn't woken by the operation of another one. True if the operation finished correctly.
+/// `ClearSlot` and `ActiveBleProfile`, which only the BLE profile manager sends
+#[cfg(feature = "_ble")]
+pub(crate) static BLE_PROFILE_SAVED: Signal<crate::RawMutex, bool> = Signal::new();
+#[cfg(feature = "host")]
+static BACKUP_ITEM_RESTORED: Signal<crate::RawMutex, bool> = Signal::new();
+static FLUSHED: Signal<crate::RawMutex, bool> = Signal::new();
 
 // Request/response over `FLASH_CHANNEL`. One `Signal` per read variant; the
 // storage task fires the matching one once it has the result.
@@ -41,9 +66,23 @@ static PEER_ADDRESS_RESPONSE: Signal<crate::RawMutex, Option<PeerAddress>> = Sig
 static CONNECTION_TYPE_RESPONSE: Signal<crate::RawMutex, Option<ConnectionType>> = Signal::new();
 #[cfg(feature = "_ble")]
 static ACTIVE_BLE_PROFILE_RESPONSE: Signal<crate::RawMutex, Option<u8>> = Signal::new();
-
 #[cfg(feature = "_ble")]
-async fn request_read<T: Send>(msg: FlashOperationMessage, response: &Signal<crate::RawMutex, T>) -> T {
+static BLE_NAME_RESPONSE: Signal<crate::RawMutex, Option<heapless::String<BLE_NAME_MAX_LEN>>> = Signal::new();
+#[cfg(feature = "audio")]
+static AUDIO_SETTINGS_RESPONSE: Signal<crate::RawMutex, Option<AudioSettings>> = Signal::new();
+#[cfg(feature = "host")]
+static BACKUP_ITEM_RESPONSE: Signal<crate::RawMutex, BackupItem> = Signal::new();
+static USER_DATA_RESPONSE: Signal<crate::RawMutex, Option<UserValue>> = Signal::new();
+/// Keys of the keymap profile being switched to, as `(layer, row, col, action)`, `None` after the last one
+#[cfg(feature = "host")]
+pub(crate) static PROFILE_KEY_CHANNEL: Channel<crate::RawMutex, Option<(u8, u8, u8, KeyAction)>, 8> = Channel::new();
+
+/// Held during a request, so that concurrent requests sharing a response `Signal` don't take each other's reply
+static REQUEST_LOCK: Mutex<crate::RawMutex, ()> = Mutex::new(());
+
+/// Send `msg` to the storage task, and wait for its reply on `response`
+async fn request<T: Send>(msg: FlashOperationMessage, response: &Signal<crate::RawMutex, T>) -> T {
+    let _lock = REQUEST_LOCK.lock().await;
     response.reset();
     FLASH_CHANNEL.send(msg).await;
     response.wait().await
@@ -51,35 +90,86 @@ async fn request_read<T: Send>(msg: FlashOperationMessage, response: &Signal<cra
 
 #[cfg(feature = "_ble")]
 pub(crate) async fn read_bond_info(slot_num: u8) -> Option<ProfileInfo> {
-    request_read(FlashOperationMessage::ReadBleBondInfo(slot_num), &BOND_INFO_RESPONSE).await
+    request(FlashOperationMessage::ReadBleBondInfo(slot_num), &BOND_INFO_RESPONSE).await
 }
 
 #[cfg(all(feature = "_ble", feature = "split"))]
 pub(crate) async fn read_peer_address(peer_id: u8) -> Option<PeerAddress> {
-    request_read(FlashOperationMessage::ReadPeerAddress(peer_id), &PEER_ADDRESS_RESPONSE).await
+    request(FlashOperationMessage::ReadPeerAddress(peer_id), &PEER_ADDRESS_RESPONSE).await
 }
 
 #[cfg(feature = "_ble")]
 pub(crate) async fn read_connection_type() -> Option<ConnectionType> {
-    request_read(FlashOperationMessage::ReadConnectionType, &CONNECTION_TYPE_RESPONSE).await
+    request(FlashOperationMessage::ReadConnectionType, &CONNECTION_TYPE_RESPONSE).await
 }
 
 #[cfg(feature = "_ble")]
 pub(crate) async fn read_active_ble_profile() -> Option<u8> {
-    request_read(
+    request(
         FlashOperationMessage::ReadActiveBleProfile,
         &ACTIVE_BLE_PROFILE_RESPONSE,
     )
     .await
 }
 
-/// Send a peer address to be persisted; wait for the storage task to finish.
-/// Returns `true` if the write completed successfully.
-#[cfg(all(feature = "_ble", feature = "split"))]
-pub(crate) async fn write_peer_address(addr: PeerAddress) -> bool {
-    FLASH_OPERATION_FINISHED.reset();
-    FLASH_CHANNEL.send(FlashOperationMessage::PeerAddress(addr)).await;
-    FLASH_OPERATION_FINISHED.wait().await
+#[cfg(feature = "_ble")]
+pub(crate) async fn read_ble_name() -> Option<heapless::String<BLE_NAME_MAX_LEN>> {
+    request(FlashOperationMessage::ReadBleName, &BLE_NAME_RESPONSE).await
+}
+
+#[cfg(feature = "audio")]
+pub(crate) async fn read_audio_settings() -> Option<AudioSettings> {
+    request(FlashOperationMessage::ReadAudioSettings, &AUDIO_SETTINGS_RESPONSE).await
+}
+
+#[cfg(feature = "vial")]
+pub(crate) async fn read_backup_item(index: u16) -> BackupItem {
+    request(FlashOperationMessage::ReadBackupItem(index), &BACKUP_ITEM_RESPONSE).await
+}
+
+/// Send a backup record to be restored; wait for the storage task to finish.
+/// Returns `true` if the record was valid and saved.
+#[cfg(feature = "vial")]
+pub(crate) async fn restore_backup_item(record: BackupRecord) -> bool {
+    request(FlashOperationMessage::RestoreBackupItem(record), &BACKUP_ITEM_RESTORED).await
+}
+
+/// Remove the saved item of a backup index, which was empty in the backup being restored.
+/// Returns `true` if the index is a record which can be cleared.
+#[cfg(feature = "vial")]
+pub(crate) async fn clear_backup_item(index: u16) -> bool {
+    request(FlashOperationMessage::ClearBackupItem(index), &BACKUP_ITEM_RESTORED).await
+}
+
+/// Save the batched edits right away, as a reset or a power off would lose them.
+///
+/// Gives up after a second, so that a stuck flash doesn't keep the keyboard from rebooting.
+pub(crate) async fn flush() {
+    if with_timeout(Duration::from_secs(1), request(FlashOperationMessage::Flush, &FLUSHED))
+        .await
+        .is_err()
+    {
+        warn!("Timeout saving the batched storage edits");
+    }
+}
+
+/// Switch to the keymap `profile` saved in the storage, and load its keys into `keymap`.
+///
+/// The storage sends the keys as it reads them, so a keymap profile doesn't need a second
+/// keymap in RAM.
+#[cfg(feature = "host")]
+pub(crate) async fn switch_keymap_profile(profile: u8, keymap: &KeyMap<'_>) {
+    FLASH_CHANNEL.send(FlashOperationMessage::SwitchProfile(profile)).await;
+    while let Some((layer, row, col, action)) = PROFILE_KEY_CHANNEL.receive().await {
+        keymap.set_action_at(KeyboardEventPos::key_pos(col, row), layer as usize, action);
+    }
+}
+
+/// Save the state which a key changed, like the forced output, to restore it at boot.
+pub(crate) async fn save_runtime_state() {
+    FLASH_CHANNEL
+        .send(FlashOperationMessage::RuntimeState(RuntimeState::current()))
+        .await;
 }
 
 // Message send from other tasks, which will do saving or clearing operation
@@ -151,6 +241,12 @@ pub(crate) enum FlashOperationMessage {
     PriorIdleTime(u16),
     // Default morse profile containing all morse/tap-hold settings (mode, timeouts, unilateral_tap)
     MorseDefaultProfile(MorseProfile),
+    #[cfg(feature = "audio")]
+    // Audio mute and key click settings
+    AudioSettings(AudioSettings),
+    #[cfg(feature = "_ble")]
+    // Advertised BLE name set at runtime
+    BleName(heapless::String<BLE_NAME_MAX_LEN>),
     #[cfg(feature = "_ble")]
     // Read bond info for the given slot; storage task replies via `BOND_INFO_RESPONSE`.
     ReadBleBondInfo(u8),
@@ -163,6 +259,118 @@ pub(crate) enum FlashOperationMessage {
     #[cfg(feature = "_ble")]
     // Read the persisted active BLE profile number; storage task replies via `ACTIVE_BLE_PROFILE_RESPONSE`.
     ReadActiveBleProfile,
+    #[cfg(feature = "audio")]
+    // Read the persisted audio settings; storage task replies via `AUDIO_SETTINGS_RESPONSE`.
+    ReadAudioSettings,
+    #[cfg(feature = "_ble")]
+    // Read the persisted BLE name; storage task replies via `BLE_NAME_RESPONSE`.
+    ReadBleName,
+    #[cfg(feature = "host")]
+    // Read the record of the given backup index; storage task replies via `BACKUP_ITEM_RESPONSE`.
+    ReadBackupItem(u16),
+    #[cfg(feature = "host")]
+    // Save a record of a backup
+    RestoreBackupItem(BackupRecord),
+    #[cfg(feature = "host")]
+    // Remove the saved item of the given backup index
+    ClearBackupItem(u16),
+    // Serialized value of user code
+    UserData {
+        namespace: u16,
+        key: u16,
+        value: UserValue,
+    },
+    // Remove a value of user code
+    RemoveUserData {
+        namespace: u16,
+        key: u16,
+    },
+    // Read a value of user code; storage task replies via `USER_DATA_RESPONSE`.
+    ReadUserData {
+        namespace: u16,
+        key: u16,
+    },
+    #[cfg(feature = "host")]
+    // Switch the keymap profile; storage task sends its keys via `PROFILE_KEY_CHANNEL`.
+    SwitchProfile(u8),
+    // Output, host OS and lighting state set with keys
+    RuntimeState(RuntimeState),
+    // Save the batched edits now; storage task replies via `FLUSHED`.
+    Flush,
+}
+
+impl FlashOperationMessage {
+    /// Edits of the keymap and the settings, which nobody waits for. The storage task batches
+    /// them and saves them after a pause, so that bursts of edits, like Vial writing a whole
+    /// layer, neither stall the key scanning nor save the same item again and again.
+    fn is_batched(&self) -> bool {
+        match self {
+            #[cfg(feature = "host")]
+            Self::MacroData(_)
+            | Self::KeymapKey { .. }
+            | Self::Encoder { .. }
+            | Self::Combo { .. }
+            | Self::Fork { .. }
+            | Self::Morse { .. } => true,
+            #[cfg(feature = "audio")]
+            Self::AudioSettings(_) => true,
+            Self::LayoutOptions(_)
+            | Self::DefaultLayer(_)
+            | Self::ComboTimeout(_)
+            | Self::OneShotTimeout(_)
+            | Self::TapInterval(_)
+            | Self::TapCapslockInterval(_)
+            | Self::PriorIdleTime(_)
+            | Self::MorseDefaultProfile(_)
+            | Self::UserData { .. }
+            | Self::RemoveUserData { .. }
+            | Self::RuntimeState(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Signal of the task which waits for this operation to finish
+    fn completion(&self) -> Option<&'static Signal<crate::RawMutex, bool>> {
+        match self {
+            #[cfg(feature = "_ble")]
+            Self::ClearSlot(_) | Self::ActiveBleProfile(_) => Some(&BLE_PROFILE_SAVED),
+            #[cfg(feature = "host")]
+            Self::RestoreBackupItem(_) | Self::ClearBackupItem(_) => Some(&BACKUP_ITEM_RESTORED),
+            Self::Flush => Some(&FLUSHED),
+            _ => None,
+        }
+    }
+
+    /// Whether `self` and `other` save the same item, or the same field of it.
+    fn saves_same_item(&self, other: &Self) -> bool {
+        match (self, other) {
+            #[cfg(feature = "host")]
+            (
+                Self::KeymapKey { layer, row, col, .. },
+                Self::KeymapKey {
+                    layer: l,
+                    row: r,
+                    col: c,
+                    ..
+                },
+            ) => (layer, row, col) == (l, r, c),
+            #[cfg(feature = "host")]
+            (Self::Encoder { layer, idx, .. }, Self::Encoder { layer: l, idx: i, .. }) => (layer, idx) == (l, i),
+            #[cfg(feature = "host")]
+            (Self::Combo { idx, .. }, Self::Combo { idx: i, .. })
+            | (Self::Fork { idx, .. }, Self::Fork { idx: i, .. })
+            | (Self::Morse { idx, .. }, Self::Morse { idx: i, .. }) => idx == i,
+            // A removal and a write of the same value replace each other
+            (
+                Self::UserData { namespace, key, .. } | Self::RemoveUserData { namespace, key },
+                Self::UserData {
+                    namespace: n, key: k, ..
+                }
+                | Self::RemoveUserData { namespace: n, key: k },
+            ) => (namespace, key) == (n, k),
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -197,6 +405,28 @@ pub(crate) enum StorageKey {
     ActiveBleProfile,
     #[cfg(feature = "_ble")]
     BondInfo(u8),
+    #[cfg(feature = "audio")]
+    AudioSettings,
+    #[cfg(feature = "_ble")]
+    BleName,
+    User {
+        namespace: u16,
+        key: u16,
+    },
+    /// Key of the keymap profiles after the first one, which uses `Keymap`
+    #[cfg(feature = "host")]
+    ProfileKeymap {
+        profile: u8,
+        layer: u8,
+        row: u8,
+        col: u8,
+    },
+    #[cfg(feature = "host")]
+    KeymapProfile,
+    RuntimeState,
+    /// The last crash, see [`crate::watchdog::crash`]
+    #[cfg(feature = "crash_dump")]
+    CrashRecord,
 }
 
 impl StorageKey {
@@ -205,6 +435,34 @@ impl StorageKey {
         Self::Keymap { layer, row, col }
     }
 
+    #[cfg(feature = "host")]
+    pub(crate) const fn profile_keymap(profile: u8, layer: u8, row: u8, col: u8) -> Self {
+        match profile {
+            0 => Self::Keymap { layer, row, col },
+            _ => Self::ProfileKeymap {
+                profile,
+                layer,
+                row,
+                col,
+            },
+        }
+    }
+
+    /// `(profile, layer, row, col)` of a keymap key
+    #[cfg(feature = "host")]
+    pub(crate) const fn keymap_position(&self) -> Option<(u8, u8, u8, u8)> {
+        match *self {
+            Self::Keymap { layer, row, col } => Some((0, layer, row, col)),
+            Self::ProfileKeymap {
+                profile,
+                layer,
+                row,
+                col,
+            } => Some((profile, layer, row, col)),
+            _ => None,
+        }
+    }
+
     #[cfg(feature = "_ble")]
     pub(crate) const fn bond_info(slot_num: u8) -> Self {
         Self::BondInfo(slot_num)
@@ -278,10 +536,61 @@ pub(crate) enum StorageData {
     BondInfo(ProfileInfo),
     #[cfg(feature = "_ble")]
     ActiveBleProfile(u8),
+    #[cfg(feature = "audio")]
+    AudioSettings(AudioSettings),
+    #[cfg(feature = "_ble")]
+    BleName(heapless::String<BLE_NAME_MAX_LEN>),
+    UserData(UserValue),
+    #[cfg(feature = "host")]
+    KeymapProfile(u8),
+    RuntimeState(RuntimeState),
+    #[cfg(feature = "crash_dump")]
+    CrashRecord(CrashRecord),
 }
 
 impl<'a> PostcardValue<'a> for StorageData {}
 
+impl StorageData {
+    /// Whether this is the kind of data saved under `key`
+    fn is_data_of(&self, key: &StorageKey) -> bool {
+        // Matched on every key, so that a new key can't be left out
+        match key {
+            StorageKey::StorageConfig => matches!(self, Self::StorageConfig(_)),
+            StorageKey::LayoutConfig => matches!(self, Self::LayoutConfig(_)),
+            StorageKey::BehaviorConfig => matches!(self, Self::BehaviorConfig(_)),
+            StorageKey::ConnectionType => matches!(self, Self::ConnectionType(_)),
+            #[cfg(feature = "host")]
+            StorageKey::MacroData => matches!(self, Self::MacroData(_)),
+            #[cfg(feature = "host")]
+            StorageKey::Keymap { .. } | StorageKey::ProfileKeymap { .. } => matches!(self, Self::KeyAction(_)),
+            #[cfg(feature = "host")]
+            StorageKey::Encoder { .. } => matches!(self, Self::EncoderAction(_)),
+            #[cfg(feature = "host")]
+            StorageKey::Combo(_) => matches!(self, Self::Combo(_)),
+            #[cfg(feature = "host")]
+            StorageKey::Fork(_) => matches!(self, Self::Fork(_)),
+            #[cfg(feature = "host")]
+            StorageKey::Morse(_) => matches!(self, Self::Morse(_)),
+            #[cfg(feature = "host")]
+            StorageKey::KeymapProfile => matches!(self, Self::KeymapProfile(_)),
+            #[cfg(all(feature = "_ble", feature = "split"))]
+            StorageKey::PeerAddress(_) => matches!(self, Self::PeerAddress(_)),
+            #[cfg(feature = "_ble")]
+            StorageKey::ActiveBleProfile => matches!(self, Self::ActiveBleProfile(_)),
+            #[cfg(feature = "_ble")]
+            StorageKey::BondInfo(_) => matches!(self, Self::BondInfo(_)),
+            #[cfg(feature = "_ble")]
+            StorageKey::BleName => matches!(self, Self::BleName(_)),
+            #[cfg(feature = "audio")]
+            StorageKey::AudioSettings => matches!(self, Self::AudioSettings(_)),
+            StorageKey::User { .. } => matches!(self, Self::UserData(_)),
+            StorageKey::RuntimeState => matches!(self, Self::RuntimeState(_)),
+            #[cfg(feature = "crash_dump")]
+            StorageKey::CrashRecord => matches!(self, Self::CrashRecord(_)),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) struct LocalStorageConfig {
@@ -315,6 +624,51 @@ pub(crate) struct BehaviorConfig {
     pub(crate) tap_capslock_interval: u16,
 }
 
+/// State which keys change at runtime, restored at boot so the keyboard comes back the way it was left.
+///
+/// The fields don't depend on the enabled features, so that the saved state stays readable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) struct RuntimeState {
+    /// Output forced with `OutputUsb` or `OutputBluetooth`, the output itself is the saved `ConnectionType`
+    output_forced: bool,
+    output_mirror: bool,
+    host_os_override: Option<HostOs>,
+    auto_brightness: bool,
+}
+
+impl RuntimeState {
+    fn current() -> Self {
+        #[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+        let (output_forced, output_mirror) = {
+            let status = crate::state::current_connection_status();
+            (status.forced, status.mirror)
+        };
+        #[cfg(not(all(feature = "_ble", not(feature = "_no_usb"))))]
+        let (output_forced, output_mirror) = (false, false);
+        #[cfg(feature = "ambient_light")]
+        let auto_brightness = crate::ambient_light::auto_brightness_enabled();
+        #[cfg(not(feature = "ambient_light"))]
+        let auto_brightness = true;
+
+        Self {
+            output_forced,
+            output_mirror,
+            host_os_override: crate::host_os::host_os_override(),
+            auto_brightness,
+        }
+    }
+
+    fn apply(&self) {
+        debug!("Restoring runtime state: {:?}", self);
+        #[cfg(all(feature = "_ble", not(feature = "_no_usb")))]
+        crate::state::restore_output(self.output_forced, self.output_mirror);
+        crate::host_os::set_host_os_override(self.host_os_override);
+        #[cfg(feature = "ambient_light")]
+        crate::ambient_light::set_auto_brightness(self.auto_brightness);
+    }
+}
+
 impl From<LocalStorageConfig> for StorageData {
     fn from(config: LocalStorageConfig) -> Self {
         Self::StorageConfig(config)
@@ -329,7 +683,7 @@ impl From<LayoutConfig> for StorageData {
 
 impl From<&config::BehaviorConfig> for StorageData {
     fn from(behavior: &config::BehaviorConfig) -> Self {
-        // Note: default_layer persists via LayoutConfig (restored in read_keymap), not this struct.
+        // Note: default_layer persists via LayoutConfig (restored in read_runtime_state), not this struct.
         Self::BehaviorConfig(BehaviorConfig {
             prior_idle_time: behavior.morse.prior_idle_time.as_millis() as u16,
             morse_default_profile: behavior.morse.default_profile,
@@ -371,14 +725,50 @@ pub struct Storage<
 > {
     pub(crate) flash: MapStorage<StorageKey, F, NoCache>,
     pub(crate) buffer: [u8; get_buffer_size()],
+    /// Keymap profile which is loaded, and which keymap edits are saved to
+    #[cfg(feature = "host")]
+    pub(crate) profile: u8,
+}
+
+/// Postcard flavor which checks that a value serializes to `expected`, without writing it.
+struct SameBytes<'a> {
+    expected: &'a [u8],
+    len: usize,
+    same: bool,
+}
+
+impl<'a> SameBytes<'a> {
+    fn new(expected: &'a [u8]) -> Self {
+        Self {
+            expected,
+            len: 0,
+            same: true,
+        }
+    }
+}
+
+impl postcard::ser_flavors::Flavor for SameBytes<'_> {
+    type Output = bool;
+
+    fn try_push(&mut self, data: u8) -> postcard::Result<()> {
+        self.same &= self.expected.get(self.len) == Some(&data);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finalize(self) -> postcard::Result<bool> {
+        Ok(self.same && self.len == self.expected.len())
+    }
 }
 
 /// Read out storage config, update and then save back.
-/// This macro applies to only some of the configs.
+/// This macro applies to only some of the configs, and skips the write when the field is unchanged.
 macro_rules! update_storage_field {
     ($f: expr, $buf: expr, $key:ident, $field:ident) => {{
         let key = StorageKey::$key;
-        if let Ok(Some(StorageData::$key(mut saved))) = $f.fetch_item($buf, &key).await {
+        if let Ok(Some(StorageData::$key(mut saved))) = $f.fetch_item($buf, &key).await
+            && saved.$field != $field
+        {
             saved.$field = $field;
             $f.store_item($buf, &key, &StorageData::$key(saved)).await
         } else {
@@ -391,16 +781,45 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     Storage<F, ROW, COL, NUM_LAYER, NUM_ENCODER>
 {
     async fn fetch_data(&mut self, key: StorageKey) -> Option<StorageData> {
-        match self.flash.fetch_item(&mut self.buffer, &key).await {
-            Ok(data) => data,
+        self.fetch_checked(key).await.ok().flatten()
+    }
+
+    /// Fetch the saved item of `key`, `None` if nothing usable is saved.
+    ///
+    /// sequential-storage checks every item with a CRC, so items torn by a power loss are skipped
+    /// already. An item which passes the CRC but doesn't decode, or isn't the data of `key`, like
+    /// one written by an incompatible firmware, is removed, so that the default is used instead
+    /// of garbage, now and at the next boot.
+    pub(crate) async fn fetch_checked(&mut self, key: StorageKey) -> Result<Option<StorageData>, ()> {
+        match self.flash.fetch_item::<StorageData>(&mut self.buffer, &key).await {
+            Ok(Some(data)) if data.is_data_of(&key) => Ok(Some(data)),
+            Ok(None) => Ok(None),
+            Ok(Some(_)) | Err(SSError::SerializationError(_)) => {
+                warn!("Storage item {:?} is corrupted, using the default", key);
+                if let Err(e) = self.flash.remove_item(&mut self.buffer, &key).await {
+                    print_storage_error::<F>(e);
+                }
+                Ok(None)
+            }
             Err(e) => {
                 print_storage_error::<F>(e);
-                None
+                Err(())
             }
         }
     }
 
-    async fn store_data(&mut self, key: StorageKey, data: &StorageData) -> Result<(), SSError<F::Error>> {
+    /// Save `data`, unless the saved value is the same already.
+    ///
+    /// The map appends every stored item to a log and erases a sector only when the log wraps,
+    /// so skipping unchanged values keeps repeated saves of the same setting from wearing the flash.
+    pub(crate) async fn store_data(&mut self, key: StorageKey, data: &StorageData) -> Result<(), SSError<F::Error>> {
+        if let Ok(Some(saved)) = self.flash.fetch_item::<StorageData>(&mut self.buffer, &key).await
+            && let Ok(bytes) = postcard::to_slice(data, &mut self.buffer)
+            && matches!(postcard::serialize_with_flavor(&saved, SameBytes::new(bytes)), Ok(true))
+        {
+            debug!("Skipping the unchanged storage item {:?}", key);
+            return Ok(());
+        }
         self.flash.store_item(&mut self.buffer, &key, data).await
     }
 
@@ -450,9 +869,20 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
             start_addr as u32..(start_addr + storage_config.num_sectors as usize * F::ERASE_SIZE) as u32
         };
 
+        // The map needs a free sector to move the live items into when it erases one
+        let required = required_storage_size::<ROW, COL, NUM_LAYER, NUM_ENCODER>(F::WRITE_SIZE);
+        if required > (storage_config.num_sectors as usize - 1) * F::ERASE_SIZE {
+            warn!(
+                "The keymap and settings need up to {} KB of storage plus a free sector, increase `num_sectors`",
+                required.div_ceil(1024)
+            );
+        }
+
         let mut storage = Self {
             flash: MapStorage::new(flash, MapConfig::new(storage_range), NoCache::new()),
             buffer: [0; get_buffer_size()],
+            #[cfg(feature = "host")]
+            profile: 0,
         };
 
         // Check whether keymap and configs have been storaged in flash
@@ -494,20 +924,36 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
             }
         }
 
+        #[cfg(feature = "crash_dump")]
+        storage.load_crash_record().await;
+
         storage
     }
 
+    /// Save the crash before this boot, or load the last saved crash, for `crash::last_crash`.
+    #[cfg(feature = "crash_dump")]
+    async fn load_crash_record(&mut self) {
+        use crate::watchdog::crash::{set_last_crash, take_crash_slot};
+
+        if let Some(record) = take_crash_slot() {
+            error!("Crashed before this boot: {:?}", record);
+            if let Err(e) = self
+                .store_data(StorageKey::CrashRecord, &StorageData::CrashRecord(record.clone()))
+                .await
+            {
+                print_storage_error::<F>(e);
+            }
+            set_last_crash(record);
+        } else if let Some(StorageData::CrashRecord(record)) = self.fetch_data(StorageKey::CrashRecord).await {
+            set_last_crash(record);
+        }
+    }
+
     pub(crate) async fn read_behavior_config(
         &mut self,
         behavior_config: &mut config::BehaviorConfig,
     ) -> Result<(), ()> {
-        let read_data = self
-            .flash
-            .fetch_item(&mut self.buffer, &StorageKey::BehaviorConfig)
-            .await
-            .map_err(|e| print_storage_error::<F>(e))?;
-
-        if let Some(StorageData::BehaviorConfig(c)) = read_data {
+        if let Some(StorageData::BehaviorConfig(c)) = self.fetch_checked(StorageKey::BehaviorConfig).await? {
             behavior_config.morse.prior_idle_time = Duration::from_millis(c.prior_idle_time as u64);
             behavior_config.morse.default_profile = c.morse_default_profile;
 
@@ -520,64 +966,78 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         Ok(())
     }
 
+    /// Restore the default layer set with a `PDF` key, and the [`RuntimeState`].
+    pub(crate) async fn read_runtime_state(&mut self, behavior_config: &mut config::BehaviorConfig) {
+        if let Some(StorageData::LayoutConfig(config)) = self.fetch_data(StorageKey::LayoutConfig).await {
+            behavior_config.default_layer = config.default_layer;
+        }
+        if let Some(StorageData::RuntimeState(state)) = self.fetch_data(StorageKey::RuntimeState).await {
+            state.apply();
+        }
+    }
+
     async fn initialize_storage_with_config(
         &mut self,
         #[cfg(feature = "host")] keymap: &[[[KeyAction; COL]; ROW]; NUM_LAYER],
         #[cfg(feature = "host")] encoder_map: &Option<&mut [[EncoderAction; NUM_ENCODER]; NUM_LAYER]>,
         behavior: &config::BehaviorConfig,
     ) -> Result<(), ()> {
+        // The storage was just erased, so skip the lookup of unchanged values in `store_data`,
+        // which reads the whole map for every item.
         // Save storage config
-        self.store_data(
-            StorageKey::StorageConfig,
-            &StorageData::from(LocalStorageConfig {
-                enable: true,
-                build_hash: BUILD_HASH,
-            }),
-        )
-        .await
-        .map_err(|e| print_storage_error::<F>(e))?;
+        self.flash
+            .store_item(
+                &mut self.buffer,
+                &StorageKey::StorageConfig,
+                &StorageData::from(LocalStorageConfig {
+                    enable: true,
+                    build_hash: BUILD_HASH,
+                }),
+            )
+            .await
+            .map_err(|e| print_storage_error::<F>(e))?;
 
         // Save layout config
-        self.store_data(
-            StorageKey::LayoutConfig,
-            &StorageData::from(LayoutConfig {
-                default_layer: 0,
-                layout_option: 0,
-            }),
-        )
-        .await
-        .map_err(|e| print_storage_error::<F>(e))?;
+        self.flash
+            .store_item(
+                &mut self.buffer,
+                &StorageKey::LayoutConfig,
+                &StorageData::from(LayoutConfig {
+                    default_layer: 0,
+                    layout_option: 0,
+                }),
+            )
+            .await
+            .map_err(|e| print_storage_error::<F>(e))?;
 
         // Save behavior config
-        self.store_data(StorageKey::BehaviorConfig, &StorageData::from(behavior))
+        self.flash
+            .store_item(
+                &mut self.buffer,
+                &StorageKey::BehaviorConfig,
+                &StorageData::from(behavior),
+            )
             .await
             .map_err(|e| print_storage_error::<F>(e))?;
 
         #[cfg(feature = "host")]
-        for (layer, layer_data) in keymap.iter().enumerate() {
-            for (row, row_data) in layer_data.iter().enumerate() {
-                for (col, action) in row_data.iter().enumerate() {
-                    self.store_data(
-                        StorageKey::keymap(layer as u8, row as u8, col as u8),
-                        &StorageData::KeyAction(*action),
-                    )
-                    .await
-                    .map_err(|e| print_storage_error::<F>(e))?;
-                }
-            }
-        }
+        self.store_keymap_profiles(keymap)
+            .await
+            .map_err(|e| print_storage_error::<F>(e))?;
 
         // Save encoder configurations
         #[cfg(feature = "host")]
         if let Some(encoder_map) = encoder_map {
             for (layer, layer_data) in encoder_map.iter().enumerate() {
                 for (idx, action) in layer_data.iter().enumerate() {
-                    self.store_data(
-                        StorageKey::encoder(idx as u8, layer as u8),
-                        &StorageData::EncoderAction(*action),
-                    )
-                    .await
-                    .map_err(|e| print_storage_error::<F>(e))?;
+                    self.flash
+                        .store_item(
+                            &mut self.buffer,
+                            &StorageKey::encoder(idx as u8, layer as u8),
+                            &StorageData::EncoderAction(*action),
+                        )
+                        .await
+                        .map_err(|e| print_storage_error::<F>(e))?;
                 }
             }
         }
@@ -603,18 +1063,11 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         self.store_data(StorageKey::BehaviorConfig, &StorageData::from(behavior))
             .await?;
 
+        self.store_data(StorageKey::KeymapProfile, &StorageData::KeymapProfile(0))
+            .await?;
+
         // TODO: Generic reset for vial and other hosts
-        for (layer, layer_data) in keymap.iter().enumerate() {
-            for (row, row_data) in layer_data.iter().enumerate() {
-                for (col, action) in row_data.iter().enumerate() {
-                    self.store_data(
-                        StorageKey::keymap(layer as u8, row as u8, col as u8),
-                        &StorageData::KeyAction(*action),
-                    )
-                    .await?;
-                }
-            }
-        }
+        self.store_keymap_profiles(keymap).await?;
 
         // TODO: Generic reset for vial and other hosts
         if let Some(encoder_map) = encoder_map {
@@ -632,6 +1085,34 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         Ok(())
     }
 
+    /// Save `keymap` as the keymap of every profile.
+    ///
+    /// The keys are written as one batch, without looking for unchanged values like
+    /// [`Self::store_data`] does: every lookup reads the whole map, which would make saving
+    /// the profiles quadratic in the size of the keymap.
+    #[cfg(feature = "host")]
+    async fn store_keymap_profiles(
+        &mut self,
+        keymap: &[[[KeyAction; COL]; ROW]; NUM_LAYER],
+    ) -> Result<(), SSError<F::Error>> {
+        for profile in 0..KEYMAP_PROFILE_NUM as u8 {
+            for (layer, layer_data) in keymap.iter().enumerate() {
+                for (row, row_data) in layer_data.iter().enumerate() {
+                    for (col, action) in row_data.iter().enumerate() {
+                        self.flash
+                            .store_item(
+                                &mut self.buffer,
+                                &StorageKey::profile_keymap(profile, layer as u8, row as u8, col as u8),
+                                &StorageData::KeyAction(*action),
+                            )
+                            .await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn check_enable(&mut self) -> bool {
         if let Some(StorageData::StorageConfig(config)) = self.fetch_data(StorageKey::StorageConfig).await
             && config.enable
@@ -654,7 +1135,10 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
         let mut peripheral_addresses: heapless::Vec<Option<[u8; 6]>, PERI_NUM> = heapless::Vec::new();
         for id in 0..PERI_NUM {
             let entry = match self.fetch_data(StorageKey::peer_address(id as u8)).await {
-                Some(StorageData::PeerAddress(addr)) if addr.is_valid => Some(addr.address),
+                Some(StorageData::PeerAddress(addr)) if addr.is_valid => {
+                    crate::split::ble::update_saved_peer(&addr);
+                    Some(addr.address)
+                }
                 _ => None,
             };
             peripheral_addresses.push(entry).unwrap();
@@ -667,164 +1151,287 @@ impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usiz
     crate::core_traits::Runnable for Storage<F, ROW, COL, NUM_LAYER, NUM_ENCODER>
 {
     async fn run(&mut self) -> ! {
+        let mut batch: heapless::Vec<FlashOperationMessage, FLASH_BATCH_SIZE> = heapless::Vec::new();
+        let mut batch_start = Instant::now();
         loop {
-            let info: FlashOperationMessage = FLASH_CHANNEL.receive().await;
+            let info = if batch.is_empty() {
+                FLASH_CHANNEL.receive().await
+            } else {
+                match with_timeout(FLASH_BATCH_QUIET_TIME, FLASH_CHANNEL.receive()).await {
+                    Ok(info) => info,
+                    // Save the batch once neither edits nor key actions came in for a while
+                    Err(_) => {
+                        if !crate::keyboard::typed_within(FLASH_BATCH_QUIET_TIME)
+                            || batch_start.elapsed() >= FLASH_BATCH_MAX_DELAY
+                        {
+                            self.save_batch(&mut batch).await;
+                        }
+                        continue;
+                    }
+                }
+            };
             debug!("Flash operation: {:?}", info);
 
-            let write_result: Result<(), SSError<F::Error>> = match info {
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ReadBleBondInfo(slot_num) => {
-                    let resp = match self.fetch_data(StorageKey::bond_info(slot_num)).await {
-                        Some(StorageData::BondInfo(info)) => Some(info),
-                        _ => None,
-                    };
-                    BOND_INFO_RESPONSE.signal(resp);
-                    continue;
-                }
-                #[cfg(all(feature = "_ble", feature = "split"))]
-                FlashOperationMessage::ReadPeerAddress(peer_id) => {
-                    let resp = match self.fetch_data(StorageKey::peer_address(peer_id)).await {
-                        Some(StorageData::PeerAddress(addr)) => Some(addr),
-                        _ => None,
-                    };
-                    PEER_ADDRESS_RESPONSE.signal(resp);
-                    continue;
-                }
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ReadConnectionType => {
-                    let resp = match self.fetch_data(StorageKey::ConnectionType).await {
-                        Some(StorageData::ConnectionType(v)) => Some(v),
-                        _ => None,
-                    };
-                    CONNECTION_TYPE_RESPONSE.signal(resp);
-                    continue;
-                }
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ReadActiveBleProfile => {
-                    let resp = match self.fetch_data(StorageKey::ActiveBleProfile).await {
-                        Some(StorageData::ActiveBleProfile(v)) => Some(v),
-                        _ => None,
-                    };
-                    ACTIVE_BLE_PROFILE_RESPONSE.signal(resp);
-                    continue;
-                }
+            if !info.is_batched() {
+                self.save_batch(&mut batch).await;
+                self.process(info).await;
+                continue;
+            }
+            if batch.is_empty() {
+                batch_start = Instant::now();
+            }
+            // Only the latest value of an item needs to be saved
+            batch.retain(|pending| !pending.saves_same_item(&info));
+            if let Err(info) = batch.push(info) {
+                self.save_batch(&mut batch).await;
+                self.process(info).await;
+            }
+        }
+    }
+}
 
-                FlashOperationMessage::LayoutOptions(layout_option) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, LayoutConfig, layout_option)
-                }
-                FlashOperationMessage::Reset => self.flash.erase_all().await,
-                FlashOperationMessage::ResetLayout => {
-                    info!("Ignoring ResetLayout at runtime (handled at startup via clear_layout).");
-                    Ok(())
-                }
-                FlashOperationMessage::DefaultLayer(default_layer) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, LayoutConfig, default_layer)
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::MacroData(data) => {
-                    self.store_data(StorageKey::MacroData, &StorageData::MacroData(data))
-                        .await
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::KeymapKey {
-                    layer,
-                    row,
-                    col,
-                    action,
-                } => {
-                    self.store_data(StorageKey::keymap(layer, row, col), &StorageData::KeyAction(action))
-                        .await
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::Encoder { layer, idx, action } => {
-                    self.store_data(StorageKey::encoder(idx, layer), &StorageData::EncoderAction(action))
-                        .await
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::Combo { idx, config } => {
-                    self.store_data(StorageKey::combo(idx), &StorageData::Combo(config))
-                        .await
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::Fork { idx, fork } => {
-                    self.store_data(StorageKey::fork(idx), &StorageData::Fork(fork)).await
-                }
-                #[cfg(feature = "host")]
-                FlashOperationMessage::Morse { idx, morse } => {
-                    self.store_data(StorageKey::morse(idx), &StorageData::Morse(morse))
-                        .await
-                }
-                FlashOperationMessage::ConnectionType(ty) => {
-                    self.store_data(StorageKey::ConnectionType, &StorageData::ConnectionType(ty))
-                        .await
-                }
-                #[cfg(all(feature = "_ble", feature = "split"))]
-                FlashOperationMessage::PeerAddress(peer) => {
-                    self.store_data(StorageKey::peer_address(peer.peer_id), &StorageData::PeerAddress(peer))
-                        .await
-                }
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ActiveBleProfile(profile) => {
-                    self.store_data(StorageKey::ActiveBleProfile, &StorageData::ActiveBleProfile(profile))
-                        .await
-                }
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ClearSlot(slot_num) => {
-                    use trouble_host::prelude::SecurityLevel;
-                    use trouble_host::{Address, BondInformation, Identity, LongTermKey};
-
-                    info!("Clearing bond info slot_num: {}", slot_num);
-                    // Remove item in `sequential-storage` is quite expensive, so just override the item with `removed = true`
-                    let empty = ProfileInfo {
-                        removed: true,
-                        slot_num,
-                        info: BondInformation::new(
-                            Identity {
-                                addr: Address::default(),
-                                irk: None,
-                            },
-                            LongTermKey::from_le_bytes([0; 16]),
-                            SecurityLevel::NoEncryption,
-                            false,
-                        ),
-                        cccd_table: heapless::Vec::new(),
-                    };
-                    self.store_data(StorageKey::bond_info(slot_num), &StorageData::BondInfo(empty))
-                        .await
-                }
-                #[cfg(feature = "_ble")]
-                FlashOperationMessage::ProfileInfo(b) => {
-                    debug!("Saving profile info: {:?}", b);
-                    self.store_data(StorageKey::bond_info(b.slot_num), &StorageData::BondInfo(b))
-                        .await
-                }
-                FlashOperationMessage::ComboTimeout(combo_timeout) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, combo_timeout)
-                }
-                FlashOperationMessage::OneShotTimeout(one_shot_timeout) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, one_shot_timeout)
-                }
-                FlashOperationMessage::TapInterval(tap_interval) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, tap_interval)
-                }
-                FlashOperationMessage::TapCapslockInterval(tap_capslock_interval) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, tap_capslock_interval)
-                }
-                FlashOperationMessage::PriorIdleTime(prior_idle_time) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, prior_idle_time)
-                }
-                FlashOperationMessage::MorseDefaultProfile(morse_default_profile) => {
-                    update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, morse_default_profile)
-                }
-            };
+impl<F: AsyncNorFlash, const ROW: usize, const COL: usize, const NUM_LAYER: usize, const NUM_ENCODER: usize>
+    Storage<F, ROW, COL, NUM_LAYER, NUM_ENCODER>
+{
+    /// Save the batched operations, in the order they came in.
+    async fn save_batch(&mut self, batch: &mut heapless::Vec<FlashOperationMessage, FLASH_BATCH_SIZE>) {
+        if !batch.is_empty() {
+            debug!("Saving {} batched flash operations", batch.len());
+        }
+        for info in core::mem::take(batch) {
+            self.process(info).await;
+        }
+    }
 
-            match write_result {
-                Ok(()) => FLASH_OPERATION_FINISHED.signal(true),
-                Err(e) => {
-                    print_storage_error::<F>(e);
-                    FLASH_OPERATION_FINISHED.signal(false);
-                }
+    /// Run a flash operation, and signal its result to the task waiting for it.
+    async fn process(&mut self, info: FlashOperationMessage) {
+        let completion = info.completion();
+        let write_result: Result<(), SSError<F::Error>> = match info {
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ReadBleBondInfo(slot_num) => {
+                let resp = match self.fetch_data(StorageKey::bond_info(slot_num)).await {
+                    Some(StorageData::BondInfo(info)) => Some(info),
+                    _ => None,
+                };
+                BOND_INFO_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(all(feature = "_ble", feature = "split"))]
+            FlashOperationMessage::ReadPeerAddress(peer_id) => {
+                let resp = match self.fetch_data(StorageKey::peer_address(peer_id)).await {
+                    Some(StorageData::PeerAddress(addr)) => Some(addr),
+                    _ => None,
+                };
+                PEER_ADDRESS_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ReadConnectionType => {
+                let resp = match self.fetch_data(StorageKey::ConnectionType).await {
+                    Some(StorageData::ConnectionType(v)) => Some(v),
+                    _ => None,
+                };
+                CONNECTION_TYPE_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ReadActiveBleProfile => {
+                let resp = match self.fetch_data(StorageKey::ActiveBleProfile).await {
+                    Some(StorageData::ActiveBleProfile(v)) => Some(v),
+                    _ => None,
+                };
+                ACTIVE_BLE_PROFILE_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(feature = "audio")]
+            FlashOperationMessage::ReadAudioSettings => {
+                let resp = match self.fetch_data(StorageKey::AudioSettings).await {
+                    Some(StorageData::AudioSettings(v)) => Some(v),
+                    _ => None,
+                };
+                AUDIO_SETTINGS_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ReadBleName => {
+                let resp = match self.fetch_data(StorageKey::BleName).await {
+                    Some(StorageData::BleName(name)) => Some(name),
+                    _ => None,
+                };
+                BLE_NAME_RESPONSE.signal(resp);
+                return;
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::ReadBackupItem(index) => {
+                let item = self.fetch_backup_item(index).await;
+                BACKUP_ITEM_RESPONSE.signal(item);
+                return;
+            }
+            FlashOperationMessage::ReadUserData { namespace, key } => {
+                let resp = match self.fetch_data(StorageKey::User { namespace, key }).await {
+                    Some(StorageData::UserData(value)) => Some(value),
+                    _ => None,
+                };
+                USER_DATA_RESPONSE.signal(resp);
+                return;
+            }
+
+            FlashOperationMessage::LayoutOptions(layout_option) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, LayoutConfig, layout_option)
+            }
+            FlashOperationMessage::Reset => self.flash.erase_all().await,
+            FlashOperationMessage::ResetLayout => {
+                info!("Ignoring ResetLayout at runtime (handled at startup via clear_layout).");
+                Ok(())
+            }
+            FlashOperationMessage::DefaultLayer(default_layer) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, LayoutConfig, default_layer)
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::MacroData(data) => {
+                self.store_data(StorageKey::MacroData, &StorageData::MacroData(data))
+                    .await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::KeymapKey {
+                layer,
+                row,
+                col,
+                action,
+            } => {
+                self.store_data(
+                    StorageKey::profile_keymap(self.profile, layer, row, col),
+                    &StorageData::KeyAction(action),
+                )
+                .await
             }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::Encoder { layer, idx, action } => {
+                self.store_data(StorageKey::encoder(idx, layer), &StorageData::EncoderAction(action))
+                    .await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::Combo { idx, config } => {
+                self.store_data(StorageKey::combo(idx), &StorageData::Combo(config))
+                    .await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::Fork { idx, fork } => {
+                self.store_data(StorageKey::fork(idx), &StorageData::Fork(fork)).await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::Morse { idx, morse } => {
+                self.store_data(StorageKey::morse(idx), &StorageData::Morse(morse))
+                    .await
+            }
+            FlashOperationMessage::ConnectionType(ty) => {
+                self.store_data(StorageKey::ConnectionType, &StorageData::ConnectionType(ty))
+                    .await
+            }
+            #[cfg(all(feature = "_ble", feature = "split"))]
+            FlashOperationMessage::PeerAddress(peer) => {
+                self.store_data(StorageKey::peer_address(peer.peer_id), &StorageData::PeerAddress(peer))
+                    .await
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ActiveBleProfile(profile) => {
+                self.store_data(StorageKey::ActiveBleProfile, &StorageData::ActiveBleProfile(profile))
+                    .await
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ClearSlot(slot_num) => {
+                use trouble_host::prelude::SecurityLevel;
+                use trouble_host::{Address, BondInformation, Identity, LongTermKey};
+
+                info!("Clearing bond info slot_num: {}", slot_num);
+                // Remove item in `sequential-storage` is quite expensive, so just override the item with `removed = true`
+                let empty = ProfileInfo {
+                    removed: true,
+                    slot_num,
+                    info: BondInformation::new(
+                        Identity {
+                            addr: Address::default(),
+                            irk: None,
+                        },
+                        LongTermKey::from_le_bytes([0; 16]),
+                        SecurityLevel::NoEncryption,
+                        false,
+                    ),
+                    cccd_table: heapless::Vec::new(),
+                };
+                self.store_data(StorageKey::bond_info(slot_num), &StorageData::BondInfo(empty))
+                    .await
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::ProfileInfo(b) => {
+                debug!("Saving profile info: {:?}", b);
+                self.store_data(StorageKey::bond_info(b.slot_num), &StorageData::BondInfo(b))
+                    .await
+            }
+            FlashOperationMessage::ComboTimeout(combo_timeout) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, combo_timeout)
+            }
+            FlashOperationMessage::OneShotTimeout(one_shot_timeout) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, one_shot_timeout)
+            }
+            FlashOperationMessage::TapInterval(tap_interval) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, tap_interval)
+            }
+            FlashOperationMessage::TapCapslockInterval(tap_capslock_interval) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, tap_capslock_interval)
+            }
+            FlashOperationMessage::PriorIdleTime(prior_idle_time) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, prior_idle_time)
+            }
+            FlashOperationMessage::MorseDefaultProfile(morse_default_profile) => {
+                update_storage_field!(&mut self.flash, &mut self.buffer, BehaviorConfig, morse_default_profile)
+            }
+            #[cfg(feature = "audio")]
+            FlashOperationMessage::AudioSettings(settings) => {
+                self.store_data(StorageKey::AudioSettings, &StorageData::AudioSettings(settings))
+                    .await
+            }
+            #[cfg(feature = "_ble")]
+            FlashOperationMessage::BleName(name) => {
+                self.store_data(StorageKey::BleName, &StorageData::BleName(name)).await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::RestoreBackupItem(record) => self.store_backup_item(&record).await,
+            #[cfg(feature = "host")]
+            FlashOperationMessage::ClearBackupItem(index) => self.clear_backup_item(index).await,
+            FlashOperationMessage::UserData { namespace, key, value } => {
+                self.store_data(StorageKey::User { namespace, key }, &StorageData::UserData(value))
+                    .await
+            }
+            FlashOperationMessage::RuntimeState(state) => {
+                self.store_data(StorageKey::RuntimeState, &StorageData::RuntimeState(state))
+                    .await
+            }
+            FlashOperationMessage::RemoveUserData { namespace, key } => {
+                self.flash
+                    .remove_item(&mut self.buffer, &StorageKey::User { namespace, key })
+                    .await
+            }
+            #[cfg(feature = "host")]
+            FlashOperationMessage::SwitchProfile(profile) => {
+                let result = self.send_keymap_profile(profile).await;
+                // The keyboard waits for the end of the keys, even if the switch fails
+                PROFILE_KEY_CHANNEL.send(None).await;
+                result
+            }
+            // The batch was saved before this message, see `run`
+            FlashOperationMessage::Flush => Ok(()),
+        };
+
+        let ok = match write_result {
+            Ok(()) => true,
+            Err(e) => {
+                print_storage_error::<F>(e);
+                false
+            }
+        };
+        if let Some(completion) = completion {
+            completion.signal(ok);
         }
     }
 }
@@ -845,7 +1452,50 @@ pub(crate) fn print_storage_error<F: AsyncNorFlash>(e: SSError<F::Error>) {
     }
 }
 
-const fn get_buffer_size() -> usize {
+/// Worst-case bytes of every item in the storage, at its largest: the settings, and with `host`
+/// the keymap, the encoders, the macros and as many combos, forks and morses as configured.
+///
+/// The capacities come from the `[rmk]` section of `keyboard.toml`, like `combo_max_num`, so the
+/// storage grows with them. BLE bonds and user data come on top.
+pub(crate) const fn required_storage_size<
+    const ROW: usize,
+    const COL: usize,
+    const NUM_LAYER: usize,
+    const NUM_ENCODER: usize,
+>(
+    write_size: usize,
+) -> usize {
+    // Every item has an 8 byte header and is padded to the write size of the flash, its key has
+    // a tag and at most 3 indices, its data a tag
+    const fn item(data_size: usize, write_size: usize) -> usize {
+        (8 + 4 + 1 + data_size).next_multiple_of(write_size)
+    }
+
+    let settings = item(LocalStorageConfig::POSTCARD_MAX_SIZE, write_size)
+        + item(LayoutConfig::POSTCARD_MAX_SIZE, write_size)
+        + item(BehaviorConfig::POSTCARD_MAX_SIZE, write_size)
+        + item(ConnectionType::POSTCARD_MAX_SIZE, write_size)
+        + item(RuntimeState::POSTCARD_MAX_SIZE, write_size);
+    #[cfg(feature = "crash_dump")]
+    let settings = settings + item(CrashRecord::POSTCARD_MAX_SIZE, write_size);
+
+    #[cfg(feature = "host")]
+    {
+        settings
+            + item(MACRO_SPACE_SIZE + 2, write_size)
+            + item(1, write_size)
+            + KEYMAP_PROFILE_NUM * NUM_LAYER * ROW * COL * item(KeyAction::POSTCARD_MAX_SIZE, write_size)
+            + NUM_LAYER * NUM_ENCODER * item(EncoderAction::POSTCARD_MAX_SIZE, write_size)
+            + crate::COMBO_MAX_NUM * item(ComboConfig::POSTCARD_MAX_SIZE, write_size)
+            + crate::FORK_MAX_NUM * item(Fork::POSTCARD_MAX_SIZE, write_size)
+            + crate::MORSE_MAX_NUM * item(Morse::POSTCARD_MAX_SIZE, write_size)
+    }
+
+    #[cfg(not(feature = "host"))]
+    settings
+}
+
+pub(crate) const fn get_buffer_size() -> usize {
     #[cfg(feature = "host")]
     {
         // The buffer size needed = size_of(StorageData) = MACRO_SPACE_SIZE + 8(generally)
@@ -872,7 +1522,7 @@ mod tests {
 
     use super::*;
     use crate::config::{BehaviorConfig as RuntimeBehaviorConfig, StorageConfig as RuntimeStorageConfig};
-    use crate::test_support::test_block_on as block_on;
+    use crate::simulation::block_on;
 
     #[derive(Debug, Clone, Copy)]
     struct TestFlashError;
@@ -995,6 +1645,23 @@ mod tests {
             StorageKey::ActiveBleProfile,
             #[cfg(feature = "_ble")]
             StorageKey::BondInfo(0),
+            #[cfg(feature = "audio")]
+            StorageKey::AudioSettings,
+            #[cfg(feature = "_ble")]
+            StorageKey::BleName,
+            StorageKey::User { namespace: 1, key: 2 },
+            #[cfg(feature = "host")]
+            StorageKey::ProfileKeymap {
+                profile: 1,
+                layer: 2,
+                row: 3,
+                col: 4,
+            },
+            #[cfg(feature = "host")]
+            StorageKey::KeymapProfile,
+            StorageKey::RuntimeState,
+            #[cfg(feature = "crash_dump")]
+            StorageKey::CrashRecord,
         ];
 
         let mut buffer = [0u8; 64];
@@ -1006,6 +1673,201 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "host")]
+    #[test]
+    fn batched_edits_of_the_same_item_are_merged() {
+        let key = |col, action| FlashOperationMessage::KeymapKey {
+            layer: 0,
+            row: 0,
+            col,
+            action,
+        };
+        assert!(key(0, KeyAction::No).is_batched());
+        assert!(key(0, KeyAction::No).saves_same_item(&key(0, KeyAction::Transparent)));
+        assert!(!key(0, KeyAction::No).saves_same_item(&key(1, KeyAction::No)));
+        assert!(FlashOperationMessage::TapInterval(200).saves_same_item(&FlashOperationMessage::TapInterval(150)));
+        assert!(!FlashOperationMessage::TapInterval(200).saves_same_item(&FlashOperationMessage::ComboTimeout(200)));
+        assert!(!FlashOperationMessage::Reset.is_batched());
+    }
+
+    #[cfg(feature = "host")]
+    #[test]
+    fn first_keymap_profile_uses_the_keymap_keys() {
+        assert_eq!(StorageKey::profile_keymap(0, 1, 2, 3), StorageKey::keymap(1, 2, 3));
+        assert_eq!(StorageKey::keymap(1, 2, 3).keymap_position(), Some((0, 1, 2, 3)));
+        assert_eq!(
+            StorageKey::profile_keymap(4, 1, 2, 3).keymap_position(),
+            Some((4, 1, 2, 3))
+        );
+        assert_eq!(StorageKey::KeymapProfile.keymap_position(), None);
+    }
+
+    #[test]
+    fn default_layer_is_restored_at_boot() {
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            #[cfg(feature = "host")]
+            let keymap = [[[KeyAction::No; 1]; 1]; 2];
+            #[cfg(feature = "host")]
+            let encoder_map: Option<&mut [[EncoderAction; 0]; 2]> = None;
+            let mut storage = Storage::<Flash, 1, 1, 2, 0>::new(
+                Flash::new(),
+                #[cfg(feature = "host")]
+                &keymap,
+                #[cfg(feature = "host")]
+                &encoder_map,
+                &RuntimeStorageConfig::default(),
+                &RuntimeBehaviorConfig::default(),
+            )
+            .await;
+            storage.process(FlashOperationMessage::DefaultLayer(1)).await;
+
+            let mut behavior_config = RuntimeBehaviorConfig::default();
+            storage.read_runtime_state(&mut behavior_config).await;
+            assert_eq!(behavior_config.default_layer, 1);
+        });
+    }
+
+    #[test]
+    fn user_data_is_saved_and_removed() {
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            #[cfg(feature = "host")]
+            let keymap = [[[KeyAction::No; 1]; 1]; 1];
+            #[cfg(feature = "host")]
+            let encoder_map: Option<&mut [[EncoderAction; 0]; 1]> = None;
+            let mut storage = Storage::<Flash, 1, 1, 1, 0>::new(
+                Flash::new(),
+                #[cfg(feature = "host")]
+                &keymap,
+                #[cfg(feature = "host")]
+                &encoder_map,
+                &RuntimeStorageConfig::default(),
+                &RuntimeBehaviorConfig::default(),
+            )
+            .await;
+            let value = UserValue::from_slice(&[0x40, 0x06]).unwrap();
+            let read = |namespace| FlashOperationMessage::ReadUserData { namespace, key: 0 };
+
+            storage
+                .process(FlashOperationMessage::UserData {
+                    namespace: 1,
+                    key: 0,
+                    value: value.clone(),
+                })
+                .await;
+            storage.process(read(1)).await;
+            assert_eq!(USER_DATA_RESPONSE.try_take(), Some(Some(value)));
+            storage.process(read(2)).await;
+            assert_eq!(USER_DATA_RESPONSE.try_take(), Some(None));
+
+            storage
+                .process(FlashOperationMessage::RemoveUserData { namespace: 1, key: 0 })
+                .await;
+            storage.process(read(1)).await;
+            assert_eq!(USER_DATA_RESPONSE.try_take(), Some(None));
+        });
+    }
+
+    #[test]
+    fn required_storage_size_grows_with_the_keymap() {
+        let key_item = required_storage_size::<2, 1, 1, 0>(4) - required_storage_size::<1, 1, 1, 0>(4);
+        #[cfg(feature = "host")]
+        assert!(key_item > KeyAction::POSTCARD_MAX_SIZE && key_item % 4 == 0);
+        #[cfg(not(feature = "host"))]
+        assert_eq!(key_item, 0);
+    }
+
+    #[test]
+    fn corrupted_item_is_removed() {
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            #[cfg(feature = "host")]
+            let keymap = [[[KeyAction::No; 1]; 1]; 1];
+            #[cfg(feature = "host")]
+            let encoder_map: Option<&mut [[EncoderAction; 0]; 1]> = None;
+            let mut storage = Storage::<Flash, 1, 1, 1, 0>::new(
+                Flash::new(),
+                #[cfg(feature = "host")]
+                &keymap,
+                #[cfg(feature = "host")]
+                &encoder_map,
+                &RuntimeStorageConfig::default(),
+                &RuntimeBehaviorConfig::default(),
+            )
+            .await;
+
+            // An item which decodes, but as the data of another key
+            storage
+                .flash
+                .store_item(
+                    &mut storage.buffer,
+                    &StorageKey::LayoutConfig,
+                    &StorageData::ConnectionType(ConnectionType::Ble),
+                )
+                .await
+                .unwrap();
+            assert!(matches!(
+                storage.fetch_checked(StorageKey::LayoutConfig).await,
+                Ok(None)
+            ));
+            assert!(matches!(
+                storage
+                    .flash
+                    .fetch_item::<StorageData>(&mut storage.buffer, &StorageKey::LayoutConfig)
+                    .await,
+                Ok(None)
+            ));
+            assert!(matches!(
+                storage.fetch_checked(StorageKey::BehaviorConfig).await,
+                Ok(Some(StorageData::BehaviorConfig(_)))
+            ));
+        });
+    }
+
+    #[test]
+    fn unchanged_value_is_not_written_again() {
+        block_on(async {
+            type Flash = TestFlash<16_384, 4_096, 1>;
+
+            #[cfg(feature = "host")]
+            let keymap = [[[KeyAction::No; 1]; 1]; 1];
+            #[cfg(feature = "host")]
+            let encoder_map: Option<&mut [[EncoderAction; 0]; 1]> = None;
+            let storage_config = RuntimeStorageConfig::default();
+            let mut storage = Storage::<Flash, 1, 1, 1, 0>::new(
+                Flash::new(),
+                #[cfg(feature = "host")]
+                &keymap,
+                #[cfg(feature = "host")]
+                &encoder_map,
+                &storage_config,
+                &RuntimeBehaviorConfig::default(),
+            )
+            .await;
+            let storage_range = (16_384 - storage_config.num_sectors as usize * 4_096) as u32..16_384u32;
+
+            let usb = StorageData::ConnectionType(ConnectionType::Usb);
+            storage.store_data(StorageKey::ConnectionType, &usb).await.unwrap();
+            let (flash, _) = storage.flash.destroy();
+            let written = flash.bytes;
+
+            storage.flash = MapStorage::new(flash, MapConfig::new(storage_range.clone()), NoCache::new());
+            storage.store_data(StorageKey::ConnectionType, &usb).await.unwrap();
+            let (flash, _) = storage.flash.destroy();
+            assert!(flash.bytes == written);
+
+            storage.flash = MapStorage::new(flash, MapConfig::new(storage_range), NoCache::new());
+            let ble = StorageData::ConnectionType(ConnectionType::Ble);
+            storage.store_data(StorageKey::ConnectionType, &ble).await.unwrap();
+            let (flash, _) = storage.flash.destroy();
+            assert!(flash.bytes != written);
+        });
+    }
+
     #[test]
     fn build_hash_mismatch_reinitializes_storage() {
         block_on(async {
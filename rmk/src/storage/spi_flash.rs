@@ -0,0 +1,214 @@
+//! External SPI NOR flash as the storage.
+//!
+//! Keyboards whose chip has a tiny internal flash, or which don't want the input to stall
+//! while the internal flash erases a sector, can keep the storage on an external SPI NOR flash
+//! chip instead, like the W25Q or GD25Q series. Any chip with the common JEDEC commands, 4 KiB
+//! sector erase and 256 byte pages works:
+//!
+//! ```ignore
+//! let flash = SpiNorFlash::new(spi_device, 2 * 1024 * 1024);
+//! let (keymap, storage) =
+//!     initialize_keymap_and_storage(&mut keymap_data, flash, &storage_config, &mut behavior, &positional).await;
+//! ```
+//!
+//! The driver waits for erases and writes with timers, so other tasks keep running meanwhile.
+//! For QSPI flashes and other memories, pass any driver which implements `NorFlash` of
+//! `embedded-storage-async` to the storage.
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash};
+
+const CMD_READ: u8 = 0x03;
+const CMD_PAGE_PROGRAM: u8 = 0x02;
+const CMD_SECTOR_ERASE: u8 = 0x20;
+const CMD_WRITE_ENABLE: u8 = 0x06;
+const CMD_READ_STATUS: u8 = 0x05;
+/// Write in progress bit of the status register
+const STATUS_BUSY: u8 = 0x01;
+
+/// Bytes written by one page program
+const PAGE_SIZE: usize = 256;
+/// Bytes erased by one sector erase
+pub const SECTOR_SIZE: usize = 4096;
+/// Largest flash reachable with 3 byte addresses
+const MAX_CAPACITY: usize = 16 * 1024 * 1024;
+/// Time between two polls of the status register while the flash is busy
+const BUSY_POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+/// Error of a [`SpiNorFlash`]
+#[derive(Debug)]
+pub enum SpiNorFlashError<E> {
+    Spi(E),
+    OutOfBounds,
+    NotAligned,
+}
+
+impl<E: core::fmt::Debug> NorFlashError for SpiNorFlashError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::Spi(_) => NorFlashErrorKind::Other,
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+        }
+    }
+}
+
+/// SPI NOR flash chip, addressed with 3 byte addresses
+pub struct SpiNorFlash<S> {
+    spi: S,
+    capacity: usize,
+}
+
+impl<S: SpiDevice> SpiNorFlash<S> {
+    /// Create the flash on the `spi` device, with a chip select of its own.
+    ///
+    /// `capacity` is the size of the chip in bytes, at most 16 MiB.
+    pub fn new(spi: S, capacity: usize) -> Self {
+        assert!(
+            capacity <= MAX_CAPACITY,
+            "SPI NOR flash larger than 16 MiB is not supported"
+        );
+        Self { spi, capacity }
+    }
+
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), SpiNorFlashError<S::Error>> {
+        if (offset as usize).checked_add(len).is_none_or(|end| end > self.capacity) {
+            return Err(SpiNorFlashError::OutOfBounds);
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiNorFlashError<S::Error>> {
+        self.spi.transaction(operations).await.map_err(SpiNorFlashError::Spi)
+    }
+
+    /// Run a command which modifies the flash, and wait until the flash has done it.
+    async fn modify(&mut self, command: [u8; 4], data: &[u8]) -> Result<(), SpiNorFlashError<S::Error>> {
+        self.send(&mut [Operation::Write(&[CMD_WRITE_ENABLE])]).await?;
+        self.send(&mut [Operation::Write(&command), Operation::Write(data)])
+            .await?;
+        loop {
+            let mut status = [0];
+            self.send(&mut [Operation::Write(&[CMD_READ_STATUS]), Operation::Read(&mut status)])
+                .await?;
+            if status[0] & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            Timer::after(BUSY_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Command with a 3 byte address
+fn command(cmd: u8, address: u32) -> [u8; 4] {
+    let [_, a2, a1, a0] = address.to_be_bytes();
+    [cmd, a2, a1, a0]
+}
+
+impl<S: SpiDevice> ErrorType for SpiNorFlash<S> {
+    type Error = SpiNorFlashError<S::Error>;
+}
+
+impl<S: SpiDevice> ReadNorFlash for SpiNorFlash<S> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        self.send(&mut [Operation::Write(&command(CMD_READ, offset)), Operation::Read(bytes)])
+            .await
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<S: SpiDevice> NorFlash for SpiNorFlash<S> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from as usize % SECTOR_SIZE != 0 || to as usize % SECTOR_SIZE != 0 {
+            return Err(SpiNorFlashError::NotAligned);
+        }
+        self.check_bounds(from, to.saturating_sub(from) as usize)?;
+        for sector in (from..to).step_by(SECTOR_SIZE) {
+            self.modify(command(CMD_SECTOR_ERASE, sector), &[]).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        let mut address = offset as usize;
+        let mut bytes = bytes;
+        // A page program wraps around at the end of the page, so split the bytes at page boundaries
+        while !bytes.is_empty() {
+            let (page, rest) = bytes.split_at(bytes.len().min(PAGE_SIZE - address % PAGE_SIZE));
+            self.modify(command(CMD_PAGE_PROGRAM, address as u32), page).await?;
+            address += page.len();
+            bytes = rest;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTrans};
+
+    use super::*;
+    use crate::simulation::block_on;
+
+    /// Transactions of a command which modifies the flash, which is ready at the first poll
+    fn modify(command: [u8; 4], data: Vec<u8>) -> Vec<SpiTrans<u8>> {
+        vec![
+            SpiTrans::transaction_start(),
+            SpiTrans::write_vec(vec![CMD_WRITE_ENABLE]),
+            SpiTrans::transaction_end(),
+            SpiTrans::transaction_start(),
+            SpiTrans::write_vec(command.to_vec()),
+            SpiTrans::write_vec(data),
+            SpiTrans::transaction_end(),
+            SpiTrans::transaction_start(),
+            SpiTrans::write_vec(vec![CMD_READ_STATUS]),
+            SpiTrans::read_vec(vec![0]),
+            SpiTrans::transaction_end(),
+        ]
+    }
+
+    #[test]
+    fn writes_are_split_at_page_boundaries() {
+        let mut expectations = modify([CMD_PAGE_PROGRAM, 0x00, 0x00, 0xFE], vec![1, 2]);
+        expectations.extend(modify([CMD_PAGE_PROGRAM, 0x00, 0x01, 0x00], vec![3, 4]));
+        let mut flash = SpiNorFlash::new(SpiMock::new(&expectations), 64 * 1024);
+
+        block_on(flash.write(0xFE, &[1, 2, 3, 4])).unwrap();
+        flash.spi.done();
+    }
+
+    #[test]
+    fn reads_and_erases_are_checked() {
+        let expectations = [
+            SpiTrans::transaction_start(),
+            SpiTrans::write_vec(vec![CMD_READ, 0x00, 0x10, 0x00]),
+            SpiTrans::read_vec(vec![0xAB, 0xCD]),
+            SpiTrans::transaction_end(),
+        ];
+        let mut flash = SpiNorFlash::new(SpiMock::new(&expectations), 64 * 1024);
+
+        let mut bytes = [0; 2];
+        block_on(flash.read(0x1000, &mut bytes)).unwrap();
+        assert_eq!(bytes, [0xAB, 0xCD]);
+        assert!(matches!(
+            block_on(flash.read(64 * 1024 - 1, &mut bytes)),
+            Err(SpiNorFlashError::OutOfBounds)
+        ));
+        assert!(matches!(
+            block_on(flash.erase(0x100, 0x1000)),
+            Err(SpiNorFlashError::NotAligned)
+        ));
+        flash.spi.done();
+    }
+}
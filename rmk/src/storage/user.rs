@@ -0,0 +1,101 @@
+//! Settings of user code in the storage.
+//!
+//! Custom processors and features can keep their own settings, like a pointer DPI or a display
+//! brightness, next to RMK's. Every feature picks a namespace, and keeps its values there under
+//! keys of its own:
+//!
+//! ```ignore
+//! const POINTER: UserStorage = UserStorage::new(0x5054);
+//! const DPI: u16 = 0;
+//!
+//! let dpi: u16 = POINTER.read(DPI).await.unwrap_or(800);
+//! POINTER.write(DPI, &1600_u16).await?;
+//! ```
+//!
+//! Values are serialized with postcard, so any `serde` type of at most [`USER_DATA_MAX_SIZE`]
+//! bytes works. Writes are batched with the keymap edits, reads see the batched writes.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::{FlashOperationMessage, USER_DATA_RESPONSE, request};
+use crate::channel::FLASH_CHANNEL;
+
+/// Largest serialized size of a user value
+pub const USER_DATA_MAX_SIZE: usize = 64;
+
+/// Serialized user value
+pub(crate) type UserValue = heapless::Vec<u8, USER_DATA_MAX_SIZE>;
+
+/// Error of [`UserStorage::write`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UserStorageError {
+    /// The value is larger than [`USER_DATA_MAX_SIZE`] once serialized
+    TooLarge,
+    /// The value can't be serialized
+    Serialize,
+}
+
+/// Values of one user feature in the storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UserStorage {
+    namespace: u16,
+}
+
+impl UserStorage {
+    /// Values in `namespace`, which no other feature of the firmware uses.
+    pub const fn new(namespace: u16) -> Self {
+        Self { namespace }
+    }
+
+    /// Read the value saved under `key`.
+    ///
+    /// Returns `None` if nothing is saved, or the saved value isn't a `T`.
+    pub async fn read<T: DeserializeOwned>(&self, key: u16) -> Option<T> {
+        let value = request(
+            FlashOperationMessage::ReadUserData {
+                namespace: self.namespace,
+                key,
+            },
+            &USER_DATA_RESPONSE,
+        )
+        .await?;
+        match postcard::from_bytes(&value) {
+            Ok(v) => Some(v),
+            Err(_) => {
+                warn!("User value {}:{} has a different type", self.namespace, key);
+                None
+            }
+        }
+    }
+
+    /// Save `value` under `key`.
+    pub async fn write<T: Serialize>(&self, key: u16, value: &T) -> Result<(), UserStorageError> {
+        let mut buf = [0_u8; USER_DATA_MAX_SIZE];
+        let bytes = postcard::to_slice(value, &mut buf).map_err(|e| match e {
+            postcard::Error::SerializeBufferFull => UserStorageError::TooLarge,
+            _ => UserStorageError::Serialize,
+        })?;
+        // `bytes` fits, it's a slice of `buf`
+        let value = UserValue::from_slice(bytes).map_err(|_| UserStorageError::TooLarge)?;
+        FLASH_CHANNEL
+            .send(FlashOperationMessage::UserData {
+                namespace: self.namespace,
+                key,
+                value,
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Remove the value saved under `key`.
+    pub async fn remove(&self, key: u16) {
+        FLASH_CHANNEL
+            .send(FlashOperationMessage::RemoveUserData {
+                namespace: self.namespace,
+                key,
+            })
+            .await;
+    }
+}
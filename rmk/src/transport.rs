@@ -0,0 +1,121 @@
+//! Custom wireless transports.
+//!
+//! Implement [`WirelessTransport`] for a radio that RMK doesn't drive itself, like a
+//! UART-to-BLE bridge (CH9141 and similar modules) or a proprietary 2.4 GHz link, and
+//! run it with [`run_wireless_transport`]. It takes the place of RMK's BLE stack: reports
+//! are routed to it like to a BLE host, and it reports the connection state and the lock
+//! LEDs of its host with [`WirelessEvent`]s.
+//!
+//! ```rust,ignore
+//! use embedded_io_async::{Read, Write};
+//! use rmk::hid::{HidError, HidWriterTrait, Report};
+//! use rmk::transport::{WirelessEvent, WirelessTransport, run_wireless_transport};
+//! use rmk::types::led_indicator::LedIndicator;
+//! use usbd_hid::descriptor::AsInputReport;
+//!
+//! /// A UART-to-BLE module, which takes a report as its length followed by its bytes,
+//! /// and sends a status byte when its link changes
+//! struct UartBridge<U> {
+//!     uart: U,
+//! }
+//!
+//! impl<U: Read + Write> HidWriterTrait for UartBridge<U> {
+//!     type ReportType = Report;
+//!
+//!     async fn write_report(&mut self, report: &Report) -> Result<usize, HidError> {
+//!         let mut frame = [0; 33];
+//!         let n = report
+//!             .serialize(&mut frame[1..])
+//!             .map_err(|_| HidError::ReportSerializeError)?;
+//!         frame[0] = n as u8;
+//!         self.uart
+//!             .write_all(&frame[..=n])
+//!             .await
+//!             .map_err(|_| HidError::TransportError)?;
+//!         Ok(n)
+//!     }
+//! }
+//!
+//! impl<U: Read + Write> WirelessTransport for UartBridge<U> {
+//!     async fn next_event(&mut self) -> WirelessEvent {
+//!         loop {
+//!             // One byte at a time, so that no status is lost when the future is dropped
+//!             let mut status = [0];
+//!             if !matches!(self.uart.read(&mut status).await, Ok(1)) {
+//!                 continue;
+//!             }
+//!             match status[0] {
+//!                 0x01 => return WirelessEvent::Pairing,
+//!                 0x02 => return WirelessEvent::Connected,
+//!                 0x03 => return WirelessEvent::Disconnected,
+//!                 // The lock LEDs of the host are in the low bits
+//!                 s if s & 0x80 != 0 => return WirelessEvent::LedIndicator(LedIndicator::from_bits(s & 0x1F)),
+//!                 _ => {}
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! join(run_all!(matrix), run_wireless_transport(UartBridge { uart })).await;
+//! ```
+
+use embassy_futures::select::{Either, select};
+use rmk_types::ble::BleState;
+use rmk_types::connection::{ConnectionType, ReportQueueOverflow};
+use rmk_types::led_indicator::LedIndicator;
+
+use crate::REPORT_QUEUE_OVERFLOW;
+use crate::channel::{BLE_REPORT_CHANNEL, ReportReceiver};
+use crate::hid::{HidWriterTrait, Report, set_host_led_indicator};
+use crate::state::set_ble_state;
+
+/// What happened on the link to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WirelessEvent {
+    /// The radio waits for a host to connect, shown as advertising
+    Pairing,
+    /// A host is connected, reports are sent to it when it's the active output
+    Connected,
+    /// The host is gone, or the radio is off
+    Disconnected,
+    /// The host sent its lock LED state
+    LedIndicator(LedIndicator),
+}
+
+/// A report transport to a wireless host.
+///
+/// Reports are written with [`HidWriterTrait::write_report`] while the host is connected.
+pub trait WirelessTransport: HidWriterTrait<ReportType = Report> {
+    /// Wait for the next event of the link.
+    ///
+    /// The future is dropped when a report is ready to be sent, so it must be cancel safe:
+    /// an event must not be lost if the future is dropped before it completes.
+    fn next_event(&mut self) -> impl Future<Output = WirelessEvent>;
+}
+
+/// Run a custom wireless transport, forever.
+///
+/// The transport uses the BLE slot of the output routing, so it can't be used together
+/// with RMK's BLE stack.
+pub async fn run_wireless_transport<T: WirelessTransport>(mut transport: T) -> ! {
+    let mut reports = ReportReceiver::new(
+        &BLE_REPORT_CHANNEL,
+        REPORT_QUEUE_OVERFLOW == ReportQueueOverflow::Coalesce,
+    );
+    loop {
+        match select(reports.next(), transport.next_event()).await {
+            Either::First(report) => {
+                if let Err(e) = transport.write_report(&report).await {
+                    error!("Failed to send report: {:?}", e);
+                }
+            }
+            Either::Second(WirelessEvent::Pairing) => set_ble_state(BleState::Advertising),
+            Either::Second(WirelessEvent::Connected) => set_ble_state(BleState::Connected),
+            Either::Second(WirelessEvent::Disconnected) => set_ble_state(BleState::Inactive),
+            Either::Second(WirelessEvent::LedIndicator(led_indicator)) => {
+                set_host_led_indicator(ConnectionType::Ble, led_indicator)
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Serial console over the USB logger's CDC-ACM interface.
+//!
+//! Replaces `embassy-usb-logger` when the `usb_console` feature is enabled: log
+//! output goes to the same serial port, and lines typed in the serial monitor are
+//! run as commands, so the keyboard can be inspected without a debug probe.
+//!
+//! | Command      | Reply                                                  |
+//! |--------------|--------------------------------------------------------|
+//! | `help`       | List of commands                                       |
+//! | `layer`      | Last activated layer                                   |
+//! | `battery`    | Battery status, and of every peripheral of a BLE split |
+//! | `stats`      | Uptime, connection status, WPM and wireless link stats |
+//! | `latency`    | Key latency percentiles per stage, needs `latency`     |
+//! | `crash`      | Last crash record, needs `crash_dump`                  |
+//! | `state`      | Layers, pending keys and combos, needs `introspection` |
+//! | `log`        | Log level of every module, see below                   |
+//! | `reset`      | Reboot the keyboard                                    |
+//! | `bootloader` | Jump to the bootloader                                 |
+//!
+//! `log <level>` sets the log level of every module, `log <module> <level>` of one module, see
+//! [`crate::logging`]. The levels are `off`, `error`, `warn`, `info`, `debug` and `trace`.
+
+use core::cell::Cell;
+use core::fmt::Write;
+
+use embassy_futures::join::join3;
+use embassy_sync::pipe::Pipe;
+use embassy_time::Instant;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embassy_usb::driver::Driver;
+use heapless::Vec;
+use rmk_types::logging::{LogLevel, LogModule};
+
+use crate::RawMutex;
+use crate::event::{EventSubscriber, LayerChangeEvent, SubscribableEvent};
+use crate::logging::{log_level, module_of, set_all_log_levels, set_log_level};
+
+/// Max packet size of the CDC-ACM endpoints, see `add_usb_logger!`
+const MAX_PACKET_SIZE: usize = 64;
+/// Longest accepted command line, longer lines are discarded
+const MAX_LINE_LEN: usize = 32;
+
+/// Bytes waiting to be sent to the host, shared by the logger and the console
+static OUTPUT: Pipe<RawMutex, 1024> = Pipe::new();
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// Writes into [`OUTPUT`] without blocking, whatever doesn't fit is dropped.
+struct OutputWriter;
+
+impl Write for OutputWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let _ = OUTPUT.try_write(s.as_bytes());
+        Ok(())
+    }
+}
+
+struct ConsoleLogger;
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let ms = Instant::now().as_millis();
+        let module = module_of(record.module_path().unwrap_or_default());
+        let _ = write!(
+            OutputWriter,
+            "[{:>8}ms {:5} {}] {}\r\n",
+            ms,
+            record.level(),
+            module.name(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Help,
+    Layer,
+    Battery,
+    Stats,
+    Latency,
+    Crash,
+    State,
+    /// Set the level of a module, or of all modules, and show the levels
+    Log(Option<LogModule>, Option<LogLevel>),
+    Reset,
+    Bootloader,
+}
+
+impl Command {
+    fn parse(line: &[u8]) -> Option<Self> {
+        let mut words = core::str::from_utf8(line).ok()?.split_whitespace();
+        let command = match words.next()? {
+            "help" | "?" => Self::Help,
+            "layer" => Self::Layer,
+            "battery" => Self::Battery,
+            "stats" => Self::Stats,
+            "latency" => Self::Latency,
+            "crash" => Self::Crash,
+            "state" => Self::State,
+            "log" => match (words.next(), words.next()) {
+                (None, _) => Self::Log(None, None),
+                (Some(word), None) => match LogLevel::from_name(word) {
+                    Some(level) => Self::Log(None, Some(level)),
+                    None => Self::Log(Some(LogModule::from_name(word)?), None),
+                },
+                (Some(module), Some(level)) => {
+                    Self::Log(Some(LogModule::from_name(module)?), Some(LogLevel::from_name(level)?))
+                }
+            },
+            "reset" | "reboot" => Self::Reset,
+            "bootloader" => Self::Bootloader,
+            _ => return None,
+        };
+        // Nothing may follow the command and its arguments
+        words.next().is_none().then_some(command)
+    }
+}
+
+async fn run_command(line: &[u8], layer: u8) {
+    let mut out = OutputWriter;
+    let Some(command) = Command::parse(line) else {
+        let _ = write!(out, "unknown command, type `help` for a list\r\n");
+        return;
+    };
+    match command {
+        Command::Help => {
+            let _ = write!(
+                out,
+                "commands: help, layer, battery, stats, latency, crash, state, log, reset, bootloader\r\n"
+            );
+        }
+        Command::Layer => {
+            let _ = write!(out, "layer: {}\r\n", layer);
+        }
+        Command::Battery => {
+            #[cfg(feature = "_ble")]
+            let _ = write!(
+                out,
+                "battery: {:?}\r\n",
+                crate::input_device::battery::current_battery_status()
+            );
+            #[cfg(all(feature = "split", feature = "_ble"))]
+            for id in 0..crate::SPLIT_PERIPHERALS_NUM {
+                let _ = write!(
+                    out,
+                    "peripheral {} battery: {:?}\r\n",
+                    id,
+                    crate::split::ble::current_peripheral_battery_status(id)
+                );
+            }
+            #[cfg(not(feature = "_ble"))]
+            let _ = write!(out, "battery: unavailable\r\n");
+        }
+        Command::Stats => {
+            let _ = write!(
+                out,
+                "uptime: {}s\r\nconnection: {:?}\r\nwpm: {}\r\n",
+                Instant::now().as_secs(),
+                crate::state::current_connection_status(),
+                crate::processor::builtin::wpm::current_wpm()
+            );
+            #[cfg(any(feature = "_ble", feature = "esb"))]
+            let _ = write!(out, "link: {:?}\r\n", crate::link_stats::link_stats());
+        }
+        Command::Latency => {
+            #[cfg(feature = "latency")]
+            {
+                let stats = crate::latency::latency_stats();
+                for (stage, s) in [
+                    ("debounce", stats.debounce),
+                    ("queue", stats.queue),
+                    ("processing", stats.processing),
+                    ("transport", stats.transport),
+                    ("total", stats.total),
+                ] {
+                    let _ = write!(
+                        out,
+                        "{}: p50 {}us, p90 {}us, p99 {}us, max {}us ({} samples)\r\n",
+                        stage, s.p50_us, s.p90_us, s.p99_us, s.max_us, s.samples
+                    );
+                }
+            }
+            #[cfg(not(feature = "latency"))]
+            let _ = write!(out, "latency: disabled, enable the `latency` feature\r\n");
+        }
+        Command::Crash => {
+            #[cfg(feature = "crash_dump")]
+            match crate::watchdog::crash::last_crash() {
+                Some(r) => {
+                    let _ = write!(
+                        out,
+                        "crash: {:?} of {:?} after {}ms: {}\r\n",
+                        r.reason, r.task, r.uptime_ms, r.message
+                    );
+                }
+                None => {
+                    let _ = write!(out, "crash: none\r\n");
+                }
+            }
+            #[cfg(not(feature = "crash_dump"))]
+            let _ = write!(out, "crash: disabled, enable the `crash_dump` feature\r\n");
+        }
+        Command::State => {
+            #[cfg(feature = "introspection")]
+            {
+                let s = crate::introspection::runtime_state();
+                let _ = write!(
+                    out,
+                    "layers: {:#b}, default {}, top {}\r\n\
+                     tap-hold: {} pending, {} holding\r\n\
+                     one-shot: layer {:?}, modifiers {:#04x}\r\n\
+                     modifiers: {:#04x}, caps word: {}\r\n\
+                     combos: on {}, {} keys pending, {} in progress, {} triggered\r\n\
+                     connection: {:?}\r\n",
+                    s.layers,
+                    s.default_layer,
+                    s.top_layer,
+                    s.pending_tap_hold,
+                    s.holding,
+                    s.oneshot_layer,
+                    s.oneshot_modifiers,
+                    s.held_modifiers,
+                    s.caps_word,
+                    s.combos_enabled,
+                    s.pending_combo,
+                    s.combos_in_progress,
+                    s.combos_triggered,
+                    s.connection
+                );
+            }
+            #[cfg(not(feature = "introspection"))]
+            let _ = write!(out, "state: disabled, enable the `introspection` feature\r\n");
+        }
+        Command::Log(module, level) => {
+            if let Some(level) = level {
+                match module {
+                    Some(module) => set_log_level(module, level),
+                    None => set_all_log_levels(level),
+                }
+            }
+            for m in LogModule::all().filter(|m| module.is_none_or(|module| module == *m)) {
+                let _ = write!(out, "log {}: {}\r\n", m.name(), log_level(m).name());
+            }
+        }
+        Command::Reset => {
+            crate::boot::flush_storage().await;
+            crate::boot::reboot_keyboard();
+        }
+        Command::Bootloader => {
+            crate::boot::flush_storage().await;
+            crate::boot::jump_to_bootloader();
+        }
+    }
+}
+
+/// Install the console as the global logger and serve the serial port.
+pub(crate) async fn run_console<'d, D: Driver<'d>>(class: CdcAcmClass<'d, D>, level: log::LevelFilter) {
+    // SAFETY: called once from the USB task, before any other logger could be set
+    unsafe {
+        let _ = log::set_logger_racy(&LOGGER).map(|()| log::set_max_level_racy(level));
+    }
+
+    let (mut sender, mut receiver) = class.split();
+    let layer = Cell::new(0u8);
+
+    let output_task = async {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        loop {
+            sender.wait_connection().await;
+            loop {
+                // Stay below the max packet size so a transfer never needs a zero-length packet
+                let n = OUTPUT.read(&mut buf[..MAX_PACKET_SIZE - 1]).await;
+                if sender.write_packet(&buf[..n]).await.is_err() {
+                    break;
+                }
+            }
+        }
+    };
+
+    let input_task = async {
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let mut line: Vec<u8, MAX_LINE_LEN> = Vec::new();
+        loop {
+            receiver.wait_connection().await;
+            while let Ok(n) = receiver.read_packet(&mut buf).await {
+                for &byte in &buf[..n] {
+                    match byte {
+                        b'\r' | b'\n' => {
+                            let _ = OutputWriter.write_str("\r\n");
+                            if !line.is_empty() {
+                                run_command(&line, layer.get()).await;
+                                line.clear();
+                            }
+                        }
+                        _ => {
+                            // Serial monitors usually don't echo locally
+                            let _ = OUTPUT.try_write(&[byte]);
+                            if line.push(byte).is_err() {
+                                line.clear();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let layer_task = async {
+        let mut layer_sub = LayerChangeEvent::subscriber();
+        loop {
+            layer.set(layer_sub.next_event().await.0);
+        }
+    };
+
+    join3(output_task, input_task, layer_task).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Command::parse(b"layer"), Some(Command::Layer));
+        assert_eq!(Command::parse(b"  stats "), Some(Command::Stats));
+        assert_eq!(Command::parse(b"latency"), Some(Command::Latency));
+        assert_eq!(Command::parse(b"crash"), Some(Command::Crash));
+        assert_eq!(Command::parse(b"state"), Some(Command::State));
+        assert_eq!(Command::parse(b"reboot"), Some(Command::Reset));
+        assert_eq!(Command::parse(b"?"), Some(Command::Help));
+        assert_eq!(Command::parse(b"Layer"), None);
+        assert_eq!(Command::parse(b"stats now"), None);
+        assert_eq!(Command::parse(b"log"), Some(Command::Log(None, None)));
+        assert_eq!(
+            Command::parse(b"log warn"),
+            Some(Command::Log(None, Some(LogLevel::Warn)))
+        );
+        assert_eq!(
+            Command::parse(b"log split"),
+            Some(Command::Log(Some(LogModule::Split), None))
+        );
+        assert_eq!(
+            Command::parse(b"log  split debug"),
+            Some(Command::Log(Some(LogModule::Split), Some(LogLevel::Debug)))
+        );
+        assert_eq!(Command::parse(b"log split loud"), None);
+        assert_eq!(Command::parse(&[0xFF, 0xFE]), None);
+    }
+}
@@ -0,0 +1,128 @@
+//! USB DFU runtime interface.
+//!
+//! Advertises a DFU 1.1 interface in run-time mode, so host tools like `dfu-util -e`
+//! can ask the keyboard to detach. On `DFU_DETACH` the keyboard jumps to its bootloader
+//! with [`crate::boot::jump_to_bootloader`], the same way as the Via bootloader jump.
+//! The firmware is then flashed by the bootloader, RMK only implements the detach.
+
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use embassy_usb::driver::Driver;
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::{Builder, Handler};
+use static_cell::StaticCell;
+
+use crate::RawMutex;
+
+const USB_CLASS_APPLICATION_SPECIFIC: u8 = 0xFE;
+const DFU_SUBCLASS: u8 = 0x01;
+const DFU_PROTOCOL_RUNTIME: u8 = 0x01;
+const DESC_DFU_FUNCTIONAL: u8 = 0x21;
+
+const DFU_DETACH: u8 = 0x00;
+const DFU_GETSTATUS: u8 = 0x03;
+const DFU_GETSTATE: u8 = 0x05;
+
+const STATE_APP_IDLE: u8 = 0;
+const STATE_APP_DETACH: u8 = 1;
+
+/// bitWillDetach | bitCanDnload: the keyboard detaches by itself, the host doesn't need to reset the bus
+const DFU_ATTRIBUTES: u8 = 0x09;
+/// Longest time the host waits for the keyboard to detach, in ms
+const DETACH_TIMEOUT_MS: u16 = 1000;
+/// Transfer size announced to the host, the bootloader announces its own after detaching
+const TRANSFER_SIZE: u16 = 64;
+
+static DETACH_REQUESTED: Signal<RawMutex, ()> = Signal::new();
+
+struct DfuRuntimeHandler {
+    interface: InterfaceNumber,
+    detaching: bool,
+}
+
+impl DfuRuntimeHandler {
+    fn is_dfu_request(&self, req: &Request) -> bool {
+        req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u8::from(self.interface) as u16
+    }
+
+    fn state(&self) -> u8 {
+        if self.detaching {
+            STATE_APP_DETACH
+        } else {
+            STATE_APP_IDLE
+        }
+    }
+}
+
+impl Handler for DfuRuntimeHandler {
+    fn reset(&mut self) {
+        self.detaching = false;
+    }
+
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if !self.is_dfu_request(&req) {
+            return None;
+        }
+        if req.request != DFU_DETACH {
+            return Some(OutResponse::Rejected);
+        }
+        info!("DFU detach requested");
+        self.detaching = true;
+        DETACH_REQUESTED.signal(());
+        Some(OutResponse::Accepted)
+    }
+
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if !self.is_dfu_request(&req) {
+            return None;
+        }
+        match req.request {
+            DFU_GETSTATUS if buf.len() >= 6 => {
+                // bStatus OK, bwPollTimeout 0, bState, no status string
+                buf[..6].copy_from_slice(&[0, 0, 0, 0, self.state(), 0]);
+                Some(InResponse::Accepted(&buf[..6]))
+            }
+            DFU_GETSTATE if !buf.is_empty() => {
+                buf[0] = self.state();
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Add the DFU runtime interface to the USB device.
+pub(crate) fn add_dfu_runtime<'d, D: Driver<'d>>(builder: &mut Builder<'d, D>) {
+    let mut func = builder.function(USB_CLASS_APPLICATION_SPECIFIC, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME);
+    let mut iface = func.interface();
+    let interface = iface.interface_number();
+    let mut alt = iface.alt_setting(USB_CLASS_APPLICATION_SPECIFIC, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME, None);
+    let [timeout_lo, timeout_hi] = DETACH_TIMEOUT_MS.to_le_bytes();
+    let [size_lo, size_hi] = TRANSFER_SIZE.to_le_bytes();
+    // bcdDFUVersion 1.1
+    alt.descriptor(
+        DESC_DFU_FUNCTIONAL,
+        &[DFU_ATTRIBUTES, timeout_lo, timeout_hi, size_lo, size_hi, 0x10, 0x01],
+    );
+    drop(func);
+
+    static HANDLER: StaticCell<DfuRuntimeHandler> = StaticCell::new();
+    builder.handler(HANDLER.init(DfuRuntimeHandler {
+        interface,
+        detaching: false,
+    }));
+}
+
+/// Jump to the bootloader once the host asked to detach.
+pub(crate) async fn run_dfu_detach() -> ! {
+    DETACH_REQUESTED.wait().await;
+    // Let the status stage of DFU_DETACH complete, so the host doesn't report an error
+    Timer::after_millis(10).await;
+    crate::boot::flush_storage().await;
+    crate::boot::jump_to_bootloader();
+    // Only reached when no bootloader is configured, see `jump_to_bootloader`
+    core::future::pending().await
+}
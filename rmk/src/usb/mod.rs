@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use embassy_futures::join::join4;
 use embassy_futures::select::{Either, select};
 use embassy_sync::signal::Signal;
@@ -12,20 +14,66 @@ use static_cell::StaticCell;
 use usbd_hid::descriptor::AsInputReport;
 
 use crate::RawMutex;
-use crate::channel::USB_REPORT_CHANNEL;
-use crate::config::DeviceConfig;
+use crate::channel::{ReportReceiver, USB_REPORT_CHANNEL};
+use crate::config::{DeviceConfig, UsbConfig};
 use crate::core_traits::Runnable;
+use crate::event::{PowerStateEvent, SleepStateEvent, publish_event};
+#[cfg(feature = "_ble")]
+use crate::hid::BatteryReport;
+#[cfg(feature = "raw_hid")]
+use crate::hid::RawHidReport;
 #[cfg(feature = "steno")]
 use crate::hid::StenoReport;
 #[cfg(feature = "host")]
 use crate::hid::ViaReport;
 use crate::hid::{
-    CompositeReport, CompositeReportType, HidError, HidWriterTrait, KeyboardReport, Report, run_led_reader,
+    COMPOSITE_BATTERY_DESCRIPTOR, CompositeMediaDescriptor, CompositeMouseDescriptor, CompositeReport,
+    CompositeReportType, CompositeSystemDescriptor, DigitizerReport, HidError, HidWriterTrait, KeyboardReport,
+    NKRO_ENABLED, NkroKeyboardReport, Report, run_led_reader,
 };
 use crate::light::UsbLedReader;
-use crate::state::{current_usb_state, set_usb_state};
+use crate::state::{active_transport, current_usb_state, set_usb_state};
+use crate::usb::os_detect::OsDetectDriver;
+
+#[cfg(feature = "usb_console")]
+mod console;
+#[cfg(feature = "usb_dfu")]
+mod dfu;
+mod os_detect;
+#[cfg(feature = "raw_hid")]
+pub mod raw_hid;
 
 pub(crate) static USB_REMOTE_WAKEUP: Signal<RawMutex, ()> = Signal::new();
+/// Whether the host enabled remote wakeup, set by `SET_FEATURE(DEVICE_REMOTE_WAKEUP)` and
+/// cleared by the host or on bus reset.
+static USB_REMOTE_WAKEUP_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Latest battery level to report to the host, see `UsbConfig::battery`
+#[cfg(feature = "_ble")]
+static USB_BATTERY_LEVEL: Signal<RawMutex, u8> = Signal::new();
+
+#[cfg(feature = "_ble")]
+fn report_battery_level(status: rmk_types::battery::BatteryStatus) {
+    if let rmk_types::battery::BatteryStatus::Available { level: Some(level), .. } = status {
+        USB_BATTERY_LEVEL.signal(level);
+    }
+}
+
+/// Wake the host up if the USB bus is suspended and the host allows remote wakeup.
+///
+/// Returns false if the bus is suspended and the host can't be woken up, so anything
+/// sent to it would only be delivered on the next resume.
+pub(crate) fn request_remote_wakeup() -> bool {
+    if current_usb_state() != UsbState::Suspended {
+        return true;
+    }
+    if USB_REMOTE_WAKEUP_ENABLED.load(Ordering::Acquire) {
+        USB_REMOTE_WAKEUP.signal(());
+        true
+    } else {
+        false
+    }
+}
 
 /// Borrowed view over the USB HID IN endpoints used by the report writer task.
 ///
@@ -34,7 +82,12 @@ pub(crate) static USB_REMOTE_WAKEUP: Signal<RawMutex, ()> = Signal::new();
 /// concurrently without moving the whole transport into one task.
 pub(crate) struct UsbKeyboardWriter<'a, 'd, D: Driver<'d>> {
     pub(crate) keyboard_writer: &'a mut HidWriter<'d, D, 8>,
-    pub(crate) other_writer: &'a mut HidWriter<'d, D, 9>,
+    /// Mouse, consumer and system control interface, `None` if all of them are disabled
+    pub(crate) other_writer: Option<&'a mut HidWriter<'d, D, 9>>,
+    /// Keys beyond the 6 of the keyboard report, see `UsbConfig::nkro`
+    pub(crate) nkro_writer: Option<&'a mut HidWriter<'d, D, 28>>,
+    pub(crate) digitizer_writer: Option<&'a mut HidWriter<'d, D, 5>>,
+    pub(crate) usb_config: UsbConfig,
     #[cfg(feature = "steno")]
     pub(crate) steno_writer: &'a mut HidWriter<'d, D, 9>,
 }
@@ -42,26 +95,39 @@ pub(crate) struct UsbKeyboardWriter<'a, 'd, D: Driver<'d>> {
 impl<'a, 'd, D: Driver<'d>> UsbKeyboardWriter<'a, 'd, D> {
     pub(crate) fn new(
         keyboard_writer: &'a mut HidWriter<'d, D, 8>,
-        other_writer: &'a mut HidWriter<'d, D, 9>,
+        other_writer: Option<&'a mut HidWriter<'d, D, 9>>,
+        nkro_writer: Option<&'a mut HidWriter<'d, D, 28>>,
+        digitizer_writer: Option<&'a mut HidWriter<'d, D, 5>>,
+        usb_config: UsbConfig,
         #[cfg(feature = "steno")] steno_writer: &'a mut HidWriter<'d, D, 9>,
     ) -> Self {
         Self {
             keyboard_writer,
             other_writer,
+            nkro_writer,
+            digitizer_writer,
+            usb_config,
             #[cfg(feature = "steno")]
             steno_writer,
         }
     }
 
     pub(crate) async fn run_writer(&mut self) -> ! {
+        // Mouse motion queued while the host was polling the previous report is sent as
+        // one report, so pointing devices reporting faster than the polling interval don't
+        // build up a backlog.
+        let mut reports = ReportReceiver::new(&USB_REPORT_CHANNEL, true);
         loop {
-            let report = USB_REPORT_CHANNEL.receive().await;
+            let report = self.receive_report(&mut reports).await;
 
             // EndpointError::Disabled never fires on non-OTG STM32/GD32
             // peripherals during suspend, so signal wakeup proactively when a
-            // USB report is pending and the bus is suspended.
-            if current_usb_state() == UsbState::Suspended {
-                USB_REMOTE_WAKEUP.signal(());
+            // USB report is pending and the bus is suspended. If the host
+            // doesn't allow remote wakeup, drop the report instead of
+            // replaying stale keystrokes on the next resume.
+            if !request_remote_wakeup() {
+                debug!("USB suspended without remote wakeup, dropping report");
+                continue;
             }
 
             if let Err(e) = self.write_report(&report).await {
@@ -70,7 +136,9 @@ impl<'a, 'd, D: Driver<'d>> UsbKeyboardWriter<'a, 'd, D> {
                 // Belt-and-braces for OTG peripherals where Disabled is the
                 // correct suspend indicator: signal wakeup, give the host a
                 // moment, then retry the same report once.
-                if let HidError::UsbEndpointError(EndpointError::Disabled) = e {
+                if let HidError::UsbEndpointError(EndpointError::Disabled) = e
+                    && USB_REMOTE_WAKEUP_ENABLED.load(Ordering::Acquire)
+                {
                     USB_REMOTE_WAKEUP.signal(());
                     embassy_time::Timer::after_millis(500).await;
                     if let Err(e) = self.write_report(&report).await {
@@ -81,17 +149,50 @@ impl<'a, 'd, D: Driver<'d>> UsbKeyboardWriter<'a, 'd, D> {
         }
     }
 
+    /// Wait for the next report, sending battery level updates in the meantime.
+    async fn receive_report(&mut self, reports: &mut ReportReceiver<'_>) -> Report {
+        #[cfg(feature = "_ble")]
+        loop {
+            match select(reports.next(), USB_BATTERY_LEVEL.wait()).await {
+                Either::First(report) => return report,
+                // Don't wake a suspended host up for the battery, it gets the level again on resume
+                Either::Second(level) if current_usb_state() == UsbState::Configured => {
+                    if let Err(e) = self
+                        .write_composite(CompositeReportType::Battery, &BatteryReport { level })
+                        .await
+                    {
+                        error!("Failed to send battery level: {:?}", e);
+                    }
+                }
+                Either::Second(_) => {}
+            }
+        }
+        #[cfg(not(feature = "_ble"))]
+        reports.next().await
+    }
+
     async fn write_composite<R: AsInputReport>(
         &mut self,
         kind: CompositeReportType,
         report: &R,
     ) -> Result<usize, HidError> {
+        let enabled = match kind {
+            CompositeReportType::Mouse => self.usb_config.mouse,
+            CompositeReportType::Media => self.usb_config.consumer,
+            CompositeReportType::System => self.usb_config.system_control,
+            CompositeReportType::Battery => self.usb_config.battery,
+            CompositeReportType::None => false,
+        };
+        // The host doesn't know this report
+        let Some(other_writer) = self.other_writer.as_mut().filter(|_| enabled) else {
+            return Err(HidError::ReportDisabled);
+        };
         let mut buf = [0u8; 9];
         buf[0] = kind as u8;
         let n = report
             .serialize(&mut buf[1..])
             .map_err(|_| HidError::ReportSerializeError)?;
-        self.other_writer
+        other_writer
             .write(&buf[0..n + 1])
             .await
             .map_err(HidError::UsbEndpointError)?;
@@ -113,6 +214,32 @@ impl<'d, D: Driver<'d>> HidWriterTrait for UsbKeyboardWriter<'_, 'd, D> {
                     .write(&buf[0..n])
                     .await
                     .map_err(HidError::UsbEndpointError)?;
+                #[cfg(feature = "latency")]
+                crate::latency::record_submitted();
+                Ok(n)
+            }
+            Report::NkroKeyboardReport(r) => {
+                let Some(nkro_writer) = self.nkro_writer.as_mut() else {
+                    return Err(HidError::ReportDisabled);
+                };
+                let mut buf = [0u8; 28];
+                let n = r.serialize(&mut buf).map_err(|_| HidError::ReportSerializeError)?;
+                nkro_writer
+                    .write(&buf[0..n])
+                    .await
+                    .map_err(HidError::UsbEndpointError)?;
+                Ok(n)
+            }
+            Report::DigitizerReport(r) => {
+                let Some(digitizer_writer) = self.digitizer_writer.as_mut() else {
+                    return Err(HidError::ReportDisabled);
+                };
+                let mut buf = [0u8; 5];
+                let n = r.serialize(&mut buf).map_err(|_| HidError::ReportSerializeError)?;
+                digitizer_writer
+                    .write(&buf[0..n])
+                    .await
+                    .map_err(HidError::UsbEndpointError)?;
                 Ok(n)
             }
             Report::MouseReport(r) => self.write_composite(CompositeReportType::Mouse, r).await,
@@ -145,11 +272,36 @@ impl<'d, D: Driver<'d>> HidWriterTrait for UsbKeyboardWriter<'_, 'd, D> {
     }
 }
 
+/// Assemble the report descriptor of the mouse, consumer and system control interface
+/// from the reports enabled in `usb_config`, `None` if all of them are disabled.
+fn composite_descriptor(usb_config: &UsbConfig) -> Option<&'static [u8]> {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    let parts = [
+        (usb_config.mouse, CompositeMouseDescriptor::desc()),
+        (usb_config.consumer, CompositeMediaDescriptor::desc()),
+        (usb_config.system_control, CompositeSystemDescriptor::desc()),
+        // Without BLE there's no battery level to report
+        (
+            usb_config.battery && cfg!(feature = "_ble"),
+            &COMPOSITE_BATTERY_DESCRIPTOR[..],
+        ),
+    ];
+    static COMPOSITE_DESC: StaticCell<[u8; 192]> = StaticCell::new();
+    let buf = COMPOSITE_DESC.init([0; 192]);
+    let mut len = 0;
+    for (_, desc) in parts.iter().filter(|(enabled, _)| *enabled) {
+        buf[len..len + desc.len()].copy_from_slice(desc);
+        len += desc.len();
+    }
+    (len > 0).then_some(&buf[..len])
+}
+
 pub(crate) fn new_usb_builder<'d, D: Driver<'d>>(driver: D, keyboard_config: DeviceConfig<'d>) -> Builder<'d, D> {
     let mut usb_config = embassy_usb::Config::new(keyboard_config.vid, keyboard_config.pid);
     usb_config.manufacturer = Some(keyboard_config.manufacturer);
     usb_config.product = Some(keyboard_config.product_name);
-    usb_config.serial_number = Some(keyboard_config.serial_number);
+    usb_config.serial_number = Some(keyboard_config.serial());
     usb_config.max_power = 450;
     usb_config.supports_remote_wakeup = true;
 
@@ -160,13 +312,16 @@ pub(crate) fn new_usb_builder<'d, D: Driver<'d>>(driver: D, keyboard_config: Dev
     usb_config.device_protocol = 0x01;
     usb_config.composite_with_iads = true;
 
-    // Extra HID interfaces (usb_log, steno) overflow the 128-byte config descriptor buffer.
-    #[cfg(any(feature = "usb_log", feature = "steno"))]
+    // Extra interfaces (usb_log, steno, raw_hid, usb_dfu) overflow the 128-byte config descriptor buffer.
+    #[cfg(any(feature = "usb_log", feature = "steno", feature = "raw_hid", feature = "usb_dfu"))]
     const USB_BUF_SIZE: usize = 256;
-    #[cfg(not(any(feature = "usb_log", feature = "steno")))]
+    #[cfg(not(any(feature = "usb_log", feature = "steno", feature = "raw_hid", feature = "usb_dfu")))]
     const USB_BUF_SIZE: usize = 128;
 
-    static CONFIG_DESC: StaticCell<[u8; USB_BUF_SIZE]> = StaticCell::new();
+    // Room for the NKRO and digitizer interfaces of `UsbConfig`, 33 bytes each with their IAD
+    const CONFIG_DESC_SIZE: usize = USB_BUF_SIZE + 66;
+
+    static CONFIG_DESC: StaticCell<[u8; CONFIG_DESC_SIZE]> = StaticCell::new();
     static BOS_DESC: StaticCell<[u8; 16]> = StaticCell::new();
     static MSOS_DESC: StaticCell<[u8; 16]> = StaticCell::new();
     static CONTROL_BUF: StaticCell<[u8; USB_BUF_SIZE]> = StaticCell::new();
@@ -174,7 +329,7 @@ pub(crate) fn new_usb_builder<'d, D: Driver<'d>>(driver: D, keyboard_config: Dev
     let mut builder = Builder::new(
         driver,
         usb_config,
-        &mut CONFIG_DESC.init([0; USB_BUF_SIZE])[..],
+        &mut CONFIG_DESC.init([0; CONFIG_DESC_SIZE])[..],
         &mut BOS_DESC.init([0; 16])[..],
         &mut MSOS_DESC.init([0; 16])[..],
         &mut CONTROL_BUF.init([0; USB_BUF_SIZE])[..],
@@ -190,29 +345,34 @@ pub(crate) fn new_usb_builder<'d, D: Driver<'d>>(driver: D, keyboard_config: Dev
 /// reader/writer pair and runs them concurrently for the lifetime of the
 /// program.
 pub struct UsbTransport<D: Driver<'static>> {
-    device: UsbDevice<'static, D>,
-    keyboard_reader: HidReader<'static, D, 1>,
-    keyboard_writer: HidWriter<'static, D, 8>,
-    other_writer: HidWriter<'static, D, 9>,
+    device: UsbDevice<'static, OsDetectDriver<D>>,
+    keyboard_reader: HidReader<'static, OsDetectDriver<D>, 1>,
+    keyboard_writer: HidWriter<'static, OsDetectDriver<D>, 8>,
+    other_writer: Option<HidWriter<'static, OsDetectDriver<D>, 9>>,
+    nkro_writer: Option<HidWriter<'static, OsDetectDriver<D>, 28>>,
+    digitizer_writer: Option<HidWriter<'static, OsDetectDriver<D>, 5>>,
+    usb_config: UsbConfig,
     #[cfg(feature = "steno")]
-    steno_writer: HidWriter<'static, D, 9>,
+    steno_writer: HidWriter<'static, OsDetectDriver<D>, 9>,
     #[cfg(feature = "host")]
-    host_rw: HidReaderWriter<'static, D, 32, 32>,
+    host_rw: HidReaderWriter<'static, OsDetectDriver<D>, 32, 32>,
+    #[cfg(feature = "raw_hid")]
+    raw_hid_rw: HidReaderWriter<'static, OsDetectDriver<D>, 32, 32>,
     #[cfg(feature = "usb_log")]
-    logger: Option<embassy_usb::class::cdc_acm::CdcAcmClass<'static, D>>,
+    logger: Option<embassy_usb::class::cdc_acm::CdcAcmClass<'static, OsDetectDriver<D>>>,
 }
 
 impl<D: Driver<'static>> UsbTransport<D> {
+    /// Create the USB transport with all HID interfaces of [`UsbConfig::default`].
     pub fn new(driver: D, device_config: DeviceConfig<'static>) -> Self {
-        // nRF chips don't have a stable USB serial number unless one is derived
-        // from the FICR. Override here so user code doesn't have to know.
-        #[cfg(feature = "_nrf_ble")]
-        let device_config = {
-            let mut device_config = device_config;
-            device_config.serial_number = crate::ble::nrf::get_serial_number();
-            device_config
-        };
-        let mut builder: Builder<'static, D> = new_usb_builder(driver, device_config);
+        Self::with_config(driver, device_config, UsbConfig::default())
+    }
+
+    /// Create the USB transport with the HID interfaces enabled in `usb_config`.
+    pub fn with_config(driver: D, device_config: DeviceConfig<'static>, usb_config: UsbConfig) -> Self {
+        let mut builder: Builder<'static, OsDetectDriver<D>> = new_usb_builder(OsDetectDriver(driver), device_config);
+        // Full speed endpoints can't be polled faster than every 1ms
+        let poll_ms = usb_config.poll_interval_ms.max(1);
         // Linux's usbhid driver auto-enables power/wakeup when it probes a
         // boot-protocol keyboard, so advertise Boot/Keyboard on the primary
         // HID interface.
@@ -223,15 +383,36 @@ impl<D: Driver<'static>> UsbTransport<D> {
             8,
             8,
             ::embassy_usb::class::hid::HidSubclass::Boot,
-            ::embassy_usb::class::hid::HidBootProtocol::Keyboard
+            ::embassy_usb::class::hid::HidBootProtocol::Keyboard,
+            poll_ms = poll_ms
         );
-        let other_writer = add_usb_writer!(&mut builder, CompositeReport, 9, 16);
+        let other_writer = composite_descriptor(&usb_config).map(|desc| {
+            add_usb_writer!(
+                &mut builder,
+                CompositeReport,
+                9,
+                16,
+                report_descriptor = desc,
+                poll_ms = poll_ms
+            )
+        });
+        let nkro_writer = usb_config
+            .nkro
+            .then(|| add_usb_writer!(&mut builder, NkroKeyboardReport, 28, 32, poll_ms = poll_ms));
+        NKRO_ENABLED.store(usb_config.nkro, Ordering::Relaxed);
+        let digitizer_writer = usb_config
+            .digitizer
+            .then(|| add_usb_writer!(&mut builder, DigitizerReport, 5, 8, poll_ms = poll_ms));
         #[cfg(feature = "steno")]
         let steno_writer = add_usb_writer!(&mut builder, StenoReport, 9, 16);
         #[cfg(feature = "host")]
         let host_rw = add_usb_reader_writer!(&mut builder, ViaReport, 32, 32, 32);
+        #[cfg(feature = "raw_hid")]
+        let raw_hid_rw = add_usb_reader_writer!(&mut builder, RawHidReport, 32, 32, 32);
         #[cfg(feature = "usb_log")]
         let logger = Some(add_usb_logger!(&mut builder));
+        #[cfg(feature = "usb_dfu")]
+        dfu::add_dfu_runtime(&mut builder);
 
         let (keyboard_reader, keyboard_writer) = keyboard_rw.split();
         let device = builder.build();
@@ -241,10 +422,15 @@ impl<D: Driver<'static>> UsbTransport<D> {
             keyboard_reader,
             keyboard_writer,
             other_writer,
+            nkro_writer,
+            digitizer_writer,
+            usb_config,
             #[cfg(feature = "steno")]
             steno_writer,
             #[cfg(feature = "host")]
             host_rw,
+            #[cfg(feature = "raw_hid")]
+            raw_hid_rw,
             #[cfg(feature = "usb_log")]
             logger,
         }
@@ -258,10 +444,15 @@ impl<D: Driver<'static>> Runnable for UsbTransport<D> {
             keyboard_reader,
             keyboard_writer,
             other_writer,
+            nkro_writer,
+            digitizer_writer,
+            usb_config,
             #[cfg(feature = "steno")]
             steno_writer,
             #[cfg(feature = "host")]
             host_rw,
+            #[cfg(feature = "raw_hid")]
+            raw_hid_rw,
             #[cfg(feature = "usb_log")]
             logger,
         } = self;
@@ -284,7 +475,10 @@ impl<D: Driver<'static>> Runnable for UsbTransport<D> {
 
         let mut writer = UsbKeyboardWriter::new(
             keyboard_writer,
-            other_writer,
+            other_writer.as_mut(),
+            nkro_writer.as_mut(),
+            digitizer_writer.as_mut(),
+            *usb_config,
             #[cfg(feature = "steno")]
             steno_writer,
         );
@@ -299,9 +493,30 @@ impl<D: Driver<'static>> Runnable for UsbTransport<D> {
             #[cfg(not(feature = "host"))]
             let host_task = core::future::pending::<()>();
 
+            #[cfg(feature = "raw_hid")]
+            let host_task = embassy_futures::join::join(host_task, raw_hid::run_raw_hid(raw_hid_rw));
+            let host_task = embassy_futures::join::join(host_task, os_detect::run_os_detection());
+            #[cfg(feature = "_ble")]
+            let host_task = embassy_futures::join::join(host_task, async {
+                use crate::event::{BatteryStatusEvent, EventSubscriber, SubscribableEvent};
+
+                if usb_config.battery {
+                    let mut sub = BatteryStatusEvent::subscriber();
+                    loop {
+                        report_battery_level(sub.next_event().await.0);
+                    }
+                }
+                core::future::pending::<()>().await
+            });
+            #[cfg(feature = "usb_dfu")]
+            let host_task = embassy_futures::join::join(host_task, dfu::run_dfu_detach());
+
             #[cfg(feature = "usb_log")]
             {
                 let logger_class = logger.take().expect("UsbTransport::run called twice");
+                #[cfg(feature = "usb_console")]
+                let logger_fut = console::run_console(logger_class, log::LevelFilter::Debug);
+                #[cfg(not(feature = "usb_console"))]
                 let logger_fut = embassy_usb_logger::with_custom_style!(
                     1024,
                     log::LevelFilter::Debug,
@@ -341,9 +556,16 @@ macro_rules! add_usb_writer {
         $crate::usb::add_usb_writer!($usb_builder, $descriptor, $n, 64)
     };
     // Size $max_packet to the actual report to conserve Packet Memory Area on tight parts.
-    ($usb_builder:expr, $descriptor:ty, $n:expr, $max_packet:expr) => {{
-        // `paste` generates per-descriptor `static`s so each writer keeps its own State/Handler.
+    ($usb_builder:expr, $descriptor:ty, $n:expr, $max_packet:expr) => {
+        $crate::usb::add_usb_writer!($usb_builder, $descriptor, $n, $max_packet, poll_ms = 1)
+    };
+    ($usb_builder:expr, $descriptor:ty, $n:expr, $max_packet:expr, poll_ms = $poll_ms:expr) => {{
         use usbd_hid::descriptor::SerializedDescriptor;
+        $crate::usb::add_usb_writer!($usb_builder, $descriptor, $n, $max_packet, report_descriptor = <$descriptor>::desc(), poll_ms = $poll_ms)
+    }};
+    // Use a report descriptor assembled at runtime instead of the one of $descriptor.
+    ($usb_builder:expr, $descriptor:ty, $n:expr, $max_packet:expr, report_descriptor = $report_descriptor:expr, poll_ms = $poll_ms:expr) => {{
+        // `paste` generates per-descriptor `static`s so each writer keeps its own State/Handler.
         paste::paste! {
             static [<$descriptor:snake:upper _STATE>]: ::static_cell::StaticCell<::embassy_usb::class::hid::State> = ::static_cell::StaticCell::new();
             static [<$descriptor:snake:upper _HANDLER>]: ::static_cell::StaticCell<$crate::usb::UsbRequestHandler> = ::static_cell::StaticCell::new();
@@ -353,9 +575,9 @@ macro_rules! add_usb_writer {
         let request_handler = paste::paste! { [<$descriptor:snake:upper _HANDLER>].init($crate::usb::UsbRequestHandler {}) };
 
         let hid_config = ::embassy_usb::class::hid::Config {
-            report_descriptor: <$descriptor>::desc(),
+            report_descriptor: $report_descriptor,
             request_handler: Some(request_handler),
-            poll_ms: 1,
+            poll_ms: $poll_ms,
             max_packet_size: $max_packet,
             hid_subclass: ::embassy_usb::class::hid::HidSubclass::No,
             hid_boot_protocol: ::embassy_usb::class::hid::HidBootProtocol::None,
@@ -378,7 +600,12 @@ macro_rules! add_usb_reader_writer {
             ::embassy_usb::class::hid::HidBootProtocol::None
         )
     };
-    ($usb_builder:expr, $descriptor:ty, $read_n:expr, $write_n:expr, $max_packet:expr, $subclass:expr, $protocol:expr) => {{
+    ($usb_builder:expr, $descriptor:ty, $read_n:expr, $write_n:expr, $max_packet:expr, $subclass:expr, $protocol:expr) => {
+        $crate::usb::add_usb_reader_writer!(
+            $usb_builder, $descriptor, $read_n, $write_n, $max_packet, $subclass, $protocol, poll_ms = 1
+        )
+    };
+    ($usb_builder:expr, $descriptor:ty, $read_n:expr, $write_n:expr, $max_packet:expr, $subclass:expr, $protocol:expr, poll_ms = $poll_ms:expr) => {{
         // `paste` generates per-descriptor `static`s so each reader/writer keeps its own State/Handler.
         use usbd_hid::descriptor::SerializedDescriptor;
         paste::paste! {
@@ -392,7 +619,7 @@ macro_rules! add_usb_reader_writer {
         let hid_config = ::embassy_usb::class::hid::Config {
             report_descriptor: <$descriptor>::desc(),
             request_handler: Some(request_handler),
-            poll_ms: 1,
+            poll_ms: $poll_ms,
             max_packet_size: $max_packet,
             hid_subclass: $subclass,
             hid_boot_protocol: $protocol,
@@ -418,15 +645,27 @@ impl RequestHandler for UsbRequestHandler {
 }
 
 pub(crate) struct UsbDeviceHandler {
-    /// State to restore on resume. Captured at suspend so an Enabled-but-not-yet-Configured
-    /// device that suspends/resumes doesn't get incorrectly upgraded to Configured.
+    /// State to restore on resume. Captured at suspend so a configured device goes back to
+    /// Configured, an Enabled-but-not-yet-Configured device suspends into `PowerOnly` instead.
     pre_suspend: UsbState,
+    /// Whether the keyboard was put to sleep because the USB host it's typing to suspended
+    sleeping: bool,
 }
 
 impl UsbDeviceHandler {
     fn new() -> Self {
         UsbDeviceHandler {
             pre_suspend: UsbState::Disabled,
+            sleeping: false,
+        }
+    }
+
+    /// Wake the keyboard up if the USB suspend put it to sleep
+    fn wake(&mut self) {
+        if self.sleeping {
+            self.sleeping = false;
+            publish_event(SleepStateEvent::new(false));
+            publish_event(PowerStateEvent::Sleep(false));
         }
     }
 }
@@ -439,11 +678,19 @@ impl Handler for UsbDeviceHandler {
         } else {
             info!("Device disabled");
             set_usb_state(UsbState::Disabled);
+            // Unplugged while suspended, the keyboard may carry on over BLE
+            self.wake();
         }
     }
 
     fn reset(&mut self) {
         info!("Bus reset, the Vbus current limit is 100mA");
+        USB_REMOTE_WAKEUP_ENABLED.store(false, Ordering::Release);
+        os_detect::reset_fingerprint();
+        // A host showed up on a port that was only supplying power
+        if current_usb_state() == UsbState::PowerOnly {
+            set_usb_state(UsbState::Enabled);
+        }
     }
 
     fn addressed(&mut self, addr: u8) {
@@ -453,6 +700,9 @@ impl Handler for UsbDeviceHandler {
     fn configured(&mut self, configured: bool) {
         if configured {
             set_usb_state(UsbState::Configured);
+            os_detect::USB_CONFIGURED.signal(());
+            #[cfg(feature = "_ble")]
+            report_battery_level(crate::input_device::battery::current_battery_status());
             info!("Device configured, it may now draw up to the configured current from Vbus.")
         } else {
             set_usb_state(UsbState::Enabled);
@@ -465,14 +715,29 @@ impl Handler for UsbDeviceHandler {
         // both arms collapse to identical empty blocks — suppress the lint.
         #[allow(clippy::if_same_then_else)]
         if suspended {
+            let live = current_usb_state();
+            // Chargers never send SOFs, so the bus suspends right after Vbus comes up
+            // without being configured. Don't treat that as a sleeping host: keep
+            // USB unroutable so the output stays on BLE.
+            if matches!(live, UsbState::Enabled | UsbState::PowerOnly) {
+                info!("USB bus idle before enumeration, Vbus is only supplying power");
+                set_usb_state(UsbState::PowerOnly);
+                return;
+            }
             // Snapshot the live state so resume can restore it. Skip the snapshot
             // if a stray duplicate `suspended(true)` ever fires while we're already
             // Suspended — otherwise we'd lose the original pre-suspend state.
-            let live = current_usb_state();
             if live != UsbState::Suspended {
                 self.pre_suspend = live;
             }
             set_usb_state(UsbState::Suspended);
+            // Put indicators, displays and split peripherals to sleep with the host. Not when
+            // typing to a BLE host, which is unaffected by the USB host sleeping.
+            if !self.sleeping && active_transport() == Some(ConnectionType::Usb) {
+                self.sleeping = true;
+                publish_event(SleepStateEvent::new(true));
+                publish_event(PowerStateEvent::Sleep(true));
+            }
             info!(
                 "Device suspended, the Vbus current limit is 500µA (or 2.5mA for high-power devices with remote wakeup enabled)."
             );
@@ -481,7 +746,10 @@ impl Handler for UsbDeviceHandler {
             // callbacks), don't overwrite — `configured()`/`enabled()` will resync.
             if current_usb_state() == UsbState::Suspended {
                 set_usb_state(self.pre_suspend);
+                #[cfg(feature = "_ble")]
+                report_battery_level(crate::input_device::battery::current_battery_status());
             }
+            self.wake();
             info!(
                 "Device resumed, the Vbus current limit is 500µA (or 2.5mA for high-power devices with remote wakeup enabled)."
             );
@@ -490,5 +758,36 @@ impl Handler for UsbDeviceHandler {
 
     fn remote_wakeup_enabled(&mut self, enabled: bool) {
         info!("Remote wakeup enabled state: {}", enabled);
+        USB_REMOTE_WAKEUP_ENABLED.store(enabled, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::MouseReport;
+
+    use super::*;
+    use crate::hid::merge_mouse_report;
+
+    fn mouse(buttons: u8, x: i8, y: i8) -> MouseReport {
+        MouseReport {
+            buttons,
+            x,
+            y,
+            wheel: 0,
+            pan: 0,
+        }
+    }
+
+    #[test]
+    fn test_merge_mouse_report() {
+        let mut report = mouse(0, 10, -5);
+        assert!(merge_mouse_report(&mut report, &mouse(0, 3, -2)));
+        assert_eq!((report.x, report.y), (13, -7));
+
+        // Button changes and overflowing motion must be sent in their own reports
+        assert!(!merge_mouse_report(&mut report, &mouse(1, 1, 1)));
+        assert!(!merge_mouse_report(&mut report, &mouse(0, 120, 0)));
+        assert_eq!((report.buttons, report.x, report.y), (0, 13, -7));
     }
 }
@@ -0,0 +1,179 @@
+//! Host OS detection from USB enumeration.
+//!
+//! Operating systems read string descriptors differently while enumerating a
+//! device: Linux always asks for 255 bytes, Windows also reads a 4 byte header,
+//! and macOS/iOS read the 2 byte header first. [`OsDetectDriver`] wraps the USB
+//! driver to look at the setup packets and count the lengths of those requests.
+//! This is a heuristic, so the result can be overridden, see [`crate::host_os`].
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+use embassy_usb::driver::{ControlPipe, Driver, EndpointAddress, EndpointAllocError, EndpointError, EndpointType};
+use rmk_types::connection::HostOs;
+
+use crate::RawMutex;
+
+/// Lengths of the string descriptor requests since the last bus reset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct StringRequests {
+    total: u8,
+    len_2: u8,
+    len_4: u8,
+    len_255: u8,
+}
+
+impl StringRequests {
+    fn record(&mut self, length: u16) {
+        self.total = self.total.saturating_add(1);
+        match length {
+            2 => self.len_2 = self.len_2.saturating_add(1),
+            4 => self.len_4 = self.len_4.saturating_add(1),
+            0xFF => self.len_255 = self.len_255.saturating_add(1),
+            _ => {}
+        }
+    }
+
+    fn host_os(&self) -> HostOs {
+        if self.total == 0 {
+            HostOs::Unknown
+        } else if self.len_255 == self.total {
+            HostOs::Linux
+        } else if self.len_255 >= 2 && self.len_4 >= 1 {
+            HostOs::Windows
+        } else if self.len_2 >= 1 && self.len_255 == 0 {
+            // iOS reads fewer strings than macOS
+            if self.total <= 3 { HostOs::Ios } else { HostOs::MacOs }
+        } else {
+            HostOs::Unknown
+        }
+    }
+}
+
+static STRING_REQUESTS: Mutex<RawMutex, Cell<StringRequests>> = Mutex::new(Cell::new(StringRequests {
+    total: 0,
+    len_2: 0,
+    len_4: 0,
+    len_255: 0,
+}));
+/// Signaled by the device handler once the host configured the device
+pub(crate) static USB_CONFIGURED: Signal<RawMutex, ()> = Signal::new();
+
+/// Forget the requests of the previous enumeration, called on bus reset.
+pub(crate) fn reset_fingerprint() {
+    STRING_REQUESTS.lock(|r| r.set(StringRequests::default()));
+}
+
+/// Update the detected host OS after every enumeration.
+pub(crate) async fn run_os_detection() -> ! {
+    loop {
+        USB_CONFIGURED.wait().await;
+        // Some hosts keep reading strings for a while after configuring the device
+        Timer::after_millis(500).await;
+        let os = STRING_REQUESTS.lock(|r| r.get()).host_os();
+        crate::host_os::set_detected_host_os(os);
+    }
+}
+
+/// USB driver wrapper that records the string descriptor requests of the host.
+pub(crate) struct OsDetectDriver<D>(pub(crate) D);
+
+impl<'d, D: Driver<'d>> Driver<'d> for OsDetectDriver<D> {
+    type EndpointOut = D::EndpointOut;
+    type EndpointIn = D::EndpointIn;
+    type ControlPipe = OsDetectControlPipe<D::ControlPipe>;
+    type Bus = D::Bus;
+
+    fn alloc_endpoint_out(
+        &mut self,
+        ep_type: EndpointType,
+        ep_addr: Option<EndpointAddress>,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> Result<Self::EndpointOut, EndpointAllocError> {
+        self.0
+            .alloc_endpoint_out(ep_type, ep_addr, max_packet_size, interval_ms)
+    }
+
+    fn alloc_endpoint_in(
+        &mut self,
+        ep_type: EndpointType,
+        ep_addr: Option<EndpointAddress>,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> Result<Self::EndpointIn, EndpointAllocError> {
+        self.0.alloc_endpoint_in(ep_type, ep_addr, max_packet_size, interval_ms)
+    }
+
+    fn start(self, control_max_packet_size: u16) -> (Self::Bus, Self::ControlPipe) {
+        let (bus, control) = self.0.start(control_max_packet_size);
+        (bus, OsDetectControlPipe(control))
+    }
+}
+
+pub(crate) struct OsDetectControlPipe<C>(C);
+
+impl<C: ControlPipe> ControlPipe for OsDetectControlPipe<C> {
+    fn max_packet_size(&self) -> usize {
+        self.0.max_packet_size()
+    }
+
+    async fn setup(&mut self) -> [u8; 8] {
+        let setup = self.0.setup().await;
+        // Standard device-to-host GET_DESCRIPTOR of a string, except the language id list
+        if setup[0] == 0x80 && setup[1] == 0x06 && setup[3] == 0x03 && setup[2] != 0 {
+            let length = u16::from_le_bytes([setup[6], setup[7]]);
+            STRING_REQUESTS.lock(|r| {
+                let mut requests = r.get();
+                requests.record(length);
+                r.set(requests);
+            });
+        }
+        setup
+    }
+
+    async fn data_out(&mut self, buf: &mut [u8], first: bool, last: bool) -> Result<usize, EndpointError> {
+        self.0.data_out(buf, first, last).await
+    }
+
+    async fn data_in(&mut self, data: &[u8], first: bool, last: bool) -> Result<(), EndpointError> {
+        self.0.data_in(data, first, last).await
+    }
+
+    async fn accept(&mut self) {
+        self.0.accept().await
+    }
+
+    async fn reject(&mut self) {
+        self.0.reject().await
+    }
+
+    async fn accept_set_address(&mut self, addr: u8) {
+        self.0.accept_set_address(addr).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests(lengths: &[u16]) -> StringRequests {
+        let mut requests = StringRequests::default();
+        for &length in lengths {
+            requests.record(length);
+        }
+        requests
+    }
+
+    #[test]
+    fn test_host_os_fingerprint() {
+        assert_eq!(requests(&[]).host_os(), HostOs::Unknown);
+        assert_eq!(requests(&[0xFF, 0xFF, 0xFF]).host_os(), HostOs::Linux);
+        assert_eq!(requests(&[0xFF, 4, 0xFF, 0xFF]).host_os(), HostOs::Windows);
+        assert_eq!(requests(&[2, 0x1A, 2, 0x22, 2, 0x2C]).host_os(), HostOs::MacOs);
+        assert_eq!(requests(&[2, 0x1A, 2]).host_os(), HostOs::Ios);
+        assert_eq!(requests(&[0x40]).host_os(), HostOs::Unknown);
+    }
+}
@@ -0,0 +1,63 @@
+//! Raw HID interface for host companion apps.
+//!
+//! A vendor defined HID interface (usage page `0xFF00`, usage `0x01`) besides the
+//! Vial one, exchanging 32 byte reports. The content of the reports is up to the
+//! user, RMK only moves them between the host and [`read`]/[`write`], so a host app
+//! showing the current layer or pushing notifications doesn't get in the way of Vial.
+
+use embassy_futures::select::{Either, select};
+use embassy_sync::channel::Channel;
+use embassy_usb::class::hid::{HidReaderWriter, ReadError};
+use embassy_usb::driver::{Driver, EndpointError};
+
+use crate::RawMutex;
+
+/// Size of the raw HID reports, in both directions
+pub const RAW_HID_REPORT_SIZE: usize = 32;
+
+static RAW_HID_RX: Channel<RawMutex, [u8; RAW_HID_REPORT_SIZE], 2> = Channel::new();
+static RAW_HID_TX: Channel<RawMutex, [u8; RAW_HID_REPORT_SIZE], 2> = Channel::new();
+
+/// Wait for the next report sent by the host.
+pub async fn read() -> [u8; RAW_HID_REPORT_SIZE] {
+    RAW_HID_RX.receive().await
+}
+
+/// Send a report to the host, waits if previous reports are still queued.
+///
+/// Reports queued while no host app listens are sent once the host configures the interface.
+pub async fn write(report: [u8; RAW_HID_REPORT_SIZE]) {
+    RAW_HID_TX.send(report).await
+}
+
+/// Send a report to the host without waiting, returns false if the queue is full.
+pub fn try_write(report: [u8; RAW_HID_REPORT_SIZE]) -> bool {
+    RAW_HID_TX.try_send(report).is_ok()
+}
+
+/// Move reports between the raw HID endpoints and the user facing channels.
+pub(crate) async fn run_raw_hid<'d, D: Driver<'d>>(
+    rw: &mut HidReaderWriter<'d, D, RAW_HID_REPORT_SIZE, RAW_HID_REPORT_SIZE>,
+) -> ! {
+    let mut buf = [0u8; RAW_HID_REPORT_SIZE];
+    loop {
+        rw.ready().await;
+        loop {
+            match select(rw.read(&mut buf), RAW_HID_TX.receive()).await {
+                Either::First(Ok(_)) => {
+                    // Drop the report if the user code doesn't keep up, instead of stalling the endpoint
+                    if RAW_HID_RX.try_send(buf).is_err() {
+                        warn!("Raw HID report dropped, nobody reads them");
+                    }
+                }
+                Either::First(Err(ReadError::Disabled)) => break,
+                Either::First(Err(e)) => error!("Raw HID read error: {:?}", e),
+                Either::Second(report) => match rw.write(&report).await {
+                    Ok(()) => {}
+                    Err(EndpointError::Disabled) => break,
+                    Err(e) => error!("Raw HID write error: {:?}", e),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Crash records which survive the reset.
+//!
+//! With the `crash_dump` feature RMK provides the panic handler of Cortex-M chips, so remove
+//! `panic-probe` or `panic-halt` from the firmware. On a panic it keeps the panic message, the
+//! [`CoreTask`] which was busy and the uptime in RAM which isn't cleared at boot, then resets the
+//! keyboard. The [`WatchdogRunner`](super::WatchdogRunner) does the same when a core task stalls,
+//! before the hardware watchdog resets the keyboard.
+//!
+//! The flash can't be written from the panic handler, as the flash drivers are async and need
+//! the executor. At the next boot, the storage saves the record as its own item in the flash
+//! instead, so it's kept over power cycles until the next crash replaces it.
+//!
+//! Read the last crash with [`last_crash`], the `crash` command of the USB console, or the
+//! `CrashRecord` Via command, see `rmk_types::protocol::vial::ViaCommand`.
+
+use core::cell::RefCell;
+use core::fmt::Write;
+
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+use super::CoreTask;
+use crate::RawMutex;
+
+/// Longest kept panic message, longer ones are cut
+pub const CRASH_MESSAGE_LEN: usize = 64;
+
+/// Marks a valid record in the RAM slot, "RMKC"
+const CRASH_MAGIC: u32 = 0x524D_4B43;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CrashReason {
+    Panic = 0,
+    /// A core task was busy for longer than [`STALL_TIMEOUT`](super::STALL_TIMEOUT)
+    Stall = 1,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CrashRecord {
+    pub reason: CrashReason,
+    /// The core task which was busy the longest, which is the stalled one for [`CrashReason::Stall`]
+    pub task: Option<CoreTask>,
+    /// Time since boot in ms
+    pub uptime_ms: u32,
+    /// Panic message with its location, at most [`CRASH_MESSAGE_LEN`] bytes
+    pub message: heapless::String<CRASH_MESSAGE_LEN>,
+}
+
+// `#[derive(MaxSize)]` doesn't support `heapless::String`
+impl MaxSize for CrashRecord {
+    // Reason, optional task, varint uptime, varint length and the message
+    const POSTCARD_MAX_SIZE: usize = 1 + 2 + 5 + 1 + CRASH_MESSAGE_LEN;
+}
+
+// Raw record in the RAM slot, which holds whatever was in the RAM at a cold boot:
+// magic `u32`, reason `u8`, `CoreTask` or 0xFF `u8`, uptime `u32`, message length `u8`, message
+const SLOT_MESSAGE: usize = 11;
+const SLOT_LEN: usize = SLOT_MESSAGE + CRASH_MESSAGE_LEN;
+
+// cortex-m-rt doesn't zero `.uninit` at boot, so the initial value is only used on the host.
+// Plain bytes, as any bit pattern left in the RAM is a valid `u8`.
+#[cfg_attr(
+    all(target_arch = "arm", target_os = "none"),
+    unsafe(link_section = ".uninit.rmk_crash")
+)]
+static mut CRASH_SLOT: [u8; SLOT_LEN] = [0; SLOT_LEN];
+
+/// Write the RAM slot byte by byte, the compiler can't assume what the RAM holds.
+fn write_slot(bytes: &[u8; SLOT_LEN]) {
+    let slot = (&raw mut CRASH_SLOT).cast::<u8>();
+    for (i, &byte) in bytes.iter().enumerate() {
+        // SAFETY: `i` is in the slot, which is only accessed with volatile byte reads and writes
+        unsafe { slot.add(i).write_volatile(byte) };
+    }
+}
+
+/// Last crash, loaded from the RAM slot or the storage
+static LAST_CRASH: Mutex<RawMutex, RefCell<Option<CrashRecord>>> = Mutex::new(RefCell::new(None));
+
+/// Collects the message, cutting it at a char boundary when it's too long
+struct MessageWriter(heapless::String<CRASH_MESSAGE_LEN>);
+
+impl Write for MessageWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keep a crash record in the RAM slot for the next boot.
+///
+/// Doesn't take locks, so it's safe to call from the panic handler.
+pub(crate) fn record_crash(reason: CrashReason, task: Option<CoreTask>, message: core::fmt::Arguments) {
+    let mut writer = MessageWriter(heapless::String::new());
+    let _ = writer.write_fmt(message);
+    let len = writer.0.len();
+    let mut slot = [0; SLOT_LEN];
+    slot[0..4].copy_from_slice(&CRASH_MAGIC.to_le_bytes());
+    slot[4] = reason as u8;
+    slot[5] = task.map_or(0xFF, |t| t as u8);
+    slot[6..10].copy_from_slice(&(Instant::now().as_millis() as u32).to_le_bytes());
+    slot[10] = len as u8;
+    slot[SLOT_MESSAGE..SLOT_MESSAGE + len].copy_from_slice(writer.0.as_bytes());
+    write_slot(&slot);
+}
+
+/// Take the record of the crash before this boot out of the RAM slot
+pub(crate) fn take_crash_slot() -> Option<CrashRecord> {
+    let ptr = (&raw const CRASH_SLOT).cast::<u8>();
+    // SAFETY: see `write_slot`
+    let slot: [u8; SLOT_LEN] = core::array::from_fn(|i| unsafe { ptr.add(i).read_volatile() });
+    if slot[0..4] != CRASH_MAGIC.to_le_bytes() {
+        return None;
+    }
+    write_slot(&[0; SLOT_LEN]);
+
+    let reason = match slot[4] {
+        0 => CrashReason::Panic,
+        1 => CrashReason::Stall,
+        _ => return None,
+    };
+    let task = match slot[5] {
+        0xFF => None,
+        t => Some(*CoreTask::ALL.get(t as usize)?),
+    };
+    let message = slot[SLOT_MESSAGE..].get(..slot[10] as usize)?;
+    Some(CrashRecord {
+        reason,
+        task,
+        uptime_ms: u32::from_le_bytes([slot[6], slot[7], slot[8], slot[9]]),
+        message: heapless::String::try_from(core::str::from_utf8(message).ok()?).ok()?,
+    })
+}
+
+pub(crate) fn set_last_crash(record: CrashRecord) {
+    LAST_CRASH.lock(|c| *c.borrow_mut() = Some(record));
+}
+
+/// The last crash of the keyboard, if it ever crashed since the storage was cleared.
+///
+/// Without the `storage` feature only the crash right before this boot is known.
+pub fn last_crash() -> Option<CrashRecord> {
+    LAST_CRASH.lock(|c| {
+        let mut last = c.borrow_mut();
+        if let Some(record) = take_crash_slot() {
+            *last = Some(record);
+        }
+        last.clone()
+    })
+}
+
+#[cfg(all(target_arch = "arm", target_os = "none"))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let task = super::busy_task().map(|(task, _)| task);
+    record_crash(CrashReason::Panic, task, format_args!("{}", info));
+    // Not `boot::reboot_keyboard`, which logs, and the logger may be what panicked
+    cortex_m::peripheral::SCB::sys_reset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crash_slot_round_trip() {
+        record_crash(
+            CrashReason::Panic,
+            Some(CoreTask::HostService),
+            format_args!("é{:x<80}", ""),
+        );
+        let record = take_crash_slot().unwrap();
+        assert_eq!(record.reason, CrashReason::Panic);
+        assert_eq!(record.task, Some(CoreTask::HostService));
+        // Cut at a char boundary
+        assert_eq!(record.message.len(), CRASH_MESSAGE_LEN);
+        assert!(record.message.starts_with("éxx"));
+        // The slot is emptied
+        assert_eq!(take_crash_slot(), None);
+
+        record_crash(CrashReason::Stall, None, format_args!("stalled"));
+        assert_eq!(last_crash().unwrap().message, "stalled");
+        assert_eq!(last_crash().unwrap().reason, CrashReason::Stall);
+    }
+}
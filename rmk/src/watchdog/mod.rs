@@ -1,7 +1,12 @@
-use embassy_time::{Duration, Timer};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_time::{Duration, Instant, Timer};
+use serde::{Deserialize, Serialize};
 
 use crate::core_traits::Runnable;
 
+#[cfg(feature = "crash_dump")]
+pub mod crash;
 #[cfg(feature = "_esp_ble")]
 mod esp32;
 #[cfg(all(feature = "_nrf_ble", not(any(feature = "nrf54l15_ble", feature = "nrf54lm20_ble"))))]
@@ -22,12 +27,87 @@ pub trait WatchdogFeed: Send {
     fn feed(&mut self);
 }
 
+/// Time a core task may spend on one step before the watchdog stops being fed
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Core tasks whose steps are checked by [`WatchdogRunner`].
+///
+/// Only steps which finish on their own are checked. The keyboard waiting for room in the report
+/// queue, or USB waiting for the host to poll the endpoint, may last as long as the host wants, see
+/// [`wait_on_host`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CoreTask {
+    /// Writing a report to BLE, which the link either takes or drops by the supervision timeout
+    BleWriter = 0,
+    /// Handling a request of the host tool
+    HostService = 1,
+    /// Processing a key event, including the storage requests of its actions
+    Keyboard = 2,
+}
+
+impl CoreTask {
+    const ALL: [Self; 3] = [Self::BleWriter, Self::HostService, Self::Keyboard];
+}
+
+/// Uptime in ms when the current step of every [`CoreTask`] started, 0 when it's idle.
+///
+/// Atomics rather than a mutex, so the panic handler can read them from any context.
+static BUSY_SINCE: [AtomicU32; CoreTask::ALL.len()] = [const { AtomicU32::new(0) }; CoreTask::ALL.len()];
+
+/// Marks `task` busy until the returned guard is dropped.
+///
+/// Wrap steps which must finish, like writing a report to BLE, not waits for input or for the host.
+pub(crate) fn busy(task: CoreTask) -> BusyGuard {
+    // 0 means idle, so a step started in the first ms counts from 1
+    let now = (Instant::now().as_millis() as u32).max(1);
+    BUSY_SINCE[task as usize].store(now, Ordering::Relaxed);
+    BusyGuard(task)
+}
+
+/// Run `wait` without counting it in the current step of `task`, for waits on the host in a step.
+pub(crate) async fn wait_on_host<F: Future>(task: CoreTask, wait: F) -> F::Output {
+    let busy = BUSY_SINCE[task as usize].swap(0, Ordering::Relaxed) != 0;
+    let output = wait.await;
+    if busy {
+        // The rest of the step counts from now on
+        BUSY_SINCE[task as usize].store((Instant::now().as_millis() as u32).max(1), Ordering::Relaxed);
+    }
+    output
+}
+
+pub(crate) struct BusyGuard(CoreTask);
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        BUSY_SINCE[self.0 as usize].store(0, Ordering::Relaxed);
+    }
+}
+
+/// The core task which has been busy the longest, and for how long
+pub fn busy_task() -> Option<(CoreTask, Duration)> {
+    let now = Instant::now().as_millis() as u32;
+    CoreTask::ALL
+        .into_iter()
+        .filter_map(|task| match BUSY_SINCE[task as usize].load(Ordering::Relaxed) {
+            0 => None,
+            since => Some((task, Duration::from_millis(now.wrapping_sub(since) as u64))),
+        })
+        .max_by_key(|(_, busy)| *busy)
+}
+
 /// A [`Runnable`] that periodically feeds a hardware watchdog.
 ///
 /// Pass this to `run_all!` alongside your keyboard and matrix. Because
 /// all runnables are joined cooperatively, a tight-loop stall in any
 /// sibling task will block this runner too, letting the hardware
 /// watchdog fire a reset.
+///
+/// Core tasks which wait forever in a step, like a BLE report write which
+/// never completes, don't block the runner. It stops
+/// feeding the watchdog once a [`CoreTask`] is busy for longer than
+/// [`STALL_TIMEOUT`].
 pub struct WatchdogRunner<W: WatchdogFeed> {
     watchdog: W,
     interval: Duration,
@@ -42,7 +122,18 @@ impl<W: WatchdogFeed> WatchdogRunner<W> {
 impl<W: WatchdogFeed> Runnable for WatchdogRunner<W> {
     async fn run(&mut self) -> ! {
         loop {
-            self.watchdog.feed();
+            match busy_task() {
+                Some((task, busy)) if busy > STALL_TIMEOUT => {
+                    error!(
+                        "{:?} stalled for {}ms, letting the watchdog reset",
+                        task,
+                        busy.as_millis()
+                    );
+                    #[cfg(feature = "crash_dump")]
+                    crash::record_crash(crash::CrashReason::Stall, Some(task), format_args!("stalled"));
+                }
+                _ => self.watchdog.feed(),
+            }
             Timer::after(self.interval).await;
         }
     }
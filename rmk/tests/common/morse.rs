@@ -1,5 +1,5 @@
 use heapless::{LinearMap, Vec};
-use rmk::config::{BehaviorConfig, Hand, MorsesConfig, PositionalConfig};
+use rmk::config::{BehaviorConfig, Hand, KeyTapHold, MorsesConfig, PositionalConfig};
 use rmk::keyboard::Keyboard;
 use rmk::types::action::Action;
 use rmk::types::keycode::{HidKeyCode, KeyCode};
@@ -10,6 +10,14 @@ use rmk::{k, lt, mt, td};
 use crate::common::wrap_keymap;
 
 pub fn create_simple_morse_keyboard(behavior_config: BehaviorConfig) -> Keyboard<'static> {
+    create_per_key_morse_keyboard(behavior_config, [[KeyTapHold::default(); 5]; 1])
+}
+
+/// The keyboard of [`create_simple_morse_keyboard`], with per-key tap-hold configurations
+pub fn create_per_key_morse_keyboard(
+    behavior_config: BehaviorConfig,
+    tap_hold: [[KeyTapHold; 5]; 1],
+) -> Keyboard<'static> {
     let keymap = [
         [[
             k!(A),
@@ -65,7 +73,8 @@ pub fn create_simple_morse_keyboard(behavior_config: BehaviorConfig) -> Keyboard
     };
 
     let behavior_config: &'static mut BehaviorConfig = Box::leak(Box::new(behavior_config));
-    let per_key_config: &'static PositionalConfig<1, 5> = Box::leak(Box::new(PositionalConfig::default()));
+    let per_key_config: &'static PositionalConfig<1, 5> =
+        Box::leak(Box::new(PositionalConfig::default().with_tap_hold(tap_hold)));
     Keyboard::new(wrap_keymap(keymap, per_key_config, behavior_config))
 }
 
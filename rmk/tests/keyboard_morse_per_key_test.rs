@@ -0,0 +1,89 @@
+pub mod common;
+
+use rmk::config::{BehaviorConfig, KeyTapHold};
+use rmk::types::morse::MorseProfile;
+
+use crate::common::KC_LSHIFT;
+use crate::common::morse::create_per_key_morse_keyboard;
+
+/// Tap-hold configuration of `mt!(B, LShift)` at (0, 1)
+fn mt_b_config(tap_hold: KeyTapHold) -> [[KeyTapHold; 5]; 1] {
+    let mut config = [[KeyTapHold::default(); 5]; 1];
+    config[0][1] = tap_hold;
+    config
+}
+
+#[test]
+fn test_per_key_hold_timeout() {
+    let tap_hold = KeyTapHold {
+        profile: MorseProfile::new(None, None, Some(100), None),
+        ..Default::default()
+    };
+    key_sequence_test! {
+        keyboard: create_per_key_morse_keyboard(BehaviorConfig::default(), mt_b_config(tap_hold)),
+        sequence: [
+            [0, 1, true, 10],  // Press mt!(B, LShift)
+            [0, 1, false, 150], // Release B after the per-key hold timeout
+        ],
+        expected_reports: [
+            [KC_LSHIFT, [0, 0, 0, 0, 0, 0]], // Hold LShift
+            [0, [0, 0, 0, 0, 0, 0]], // All released
+        ]
+    };
+}
+
+#[test]
+fn test_retro_tap() {
+    let tap_hold = KeyTapHold {
+        retro_tap: true,
+        ..Default::default()
+    };
+    key_sequence_test! {
+        keyboard: create_per_key_morse_keyboard(BehaviorConfig::default(), mt_b_config(tap_hold)),
+        sequence: [
+            [0, 1, true, 10],  // Press mt!(B, LShift)
+            [0, 1, false, 300], // Release B after hold timeout
+            [0, 1, true, 300],  // Press mt!(B, LShift)
+            [0, 0, true, 300],  // Press A while holding
+            [0, 0, false, 10], // Release A
+            [0, 1, false, 10], // Release B, no retro tap after another key
+        ],
+        expected_reports: [
+            [KC_LSHIFT, [0, 0, 0, 0, 0, 0]], // Hold LShift
+            [0, [0, 0, 0, 0, 0, 0]], // Release LShift
+            [0, [kc_to_u8!(B), 0, 0, 0, 0, 0]], // Retro tap B
+            [0, [0, 0, 0, 0, 0, 0]], // Release B
+            [KC_LSHIFT, [0, 0, 0, 0, 0, 0]], // Hold LShift
+            [KC_LSHIFT, [kc_to_u8!(A), 0, 0, 0, 0, 0]], // Press A
+            [KC_LSHIFT, [0, 0, 0, 0, 0, 0]], // Release A
+            [0, [0, 0, 0, 0, 0, 0]], // Release LShift
+        ]
+    };
+}
+
+#[test]
+fn test_quick_tap() {
+    let tap_hold = KeyTapHold {
+        quick_tap_ms: 200,
+        ..Default::default()
+    };
+    key_sequence_test! {
+        keyboard: create_per_key_morse_keyboard(BehaviorConfig::default(), mt_b_config(tap_hold)),
+        sequence: [
+            [0, 1, true, 10],  // Press mt!(B, LShift)
+            [0, 1, false, 50], // Tap B
+            [0, 1, true, 50],  // Press again within quick tap
+            [0, 1, false, 300], // Release after hold timeout, B was held
+            [0, 1, true, 300],  // Press after quick tap
+            [0, 1, false, 300], // Release after hold timeout
+        ],
+        expected_reports: [
+            [0, [kc_to_u8!(B), 0, 0, 0, 0, 0]], // Tap B
+            [0, [0, 0, 0, 0, 0, 0]],
+            [0, [kc_to_u8!(B), 0, 0, 0, 0, 0]], // Hold B
+            [0, [0, 0, 0, 0, 0, 0]],
+            [KC_LSHIFT, [0, 0, 0, 0, 0, 0]], // Hold LShift
+            [0, [0, 0, 0, 0, 0, 0]],
+        ]
+    };
+}
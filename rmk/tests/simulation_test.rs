@@ -0,0 +1,51 @@
+pub mod common;
+
+use rmk::config::BehaviorConfig;
+use rmk::hid::KeyboardReport;
+use rmk::keyboard::Keyboard;
+use rmk::simulation::{Timeline, keymap, simulate};
+
+use crate::common::{KC_LSHIFT, get_keymap};
+
+fn report(modifier: u8, keycodes: [u8; 6]) -> KeyboardReport {
+    KeyboardReport {
+        modifier,
+        keycodes,
+        leds: 0,
+        reserved: 0,
+    }
+}
+
+#[test]
+fn test_tap_hold_is_resolved_at_the_hold_timeout() {
+    // (2, 1) is `th!(A, LShift)`, with the default hold timeout of 250 ms
+    let mut keyboard = Keyboard::new(keymap(get_keymap(), BehaviorConfig::default()));
+    let timeline = Timeline::new().press(2, 1).wait(400).release(2, 1).wait(100);
+    let result = simulate(&mut keyboard, &timeline);
+
+    let reports = result.timed_keyboard_reports();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].1, report(KC_LSHIFT, [0; 6]));
+    assert!(
+        (250..260).contains(&reports[0].0),
+        "hold resolved at {} ms",
+        reports[0].0
+    );
+    assert_eq!(reports[1].1, report(0, [0; 6]));
+    assert!((400..402).contains(&reports[1].0), "released at {} ms", reports[1].0);
+    assert!(keyboard.held_buffer.is_empty());
+}
+
+#[test]
+fn test_tap_is_sent_at_the_release() {
+    let mut keyboard = Keyboard::new(keymap(get_keymap(), BehaviorConfig::default()));
+    let timeline = Timeline::new().wait(10).tap(2, 1, 100).wait(300);
+    let result = simulate(&mut keyboard, &timeline);
+
+    let reports = result.timed_keyboard_reports();
+    assert_eq!(
+        result.keyboard_reports(),
+        vec![report(0, [kc_to_u8!(A), 0, 0, 0, 0, 0]), report(0, [0; 6])]
+    );
+    assert!((110..112).contains(&reports[0].0), "tapped at {} ms", reports[0].0);
+}